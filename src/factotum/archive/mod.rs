@@ -0,0 +1,293 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::task_list::Task;
+use factotum::factfile::Task as FactfileTask;
+use factotum::webhook::jobcontext::JobContext;
+use factotum::gcp;
+use factotum::report;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use hyper::mime::{Mime, TopLevel, SubLevel};
+
+pub const RUNS_DIR: &'static str = ".factotum/runs";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ArchiveLocation {
+    pub scheme: String,
+    pub bucket: String,
+    pub prefix: String,
+}
+
+impl ArchiveLocation {
+    pub fn object_prefix(&self, run_reference: &str) -> String {
+        if self.prefix.is_empty() {
+            run_reference.to_string()
+        } else {
+            format!("{}/{}", self.prefix, run_reference)
+        }
+    }
+
+    pub fn for_run(&self, run_reference: &str) -> String {
+        format!("{}://{}/{}/",
+                self.scheme,
+                self.bucket,
+                self.object_prefix(run_reference))
+    }
+}
+
+pub trait ArchiveBackend {
+    fn upload(&self,
+              source_dir: &Path,
+              location: &ArchiveLocation,
+              run_reference: &str)
+              -> Result<(), String>;
+}
+
+pub fn parse_archive_url(url: &str) -> Result<ArchiveLocation, String> {
+    let (scheme, path) = if url.starts_with("s3://") {
+        ("s3", &url["s3://".len()..])
+    } else if url.starts_with("gs://") {
+        ("gs", &url["gs://".len()..])
+    } else {
+        return Err("archive location must begin with 's3://' or 'gs://'".into());
+    };
+
+    let mut parts = path.trim_end_matches('/').splitn(2, '/');
+    let bucket = parts.next().unwrap_or("");
+
+    if bucket.is_empty() {
+        return Err("archive location is missing a bucket name".into());
+    }
+
+    Ok(ArchiveLocation {
+        scheme: scheme.to_string(),
+        bucket: bucket.to_string(),
+        prefix: parts.next().unwrap_or("").trim_matches('/').to_string(),
+    })
+}
+
+pub fn get_backend(location: &ArchiveLocation) -> Box<dyn ArchiveBackend> {
+    match location.scheme.as_ref() {
+        "gs" => Box::new(GcsBackend),
+        _ => Box::new(S3Backend),
+    }
+}
+
+pub fn log_file_name(task_name: &str, stream: &str) -> String {
+    let safe_name = task_name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect::<String>();
+    format!("{}.{}.log", safe_name, stream)
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), String> {
+    let mut f = try!(File::create(path)
+        .map_err(|e| format!("couldn't create file '{}' ({})", path.display(), e)));
+    f.write_all(contents.as_bytes())
+        .map_err(|e| format!("couldn't write to file '{}' ({})", path.display(), e))
+}
+
+// output that was spilled to disk is copied in full, rather than just its tail
+fn write_log(path: &Path,
+             contents: &Option<String>,
+             spill_file: Option<&PathBuf>)
+             -> Result<(), String> {
+    match (spill_file, contents) {
+        (Some(spill_file), _) => {
+            fs::copy(spill_file, path).map(|_| ()).map_err(|e| {
+                format!("couldn't copy '{}' to '{}' ({})",
+                        spill_file.display(),
+                        path.display(),
+                        e)
+            })
+        }
+        (None, &Some(ref contents)) => write_file(path, contents),
+        (None, &None) => Ok(()),
+    }
+}
+
+pub fn write_run_files(dir: &Path,
+                       context: &JobContext,
+                       tasks: &Vec<&Task<&FactfileTask>>)
+                       -> Result<(), String> {
+    let logs_dir = dir.join("logs");
+    try!(fs::create_dir_all(&logs_dir)
+        .map_err(|e| format!("couldn't create directory '{}' ({})", logs_dir.display(), e)));
+
+    for task in tasks.iter() {
+        if let Some(ref result) = task.run_result {
+            let (stdout_file, stderr_file) = match result.spilled_output {
+                Some(ref spilled) => (spilled.stdout_file.as_ref(), spilled.stderr_file.as_ref()),
+                None => (None, None),
+            };
+            try!(write_log(&logs_dir.join(log_file_name(&task.name, "stdout")),
+                           &result.stdout,
+                           stdout_file));
+            try!(write_log(&logs_dir.join(log_file_name(&task.name, "stderr")),
+                           &result.stderr,
+                           stderr_file));
+        }
+    }
+
+    try!(write_file(&dir.join("result.json"),
+                    &report::run_result_json(context, tasks).pretty().to_string()));
+    write_file(&dir.join("report.html"), &report::html_report(context, tasks))
+}
+
+pub struct S3Backend;
+
+impl ArchiveBackend for S3Backend {
+    fn upload(&self,
+              source_dir: &Path,
+              location: &ArchiveLocation,
+              run_reference: &str)
+              -> Result<(), String> {
+        let destination = location.for_run(run_reference);
+        info!("uploading '{}' to '{}'", source_dir.display(), destination);
+        let output = try!(Command::new("aws")
+            .arg("s3")
+            .arg("cp")
+            .arg("--recursive")
+            .arg("--only-show-errors")
+            .arg(source_dir)
+            .arg(&destination)
+            .output()
+            .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!("aws s3 cp exited with {}: {}",
+                        output.status.code().unwrap_or(-1),
+                        String::from_utf8_lossy(&output.stderr).trim()))
+        }
+    }
+}
+
+pub struct GcsBackend;
+
+impl GcsBackend {
+    fn upload_object(token: &str, bucket: &str, object: &str, file: &Path) -> Result<(), String> {
+        use hyper::Client;
+        use hyper::Url;
+        use hyper::net::HttpsConnector;
+        use hyper_native_tls::NativeTlsClient;
+        use hyper::header::{Headers, Authorization, Bearer, ContentType};
+        use hyper::status;
+
+        let mut contents = vec![];
+        try!(File::open(file)
+            .and_then(|mut f| f.read_to_end(&mut contents))
+            .map_err(|e| format!("couldn't read '{}' ({})", file.display(), e)));
+
+        let mut url = try!(Url::parse(&format!("https://storage.googleapis.com/upload/storage/v1/b/{}/o",
+                                               bucket))
+            .map_err(|e| format!("{}", e)));
+        url.query_pairs_mut()
+            .append_pair("uploadType", "media")
+            .append_pair("name", object);
+
+        let ssl = try!(NativeTlsClient::new().map_err(|e| format!("{}", e)));
+        let client = Client::with_connector(HttpsConnector::new(ssl));
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: token.to_string() }));
+        headers.set(ContentType(content_type(file)));
+
+        match client.post(url).headers(headers).body(&contents[..]).send() {
+            Ok(res) => {
+                if res.status == status::StatusCode::Ok {
+                    Ok(())
+                } else {
+                    Err(format!("upload of '{}' failed: {}", object, res.status))
+                }
+            }
+            Err(e) => Err(format!("upload of '{}' failed: {}", object, e)),
+        }
+    }
+}
+
+impl ArchiveBackend for GcsBackend {
+    fn upload(&self,
+              source_dir: &Path,
+              location: &ArchiveLocation,
+              run_reference: &str)
+              -> Result<(), String> {
+        info!("uploading '{}' to '{}'",
+              source_dir.display(),
+              location.for_run(run_reference));
+        let token = try!(gcp::access_token());
+        let object_prefix = location.object_prefix(run_reference);
+
+        for relative_path in try!(list_files(source_dir)) {
+            let object = format!("{}/{}", object_prefix, relative_path);
+            try!(GcsBackend::upload_object(&token,
+                                           &location.bucket,
+                                           &object,
+                                           &source_dir.join(&relative_path)));
+        }
+
+        Ok(())
+    }
+}
+
+fn content_type(file: &Path) -> Mime {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("json") => Mime(TopLevel::Application, SubLevel::Json, vec![]),
+        Some("html") => Mime(TopLevel::Text, SubLevel::Html, vec![]),
+        _ => Mime(TopLevel::Text, SubLevel::Plain, vec![]),
+    }
+}
+
+pub fn list_files(dir: &Path) -> Result<Vec<String>, String> {
+    let mut files = vec![];
+    let mut to_visit = vec![PathBuf::new()];
+
+    while let Some(relative_dir) = to_visit.pop() {
+        let entries = try!(fs::read_dir(dir.join(&relative_dir))
+            .map_err(|e| format!("couldn't read directory '{}' ({})", dir.display(), e)));
+        for entry in entries {
+            let entry = try!(entry.map_err(|e| e.to_string()));
+            let relative_path = relative_dir.join(entry.file_name());
+            if entry.path().is_dir() {
+                to_visit.push(relative_path);
+            } else {
+                files.push(relative_path.to_string_lossy().replace("\\", "/"));
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+pub fn run_dir(run_reference: &str) -> PathBuf {
+    Path::new(RUNS_DIR).join(run_reference)
+}
+
+pub fn archive_run(location: &ArchiveLocation,
+                   staging_dir: &Path,
+                   context: &JobContext,
+                   tasks: &Vec<&Task<&FactfileTask>>,
+                   backend: &dyn ArchiveBackend)
+                   -> Result<String, String> {
+    try!(write_run_files(staging_dir, context, tasks));
+    try!(backend.upload(staging_dir, location, &context.run_reference));
+    Ok(location.for_run(&context.run_reference))
+}
@@ -0,0 +1,179 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::task_list::{Task, State};
+use factotum::executor::execution_strategy::{RunResult, SpilledOutput};
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+struct MockBackend {
+    result: Result<(), String>,
+    expected_destination: String,
+}
+
+impl ArchiveBackend for MockBackend {
+    fn upload(&self,
+              source_dir: &Path,
+              location: &ArchiveLocation,
+              run_reference: &str)
+              -> Result<(), String> {
+        assert!(source_dir.join("result.json").exists());
+        assert!(source_dir.join("report.html").exists());
+        assert_eq!(location.for_run(run_reference), self.expected_destination);
+        self.result.clone()
+    }
+}
+
+#[test]
+fn parse_archive_url_good() {
+    assert_eq!(parse_archive_url("s3://bucket/some/prefix/"),
+               Ok(ArchiveLocation {
+                   scheme: "s3".to_string(),
+                   bucket: "bucket".to_string(),
+                   prefix: "some/prefix".to_string(),
+               }));
+    assert_eq!(parse_archive_url("s3://bucket"),
+               Ok(ArchiveLocation {
+                   scheme: "s3".to_string(),
+                   bucket: "bucket".to_string(),
+                   prefix: "".to_string(),
+               }));
+    assert_eq!(parse_archive_url("gs://bucket/prefix"),
+               Ok(ArchiveLocation {
+                   scheme: "gs".to_string(),
+                   bucket: "bucket".to_string(),
+                   prefix: "prefix".to_string(),
+               }));
+}
+
+#[test]
+fn parse_archive_url_bad() {
+    assert_eq!(parse_archive_url("http://bucket/prefix"),
+               Err("archive location must begin with 's3://' or 'gs://'".to_string()));
+    assert_eq!(parse_archive_url("s3:///prefix"),
+               Err("archive location is missing a bucket name".to_string()));
+}
+
+#[test]
+fn for_run_keys_by_run_reference() {
+    let with_prefix = parse_archive_url("s3://bucket/logs/").unwrap();
+    assert_eq!(with_prefix.for_run("abc"), "s3://bucket/logs/abc/");
+
+    let without_prefix = parse_archive_url("s3://bucket/").unwrap();
+    assert_eq!(without_prefix.for_run("abc"), "s3://bucket/abc/");
+
+    let gcs = parse_archive_url("gs://bucket/logs").unwrap();
+    assert_eq!(gcs.for_run("abc"), "gs://bucket/logs/abc/");
+    assert_eq!(gcs.object_prefix("abc"), "logs/abc");
+}
+
+#[test]
+fn log_file_name_is_path_safe() {
+    assert_eq!(log_file_name("echo alpha/beta", "stdout"),
+               "echo_alpha_beta.stdout.log");
+}
+
+#[test]
+fn archive_run_writes_and_uploads() {
+    let context = JobContext::new("archive test", "{}", None);
+    let spec = make_task("say hello", &vec![]);
+    let mut task = Task::new("say hello", &spec);
+    task.state = State::Success;
+    task.run_result = Some(RunResult {
+        duration: Duration::from_secs(1),
+        task_execution_error: None,
+        stdout: Some("hello".to_string()),
+        stderr: Some("oops".to_string()),
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+    });
+
+    let mut staging = env::temp_dir();
+    staging.push(format!("factotum-archive-test-{}", context.run_reference));
+
+    let location = parse_archive_url("s3://bucket/prefix").unwrap();
+    let expected_destination = format!("s3://bucket/prefix/{}/", context.run_reference);
+
+    let backend = MockBackend {
+        result: Ok(()),
+        expected_destination: expected_destination.clone(),
+    };
+
+    let result = archive_run(&location, &staging, &context, &vec![&task], &backend);
+
+    assert_eq!(result, Ok(expected_destination));
+    assert_eq!(list_files(&staging),
+               Ok(vec!["logs/say_hello.stderr.log".to_string(),
+                       "logs/say_hello.stdout.log".to_string(),
+                       "report.html".to_string(),
+                       "result.json".to_string()]));
+    assert!(fs::remove_dir_all(&staging).is_ok());
+}
+
+#[test]
+fn archive_run_reports_upload_failure() {
+    let context = JobContext::new("archive test", "{}", None);
+    let mut staging = env::temp_dir();
+    staging.push(format!("factotum-archive-test-{}", context.run_reference));
+
+    let location = parse_archive_url("gs://bucket").unwrap();
+    let backend = MockBackend {
+        result: Err("no credentials".to_string()),
+        expected_destination: format!("gs://bucket/{}/", context.run_reference),
+    };
+    let result = archive_run(&location, &staging, &context, &vec![], &backend);
+
+    assert_eq!(result, Err("no credentials".to_string()));
+    assert!(fs::remove_dir_all(&staging).is_ok());
+}
+
+#[test]
+fn write_run_files_copies_spilled_output() {
+    let context = JobContext::new("archive test", "{}", None);
+    let mut staging = env::temp_dir();
+    staging.push(format!("factotum-archive-test-{}", context.run_reference));
+    let spill_file = env::temp_dir()
+        .join(format!("factotum-archive-test-{}.stdout.log", context.run_reference));
+    File::create(&spill_file).unwrap().write_all(b"line 1\nline 2\nline 3\n").unwrap();
+
+    let spec = make_task("chatty", &vec![]);
+    let mut task = Task::new("chatty", &spec);
+    task.state = State::Success;
+    task.run_result = Some(RunResult {
+        duration: Duration::from_secs(1),
+        task_execution_error: None,
+        stdout: Some("line 3".to_string()),
+        stderr: None,
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: Some(SpilledOutput {
+            stdout_file: Some(spill_file.clone()),
+            stderr_file: None,
+        }),
+    });
+
+    assert_eq!(write_run_files(&staging, &context, &vec![&task]), Ok(()));
+    assert_eq!(fs::read_to_string(staging.join("logs/chatty.stdout.log")).unwrap(),
+               "line 1\nline 2\nline 3\n");
+    assert!(!staging.join("logs/chatty.stderr.log").exists());
+    assert!(fs::remove_dir_all(&staging).is_ok());
+    assert!(fs::remove_file(&spill_file).is_ok());
+}
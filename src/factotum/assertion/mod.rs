@@ -0,0 +1,50 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::HashMap;
+
+// each of an assert task's arguments is an expression (see factotum::expression) that
+// has to hold
+pub const EXECUTOR: &'static str = "assert";
+
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace("\\", "\\\\").replace("'", "\\'"))
+}
+
+// replaces ${task name} with the (quoted, trimmed) stdout of that task, if it has run
+pub fn substitute_outputs(expression: &str, outputs: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut rest = expression;
+
+    while let Some(start) = rest.find("${") {
+        match rest[start..].find('}') {
+            Some(len) => {
+                let name = &rest[start + 2..start + len];
+                result.push_str(&rest[..start]);
+                match outputs.get(name) {
+                    Some(output) => result.push_str(&quote(output.trim())),
+                    None => result.push_str(&rest[start..start + len + 1]),
+                }
+                rest = &rest[start + len + 1..];
+            }
+            None => break,
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
@@ -0,0 +1,35 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::expression;
+
+fn holds(source: &str) -> Result<bool, String> {
+    expression::parse(source).and_then(|e| e.evaluate(&expression::Context::default()))
+}
+
+#[test]
+fn substitute_outputs_quotes_values() {
+    let mut outputs = HashMap::new();
+    outputs.insert("count rows".to_string(), "  1234\n".to_string());
+    outputs.insert("region".to_string(), "it's eu".to_string());
+
+    assert_eq!(substitute_outputs("${count rows} >= 1000", &outputs),
+               "'1234' >= 1000");
+    assert_eq!(substitute_outputs("${region} =~ eu", &outputs),
+               "'it\\'s eu' =~ eu");
+    assert_eq!(substitute_outputs("${missing} == ${region", &outputs),
+               "${missing} == ${region");
+    assert_eq!(holds(&substitute_outputs("${region} =~ eu$", &outputs)), Ok(true));
+}
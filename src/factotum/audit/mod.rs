@@ -0,0 +1,220 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::exitcode;
+use factotum::manifest;
+use factotum::syslog::{self, LifecycleMessage, Priority};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const REDACTED: &'static str = "<redacted>";
+
+// variables whose names contain any of these (ignoring case) are never written out
+pub const SECRET_PATTERNS: [&'static str; 9] = ["password",
+                                                "passwd",
+                                                "secret",
+                                                "token",
+                                                "credential",
+                                                "api_key",
+                                                "apikey",
+                                                "private_key",
+                                                "access_key"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditLog {
+    File(PathBuf),
+    Syslog,
+    Journald,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub user: String,
+    pub uid: u32,
+    pub host: String,
+    pub command: String,
+    pub factfile: String,
+    pub factfile_sha256: Option<String>,
+    pub variables: Json,
+    pub exit_code: i32,
+    pub duration_millis: u64,
+}
+
+pub fn parse_audit_log(log: &str) -> Result<AuditLog, String> {
+    match log {
+        "" => Err("the audit log must be a file, 'syslog' or 'journald'".to_string()),
+        "syslog" => Ok(AuditLog::Syslog),
+        "journald" => Ok(AuditLog::Journald),
+        path => Ok(AuditLog::File(PathBuf::from(path))),
+    }
+}
+
+pub fn is_secret(name: &str, extra: &Vec<String>) -> bool {
+    let lower = name.to_lowercase();
+    SECRET_PATTERNS.iter().any(|p| lower.contains(p)) || extra.iter().any(|e| e == name)
+}
+
+pub fn redact(variables: &Json, extra: &Vec<String>) -> Json {
+    match *variables {
+        Json::Object(ref vars) => {
+            Json::Object(vars.iter()
+                .map(|(k, v)| {
+                    if is_secret(k, extra) {
+                        (k.clone(), REDACTED.to_json())
+                    } else {
+                        (k.clone(), redact(v, extra))
+                    }
+                })
+                .collect())
+        }
+        Json::Array(ref items) => Json::Array(items.iter().map(|i| redact(i, extra)).collect()),
+        ref other => other.clone(),
+    }
+}
+
+// the variables redact() hid, nested ones named by their path such as 'aws.secret_key'
+pub fn redacted_names(variables: &Json) -> Vec<String> {
+    fn collect(value: &Json, path: &str, names: &mut Vec<String>) {
+        match *value {
+            Json::Object(ref vars) => {
+                for (k, v) in vars.iter() {
+                    let name = if path.is_empty() {
+                        k.clone()
+                    } else {
+                        format!("{}.{}", path, k)
+                    };
+                    collect(v, &name, names);
+                }
+            }
+            Json::Array(ref items) => {
+                for item in items.iter() {
+                    collect(item, path, names);
+                }
+            }
+            Json::String(ref s) if s == REDACTED && !path.is_empty() => {
+                names.push(path.to_string())
+            }
+            _ => {}
+        }
+    }
+
+    let mut names = vec![];
+    collect(variables, "", &mut names);
+    names
+}
+
+// the values of the secret variables, so they can be taken out of anything they were templated into
+pub fn secret_values(variables: &BTreeMap<String, String>, extra: &Vec<String>) -> Vec<String> {
+    variables.iter()
+        .filter(|&(name, value)| !value.is_empty() && is_secret(name, extra))
+        .map(|(_, value)| value.clone())
+        .collect()
+}
+
+pub fn redact_values(text: &str, secrets: &Vec<String>) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| text.replace(secret, REDACTED))
+}
+
+// a Factfile that can't be read still gets a record, just without a checksum
+pub fn factfile_checksum(factfile: &Path) -> Option<String> {
+    match manifest::checksum(factfile) {
+        Ok(checksum) => Some(checksum.sha256),
+        Err(msg) => {
+            warn!("Couldn't checksum the Factfile for the audit log: {}", msg);
+            None
+        }
+    }
+}
+
+impl ToJson for AuditRecord {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("timestamp".to_string(), self.timestamp.to_json());
+        d.insert("user".to_string(), self.user.to_json());
+        d.insert("uid".to_string(), self.uid.to_json());
+        d.insert("host".to_string(), self.host.to_json());
+        d.insert("command".to_string(), self.command.to_json());
+        d.insert("factfile".to_string(), self.factfile.to_json());
+        d.insert("factfileSha256".to_string(), self.factfile_sha256.to_json());
+        d.insert("variables".to_string(), self.variables.clone());
+        d.insert("exitCode".to_string(), self.exit_code.to_json());
+        d.insert("outcome".to_string(),
+                 exitcode::class_name(self.exit_code).to_json());
+        d.insert("durationMillis".to_string(), self.duration_millis.to_json());
+        Json::Object(d)
+    }
+}
+
+pub fn audit_message(record: &AuditRecord) -> LifecycleMessage {
+    let outcome = exitcode::class_name(record.exit_code);
+    let mut fields = BTreeMap::new();
+    fields.insert("FACTOTUM_AUDIT_USER".to_string(), record.user.clone());
+    fields.insert("FACTOTUM_AUDIT_UID".to_string(), record.uid.to_string());
+    fields.insert("FACTOTUM_AUDIT_HOST".to_string(), record.host.clone());
+    fields.insert("FACTOTUM_AUDIT_COMMAND".to_string(), record.command.clone());
+    fields.insert("FACTOTUM_AUDIT_FACTFILE".to_string(), record.factfile.clone());
+    fields.insert("FACTOTUM_AUDIT_FACTFILE_SHA256".to_string(),
+                  record.factfile_sha256.clone().unwrap_or_default());
+    fields.insert("FACTOTUM_AUDIT_VARIABLES".to_string(),
+                  record.variables.to_string());
+    fields.insert("FACTOTUM_AUDIT_OUTCOME".to_string(), outcome.to_string());
+    fields.insert("FACTOTUM_AUDIT_EXIT_CODE".to_string(),
+                  record.exit_code.to_string());
+
+    LifecycleMessage {
+        priority: if record.exit_code == exitcode::SUCCESS {
+            Priority::Notice
+        } else {
+            Priority::Warning
+        },
+        message: format!("'{}' ran '{}' ({}) on {}: {}",
+                         record.user,
+                         record.factfile,
+                         record.command,
+                         record.host,
+                         outcome),
+        fields: fields,
+    }
+}
+
+// the file is only ever appended to, a line of JSON per invocation
+pub fn write_record(log: &AuditLog, record: &AuditRecord) -> Result<(), String> {
+    match *log {
+        AuditLog::File(ref path) => {
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    try!(fs::create_dir_all(parent)
+                        .map_err(|e| format!("couldn't create '{}' ({})", parent.display(), e)));
+                }
+            }
+            let mut f = try!(OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(path)
+                .map_err(|e| format!("couldn't open '{}' ({})", path.display(), e)));
+            writeln!(f, "{}", record.to_json())
+                .map_err(|e| format!("couldn't write to '{}' ({})", path.display(), e))
+        }
+        AuditLog::Syslog => syslog::emit_syslog(&audit_message(record)),
+        AuditLog::Journald => syslog::emit_journald(&audit_message(record)),
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+use super::*;
+use factotum::exitcode;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+
+fn make_record() -> AuditRecord {
+    AuditRecord {
+        timestamp: "2021-01-01T00:00:00+00:00".to_string(),
+        user: "jane".to_string(),
+        uid: 1000,
+        host: "etl-1".to_string(),
+        command: "run".to_string(),
+        factfile: "/jobs/etl.factfile".to_string(),
+        factfile_sha256: Some("abc".to_string()),
+        variables: Json::from_str(r#"{"date": "2021-01-01"}"#).unwrap(),
+        exit_code: exitcode::TASK_FAILED,
+        duration_millis: 1500,
+    }
+}
+
+#[test]
+fn parse_audit_log_good() {
+    assert_eq!(parse_audit_log("syslog"), Ok(AuditLog::Syslog));
+    assert_eq!(parse_audit_log("journald"), Ok(AuditLog::Journald));
+    assert_eq!(parse_audit_log("/var/log/factotum/audit.jsonl"),
+               Ok(AuditLog::File(PathBuf::from("/var/log/factotum/audit.jsonl"))));
+    assert!(parse_audit_log("").is_err());
+}
+
+#[test]
+fn redact_hides_secrets() {
+    let variables = Json::from_str(r#"{"date": "2021-01-01", "DB_PASSWORD": "hunter2",
+                                       "github_token": "ghp_1", "region": "eu-west-1",
+                                       "nested": {"aws_secret": "x", "bucket": "b"}}"#)
+        .unwrap();
+
+    let redacted = redact(&variables, &vec!["region".to_string()]);
+    assert_eq!(redacted,
+               Json::from_str(r#"{"date": "2021-01-01", "DB_PASSWORD": "<redacted>",
+                                  "github_token": "<redacted>", "region": "<redacted>",
+                                  "nested": {"aws_secret": "<redacted>", "bucket": "b"}}"#)
+                   .unwrap());
+}
+
+#[test]
+fn redacted_names_good() {
+    let variables = Json::from_str(r#"{"date": "2021-01-01", "DB_PASSWORD": "<redacted>",
+                                       "nested": {"aws_secret": "<redacted>", "bucket": "b"}}"#)
+        .unwrap();
+    assert_eq!(redacted_names(&variables),
+               vec!["DB_PASSWORD".to_string(), "nested.aws_secret".to_string()]);
+    assert!(redacted_names(&Json::from_str(r#"{"date": "2021-01-01"}"#).unwrap()).is_empty());
+}
+
+#[test]
+fn secret_values_are_taken_out_of_text() {
+    let mut variables = BTreeMap::new();
+    variables.insert("date".to_string(), "2021-01-01".to_string());
+    variables.insert("DB_PASSWORD".to_string(), "hunter2".to_string());
+    variables.insert("api_token".to_string(), "".to_string());
+
+    let secrets = secret_values(&variables, &vec![]);
+    assert_eq!(secrets, vec!["hunter2".to_string()]);
+    assert_eq!(redact_values("psql -p hunter2 -d 2021-01-01", &secrets),
+               "psql -p <redacted> -d 2021-01-01");
+}
+
+#[test]
+fn audit_record_to_json() {
+    let json = make_record().to_json();
+    let obj = json.as_object().unwrap();
+
+    assert_eq!(obj.get("outcome"), Some(&"task-failed".to_json()));
+    assert_eq!(obj.get("exitCode"), Some(&2.to_json()));
+    assert_eq!(obj.get("factfileSha256"), Some(&"abc".to_json()));
+    assert_eq!(obj.get("user"), Some(&"jane".to_json()));
+}
+
+#[test]
+fn audit_message_good() {
+    let message = audit_message(&make_record());
+
+    assert_eq!(message.message,
+               "'jane' ran '/jobs/etl.factfile' (run) on etl-1: task-failed");
+    assert_eq!(message.priority, Priority::Warning);
+    assert_eq!(message.fields.get("FACTOTUM_AUDIT_VARIABLES"),
+               Some(&r#"{"date":"2021-01-01"}"#.to_string()));
+}
+
+#[test]
+fn write_record_appends() {
+    let dir = env::temp_dir().join("factotum-audit-test");
+    let path = dir.join("audit.jsonl");
+    let _ = fs::remove_dir_all(&dir);
+    let log = AuditLog::File(path.clone());
+
+    assert_eq!(write_record(&log, &make_record()), Ok(()));
+    assert_eq!(write_record(&log, &make_record()), Ok(()));
+
+    let contents = fs::read_to_string(&path).unwrap();
+    let lines = contents.lines().collect::<Vec<&str>>();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1], make_record().to_json().to_string());
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+#[test]
+fn factfile_checksum_good() {
+    assert_eq!(factfile_checksum(Path::new("/no/such.factfile")), None);
+    assert_eq!(factfile_checksum(Path::new("./tests/resources/example_ok.factfile"))
+                   .map(|c| c.len()),
+               Some(64));
+}
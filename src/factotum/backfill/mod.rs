@@ -0,0 +1,76 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use chrono::NaiveDate;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+
+pub const DATE_FORMAT: &'static str = "%Y-%m-%d";
+
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .map_err(|e| format!("'{}' is not a valid date in the form YYYY-MM-DD ({})", date, e))
+}
+
+pub fn date_range(from: &str, to: &str) -> Result<Vec<NaiveDate>, String> {
+    let first = try!(parse_date(from));
+    let last = try!(parse_date(to));
+
+    if last < first {
+        return Err(format!("the end date {} is before the start date {}", to, from));
+    }
+
+    let mut dates = vec![];
+    let mut date = first;
+    while date <= last {
+        dates.push(date);
+        date = date.succ();
+    }
+    Ok(dates)
+}
+
+pub const TIMESTAMP_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%SZ";
+pub const SCHEDULE_INTERVAL: &'static str = "1d";
+
+pub fn schedule_variables(date: &NaiveDate) -> BTreeMap<String, String> {
+    let mut vars = BTreeMap::new();
+    vars.insert("run_date".to_string(), date.format(DATE_FORMAT).to_string());
+    vars.insert("run_ts".to_string(),
+                date.and_hms(0, 0, 0).format(TIMESTAMP_FORMAT).to_string());
+    vars.insert("prev_run_ts".to_string(),
+                date.pred().and_hms(0, 0, 0).format(TIMESTAMP_FORMAT).to_string());
+    vars.insert("schedule_interval".to_string(),
+                SCHEDULE_INTERVAL.to_string());
+    vars
+}
+
+// the standard schedule variables are only added where --env doesn't already
+// supply a value, but the date variable named on the command line always wins
+pub fn env_for_date(env: &Option<Json>, var_name: &str, date: &NaiveDate) -> Json {
+    let mut vars = match *env {
+        Some(Json::Object(ref o)) => o.clone(),
+        _ => BTreeMap::new(),
+    };
+    for (name, value) in schedule_variables(date) {
+        if !vars.contains_key(&name) {
+            vars.insert(name, value.to_json());
+        }
+    }
+    vars.insert(var_name.to_string(),
+                date.format(DATE_FORMAT).to_string().to_json());
+    Json::Object(vars)
+}
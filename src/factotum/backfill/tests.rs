@@ -0,0 +1,69 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use rustc_serialize::json::Json;
+
+#[test]
+fn date_range_is_inclusive() {
+    let dates = date_range("2024-01-30", "2024-02-02")
+        .unwrap()
+        .iter()
+        .map(|d| d.format(DATE_FORMAT).to_string())
+        .collect::<Vec<String>>();
+    assert_eq!(dates,
+               vec!["2024-01-30", "2024-01-31", "2024-02-01", "2024-02-02"]);
+    assert_eq!(date_range("2024-01-01", "2024-01-01").unwrap().len(), 1);
+}
+
+#[test]
+fn date_range_bad() {
+    assert_eq!(date_range("2024-01-02", "2024-01-01"),
+               Err("the end date 2024-01-01 is before the start date 2024-01-02".to_string()));
+    assert!(date_range("2024-13-01", "2024-01-01")
+        .unwrap_err()
+        .starts_with("'2024-13-01' is not a valid date in the form YYYY-MM-DD"));
+}
+
+#[test]
+fn schedule_variables_good() {
+    let vars = schedule_variables(&parse_date("2024-03-01").unwrap());
+    assert_eq!(vars.get("run_date"), Some(&"2024-03-01".to_string()));
+    assert_eq!(vars.get("run_ts"), Some(&"2024-03-01T00:00:00Z".to_string()));
+    assert_eq!(vars.get("prev_run_ts"),
+               Some(&"2024-02-29T00:00:00Z".to_string()));
+    assert_eq!(vars.get("schedule_interval"), Some(&"1d".to_string()));
+}
+
+#[test]
+fn env_for_date_adds_variables() {
+    let date = parse_date("2024-01-31").unwrap();
+    let env = Some(Json::from_str("{\"bucket\":\"logs\",\"run_date\":\"old\",\
+                                   \"schedule_interval\":\"daily\"}")
+        .unwrap());
+
+    let decorated = env_for_date(&env, "run_date", &date);
+    assert_eq!(decorated.find("bucket").unwrap().as_string(), Some("logs"));
+    assert_eq!(decorated.find("run_date").unwrap().as_string(),
+               Some("2024-01-31"));
+    assert_eq!(decorated.find("schedule_interval").unwrap().as_string(),
+               Some("daily"));
+    assert_eq!(decorated.find("prev_run_ts").unwrap().as_string(),
+               Some("2024-01-30T00:00:00Z"));
+
+    let decorated = env_for_date(&None, "day", &date);
+    assert_eq!(decorated.find("day").unwrap().as_string(), Some("2024-01-31"));
+    assert_eq!(decorated.find("run_date").unwrap().as_string(),
+               Some("2024-01-31"));
+}
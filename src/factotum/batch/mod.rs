@@ -0,0 +1,302 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::retry;
+use factotum::waitfor::WaitOptions;
+use rustc_serialize::json::{self, Json, ToJson};
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+pub const EXECUTOR: &'static str = "aws-batch";
+pub const DEFAULT_LOG_GROUP: &'static str = "/aws/batch/job";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BatchJob {
+    pub definition: String,
+    pub queue: String,
+    pub name: String,
+    pub parameters: BTreeMap<String, String>,
+    pub region: Option<String>,
+    pub log_group: String,
+    pub log_lines: u32,
+    pub polling: WaitOptions,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct JobStatus {
+    pub status: String,
+    pub reason: Option<String>,
+    pub exit_code: Option<i64>,
+    pub log_stream: Option<String>,
+}
+
+// a batch task's command is its job definition (name, name:revision or ARN) and its arguments
+// the job's parameters, e.g. [ "queue=heavy", "param=date=2021-01-01", "timeout=4h" ]
+pub fn parse_job(definition: &str, params: &Vec<String>) -> Result<BatchJob, String> {
+    let definition = definition.trim();
+    if definition.is_empty() {
+        return Err("there is no job definition to submit".to_string());
+    }
+
+    let mut job = BatchJob {
+        definition: definition.to_string(),
+        queue: String::new(),
+        name: String::new(),
+        parameters: BTreeMap::new(),
+        region: None,
+        log_group: DEFAULT_LOG_GROUP.to_string(),
+        log_lines: 50,
+        polling: WaitOptions {
+            poll_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(24 * 60 * 60),
+        },
+    };
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value,
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "queue" => job.queue = value.to_string(),
+            "name" => job.name = value.to_string(),
+            "param" => {
+                match value.find('=') {
+                    Some(i) if i > 0 => {
+                        job.parameters.insert(value[..i].to_string(), value[i + 1..].to_string());
+                    }
+                    _ => return Err(format!("'{}' must be given as param=<name>=<value>", param)),
+                }
+            }
+            "region" => job.region = Some(value.to_string()),
+            "log-group" => job.log_group = value.to_string(),
+            "log-lines" => {
+                job.log_lines = try!(value.parse()
+                    .map_err(|_| format!("'{}' isn't a number of log lines", value)))
+            }
+            "poll-interval" => job.polling.poll_interval = try!(retry::parse_duration(value)),
+            "timeout" => job.polling.timeout = try!(retry::parse_duration(value)),
+            _ => {
+                return Err(format!("'{}' isn't a job parameter (expected queue, name, param, \
+                                    region, log-group, log-lines, poll-interval or timeout)",
+                                   name))
+            }
+        }
+    }
+
+    if job.queue.is_empty() {
+        return Err("the job has no queue to be submitted to (e.g. queue=heavy)".to_string());
+    }
+    if job.name.is_empty() {
+        job.name = job_name(&job.definition);
+    }
+    if job.polling.poll_interval.as_secs() == 0 {
+        return Err("the poll interval must be at least 1s".to_string());
+    }
+
+    Ok(job)
+}
+
+// job names can only have letters, numbers, hyphens and underscores
+pub fn job_name(definition: &str) -> String {
+    let name = definition.rsplit('/').next().unwrap_or(definition);
+    let name = name.split(':').next().unwrap_or(name);
+    name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .take(128)
+        .collect()
+}
+
+// None while the job's still to finish
+pub fn job_outcome(status: &JobStatus) -> Option<Result<(), String>> {
+    match status.status.as_ref() {
+        "SUCCEEDED" => Some(Ok(())),
+        "FAILED" => {
+            let mut details = vec![];
+            if let Some(ref reason) = status.reason {
+                details.push(reason.clone());
+            }
+            if let Some(code) = status.exit_code {
+                details.push(format!("exit code {}", code));
+            }
+            Some(Err(if details.is_empty() {
+                "the job FAILED".to_string()
+            } else {
+                format!("the job FAILED ({})", details.join(", "))
+            }))
+        }
+        _ => None,
+    }
+}
+
+pub trait BatchClient {
+    fn submit_job(&self, job: &BatchJob) -> Result<String, String>;
+    fn describe_job(&self, job_id: &str) -> Result<JobStatus, String>;
+    fn terminate_job(&self, job_id: &str, reason: &str) -> Result<(), String>;
+    fn log_tail(&self, group: &str, stream: &str, lines: u32) -> Result<Vec<String>, String>;
+}
+
+pub struct AwsCliBatchClient {
+    pub region: Option<String>,
+}
+
+impl AwsCliBatchClient {
+    fn run(&self, service: &str, args: Vec<&str>) -> Result<Json, String> {
+        let mut command = Command::new("aws");
+        command.arg(service).args(&args).arg("--output=json");
+        if let Some(ref region) = self.region {
+            command.arg(format!("--region={}", region));
+        }
+        let output = try!(command.output()
+            .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.trim().is_empty() {
+            Ok(Json::Null)
+        } else {
+            Json::from_str(&stdout).map_err(|e| format!("the aws cli's output isn't JSON ({})", e))
+        }
+    }
+}
+
+pub fn parse_job_status(json: &Json) -> Result<JobStatus, String> {
+    let job = try!(json.find("jobs")
+        .and_then(|jobs| jobs.as_array())
+        .and_then(|jobs| jobs.first())
+        .ok_or("the aws cli's output has no jobs".to_string()));
+    let status = try!(job.find("status")
+        .and_then(|s| s.as_string())
+        .ok_or("the aws cli's output has no job status".to_string()));
+
+    Ok(JobStatus {
+        status: status.to_string(),
+        reason: job.find("statusReason").and_then(|r| r.as_string()).map(|r| r.to_string()),
+        exit_code: job.find_path(&["container", "exitCode"]).and_then(|c| c.as_i64()),
+        log_stream: job.find_path(&["container", "logStreamName"])
+            .and_then(|s| s.as_string())
+            .map(|s| s.to_string()),
+    })
+}
+
+impl BatchClient for AwsCliBatchClient {
+    fn submit_job(&self, job: &BatchJob) -> Result<String, String> {
+        let parameters = try!(json::encode(&job.parameters.to_json()).map_err(|e| e.to_string()));
+        let submitted = try!(self.run("batch",
+                                      vec!["submit-job",
+                                           "--job-name",
+                                           &job.name,
+                                           "--job-queue",
+                                           &job.queue,
+                                           "--job-definition",
+                                           &job.definition,
+                                           "--parameters",
+                                           &parameters]));
+        submitted.find("jobId")
+            .and_then(|id| id.as_string())
+            .map(|id| id.to_string())
+            .ok_or("the aws cli's output has no jobId".to_string())
+    }
+
+    fn describe_job(&self, job_id: &str) -> Result<JobStatus, String> {
+        let described = try!(self.run("batch", vec!["describe-jobs", "--jobs", job_id]));
+        parse_job_status(&described)
+    }
+
+    fn terminate_job(&self, job_id: &str, reason: &str) -> Result<(), String> {
+        self.run("batch",
+                 vec!["terminate-job", "--job-id", job_id, "--reason", reason])
+            .map(|_| ())
+    }
+
+    fn log_tail(&self, group: &str, stream: &str, lines: u32) -> Result<Vec<String>, String> {
+        let events = try!(self.run("logs",
+                                   vec!["get-log-events",
+                                        "--log-group-name",
+                                        group,
+                                        "--log-stream-name",
+                                        stream,
+                                        "--limit",
+                                        &lines.to_string(),
+                                        "--no-start-from-head"]));
+        Ok(events.find("events")
+            .and_then(|e| e.as_array())
+            .map(|events| {
+                events.iter()
+                    .filter_map(|e| e.find("message").and_then(|m| m.as_string()))
+                    .map(|m| m.to_string())
+                    .collect()
+            })
+            .unwrap_or(vec![]))
+    }
+}
+
+// submits the job, polls it until it finishes and then reports the tail of its log, returning
+// how many checks it took
+pub fn run_job<C, S, R>(client: &C, job: &BatchJob, sleep: S, report: R) -> Result<u64, String>
+    where C: BatchClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let job_id = try!(client.submit_job(job)
+        .map_err(|e| format!("couldn't submit the job to '{}': {}", job.queue, e)));
+    report(format!("Submitted '{}' to '{}' as {}", job.definition, job.queue, job_id));
+
+    let max_checks = job.polling.timeout.as_secs() / job.polling.poll_interval.as_secs() + 1;
+    for attempt in 1..max_checks + 1 {
+        let status = try!(client.describe_job(&job_id));
+        if let Some(outcome) = job_outcome(&status) {
+            report_log_tail(client, job, &status, &report);
+            return outcome.map(|_| attempt);
+        }
+        if attempt < max_checks {
+            sleep(job.polling.poll_interval);
+        }
+    }
+
+    // don't leave the job running once nothing's watching it
+    if let Err(e) = client.terminate_job(&job_id, "factotum timed out waiting for it") {
+        report(format!("Couldn't terminate the job {}: {}", job_id, e));
+    }
+    Err(format!("timed out after {}s waiting for the job {} to finish",
+                job.polling.timeout.as_secs(),
+                job_id))
+}
+
+fn report_log_tail<C, R>(client: &C, job: &BatchJob, status: &JobStatus, report: &R)
+    where C: BatchClient,
+          R: Fn(String)
+{
+    let stream = match status.log_stream {
+        Some(ref stream) if job.log_lines > 0 => stream,
+        _ => return,
+    };
+    match client.log_tail(&job.log_group, stream, job.log_lines) {
+        Ok(lines) => {
+            report(format!("The end of its log ({}):", stream));
+            for line in lines {
+                report(line);
+            }
+        }
+        Err(e) => report(format!("Couldn't fetch the job's log from '{}': {}", stream, e)),
+    }
+}
@@ -0,0 +1,181 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::cell::RefCell;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+fn job() -> BatchJob {
+    parse_job("enrich:3",
+              &params(vec!["queue=heavy", "poll-interval=1s", "timeout=3s", "log-lines=2"]))
+        .unwrap()
+}
+
+struct FakeBatch {
+    statuses: RefCell<Vec<&'static str>>,
+    calls: RefCell<Vec<String>>,
+}
+
+impl FakeBatch {
+    fn new(mut statuses: Vec<&'static str>) -> FakeBatch {
+        statuses.reverse();
+        FakeBatch {
+            statuses: RefCell::new(statuses),
+            calls: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl BatchClient for FakeBatch {
+    fn submit_job(&self, job: &BatchJob) -> Result<String, String> {
+        self.calls.borrow_mut().push(format!("submit {} {}", job.name, job.queue));
+        Ok("abc-123".to_string())
+    }
+
+    fn describe_job(&self, job_id: &str) -> Result<JobStatus, String> {
+        self.calls.borrow_mut().push(format!("describe {}", job_id));
+        let status = try!(self.statuses.borrow_mut().pop().ok_or("no more statuses".to_string()));
+        Ok(JobStatus {
+            status: status.to_string(),
+            reason: None,
+            exit_code: if status == "FAILED" { Some(3) } else { None },
+            log_stream: Some("enrich/default/abc".to_string()),
+        })
+    }
+
+    fn terminate_job(&self, job_id: &str, _: &str) -> Result<(), String> {
+        self.calls.borrow_mut().push(format!("terminate {}", job_id));
+        Ok(())
+    }
+
+    fn log_tail(&self, group: &str, stream: &str, lines: u32) -> Result<Vec<String>, String> {
+        self.calls.borrow_mut().push(format!("logs {} {} {}", group, stream, lines));
+        Ok(vec!["loading".to_string(), "done".to_string()])
+    }
+}
+
+#[test]
+fn parse_job_good() {
+    let job = parse_job("arn:aws:batch:eu-west-1:123:job-definition/enrich:3",
+                        &params(vec!["queue=heavy",
+                                     "param=date=2021-01-01",
+                                     "param=filter=a=b",
+                                     "region=eu-west-1",
+                                     "timeout=4h"]))
+        .unwrap();
+    assert_eq!(job.name, "enrich");
+    assert_eq!(job.queue, "heavy");
+    assert_eq!(job.parameters.get("date"), Some(&"2021-01-01".to_string()));
+    assert_eq!(job.parameters.get("filter"), Some(&"a=b".to_string()));
+    assert_eq!(job.region, Some("eu-west-1".to_string()));
+    assert_eq!(job.log_group, DEFAULT_LOG_GROUP);
+    assert_eq!(job.log_lines, 50);
+    assert_eq!(job.polling.timeout, Duration::from_secs(4 * 60 * 60));
+}
+
+#[test]
+fn parse_job_bad() {
+    assert_eq!(parse_job(" ", &params(vec!["queue=heavy"])),
+               Err("there is no job definition to submit".to_string()));
+    assert_eq!(parse_job("enrich", &params(vec![])),
+               Err("the job has no queue to be submitted to (e.g. queue=heavy)".to_string()));
+    assert_eq!(parse_job("enrich", &params(vec!["queue=heavy", "param=date"])),
+               Err("'param=date' must be given as param=<name>=<value>".to_string()));
+    assert!(parse_job("enrich", &params(vec!["queue=heavy", "memory=4096"]))
+        .unwrap_err()
+        .starts_with("'memory' isn't a job parameter"));
+    assert_eq!(parse_job("enrich", &params(vec!["queue=heavy", "log-lines=all"])),
+               Err("'all' isn't a number of log lines".to_string()));
+}
+
+#[test]
+fn job_names_are_made_safe() {
+    assert_eq!(job_name("enrich:3"), "enrich");
+    assert_eq!(job_name("arn:aws:batch:eu-west-1:123:job-definition/load.v2:1"),
+               "load_v2");
+}
+
+#[test]
+fn job_statuses_map_to_outcomes() {
+    let status = |status: &str, reason: Option<&str>, exit_code: Option<i64>| {
+        JobStatus {
+            status: status.to_string(),
+            reason: reason.map(|r| r.to_string()),
+            exit_code: exit_code,
+            log_stream: None,
+        }
+    };
+    assert_eq!(job_outcome(&status("RUNNABLE", None, None)), None);
+    assert_eq!(job_outcome(&status("RUNNING", None, None)), None);
+    assert_eq!(job_outcome(&status("SUCCEEDED", None, Some(0))), Some(Ok(())));
+    assert_eq!(job_outcome(&status("FAILED", None, None)),
+               Some(Err("the job FAILED".to_string())));
+    assert_eq!(job_outcome(&status("FAILED", Some("Essential container in task exited"), Some(1))),
+               Some(Err("the job FAILED (Essential container in task exited, exit code 1)"
+                   .to_string())));
+}
+
+#[test]
+fn parse_job_status_reads_the_container() {
+    let described = Json::from_str("{\"jobs\": [{\"status\": \"FAILED\", \"statusReason\": \
+                                    \"OOM\", \"container\": {\"exitCode\": 137, \
+                                    \"logStreamName\": \"enrich/default/abc\"}}]}")
+        .unwrap();
+    assert_eq!(parse_job_status(&described),
+               Ok(JobStatus {
+                   status: "FAILED".to_string(),
+                   reason: Some("OOM".to_string()),
+                   exit_code: Some(137),
+                   log_stream: Some("enrich/default/abc".to_string()),
+               }));
+    assert!(parse_job_status(&Json::from_str("{\"jobs\": []}").unwrap()).is_err());
+}
+
+#[test]
+fn run_job_polls_until_the_job_succeeds_and_reports_its_log() {
+    let batch = FakeBatch::new(vec!["RUNNABLE", "RUNNING", "SUCCEEDED"]);
+    let reports = RefCell::new(vec![]);
+
+    let result = run_job(&batch, &job(), |_| {}, |r| reports.borrow_mut().push(r));
+
+    assert_eq!(result, Ok(3));
+    assert_eq!(*reports.borrow(),
+               vec!["Submitted 'enrich:3' to 'heavy' as abc-123",
+                    "The end of its log (enrich/default/abc):",
+                    "loading",
+                    "done"]);
+    assert_eq!(batch.calls.borrow().last(),
+               Some(&"logs /aws/batch/job enrich/default/abc 2".to_string()));
+}
+
+#[test]
+fn run_job_fails_when_the_job_fails() {
+    let batch = FakeBatch::new(vec!["RUNNING", "FAILED"]);
+    let reports = RefCell::new(vec![]);
+    let result = run_job(&batch, &job(), |_| {}, |r| reports.borrow_mut().push(r));
+    assert_eq!(result, Err("the job FAILED (exit code 3)".to_string()));
+    assert_eq!(reports.borrow().last(), Some(&"done".to_string()));
+}
+
+#[test]
+fn run_job_terminates_the_job_on_timeout() {
+    let batch = FakeBatch::new(vec!["RUNNING"; 10]);
+    let result = run_job(&batch, &job(), |_| {}, |_| {});
+    assert_eq!(result,
+               Err("timed out after 3s waiting for the job abc-123 to finish".to_string()));
+    assert_eq!(batch.calls.borrow().last(), Some(&"terminate abc-123".to_string()));
+}
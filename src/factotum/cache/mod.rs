@@ -0,0 +1,132 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use factotum::archive;
+use factotum::factfile::{Factfile, TaskInput};
+use factotum::factfile::Task as FactfileTask;
+use factotum::history::RunRecord;
+use factotum::manifest;
+use std::path::Path;
+
+pub const SKIP_REASON_PREFIX: &'static str = "cached";
+
+fn hash_input(digest: &mut Sha256, path: &Path) -> Result<(), String> {
+    manifest::hash_file(digest, path)
+        .map(|_| ())
+        .map_err(|e| format!("couldn't read the input '{}' ({})", path.display(), e))
+}
+
+fn part(digest: &mut Sha256, s: &str) {
+    digest.input_str(&format!("{}:", s.len()));
+    digest.input_str(s);
+}
+
+// everything that decides what the task does, and the contents of its inputs; each part
+// is length prefixed so moving text from one part to the next changes the key
+pub fn cache_key(task: &FactfileTask) -> Result<String, String> {
+    let cache = match task.cache {
+        Some(ref cache) => cache,
+        None => return Err(format!("the task '{}' isn't cached", task.name)),
+    };
+
+    let mut digest = Sha256::new();
+
+    part(&mut digest, &task.executor);
+    part(&mut digest, &task.command);
+    for arg in task.arguments.iter() {
+        part(&mut digest, arg);
+    }
+    part(&mut digest, &format!("{:?}", task.shell));
+    match task.stdin {
+        Some(TaskInput::Text(ref text)) => part(&mut digest, text),
+        Some(TaskInput::File(ref path)) => {
+            part(&mut digest, path);
+            try!(hash_input(&mut digest, Path::new(path)));
+        }
+        None => {}
+    }
+    for (name, value) in cache.variables.iter() {
+        part(&mut digest, name);
+        part(&mut digest, value);
+    }
+
+    // a directory is the files in it, so a file added to it changes the key
+    for input in cache.inputs.iter() {
+        let path = Path::new(input);
+        part(&mut digest, input);
+        if path.is_dir() {
+            for file in try!(archive::list_files(path)) {
+                part(&mut digest, &file);
+                try!(hash_input(&mut digest, &path.join(&file)));
+            }
+        } else {
+            try!(hash_input(&mut digest, path));
+        }
+    }
+
+    Ok(digest.result_str())
+}
+
+// the run the task last succeeded in, and the key it had then
+pub fn last_success(task_name: &str, runs: &Vec<RunRecord>) -> Option<(String, String)> {
+    runs.iter()
+        .rev()
+        .filter_map(|run| run.task(task_name).map(|task| (run, task)))
+        .find(|&(_, task)| task.state == "SUCCEEDED")
+        .and_then(|(run, task)| task.cache_key.clone().map(|key| (run.run_reference.clone(), key)))
+}
+
+// readies each cached task to be checked when its turn comes, with its last success
+pub fn apply(factfile: &mut Factfile, runs: &Vec<RunRecord>) {
+    let names = factfile.get_tasks_in_order()
+        .iter()
+        .flat_map(|grp| grp.iter())
+        .filter(|task| task.cache.is_some())
+        .map(|task| task.name.clone())
+        .collect::<Vec<String>>();
+
+    for name in names.iter() {
+        if let Some(cache) = factfile.find_task_mut(name).and_then(|task| task.cache.as_mut()) {
+            cache.enabled = true;
+            cache.last_success = last_success(name, runs);
+        }
+    }
+}
+
+// the task's key as it is now, and the run whose result is reused if the key hasn't changed
+// since the task last succeeded; None when the task isn't cached in this run
+pub fn check(task: &FactfileTask) -> Result<Option<(String, Option<String>)>, String> {
+    let cache = match task.cache {
+        Some(ref cache) if cache.enabled => cache,
+        _ => return Ok(None),
+    };
+
+    let key = try!(cache_key(task));
+    let hit = match cache.last_success {
+        Some((ref run_reference, ref last_key)) if *last_key == key => Some(run_reference.clone()),
+        _ => None,
+    };
+    Ok(Some((key, hit)))
+}
+
+pub fn skip_reason(run_reference: &str) -> String {
+    format!("{} - the task's inputs haven't changed since it succeeded in run {}",
+            SKIP_REASON_PREFIX,
+            run_reference)
+}
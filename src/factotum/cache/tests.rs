@@ -0,0 +1,156 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::{Shell, TaskCache};
+use factotum::history::TaskRecord;
+use factotum::tests::make_task;
+use std::env;
+use std::fs;
+use std::io::Write;
+
+fn cached_task(inputs: Vec<String>) -> FactfileTask {
+    let mut task = make_task("train", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "./train.sh".to_string();
+    task.cache = Some(TaskCache { inputs: inputs, ..TaskCache::default() });
+    task
+}
+
+fn run(run_reference: &str, state: &str, cache_key: Option<&str>) -> RunRecord {
+    RunRecord {
+        job_name: "job".to_string(),
+        run_reference: run_reference.to_string(),
+        start_time: "".to_string(),
+        run_state: "".to_string(),
+        tasks: vec![TaskRecord {
+                        task_name: "train".to_string(),
+                        state: state.to_string(),
+                        error_message: None,
+                        depends_on: vec![],
+                        started: None,
+                        duration_millis: None,
+                        cache_key: cache_key.map(|k| k.to_string()),
+                    }],
+        source: None,
+    }
+}
+
+#[test]
+fn cache_key_changes_with_what_the_task_does() {
+    let task = cached_task(vec![]);
+    let key = cache_key(&task).unwrap();
+    assert_eq!(key.len(), 64);
+    assert_eq!(cache_key(&task), Ok(key.clone()));
+
+    let mut other = task.clone();
+    other.arguments = vec!["--fast".to_string()];
+    assert!(cache_key(&other).unwrap() != key);
+
+    let mut other = task.clone();
+    other.shell = Shell::Bash;
+    assert!(cache_key(&other).unwrap() != key);
+
+    let mut other = task.clone();
+    other.cache.as_mut().unwrap().variables.insert("region".to_string(), "eu".to_string());
+    assert!(cache_key(&other).unwrap() != key);
+
+    // the name doesn't change what the task does
+    let mut other = task.clone();
+    other.name = "retrain".to_string();
+    assert_eq!(cache_key(&other), Ok(key));
+
+    assert_eq!(cache_key(&make_task("plain", &vec![])),
+               Err("the task 'plain' isn't cached".to_string()));
+}
+
+#[test]
+fn cache_key_changes_with_the_inputs() {
+    let dir = env::temp_dir().join("factotum-cache-test-inputs");
+    fs::create_dir_all(dir.join("parts")).unwrap();
+    fs::File::create(dir.join("model.csv")).unwrap().write_all(b"a,b\n").unwrap();
+    fs::File::create(dir.join("parts/1.csv")).unwrap().write_all(b"1\n").unwrap();
+
+    let task = cached_task(vec![dir.join("model.csv").to_string_lossy().into_owned(),
+                                dir.join("parts").to_string_lossy().into_owned()]);
+    let key = cache_key(&task).unwrap();
+
+    fs::File::create(dir.join("model.csv")).unwrap().write_all(b"a,c\n").unwrap();
+    let changed_file = cache_key(&task).unwrap();
+    assert!(changed_file != key);
+
+    fs::File::create(dir.join("parts/2.csv")).unwrap().write_all(b"2\n").unwrap();
+    assert!(cache_key(&task).unwrap() != changed_file);
+
+    fs::remove_file(dir.join("model.csv")).unwrap();
+    assert!(cache_key(&task).unwrap_err().starts_with("couldn't read the input"));
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+#[test]
+fn last_success_is_the_last_successful_run() {
+    let runs = vec![run("run-1", "SUCCEEDED", Some("old")),
+                    run("run-2", "SUCCEEDED", Some("abc")),
+                    run("run-3", "SKIPPED", Some("abc")),
+                    run("run-4", "FAILED", Some("def"))];
+    assert_eq!(last_success("train", &runs),
+               Some(("run-2".to_string(), "abc".to_string())));
+    assert_eq!(last_success("other", &runs), None);
+    assert_eq!(last_success("train", &vec![run("run-1", "SUCCEEDED", None)]), None);
+}
+
+#[test]
+fn apply_readies_cached_tasks() {
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&cached_task(vec![])).unwrap();
+    ff.add_task_obj(&make_task("plain", &vec![])).unwrap();
+
+    apply(&mut ff, &vec![run("run-1", "SUCCEEDED", Some("abc"))]);
+
+    let train = ff.find_task("train").unwrap().cache.clone().unwrap();
+    assert!(train.enabled);
+    assert_eq!(train.last_success, Some(("run-1".to_string(), "abc".to_string())));
+    assert_eq!(ff.find_task("plain").unwrap().cache, None);
+}
+
+#[test]
+fn check_compares_the_key_with_the_last_success() {
+    let mut task = cached_task(vec![]);
+    assert_eq!(check(&task), Ok(None));
+
+    let key = cache_key(&task).unwrap();
+    task.cache = Some(TaskCache { enabled: true, ..TaskCache::default() });
+    assert_eq!(check(&task), Ok(Some((key.clone(), None))));
+
+    task.cache = Some(TaskCache {
+        enabled: true,
+        last_success: Some(("run-1".to_string(), key.clone())),
+        ..TaskCache::default()
+    });
+    assert_eq!(check(&task), Ok(Some((key.clone(), Some("run-1".to_string())))));
+
+    task.cache = Some(TaskCache {
+        enabled: true,
+        last_success: Some(("run-1".to_string(), "old".to_string())),
+        ..TaskCache::default()
+    });
+    assert_eq!(check(&task), Ok(Some((key, None))));
+
+    task.cache = Some(TaskCache {
+        inputs: vec!["/factotum-cache-test-missing".to_string()],
+        enabled: true,
+        ..TaskCache::default()
+    });
+    assert!(check(&task).unwrap_err().starts_with("couldn't read the input"));
+}
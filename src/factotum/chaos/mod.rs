@@ -0,0 +1,90 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::execution_strategy::{RunResult, TaskLimits};
+use rand;
+use std::process::Command;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosPolicy {
+    pub fail_percent: u32,
+    pub tasks: Vec<String>,
+    pub exit_code: i32,
+}
+
+impl ChaosPolicy {
+    pub fn new(exit_code: i32) -> Self {
+        ChaosPolicy {
+            fail_percent: 0,
+            tasks: vec![],
+            exit_code: exit_code,
+        }
+    }
+
+    pub fn with_fail_percent(mut self, fail_percent: u32) -> Result<Self, String> {
+        if fail_percent > 100 {
+            return Err(format!("the chance of failing a task must be a percentage between 0 \
+                                and 100, not {}",
+                               fail_percent));
+        }
+        self.fail_percent = fail_percent;
+        Ok(self)
+    }
+
+    pub fn with_tasks(mut self, tasks: Vec<String>) -> Self {
+        self.tasks = tasks;
+        self
+    }
+
+    // named tasks always fail, any other task fails when the roll (0 to 1) is under the percentage
+    pub fn should_fail(&self, task_name: &str, roll: f64) -> bool {
+        self.tasks.iter().any(|t| t == task_name) || roll * 100.0 < self.fail_percent as f64
+    }
+
+    pub fn failure(&self, task_name: &str) -> RunResult {
+        RunResult {
+            duration: Duration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: Some(format!("chaos mode failed the task '{}' with the exit code {}",
+                                 task_name,
+                                 self.exit_code)),
+            return_code: self.exit_code,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    }
+}
+
+// wraps an execution strategy so tasks picked by the policy fail without running
+pub fn with_chaos<F>(policy: Option<ChaosPolicy>, strategy: F)
+    -> impl Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+{
+    move |name: &str, command: &mut Command, limits: &TaskLimits| {
+        match policy {
+            Some(ref policy) if policy.should_fail(name, rand::random::<f64>()) => {
+                warn!("Chaos mode is failing the task '{}' instead of running {:?}",
+                      name,
+                      command);
+                policy.failure(name)
+            }
+            _ => strategy(name, command, limits),
+        }
+    }
+}
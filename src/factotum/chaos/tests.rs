@@ -0,0 +1,74 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::execution_strategy::{RunResult, TaskLimits};
+use std::process::Command;
+use std::time::Duration;
+
+fn succeed(_: &str, _: &mut Command, _: &TaskLimits) -> RunResult {
+    RunResult {
+        duration: Duration::from_secs(1),
+        task_execution_error: None,
+        stdout: Some("ran".to_string()),
+        stderr: None,
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+    }
+}
+
+#[test]
+fn chaos_policy_checks_percent() {
+    assert_eq!(ChaosPolicy::new(1).with_fail_percent(100).map(|p| p.fail_percent),
+               Ok(100));
+    assert_eq!(ChaosPolicy::new(1).with_fail_percent(101),
+               Err("the chance of failing a task must be a percentage between 0 and 100, not \
+                    101"
+                   .to_string()));
+}
+
+#[test]
+fn should_fail_named_tasks_and_unlucky_rolls() {
+    let policy = ChaosPolicy::new(1)
+        .with_fail_percent(25)
+        .unwrap()
+        .with_tasks(vec!["load".to_string()]);
+
+    assert!(policy.should_fail("load", 0.99));
+    assert!(policy.should_fail("other", 0.1));
+    assert!(!policy.should_fail("other", 0.25));
+    assert!(!policy.should_fail("other", 0.9));
+
+    let never = ChaosPolicy::new(1);
+    assert!(!never.should_fail("other", 0.0));
+}
+
+#[test]
+fn with_chaos_fails_instead_of_running() {
+    let policy = ChaosPolicy::new(3).with_tasks(vec!["load".to_string()]);
+    let strategy = with_chaos(Some(policy), succeed);
+
+    let failed = strategy("load", &mut Command::new("true"), &TaskLimits::default());
+    assert_eq!(failed.return_code, 3);
+    assert_eq!(failed.stdout, None);
+    assert_eq!(failed.stderr,
+               Some("chaos mode failed the task 'load' with the exit code 3".to_string()));
+
+    assert_eq!(strategy("report", &mut Command::new("true"), &TaskLimits::default()).stdout,
+               Some("ran".to_string()));
+    let unchanged = with_chaos(None, succeed);
+    assert_eq!(unchanged("load", &mut Command::new("true"), &TaskLimits::default()).return_code,
+               0);
+}
@@ -0,0 +1,111 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Factfile;
+use factotum::factfile::Task as FactfileTask;
+use factotum::history::RunRecord;
+use std::collections::HashMap;
+
+pub const SKIP_REASON_PREFIX: &'static str = "circuit breaker open";
+
+struct TaskHistory {
+    // the outcome (true if it failed) of each run the task actually ran in, newest first
+    outcomes: Vec<bool>,
+    // how many of the most recent runs skipped the task because its breaker was open
+    recent_breaker_skips: u32,
+}
+
+fn get_task_history(task_name: &str, runs: &Vec<RunRecord>) -> TaskHistory {
+    let mut history = TaskHistory {
+        outcomes: vec![],
+        recent_breaker_skips: 0,
+    };
+
+    for run in runs.iter().rev() {
+        if let Some(task) = run.task(task_name) {
+            match task.state.as_ref() {
+                "FAILED" => history.outcomes.push(true),
+                "SUCCEEDED" | "SUCCEEDED_NO_OP" => history.outcomes.push(false),
+                "SKIPPED" => {
+                    let breaker_skip = task.error_message
+                        .as_ref()
+                        .map(|m| m.starts_with(SKIP_REASON_PREFIX))
+                        .unwrap_or(false);
+                    if breaker_skip && history.outcomes.is_empty() {
+                        history.recent_breaker_skips += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    history
+}
+
+// returns why the task should be skipped (if it should be)
+pub fn check_task(task: &FactfileTask, runs: &Vec<RunRecord>) -> Option<String> {
+    let history = get_task_history(&task.name, runs);
+    let mut trips = vec![];
+
+    if let Some(failed_last) = task.circuit_breaker.skip_if_failed_last {
+        let n = failed_last as usize;
+        if history.outcomes.len() >= n && history.outcomes[..n].iter().all(|failed| *failed) {
+            trips.push((failed_last,
+                        format!("{} - the task failed in each of its last {} runs",
+                                SKIP_REASON_PREFIX,
+                                failed_last)));
+        }
+    }
+
+    if let Some(ref rate) = task.circuit_breaker.skip_if_failure_rate {
+        let n = rate.over_last as usize;
+        if history.outcomes.len() >= n {
+            let failures = history.outcomes[..n].iter().filter(|failed| **failed).count();
+            if failures as f64 / n as f64 >= rate.threshold {
+                trips.push((rate.over_last,
+                            format!("{} - the task failed in {} of its last {} runs",
+                                    SKIP_REASON_PREFIX,
+                                    failures,
+                                    rate.over_last)));
+            }
+        }
+    }
+
+    // once a task has been skipped for as many runs as the breaker looks back over,
+    // let it run again to see if it has recovered
+    let probe_after = trips.iter().map(|&(n, _)| n).min();
+
+    match probe_after {
+        Some(n) if history.recent_breaker_skips >= n => {
+            info!("Letting task '{}' run after {} skipped runs to check if it has recovered",
+                  task.name,
+                  history.recent_breaker_skips);
+            None
+        }
+        Some(_) => trips.into_iter().next().map(|(_, reason)| reason),
+        None => None,
+    }
+}
+
+pub fn get_open_circuits(factfile: &Factfile, runs: &Vec<RunRecord>) -> HashMap<String, String> {
+    factfile.get_tasks_in_order()
+        .iter()
+        .flat_map(|grp| grp.iter())
+        .filter_map(|task| check_task(task, runs).map(|reason| (task.name.clone(), reason)))
+        .collect()
+}
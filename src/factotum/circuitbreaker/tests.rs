@@ -0,0 +1,134 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::{CircuitBreaker, FailureRate};
+use factotum::history::{RunRecord, TaskRecord};
+use factotum::tests::make_task;
+
+fn make_runs(task_name: &str, states: Vec<&str>) -> Vec<RunRecord> {
+    states.iter()
+        .enumerate()
+        .map(|(idx, state)| {
+            let error_message = if *state == "BREAKER" {
+                Some(format!("{} - the task failed", SKIP_REASON_PREFIX))
+            } else {
+                None
+            };
+            RunRecord {
+                job_name: "my job".to_string(),
+                run_reference: idx.to_string(),
+                start_time: "".to_string(),
+                run_state: "".to_string(),
+                source: None,
+                tasks: vec![TaskRecord {
+                                task_name: task_name.to_string(),
+                                state: if *state == "BREAKER" { "SKIPPED" } else { *state }
+                                    .to_string(),
+                                error_message: error_message,
+                                depends_on: vec![],
+                                started: None,
+                                duration_millis: None,
+                                cache_key: None,
+                            }],
+            }
+        })
+        .collect()
+}
+
+fn failed_last_task(n: u32) -> FactfileTask {
+    let mut task = make_task("flaky", &vec![]);
+    task.circuit_breaker = CircuitBreaker {
+        skip_if_failed_last: Some(n),
+        skip_if_failure_rate: None,
+    };
+    task
+}
+
+#[test]
+fn no_breaker_never_skips() {
+    let task = make_task("flaky", &vec![]);
+    let runs = make_runs("flaky", vec!["FAILED", "FAILED", "FAILED"]);
+    assert_eq!(check_task(&task, &runs), None);
+}
+
+#[test]
+fn failed_last_trips_after_consecutive_failures() {
+    let task = failed_last_task(2);
+
+    assert_eq!(check_task(&task, &make_runs("flaky", vec!["FAILED"])), None);
+    assert_eq!(check_task(&task, &make_runs("flaky", vec!["FAILED", "SUCCEEDED"])),
+               None);
+    assert_eq!(check_task(&task,
+                          &make_runs("flaky", vec!["SUCCEEDED", "FAILED", "SKIPPED", "FAILED"])),
+               Some("circuit breaker open - the task failed in each of its last 2 runs"
+                   .to_string()));
+}
+
+#[test]
+fn open_breaker_lets_task_run_again_after_skipping() {
+    let task = failed_last_task(2);
+
+    assert!(check_task(&task, &make_runs("flaky", vec!["FAILED", "FAILED", "BREAKER"]))
+        .is_some());
+    assert_eq!(check_task(&task,
+                          &make_runs("flaky", vec!["FAILED", "FAILED", "BREAKER", "BREAKER"])),
+               None);
+}
+
+#[test]
+fn failure_rate_trips_at_threshold() {
+    let mut task = make_task("flaky", &vec![]);
+    task.circuit_breaker = CircuitBreaker {
+        skip_if_failed_last: None,
+        skip_if_failure_rate: Some(FailureRate {
+            threshold: 0.5,
+            over_last: 4,
+        }),
+    };
+
+    assert_eq!(check_task(&task,
+                          &make_runs("flaky", vec!["FAILED", "FAILED", "SUCCEEDED"])),
+               None);
+    assert_eq!(check_task(&task,
+                          &make_runs("flaky",
+                                     vec!["FAILED", "SUCCEEDED", "FAILED", "SUCCEEDED"])),
+               Some("circuit breaker open - the task failed in 2 of its last 4 runs".to_string()));
+    assert_eq!(check_task(&task,
+                          &make_runs("flaky",
+                                     vec!["FAILED", "SUCCEEDED", "SUCCEEDED", "SUCCEEDED"])),
+               None);
+}
+
+#[test]
+fn get_open_circuits_good() {
+    let mut ff = Factfile::new("N/A", "my job");
+    ff.add_task_obj(&failed_last_task(1)).unwrap();
+    ff.add_task_obj(&make_task("stable", &vec![])).unwrap();
+
+    let mut runs = make_runs("flaky", vec!["FAILED"]);
+    runs[0].tasks.push(TaskRecord {
+        task_name: "stable".to_string(),
+        state: "FAILED".to_string(),
+        error_message: None,
+        depends_on: vec![],
+        started: None,
+        duration_millis: None,
+        cache_key: None,
+    });
+
+    let open = get_open_circuits(&ff, &runs);
+    assert_eq!(open.len(), 1);
+    assert!(open.contains_key("flaky"));
+}
@@ -0,0 +1,222 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::execution_strategy::TaskOutput;
+use chrono::UTC;
+use rustc_serialize::json::{self, Json, ToJson};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::process::Command;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const MAX_BATCH_SIZE: usize = 100;
+pub const FLUSH_INTERVAL_SECS: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEvent {
+    pub timestamp: i64,
+    pub message: String,
+}
+
+impl ToJson for LogEvent {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("timestamp".to_string(), self.timestamp.to_json());
+        d.insert("message".to_string(), self.message.to_json());
+        Json::Object(d)
+    }
+}
+
+pub trait LogsClient {
+    fn create_log_stream(&self, group: &str, stream: &str) -> Result<(), String>;
+    fn put_log_events(&self,
+                      group: &str,
+                      stream: &str,
+                      events: &Vec<LogEvent>)
+                      -> Result<(), String>;
+}
+
+pub struct AwsCliLogsClient;
+
+impl AwsCliLogsClient {
+    fn run(args: Vec<&str>) -> Result<String, String> {
+        let output = try!(Command::new("aws")
+            .arg("logs")
+            .args(&args)
+            .output()
+            .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        if output.status.success() {
+            Ok(stderr)
+        } else {
+            Err(stderr)
+        }
+    }
+}
+
+impl LogsClient for AwsCliLogsClient {
+    fn create_log_stream(&self, group: &str, stream: &str) -> Result<(), String> {
+        match AwsCliLogsClient::run(vec!["create-log-stream",
+                                         "--log-group-name",
+                                         group,
+                                         "--log-stream-name",
+                                         stream]) {
+            Ok(_) => Ok(()),
+            Err(ref msg) if msg.contains("ResourceAlreadyExistsException") => Ok(()),
+            Err(msg) => Err(msg),
+        }
+    }
+
+    fn put_log_events(&self,
+                      group: &str,
+                      stream: &str,
+                      events: &Vec<LogEvent>)
+                      -> Result<(), String> {
+        let events_json = try!(json::encode(&events.to_json()).map_err(|e| e.to_string()));
+        AwsCliLogsClient::run(vec!["put-log-events",
+                                   "--log-group-name",
+                                   group,
+                                   "--log-stream-name",
+                                   stream,
+                                   "--log-events",
+                                   &events_json])
+            .map(|_| ())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudWatchResult {
+    pub lines_received: u32,
+    pub lines_sent: u32,
+    pub failures: Vec<String>,
+}
+
+pub fn stream_name(run_reference: &str, task_name: &str) -> String {
+    // ':' and '*' aren't allowed in log stream names
+    let safe_task_name = task_name.replace(":", "_").replace("*", "_");
+    format!("{}/{}", run_reference, safe_task_name)
+}
+
+pub fn now_millis() -> i64 {
+    let now = UTC::now();
+    now.timestamp() * 1000 + (now.timestamp_subsec_millis() as i64)
+}
+
+struct LogStreams<C: LogsClient> {
+    client: C,
+    group: String,
+    created: HashSet<String>,
+    pending: HashMap<String, Vec<LogEvent>>,
+    result: CloudWatchResult,
+}
+
+impl<C: LogsClient> LogStreams<C> {
+    fn flush(&mut self, stream: &str) {
+        let events = match self.pending.remove(stream) {
+            Some(e) => e,
+            None => return,
+        };
+
+        if !self.created.contains(stream) {
+            if let Err(msg) = self.client.create_log_stream(&self.group, stream) {
+                warn!("Failed to create CloudWatch log stream '{}': {}", stream, msg);
+                self.result.failures.push(msg);
+                return;
+            }
+            self.created.insert(stream.to_string());
+        }
+
+        match self.client.put_log_events(&self.group, stream, &events) {
+            Ok(_) => self.result.lines_sent += events.len() as u32,
+            Err(msg) => {
+                warn!("Failed to send {} lines to CloudWatch log stream '{}': {}",
+                      events.len(),
+                      stream,
+                      msg);
+                self.result.failures.push(msg);
+            }
+        }
+    }
+
+    fn flush_all(&mut self) {
+        let mut streams = self.pending.keys().cloned().collect::<Vec<String>>();
+        streams.sort();
+        for stream in streams {
+            self.flush(&stream);
+        }
+    }
+}
+
+pub fn connect_cloudwatch<C>(group: String,
+                             run_reference: String,
+                             output_channel: Receiver<TaskOutput>,
+                             client: C)
+                             -> JoinHandle<CloudWatchResult>
+    where C: LogsClient + Send + 'static
+{
+    thread::spawn(move || {
+        let mut streams = LogStreams {
+            client: client,
+            group: group,
+            created: HashSet::new(),
+            pending: HashMap::new(),
+            result: CloudWatchResult {
+                lines_received: 0,
+                lines_sent: 0,
+                failures: vec![],
+            },
+        };
+        let mut last_flush = Instant::now();
+
+        loop {
+            match output_channel.recv_timeout(Duration::from_secs(1)) {
+                Ok(output) => {
+                    streams.result.lines_received += 1;
+                    let stream = stream_name(&run_reference, &output.task_name);
+                    let batch_full = {
+                        let batch = streams.pending.entry(stream.clone()).or_insert(vec![]);
+                        batch.push(LogEvent {
+                            timestamp: now_millis(),
+                            // empty messages are rejected by CloudWatch
+                            message: if output.line.is_empty() {
+                                " ".to_string()
+                            } else {
+                                output.line
+                            },
+                        });
+                        batch.len() >= MAX_BATCH_SIZE
+                    };
+                    if batch_full {
+                        streams.flush(&stream);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if last_flush.elapsed() >= Duration::from_secs(FLUSH_INTERVAL_SECS) {
+                streams.flush_all();
+                last_flush = Instant::now();
+            }
+        }
+
+        streams.flush_all();
+        streams.result
+    })
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::execution_strategy::{TaskOutput, OutputStream};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+
+#[derive(Clone)]
+struct MockLogsClient {
+    calls: Arc<Mutex<Vec<String>>>,
+    fail_puts: bool,
+}
+
+impl LogsClient for MockLogsClient {
+    fn create_log_stream(&self, group: &str, stream: &str) -> Result<(), String> {
+        self.calls.lock().unwrap().push(format!("create {} {}", group, stream));
+        Ok(())
+    }
+
+    fn put_log_events(&self,
+                      group: &str,
+                      stream: &str,
+                      events: &Vec<LogEvent>)
+                      -> Result<(), String> {
+        let messages = events.iter().map(|e| e.message.clone()).collect::<Vec<String>>();
+        self.calls.lock().unwrap().push(format!("put {} {} {}", group, stream, messages.join("|")));
+        if self.fail_puts {
+            Err("AccessDenied".to_string())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+fn line(task: &str, text: &str) -> TaskOutput {
+    TaskOutput {
+        task_name: task.to_string(),
+        stream: OutputStream::Stdout,
+        line: text.to_string(),
+    }
+}
+
+#[test]
+fn stream_name_is_per_run_and_task() {
+    assert_eq!(stream_name("abc", "load: events*"), "abc/load_ events_");
+}
+
+#[test]
+fn log_event_to_json() {
+    let event = LogEvent {
+        timestamp: 1000,
+        message: "hello".to_string(),
+    };
+    assert_eq!(json::encode(&event.to_json()).unwrap(),
+               "{\"message\":\"hello\",\"timestamp\":1000}");
+}
+
+#[test]
+fn lines_are_flushed_per_stream_on_finish() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let client = MockLogsClient {
+        calls: calls.clone(),
+        fail_puts: false,
+    };
+    let (tx, rx) = mpsc::sync_channel::<TaskOutput>(10);
+    let handle = connect_cloudwatch("group".to_string(), "run".to_string(), rx, client);
+
+    tx.send(line("a", "one")).unwrap();
+    tx.send(line("b", "")).unwrap();
+    tx.send(line("a", "two")).unwrap();
+    drop(tx);
+
+    let result = handle.join().unwrap();
+
+    assert_eq!(result,
+               CloudWatchResult {
+                   lines_received: 3,
+                   lines_sent: 3,
+                   failures: vec![],
+               });
+    assert_eq!(*calls.lock().unwrap(),
+               vec!["create group run/a",
+                    "put group run/a one|two",
+                    "create group run/b",
+                    "put group run/b  "]);
+}
+
+#[test]
+fn full_batches_are_sent_immediately() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let client = MockLogsClient {
+        calls: calls.clone(),
+        fail_puts: false,
+    };
+    let (tx, rx) = mpsc::sync_channel::<TaskOutput>(MAX_BATCH_SIZE + 1);
+    let handle = connect_cloudwatch("group".to_string(), "run".to_string(), rx, client);
+
+    for i in 0..MAX_BATCH_SIZE + 1 {
+        tx.send(line("a", &i.to_string())).unwrap();
+    }
+    drop(tx);
+
+    let result = handle.join().unwrap();
+    assert_eq!(result.lines_sent, MAX_BATCH_SIZE as u32 + 1);
+
+    let recorded = calls.lock().unwrap();
+    assert_eq!(recorded.len(), 3);
+    assert_eq!(recorded[2], format!("put group run/a {}", MAX_BATCH_SIZE));
+}
+
+#[test]
+fn failures_are_recorded() {
+    let calls = Arc::new(Mutex::new(vec![]));
+    let client = MockLogsClient {
+        calls: calls.clone(),
+        fail_puts: true,
+    };
+    let (tx, rx) = mpsc::sync_channel::<TaskOutput>(10);
+    let handle = connect_cloudwatch("group".to_string(), "run".to_string(), rx, client);
+
+    tx.send(line("a", "one")).unwrap();
+    drop(tx);
+
+    let result = handle.join().unwrap();
+    assert_eq!(result.lines_received, 1);
+    assert_eq!(result.lines_sent, 0);
+    assert_eq!(result.failures, vec!["AccessDenied".to_string()]);
+}
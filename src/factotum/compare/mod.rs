@@ -0,0 +1,139 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::history::{RunRecord, TaskRecord};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskComparison {
+    pub task_name: String,
+    pub state_before: Option<String>,
+    pub state_after: Option<String>,
+    pub duration_before: Option<u64>,
+    pub duration_after: Option<u64>,
+}
+
+impl TaskComparison {
+    pub fn duration_delta(&self) -> Option<i64> {
+        match (self.duration_before, self.duration_after) {
+            (Some(before), Some(after)) => Some(after as i64 - before as i64),
+            _ => None,
+        }
+    }
+
+    pub fn state_changed(&self) -> bool {
+        self.state_before != self.state_after
+    }
+}
+
+// tasks are listed in the order of the later run, followed by any only the earlier run had
+pub fn compare_runs(before: &RunRecord, after: &RunRecord) -> Vec<TaskComparison> {
+    let compare = |name: &str, b: Option<&TaskRecord>, a: Option<&TaskRecord>| {
+        TaskComparison {
+            task_name: name.to_string(),
+            state_before: b.map(|t| t.state.clone()),
+            state_after: a.map(|t| t.state.clone()),
+            duration_before: b.and_then(|t| t.duration_millis),
+            duration_after: a.and_then(|t| t.duration_millis),
+        }
+    };
+
+    let mut comparisons = after.tasks
+        .iter()
+        .map(|a| compare(&a.task_name, before.task(&a.task_name), Some(a)))
+        .collect::<Vec<TaskComparison>>();
+    for b in before.tasks.iter().filter(|b| after.task(&b.task_name).is_none()) {
+        comparisons.push(compare(&b.task_name, Some(b), None));
+    }
+    comparisons
+}
+
+fn format_millis(millis: Option<u64>) -> String {
+    match millis {
+        Some(m) => format!("{:.1}s", m as f64 / 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+fn format_delta(comparison: &TaskComparison) -> String {
+    match (comparison.duration_delta(), comparison.duration_before) {
+        (Some(delta), Some(before)) => {
+            let sign = if delta < 0 { "-" } else { "+" };
+            let seconds = format!("{}{:.1}s", sign, delta.abs() as f64 / 1000.0);
+            if before > 0 {
+                format!("{} ({}{:.0}%)",
+                        seconds,
+                        sign,
+                        delta.abs() as f64 * 100.0 / before as f64)
+            } else {
+                seconds
+            }
+        }
+        _ => "".to_string(),
+    }
+}
+
+fn format_state(comparison: &TaskComparison) -> String {
+    let name = |s: &Option<String>| s.clone().unwrap_or("NOT RUN".to_string());
+    if comparison.state_changed() {
+        format!("{} -> {}",
+                name(&comparison.state_before),
+                name(&comparison.state_after))
+    } else {
+        name(&comparison.state_after)
+    }
+}
+
+pub fn format_comparison(comparisons: &Vec<TaskComparison>) -> String {
+    let rows = comparisons.iter()
+        .map(|c| {
+            vec![c.task_name.clone(),
+                 format_millis(c.duration_before),
+                 format_millis(c.duration_after),
+                 format_delta(c),
+                 format_state(c)]
+        })
+        .collect::<Vec<Vec<String>>>();
+    let header = vec!["TASK", "BEFORE", "AFTER", "CHANGE", "STATE"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect::<Vec<String>>();
+
+    let widths = (0..header.len())
+        .map(|col| {
+            rows.iter()
+                .map(|r| r[col].len())
+                .chain(Some(header[col].len()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<usize>>();
+
+    Some(&header)
+        .into_iter()
+        .chain(rows.iter())
+        .map(|r| {
+            r.iter()
+                .enumerate()
+                .map(|(col, value)| format!("{:width$}", value, width = widths[col]))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
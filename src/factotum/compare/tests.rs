@@ -0,0 +1,88 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::history::{RunRecord, TaskRecord};
+
+fn task(name: &str, state: &str, duration: Option<u64>) -> TaskRecord {
+    TaskRecord {
+        task_name: name.to_string(),
+        state: state.to_string(),
+        error_message: None,
+        depends_on: vec![],
+        started: None,
+        duration_millis: duration,
+        cache_key: None,
+    }
+}
+
+fn run(reference: &str, tasks: Vec<TaskRecord>) -> RunRecord {
+    RunRecord {
+        job_name: "job".to_string(),
+        run_reference: reference.to_string(),
+        start_time: "".to_string(),
+        run_state: "SUCCEEDED".to_string(),
+        source: None,
+        tasks: tasks,
+    }
+}
+
+#[test]
+fn compare_runs_matches_tasks_by_name() {
+    let before = run("a",
+                     vec![task("extract", "SUCCEEDED", Some(2000)),
+                          task("load", "SUCCEEDED", Some(4000)),
+                          task("old", "SUCCEEDED", Some(100))]);
+    let after = run("b",
+                    vec![task("extract", "SUCCEEDED", Some(3000)),
+                         task("load", "FAILED", Some(1000)),
+                         task("new", "SKIPPED", None)]);
+
+    let comparisons = compare_runs(&before, &after);
+
+    assert_eq!(comparisons.iter().map(|c| c.task_name.as_ref()).collect::<Vec<&str>>(),
+               vec!["extract", "load", "new", "old"]);
+    assert_eq!(comparisons[0].duration_delta(), Some(1000));
+    assert!(!comparisons[0].state_changed());
+    assert_eq!(comparisons[1].duration_delta(), Some(-3000));
+    assert!(comparisons[1].state_changed());
+    assert_eq!(comparisons[2],
+               TaskComparison {
+                   task_name: "new".to_string(),
+                   state_before: None,
+                   state_after: Some("SKIPPED".to_string()),
+                   duration_before: None,
+                   duration_after: None,
+               });
+    assert_eq!(comparisons[3].duration_delta(), None);
+}
+
+#[test]
+fn format_comparison_good() {
+    let before = run("a",
+                     vec![task("extract", "SUCCEEDED", Some(2000)),
+                          task("load", "SUCCEEDED", Some(4000)),
+                          task("old", "SUCCEEDED", Some(100))]);
+    let after = run("b",
+                    vec![task("extract", "SUCCEEDED", Some(3000)),
+                         task("load", "FAILED", Some(1000))]);
+
+    let expected = vec!["TASK     BEFORE  AFTER  CHANGE        STATE",
+                        "extract  2.0s    3.0s   +1.0s (+50%)  SUCCEEDED",
+                        "load     4.0s    1.0s   -3.0s (-75%)  SUCCEEDED -> FAILED",
+                        "old      0.1s    -                    SUCCEEDED -> NOT RUN"];
+
+    assert_eq!(format_comparison(&compare_runs(&before, &after)),
+               expected.join("\n"));
+}
@@ -0,0 +1,208 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+pub const SHELLS: &'static [&'static str] = &["bash", "zsh", "fish"];
+
+// options that take a task name, completed from the factfile given on the command line
+pub const TASK_OPTIONS: &'static [&'static str] = &["--start", "--only"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandUsage {
+    pub name: String,
+    pub takes_factfile: bool,
+    pub options: Vec<(String, bool)>,
+}
+
+// the subcommands and their options, read from the docopt usage so the
+// completions can't drift from what's accepted
+pub fn parse_usage(usage: &str) -> Vec<CommandUsage> {
+    let mut commands: Vec<CommandUsage> = vec![];
+
+    for line in usage.lines() {
+        let mut words = line.trim().split_whitespace();
+        if words.next() != Some("factotum") {
+            continue;
+        }
+        let name = match words.next() {
+            Some(name) if !name.starts_with('(') => name.to_string(),
+            _ => continue,
+        };
+        let rest = words.collect::<Vec<&str>>();
+
+        let mut options = vec![];
+        for word in rest.iter() {
+            let option = word.trim_start_matches('[').trim_end_matches("...").trim_end_matches(']');
+            if !option.starts_with("--") {
+                continue;
+            }
+            let (option_name, takes_value) = match option.find('=') {
+                Some(idx) => (&option[..idx], true),
+                None => (option, false),
+            };
+            if option_name != "--no-colour" &&
+               !options.iter().any(|&(ref o, _): &(String, bool)| o == option_name) {
+                options.push((option_name.to_string(), takes_value));
+            }
+        }
+
+        commands.push(CommandUsage {
+            name: name,
+            takes_factfile: rest.contains(&"<factfile>"),
+            options: options,
+        });
+    }
+
+    commands
+}
+
+fn option_words(command: &CommandUsage) -> String {
+    command.options
+        .iter()
+        .map(|&(ref name, takes_value)| if takes_value { format!("{}=", name) } else { name.clone() })
+        .chain(Some("--no-colour".to_string()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn bash_script(commands: &Vec<CommandUsage>) -> String {
+    let names = commands.iter().map(|c| c.name.clone()).collect::<Vec<String>>().join(" ");
+    let cases = commands.iter()
+        .map(|c| format!("        {}) opts=\"{}\" ;;\n", c.name, option_words(c)))
+        .collect::<String>();
+    let task_options = TASK_OPTIONS.join("|");
+
+    format!(r#"# factotum completions for bash
+_factotum_tasks() {{
+    if [ -f "$1" ]; then
+        factotum list-tasks "$1" --format=names 2>/dev/null
+    fi
+}}
+
+_factotum() {{
+    local cur prev cmd factfile opts option
+    cur="${{COMP_WORDS[COMP_CWORD]}}"
+    prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    cmd="${{COMP_WORDS[1]}}"
+    factfile="${{COMP_WORDS[2]}}"
+
+    if [ "$COMP_CWORD" -eq 1 ]; then
+        COMPREPLY=( $(compgen -W "{names}" -- "$cur") )
+        return
+    fi
+
+    # "=" is a word break, so --start=<task> arrives as three words
+    if [ "$cur" = "=" ]; then
+        option="$prev"
+        cur=""
+    elif [ "$prev" = "=" ]; then
+        option="${{COMP_WORDS[COMP_CWORD-2]}}"
+    fi
+    case "$option" in
+        {task_options})
+            local IFS=$'\n'
+            COMPREPLY=( $(compgen -W "$(_factotum_tasks "$factfile")" -- "$cur") )
+            return
+            ;;
+    esac
+
+    if [ "$cmd" = "explain" ] && [ "$COMP_CWORD" -eq 3 ]; then
+        local IFS=$'\n'
+        COMPREPLY=( $(compgen -W "$(_factotum_tasks "$factfile")" -- "$cur") )
+        return
+    fi
+
+    case "$cmd" in
+{cases}        *) opts="" ;;
+    esac
+
+    if [[ "$cur" == -* ]]; then
+        COMPREPLY=( $(compgen -W "$opts" -- "$cur") )
+        if [[ "${{COMPREPLY[0]}}" == *= ]]; then
+            compopt -o nospace
+        fi
+    else
+        COMPREPLY=( $(compgen -f -- "$cur") )
+    fi
+}}
+
+complete -F _factotum factotum
+"#,
+            names = names,
+            task_options = task_options,
+            cases = cases)
+}
+
+fn zsh_script(commands: &Vec<CommandUsage>) -> String {
+    format!("#compdef factotum\n# factotum completions for zsh, using zsh's support for bash \
+             completions\nautoload -U +X bashcompinit && bashcompinit\n\n{}",
+            bash_script(commands).replacen("# factotum completions for bash\n", "", 1))
+}
+
+fn fish_script(commands: &Vec<CommandUsage>) -> String {
+    let names = commands.iter().map(|c| c.name.clone()).collect::<Vec<String>>().join(" ");
+    let mut lines = vec!["# factotum completions for fish".to_string(),
+                         "function __factotum_tasks".to_string(),
+                         "    set -l tokens (commandline -opc)".to_string(),
+                         "    if test (count $tokens) -ge 3; and test -f $tokens[3]".to_string(),
+                         "        factotum list-tasks $tokens[3] --format=names 2>/dev/null"
+                             .to_string(),
+                         "    end".to_string(),
+                         "end".to_string(),
+                         "".to_string(),
+                         "complete -c factotum -f".to_string(),
+                         format!("complete -c factotum -n __fish_use_subcommand -a \"{}\"", names),
+                         "complete -c factotum -n \"__fish_seen_subcommand_from explain; and test \
+                          (count (commandline -opc)) -eq 3\" -a \"(__factotum_tasks)\""
+                             .to_string()];
+
+    for command in commands.iter() {
+        let condition = format!("-n \"__fish_seen_subcommand_from {}\"", command.name);
+        if command.takes_factfile {
+            lines.push(format!("complete -c factotum {} -F", condition));
+        }
+        for &(ref option, takes_value) in command.options.iter() {
+            let long = &option[2..];
+            let line = if TASK_OPTIONS.contains(&option.as_str()) {
+                format!("complete -c factotum {} -l {} -x -a \"(__factotum_tasks)\"",
+                        condition,
+                        long)
+            } else if takes_value {
+                format!("complete -c factotum {} -l {} -r", condition, long)
+            } else {
+                format!("complete -c factotum {} -l {}", condition, long)
+            };
+            lines.push(line);
+        }
+        lines.push(format!("complete -c factotum {} -l no-colour", condition));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+pub fn script(shell: &str, usage: &str) -> Result<String, String> {
+    let commands = parse_usage(usage);
+    match shell {
+        "bash" => Ok(bash_script(&commands)),
+        "zsh" => Ok(zsh_script(&commands)),
+        "fish" => Ok(fish_script(&commands)),
+        other => {
+            Err(format!("can't generate completions for '{}', use one of {}",
+                        other,
+                        SHELLS.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+
+const USAGE: &'static str = "
+Usage:
+  factotum run <factfile> [--start=<start_task>] [--var=<var>]... [--dry-run] [--no-colour] [--only=<task>]...
+  factotum wait <condition> [--timeout=<timeout>] [--no-colour]
+  factotum explain <factfile> <task> [--env=<env>] [--no-colour]
+  factotum (-h | --help) [--no-colour]
+
+Options:
+  --start=<start_task>                  Begin at specified task.
+";
+
+#[test]
+fn parse_usage_reads_commands() {
+    let commands = parse_usage(USAGE);
+
+    assert_eq!(commands.len(), 3);
+    assert_eq!(commands[0],
+               CommandUsage {
+                   name: "run".to_string(),
+                   takes_factfile: true,
+                   options: vec![("--start".to_string(), true),
+                                 ("--var".to_string(), true),
+                                 ("--dry-run".to_string(), false),
+                                 ("--only".to_string(), true)],
+               });
+    assert!(!commands[1].takes_factfile);
+    assert_eq!(commands[2].name, "explain");
+}
+
+#[test]
+fn bash_script_completes_tasks() {
+    let bash = script("bash", USAGE).unwrap();
+
+    assert!(bash.contains("compgen -W \"run wait explain\""));
+    assert!(bash.contains("        --start|--only)\n"));
+    assert!(bash.contains("        run) opts=\"--start= --var= --dry-run --only= --no-colour\" ;;\n"));
+    assert!(bash.contains("factotum list-tasks \"$1\" --format=names"));
+    assert!(bash.ends_with("complete -F _factotum factotum\n"));
+
+    let zsh = script("zsh", USAGE).unwrap();
+    assert!(zsh.starts_with("#compdef factotum\n"));
+    assert!(zsh.contains("bashcompinit"));
+}
+
+#[test]
+fn fish_script_completes_tasks() {
+    let fish = script("fish", USAGE).unwrap();
+
+    assert!(fish.contains("complete -c factotum -n __fish_use_subcommand -a \"run wait explain\"\n"));
+    assert!(fish.contains("complete -c factotum -n \"__fish_seen_subcommand_from run\" -l start \
+                           -x -a \"(__factotum_tasks)\"\n"));
+    assert!(fish.contains("complete -c factotum -n \"__fish_seen_subcommand_from wait\" -l \
+                           timeout -r\n"));
+    assert!(fish.contains("complete -c factotum -n \"__fish_seen_subcommand_from run\" -l dry-run\n"));
+    assert!(!fish.contains("__fish_seen_subcommand_from wait\" -F"));
+}
+
+#[test]
+fn script_unknown_shell() {
+    assert_eq!(script("powershell", USAGE),
+               Err("can't generate completions for 'powershell', use one of bash, zsh, fish"
+                   .to_string()));
+}
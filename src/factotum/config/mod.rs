@@ -0,0 +1,229 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::Decodable;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use toml;
+
+// settings that fill in for command line options that weren't given
+#[derive(RustcDecodable, Debug, PartialEq, Clone, Default)]
+pub struct Settings {
+    pub webhook: Option<String>,
+    pub alert_webhook: Option<String>,
+    pub archive: Option<String>,
+    pub log_dir: Option<String>,
+    pub parallelism: Option<usize>,
+    pub constraints: Option<Vec<String>>,
+    pub tags: Option<Vec<String>>,
+    pub max_stdouterr_size: Option<usize>,
+    pub spill_threshold: Option<usize>,
+    pub spill_dir: Option<String>,
+    pub policy: Option<String>,
+    pub workspace_dir: Option<String>,
+    pub workspace_retention: Option<String>,
+    pub require_signature: Option<bool>,
+    pub signer: Option<String>,
+    pub audit_log: Option<String>,
+    pub audit_redact: Option<Vec<String>>,
+    pub heartbeat: Option<String>,
+    pub heartbeat_webhook: Option<String>,
+    pub history_store: Option<String>,
+    pub history_keep: Option<String>,
+    pub history_max_runs: Option<usize>,
+}
+
+impl Settings {
+    // a profile's settings, with anything it doesn't set taken from the defaults
+    fn or(self, defaults: Settings) -> Settings {
+        Settings {
+            webhook: self.webhook.or(defaults.webhook),
+            alert_webhook: self.alert_webhook.or(defaults.alert_webhook),
+            archive: self.archive.or(defaults.archive),
+            log_dir: self.log_dir.or(defaults.log_dir),
+            parallelism: self.parallelism.or(defaults.parallelism),
+            constraints: self.constraints.or(defaults.constraints),
+            tags: self.tags.or(defaults.tags),
+            max_stdouterr_size: self.max_stdouterr_size.or(defaults.max_stdouterr_size),
+            spill_threshold: self.spill_threshold.or(defaults.spill_threshold),
+            spill_dir: self.spill_dir.or(defaults.spill_dir),
+            policy: self.policy.or(defaults.policy),
+            workspace_dir: self.workspace_dir.or(defaults.workspace_dir),
+            workspace_retention: self.workspace_retention.or(defaults.workspace_retention),
+            require_signature: self.require_signature.or(defaults.require_signature),
+            signer: self.signer.or(defaults.signer),
+            audit_log: self.audit_log.or(defaults.audit_log),
+            audit_redact: self.audit_redact.or(defaults.audit_redact),
+            heartbeat: self.heartbeat.or(defaults.heartbeat),
+            heartbeat_webhook: self.heartbeat_webhook.or(defaults.heartbeat_webhook),
+            history_store: self.history_store.or(defaults.history_store),
+            history_keep: self.history_keep.or(defaults.history_keep),
+            history_max_runs: self.history_max_runs.or(defaults.history_max_runs),
+        }
+    }
+}
+
+// a database the sql executor can run statements against, e.g.
+// [connections.warehouse] driver = "postgres", host = "db.internal", user = "loader",
+// password = "env:WAREHOUSE_PASSWORD"
+#[derive(RustcDecodable, Debug, PartialEq, Clone, Default)]
+pub struct Connection {
+    pub driver: String,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub database: Option<String>,
+    pub schema: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub account: Option<String>,
+    pub warehouse: Option<String>,
+    pub role: Option<String>,
+    pub dsn: Option<String>,
+}
+
+#[derive(RustcDecodable)]
+struct ConfigFile {
+    defaults: Option<Settings>,
+    profiles: Option<BTreeMap<String, Settings>>,
+    connections: Option<BTreeMap<String, Connection>>,
+    executors: Option<BTreeMap<String, Vec<String>>>,
+}
+
+// set so the factotum processes a run starts (e.g. for sql tasks) read the same config file
+pub const CONFIG_VAR: &'static str = "FACTOTUM_CONFIG";
+
+// $FACTOTUM_CONFIG, ~/.config/factotum/config.toml, or under $XDG_CONFIG_HOME if it's set
+pub fn default_path() -> Option<PathBuf> {
+    if let Ok(path) = env::var(CONFIG_VAR) {
+        if !path.is_empty() {
+            return Some(PathBuf::from(path));
+        }
+    }
+    let config_home = match env::var("XDG_CONFIG_HOME") {
+        Ok(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+        _ => env::home_dir().map(|home| home.join(".config")),
+    };
+    config_home.map(|dir| dir.join("factotum").join("config.toml"))
+}
+
+fn decode_config(contents: &str) -> Result<ConfigFile, String> {
+    let mut parser = toml::Parser::new(contents);
+    let table = match parser.parse() {
+        Some(table) => table,
+        None => {
+            let problems = parser.errors
+                .iter()
+                .map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{} at line {} column {}", e.desc, line + 1, col + 1)
+                })
+                .collect::<Vec<String>>();
+            return Err(format!("it isn't valid TOML ({})", problems.join(", ")));
+        }
+    };
+
+    let mut decoder = toml::Decoder::new(toml::Value::Table(table));
+    let config = try!(ConfigFile::decode(&mut decoder).map_err(|e| format!("{}", e)));
+    if let Some(toml::Value::Table(ref leftover)) = decoder.toml {
+        if !leftover.is_empty() {
+            return Err(format!("it has unknown settings: {}",
+                               leftover.keys().cloned().collect::<Vec<String>>().join(", ")));
+        }
+    }
+    Ok(config)
+}
+
+pub fn parse_config(contents: &str, profile: Option<&str>) -> Result<Settings, String> {
+    let config = try!(decode_config(contents));
+
+    let defaults = config.defaults.unwrap_or(Settings::default());
+    match profile {
+        Some(name) => {
+            match config.profiles.as_ref().and_then(|p| p.get(name)) {
+                Some(settings) => Ok(settings.clone().or(defaults)),
+                None => Err(format!("it has no profile called '{}'", name)),
+            }
+        }
+        None => Ok(defaults),
+    }
+}
+
+// a missing config file is only a problem if a profile was asked for
+pub fn load_config(path: &Path, profile: Option<&str>) -> Result<Settings, String> {
+    if !path.exists() {
+        return match profile {
+            Some(name) => {
+                Err(format!("the profile '{}' can't be used as there's no config file at '{}'",
+                            name,
+                            path.display()))
+            }
+            None => Ok(Settings::default()),
+        };
+    }
+
+    let mut contents = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+
+    parse_config(&contents, profile)
+        .map_err(|e| format!("the config file '{}' can't be used: {}", path.display(), e))
+}
+
+pub fn parse_connections(contents: &str) -> Result<BTreeMap<String, Connection>, String> {
+    decode_config(contents).map(|config| config.connections.unwrap_or(BTreeMap::new()))
+}
+
+pub fn load_connection(path: &Path, name: &str) -> Result<Connection, String> {
+    let mut contents = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+
+    let connections = try!(parse_connections(&contents)
+        .map_err(|e| format!("the config file '{}' can't be used: {}", path.display(), e)));
+    connections.get(name)
+        .cloned()
+        .ok_or(format!("the config file '{}' has no connection called '{}'",
+                       path.display(),
+                       name))
+}
+
+// arguments every task using an executor is given, e.g. [executors] spark = ["master=yarn"]
+pub type ExecutorSettings = BTreeMap<String, Vec<String>>;
+
+pub fn parse_executor_settings(contents: &str) -> Result<ExecutorSettings, String> {
+    decode_config(contents).map(|config| config.executors.unwrap_or(BTreeMap::new()))
+}
+
+// there are none without a config file
+pub fn load_executor_settings(path: Option<&Path>) -> Result<ExecutorSettings, String> {
+    let path = match path {
+        Some(path) if path.exists() => path,
+        _ => return Ok(BTreeMap::new()),
+    };
+
+    let mut contents = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+    parse_executor_settings(&contents)
+        .map_err(|e| format!("the config file '{}' can't be used: {}", path.display(), e))
+}
@@ -0,0 +1,180 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::env;
+use std::fs;
+use std::io::Write;
+
+const CONFIG: &'static str = r#"
+[defaults]
+log_dir = "/var/log/factotum"
+parallelism = 2
+tags = ["team,data"]
+
+[profiles.prod]
+webhook = "https://hooks.acme.com/factotum"
+parallelism = 4
+constraints = ["host,*"]
+"#;
+
+#[test]
+fn parse_config_defaults() {
+    let settings = parse_config(CONFIG, None).unwrap();
+
+    assert_eq!(settings,
+               Settings {
+                   log_dir: Some("/var/log/factotum".to_string()),
+                   parallelism: Some(2),
+                   tags: Some(vec!["team,data".to_string()]),
+                   ..Settings::default()
+               });
+    assert_eq!(parse_config("", None), Ok(Settings::default()));
+}
+
+#[test]
+fn parse_config_profile_overrides_defaults() {
+    let settings = parse_config(CONFIG, Some("prod")).unwrap();
+
+    assert_eq!(settings,
+               Settings {
+                   webhook: Some("https://hooks.acme.com/factotum".to_string()),
+                   log_dir: Some("/var/log/factotum".to_string()),
+                   parallelism: Some(4),
+                   constraints: Some(vec!["host,*".to_string()]),
+                   tags: Some(vec!["team,data".to_string()]),
+                   ..Settings::default()
+               });
+}
+
+#[test]
+fn parse_config_spill_settings() {
+    let settings = parse_config("[defaults]\nspill_threshold = 1048576\n\n[profiles.small]\n\
+                                 spill_dir = \"/mnt/scratch\"\n",
+                                Some("small"))
+        .unwrap();
+
+    assert_eq!(settings.spill_threshold, Some(1048576));
+    assert_eq!(settings.spill_dir, Some("/mnt/scratch".to_string()));
+}
+
+#[test]
+fn parse_config_workspace_settings() {
+    let settings = parse_config("[defaults]\nworkspace_dir = \"/mnt/scratch\"\n\n                                 [profiles.debug]\nworkspace_retention = \"keep\"\n",
+                                Some("debug"))
+        .unwrap();
+
+    assert_eq!(settings.workspace_dir, Some("/mnt/scratch".to_string()));
+    assert_eq!(settings.workspace_retention, Some("keep".to_string()));
+}
+
+#[test]
+fn parse_config_signature_settings() {
+    let settings = parse_config("[defaults]\nrequire_signature = true\n\n[profiles.prod]\n\
+                                 signer = \"release@acme.com\"\n",
+                                Some("prod"))
+        .unwrap();
+
+    assert_eq!(settings.require_signature, Some(true));
+    assert_eq!(settings.signer, Some("release@acme.com".to_string()));
+}
+
+#[test]
+fn parse_config_audit_settings() {
+    let settings = parse_config("[defaults]\naudit_log = \"/var/log/factotum/audit.jsonl\"\n\
+                                 audit_redact = [\"DB_URL\"]\n",
+                                None)
+        .unwrap();
+
+    assert_eq!(settings.audit_log,
+               Some("/var/log/factotum/audit.jsonl".to_string()));
+    assert_eq!(settings.audit_redact, Some(vec!["DB_URL".to_string()]));
+}
+
+#[test]
+fn parse_config_bad() {
+    assert_eq!(parse_config(CONFIG, Some("staging")),
+               Err("it has no profile called 'staging'".to_string()));
+    assert!(parse_config("[defaults\n", None).unwrap_err().starts_with("it isn't valid TOML ("));
+    assert_eq!(parse_config("[defaults]\nparallelism = \"two\"\n", None).is_err(),
+               true);
+    assert_eq!(parse_config("[default]\nparallelism = 2\n", None),
+               Err("it has unknown settings: default".to_string()));
+}
+
+#[test]
+fn load_config_missing_file() {
+    let mut path = env::temp_dir();
+    path.push("factotum-config-test-missing.toml");
+
+    assert_eq!(load_config(&path, None), Ok(Settings::default()));
+    assert_eq!(load_config(&path, Some("prod")),
+               Err(format!("the profile 'prod' can't be used as there's no config file at '{}'",
+                           path.display())));
+}
+
+#[test]
+fn load_config_reads_file() {
+    let mut path = env::temp_dir();
+    path.push("factotum-config-test.toml");
+    fs::File::create(&path).unwrap().write_all(CONFIG.as_bytes()).unwrap();
+
+    assert_eq!(load_config(&path, Some("prod")).unwrap().parallelism, Some(4));
+    assert!(fs::remove_file(&path).is_ok());
+}
+
+#[test]
+fn parse_connections_reads_named_connections() {
+    let config = format!("{}\n[connections.warehouse]\ndriver = \"postgres\"\nhost = \
+                          \"db.internal\"\nport = 5439\npassword = \"env:PW\"\n",
+                         CONFIG);
+    let connections = parse_connections(&config).unwrap();
+
+    assert_eq!(connections.get("warehouse"),
+               Some(&Connection {
+                   driver: "postgres".to_string(),
+                   host: Some("db.internal".to_string()),
+                   port: Some(5439),
+                   password: Some("env:PW".to_string()),
+                   ..Connection::default()
+               }));
+    assert_eq!(parse_connections(CONFIG).map(|c| c.len()), Ok(0));
+    assert!(parse_connections("[connections.warehouse]\nhost = \"db\"\n").is_err());
+}
+
+#[test]
+fn load_connection_needs_the_connection() {
+    let path = env::temp_dir().join("factotum-connections-test.toml");
+    fs::File::create(&path)
+        .unwrap()
+        .write_all(b"[connections.warehouse]\ndriver = \"snowflake\"\naccount = \"acme\"\n")
+        .unwrap();
+
+    assert_eq!(load_connection(&path, "warehouse").map(|c| c.account),
+               Ok(Some("acme".to_string())));
+    assert_eq!(load_connection(&path, "lake"),
+               Err(format!("the config file '{}' has no connection called 'lake'",
+                           path.display())));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn executor_settings_are_read_from_the_config() {
+    let settings = parse_executor_settings("[executors]\nspark = [\"master=yarn\"]\n").unwrap();
+    assert_eq!(settings.get("spark"), Some(&vec!["master=yarn".to_string()]));
+    assert_eq!(parse_executor_settings(CONFIG).map(|s| s.len()), Ok(0));
+    assert_eq!(load_executor_settings(Some(Path::new("/no/such/config.toml"))).map(|s| s.len()),
+               Ok(0));
+    assert_eq!(load_executor_settings(None).map(|s| s.len()), Ok(0));
+}
@@ -0,0 +1,169 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use colored::*;
+use factotum::executor::{ExecutionState, ExecutionUpdate, TaskTransition, Transition};
+use factotum::executor::execution_strategy::{OutputStream, RunResult, TaskLimits, TaskOutput};
+use factotum::executor::task_list::State;
+use log::LogLevelFilter;
+use std::io::{self, Write};
+use std::process::Command;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+// how task output is printed as a job runs, rather than all together once it ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    // each line as the task writes it
+    Stream,
+    // all of a task's output in one piece when it finishes
+    Group,
+}
+
+// how much is printed as a job runs: -q only prints what failed, -v follows each task as
+// it starts and finishes, and -vv adds factotum's debug log, templating included
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+    Debug,
+}
+
+impl Default for Verbosity {
+    fn default() -> Verbosity {
+        Verbosity::Normal
+    }
+}
+
+impl Verbosity {
+    pub fn log_level(&self) -> LogLevelFilter {
+        match *self {
+            Verbosity::Debug => LogLevelFilter::Debug,
+            _ => LogLevelFilter::Info,
+        }
+    }
+}
+
+pub fn verbosity(quiet: bool, verbose: usize) -> Verbosity {
+    match (quiet, verbose) {
+        (true, _) => Verbosity::Quiet,
+        (false, 0) => Verbosity::Normal,
+        (false, 1) => Verbosity::Verbose,
+        (false, _) => Verbosity::Debug,
+    }
+}
+
+const TASK_COLOURS: [Color; 6] = [Color::Cyan,
+                                  Color::Magenta,
+                                  Color::Yellow,
+                                  Color::Blue,
+                                  Color::Green,
+                                  Color::BrightRed];
+
+// a task keeps its colour from run to run
+pub fn task_colour(task_name: &str) -> Color {
+    let sum = task_name.bytes().fold(0usize, |sum, b| sum.wrapping_add(b as usize));
+    TASK_COLOURS[sum % TASK_COLOURS.len()]
+}
+
+// colour is left to the colored crate, so --no-colour and NO_COLOR turn it off
+pub fn prefixed(task_name: &str, line: &str) -> String {
+    format!("{} {}",
+            format!("[{}]", task_name).color(task_colour(task_name)),
+            line)
+}
+
+fn print_line(output: &TaskOutput) {
+    let line = prefixed(&output.task_name, &output.line);
+    match output.stream {
+        OutputStream::Stdout => writeln!(io::stdout(), "{}", line).ok(),
+        OutputStream::Stderr => writeln!(io::stderr(), "{}", line).ok(),
+    };
+}
+
+// prints lines until the job's output channel closes, returning how many were printed
+pub fn connect_console(output_channel: Receiver<TaskOutput>) -> JoinHandle<u32> {
+    thread::spawn(move || {
+        let mut printed = 0;
+        for output in output_channel.iter() {
+            print_line(&output);
+            printed += 1;
+        }
+        printed
+    })
+}
+
+fn print_prefixed<W: Write>(out: &mut W, task_name: &str, text: &Option<String>) {
+    if let Some(ref text) = *text {
+        for line in text.lines() {
+            writeln!(out, "{}", prefixed(task_name, line.trim_end_matches('\r'))).ok();
+        }
+    }
+}
+
+// wraps an execution strategy so a task's output is printed once it finishes, holding the
+// console so tasks finishing together don't interleave
+pub fn with_grouped_output<F>(group: bool, strategy: F)
+    -> impl Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+{
+    move |name: &str, command: &mut Command, limits: &TaskLimits| {
+        let result = strategy(name, command, limits);
+        if group {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            print_prefixed(&mut stdout, name, &result.stdout);
+            let stderr = io::stderr();
+            print_prefixed(&mut stderr.lock(), name, &result.stderr);
+        }
+        result
+    }
+}
+
+// a task becoming ready to run isn't worth a line of its own
+pub fn transition_line(transition: &TaskTransition) -> Option<String> {
+    let change = match transition.to_state {
+        State::Waiting => return None,
+        State::Running => "started".to_string(),
+        State::Success => "succeeded".green().to_string(),
+        State::SuccessNoop => "requested an early finish".green().to_string(),
+        State::Failed(ref reason) => format!("failed: {}", reason).red().to_string(),
+        State::Skipped(ref reason) => format!("skipped: {}", reason),
+    };
+    Some(prefixed(&transition.task_name, &change))
+}
+
+// prints each task's transitions until the job finishes, returning how many were printed
+pub fn connect_transitions(updates_channel: Receiver<ExecutionUpdate>) -> JoinHandle<u32> {
+    thread::spawn(move || {
+        let mut printed = 0;
+        for update in updates_channel.iter() {
+            if let Transition::Task(ref transitions) = update.transition {
+                for line in transitions.iter().filter_map(transition_line) {
+                    writeln!(io::stdout(), "{}", line).ok();
+                    printed += 1;
+                }
+            }
+            if update.execution_state == ExecutionState::Finished {
+                break;
+            }
+        }
+        printed
+    })
+}
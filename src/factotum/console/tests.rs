@@ -0,0 +1,103 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::execution_strategy::{execute_simulation, TaskLimits};
+use factotum::executor::JobTransition;
+use std::process::Command;
+use std::sync::mpsc;
+
+#[test]
+fn task_colours_are_stable() {
+    assert_eq!(task_colour("load"), task_colour("load"));
+    assert!(TASK_COLOURS.contains(&task_colour("extract")));
+    assert!(task_colour("a") != task_colour("b"));
+}
+
+#[test]
+fn lines_prefixed_with_the_task() {
+    let line = prefixed("load", "12 rows");
+    assert!(line.contains("[load]"));
+    assert!(line.ends_with(" 12 rows"));
+}
+
+#[test]
+fn grouped_output_prefixed() {
+    let mut out = vec![];
+    print_prefixed(&mut out, "load", &Some("one\r\ntwo".to_string()));
+    print_prefixed(&mut out, "load", &None);
+    assert_eq!(String::from_utf8(out).unwrap(),
+               format!("{}\n{}\n", prefixed("load", "one"), prefixed("load", "two")));
+}
+
+#[test]
+fn grouped_output_keeps_the_result() {
+    let strategy = with_grouped_output(true, execute_simulation);
+    let result = strategy("load", &mut Command::new("true"), &TaskLimits::default());
+    assert_eq!(result.return_code, 0);
+    assert!(result.stdout.unwrap().contains("load"));
+}
+
+#[test]
+fn verbosity_from_flags() {
+    assert_eq!(verbosity(true, 0), Verbosity::Quiet);
+    assert_eq!(verbosity(false, 0), Verbosity::Normal);
+    assert_eq!(verbosity(false, 1), Verbosity::Verbose);
+    assert_eq!(verbosity(false, 2), Verbosity::Debug);
+    assert_eq!(verbosity(false, 5), Verbosity::Debug);
+    assert!(Verbosity::Quiet < Verbosity::Normal && Verbosity::Verbose < Verbosity::Debug);
+}
+
+#[test]
+fn only_debug_logs_debug() {
+    assert_eq!(Verbosity::Quiet.log_level(), LogLevelFilter::Info);
+    assert_eq!(Verbosity::Verbose.log_level(), LogLevelFilter::Info);
+    assert_eq!(Verbosity::Debug.log_level(), LogLevelFilter::Debug);
+}
+
+#[test]
+fn transition_lines() {
+    let line = |to| transition_line(&TaskTransition::new("load", State::Running, to));
+    assert_eq!(transition_line(&TaskTransition::new("load", State::Waiting, State::Waiting)),
+               None);
+    assert_eq!(line(State::Running), Some(prefixed("load", "started")));
+    assert!(line(State::Success).unwrap().contains("succeeded"));
+    assert!(line(State::Failed("exit code 3".to_string()))
+        .unwrap()
+        .contains("failed: exit code 3"));
+    assert_eq!(line(State::Skipped("a dependency failed".to_string())),
+               Some(prefixed("load", "skipped: a dependency failed")));
+}
+
+#[test]
+fn transitions_printed_until_the_job_finishes() {
+    let (tx, rx) = mpsc::channel();
+    let handle = connect_transitions(rx);
+    let task = |from, to| TaskTransition::new("load", from, to);
+    tx.send(ExecutionUpdate::new(ExecutionState::Running,
+                                 vec![],
+                                 Transition::Task(vec![task(State::Waiting, State::Running),
+                                                       task(State::Waiting, State::Waiting)])))
+        .unwrap();
+    tx.send(ExecutionUpdate::new(ExecutionState::Running,
+                                 vec![],
+                                 Transition::Task(vec![task(State::Running, State::Success)])))
+        .unwrap();
+    tx.send(ExecutionUpdate::new(ExecutionState::Finished,
+                                 vec![],
+                                 Transition::Job(JobTransition::new(Some(ExecutionState::Running),
+                                                                    ExecutionState::Finished))))
+        .unwrap();
+    assert_eq!(handle.join().unwrap(), 2);
+}
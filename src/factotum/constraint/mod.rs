@@ -0,0 +1,286 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use chrono::{DateTime, UTC};
+use dns_lookup;
+use factotum::envfile;
+use factotum::parser;
+use factotum::timewindow::{self, TimeWindow};
+use ifaces;
+use libc;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::env;
+use std::net;
+use std::process::{Command, Stdio};
+
+pub const HOST: &'static str = "host";
+pub const WINDOW: &'static str = "window";
+pub const CHECK: &'static str = "check";
+
+// what has to be true for a job, or a single task, to run
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Constraints {
+    pub host: Option<String>,
+    pub window: Option<TimeWindow>,
+    // a shell command that has to exit with 0
+    pub check: Option<String>,
+}
+
+// the first of the constraints that isn't met, and why
+pub fn unmet(constraints: &Constraints, now: &DateTime<UTC>) -> Option<String> {
+    if let Some(ref host) = constraints.host {
+        if let Err(msg) = is_valid_host(host) {
+            return Some(format!("the host constraint '{}' isn't met: {}", host, msg));
+        }
+    }
+    if let Some(ref window) = constraints.window {
+        if let Err(msg) = timewindow::check_time_window(window, now) {
+            return Some(format!("the window constraint isn't met: {}", msg));
+        }
+    }
+    if let Some(ref check) = constraints.check {
+        if let Err(msg) = run_check(check) {
+            return Some(format!("the check constraint isn't met: {}", msg));
+        }
+    }
+    None
+}
+
+// the check's output isn't kept, only whether it succeeded
+pub fn run_check(check: &str) -> Result<(), String> {
+    let status = try!(Command::new("sh")
+        .arg("-c")
+        .arg(check)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("'{}' couldn't be run ({})", check, e)));
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(code) => Err(format!("'{}' exited with {}", check, code)),
+        None => Err(format!("'{}' was killed by a signal", check)),
+    }
+}
+
+pub const CONSUL_ADDR_VAR: &'static str = "CONSUL_HTTP_ADDR";
+pub const CONSUL_TOKEN_VAR: &'static str = "CONSUL_HTTP_TOKEN";
+pub const DEFAULT_CONSUL_ADDR: &'static str = "http://127.0.0.1:8500";
+
+// the local Consul agent's node
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConsulNode {
+    pub name: String,
+    pub meta: BTreeMap<String, String>,
+}
+
+// how a host constraint finds out about the host it's checked on
+pub trait HostLookup {
+    fn hostname(&self) -> Result<String, String>;
+    // whether the host's addresses are this host's own
+    fn resolves_here(&self, host: &str) -> Result<(), String>;
+    fn consul_node(&self) -> Result<ConsulNode, String>;
+}
+
+pub struct LocalHost;
+
+impl HostLookup for LocalHost {
+    fn hostname(&self) -> Result<String, String> {
+        gethostname_safe()
+    }
+
+    fn resolves_here(&self, host: &str) -> Result<(), String> {
+        let external_addrs = try!(get_external_addrs().map_err(|e| e.to_string()));
+        let host_addrs = try!(dns_lookup::lookup_host(&host)
+            .map_err(|_| "could not find any IPv4 addresses for the supplied hostname"));
+
+        for host_addr in host_addrs {
+            if let Ok(good_host_addr) = host_addr {
+                if external_addrs.iter()
+                    .any(|external_addr| external_addr.ip() == good_host_addr) {
+                    return Ok(());
+                }
+            }
+        }
+
+        Err("failed to match any of the interface addresses to the found host addresses".into())
+    }
+
+    fn consul_node(&self) -> Result<ConsulNode, String> {
+        let addr = env::var(CONSUL_ADDR_VAR).unwrap_or(DEFAULT_CONSUL_ADDR.to_string());
+        let addr = if addr.contains("://") {
+            addr
+        } else {
+            format!("http://{}", addr)
+        };
+        let token = env::var(CONSUL_TOKEN_VAR).ok().map(|t| format!("X-Consul-Token: {}", t));
+        let agent = try!(envfile::fetch_url(&format!("{}/v1/agent/self",
+                                                     addr.trim_right_matches('/')),
+                                            token.as_ref().map(|t| t.as_str()))
+            .map_err(|e| format!("the Consul agent couldn't be asked about its node: {}", e)));
+        parse_consul_node(&agent)
+    }
+}
+
+pub fn parse_consul_node(agent: &str) -> Result<ConsulNode, String> {
+    let agent = try!(Json::from_str(agent)
+        .map_err(|e| format!("the Consul agent's reply isn't JSON ({})", e)));
+    let name = try!(agent.find_path(&["Config", "NodeName"])
+        .and_then(|n| n.as_string())
+        .ok_or("the Consul agent didn't give its node name".to_string()));
+    let meta = agent.find("Meta")
+        .and_then(|m| m.as_object())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| v.as_string().map(|v| (k.clone(), v.to_string())))
+                .collect()
+        })
+        .unwrap_or(BTreeMap::new());
+    Ok(ConsulNode {
+        name: name.to_string(),
+        meta: meta,
+    })
+}
+
+pub fn is_valid_host(host: &str) -> Result<(), String> {
+    host_matches(host, &LocalHost)
+}
+
+// a host constraint is a comma separated list, any one of which will do: a name is the
+// hostname or resolves to one of the host's addresses, a glob such as etl-*.prod is matched
+// against the hostname and then the Consul node's name, and a <key>=<value> (where the value
+// can be a glob) is matched against the Consul node's metadata
+pub fn host_matches(constraint: &str, lookup: &dyn HostLookup) -> Result<(), String> {
+    let hosts = constraint.split(',')
+        .map(|h| h.trim())
+        .filter(|h| !h.is_empty())
+        .collect::<Vec<&str>>();
+    if hosts.is_empty() {
+        return Err("no hosts were given".to_string());
+    }
+
+    let hostname = lookup.hostname();
+    let mut consul_node = None;
+    let mut reasons = vec![];
+
+    for host in hosts {
+        if host == "*" {
+            return Ok(());
+        }
+
+        if let Some(idx) = host.find('=') {
+            let (key, value) = (host[..idx].trim(), host[idx + 1..].trim());
+            match *consul_node.get_or_insert_with(|| lookup.consul_node()) {
+                Ok(ref node) => {
+                    if node.meta.get(key).map_or(false, |v| parser::pattern_matches(value, v)) {
+                        return Ok(());
+                    }
+                    reasons.push(format!("the Consul node's {} isn't '{}'", key, value));
+                }
+                Err(_) => reasons.push(format!("the Consul node's {} couldn't be checked", key)),
+            }
+            continue;
+        }
+
+        match hostname {
+            Ok(ref name) if parser::pattern_matches(host, name) => return Ok(()),
+            Err(ref msg) => reasons.push(msg.clone()),
+            _ => (),
+        }
+
+        if parser::is_pattern(host) {
+            match *consul_node.get_or_insert_with(|| lookup.consul_node()) {
+                Ok(ref node) if parser::pattern_matches(host, &node.name) => return Ok(()),
+                Ok(_) => {
+                    reasons.push(format!("'{}' matches neither the hostname nor the Consul \
+                                          node's name",
+                                         host))
+                }
+                Err(_) => reasons.push(format!("'{}' doesn't match the hostname", host)),
+            }
+        } else if let Err(msg) = lookup.resolves_here(host) {
+            reasons.push(format!("'{}': {}", host, msg));
+        } else {
+            return Ok(());
+        }
+    }
+
+    // why Consul couldn't be asked is only given once
+    if let Some(Err(msg)) = consul_node {
+        reasons.push(msg);
+    }
+    reasons.dedup();
+    Err(reasons.join("; "))
+}
+
+extern "C" {
+    fn gethostname(name: *mut libc::c_char, size: libc::size_t) -> libc::c_int;
+}
+
+pub fn gethostname_safe() -> Result<String, String> {
+    let len = 255;
+    let mut buf = Vec::<u8>::with_capacity(len);
+
+    let ptr = buf.as_mut_slice().as_mut_ptr();
+
+    let err = unsafe { gethostname(ptr as *mut libc::c_char, len as libc::size_t) } as libc::c_int;
+
+    match err {
+        0 => {
+            let mut _real_len = len;
+            let mut i = 0;
+            loop {
+                let byte = unsafe { *(((ptr as u64) + (i as u64)) as *const u8) };
+                if byte == 0 {
+                    _real_len = i;
+                    break;
+                }
+                i += 1;
+            }
+            unsafe { buf.set_len(_real_len) }
+            Ok(String::from_utf8_lossy(buf.as_slice()).into_owned())
+        }
+        _ => {
+            Err("could not get hostname from system; cannot compare against supplied hostname"
+                .into())
+        }
+    }
+}
+
+pub fn get_external_addrs() -> Result<Vec<net::SocketAddr>, String> {
+    let mut external_addrs = vec![];
+
+    for iface in ifaces::Interface::get_all().unwrap().into_iter() {
+        if iface.kind == ifaces::Kind::Ipv4 {
+            if let Some(addr) = iface.addr {
+                if !addr.ip().is_loopback() {
+                    external_addrs.push(addr)
+                }
+            }
+        }
+    }
+
+    if external_addrs.len() == 0 {
+        Err("could not find any non-loopback IPv4 addresses in the network interfaces; do you \
+             have a working network interface card?"
+            .into())
+    } else {
+        Ok(external_addrs)
+    }
+}
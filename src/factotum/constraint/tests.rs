@@ -0,0 +1,170 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use chrono::TimeZone;
+
+#[test]
+fn test_gethostname_safe() {
+    let hostname = gethostname_safe();
+    if let Ok(ok_hostname) = hostname {
+        assert!(!ok_hostname.is_empty());
+    } else {
+        panic!("gethostname_safe() must return a Ok(<String>)");
+    }
+}
+
+#[test]
+fn test_get_external_addrs() {
+    let external_addrs = get_external_addrs();
+    if let Ok(ok_external_addrs) = external_addrs {
+        assert!(ok_external_addrs.len() > 0);
+    } else {
+        panic!("get_external_addrs() must return a Ok(Vec<net::SocketAddr>) that is non-empty");
+    }
+}
+
+#[test]
+fn test_is_valid_host() {
+    is_valid_host("*").expect("must be Ok() for wildcard");
+
+    // Test each external addr is_valid_host
+    let external_addrs = get_external_addrs()
+        .expect("get_external_addrs() must return a Ok(Vec<net::SocketAddr>) that is non-empty");
+    for external_addr in external_addrs {
+        let ip_str = external_addr.ip().to_string();
+        is_valid_host(&ip_str).expect(&format!("must be Ok() for IP {}", &ip_str));
+    }
+}
+
+#[test]
+fn nothing_unmet_without_constraints() {
+    assert_eq!(unmet(&Constraints::default(), &UTC::now()), None);
+}
+
+#[test]
+fn first_unmet_constraint_given() {
+    let now = UTC.ymd(2024, 3, 1).and_hms(12, 0, 0);
+    let outside = Constraints {
+        window: Some(timewindow::parse_time_window("22:00-06:00").unwrap()),
+        check: Some("exit 3".to_string()),
+        ..Constraints::default()
+    };
+    assert_eq!(unmet(&outside, &now),
+               Some("the window constraint isn't met: the time in UTC is 12:00, outside of the \
+                     allowed window 22:00-06:00"
+                   .to_string()));
+
+    let inside = Constraints {
+        host: Some("*".to_string()),
+        window: Some(timewindow::parse_time_window("09:00-17:00").unwrap()),
+        ..outside
+    };
+    assert_eq!(unmet(&inside, &now),
+               Some("the check constraint isn't met: 'exit 3' exited with 3".to_string()));
+}
+
+#[test]
+fn checks_pass_on_success() {
+    assert_eq!(run_check("true"), Ok(()));
+    assert_eq!(run_check("echo ignored; false"),
+               Err("'echo ignored; false' exited with 1".to_string()));
+}
+
+struct FakeHost {
+    consul_node: Result<ConsulNode, String>,
+}
+
+impl HostLookup for FakeHost {
+    fn hostname(&self) -> Result<String, String> {
+        Ok("etl-2.prod".to_string())
+    }
+
+    fn resolves_here(&self, host: &str) -> Result<(), String> {
+        if host == "10.0.0.2" {
+            Ok(())
+        } else {
+            Err("it resolves elsewhere".to_string())
+        }
+    }
+
+    fn consul_node(&self) -> Result<ConsulNode, String> {
+        self.consul_node.clone()
+    }
+}
+
+fn consul_host() -> FakeHost {
+    let mut meta = BTreeMap::new();
+    meta.insert("role".to_string(), "etl-batch".to_string());
+    FakeHost {
+        consul_node: Ok(ConsulNode {
+            name: "node-7".to_string(),
+            meta: meta,
+        }),
+    }
+}
+
+#[test]
+fn any_listed_host_will_do() {
+    let host = consul_host();
+    assert_eq!(host_matches("*", &host), Ok(()));
+    assert_eq!(host_matches("etl-1.prod, etl-2.prod", &host), Ok(()));
+    assert_eq!(host_matches("etl-1.prod,10.0.0.2", &host), Ok(()));
+    assert_eq!(host_matches("etl-1.prod", &host),
+               Err("'etl-1.prod': it resolves elsewhere".to_string()));
+    assert_eq!(host_matches(" , ", &host),
+               Err("no hosts were given".to_string()));
+}
+
+#[test]
+fn host_globs_match_the_hostname_or_consul_node() {
+    let host = consul_host();
+    assert_eq!(host_matches("etl-*.prod", &host), Ok(()));
+    assert_eq!(host_matches("web-*,node-?", &host), Ok(()));
+    assert_eq!(host_matches("web-*", &host),
+               Err("'web-*' matches neither the hostname nor the Consul node's name"
+                   .to_string()));
+
+    let no_consul = FakeHost { consul_node: Err("no agent".to_string()) };
+    assert_eq!(host_matches("etl-*", &no_consul), Ok(()));
+    assert_eq!(host_matches("web-*", &no_consul),
+               Err("'web-*' doesn't match the hostname; no agent".to_string()));
+}
+
+#[test]
+fn host_metadata_matched_in_consul() {
+    let host = consul_host();
+    assert_eq!(host_matches("role=etl-*", &host), Ok(()));
+    assert_eq!(host_matches("role=web,team=data", &host),
+               Err("the Consul node's role isn't 'web'; the Consul node's team isn't 'data'"
+                   .to_string()));
+    let no_consul = FakeHost { consul_node: Err("no agent".to_string()) };
+    assert_eq!(host_matches("role=etl-*,role=web,web-*", &no_consul),
+               Err("the Consul node's role couldn't be checked; 'web-*' doesn't match the \
+                    hostname; no agent"
+                   .to_string()));
+}
+
+#[test]
+fn consul_node_from_agent() {
+    let node = parse_consul_node(r#"{"Config": {"NodeName": "node-7", "Datacenter": "dc1"},
+                                      "Meta": {"role": "etl", "rack": "b"}}"#)
+        .unwrap();
+    assert_eq!(node.name, "node-7");
+    assert_eq!(node.meta.get("role"), Some(&"etl".to_string()));
+    assert_eq!(parse_consul_node(r#"{"Config": {}}"#),
+               Err("the Consul agent didn't give its node name".to_string()));
+    assert_eq!(parse_consul_node(r#"{"Config": {"NodeName": "n"}}"#).unwrap().meta,
+               BTreeMap::new());
+}
@@ -0,0 +1,273 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::interrupt;
+use libc;
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// a socket per run, named after its run reference, so `factotum cancel` can find it from
+// any directory on the host
+pub fn socket_path(run_reference: &str) -> PathBuf {
+    env::temp_dir().join("factotum-control").join(format!("{}.sock", run_reference))
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    CancelRun,
+    CancelTask(String),
+}
+
+pub fn encode_request(request: &Request) -> String {
+    match *request {
+        Request::CancelRun => "cancel\n".to_string(),
+        Request::CancelTask(ref task) => format!("cancel {}\n", task),
+    }
+}
+
+pub fn parse_request(line: &str) -> Result<Request, String> {
+    let line = line.trim();
+    if line == "cancel" {
+        Ok(Request::CancelRun)
+    } else if line.starts_with("cancel ") {
+        Ok(Request::CancelTask(line["cancel ".len()..].trim().to_string()))
+    } else {
+        Err(format!("'{}' isn't a request factotum understands", line))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Running {
+    pids: BTreeMap<String, u32>,
+    cancelled: BTreeSet<String>,
+}
+
+// the tasks of a run that have a process, so they can be cancelled while they run
+#[derive(Debug, Clone, Default)]
+pub struct RunningTasks {
+    running: Arc<Mutex<Running>>,
+}
+
+// the processes the task's process started, found with pgrep - a shell running a script
+// waits for its commands, so stopping just the shell would leave them running
+fn descendants(pid: u32) -> Vec<u32> {
+    let children = Command::new("pgrep")
+        .arg("-P")
+        .arg(pid.to_string())
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .filter_map(|l| l.trim().parse::<u32>().ok())
+                .collect::<Vec<u32>>()
+        })
+        .unwrap_or(vec![]);
+    let mut all = vec![];
+    for child in children {
+        all.push(child);
+        all.extend(descendants(child));
+    }
+    all
+}
+
+// the task's own process and everything it started, found before any of them is signalled -
+// once a shell's gone, the processes it started can't be found from it
+pub fn process_tree(pid: u32) -> Vec<u32> {
+    let mut tree = vec![pid];
+    tree.extend(descendants(pid));
+    tree
+}
+
+// the signal has to reach the task's own process (the first); the others may have gone already
+pub fn signal_tree(tree: &[u32], signal: libc::c_int) -> Result<(), String> {
+    for (i, pid) in tree.iter().enumerate() {
+        if unsafe { libc::kill(*pid as libc::pid_t, signal) } != 0 && i == 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+    }
+    Ok(())
+}
+
+// the whole process tree is sent SIGTERM, from the task's own process down
+fn terminate(pid: u32) -> Result<(), String> {
+    signal_tree(&process_tree(pid), libc::SIGTERM)
+}
+
+impl RunningTasks {
+    pub fn started(&self, task_name: &str, pid: u32) {
+        let mut running = self.running.lock().unwrap();
+        running.pids.insert(task_name.to_string(), pid);
+    }
+
+    // true if the task ended because it was cancelled
+    pub fn finished(&self, task_name: &str) -> bool {
+        let mut running = self.running.lock().unwrap();
+        running.pids.remove(task_name);
+        running.cancelled.remove(task_name)
+    }
+
+    pub fn cancel(&self, task_name: &str) -> Result<String, String> {
+        let mut running = self.running.lock().unwrap();
+        let pid = match running.pids.get(task_name) {
+            Some(pid) => *pid,
+            None => return Err(format!("the task '{}' isn't running", task_name)),
+        };
+        try!(terminate(pid)
+            .map_err(|e| format!("couldn't stop the task '{}' ({})", task_name, e)));
+        running.cancelled.insert(task_name.to_string());
+        Ok(format!("the task '{}' was cancelled", task_name))
+    }
+
+    // returns the tasks that were stopped
+    pub fn cancel_all(&self) -> Vec<String> {
+        let mut running = self.running.lock().unwrap();
+        let stopped = running.pids
+            .iter()
+            .filter(|&(name, pid)| match terminate(*pid) {
+                Ok(_) => true,
+                Err(e) => {
+                    warn!("couldn't stop the task '{}' ({})", name, e);
+                    false
+                }
+            })
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+        running.cancelled.extend(stopped.iter().cloned());
+        stopped
+    }
+}
+
+// cancelling the run stops its running tasks and aborts it as SIGTERM would, so no more tasks
+// start
+fn handle(request: &Request, running: &RunningTasks) -> Result<String, String> {
+    match *request {
+        Request::CancelTask(ref task_name) => running.cancel(task_name),
+        Request::CancelRun => {
+            interrupt::abort(libc::SIGTERM);
+            let stopped = running.cancel_all();
+            Ok(if stopped.is_empty() {
+                "the run was cancelled".to_string()
+            } else {
+                format!("the run was cancelled, stopping {}",
+                        stopped.iter()
+                            .map(|t| format!("'{}'", t))
+                            .collect::<Vec<String>>()
+                            .join(", "))
+            })
+        }
+    }
+}
+
+fn serve(stream: UnixStream, running: &RunningTasks) -> io::Result<()> {
+    try!(stream.set_nonblocking(false));
+    try!(stream.set_read_timeout(Some(Duration::from_secs(5))));
+    let mut line = String::new();
+    try!(BufReader::new(&stream).read_line(&mut line));
+    let reply = match parse_request(&line).and_then(|request| {
+        info!("Control request: {:?}", request);
+        handle(&request, running)
+    }) {
+        Ok(msg) => format!("ok {}\n", msg),
+        Err(msg) => format!("error {}\n", msg),
+    };
+    (&stream).write_all(reply.as_bytes())
+}
+
+pub struct ControlSocket {
+    path: PathBuf,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl ControlSocket {
+    pub fn close(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.handle.join().ok();
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+// the socket is only open to the user running the job
+pub fn listen(path: &Path, running: RunningTasks) -> Result<ControlSocket, String> {
+    if let Some(dir) = path.parent() {
+        try!(fs::create_dir_all(dir)
+            .map_err(|e| format!("couldn't create '{}' ({})", dir.display(), e)));
+    }
+    let listener = try!(UnixListener::bind(path)
+        .map_err(|e| format!("couldn't listen on '{}' ({})", path.display(), e)));
+    try!(fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .and_then(|_| listener.set_nonblocking(true))
+        .map_err(|e| format!("couldn't set up '{}' ({})", path.display(), e)));
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = stop.clone();
+    let handle = thread::spawn(move || {
+        while !thread_stop.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(e) = serve(stream, &running) {
+                        warn!("Couldn't answer a control request: {}", e);
+                    }
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(100))
+                }
+                Err(e) => {
+                    warn!("Couldn't accept a control request: {}", e);
+                    thread::sleep(Duration::from_millis(100))
+                }
+            }
+        }
+    });
+
+    Ok(ControlSocket {
+        path: path.to_path_buf(),
+        stop: stop,
+        handle: handle,
+    })
+}
+
+pub fn send(path: &Path, request: &Request) -> Result<String, String> {
+    let stream = try!(UnixStream::connect(path)
+        .map_err(|e| format!("couldn't reach the run at '{}' ({})", path.display(), e)));
+    try!((&stream)
+        .write_all(encode_request(request).as_bytes())
+        .map_err(|e| format!("couldn't send the request ({})", e)));
+    let mut reply = String::new();
+    try!(BufReader::new(&stream)
+        .read_line(&mut reply)
+        .map_err(|e| format!("couldn't read the reply ({})", e)));
+    let reply = reply.trim_end();
+    if reply.starts_with("ok ") {
+        Ok(reply["ok ".len()..].to_string())
+    } else if reply.starts_with("error ") {
+        Err(reply["error ".len()..].to_string())
+    } else {
+        Err(format!("the run sent an unexpected reply '{}'", reply))
+    }
+}
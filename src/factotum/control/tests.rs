@@ -0,0 +1,94 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::io::Read;
+use std::process::{Command, Stdio};
+
+#[test]
+fn requests_encoded_and_parsed() {
+    assert_eq!(encode_request(&Request::CancelRun), "cancel\n");
+    assert_eq!(encode_request(&Request::CancelTask("load".to_string())),
+               "cancel load\n");
+    assert_eq!(parse_request("cancel\n"), Ok(Request::CancelRun));
+    assert_eq!(parse_request("cancel load two\n"),
+               Ok(Request::CancelTask("load two".to_string())));
+    assert_eq!(parse_request("pause"),
+               Err("'pause' isn't a request factotum understands".to_string()));
+}
+
+#[test]
+fn running_task_cancelled() {
+    let running = RunningTasks::default();
+    let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+    running.started("load", child.id());
+
+    assert_eq!(running.cancel("other"),
+               Err("the task 'other' isn't running".to_string()));
+    assert_eq!(running.cancel("load"),
+               Ok("the task 'load' was cancelled".to_string()));
+    assert!(!child.wait().unwrap().success());
+    assert!(running.finished("load"));
+    assert_eq!(running.cancel("load"),
+               Err("the task 'load' isn't running".to_string()));
+}
+
+#[test]
+fn cancelling_a_shell_stops_its_commands() {
+    let running = RunningTasks::default();
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg("sleep 30; echo done")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(descendants(child.id()).len(), 1);
+    running.started("load", child.id());
+
+    assert!(running.cancel("load").is_ok());
+    // the pipe only closes once the sleep has gone too
+    let mut output = String::new();
+    child.stdout.take().unwrap().read_to_string(&mut output).unwrap();
+    assert_eq!(output, "");
+    assert!(!child.wait().unwrap().success());
+}
+
+#[test]
+fn finished_tasks_were_not_cancelled() {
+    let running = RunningTasks::default();
+    running.started("load", 1);
+    assert!(!running.finished("load"));
+    assert_eq!(running.cancel_all(), Vec::<String>::new());
+}
+
+#[test]
+fn requests_sent_over_the_socket() {
+    let path = socket_path("control-test");
+    fs::remove_file(&path).ok();
+    let running = RunningTasks::default();
+    let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+    running.started("load", child.id());
+
+    let socket = listen(&path, running.clone()).unwrap();
+    assert_eq!(send(&path, &Request::CancelTask("extract".to_string())),
+               Err("the task 'extract' isn't running".to_string()));
+    assert_eq!(send(&path, &Request::CancelTask("load".to_string())),
+               Ok("the task 'load' was cancelled".to_string()));
+    assert!(!child.wait().unwrap().success());
+    socket.close();
+
+    assert!(!path.exists());
+    assert!(send(&path, &Request::CancelRun).is_err());
+}
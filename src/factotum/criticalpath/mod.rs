@@ -0,0 +1,111 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::task_list::Task;
+use factotum::factfile::Task as FactfileTask;
+use factotum::history::RunRecord;
+use factotum::report;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedTask {
+    pub name: String,
+    pub depends_on: Vec<String>,
+    pub duration_millis: u64,
+}
+
+// tasks that never ran (skipped, or not reached) have no duration and can't be on the path
+pub fn from_tasks(tasks: &Vec<&Task<&FactfileTask>>) -> Vec<TimedTask> {
+    tasks.iter()
+        .filter_map(|t| {
+            t.run_result.as_ref().map(|r| {
+                TimedTask {
+                    name: t.name.clone(),
+                    depends_on: t.task_spec.depends_on.clone(),
+                    duration_millis: report::duration_millis(&r.duration),
+                }
+            })
+        })
+        .collect()
+}
+
+pub fn from_run_record(run: &RunRecord) -> Vec<TimedTask> {
+    run.tasks
+        .iter()
+        .filter_map(|t| {
+            t.duration_millis.map(|d| {
+                TimedTask {
+                    name: t.task_name.clone(),
+                    depends_on: t.depends_on.clone(),
+                    duration_millis: d,
+                }
+            })
+        })
+        .collect()
+}
+
+fn finish_time<'a>(task: &'a TimedTask,
+                   by_name: &HashMap<&'a str, &'a TimedTask>,
+                   finishes: &mut HashMap<&'a str, (u64, Option<&'a str>)>)
+                   -> u64 {
+    if let Some(&(finish, _)) = finishes.get(task.name.as_str()) {
+        return finish;
+    }
+
+    let mut slowest: Option<(&'a str, u64)> = None;
+    for dep in task.depends_on.iter() {
+        if let Some(dep_task) = by_name.get(dep.as_str()) {
+            let dep_finish = finish_time(dep_task, by_name, finishes);
+            if slowest.map(|(_, f)| dep_finish > f).unwrap_or(true) {
+                slowest = Some((dep_task.name.as_str(), dep_finish));
+            }
+        }
+    }
+
+    let finish = task.duration_millis + slowest.map(|(_, f)| f).unwrap_or(0);
+    finishes.insert(task.name.as_str(), (finish, slowest.map(|(n, _)| n)));
+    finish
+}
+
+// the chain of dependent tasks with the largest total duration, in the order they ran
+pub fn critical_path(tasks: &Vec<TimedTask>) -> Vec<TimedTask> {
+    let by_name = tasks.iter()
+        .map(|t| (t.name.as_str(), t))
+        .collect::<HashMap<&str, &TimedTask>>();
+    let mut finishes = HashMap::new();
+
+    let mut last: Option<(&str, u64)> = None;
+    for task in tasks.iter() {
+        let finish = finish_time(task, &by_name, &mut finishes);
+        if last.map(|(_, f)| finish > f).unwrap_or(true) {
+            last = Some((task.name.as_str(), finish));
+        }
+    }
+
+    let mut path = vec![];
+    let mut current = last.map(|(n, _)| n);
+    while let Some(name) = current {
+        path.push(by_name[name].clone());
+        current = finishes[name].1;
+    }
+    path.reverse();
+    path
+}
+
+pub fn total_millis(path: &Vec<TimedTask>) -> u64 {
+    path.iter().map(|t| t.duration_millis).sum()
+}
@@ -0,0 +1,103 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::task_list::{Task, State};
+use factotum::executor::execution_strategy::RunResult;
+use factotum::history::{RunRecord, TaskRecord};
+use factotum::tests::make_task;
+use std::time::Duration;
+
+fn timed(name: &str, depends_on: Vec<&str>, duration_millis: u64) -> TimedTask {
+    TimedTask {
+        name: name.to_string(),
+        depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+        duration_millis: duration_millis,
+    }
+}
+
+fn names(path: &Vec<TimedTask>) -> Vec<&str> {
+    path.iter().map(|t| t.name.as_str()).collect()
+}
+
+#[test]
+fn critical_path_follows_slowest_chain() {
+    //    a (1s)
+    //   /      \
+    // b (10s)  c (2s)
+    //   \      /
+    //    d (3s)     e (5s)
+    let tasks = vec![timed("a", vec![], 1000),
+                     timed("b", vec!["a"], 10000),
+                     timed("c", vec!["a"], 2000),
+                     timed("d", vec!["b", "c"], 3000),
+                     timed("e", vec![], 5000)];
+
+    let path = critical_path(&tasks);
+    assert_eq!(names(&path), vec!["a", "b", "d"]);
+    assert_eq!(total_millis(&path), 14000);
+}
+
+#[test]
+fn critical_path_ignores_tasks_that_didnt_run() {
+    let tasks = vec![timed("b", vec!["a"], 2000), timed("c", vec![], 1000)];
+
+    assert_eq!(names(&critical_path(&tasks)), vec!["b"]);
+    assert_eq!(critical_path(&vec![]), vec![]);
+}
+
+#[test]
+fn from_run_record_uses_recorded_durations() {
+    let task = |name: &str, depends_on: Vec<&str>, duration: Option<u64>| {
+        TaskRecord {
+            task_name: name.to_string(),
+            state: "SUCCEEDED".to_string(),
+            error_message: None,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            started: None,
+            duration_millis: duration,
+            cache_key: None,
+        }
+    };
+    let run = RunRecord {
+        job_name: "job".to_string(),
+        run_reference: "abc".to_string(),
+        start_time: "".to_string(),
+        run_state: "FAILED".to_string(),
+        source: None,
+        tasks: vec![task("a", vec![], Some(500)), task("b", vec!["a"], None)],
+    };
+
+    assert_eq!(from_run_record(&run), vec![timed("a", vec![], 500)]);
+}
+
+#[test]
+fn from_tasks_uses_run_results() {
+    let spec_a = make_task("a", &vec![]);
+    let spec_b = make_task("b", &vec!["a"]);
+    let mut a = Task::new("a", &spec_a);
+    a.state = State::Success;
+    a.run_result = Some(RunResult {
+        duration: Duration::from_millis(1500),
+        task_execution_error: None,
+        stdout: None,
+        stderr: None,
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+    });
+    let b = Task::new("b", &spec_b);
+
+    assert_eq!(from_tasks(&vec![&a, &b]), vec![timed("a", vec![], 1500)]);
+}
@@ -0,0 +1,226 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{self, Json, ToJson};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+pub const EXECUTOR: &'static str = "dbt";
+
+// the dbt commands that leave a run_results.json behind
+pub const DBT_COMMANDS: &'static [&'static str] = &["run", "build", "test", "seed", "snapshot"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DbtInvocation {
+    pub command: String,
+    pub project_dir: Option<String>,
+    pub profiles_dir: Option<String>,
+    pub profile: Option<String>,
+    pub target: Option<String>,
+    pub select: Vec<String>,
+    pub exclude: Vec<String>,
+    pub vars: BTreeMap<String, String>,
+}
+
+// a dbt task's command is the dbt command and its arguments its parameters, e.g.
+// [ "project-dir=analytics", "target=prod", "select=tag:nightly", "var=run_date=2021-01-01" ]
+pub fn parse_invocation(command: &str, params: &Vec<String>) -> Result<DbtInvocation, String> {
+    let command = command.trim();
+    if !DBT_COMMANDS.contains(&command) {
+        return Err(format!("'{}' isn't a dbt command factotum can run, it must be one of {}",
+                           command,
+                           DBT_COMMANDS.join(", ")));
+    }
+
+    let mut invocation = DbtInvocation {
+        command: command.to_string(),
+        project_dir: None,
+        profiles_dir: None,
+        profile: None,
+        target: None,
+        select: vec![],
+        exclude: vec![],
+        vars: BTreeMap::new(),
+    };
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value.to_string(),
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "project-dir" => invocation.project_dir = Some(value),
+            "profiles-dir" => invocation.profiles_dir = Some(value),
+            "profile" => invocation.profile = Some(value),
+            "target" => invocation.target = Some(value),
+            "select" => invocation.select.push(value),
+            "exclude" => invocation.exclude.push(value),
+            "var" => {
+                match value.find('=') {
+                    Some(i) if i > 0 => {
+                        invocation.vars.insert(value[..i].to_string(), value[i + 1..].to_string());
+                    }
+                    _ => return Err(format!("'{}' must be given as var=<name>=<value>", param)),
+                }
+            }
+            _ => {
+                return Err(format!("'{}' isn't a dbt parameter (expected project-dir, \
+                                    profiles-dir, profile, target, select, exclude or var)",
+                                   name))
+            }
+        }
+    }
+
+    Ok(invocation)
+}
+
+pub fn dbt_args(invocation: &DbtInvocation) -> Vec<String> {
+    let mut args = vec![invocation.command.clone()];
+    let mut flag = |name: &str, value: &Option<String>| {
+        if let Some(ref value) = *value {
+            args.push(format!("--{}", name));
+            args.push(value.clone());
+        }
+    };
+    flag("project-dir", &invocation.project_dir);
+    flag("profiles-dir", &invocation.profiles_dir);
+    flag("profile", &invocation.profile);
+    flag("target", &invocation.target);
+
+    if !invocation.select.is_empty() {
+        args.push("--select".to_string());
+        args.extend(invocation.select.iter().cloned());
+    }
+    if !invocation.exclude.is_empty() {
+        args.push("--exclude".to_string());
+        args.extend(invocation.exclude.iter().cloned());
+    }
+    if !invocation.vars.is_empty() {
+        // YAML's a superset of JSON, so this is what --vars expects
+        args.push("--vars".to_string());
+        args.push(json::encode(&invocation.vars).unwrap_or("{}".to_string()));
+    }
+    args
+}
+
+pub fn run_results_path(invocation: &DbtInvocation) -> PathBuf {
+    Path::new(invocation.project_dir.as_ref().map(|d| &d[..]).unwrap_or("."))
+        .join("target")
+        .join("run_results.json")
+}
+
+fn model_state(status: &str) -> &'static str {
+    match status {
+        "success" | "pass" | "warn" => "SUCCEEDED",
+        "skipped" => "SKIPPED",
+        _ => "FAILED",
+    }
+}
+
+// "model.jaffle_shop.orders" is reported as "orders"
+fn model_name(unique_id: &str) -> String {
+    unique_id.splitn(3, '.').nth(2).unwrap_or(unique_id).to_string()
+}
+
+// each node in a run_results.json as a task of the run's result, for the report to show
+pub fn model_results(run_results: &Json) -> Result<Vec<Json>, String> {
+    let results = try!(run_results.find("results")
+        .and_then(|r| r.as_array())
+        .ok_or("there are no results in the run results".to_string()));
+
+    Ok(results.iter()
+        .filter_map(|result| {
+            let unique_id = match result.find("unique_id").and_then(|u| u.as_string()) {
+                Some(unique_id) => unique_id,
+                None => return None,
+            };
+            let status = result.find("status").and_then(|s| s.as_string()).unwrap_or("");
+            let state = model_state(status);
+
+            let mut d = BTreeMap::new();
+            d.insert("taskName".to_string(), model_name(unique_id).to_json());
+            d.insert("state".to_string(), state.to_json());
+            d.insert("dependsOn".to_string(), Json::Array(vec![]));
+            d.insert("description".to_string(), unique_id.to_json());
+            if let Some(secs) = result.find("execution_time").and_then(|t| t.as_f64()) {
+                let millis = (secs * 1000.0) as i64;
+                d.insert("duration".to_string(),
+                         ChronoDuration::milliseconds(millis).to_string().to_json());
+                d.insert("durationMillis".to_string(), millis.to_json());
+            }
+            if state != "SUCCEEDED" {
+                if let Some(message) = result.find("message").and_then(|m| m.as_string()) {
+                    d.insert("errorMessage".to_string(), message.to_json());
+                }
+            }
+            Some(Json::Object(d))
+        })
+        .collect())
+}
+
+pub fn summarise(models: &Vec<Json>) -> String {
+    let count = |state: &str| {
+        models.iter()
+            .filter(|m| m.find("state").and_then(|s| s.as_string()) == Some(state))
+            .count()
+    };
+    format!("{} node{}: {} succeeded, {} failed, {} skipped",
+            models.len(),
+            if models.len() == 1 { "" } else { "s" },
+            count("SUCCEEDED"),
+            count("FAILED"),
+            count("SKIPPED"))
+}
+
+pub fn read_model_results(path: &Path) -> Result<Vec<Json>, String> {
+    let mut contents = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+    let run_results = try!(Json::from_str(&contents)
+        .map_err(|e| format!("'{}' isn't a dbt run results file ({})", path.display(), e)));
+    model_results(&run_results)
+}
+
+// leaves the models where the parent run picks up nested results
+pub fn write_model_results(path: &Path, models: &Vec<Json>) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        try!(fs::create_dir_all(parent)
+            .map_err(|e| format!("couldn't create '{}' ({})", parent.display(), e)));
+    }
+    let mut d = BTreeMap::new();
+    d.insert("tasks".to_string(), Json::Array(models.clone()));
+    let mut f = try!(File::create(path)
+        .map_err(|e| format!("couldn't create '{}' ({})", path.display(), e)));
+    f.write_all(Json::Object(d).pretty().to_string().as_bytes())
+        .map_err(|e| format!("couldn't write to '{}' ({})", path.display(), e))
+}
+
+// runs dbt with the task's output going straight to factotum, returning its exit code
+pub fn run_dbt(invocation: &DbtInvocation) -> Result<i32, String> {
+    let status = try!(Command::new("dbt")
+        .args(&dbt_args(invocation))
+        .status()
+        .map_err(|e| format!("couldn't run dbt ({})", e)));
+    status.code().ok_or("dbt was killed by a signal".to_string())
+}
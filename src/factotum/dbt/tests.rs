@@ -0,0 +1,130 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::env;
+use uuid::Uuid;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+const RUN_RESULTS: &'static str = r#"{"results": [
+    {"unique_id": "model.jaffle_shop.customers", "status": "success", "execution_time": 1.5},
+    {"unique_id": "model.jaffle_shop.orders", "status": "error", "execution_time": 0.25,
+     "message": "Database Error in model orders"},
+    {"unique_id": "test.jaffle_shop.not_null_orders_id.c2f4", "status": "skipped",
+     "message": null},
+    {"unique_id": "test.jaffle_shop.unique_customers_id.a1b2", "status": "warn"}
+]}"#;
+
+#[test]
+fn parse_invocation_good() {
+    let invocation = parse_invocation("run",
+                                      &params(vec!["project-dir=analytics",
+                                                   "target=prod",
+                                                   "select=tag:nightly",
+                                                   "select=orders+",
+                                                   "var=run_date=2021-01-01"]))
+        .unwrap();
+    assert_eq!(invocation.project_dir, Some("analytics".to_string()));
+    assert_eq!(invocation.target, Some("prod".to_string()));
+    assert_eq!(invocation.select, vec!["tag:nightly", "orders+"]);
+    assert_eq!(invocation.vars.get("run_date"), Some(&"2021-01-01".to_string()));
+}
+
+#[test]
+fn parse_invocation_bad() {
+    assert!(parse_invocation("compile", &vec![])
+        .unwrap_err()
+        .starts_with("'compile' isn't a dbt command factotum can run"));
+    assert_eq!(parse_invocation("run", &params(vec!["target"])),
+               Err("'target' must be given as <parameter>=<value>".to_string()));
+    assert_eq!(parse_invocation("run", &params(vec!["var=run_date"])),
+               Err("'var=run_date' must be given as var=<name>=<value>".to_string()));
+    assert!(parse_invocation("run", &params(vec!["models=orders"]))
+        .unwrap_err()
+        .starts_with("'models' isn't a dbt parameter"));
+}
+
+#[test]
+fn dbt_args_follow_the_parameters() {
+    let invocation = parse_invocation("build",
+                                      &params(vec!["profile=warehouse",
+                                                   "select=orders",
+                                                   "select=customers",
+                                                   "exclude=tag:slow",
+                                                   "var=region=eu"]))
+        .unwrap();
+    assert_eq!(dbt_args(&invocation),
+               vec!["build",
+                    "--profile",
+                    "warehouse",
+                    "--select",
+                    "orders",
+                    "customers",
+                    "--exclude",
+                    "tag:slow",
+                    "--vars",
+                    "{\"region\":\"eu\"}"]);
+    assert_eq!(dbt_args(&parse_invocation("test", &vec![]).unwrap()), vec!["test"]);
+}
+
+#[test]
+fn run_results_are_in_the_project_target() {
+    assert_eq!(run_results_path(&parse_invocation("run", &vec![]).unwrap()),
+               PathBuf::from("./target/run_results.json"));
+    assert_eq!(run_results_path(&parse_invocation("run", &params(vec!["project-dir=a"]))
+                   .unwrap()),
+               PathBuf::from("a/target/run_results.json"));
+}
+
+#[test]
+fn model_results_map_statuses_to_states() {
+    let models = model_results(&Json::from_str(RUN_RESULTS).unwrap()).unwrap();
+    let field = |i: usize, key: &str| models[i].find(key).map(|v| v.to_string());
+
+    assert_eq!(field(0, "taskName"), Some("\"customers\"".to_string()));
+    assert_eq!(field(0, "state"), Some("\"SUCCEEDED\"".to_string()));
+    assert_eq!(field(0, "durationMillis"), Some("1500".to_string()));
+    assert_eq!(field(0, "errorMessage"), None);
+    assert_eq!(field(1, "state"), Some("\"FAILED\"".to_string()));
+    assert_eq!(field(1, "errorMessage"),
+               Some("\"Database Error in model orders\"".to_string()));
+    assert_eq!(field(2, "taskName"), Some("\"not_null_orders_id.c2f4\"".to_string()));
+    assert_eq!(field(2, "state"), Some("\"SKIPPED\"".to_string()));
+    assert_eq!(field(3, "state"), Some("\"SUCCEEDED\"".to_string()));
+
+    assert_eq!(summarise(&models), "4 nodes: 2 succeeded, 1 failed, 1 skipped");
+    assert!(model_results(&Json::from_str("{}").unwrap()).is_err());
+}
+
+#[test]
+fn model_results_round_trip_through_a_result_file() {
+    let dir = env::temp_dir().join(format!("factotum-dbt-{}", Uuid::new_v4()));
+    let run_results = dir.join("run_results.json");
+    fs::create_dir_all(&dir).unwrap();
+    File::create(&run_results).unwrap().write_all(RUN_RESULTS.as_bytes()).unwrap();
+
+    let models = read_model_results(&run_results).unwrap();
+    let result_file = dir.join("nested").join("transform.json");
+    write_model_results(&result_file, &models).unwrap();
+
+    let mut contents = String::new();
+    File::open(&result_file).unwrap().read_to_string(&mut contents).unwrap();
+    let written = Json::from_str(&contents).unwrap();
+    assert_eq!(written.find("tasks").and_then(|t| t.as_array()).map(|t| t.len()),
+               Some(4));
+    fs::remove_dir_all(&dir).unwrap();
+}
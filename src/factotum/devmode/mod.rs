@@ -0,0 +1,67 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// the local files a factfile is built from; an env file fetched over https can't be watched
+pub fn watched_files(factfile: &str, env_file: Option<&str>) -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from(factfile)];
+    if let Some(env_file) = env_file {
+        if !env_file.starts_with("https://") {
+            files.push(PathBuf::from(env_file));
+        }
+    }
+    files
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub struct Watcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Watcher {
+    pub fn new(files: Vec<PathBuf>) -> Watcher {
+        Watcher {
+            files: files.into_iter()
+                .map(|f| {
+                    let stamp = modified(&f);
+                    (f, stamp)
+                })
+                .collect(),
+        }
+    }
+
+    // the files that have been written, created or removed since the last check
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for &mut (ref file, ref mut stamp) in self.files.iter_mut() {
+            let now = modified(file);
+            if now != *stamp {
+                *stamp = now;
+                changed.push(file.clone());
+            }
+        }
+        changed
+    }
+}
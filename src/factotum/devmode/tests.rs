@@ -0,0 +1,59 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn watched_files_skip_remote_env_files() {
+    assert_eq!(watched_files("job.factfile", None),
+               vec![PathBuf::from("job.factfile")]);
+    assert_eq!(watched_files("job.factfile", Some("env.json")),
+               vec![PathBuf::from("job.factfile"), PathBuf::from("env.json")]);
+    assert_eq!(watched_files("job.factfile", Some("https://example.com/env.json")),
+               vec![PathBuf::from("job.factfile")]);
+}
+
+#[test]
+fn watcher_sees_changed_files() {
+    let mut dir = env::temp_dir();
+    dir.push(format!("factotum-devmode-test-{:?}",
+                     SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap()));
+    fs::create_dir_all(&dir).unwrap();
+    let factfile = dir.join("job.factfile");
+    let env_file = dir.join("env.json");
+    File::create(&factfile).unwrap();
+
+    let mut watcher = Watcher::new(vec![factfile.clone(), env_file.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    File::create(&env_file).unwrap();
+    assert_eq!(watcher.changed(), vec![env_file.clone()]);
+    assert!(watcher.changed().is_empty());
+
+    // set the time explicitly, as the write can land within the file system's timestamp resolution
+    let mut f = fs::OpenOptions::new().append(true).open(&factfile).unwrap();
+    f.write_all(b"{}").unwrap();
+    f.set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+    assert_eq!(watcher.changed(), vec![factfile.clone()]);
+
+    fs::remove_file(&env_file).unwrap();
+    assert_eq!(watcher.changed(), vec![env_file.clone()]);
+
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
@@ -0,0 +1,211 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::sourcemap::{self, Position, SourceMap};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+
+pub const FORMAT_TABLE: &'static str = "table";
+pub const FORMAT_JSON_DIAGNOSTICS: &'static str = "json-diagnostics";
+
+const INVALID_FACTFILE: &'static str = "is not a valid factotum factfile: ";
+const LOCATION_PREFIX: &'static str = " (line ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub position: Position,
+    pub message: String,
+}
+
+impl ToJson for Diagnostic {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("line".to_string(), self.position.line.to_json());
+        d.insert("column".to_string(), self.position.column.to_json());
+        d.insert("severity".to_string(), "error".to_json());
+        d.insert("message".to_string(), self.message.to_json());
+        Json::Object(d)
+    }
+}
+
+// "at line 3, column 7", as rustc_serialize reports a syntax error
+fn syntax_error_position(problem: &str) -> Option<Position> {
+    let at = match problem.rfind(" at line ") {
+        Some(idx) => &problem[idx + " at line ".len()..],
+        None => return None,
+    };
+    parse_position(at)
+}
+
+// "3, column 7"
+fn parse_position(at: &str) -> Option<Position> {
+    let mut parts = at.split(", column ");
+    let line = parts.next().and_then(|l| l.trim().parse::<usize>().ok());
+    let column = parts.next().and_then(|c| c.trim().parse::<usize>().ok());
+    match (line, column) {
+        (Some(line), Some(column)) => {
+            Some(Position {
+                line: line,
+                column: column,
+            })
+        }
+        _ => None,
+    }
+}
+
+// the field a problem with a task is about, so it's placed there rather than at the task
+const TASK_FIELDS: [(&'static str, &'static str); 6] = [("conflicting actions", "onResult"),
+                                                        ("no way to continue",
+                                                         "onResult/continueJob"),
+                                                        ("depends on", "dependsOn"),
+                                                        ("expectedDuration",
+                                                         "expectedDuration"),
+                                                        ("executor", "executor"),
+                                                        ("forEach", "forEach")];
+
+// a task's named as it's run, so a forEach instance ("load [a]") is found by its base name
+fn task_position(source: &str, map: &SourceMap, name: &str, problem: &str) -> Option<Position> {
+    let factfile = match Json::from_str(source) {
+        Ok(factfile) => factfile,
+        Err(_) => return None,
+    };
+    let tasks = match factfile.find_path(&["data", "tasks"]).and_then(|t| t.as_array()) {
+        Some(tasks) => tasks,
+        None => return None,
+    };
+    let base_name = name.split(" [").next().unwrap_or(name);
+    let field = TASK_FIELDS.iter()
+        .find(|&&(fragment, _)| problem.contains(fragment))
+        .map(|&(_, field)| format!("/{}", field))
+        .unwrap_or(String::new());
+
+    tasks.iter()
+        .position(|t| {
+            let task_name = t.find("name").and_then(|n| n.as_string());
+            task_name == Some(name) || task_name == Some(base_name)
+        })
+        .and_then(|idx| sourcemap::locate(map, &format!("/data/tasks/{}{}", idx, field)))
+}
+
+// where in the source a problem is, if it can be told from the problem
+fn place(source: &str, map: &SourceMap, problem: &str) -> Option<Position> {
+    // schema violations (and bad templates) are given as "'/data/tasks/0' - reason"
+    if problem.starts_with("'/") {
+        if let Some(end) = problem.find("' - ") {
+            return sourcemap::locate(map, &problem[1..end]);
+        }
+    }
+
+    if problem.starts_with("invalid JSON") {
+        syntax_error_position(problem)
+    } else if let Some(idx) = problem.find("the task '") {
+        let rest = &problem[idx + "the task '".len()..];
+        rest.find('\'').and_then(|end| task_position(source, map, &rest[..end], problem))
+    } else {
+        None
+    }
+}
+
+// a problem that's already been placed, as "problem (line 3, column 7)"
+fn placed(problem: &str) -> Option<(Position, &str)> {
+    if !problem.ends_with(')') {
+        return None;
+    }
+    problem.rfind(LOCATION_PREFIX).and_then(|idx| {
+        parse_position(&problem[idx + LOCATION_PREFIX.len()..problem.len() - 1])
+            .map(|position| (position, &problem[..idx]))
+    })
+}
+
+// the problems in an error, each followed by where it is in the source (syntax errors
+// already say where they are)
+pub fn locate_problems(source: &str, problems: &str) -> String {
+    let map = sourcemap::source_map(source);
+    problems.lines()
+        .map(|problem| {
+            if problem.starts_with("invalid JSON") || placed(problem).is_some() {
+                return problem.to_string();
+            }
+            match place(source, &map, problem.trim()) {
+                Some(position) => {
+                    format!("{}{}{}, column {})",
+                            problem,
+                            LOCATION_PREFIX,
+                            position.line,
+                            position.column)
+                }
+                None => problem.to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn diagnose_problem(source: &str, map: &SourceMap, problem: &str) -> Diagnostic {
+    match placed(problem) {
+        Some((position, problem)) => {
+            Diagnostic {
+                position: position,
+                message: problem.to_string(),
+            }
+        }
+        None => {
+            Diagnostic {
+                position: place(source, map, problem).unwrap_or(Position::start()),
+                message: problem.to_string(),
+            }
+        }
+    }
+}
+
+// the problems in an error from parsing the factfile, each placed where it's found in
+// the source - or at the start, if it can't be placed
+pub fn diagnose(source: &str, error: &str) -> Vec<Diagnostic> {
+    let problems = match error.find(INVALID_FACTFILE) {
+        Some(idx) => &error[idx + INVALID_FACTFILE.len()..],
+        None => error,
+    };
+    let map = sourcemap::source_map(source);
+
+    problems.lines()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .map(|p| diagnose_problem(source, &map, p))
+        .collect()
+}
+
+pub fn format_diagnostics(file: &str, diagnostics: &Vec<Diagnostic>) -> String {
+    diagnostics.iter()
+        .map(|d| {
+            format!("{}:{}:{}: error: {}",
+                    file,
+                    d.position.line,
+                    d.position.column,
+                    d.message)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+// one line for each check, so an editor can read them as they're written
+pub fn diagnostics_json(file: &str, diagnostics: &Vec<Diagnostic>) -> Json {
+    let mut d = BTreeMap::new();
+    d.insert("file".to_string(), file.to_json());
+    d.insert("diagnostics".to_string(), diagnostics.to_json());
+    Json::Object(d)
+}
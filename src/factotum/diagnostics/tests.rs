@@ -0,0 +1,92 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+
+const SOURCE: &'static str = "{\n  \"data\": {\n    \"tasks\": [\n      {\n        \"name\": \"a\"\n      },\n      {\n        \"name\": \"b\"\n      }\n    ]\n  }\n}";
+
+fn at(line: usize, column: usize) -> Position {
+    Position {
+        line: line,
+        column: column,
+    }
+}
+
+#[test]
+fn pointer_problems_are_placed() {
+    let error = "'f.factfile' is not a valid factotum factfile: '/data/tasks/1' - missing \
+                 the field 'command'";
+    let diagnostics = diagnose(SOURCE, error);
+    assert_eq!(diagnostics,
+               vec![Diagnostic {
+                        position: at(7, 7),
+                        message: "'/data/tasks/1' - missing the field 'command'".to_string(),
+                    }]);
+}
+
+#[test]
+fn task_problems_are_placed_at_the_task() {
+    let diagnostics = diagnose(SOURCE, "the task 'b' depends on itself");
+    assert_eq!(diagnostics[0].position, at(7, 7));
+    let diagnostics = diagnose(SOURCE, "the task 'a [1]' failed");
+    assert_eq!(diagnostics[0].position, at(4, 7));
+}
+
+#[test]
+fn syntax_errors_are_placed() {
+    let diagnostics = diagnose("{", "invalid JSON - EOF While parsing object at line 1, column 2");
+    assert_eq!(diagnostics[0].position, at(1, 2));
+}
+
+#[test]
+fn unplaceable_problems_are_at_the_start() {
+    let diagnostics = diagnose(SOURCE, "something went wrong\n\nand again");
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0].position, Position::start());
+    assert_eq!(diagnostics[1].message, "and again");
+}
+
+#[test]
+fn diagnostics_are_formatted() {
+    let diagnostics = vec![Diagnostic {
+                               position: at(3, 4),
+                               message: "bad".to_string(),
+                           }];
+    assert_eq!(format_diagnostics("f.factfile", &diagnostics),
+               "f.factfile:3:4: error: bad");
+    assert_eq!(diagnostics_json("f.factfile", &diagnostics).to_string(),
+               "{\"diagnostics\":[{\"column\":4,\"line\":3,\"message\":\"bad\",\
+                \"severity\":\"error\"}],\"file\":\"f.factfile\"}");
+}
+
+#[test]
+fn problems_are_located_at_their_fields() {
+    let located = locate_problems(SOURCE,
+                                  "the task 'b' has conflicting actions.\nsomething else\n\
+                                   '/data/tasks/0/name' - bad");
+    assert_eq!(located,
+               "the task 'b' has conflicting actions. (line 7, column 7)\nsomething else\n\
+                '/data/tasks/0/name' - bad (line 5, column 9)");
+    assert_eq!(locate_problems(SOURCE, &located), located);
+}
+
+#[test]
+fn located_problems_keep_their_position() {
+    let diagnostics = diagnose(SOURCE, "the task 'zz' failed (line 9, column 2)");
+    assert_eq!(diagnostics,
+               vec![Diagnostic {
+                        position: at(9, 2),
+                        message: "the task 'zz' failed".to_string(),
+                    }]);
+}
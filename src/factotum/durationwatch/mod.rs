@@ -0,0 +1,163 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::{ExecutionState, ExecutionUpdate};
+use factotum::executor::task_list::State;
+use factotum::factfile::Factfile;
+use factotum::history::RunRecord;
+use factotum::webhook::jobcontext::JobContext;
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const ALERT_EVENT: &'static str = "TASK_DURATION_EXCEEDED";
+
+// a baseline is the median of the task's most recent successful durations
+pub const BASELINE_RUNS: usize = 5;
+pub const MIN_BASELINE_RUNS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DurationAlert {
+    pub task_name: String,
+    pub expected: Duration,
+    pub elapsed: Duration,
+}
+
+pub fn history_baseline(task_name: &str, runs: &Vec<RunRecord>) -> Option<Duration> {
+    let mut durations = runs.iter()
+        .rev()
+        .filter_map(|r| r.task(task_name))
+        .filter(|t| t.state == "SUCCEEDED")
+        .filter_map(|t| t.duration_millis)
+        .take(BASELINE_RUNS)
+        .collect::<Vec<u64>>();
+
+    if durations.len() < MIN_BASELINE_RUNS {
+        return None;
+    }
+
+    durations.sort();
+    Some(Duration::from_millis(durations[durations.len() / 2]))
+}
+
+// a task's own expectedDuration is used in preference to its history
+pub fn expected_durations(factfile: &Factfile, runs: &Vec<RunRecord>) -> HashMap<String, Duration> {
+    factfile.get_tasks_in_order()
+        .iter()
+        .flat_map(|grp| grp.iter())
+        .filter_map(|t| {
+            t.expected_duration
+                .or_else(|| history_baseline(&t.name, runs))
+                .map(|d| (t.name.clone(), d))
+        })
+        .collect()
+}
+
+pub fn scale(duration: &Duration, factor: f64) -> Duration {
+    let millis = duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64;
+    Duration::from_millis((millis as f64 * factor) as u64)
+}
+
+pub fn overdue_tasks(elapsed: &HashMap<String, Duration>,
+                     expected: &HashMap<String, Duration>,
+                     factor: f64,
+                     already_alerted: &HashSet<String>)
+                     -> Vec<DurationAlert> {
+    let mut alerts = elapsed.iter()
+        .filter(|&(name, _)| !already_alerted.contains(name))
+        .filter_map(|(name, running_for)| {
+            expected.get(name)
+                .filter(|e| *running_for > scale(e, factor))
+                .map(|e| {
+                    DurationAlert {
+                        task_name: name.clone(),
+                        expected: *e,
+                        elapsed: *running_for,
+                    }
+                })
+        })
+        .collect::<Vec<DurationAlert>>();
+    alerts.sort_by(|a, b| a.task_name.cmp(&b.task_name));
+    alerts
+}
+
+pub fn alert_json(context: &JobContext, alert: &DurationAlert) -> Json {
+    let mut d = BTreeMap::new();
+    d.insert("event".to_string(), ALERT_EVENT.to_json());
+    d.insert("jobName".to_string(), context.job_name.to_json());
+    d.insert("jobReference".to_string(), context.job_reference.to_json());
+    d.insert("runReference".to_string(), context.run_reference.to_json());
+    d.insert("taskName".to_string(), alert.task_name.to_json());
+    d.insert("expectedDuration".to_string(),
+             ChronoDuration::from_std(alert.expected).unwrap().to_string().to_json());
+    d.insert("elapsed".to_string(),
+             ChronoDuration::from_std(alert.elapsed).unwrap().to_string().to_json());
+    d.insert("context".to_string(), context.context_json());
+    Json::Object(d)
+}
+
+// tasks are left running - each overdue task is reported once, the first time
+// it's seen to be over its threshold
+pub fn connect_duration_watch<F>(expected: HashMap<String, Duration>,
+                                 factor: f64,
+                                 updates_channel: Receiver<ExecutionUpdate>,
+                                 poll_interval: Duration,
+                                 on_alert: F)
+                                 -> JoinHandle<Vec<DurationAlert>>
+    where F: Fn(&DurationAlert) + Send + 'static
+{
+    thread::spawn(move || {
+        let mut started: HashMap<String, Instant> = HashMap::new();
+        let mut alerted = HashSet::new();
+        let mut alerts = vec![];
+
+        loop {
+            let finished = match updates_channel.recv_timeout(poll_interval) {
+                Ok(update) => {
+                    for task in update.task_snapshot.iter() {
+                        if task.state == State::Running {
+                            started.entry(task.name.clone()).or_insert(Instant::now());
+                        } else {
+                            started.remove(&task.name);
+                        }
+                    }
+                    update.execution_state == ExecutionState::Finished
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if finished {
+                break;
+            }
+
+            let elapsed = started.iter()
+                .map(|(name, start)| (name.clone(), start.elapsed()))
+                .collect::<HashMap<String, Duration>>();
+            for alert in overdue_tasks(&elapsed, &expected, factor, &alerted) {
+                alerted.insert(alert.task_name.clone());
+                on_alert(&alert);
+                alerts.push(alert);
+            }
+        }
+
+        alerts
+    })
+}
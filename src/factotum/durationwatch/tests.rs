@@ -0,0 +1,161 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::{ExecutionState, ExecutionUpdate, Transition, JobTransition};
+use factotum::executor::task_list::{Task, State};
+use factotum::factfile::Factfile;
+use factotum::history::{RunRecord, TaskRecord};
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn run_with(task_name: &str, state: &str, duration: u64) -> RunRecord {
+    RunRecord {
+        job_name: "job".to_string(),
+        run_reference: "abc".to_string(),
+        start_time: "".to_string(),
+        run_state: "SUCCEEDED".to_string(),
+        source: None,
+        tasks: vec![TaskRecord {
+                        task_name: task_name.to_string(),
+                        state: state.to_string(),
+                        error_message: None,
+                        depends_on: vec![],
+                        started: None,
+                        duration_millis: Some(duration),
+                        cache_key: None,
+                    }],
+    }
+}
+
+fn update(state: ExecutionState, running: Vec<&str>) -> ExecutionUpdate {
+    let snapshot = running.iter()
+        .map(|name| {
+            let mut task = Task::new(*name, make_task(name, &vec![]));
+            task.state = State::Running;
+            task
+        })
+        .collect();
+    ExecutionUpdate::new(state,
+                         snapshot,
+                         Transition::Job(JobTransition::new(None, ExecutionState::Running)))
+}
+
+#[test]
+fn history_baseline_is_median_of_recent_successes() {
+    let mut runs = vec![run_with("a", "SUCCEEDED", 100000),
+                        run_with("a", "SUCCEEDED", 3000),
+                        run_with("a", "FAILED", 50)];
+    assert_eq!(history_baseline("a", &runs), None);
+
+    runs.push(run_with("a", "SUCCEEDED", 1000));
+    runs.push(run_with("a", "SUCCEEDED", 2000));
+    runs.push(run_with("a", "SUCCEEDED", 500));
+    assert_eq!(history_baseline("a", &runs), Some(Duration::from_millis(2000)));
+    assert_eq!(history_baseline("b", &runs), None);
+}
+
+#[test]
+fn expected_durations_prefer_the_factfile() {
+    let mut ff = Factfile::new("none", "job");
+    let mut a = make_task("a", &vec![]);
+    a.expected_duration = Some(Duration::from_secs(60));
+    ff.add_task_obj(&a).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["a"])).unwrap();
+
+    let runs = (0..3)
+        .map(|_| {
+            let mut run = run_with("a", "SUCCEEDED", 5000);
+            run.tasks.push(run_with("b", "SUCCEEDED", 8000).tasks[0].clone());
+            run
+        })
+        .collect();
+
+    let expected = expected_durations(&ff, &runs);
+    assert_eq!(expected.len(), 2);
+    assert_eq!(expected.get("a"), Some(&Duration::from_secs(60)));
+    assert_eq!(expected.get("b"), Some(&Duration::from_secs(8)));
+}
+
+#[test]
+fn overdue_tasks_good() {
+    let mut expected = HashMap::new();
+    expected.insert("a".to_string(), Duration::from_secs(10));
+    expected.insert("b".to_string(), Duration::from_secs(10));
+
+    let mut elapsed = HashMap::new();
+    elapsed.insert("a".to_string(), Duration::from_secs(16));
+    elapsed.insert("b".to_string(), Duration::from_secs(14));
+    elapsed.insert("c".to_string(), Duration::from_secs(1000));
+
+    assert_eq!(overdue_tasks(&elapsed, &expected, 1.5, &HashSet::new()),
+               vec![DurationAlert {
+                        task_name: "a".to_string(),
+                        expected: Duration::from_secs(10),
+                        elapsed: Duration::from_secs(16),
+                    }]);
+    assert_eq!(overdue_tasks(&elapsed, &expected, 1.0, &HashSet::new()).len(), 2);
+
+    let mut alerted = HashSet::new();
+    alerted.insert("a".to_string());
+    assert_eq!(overdue_tasks(&elapsed, &expected, 1.5, &alerted), vec![]);
+}
+
+#[test]
+fn alert_json_good() {
+    let context = JobContext::new("job", "{}", None);
+    let alert = DurationAlert {
+        task_name: "a".to_string(),
+        expected: Duration::from_secs(10),
+        elapsed: Duration::from_secs(25),
+    };
+
+    let json = alert_json(&context, &alert);
+    assert_eq!(json.find("event").unwrap().as_string(),
+               Some("TASK_DURATION_EXCEEDED"));
+    assert_eq!(json.find("runReference").unwrap().as_string(),
+               Some(context.run_reference.as_ref()));
+    assert_eq!(json.find("taskName").unwrap().as_string(), Some("a"));
+    assert_eq!(json.find("expectedDuration").unwrap().as_string(), Some("PT10S"));
+    assert_eq!(json.find("elapsed").unwrap().as_string(), Some("PT25S"));
+}
+
+#[test]
+fn duration_watch_alerts_once_per_task() {
+    let mut expected = HashMap::new();
+    expected.insert("slow".to_string(), Duration::from_millis(10));
+    expected.insert("quick".to_string(), Duration::from_secs(60));
+
+    let (tx, rx) = mpsc::channel();
+    let (alert_tx, alert_rx) = mpsc::channel();
+    let handle = connect_duration_watch(expected,
+                                        1.0,
+                                        rx,
+                                        Duration::from_millis(5),
+                                        move |alert| alert_tx.send(alert.task_name.clone()).unwrap());
+
+    tx.send(update(ExecutionState::Running, vec!["slow", "quick"])).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    tx.send(update(ExecutionState::Finished, vec![])).unwrap();
+
+    let alerts = handle.join().unwrap();
+    assert_eq!(alerts.len(), 1);
+    assert_eq!(alerts[0].task_name, "slow");
+    assert_eq!(alert_rx.iter().collect::<Vec<String>>(), vec!["slow"]);
+}
@@ -0,0 +1,326 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::retry;
+use factotum::waitfor::WaitOptions;
+use rustc_serialize::json::{self, Json, ToJson};
+use std::collections::BTreeMap;
+use std::process::Command;
+use std::time::Duration;
+
+pub const EXECUTOR: &'static str = "emr";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Cluster {
+    Existing(String),
+    // a cluster spec (as taken by `aws emr create-cluster --cli-input-json`) that's started for
+    // the step and terminated once it's finished
+    Transient(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EmrStep {
+    pub name: String,
+    pub jar: String,
+    pub main_class: Option<String>,
+    pub args: Vec<String>,
+    pub action_on_failure: String,
+    pub region: Option<String>,
+    pub polling: WaitOptions,
+}
+
+impl ToJson for EmrStep {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("Type".to_string(), "CUSTOM_JAR".to_json());
+        d.insert("Name".to_string(), self.name.to_json());
+        d.insert("Jar".to_string(), self.jar.to_json());
+        d.insert("Args".to_string(), self.args.to_json());
+        d.insert("ActionOnFailure".to_string(), self.action_on_failure.to_json());
+        if let Some(ref main_class) = self.main_class {
+            d.insert("MainClass".to_string(), main_class.to_json());
+        }
+        Json::Array(vec![Json::Object(d)])
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct StepStatus {
+    pub state: String,
+    pub failure: Option<String>,
+}
+
+const ACTIONS_ON_FAILURE: &'static [&'static str] = &["CONTINUE",
+                                                        "CANCEL_AND_WAIT",
+                                                        "TERMINATE_CLUSTER",
+                                                        "TERMINATE_JOB_FLOW"];
+
+// "j-..." is a running cluster, anything else is the path to a transient cluster's spec
+pub fn parse_cluster(cluster: &str) -> Result<Cluster, String> {
+    let cluster = cluster.trim();
+    if cluster.is_empty() {
+        Err("there is no cluster id or cluster spec to run the step on".to_string())
+    } else if cluster.starts_with("j-") {
+        Ok(Cluster::Existing(cluster.to_string()))
+    } else {
+        Ok(Cluster::Transient(cluster.trim_start_matches("file://").to_string()))
+    }
+}
+
+// an emr task's arguments are its step's parameters, e.g. [ "name=Enrich",
+// "jar=command-runner.jar", "arg=spark-submit", "arg=s3://jobs/enrich.py", "timeout=2h" ]
+pub fn parse_step(params: &Vec<String>) -> Result<EmrStep, String> {
+    let mut step = EmrStep {
+        name: String::new(),
+        jar: String::new(),
+        main_class: None,
+        args: vec![],
+        action_on_failure: "CONTINUE".to_string(),
+        region: None,
+        polling: WaitOptions {
+            poll_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(24 * 60 * 60),
+        },
+    };
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value,
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "name" => step.name = value.to_string(),
+            "jar" => step.jar = value.to_string(),
+            "main-class" => step.main_class = Some(value.to_string()),
+            "arg" => step.args.push(value.to_string()),
+            "action-on-failure" => {
+                if !ACTIONS_ON_FAILURE.contains(&value) {
+                    return Err(format!("'{}' isn't an action on failure, it must be one of {}",
+                                       value,
+                                       ACTIONS_ON_FAILURE.join(", ")));
+                }
+                step.action_on_failure = value.to_string()
+            }
+            "region" => step.region = Some(value.to_string()),
+            "poll-interval" => step.polling.poll_interval = try!(retry::parse_duration(value)),
+            "timeout" => step.polling.timeout = try!(retry::parse_duration(value)),
+            _ => {
+                return Err(format!("'{}' isn't a step parameter (expected name, jar, main-class, \
+                                    arg, action-on-failure, region, poll-interval or timeout)",
+                                   name))
+            }
+        }
+    }
+
+    if step.jar.is_empty() {
+        return Err("the step has no jar to run (e.g. jar=command-runner.jar)".to_string());
+    }
+    if step.name.is_empty() {
+        step.name = step.jar.clone();
+    }
+    if step.polling.poll_interval.as_secs() == 0 {
+        return Err("the poll interval must be at least 1s".to_string());
+    }
+
+    Ok(step)
+}
+
+// None while the step's still to finish
+pub fn step_outcome(status: &StepStatus) -> Option<Result<(), String>> {
+    match status.state.as_ref() {
+        "PENDING" | "CANCEL_PENDING" | "RUNNING" => None,
+        "COMPLETED" => Some(Ok(())),
+        state => {
+            Some(Err(match status.failure {
+                Some(ref failure) => format!("the step is {} ({})", state, failure),
+                None => format!("the step is {}", state),
+            }))
+        }
+    }
+}
+
+pub trait EmrClient {
+    fn create_cluster(&self, spec: &str) -> Result<String, String>;
+    fn add_step(&self, cluster_id: &str, step: &EmrStep) -> Result<String, String>;
+    fn describe_step(&self, cluster_id: &str, step_id: &str) -> Result<StepStatus, String>;
+    fn cancel_step(&self, cluster_id: &str, step_id: &str) -> Result<(), String>;
+    fn terminate_cluster(&self, cluster_id: &str) -> Result<(), String>;
+}
+
+pub struct AwsCliEmrClient {
+    pub region: Option<String>,
+}
+
+impl AwsCliEmrClient {
+    fn run(&self, args: Vec<&str>) -> Result<Json, String> {
+        let mut command = Command::new("aws");
+        command.arg("emr").args(&args).arg("--output=json");
+        if let Some(ref region) = self.region {
+            command.arg(format!("--region={}", region));
+        }
+        let output = try!(command.output()
+            .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        if stdout.trim().is_empty() {
+            Ok(Json::Null)
+        } else {
+            Json::from_str(&stdout).map_err(|e| format!("the aws cli's output isn't JSON ({})", e))
+        }
+    }
+}
+
+fn find_string(json: &Json, path: &[&str]) -> Result<String, String> {
+    json.find_path(path)
+        .and_then(|v| v.as_string())
+        .map(|v| v.to_string())
+        .ok_or(format!("the aws cli's output has no {}", path.join(".")))
+}
+
+pub fn parse_step_status(json: &Json) -> Result<StepStatus, String> {
+    let state = try!(find_string(json, &["Step", "Status", "State"]));
+    let failure = ["Reason", "Message", "LogFile"]
+        .iter()
+        .filter_map(|field| json.find_path(&["Step", "Status", "FailureDetails", field]))
+        .filter_map(|v| v.as_string())
+        .collect::<Vec<_>>();
+
+    Ok(StepStatus {
+        state: state,
+        failure: if failure.is_empty() {
+            None
+        } else {
+            Some(failure.join(", "))
+        },
+    })
+}
+
+impl EmrClient for AwsCliEmrClient {
+    fn create_cluster(&self, spec: &str) -> Result<String, String> {
+        let created = try!(self.run(vec!["create-cluster",
+                                         "--cli-input-json",
+                                         &format!("file://{}", spec)]));
+        find_string(&created, &["ClusterId"])
+    }
+
+    fn add_step(&self, cluster_id: &str, step: &EmrStep) -> Result<String, String> {
+        let steps = try!(json::encode(&step.to_json()).map_err(|e| e.to_string()));
+        let added = try!(self.run(vec!["add-steps",
+                                       "--cluster-id",
+                                       cluster_id,
+                                       "--steps",
+                                       &steps]));
+        added.find("StepIds")
+            .and_then(|ids| ids.as_array())
+            .and_then(|ids| ids.first())
+            .and_then(|id| id.as_string())
+            .map(|id| id.to_string())
+            .ok_or("the aws cli's output has no StepIds".to_string())
+    }
+
+    fn describe_step(&self, cluster_id: &str, step_id: &str) -> Result<StepStatus, String> {
+        let described = try!(self.run(vec!["describe-step",
+                                           "--cluster-id",
+                                           cluster_id,
+                                           "--step-id",
+                                           step_id]));
+        parse_step_status(&described)
+    }
+
+    fn cancel_step(&self, cluster_id: &str, step_id: &str) -> Result<(), String> {
+        self.run(vec!["cancel-steps", "--cluster-id", cluster_id, "--step-ids", step_id])
+            .map(|_| ())
+    }
+
+    fn terminate_cluster(&self, cluster_id: &str) -> Result<(), String> {
+        self.run(vec!["terminate-clusters", "--cluster-ids", cluster_id]).map(|_| ())
+    }
+}
+
+// submits the step, then polls it until it finishes, returning how many checks it took
+pub fn run_step<C, S, R>(client: &C,
+                         cluster: &Cluster,
+                         step: &EmrStep,
+                         sleep: S,
+                         report: R)
+                         -> Result<u64, String>
+    where C: EmrClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let cluster_id = match *cluster {
+        Cluster::Existing(ref id) => id.clone(),
+        Cluster::Transient(ref spec) => {
+            let id = try!(client.create_cluster(spec)
+                .map_err(|e| format!("couldn't create a cluster from '{}': {}", spec, e)));
+            report(format!("Created the transient cluster {}", id));
+            id
+        }
+    };
+
+    let result = submit_and_poll(client, &cluster_id, step, sleep, &report);
+
+    if let Cluster::Transient(_) = *cluster {
+        match client.terminate_cluster(&cluster_id) {
+            Ok(_) => report(format!("Terminated the transient cluster {}", cluster_id)),
+            Err(e) => {
+                report(format!("Couldn't terminate the transient cluster {}: {}", cluster_id, e))
+            }
+        }
+    }
+
+    result
+}
+
+fn submit_and_poll<C, S, R>(client: &C,
+                            cluster_id: &str,
+                            step: &EmrStep,
+                            sleep: S,
+                            report: &R)
+                            -> Result<u64, String>
+    where C: EmrClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let step_id = try!(client.add_step(cluster_id, step)
+        .map_err(|e| format!("couldn't add the step to {}: {}", cluster_id, e)));
+    report(format!("Submitted the step '{}' as {} to {}", step.name, step_id, cluster_id));
+
+    let max_checks = step.polling.timeout.as_secs() / step.polling.poll_interval.as_secs() + 1;
+    for attempt in 1..max_checks + 1 {
+        let status = try!(client.describe_step(cluster_id, &step_id));
+        if let Some(outcome) = step_outcome(&status) {
+            return outcome.map(|_| attempt);
+        }
+        if attempt < max_checks {
+            sleep(step.polling.poll_interval);
+        }
+    }
+
+    // don't leave the step running once nothing's watching it
+    if let Err(e) = client.cancel_step(cluster_id, &step_id) {
+        report(format!("Couldn't cancel the step {}: {}", step_id, e));
+    }
+    Err(format!("timed out after {}s waiting for the step {} to finish",
+                step.polling.timeout.as_secs(),
+                step_id))
+}
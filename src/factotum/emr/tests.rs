@@ -0,0 +1,217 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::cell::RefCell;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+fn step() -> EmrStep {
+    parse_step(&params(vec!["name=Enrich", "jar=command-runner.jar", "poll-interval=1s",
+                            "timeout=3s"]))
+        .unwrap()
+}
+
+struct FakeEmr {
+    states: RefCell<Vec<StepStatus>>,
+    calls: RefCell<Vec<String>>,
+}
+
+impl FakeEmr {
+    fn new(states: Vec<&str>) -> FakeEmr {
+        FakeEmr {
+            states: RefCell::new(states.iter()
+                .rev()
+                .map(|s| {
+                    StepStatus {
+                        state: s.to_string(),
+                        failure: None,
+                    }
+                })
+                .collect()),
+            calls: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl EmrClient for FakeEmr {
+    fn create_cluster(&self, spec: &str) -> Result<String, String> {
+        self.calls.borrow_mut().push(format!("create {}", spec));
+        Ok("j-NEW".to_string())
+    }
+
+    fn add_step(&self, cluster_id: &str, step: &EmrStep) -> Result<String, String> {
+        self.calls.borrow_mut().push(format!("add {} {}", cluster_id, step.name));
+        Ok("s-1".to_string())
+    }
+
+    fn describe_step(&self, cluster_id: &str, step_id: &str) -> Result<StepStatus, String> {
+        self.calls.borrow_mut().push(format!("describe {} {}", cluster_id, step_id));
+        self.states.borrow_mut().pop().ok_or("no more states".to_string())
+    }
+
+    fn cancel_step(&self, cluster_id: &str, step_id: &str) -> Result<(), String> {
+        self.calls.borrow_mut().push(format!("cancel {} {}", cluster_id, step_id));
+        Ok(())
+    }
+
+    fn terminate_cluster(&self, cluster_id: &str) -> Result<(), String> {
+        self.calls.borrow_mut().push(format!("terminate {}", cluster_id));
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_cluster_good_and_bad() {
+    assert_eq!(parse_cluster("j-2AXXXXXXGAPLF"),
+               Ok(Cluster::Existing("j-2AXXXXXXGAPLF".to_string())));
+    assert_eq!(parse_cluster("file://emr/cluster.json"),
+               Ok(Cluster::Transient("emr/cluster.json".to_string())));
+    assert_eq!(parse_cluster("cluster.json"),
+               Ok(Cluster::Transient("cluster.json".to_string())));
+    assert!(parse_cluster(" ").is_err());
+}
+
+#[test]
+fn parse_step_good() {
+    let step = parse_step(&params(vec!["jar=s3://jars/enrich.jar",
+                                       "main-class=com.acme.Enrich",
+                                       "arg=--input",
+                                       "arg=s3://in/a=b",
+                                       "action-on-failure=CANCEL_AND_WAIT",
+                                       "region=eu-west-1",
+                                       "timeout=2h"]))
+        .unwrap();
+    assert_eq!(step.name, "s3://jars/enrich.jar");
+    assert_eq!(step.main_class, Some("com.acme.Enrich".to_string()));
+    assert_eq!(step.args, vec!["--input", "s3://in/a=b"]);
+    assert_eq!(step.action_on_failure, "CANCEL_AND_WAIT");
+    assert_eq!(step.region, Some("eu-west-1".to_string()));
+    assert_eq!(step.polling.poll_interval, Duration::from_secs(30));
+    assert_eq!(step.polling.timeout, Duration::from_secs(2 * 60 * 60));
+}
+
+#[test]
+fn parse_step_bad() {
+    assert_eq!(parse_step(&params(vec!["name=Enrich"])),
+               Err("the step has no jar to run (e.g. jar=command-runner.jar)".to_string()));
+    assert_eq!(parse_step(&params(vec!["jar"])),
+               Err("'jar' must be given as <parameter>=<value>".to_string()));
+    assert!(parse_step(&params(vec!["jar=x", "class=y"]))
+        .unwrap_err()
+        .starts_with("'class' isn't a step parameter"));
+    assert!(parse_step(&params(vec!["jar=x", "action-on-failure=RETRY"]))
+        .unwrap_err()
+        .starts_with("'RETRY' isn't an action on failure"));
+    assert_eq!(parse_step(&params(vec!["jar=x", "poll-interval=0s"])),
+               Err("the poll interval must be at least 1s".to_string()));
+}
+
+#[test]
+fn step_json_is_a_custom_jar_step() {
+    assert_eq!(json::encode(&step().to_json()).unwrap(),
+               "[{\"ActionOnFailure\":\"CONTINUE\",\"Args\":[],\"Jar\":\"command-runner.jar\",\
+                \"Name\":\"Enrich\",\"Type\":\"CUSTOM_JAR\"}]");
+}
+
+#[test]
+fn step_states_map_to_outcomes() {
+    let status = |state: &str, failure: Option<&str>| {
+        StepStatus {
+            state: state.to_string(),
+            failure: failure.map(|f| f.to_string()),
+        }
+    };
+    assert_eq!(step_outcome(&status("PENDING", None)), None);
+    assert_eq!(step_outcome(&status("RUNNING", None)), None);
+    assert_eq!(step_outcome(&status("COMPLETED", None)), Some(Ok(())));
+    assert_eq!(step_outcome(&status("CANCELLED", None)),
+               Some(Err("the step is CANCELLED".to_string())));
+    assert_eq!(step_outcome(&status("FAILED", Some("Unknown error"))),
+               Some(Err("the step is FAILED (Unknown error)".to_string())));
+}
+
+#[test]
+fn parse_step_status_reads_failure_details() {
+    let described = Json::from_str("{\"Step\": {\"Status\": {\"State\": \"FAILED\", \
+                                    \"FailureDetails\": {\"Reason\": \"Unknown error.\", \
+                                    \"LogFile\": \"s3://logs/j-1/steps/s-1\"}}}}")
+        .unwrap();
+    assert_eq!(parse_step_status(&described),
+               Ok(StepStatus {
+                   state: "FAILED".to_string(),
+                   failure: Some("Unknown error., s3://logs/j-1/steps/s-1".to_string()),
+               }));
+    assert!(parse_step_status(&Json::from_str("{}").unwrap()).is_err());
+}
+
+#[test]
+fn run_step_polls_until_the_step_completes() {
+    let emr = FakeEmr::new(vec!["PENDING", "RUNNING", "COMPLETED"]);
+    let sleeps = RefCell::new(0);
+    let reports = RefCell::new(vec![]);
+
+    let result = run_step(&emr,
+                          &Cluster::Existing("j-1".to_string()),
+                          &step(),
+                          |_| *sleeps.borrow_mut() += 1,
+                          |r| reports.borrow_mut().push(r));
+
+    assert_eq!(result, Ok(3));
+    assert_eq!(*sleeps.borrow(), 2);
+    assert_eq!(*reports.borrow(),
+               vec!["Submitted the step 'Enrich' as s-1 to j-1".to_string()]);
+}
+
+#[test]
+fn run_step_fails_when_the_step_fails() {
+    let emr = FakeEmr::new(vec!["RUNNING", "FAILED"]);
+    let result = run_step(&emr,
+                          &Cluster::Existing("j-1".to_string()),
+                          &step(),
+                          |_| {},
+                          |_| {});
+    assert_eq!(result, Err("the step is FAILED".to_string()));
+}
+
+#[test]
+fn run_step_cancels_the_step_on_timeout() {
+    let emr = FakeEmr::new(vec!["RUNNING"; 10]);
+    let result = run_step(&emr,
+                          &Cluster::Existing("j-1".to_string()),
+                          &step(),
+                          |_| {},
+                          |_| {});
+    assert_eq!(result,
+               Err("timed out after 3s waiting for the step s-1 to finish".to_string()));
+    assert_eq!(emr.calls.borrow().last(), Some(&"cancel j-1 s-1".to_string()));
+}
+
+#[test]
+fn run_step_terminates_a_transient_cluster() {
+    let emr = FakeEmr::new(vec!["FAILED"]);
+    let result = run_step(&emr,
+                          &Cluster::Transient("cluster.json".to_string()),
+                          &step(),
+                          |_| {},
+                          |_| {});
+    assert!(result.is_err());
+    assert_eq!(*emr.calls.borrow(),
+               vec!["create cluster.json",
+                    "add j-NEW Enrich",
+                    "describe j-NEW s-1",
+                    "terminate j-NEW"]);
+}
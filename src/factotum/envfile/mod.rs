@@ -0,0 +1,174 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::process::Command;
+
+// files written by sops carry their encryption metadata in a top-level "sops" key
+pub fn is_sops_encrypted(contents: &str) -> bool {
+    match Json::from_str(contents) {
+        Ok(json) => json.find("sops").is_some(),
+        Err(_) => contents.lines().any(|l| l.starts_with("sops:")),
+    }
+}
+
+fn decrypt_sops(path: &str) -> Result<String, String> {
+    let output = try!(Command::new("sops")
+        .arg("--decrypt")
+        .arg("--output-type")
+        .arg("json")
+        .arg(path)
+        .output()
+        .map_err(|e| format!("couldn't run sops to decrypt '{}' ({})", path, e)));
+
+    if output.status.success() {
+        String::from_utf8(output.stdout)
+            .map_err(|e| format!("the decrypted contents of '{}' aren't utf8 ({})", path, e))
+    } else {
+        Err(format!("sops couldn't decrypt '{}': {}",
+                    path,
+                    String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+// numbers and bools are given as strings, as mustache can only render those - objects
+// and arrays are kept, so nested values can be used as "{{ aws.region }}" or "{{ @/aws/region }}"
+fn env_value(name: &str, value: Json, source: &str) -> Result<Json, String> {
+    match value {
+        Json::String(_) => Ok(value),
+        Json::I64(_) | Json::U64(_) | Json::F64(_) | Json::Boolean(_) => {
+            Ok(Json::String(value.to_string()))
+        }
+        Json::Object(fields) => {
+            let mut nested = BTreeMap::new();
+            for (key, value) in fields {
+                let value = try!(env_value(&format!("{}.{}", name, key), value, source));
+                nested.insert(key, value);
+            }
+            Ok(Json::Object(nested))
+        }
+        Json::Array(items) => {
+            let mut nested = vec![];
+            for (idx, value) in items.into_iter().enumerate() {
+                nested.push(try!(env_value(&format!("{}.{}", name, idx), value, source)));
+            }
+            Ok(Json::Array(nested))
+        }
+        Json::Null => Err(format!("the variable '{}' in '{}' is null", name, source)),
+    }
+}
+
+pub fn env_from_json(json: Json, source: &str) -> Result<BTreeMap<String, Json>, String> {
+    match json {
+        Json::Object(vars) => {
+            let mut env = BTreeMap::new();
+            for (name, value) in vars {
+                let value = try!(env_value(&name, value, source));
+                env.insert(name, value);
+            }
+            Ok(env)
+        }
+        _ => Err(format!("'{}' must contain a JSON object of variables", source)),
+    }
+}
+
+pub fn parse_env_json(contents: &str, source: &str) -> Result<BTreeMap<String, Json>, String> {
+    let json = try!(Json::from_str(contents)
+        .map_err(|e| format!("'{}' is not valid JSON ({})", source, e)));
+    env_from_json(json, source)
+}
+
+pub fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.find(':') {
+        Some(idx) if !header[..idx].trim().is_empty() => {
+            Ok((header[..idx].trim().to_string(), header[idx + 1..].trim().to_string()))
+        }
+        _ => Err(format!("the header \"{}\" must be given as <name>: <value>", header)),
+    }
+}
+
+pub fn fetch_url(url: &str, header: Option<&str>) -> Result<String, String> {
+    use hyper::Client;
+    use hyper::net::HttpsConnector;
+    use hyper_native_tls::NativeTlsClient;
+    use hyper::header::Headers;
+    use hyper::status;
+
+    let mut headers = Headers::new();
+    if let Some(header) = header {
+        let (name, value) = try!(parse_header(header));
+        headers.set_raw(name, vec![value.into_bytes()]);
+    }
+
+    let ssl = try!(NativeTlsClient::new().map_err(|e| format!("{}", e)));
+    let client = Client::with_connector(HttpsConnector::new(ssl));
+
+    match client.get(url).headers(headers).send() {
+        Ok(mut res) => {
+            if res.status == status::StatusCode::Ok {
+                let mut contents = String::new();
+                try!(res.read_to_string(&mut contents)
+                    .map_err(|e| format!("couldn't read the response from '{}' ({})", url, e)));
+                Ok(contents)
+            } else {
+                Err(format!("couldn't fetch '{}': {}", url, res.status))
+            }
+        }
+        Err(e) => Err(format!("couldn't fetch '{}': {}", url, e)),
+    }
+}
+
+pub fn load_env_location(location: &str,
+                         header: Option<&str>)
+                         -> Result<BTreeMap<String, Json>, String> {
+    if location.starts_with("https://") {
+        info!("fetching variables from '{}'", location);
+        let contents = try!(fetch_url(location, header));
+        if is_sops_encrypted(&contents) {
+            return Err(format!("'{}' is sops-encrypted, which is only supported for local files",
+                               location));
+        }
+        parse_env_json(&contents, location)
+    } else if location.starts_with("http://") {
+        Err(format!("'{}' must be fetched over https", location))
+    } else if header.is_some() {
+        Err("--env-file-header can only be used with an https:// --env-file".to_string())
+    } else {
+        load_env_file(location)
+    }
+}
+
+// encrypted files are decrypted with the sops CLI, which uses whatever age, KMS or
+// PGP keys the host has configured - the plaintext is only ever held in memory
+pub fn load_env_file(path: &str) -> Result<BTreeMap<String, Json>, String> {
+    let mut contents = String::new();
+    let mut f = try!(File::open(path)
+        .map_err(|e| format!("couldn't open '{}' for reading ({})", path, e)));
+    try!(f.read_to_string(&mut contents)
+        .map_err(|e| format!("couldn't read '{}' ({})", path, e)));
+
+    if is_sops_encrypted(&contents) {
+        info!("decrypting '{}' with sops", path);
+        let decrypted = try!(decrypt_sops(path));
+        parse_env_json(&decrypted, path)
+    } else {
+        parse_env_json(&contents, path)
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::collections::BTreeMap;
+use rustc_serialize::json::Json;
+
+#[test]
+fn sops_files_are_detected() {
+    assert!(is_sops_encrypted("{\"password\":\"ENC[AES256_GCM,data:abc]\",\"sops\":{\"version\":\"3.8.1\"}}"));
+    assert!(is_sops_encrypted("password: ENC[AES256_GCM,data:abc]\nsops:\n    version: 3.8.1\n"));
+    assert!(!is_sops_encrypted("{\"password\":\"hunter2\"}"));
+    assert!(!is_sops_encrypted("password: hunter2\n"));
+}
+
+#[test]
+fn parse_env_json_good() {
+    let env = parse_env_json("{\"name\":\"x\",\"size\":10,\"full\":true}", "vars.json").unwrap();
+    let mut expected = BTreeMap::new();
+    expected.insert("name".to_string(), Json::String("x".to_string()));
+    expected.insert("size".to_string(), Json::String("10".to_string()));
+    expected.insert("full".to_string(), Json::String("true".to_string()));
+    assert_eq!(env, expected);
+}
+
+#[test]
+fn parse_env_json_nested() {
+    let env = parse_env_json("{\"aws\":{\"emr\":[{\"id\":\"j-1\",\"nodes\":3}]}}",
+                             "vars.json")
+        .unwrap();
+    assert_eq!(env.get("aws"),
+               Some(&Json::from_str("{\"emr\":[{\"id\":\"j-1\",\"nodes\":\"3\"}]}").unwrap()));
+}
+
+#[test]
+fn parse_env_json_bad() {
+    assert_eq!(parse_env_json("[1,2]", "vars.json"),
+               Err("'vars.json' must contain a JSON object of variables".to_string()));
+    assert_eq!(parse_env_json("{\"nested\":{\"a\":[null]}}", "vars.json"),
+               Err("the variable 'nested.a.0' in 'vars.json' is null".to_string()));
+    assert!(parse_env_json("{", "vars.json").unwrap_err().starts_with("'vars.json' is not valid JSON"));
+}
+
+#[test]
+fn load_env_file_missing() {
+    assert!(load_env_file("./tests/resources/no-such-vars.json")
+        .unwrap_err()
+        .starts_with("couldn't open './tests/resources/no-such-vars.json' for reading"));
+}
+
+#[test]
+fn parse_header_good() {
+    assert_eq!(parse_header("Authorization: Bearer abc:def"),
+               Ok(("Authorization".to_string(), "Bearer abc:def".to_string())));
+    assert_eq!(parse_header("Authorization"),
+               Err("the header \"Authorization\" must be given as <name>: <value>".to_string()));
+    assert!(parse_header(": value").is_err());
+}
+
+#[test]
+fn load_env_location_requires_https() {
+    assert_eq!(load_env_location("http://config/vars.json", None),
+               Err("'http://config/vars.json' must be fetched over https".to_string()));
+    assert_eq!(load_env_location("vars.json", Some("Authorization: Bearer abc")),
+               Err("--env-file-header can only be used with an https:// --env-file".to_string()));
+}
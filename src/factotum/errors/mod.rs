@@ -0,0 +1,100 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use std::error::Error;
+use std::fmt;
+
+// what went wrong, so a caller can tell a factfile that needs fixing from a job that
+// couldn't be run
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorKind {
+    // a file couldn't be read
+    Io,
+    // the factfile isn't JSON
+    Syntax,
+    // the factfile doesn't describe a job factotum can run
+    Validation,
+    // a template couldn't be rendered
+    Template,
+    // a task couldn't be run
+    Execution,
+    // a running task was stopped with `factotum cancel`
+    Cancelled,
+    // a running task was stopped for printing nothing for longer than its idleTimeout
+    TimedOut,
+}
+
+impl ErrorKind {
+    // the factfile itself needs fixing, rather than whatever it was run against
+    pub fn is_invalid_factfile(&self) -> bool {
+        match *self {
+            ErrorKind::Syntax | ErrorKind::Validation | ErrorKind::Template => true,
+            ErrorKind::Io | ErrorKind::Execution | ErrorKind::Cancelled |
+            ErrorKind::TimedOut => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FactotumError {
+    pub kind: ErrorKind,
+    pub task: Option<String>,
+    pub message: String,
+    pub source: Option<Box<FactotumError>>,
+}
+
+impl FactotumError {
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> FactotumError {
+        FactotumError {
+            kind: kind,
+            task: None,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub fn for_task(mut self, task: &str) -> FactotumError {
+        self.task = Some(task.to_string());
+        self
+    }
+
+    pub fn caused_by(mut self, source: FactotumError) -> FactotumError {
+        self.source = Some(Box::new(source));
+        self
+    }
+}
+
+// just the message, as it's always been shown
+impl fmt::Display for FactotumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for FactotumError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|source| &**source as &(dyn Error + 'static))
+    }
+}
+
+// so the many functions that still fail with a message can try! these
+impl From<FactotumError> for String {
+    fn from(error: FactotumError) -> String {
+        error.message
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::error::Error;
+
+#[test]
+fn errors_show_their_message() {
+    let error = FactotumError::new(ErrorKind::Template, "the template '{{' is invalid");
+    assert_eq!(error.to_string(), "the template '{{' is invalid");
+    assert_eq!(String::from(error), "the template '{{' is invalid");
+}
+
+#[test]
+fn errors_keep_their_task_and_cause() {
+    let cause = FactotumError::new(ErrorKind::Io, "No such file or directory");
+    let error = FactotumError::new(ErrorKind::Execution, "Error executing process")
+        .for_task("load")
+        .caused_by(cause.clone());
+
+    assert_eq!(error.task, Some("load".to_string()));
+    assert_eq!(error.source.as_ref().map(|s| &**s), Some(&cause));
+    assert_eq!(Error::source(&error).map(|s| s.to_string()),
+               Some("No such file or directory".to_string()));
+    assert!(Error::source(&cause).is_none());
+}
+
+#[test]
+fn kinds_tell_invalid_factfiles_apart() {
+    assert!(ErrorKind::Syntax.is_invalid_factfile());
+    assert!(ErrorKind::Validation.is_invalid_factfile());
+    assert!(ErrorKind::Template.is_invalid_factfile());
+    assert!(!ErrorKind::Io.is_invalid_factfile());
+    assert!(!ErrorKind::Execution.is_invalid_factfile());
+}
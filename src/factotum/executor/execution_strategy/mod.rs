@@ -14,16 +14,193 @@
 
 #[cfg(test)]
 mod tests;
-use std::process::Command;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::os::unix::process::ExitStatusExt;
 use std::time::{Instant, Duration};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::mem;
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use libc;
+use factotum::errors::{ErrorKind, FactotumError};
+use factotum::control::{self, RunningTasks};
+
+pub const DEFAULT_SPILL_THRESHOLD: usize = 8 * 1024 * 1024;
+
+// how long a task that's been sent SIGTERM for going idle has to stop before it's killed
+pub const DEFAULT_IDLE_KILL_GRACE_SECS: u64 = 30;
+
+static SPILL_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct RunResult {
     pub duration: Duration,
-    pub task_execution_error: Option<String>,
+    pub task_execution_error: Option<FactotumError>,
     pub stdout: Option<String>,
     pub stderr: Option<String>,
     pub return_code: i32,
+    pub resource_usage: Option<ResourceUsage>,
+    pub spilled_output: Option<SpilledOutput>,
+}
+
+// what a strategy is told about a task besides its command
+#[derive(Clone, PartialEq, Debug)]
+pub struct TaskLimits {
+    // the task's stopped once it's printed nothing for this long
+    pub idle_timeout: Option<Duration>,
+    // and killed if it's still running this long after that
+    pub idle_kill_grace: Duration,
+}
+
+impl Default for TaskLimits {
+    fn default() -> Self {
+        TaskLimits {
+            idle_timeout: None,
+            idle_kill_grace: Duration::from_secs(DEFAULT_IDLE_KILL_GRACE_SECS),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ResourceUsage {
+    pub max_rss_kb: u64,
+    pub user_time: Duration,
+    pub system_time: Duration,
+}
+
+// output beyond the spill threshold is written to these files, and only the tail of it is
+// kept in stdout / stderr
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpilledOutput {
+    pub stdout_file: Option<PathBuf>,
+    pub stderr_file: Option<PathBuf>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpillPolicy {
+    pub threshold: usize,
+    pub dir: PathBuf,
+}
+
+impl Default for SpillPolicy {
+    fn default() -> Self {
+        SpillPolicy {
+            threshold: DEFAULT_SPILL_THRESHOLD,
+            dir: env::temp_dir().join("factotum-output"),
+        }
+    }
+}
+
+// collects a stream's output, holding at most about twice the threshold in memory - once
+// the threshold is passed everything is written to a file and only the tail is kept
+struct OutputCapture<'a> {
+    task_name: &'a str,
+    stream_name: &'static str,
+    policy: &'a SpillPolicy,
+    buffer: Vec<u8>,
+    spill: Option<(PathBuf, File)>,
+    spill_error: Option<String>,
+}
+
+impl<'a> OutputCapture<'a> {
+    fn new(task_name: &'a str, stream_name: &'static str, policy: &'a SpillPolicy) -> Self {
+        OutputCapture {
+            task_name: task_name,
+            stream_name: stream_name,
+            policy: policy,
+            buffer: vec![],
+            spill: None,
+            spill_error: None,
+        }
+    }
+
+    fn spill_path(&self) -> PathBuf {
+        let safe_name = self.task_name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect::<String>();
+        self.policy.dir.join(format!("{}-{}-{}.{}.log",
+                                     safe_name,
+                                     unsafe { libc::getpid() },
+                                     SPILL_FILE_COUNT.fetch_add(1, Ordering::SeqCst),
+                                     self.stream_name))
+    }
+
+    fn start_spill(&mut self) -> Result<(), String> {
+        let path = self.spill_path();
+        try!(fs::create_dir_all(&self.policy.dir).map_err(|e| {
+            format!("couldn't create the output directory '{}' ({})",
+                    self.policy.dir.display(),
+                    e)
+        }));
+        let mut file = try!(File::create(&path)
+            .map_err(|e| format!("couldn't create '{}' ({})", path.display(), e)));
+        try!(file.write_all(&self.buffer)
+            .map_err(|e| format!("couldn't write to '{}' ({})", path.display(), e)));
+        info!("task '{}' {} is larger than {} bytes, writing it to '{}'",
+              self.task_name,
+              self.stream_name,
+              self.policy.threshold,
+              path.display());
+        self.spill = Some((path, file));
+        Ok(())
+    }
+
+    fn push(&mut self, line: Vec<u8>) {
+        if self.spill.is_none() && self.spill_error.is_none() &&
+           self.buffer.len() + line.len() > self.policy.threshold {
+            if let Err(e) = self.start_spill() {
+                // keep going with the tail alone rather than failing the task
+                warn!("task '{}' {} couldn't be spilled to disk: {}",
+                      self.task_name,
+                      self.stream_name,
+                      e);
+                self.spill_error = Some(e);
+            }
+        }
+
+        if let Some((ref path, ref mut file)) = self.spill {
+            if let Err(e) = file.write_all(&line) {
+                warn!("couldn't write to '{}' ({})", path.display(), e);
+            }
+        }
+
+        self.buffer.extend(line);
+        if self.buffer.len() > self.policy.threshold * 2 {
+            self.keep_tail();
+        }
+    }
+
+    fn keep_tail(&mut self) {
+        if self.buffer.len() > self.policy.threshold {
+            let excess = self.buffer.len() - self.policy.threshold;
+            self.buffer.drain(..excess);
+        }
+    }
+
+    // the captured text (just the tail if it was spilled) and the spill file, if any
+    fn finish(mut self) -> (Option<String>, Option<PathBuf>) {
+        let path = self.spill.take().map(|(path, _)| path);
+        let was_truncated = self.buffer.len() > self.policy.threshold;
+        self.keep_tail();
+
+        let mut text: &[u8] = &self.buffer;
+        if was_truncated || path.is_some() {
+            // don't start the tail part way through a line
+            if let Some(newline) = text.iter().position(|b| *b == b'\n') {
+                if newline + 1 < text.len() {
+                    text = &text[newline + 1..];
+                }
+            }
+        }
+
+        let text: String = String::from_utf8_lossy(text).trim_end().into();
+        (if text.is_empty() { None } else { Some(text) }, path)
+    }
 }
 
 pub fn simulation_text(name: &str, command: &Command) -> String {
@@ -49,7 +226,7 @@ pub fn simulation_text(name: &str, command: &Command) -> String {
     lines.join("\n")
 }
 
-pub fn execute_simulation(name: &str, command: &mut Command) -> RunResult {
+pub fn execute_simulation(name: &str, command: &mut Command, _: &TaskLimits) -> RunResult {
     info!("Simulating execution for {} with command {:?}",
           name,
           command);
@@ -59,49 +236,321 @@ pub fn execute_simulation(name: &str, command: &mut Command) -> RunResult {
         stdout: Some(simulation_text(name, &command)),
         stderr: None,
         return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
     }
 }
 
-pub fn execute_os(name: &str, command: &mut Command) -> RunResult {
+#[derive(Clone, PartialEq, Debug)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TaskOutput {
+    pub task_name: String,
+    pub stream: OutputStream,
+    pub line: String,
+}
+
+pub fn execute_os(name: &str, command: &mut Command, spill: &SpillPolicy) -> RunResult {
+    execute_os_with_output_handler(name,
+                                   command,
+                                   &TaskLimits::default(),
+                                   spill,
+                                   None,
+                                   |_, _| {})
+}
+
+// the task's process is tracked while it runs, so it can be cancelled
+pub fn execute_os_controlled(name: &str,
+                             command: &mut Command,
+                             limits: &TaskLimits,
+                             spill: &SpillPolicy,
+                             output_channel: Option<&SyncSender<TaskOutput>>,
+                             running: &RunningTasks)
+                             -> RunResult {
+    execute_os_with_output_handler(name, command, limits, spill, Some(running), |stream, line| {
+        if let Some(output_channel) = output_channel {
+            output_channel.send(TaskOutput {
+                    task_name: name.to_string(),
+                    stream: stream,
+                    line: line.to_string(),
+                })
+                .ok();
+        }
+    })
+}
+
+pub fn execute_os_streaming(name: &str,
+                            command: &mut Command,
+                            limits: &TaskLimits,
+                            spill: &SpillPolicy,
+                            output_channel: &SyncSender<TaskOutput>)
+                            -> RunResult {
+    execute_os_with_output_handler(name, command, limits, spill, None, |stream, line| {
+        // a consumer that has gone away shouldn't stop the task from running
+        output_channel.send(TaskOutput {
+                task_name: name.to_string(),
+                stream: stream,
+                line: line.to_string(),
+            })
+            .ok();
+    })
+}
+
+fn spawn_line_reader<R>(pipe: Option<R>,
+                        stream: OutputStream,
+                        lines: mpsc::Sender<(OutputStream, Vec<u8>)>)
+                        -> Option<thread::JoinHandle<()>>
+    where R: Read + Send + 'static
+{
+    pipe.map(|p| {
+        thread::spawn(move || {
+            let mut reader = BufReader::new(p);
+            loop {
+                let mut line = vec![];
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if lines.send((stream.clone(), line)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        })
+    })
+}
+
+fn timeval_to_duration(tv: &libc::timeval) -> Duration {
+    Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000)
+}
+
+// like child.wait(), but reaps the process with wait4 so its rusage can be collected
+fn wait_with_resource_usage(child: &mut Child) -> io::Result<(ExitStatus, ResourceUsage)> {
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+    loop {
+        let pid = unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) };
+        if pid != -1 {
+            break;
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+
+    // linux reports ru_maxrss in kilobytes, macOS in bytes
+    let max_rss_kb = if cfg!(target_os = "macos") {
+        usage.ru_maxrss as u64 / 1024
+    } else {
+        usage.ru_maxrss as u64
+    };
+
+    let resource_usage = ResourceUsage {
+        max_rss_kb: max_rss_kb,
+        user_time: timeval_to_duration(&usage.ru_utime),
+        system_time: timeval_to_duration(&usage.ru_stime),
+    };
+
+    Ok((ExitStatus::from_raw(status), resource_usage))
+}
+
+// a task that's printed nothing for its idle timeout is sent SIGTERM, then SIGKILL if it's still
+// going after the grace period; a process that outlives even that (and keeps the task's pipes
+// open) is left behind rather than holding up the job
+enum Idle {
+    Active,
+    Terminated(Vec<u32>),
+    Killed,
+}
+
+// the OS error is kept as the cause, so it can be told apart from what factotum was doing
+fn execution_error(name: &str, doing: &str, cause: io::Error) -> FactotumError {
+    FactotumError::new(ErrorKind::Execution, format!("{} - {}", doing, cause))
+        .for_task(name)
+        .caused_by(FactotumError::new(ErrorKind::Io, cause.to_string()))
+}
+
+fn execute_os_with_output_handler<F>(name: &str,
+                                     command: &mut Command,
+                                     limits: &TaskLimits,
+                                     spill: &SpillPolicy,
+                                     running: Option<&RunningTasks>,
+                                     mut on_line: F)
+                                     -> RunResult
+    where F: FnMut(OutputStream, &str)
+{
     let run_start = Instant::now();
     info!("Executing sh {:?}", command);
-    match command.output() {
-        Ok(r) => {
+
+    // the task's command decides its stdin
+    let child = command.stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+
+    let mut child = match child {
+        Ok(c) => c,
+        Err(message) => {
+            return RunResult {
+                duration: Duration::from_secs(0),
+                task_execution_error: Some(execution_error(name,
+                                                           "Error executing process",
+                                                           message)),
+                stdout: None,
+                stderr: None,
+                return_code: -1,
+                resource_usage: None,
+                spilled_output: None,
+            }
+        }
+    };
+
+    if let Some(running) = running {
+        running.started(name, child.id());
+    }
+
+    let (tx, rx) = mpsc::channel::<(OutputStream, Vec<u8>)>();
+    let readers = vec![spawn_line_reader(child.stdout.take(), OutputStream::Stdout, tx.clone()),
+                       spawn_line_reader(child.stderr.take(), OutputStream::Stderr, tx)];
+
+    let mut stdout_capture = OutputCapture::new(name, "stdout", spill);
+    let mut stderr_capture = OutputCapture::new(name, "stderr", spill);
+
+    // finishes once both pipes have been closed; what an idle task printed before it was
+    // stopped is still read
+    let mut idle = Idle::Active;
+    let mut abandoned = false;
+    loop {
+        let received = match (&idle, limits.idle_timeout) {
+            (&Idle::Active, Some(idle_timeout)) => rx.recv_timeout(idle_timeout),
+            (&Idle::Active, None) => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            (&Idle::Terminated(_), _) => rx.recv_timeout(limits.idle_kill_grace),
+            (&Idle::Killed, _) => rx.recv_timeout(Duration::from_secs(5)),
+        };
+        let (stream, line) = match received {
+            Ok(received) => received,
+            Err(RecvTimeoutError::Timeout) => {
+                idle = match idle {
+                    Idle::Active => {
+                        warn!("task '{}' printed nothing for {}s, stopping it",
+                              name,
+                              limits.idle_timeout.map(|t| t.as_secs()).unwrap_or(0));
+                        let tree = control::process_tree(child.id());
+                        if let Err(msg) = control::signal_tree(&tree, libc::SIGTERM) {
+                            warn!("couldn't stop task '{}' ({})", name, msg);
+                        }
+                        Idle::Terminated(tree)
+                    }
+                    Idle::Terminated(mut tree) => {
+                        warn!("task '{}' didn't stop within {}s, killing it",
+                              name,
+                              limits.idle_kill_grace.as_secs());
+                        tree.extend(control::process_tree(child.id()));
+                        if let Err(msg) = control::signal_tree(&tree, libc::SIGKILL) {
+                            warn!("couldn't kill task '{}' ({})", name, msg);
+                        }
+                        Idle::Killed
+                    }
+                    Idle::Killed => {
+                        warn!("task '{}' was killed but its output is still open, leaving it",
+                              name);
+                        abandoned = true;
+                        break;
+                    }
+                };
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        on_line(stream.clone(),
+                String::from_utf8_lossy(&line).trim_end_matches(|c| c == '\n' || c == '\r'));
+        match stream {
+            OutputStream::Stdout => stdout_capture.push(line),
+            OutputStream::Stderr => stderr_capture.push(line),
+        }
+    }
+
+    let timed_out = match idle {
+        Idle::Active => false,
+        _ => true,
+    };
+    if !abandoned {
+        for reader in readers.into_iter().filter_map(|r| r) {
+            reader.join().ok();
+        }
+    }
+
+    let waited = wait_with_resource_usage(&mut child);
+    let cancelled = running.map(|r| r.finished(name)).unwrap_or(false);
+
+    match waited {
+        Ok((status, resource_usage)) => {
             let run_duration = run_start.elapsed();
-            let return_code = r.status.code().unwrap_or(1); // 1 will be returned if the process was killed by a signal
+            let return_code = status.code().unwrap_or(1); // 1 will be returned if the process was killed by a signal
 
-            let task_stdout: String = String::from_utf8_lossy(&r.stdout).trim_right().into();
-            let task_stderr: String = String::from_utf8_lossy(&r.stderr).trim_right().into();
+            let (task_stdout_opt, stdout_file) = stdout_capture.finish();
+            let (task_stderr_opt, stderr_file) = stderr_capture.finish();
 
-            info!("task '{}' stdout:\n'{}'", name, task_stdout);
-            info!("task '{}' stderr:\n'{}'", name, task_stderr);
+            info!("task '{}' stdout:\n'{}'",
+                  name,
+                  task_stdout_opt.as_ref().map(|s| s.as_str()).unwrap_or(""));
+            info!("task '{}' stderr:\n'{}'",
+                  name,
+                  task_stderr_opt.as_ref().map(|s| s.as_str()).unwrap_or(""));
 
-            let task_stdout_opt = if task_stdout.is_empty() {
-                None
+            let spilled_output = if stdout_file.is_some() || stderr_file.is_some() {
+                Some(SpilledOutput {
+                    stdout_file: stdout_file,
+                    stderr_file: stderr_file,
+                })
             } else {
-                Some(task_stdout)
-            };
-            let task_stderr_opt = if task_stderr.is_empty() {
                 None
+            };
+
+            // a cancelled or idle task fails whatever its exit code
+            let (task_execution_error, return_code) = if cancelled {
+                (Some(FactotumError::new(ErrorKind::Cancelled,
+                                         "the task was cancelled".to_string())
+                     .for_task(name)),
+                 -1)
+            } else if timed_out {
+                (Some(FactotumError::new(ErrorKind::TimedOut,
+                                         format!("the task printed nothing for {}s",
+                                                 limits.idle_timeout
+                                                     .map(|t| t.as_secs())
+                                                     .unwrap_or(0)))
+                     .for_task(name)),
+                 -1)
             } else {
-                Some(task_stderr)
+                (None, return_code)
             };
 
             RunResult {
                 duration: run_duration,
-                task_execution_error: None,
+                task_execution_error: task_execution_error,
                 stdout: task_stdout_opt,
                 stderr: task_stderr_opt,
                 return_code: return_code,
+                resource_usage: Some(resource_usage),
+                spilled_output: spilled_output,
             }
         }
         Err(message) => {
             RunResult {
-                duration: Duration::from_secs(0),
-                task_execution_error: Some(format!("Error executing process - {}", message)),
+                duration: run_start.elapsed(),
+                task_execution_error: Some(execution_error(name,
+                                                           "Error waiting for process",
+                                                           message)),
                 stdout: None,
                 stderr: None,
                 return_code: -1,
+                resource_usage: None,
+                spilled_output: None,
             }
         }
     }
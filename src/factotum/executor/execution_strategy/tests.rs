@@ -13,6 +13,7 @@
 //
 
 use factotum::executor::execution_strategy::*;
+use factotum::errors::ErrorKind;
 use std::process::Command;
 use chrono::duration::Duration;
 use std::cmp;
@@ -80,7 +81,7 @@ fn simulation_text_good() {
 fn simulation_returns_good() {
     let mut command: Command = Command::new("banana");
     command.arg("hello_world");
-    let result = execute_simulation("hello-world", &mut command);
+    let result = execute_simulation("hello-world", &mut command, &TaskLimits::default());
 
     assert_eq!(result.return_code, 0);
     assert_eq!(result.duration, Duration::seconds(0).to_std().ok().unwrap());
@@ -94,7 +95,7 @@ fn os_execution_notfound() {
     let mut command: Command = Command::new("sh");
     command.arg("-c");
     command.arg("banana");
-    let result = execute_os("hello-world", &mut command);
+    let result = execute_os("hello-world", &mut command, &SpillPolicy::default());
 
     assert_eq!(result.return_code, 127);
     assert_eq!(result.duration.as_secs(), 0);
@@ -109,15 +110,18 @@ fn os_execution_notfound() {
 #[test]
 fn os_execution_task_exec_failed() {
     let mut command: Command = Command::new("this-doesn't-exist");
-    let result = execute_os("hello-world", &mut command);
+    let result = execute_os("hello-world", &mut command, &SpillPolicy::default());
 
     assert_eq!(result.return_code, -1);
     assert_eq!(result.duration.as_secs(), 0);
     assert_eq!(result.stderr, None);
     assert_eq!(result.stdout, None);
     let expected_msg = "Error executing process - No such file or directory".to_string();
-    assert_eq!(result.task_execution_error.unwrap()[..expected_msg.len()],
-               expected_msg);
+    let error = result.task_execution_error.unwrap();
+    assert_eq!(error.message[..expected_msg.len()], expected_msg);
+    assert_eq!(error.kind, ErrorKind::Execution);
+    assert_eq!(error.task, Some("hello-world".to_string()));
+    assert_eq!(error.source.unwrap().kind, ErrorKind::Io);
 }
 
 #[test]
@@ -125,7 +129,7 @@ fn os_execution_good() {
     let mut command: Command = Command::new("sh");
     command.arg("-c");
     command.arg("type echo");
-    let result = execute_os("hello-world", &mut command);
+    let result = execute_os("hello-world", &mut command, &SpillPolicy::default());
 
     assert_eq!(result.return_code, 0);
     assert_eq!(result.duration.as_secs(), 0);
@@ -133,3 +137,162 @@ fn os_execution_good() {
     assert_eq!(result.stdout.unwrap(), "echo is a shell builtin");
     assert_eq!(result.task_execution_error, None);
 }
+
+#[test]
+fn os_execution_streams_lines() {
+    use std::sync::mpsc;
+
+    let (tx, rx) = mpsc::sync_channel::<TaskOutput>(10);
+    let mut command: Command = Command::new("sh");
+    command.arg("-c");
+    command.arg("echo hello; echo oops 1>&2; echo world");
+    let result = execute_os_streaming("hello-world",
+                                      &mut command,
+                                      &TaskLimits::default(),
+                                      &SpillPolicy::default(),
+                                      &tx);
+    drop(tx);
+
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.stdout.unwrap(), "hello\nworld");
+    assert_eq!(result.stderr.unwrap(), "oops");
+
+    let lines = rx.iter().collect::<Vec<TaskOutput>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|l| l.task_name == "hello-world"));
+
+    let stdout_lines = lines.iter()
+        .filter(|l| l.stream == OutputStream::Stdout)
+        .map(|l| l.line.clone())
+        .collect::<Vec<String>>();
+    assert_eq!(stdout_lines, vec!["hello", "world"]);
+    assert!(lines.contains(&TaskOutput {
+        task_name: "hello-world".to_string(),
+        stream: OutputStream::Stderr,
+        line: "oops".to_string(),
+    }));
+}
+
+#[test]
+fn os_execution_stops_idle_tasks() {
+    use factotum::control::RunningTasks;
+    use std::time;
+
+    let limits = TaskLimits {
+        idle_timeout: Some(time::Duration::from_secs(1)),
+        idle_kill_grace: time::Duration::from_secs(1),
+    };
+    let run = |name: &str, script: &str| {
+        let mut command: Command = Command::new("sh");
+        command.arg("-c");
+        command.arg(script);
+        execute_os_controlled(name,
+                              &mut command,
+                              &limits,
+                              &SpillPolicy::default(),
+                              None,
+                              &RunningTasks::default())
+    };
+
+    let result = run("stuck", "echo started; sleep 10; echo finished");
+
+    assert!(result.duration.as_secs() < 10);
+    assert_eq!(result.return_code, -1);
+    assert_eq!(result.stdout.unwrap(), "started");
+    let error = result.task_execution_error.unwrap();
+    assert_eq!(error.kind, ErrorKind::TimedOut);
+    assert_eq!(error.message, "the task printed nothing for 1s");
+
+    // a task that ignores SIGTERM is killed once the grace period's up
+    let result = run("stubborn", "trap '' TERM; echo started; sleep 10");
+    assert!(result.duration.as_secs() < 10);
+    assert_eq!(result.stdout.unwrap(), "started");
+    assert_eq!(result.task_execution_error.map(|e| e.kind), Some(ErrorKind::TimedOut));
+
+    // the timeout is only for time without output
+    let result = run("chatty", "for i in 1 2 3 4; do echo $i; sleep 0.5; done");
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.stdout.unwrap(), "1\n2\n3\n4");
+    assert_eq!(result.task_execution_error, None);
+}
+
+#[test]
+fn os_execution_records_resource_usage() {
+    let mut command: Command = Command::new("sh");
+    command.arg("-c");
+    command.arg("i=0; while [ $i -lt 20000 ]; do i=$((i+1)); done");
+    let result = execute_os("busy", &mut command, &SpillPolicy::default());
+
+    assert_eq!(result.return_code, 0);
+    let usage = result.resource_usage.unwrap();
+    assert!(usage.max_rss_kb > 0);
+    assert!(usage.user_time + usage.system_time > Duration::seconds(0).to_std().unwrap());
+
+    let mut missing: Command = Command::new("this-doesn't-exist");
+    assert_eq!(execute_os("missing", &mut missing, &SpillPolicy::default()).resource_usage, None);
+}
+
+#[test]
+fn os_execution_spills_large_output() {
+    use std::env;
+    use std::fs;
+
+    let spill = SpillPolicy {
+        threshold: 100,
+        dir: env::temp_dir().join("factotum-spill-test"),
+    };
+    let mut command: Command = Command::new("sh");
+    command.arg("-c");
+    command.arg("i=0; while [ $i -lt 1000 ]; do i=$((i+1)); echo line $i; done; echo oops 1>&2");
+    let result = execute_os("chatty task", &mut command, &spill);
+
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.stderr, Some("oops".to_string()));
+
+    // only whole lines from the end are kept in memory
+    let stdout = result.stdout.unwrap();
+    assert!(stdout.len() <= 100);
+    assert!(stdout.starts_with("line "));
+    assert!(stdout.ends_with("line 999\nline 1000"));
+
+    let spilled = result.spilled_output.unwrap();
+    assert_eq!(spilled.stderr_file, None);
+    let stdout_file = spilled.stdout_file.unwrap();
+    assert!(stdout_file.starts_with(&spill.dir));
+    let full = fs::read_to_string(&stdout_file).unwrap();
+    assert_eq!(full.lines().count(), 1000);
+    assert!(full.starts_with("line 1\nline 2\n"));
+    assert!(fs::remove_file(&stdout_file).is_ok());
+}
+
+#[test]
+fn os_execution_small_output_isnt_spilled() {
+    use std::env;
+    use std::fs::{self, File};
+
+    // a directory can't be created under a file
+    let not_a_dir = env::temp_dir().join("factotum-spill-test-file");
+    File::create(&not_a_dir).unwrap();
+    let spill = SpillPolicy {
+        threshold: 100,
+        dir: not_a_dir.join("output"),
+    };
+    let mut command: Command = Command::new("sh");
+    command.arg("-c");
+    command.arg("echo hello");
+    let result = execute_os("quiet task", &mut command, &spill);
+
+    assert_eq!(result.stdout, Some("hello".to_string()));
+    assert_eq!(result.spilled_output, None);
+
+    // the tail is still kept if the spill file can't be written
+    let mut chatty: Command = Command::new("sh");
+    chatty.arg("-c");
+    chatty.arg("i=0; while [ $i -lt 100 ]; do i=$((i+1)); echo line $i; done");
+    let result = execute_os("chatty task", &mut chatty, &spill);
+
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.spilled_output, None);
+    assert!(result.stdout.unwrap().ends_with("line 100"));
+    assert!(fs::remove_file(&not_a_dir).is_ok());
+}
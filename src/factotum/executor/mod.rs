@@ -14,6 +14,7 @@
 
 pub mod execution_strategy;
 pub mod task_list;
+pub mod plan;
 #[cfg(test)]
 mod tests;
 
@@ -21,10 +22,40 @@ use factotum::executor::task_list::*;
 use factotum::executor::execution_strategy::*;
 use chrono::UTC;
 use factotum::factfile::Task as FactfileTask;
-use factotum::factfile::Factfile;
-use std::process::Command;
+use factotum::factfile::{Factfile, Shell, TaskInput, TaskWorkspace, UpstreamSkipped};
+use factotum::waitfor;
+use factotum::assertion;
+use factotum::emr;
+use factotum::batch;
+use factotum::gcp;
+use factotum::dbt;
+use factotum::spark;
+use factotum::sql;
+use factotum::constraint;
+use factotum::expression;
+use factotum::report;
+use factotum::nested;
+use factotum::generator;
+use factotum::parser;
+use factotum::sandbox;
+use factotum::workspace;
+use factotum::cache;
+use factotum::manifest;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::thread;
+use libc;
+use std::time::Duration;
 use std::sync::mpsc;
+use std::collections::HashMap;
+
+// a barrier runs nothing - it only joins the branches it depends on
+pub const BARRIER_EXECUTOR: &'static str = "barrier";
 
 pub fn get_task_execution_list(factfile: &Factfile,
                                start_from: Option<String>)
@@ -154,21 +185,267 @@ pub fn get_task_snapshot(tasklist: &TaskList<&FactfileTask>) -> TaskSnapshot {
                     state: task.state.clone(),
                     run_started: task.run_started.clone(),
                     run_result: task.run_result.clone(),
+                    nested_tasks: task.nested_tasks.clone(),
+                    artifacts: task.artifacts.clone(),
+                    cache_key: task.cache_key.clone(),
+                }
+            })
+        })
+        .collect()
+}
+
+fn set_state(tasklist: &mut TaskList<&FactfileTask>,
+             name: &str,
+             state: State)
+             -> Option<TaskTransition> {
+    tasklist.get_task_by_name(name).map(|task| {
+        let transition = TaskTransition::new(&task.name, task.state.clone(), state.clone());
+        task.state = state;
+        transition
+    })
+}
+
+// the tasks downstream of a failed one can't run
+fn skip_descendants(tasklist: &mut TaskList<&FactfileTask>,
+                    name: &str,
+                    reason: &str)
+                    -> Vec<TaskTransition> {
+    let mut transitions = vec![];
+    for descendant in tasklist.get_descendants(name) {
+        if tasklist.get_task_by_name(&descendant).map(|t| t.state == State::Waiting) ==
+           Some(true) {
+            transitions.extend(set_state(tasklist, &descendant, State::Skipped(reason.into())));
+        }
+    }
+    transitions
+}
+
+// each task downstream of a skipped one runs, is skipped too or fails as its
+// whenUpstreamSkipped says; a task that doesn't say is skipped, unless the task it depends
+// on was only disabled (skip_by_default is false)
+fn skip_downstream(tasklist: &mut TaskList<&FactfileTask>,
+                   skipped: &str,
+                   skip_by_default: bool)
+                   -> Vec<TaskTransition> {
+    let reason = format!("the task '{}' was skipped", skipped);
+    let mut transitions = vec![];
+    let mut to_visit = tasklist.get_children(skipped)
+        .into_iter()
+        .map(|child| (child, skip_by_default))
+        .collect::<Vec<(String, bool)>>();
+
+    while let Some((name, skip_by_default)) = to_visit.pop() {
+        let when_skipped = match tasklist.get_task_by_name(&name) {
+            Some(ref task) if task.state == State::Waiting => {
+                match task.task_spec.when_upstream_skipped {
+                    Some(when_skipped) => when_skipped,
+                    None if skip_by_default => UpstreamSkipped::Skip,
+                    None => UpstreamSkipped::Run,
+                }
+            }
+            _ => continue,
+        };
+
+        match when_skipped {
+            UpstreamSkipped::Run => {}
+            UpstreamSkipped::Skip => {
+                info!("Skipping task '{}': {}", name, reason);
+                transitions.extend(set_state(tasklist, &name, State::Skipped(reason.clone())));
+                to_visit.extend(tasklist.get_children(&name).into_iter().map(|c| (c, true)));
+            }
+            UpstreamSkipped::Fail => {
+                warn!("Failing task '{}': {}", name, reason);
+                transitions.extend(set_state(tasklist, &name, State::Failed(reason.clone())));
+                let failed = format!("the task '{}' failed", name);
+                transitions.extend(skip_descendants(tasklist, &name, &failed));
+            }
+        }
+    }
+    transitions
+}
+
+pub fn skip_tasks(tasklist: &mut TaskList<&FactfileTask>, skip: &HashMap<String, String>) {
+    for (name, reason) in skip.iter() {
+        if !tasklist.is_task_name_present(name) {
+            continue;
+        }
+
+        info!("Skipping task '{}': {}", name, reason);
+        set_state(tasklist, name, State::Skipped(reason.clone()));
+        skip_downstream(tasklist, name, true);
+    }
+}
+
+// unlike a skipped task, a disabled task's dependents still run, unless they say otherwise
+pub fn skip_disabled_tasks(tasklist: &mut TaskList<&FactfileTask>) {
+    let disabled = tasklist.tasks
+        .iter()
+        .flat_map(|tg| tg.iter())
+        .filter(|t| t.task_spec.disabled && t.state == State::Waiting)
+        .map(|t| t.name.clone())
+        .collect::<Vec<String>>();
+
+    for name in disabled {
+        info!("Skipping task '{}': it's disabled", name);
+        set_state(tasklist, &name, State::Skipped("the task is disabled".to_string()));
+        skip_downstream(tasklist, &name, false);
+    }
+}
+
+// like its constraints, a cached task's key is worked out when its turn comes, as the tasks
+// before it may have changed its inputs; its dependents still run when it's skipped, as its
+// result from the earlier run stands
+pub fn apply_task_caches(tasklist: &mut TaskList<&FactfileTask>,
+                         group: usize)
+                         -> Vec<TaskTransition> {
+    let checked = tasklist.tasks[group]
+        .iter()
+        .enumerate()
+        .filter(|&(_, t)| t.state == State::Waiting)
+        .filter_map(|(idx, t)| {
+            match cache::check(t.task_spec) {
+                Ok(Some(checked)) => Some((idx, t.name.clone(), checked)),
+                Ok(None) => None,
+                Err(msg) => {
+                    warn!("The task '{}' can't be cached, so it will run: {}", t.name, msg);
+                    None
                 }
+            }
+        })
+        .collect::<Vec<(usize, String, (String, Option<String>))>>();
+
+    let mut transitions = vec![];
+    for (idx, name, (key, hit)) in checked {
+        tasklist.tasks[group][idx].cache_key = Some(key);
+        if let Some(run_reference) = hit {
+            info!("Skipping task '{}': its result from run {} is reused", name, run_reference);
+            let reason = cache::skip_reason(&run_reference);
+            transitions.extend(set_state(tasklist, &name, State::Skipped(reason)));
+        }
+    }
+    transitions
+}
+
+// a task's constraints are checked when its turn comes rather than as the job starts, as a
+// window or check may have changed by then; returns how the tasks that won't run changed
+pub fn apply_task_constraints(tasklist: &mut TaskList<&FactfileTask>,
+                              group: usize)
+                              -> Vec<TaskTransition> {
+    let now = UTC::now();
+    let unmet = tasklist.tasks[group]
+        .iter()
+        .filter(|t| t.state == State::Waiting)
+        .filter_map(|t| {
+            t.task_spec.constraints.as_ref().and_then(|c| {
+                constraint::unmet(&c.constraints, &now)
+                    .map(|reason| (t.name.clone(), c.fail_job, reason))
+            })
+        })
+        .collect::<Vec<(String, bool, String)>>();
+
+    let mut transitions = vec![];
+    for (name, fail_job, reason) in unmet {
+        warn!("Not running task '{}': {}", name, reason);
+        if fail_job {
+            transitions.extend(set_state(tasklist, &name, State::Failed(reason)));
+            let failed = format!("the task '{}' failed", name);
+            transitions.extend(skip_descendants(tasklist, &name, &failed));
+        } else {
+            transitions.extend(set_state(tasklist, &name, State::Skipped(reason)));
+            transitions.extend(skip_downstream(tasklist, &name, true));
+        }
+    }
+    transitions
+}
+
+// how each finished task turned out, for the when expressions of the tasks after it
+pub fn task_outcomes(tasklist: &TaskList<&FactfileTask>) -> HashMap<String, expression::Outcome> {
+    tasklist.tasks
+        .iter()
+        .flat_map(|group| group.iter())
+        .filter(|task| task.state != State::Waiting && task.state != State::Running)
+        .map(|task| {
+            (task.name.clone(),
+             expression::Outcome {
+                state: report::task_state_name(&task.state).to_string(),
+                exit_code: task.run_result.as_ref().map(|r| r.return_code),
+                output: task.run_result.as_ref().and_then(|r| r.stdout.clone()),
             })
         })
         .collect()
 }
 
+// like its constraints, a task's when expression is checked when its turn comes; when it's
+// false the task is skipped, and a when expression that can't be checked fails the task
+pub fn apply_task_conditions(tasklist: &mut TaskList<&FactfileTask>,
+                             group: usize,
+                             factfile: &Factfile)
+                             -> Vec<TaskTransition> {
+    let outcomes = task_outcomes(tasklist);
+    let checked = tasklist.tasks[group]
+        .iter()
+        .filter(|t| t.state == State::Waiting)
+        .filter_map(|t| {
+            t.task_spec.when.as_ref().map(|when| {
+                // a task's own vars are layered over the job's
+                let mut variables = factfile.variables.clone();
+                if let Some(task_vars) = factfile.task_variables.get(&t.name) {
+                    variables.extend(task_vars.clone());
+                }
+                let context = expression::Context {
+                    variables: variables,
+                    outcomes: outcomes.clone(),
+                };
+                let holds = expression::parse(when).and_then(|e| e.evaluate(&context));
+                (t.name.clone(), when.clone(), holds)
+            })
+        })
+        .collect::<Vec<(String, String, Result<bool, String>)>>();
+
+    let mut transitions = vec![];
+    for (name, when, holds) in checked {
+        match holds {
+            Ok(true) => {}
+            Ok(false) => {
+                info!("Skipping task '{}': '{}' is false", name, when);
+                let reason = format!("its when expression '{}' is false", when);
+                transitions.extend(set_state(tasklist, &name, State::Skipped(reason)));
+                transitions.extend(skip_downstream(tasklist, &name, true));
+            }
+            Err(msg) => {
+                warn!("Failing task '{}': '{}' couldn't be checked: {}", name, when, msg);
+                let reason = format!("its when expression couldn't be checked: {}", msg);
+                transitions.extend(set_state(tasklist, &name, State::Failed(reason)));
+                let failed = format!("the task '{}' failed", name);
+                transitions.extend(skip_descendants(tasklist, &name, &failed));
+            }
+        }
+    }
+    transitions
+}
+
+// the task list as the job starts, with the tasks that won't be run already skipped
+pub fn plan_execution<'a>(factfile: &'a Factfile,
+                          start_from: Option<String>,
+                          skip: &HashMap<String, String>)
+                          -> TaskList<&'a FactfileTask> {
+    let mut tasklist = get_task_execution_list(factfile, start_from);
+    skip_tasks(&mut tasklist, skip);
+    skip_disabled_tasks(&mut tasklist);
+    tasklist
+}
+
 pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
                                start_from: Option<String>,
+                               skip: &HashMap<String, String>,
                                strategy: F,
                                progress_channel: Option<mpsc::Sender<ExecutionUpdate>>)
                                -> TaskList<&'a FactfileTask>
-    where F: Fn(&str, &mut Command) -> RunResult + Send + Sync + 'static + Copy
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
 {
 
-    let mut tasklist = get_task_execution_list(factfile, start_from);
+    let mut tasklist = plan_execution(factfile, start_from, skip);
+    let nested_results = nested::new_result_dir();
 
     // notify the progress channel
     if let Some(ref send) = progress_channel {
@@ -180,9 +457,15 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
         send.send(update).unwrap();
     }
 
-    for task_grp_idx in 0..tasklist.tasks.len() {
+    // a generator task adds groups as the job runs, so the length can't be fixed up front
+    let mut task_grp_idx = 0;
+    while task_grp_idx < tasklist.tasks.len() {
         // everything in a task "group" gets run together
         let (tx, rx) = mpsc::channel::<(usize, RunResult)>();
+        let outputs = task_outputs(&tasklist);
+        let mut unmet_transitions = apply_task_constraints(&mut tasklist, task_grp_idx);
+        unmet_transitions.extend(apply_task_conditions(&mut tasklist, task_grp_idx, factfile));
+        unmet_transitions.extend(apply_task_caches(&mut tasklist, task_grp_idx));
 
         {
             let ref mut task_group = tasklist.tasks[task_grp_idx];
@@ -192,16 +475,20 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
                     info!("Running task '{}'!", task.name);
                     task.state = State::Running;
                     task.run_started = Some(UTC::now());
-                    {
+                    if task.task_spec.executor == BARRIER_EXECUTOR {
+                        tx.send((idx, barrier_result())).unwrap();
+                    } else {
                         let tx = tx.clone();
-                        let args = format_args(&task.task_spec.command, &task.task_spec.arguments);
+                        let strategy = strategy.clone();
+                        let mut command = task_command(task.task_spec, &outputs, &nested_results);
                         let task_name = task.name.to_string();
+                        let limits = TaskLimits {
+                            idle_timeout: task.task_spec.idle_timeout,
+                            ..TaskLimits::default()
+                        };
 
                         thread::spawn(move || {
-                            let mut command = Command::new("sh");
-                            command.arg("-c");
-                            command.arg(args);
-                            let task_result = strategy(&task_name, &mut command);
+                            let task_result = strategy(&task_name, &mut command, &limits);
                             tx.send((idx, task_result)).unwrap();
                         });
                     }
@@ -228,6 +515,15 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
             }
         }
 
+        if !unmet_transitions.is_empty() {
+            if let Some(ref send) = progress_channel {
+                let update = ExecutionUpdate::new(ExecutionState::Running,
+                                                  get_task_snapshot(&tasklist),
+                                                  Transition::Task(unmet_transitions));
+                send.send(update).unwrap();
+            }
+        }
+
         if expected_count > 0 {
 
             if let Some(ref send) = progress_channel {
@@ -258,6 +554,73 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
 
                 let mut additional_transitions = vec![];
 
+                // a dry run can't know what would have been generated or produced
+                let simulated = {
+                    let ref task = tasklist.tasks[task_grp_idx][idx];
+                    (task.task_spec.executor == generator::EXECUTOR ||
+                     !task.task_spec.produces.is_empty()) &&
+                    task_result.stdout ==
+                    Some(simulation_text(&task.name,
+                                         &task_command(task.task_spec,
+                                                       &outputs,
+                                                       &nested_results)))
+                };
+
+                let generated = if tasklist.tasks[task_grp_idx][idx].task_spec.executor ==
+                                   generator::EXECUTOR &&
+                                   tasklist.tasks[task_grp_idx][idx]
+                    .task_spec
+                    .on_result
+                    .continue_job
+                    .contains(&task_result.return_code) {
+                    let ref task = tasklist.tasks[task_grp_idx][idx];
+                    match task_result.stdout {
+                        _ if simulated => None,
+                        ref stdout => {
+                            let existing = tasklist.tasks
+                                .iter()
+                                .flat_map(|g| g.iter().map(|t| t.name.clone()))
+                                .collect::<Vec<String>>();
+                            Some(parser::parse_generated_tasks(stdout.as_ref()
+                                    .map(|s| s.as_str())
+                                    .unwrap_or(""))
+                                .and_then(|tasks| match factfile.policy {
+                                    Some(ref policy) => {
+                                        for generated in tasks.iter() {
+                                            try!(policy.check_task(generated));
+                                        }
+                                        Ok(tasks)
+                                    }
+                                    None => Ok(tasks),
+                                })
+                                .map(|mut tasks| {
+                                    // generated tasks share the run's workspace
+                                    for generated in tasks.iter_mut() {
+                                        generated.workspace = task.task_spec
+                                            .workspace
+                                            .as_ref()
+                                            .map(|w| {
+                                                TaskWorkspace {
+                                                    run_dir: w.run_dir.clone(),
+                                                    task_dir: None,
+                                                }
+                                            });
+                                    }
+                                    tasks
+                                })
+                                .and_then(|tasks| {
+                                    generator::plan_generated(&task.name, tasks, &existing)
+                                }))
+                        }
+                    }
+                } else {
+                    None
+                };
+                let generator_error = match generated {
+                    Some(Err(ref msg)) => Some(msg.clone()),
+                    _ => None,
+                };
+
                 if tasklist.tasks[task_grp_idx][idx]
                     .task_spec
                     .on_result
@@ -273,7 +636,7 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
 
                     for mut task in tasklist.tasks.iter_mut().flat_map(|tg| tg.iter_mut()) {
                         // all the tasks
-                        if skip_list.contains(&task.name) {
+                        if skip_list.binary_search(&task.name).is_ok() {
                             let skip_message = if let State::Skipped(ref msg) = task.state {
                                 format!("{}, the task '{}' requested early termination",
                                         msg,
@@ -292,7 +655,7 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
                     .task_spec
                     .on_result
                     .continue_job
-                    .contains(&task_result.return_code) {
+                    .contains(&task_result.return_code) && generator_error.is_none() {
                     // if the return code is in the continue list, return success
                     tasklist.tasks[task_grp_idx][idx].state = State::Success;
                 } else {
@@ -305,11 +668,16 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
                         .map(|code| code.to_string())
                         .collect::<Vec<String>>()
                         .join(",");
-                    let err_msg = format!("the task exited with a value not specified in \
-                                           continue_job - {} (task expects one of the following \
-                                           return codes to continue [{}])",
-                                          task_result.return_code,
-                                          expected_codes);
+                    let err_msg = match generator_error {
+                        Some(ref msg) => format!("the generated tasks are invalid: {}", msg),
+                        None => {
+                            format!("the task exited with a value not specified in \
+                                     continue_job - {} (task expects one of the following \
+                                     return codes to continue [{}])",
+                                    task_result.return_code,
+                                    expected_codes)
+                        }
+                    };
                     tasklist.tasks[task_grp_idx][idx].state = State::Failed(err_msg);
                     let skip_list =
                         tasklist.get_descendants(&tasklist.tasks[task_grp_idx][idx].name);
@@ -318,7 +686,7 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
 
                     for mut task in tasklist.tasks.iter_mut().flat_map(|tg| tg.iter_mut()) {
                         // all the tasks
-                        if skip_list.contains(&task.name) {
+                        if skip_list.binary_search(&task.name).is_ok() {
                             let skip_message = if let State::Skipped(ref msg) = task.state {
                                 format!("{}, the task '{}' failed", msg, cause_task)
                             } else {
@@ -335,6 +703,37 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
 
                 tasklist.tasks[task_grp_idx][idx].run_result = Some(task_result);
 
+                if tasklist.tasks[task_grp_idx][idx].state == State::Success && !simulated {
+                    let ref mut task = tasklist.tasks[task_grp_idx][idx];
+                    task.artifacts = manifest::build_manifest(&task.task_spec.produces);
+                }
+
+                if let Some(Ok(groups)) = generated {
+                    let generator_name = tasklist.tasks[task_grp_idx][idx].name.clone();
+                    info!("'{}' generated {} task(s)",
+                          generator_name,
+                          groups.iter().map(|g| g.len()).sum::<usize>());
+                    splice_generated(&mut tasklist,
+                                     factfile,
+                                     task_grp_idx,
+                                     &generator_name,
+                                     groups);
+                }
+
+                let executor = tasklist.tasks[task_grp_idx][idx].task_spec.executor.clone();
+                if executor == nested::EXECUTOR || executor == dbt::EXECUTOR {
+                    let ref mut task = tasklist.tasks[task_grp_idx][idx];
+                    // a dry run, or a child that couldn't start, leaves nothing behind
+                    if nested::result_file(&nested_results, &task.name).exists() {
+                        match nested::take_nested_tasks(&nested_results, &task.name) {
+                            Ok(nested_tasks) => task.nested_tasks = nested_tasks,
+                            Err(msg) => {
+                                warn!("Couldn't read the nested run of '{}': {}", task.name, msg)
+                            }
+                        }
+                    }
+                }
+
                 if let Some(ref send) = progress_channel {
                     let exec_task_transition =
                         TaskTransition::new(&tasklist.tasks[task_grp_idx][idx].name,
@@ -350,6 +749,8 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
 
             }
         }
+
+        task_grp_idx += 1;
     }
 
     if let Some(ref send) = progress_channel {
@@ -359,9 +760,281 @@ pub fn execute_factfile<'a, F>(factfile: &'a Factfile,
         send.send(update).unwrap();
     }
 
+    let _ = fs::remove_dir_all(&nested_results);
+
     tasklist
 }
 
+// generated tasks run straight after the generator's group, and before anything that
+// depends on the generator
+fn splice_generated<'a>(tasklist: &mut TaskList<&'a FactfileTask>,
+                        factfile: &'a Factfile,
+                        generator_group: usize,
+                        generator_name: &str,
+                        groups: Vec<Vec<FactfileTask>>) {
+    let children = tasklist.get_children(generator_name);
+    let leaves = generator::leaf_names(&groups);
+    let mut edges = vec![];
+
+    for (offset, group) in groups.into_iter().enumerate() {
+        let task_group = group.into_iter()
+            .map(|spec| {
+                for dep in spec.depends_on.iter() {
+                    edges.push((dep.clone(), spec.name.clone()));
+                }
+                // the factfile's tasks are borrowed for the whole run, so generated
+                // tasks are kept with it
+                let spec = factfile.keep_generated(spec);
+                Task::new(spec.name.clone(), spec)
+            })
+            .collect();
+        // names were checked as the tasks were planned
+        tasklist.insert_group(generator_group + 1 + offset, task_group).unwrap();
+    }
+
+    for leaf in leaves.iter() {
+        for child in children.iter() {
+            edges.push((leaf.clone(), child.clone()));
+        }
+    }
+    for (parent, child) in edges {
+        tasklist.set_child(&parent, &child).unwrap();
+    }
+}
+
+// built-in executors run as a factotum subcommand, so every strategy (simulation,
+// streaming, resource usage) treats them like any other task
+fn barrier_result() -> RunResult {
+    RunResult {
+        duration: Duration::from_secs(0),
+        task_execution_error: None,
+        stdout: None,
+        stderr: None,
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+    }
+}
+
+pub fn task_command(task: &FactfileTask,
+                    outputs: &HashMap<String, String>,
+                    nested_results: &Path)
+                    -> Command {
+    let factotum = |subcommand: &str| {
+        let mut command = Command::new(env::current_exe().unwrap_or(PathBuf::from("factotum")));
+        command.arg(subcommand);
+        command
+    };
+
+    let mut command = if task.executor == waitfor::EXECUTOR {
+        let mut command = factotum("wait");
+        command.arg(&task.command);
+        command.args(&task.arguments);
+        command
+    } else if task.executor == assertion::EXECUTOR {
+        let mut command = factotum("assert");
+        if !task.command.is_empty() {
+            command.arg(format!("--message={}", task.command));
+        }
+        command.args(task.arguments
+            .iter()
+            .map(|a| assertion::substitute_outputs(a, outputs)));
+        command
+    } else if task.executor == emr::EXECUTOR {
+        let mut command = factotum("emr");
+        command.arg(&task.command);
+        command.args(&task.arguments);
+        command
+    } else if task.executor == batch::EXECUTOR {
+        let mut command = factotum("aws-batch");
+        command.arg(&task.command);
+        command.args(&task.arguments);
+        command
+    } else if [gcp::CLOUD_RUN_EXECUTOR,
+                gcp::CLOUD_BATCH_EXECUTOR,
+                sql::EXECUTOR,
+                spark::EXECUTOR]
+        .contains(&&task.executor[..]) {
+        let mut command = factotum(&task.executor);
+        command.arg(&task.command);
+        command.args(&task.arguments);
+        command
+    } else if task.executor == dbt::EXECUTOR {
+        let mut command = factotum("dbt");
+        command.arg(&task.command);
+        command.args(&task.arguments);
+        command.arg(format!("--result-file={}",
+                            nested::result_file(nested_results, &task.name).display()));
+        command
+    } else if task.executor == nested::EXECUTOR {
+        let mut command = factotum("run");
+        command.arg(&task.command);
+        command.arg("--no-colour");
+        command.args(task.arguments.iter().map(|a| format!("--var={}", a)));
+        command.arg(format!("--result-file={}",
+                            nested::result_file(nested_results, &task.name).display()));
+        command
+    } else if task.shell == Shell::Exec {
+        let mut command = Command::new(&task.command);
+        command.args(&task.arguments);
+        command
+    } else {
+        let (shell, flags) = match task.shell {
+            Shell::Bash => ("bash", vec!["-c"]),
+            Shell::Pwsh => ("pwsh", vec!["-NoProfile", "-NonInteractive", "-Command"]),
+            _ => ("sh", vec!["-c"]),
+        };
+        let mut command = Command::new(shell);
+        command.args(&flags);
+        match task.stdin {
+            Some(TaskInput::File(ref path)) if task.shell != Shell::Pwsh => {
+                // the shell opens the file, so a missing one fails the task like any other error
+                command.arg(format!("exec <\"$1\"; {}",
+                                    format_args(&task.command, &task.arguments)));
+                command.arg("factotum-stdin");
+                command.arg(path);
+            }
+            _ => {
+                command.arg(format_args(&task.command, &task.arguments));
+            }
+        }
+        command
+    };
+
+    if task.tty {
+        command = in_tty(&command);
+    }
+
+    let stdin = match task.stdin {
+        Some(TaskInput::Text(ref text)) => {
+            stdin_pipe(text).unwrap_or_else(|e| {
+                warn!("couldn't create a pipe for the stdin of '{}' ({})", task.name, e);
+                Stdio::null()
+            })
+        }
+        Some(TaskInput::File(ref path)) if task.shell == Shell::Exec ||
+                                           task.shell == Shell::Pwsh => {
+            match File::open(path) {
+                Ok(file) => Stdio::from(file),
+                Err(e) => {
+                    // fail when the task starts rather than now, as a dry run never starts it
+                    warn!("couldn't open '{}' for the stdin of '{}' ({})", path, task.name, e);
+                    let errno = e.raw_os_error().unwrap_or(libc::ENOENT);
+                    unsafe {
+                        command.pre_exec(move || Err(io::Error::from_raw_os_error(errno)));
+                    }
+                    Stdio::null()
+                }
+            }
+        }
+        _ => Stdio::null(),
+    };
+    command.stdin(stdin);
+
+    if let Some(ref task_workspace) = task.workspace {
+        command.env(workspace::RUN_ENV_VAR, &task_workspace.run_dir);
+        if let Some(ref task_dir) = task_workspace.task_dir {
+            command.env(workspace::TASK_ENV_VAR, task_dir);
+        }
+    }
+
+    if let Some(ref sandbox) = task.sandbox {
+        // a sandboxed task can always write to its workspace
+        let mut sandbox = sandbox.clone();
+        if let Some(ref task_workspace) = task.workspace {
+            sandbox.writable_paths.push(task_workspace.run_dir.clone());
+        }
+        match sandbox::prepare(&sandbox) {
+            Ok(setup) => unsafe {
+                command.pre_exec(move || setup.enter());
+            },
+            Err(msg) => {
+                // preflight reports why; running the task unsandboxed isn't an option
+                warn!("couldn't sandbox '{}' ({})", task.name, msg);
+                unsafe {
+                    command.pre_exec(|| Err(io::Error::from_raw_os_error(libc::ENOTSUP)));
+                }
+            }
+        }
+    }
+    command
+}
+
+pub const TTY_COMMAND: &'static str = "script";
+
+// script runs the command under a pseudo-terminal and copies what it prints to its own stdout,
+// so the task's output is still captured
+pub fn in_tty(command: &Command) -> Command {
+    let mut script = Command::new(TTY_COMMAND);
+    if cfg!(target_os = "macos") {
+        script.args(&["-q", "/dev/null"]);
+        script.arg(command.get_program());
+        script.args(command.get_args());
+    } else {
+        // util-linux script takes the command as one line, which it runs with $SHELL - so
+        // that has to be sh, whatever the user logs in with
+        let line = Some(command.get_program())
+            .into_iter()
+            .chain(command.get_args())
+            .map(|word| format!("'{}'", word.to_string_lossy().replace("'", "'\\''")))
+            .collect::<Vec<String>>()
+            .join(" ");
+        script.args(&["-qefc", &line, "/dev/null"]);
+        script.env("SHELL", "/bin/sh");
+    }
+    script
+}
+
+fn cloexec_pipe() -> io::Result<(libc::c_int, libc::c_int)> {
+    let mut fds = [0 as libc::c_int; 2];
+    // tasks start from several threads at once, so the pipe has to be close-on-exec from the
+    // start - a write end leaked to another task would stop this one ever seeing the end of it
+    #[cfg(target_os = "linux")]
+    let created = unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) };
+    #[cfg(not(target_os = "linux"))]
+    let created = unsafe {
+        let created = libc::pipe(fds.as_mut_ptr());
+        if created == 0 {
+            libc::fcntl(fds[0], libc::F_SETFD, libc::FD_CLOEXEC);
+            libc::fcntl(fds[1], libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+        created
+    };
+
+    if created == 0 {
+        Ok((fds[0], fds[1]))
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+// the text is written as the task reads it; if the task never runs or stops reading, the
+// write fails once the command is dropped and the writer finishes
+fn stdin_pipe(text: &str) -> io::Result<Stdio> {
+    let (read_fd, write_fd) = try!(cloexec_pipe());
+    let (read_end, mut write_end) =
+        unsafe { (Stdio::from_raw_fd(read_fd), File::from_raw_fd(write_fd)) };
+    let text = text.to_string();
+    thread::spawn(move || {
+        write_end.write_all(text.as_bytes()).ok();
+    });
+    Ok(read_end)
+}
+
+// the stdout of every task that has run so far, by task name
+pub fn task_outputs(tasklist: &TaskList<&FactfileTask>) -> HashMap<String, String> {
+    tasklist.tasks
+        .iter()
+        .flat_map(|group| group.iter())
+        .filter_map(|task| {
+            task.run_result
+                .as_ref()
+                .and_then(|r| r.stdout.clone())
+                .map(|stdout| (task.name.clone(), stdout))
+        })
+        .collect()
+}
+
 pub fn format_args(command: &str, args: &Vec<String>) -> String {
     let arg_str = args.iter()
         .map(|s| format!("\"{}\"", s))
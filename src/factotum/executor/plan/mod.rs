@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::BARRIER_EXECUTOR;
+use factotum::executor::task_list::{State, TaskList};
+use factotum::factfile::Task as FactfileTask;
+use factotum::generator;
+
+fn will_run(state: &State) -> bool {
+    *state == State::Waiting
+}
+
+// each wave is a group of tasks that are started together, once the wave before has finished
+pub fn format_plan(job_name: &str, tasklist: &TaskList<&FactfileTask>) -> String {
+    let running = tasklist.tasks
+        .iter()
+        .map(|grp| grp.iter().filter(|t| will_run(&t.state)).count())
+        .collect::<Vec<usize>>();
+    let name_width = tasklist.tasks
+        .iter()
+        .flat_map(|grp| grp.iter())
+        .map(|t| t.name.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut lines = vec![format!("Execution plan for '{}': {} task(s) will run in {} wave(s), \
+                                  at most {} at once",
+                                 job_name,
+                                 running.iter().sum::<usize>(),
+                                 tasklist.tasks.len(),
+                                 running.iter().max().unwrap_or(&0))];
+
+    for (idx, grp) in tasklist.tasks.iter().enumerate() {
+        lines.push(String::new());
+        lines.push(format!("Wave {}", idx + 1));
+        let mut tasks = grp.iter().collect::<Vec<_>>();
+        tasks.sort_by(|a, b| a.name.cmp(&b.name));
+        for task in tasks {
+            let detail = match task.state {
+                State::Skipped(ref reason) => format!("skipped: {}", reason),
+                _ => {
+                    let mut notes = vec![];
+                    if !task.task_spec.depends_on.is_empty() {
+                        notes.push(format!("after {}", task.task_spec.depends_on.join(", ")));
+                    }
+                    if task.task_spec.executor == BARRIER_EXECUTOR {
+                        notes.push("a barrier, runs nothing".to_string());
+                    } else if task.task_spec.executor == generator::EXECUTOR {
+                        notes.push("adds the tasks it generates to the plan".to_string());
+                    }
+                    notes.join("; ")
+                }
+            };
+            let line = format!("  {} {:width$}  {}",
+                               if will_run(&task.state) { "+" } else { "-" },
+                               task.name,
+                               detail,
+                               width = name_width);
+            lines.push(line.trim_end().to_string());
+        }
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,64 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+use super::*;
+use factotum::executor::plan_execution;
+use factotum::factfile::Factfile;
+use factotum::tests::make_task;
+use std::collections::HashMap;
+
+#[test]
+fn format_plan_good() {
+    let mut ff = Factfile::new("N/A", "etl");
+    ff.add_task_obj(&make_task("extract", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("enrich", &vec!["extract"])).unwrap();
+    let mut vacuum = make_task("vacuum", &vec!["extract"]);
+    vacuum.disabled = true;
+    ff.add_task_obj(&vacuum).unwrap();
+    ff.add_task_obj(&make_task("load", &vec!["enrich", "vacuum"])).unwrap();
+
+    let plan = plan_execution(&ff, None, &HashMap::new());
+
+    assert_eq!(format_plan(&ff.name, &plan),
+               "Execution plan for 'etl': 3 task(s) will run in 3 wave(s), at most 1 at once
+
+Wave 1
+  + extract
+
+Wave 2
+  + enrich   after extract
+  - vacuum   skipped: the task is disabled
+
+Wave 3
+  + load     after enrich, vacuum");
+}
+
+#[test]
+fn format_plan_shows_skipped_descendants() {
+    let mut ff = Factfile::new("N/A", "etl");
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["b"])).unwrap();
+
+    let mut skip = HashMap::new();
+    skip.insert("b".to_string(), "its circuit is open".to_string());
+    let plan = plan_execution(&ff, None, &skip);
+    let text = format_plan(&ff.name, &plan);
+
+    assert!(text.starts_with("Execution plan for 'etl': 1 task(s) will run in 2 wave(s), at \
+                              most 1 at once"));
+    assert!(text.contains("  - b  skipped: its circuit is open"));
+    assert!(text.contains("  - c  skipped: the task 'b' was skipped"));
+}
@@ -14,10 +14,12 @@
 
 #[cfg(test)]
 mod tests;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use factotum::executor::execution_strategy::RunResult;
+use factotum::manifest::Artifact;
 use chrono::UTC;
 use chrono::DateTime;
+use rustc_serialize::json::Json;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum State {
@@ -36,6 +38,12 @@ pub struct Task<T> {
     pub task_spec: T,
     pub run_started: Option<DateTime<UTC>>,
     pub run_result: Option<RunResult>,
+    // the tasks of a nested factfile's run, as they appear in its run result
+    pub nested_tasks: Vec<Json>,
+    // the checksums of what the task produces, once it has succeeded
+    pub artifacts: Vec<Artifact>,
+    // the task's cache key, once its turn has come in a job run with --cache
+    pub cache_key: Option<String>,
 }
 
 impl<T> Task<T> {
@@ -46,6 +54,9 @@ impl<T> Task<T> {
             task_spec: task_spec,
             run_started: None,
             run_result: None,
+            nested_tasks: vec![],
+            artifacts: vec![],
+            cache_key: None,
         }
     }
 }
@@ -67,6 +78,11 @@ impl<T> TaskList<T> {
     }
 
     pub fn add_group(&mut self, tasks: TaskGroup<T>) -> Result<(), String> {
+        let index = self.tasks.len();
+        self.insert_group(index, tasks)
+    }
+
+    pub fn insert_group(&mut self, index: usize, tasks: TaskGroup<T>) -> Result<(), String> {
         {
             let new_edges: Vec<&str> = tasks.iter()
                 .map(|t| t.name.as_ref())
@@ -83,12 +99,12 @@ impl<T> TaskList<T> {
             }
         }
 
-        self.tasks.push(tasks);
+        self.tasks.insert(index, tasks);
         return Ok(());
     }
 
     pub fn set_child(&mut self, parent: &str, child: &str) -> Result<(), String> {
-        if self.get_task_by_name(&child).is_some() {
+        if self.edges.contains_key(child) {
             if let Some(children) = self.edges.get_mut(parent) {
                 children.push(child.to_string());
                 Ok(())
@@ -100,8 +116,9 @@ impl<T> TaskList<T> {
         }
     }
 
+    // every task has an entry in edges, even with no children
     pub fn is_task_name_present(&mut self, name: &str) -> bool {
-        self.get_task_by_name(name).is_some()
+        self.edges.contains_key(name)
     }
 
     pub fn get_task_by_name(&mut self, name: &str) -> Option<&mut Task<T>> {
@@ -115,25 +132,26 @@ impl<T> TaskList<T> {
         None
     }
 
-    pub fn get_descendants(&self, task_name: &str) -> Vec<String> {
-        let mut descendants = self.get_descendants_recursively(task_name);
-        descendants.sort();
-        descendants.dedup();
-        descendants
+    pub fn get_children(&self, task_name: &str) -> Vec<String> {
+        self.edges.get(task_name).cloned().unwrap_or(vec![])
     }
 
-    fn get_descendants_recursively(&self, task_name: &str) -> Vec<String> {
-        let default = &vec![];
-        let deps: Vec<String> =
-            self.edges.get(task_name).unwrap_or(default).iter().map(|x| x.clone()).collect();
-
-        let mut seen = vec![];
-
-        for dep in deps {
-            seen.push(dep.clone());
-            seen.extend(self.get_descendants_recursively(&dep));
+    pub fn get_descendants(&self, task_name: &str) -> Vec<String> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut to_visit = vec![task_name];
+
+        while let Some(name) = to_visit.pop() {
+            if let Some(children) = self.edges.get(name) {
+                for child in children.iter() {
+                    if seen.insert(child) {
+                        to_visit.push(child);
+                    }
+                }
+            }
         }
 
-        return seen;
+        let mut descendants = seen.into_iter().map(|d| d.to_string()).collect::<Vec<String>>();
+        descendants.sort();
+        descendants
     }
 }
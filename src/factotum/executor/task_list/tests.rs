@@ -139,6 +139,34 @@ fn get_children_dups_removed() {
     assert_eq!(Vec::<String>::new(), tl.get_descendants(""))
 }
 
+#[test]
+fn get_descendants_of_stacked_diamonds() {
+    // 40 diamonds stacked on top of each other have 2^40 paths from top to bottom
+    let mut tl = TaskList::<&str>::new();
+    let mut names = vec!["d0".to_string()];
+    for i in 1..41 {
+        names.push(format!("l{}", i));
+        names.push(format!("r{}", i));
+        names.push(format!("d{}", i));
+    }
+    tl.add_group(names.iter().map(|n| Task::<&str>::new(n.clone(), "world")).collect())
+        .ok()
+        .unwrap();
+    for i in 1..41 {
+        let top = format!("d{}", i - 1);
+        let bottom = format!("d{}", i);
+        for side in vec![format!("l{}", i), format!("r{}", i)] {
+            tl.set_child(&top, &side).ok().unwrap();
+            tl.set_child(&side, &bottom).ok().unwrap();
+        }
+    }
+
+    let descendants = tl.get_descendants("d0");
+    assert_eq!(descendants.len(), 120);
+    assert!(descendants.binary_search(&"d40".to_string()).is_ok());
+    assert_eq!(tl.get_descendants("d39"), vec!["d40", "l40", "r40"]);
+}
+
 #[test]
 fn is_task_name_present_good() {
     let mut tl = TaskList::<&str>::new();
@@ -152,3 +180,18 @@ fn is_task_name_present_good() {
     assert!(tl.is_task_name_present("grandchild2"));
     assert!(tl.is_task_name_present("banana") == false);
 }
+
+#[test]
+fn insert_group_keeps_edges() {
+    let mut tl = TaskList::<&str>::new();
+    tl.add_group(vec![Task::<&str>::new("parent", "world")]).ok().unwrap();
+    tl.add_group(vec![Task::<&str>::new("child", "world")]).ok().unwrap();
+    tl.set_child("parent", "child").ok();
+
+    tl.insert_group(1, vec![Task::<&str>::new("middle", "world")]).ok().unwrap();
+    tl.set_child("parent", "middle").ok();
+    assert_eq!(tl.tasks[1][0].name, "middle");
+    assert_eq!(tl.tasks[2][0].name, "child");
+    assert_eq!(vec!["child", "middle"], tl.get_children("parent"));
+    assert!(tl.insert_group(0, vec![Task::<&str>::new("child", "world")]).is_err());
+}
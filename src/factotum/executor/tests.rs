@@ -13,18 +13,23 @@
 //
 
 use factotum::tests::make_task;
+use factotum::constraint::Constraints;
 use factotum::factfile::*;
 use factotum::executor::*;
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration as StdDuration;
 
 #[test]
 fn get_task_execution_list_good() {
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     //        apple----------             turnip
     //       /     \         \
@@ -58,12 +63,12 @@ fn get_task_execution_list_good() {
 #[test]
 fn get_task_execution_list_good_reduced() {
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     let tl = get_task_execution_list(&ff, Some("potato".to_string()));
     assert!(tl.tasks[0].len() == 1);
@@ -78,6 +83,245 @@ fn get_formatted_args() {
     assert_eq!(args_list, "echo \"hello\" \"world\" \"abc abc\"");
 }
 
+#[test]
+fn task_command_by_executor() {
+    let mut shell = make_task("shell", &vec![]);
+    shell.executor = "shell".to_string();
+    shell.command = "echo".to_string();
+    shell.arguments = vec!["hello".to_string()];
+    assert_eq!(format!("{:?}", task_command(&shell, &HashMap::new(), Path::new("/tmp/nested"))),
+               "\"sh\" \"-c\" \"echo \\\"hello\\\"\"");
+
+    let mut wait = make_task("wait", &vec![]);
+    wait.executor = "wait".to_string();
+    wait.command = "tcp://localhost:5432".to_string();
+    wait.arguments = vec!["--timeout=5m".to_string()];
+    let command = format!("{:?}", task_command(&wait, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"wait\" \"tcp://localhost:5432\" \"--timeout=5m\""));
+
+    let mut step = make_task("step", &vec![]);
+    step.executor = "emr".to_string();
+    step.command = "j-1".to_string();
+    step.arguments = vec!["jar=command-runner.jar".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"emr\" \"j-1\" \"jar=command-runner.jar\""));
+
+    step.executor = "aws-batch".to_string();
+    step.command = "enrich:3".to_string();
+    step.arguments = vec!["queue=heavy".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"aws-batch\" \"enrich:3\" \"queue=heavy\""));
+
+    step.executor = "cloud-batch".to_string();
+    step.command = "nightly.json".to_string();
+    step.arguments = vec!["project=acme".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"cloud-batch\" \"nightly.json\" \"project=acme\""));
+
+    step.executor = "dbt".to_string();
+    step.command = "run".to_string();
+    step.arguments = vec!["select=orders".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"dbt\" \"run\" \"select=orders\" \
+                               \"--result-file=/tmp/nested/step.json\""));
+
+    step.executor = "sql".to_string();
+    step.command = "warehouse".to_string();
+    step.arguments = vec!["query=SELECT 1".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"sql\" \"warehouse\" \"query=SELECT 1\""));
+
+    step.executor = "spark".to_string();
+    step.command = "enrich.jar".to_string();
+    step.arguments = vec!["master=yarn".to_string()];
+    let command = format!("{:?}", task_command(&step, &HashMap::new(), Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"spark\" \"enrich.jar\" \"master=yarn\""));
+
+    let mut check = make_task("check", &vec![]);
+    check.executor = "assert".to_string();
+    check.command = "enough rows".to_string();
+    check.arguments = vec!["${count rows} >= 1000".to_string()];
+    let mut outputs = HashMap::new();
+    outputs.insert("count rows".to_string(), "1234\n".to_string());
+    let command = format!("{:?}", task_command(&check, &outputs, Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"assert\" \"--message=enough rows\" \"'1234' >= 1000\""));
+
+    let mut child = make_task("load child", &vec![]);
+    child.executor = "factfile".to_string();
+    child.command = "child.factfile".to_string();
+    child.arguments = vec!["region=eu".to_string()];
+    let command = format!("{:?}", task_command(&child, &outputs, Path::new("/tmp/nested")));
+    assert!(command.ends_with("\" \"run\" \"child.factfile\" \"--no-colour\" \
+                               \"--var=region=eu\" \"--result-file=/tmp/nested/load_child.json\""));
+}
+
+#[test]
+fn task_command_pipes_stdin() {
+    use factotum::factfile::TaskInput;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let mut query = make_task("query", &vec![]);
+    query.executor = "shell".to_string();
+    query.command = "tr".to_string();
+    query.arguments = vec!["a-z".to_string(), "A-Z".to_string()];
+    query.stdin = Some(TaskInput::Text("select 1;\n".repeat(10000)));
+    let mut command = task_command(&query, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("query", &mut command, &SpillPolicy::default());
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.stdout.unwrap().lines().collect::<Vec<&str>>(),
+               vec!["SELECT 1;"; 10000]);
+
+    // a dry run never reads the text
+    let mut command = task_command(&query, &HashMap::new(), Path::new("/tmp/nested"));
+    assert_eq!(execute_simulation("query", &mut command, &TaskLimits::default()).return_code, 0);
+
+    let path = env::temp_dir().join("factotum-stdin-test.csv");
+    File::create(&path).unwrap().write_all(b"id,name\n1,apple\n").unwrap();
+    let mut import = make_task("import", &vec![]);
+    import.executor = "shell".to_string();
+    import.command = "wc".to_string();
+    import.arguments = vec!["-l".to_string()];
+    import.stdin = Some(TaskInput::File(path.to_string_lossy().to_string()));
+    let mut command = task_command(&import, &HashMap::new(), Path::new("/tmp/nested"));
+    assert_eq!(format!("{:?}", command),
+               format!("\"sh\" \"-c\" \"exec <\\\"$1\\\"; wc \\\"-l\\\"\" \"factotum-stdin\" \"{}\"",
+                       path.display()));
+    let result = execute_os("import", &mut command, &SpillPolicy::default());
+    assert_eq!(result.stdout, Some("2".to_string()));
+    assert!(fs::remove_file(&path).is_ok());
+
+    // the task fails if there's no file to read from
+    let mut command = task_command(&import, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("import", &mut command, &SpillPolicy::default());
+    assert!(result.return_code != 0);
+    assert!(result.stderr.unwrap().contains("factotum-stdin-test.csv"));
+}
+
+#[test]
+fn task_command_by_shell() {
+    use factotum::factfile::TaskInput;
+
+    let mut task = make_task("greet", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "echo".to_string();
+    task.arguments = vec!["$HOME; rm -rf /".to_string()];
+
+    task.shell = Shell::Bash;
+    assert_eq!(format!("{:?}", task_command(&task, &HashMap::new(), Path::new("/tmp/nested"))),
+               "\"bash\" \"-c\" \"echo \\\"$HOME; rm -rf /\\\"\"");
+
+    task.shell = Shell::Pwsh;
+    assert_eq!(format!("{:?}", task_command(&task, &HashMap::new(), Path::new("/tmp/nested"))),
+               "\"pwsh\" \"-NoProfile\" \"-NonInteractive\" \"-Command\" \
+                \"echo \\\"$HOME; rm -rf /\\\"\"");
+
+    // without a shell nothing in the arguments is interpreted
+    task.shell = Shell::Exec;
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    assert_eq!(format!("{:?}", command), "\"echo\" \"$HOME; rm -rf /\"");
+    let result = execute_os("greet", &mut command, &SpillPolicy::default());
+    assert_eq!(result.stdout, Some("$HOME; rm -rf /".to_string()));
+
+    task.command = "cat".to_string();
+    task.arguments = vec![];
+    task.stdin = Some(TaskInput::File("Cargo.toml".to_string()));
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("greet", &mut command, &SpillPolicy::default());
+    assert!(result.stdout.unwrap().contains("name = \"factotum\""));
+
+    task.stdin = Some(TaskInput::File("missing.csv".to_string()));
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    assert_eq!(execute_simulation("greet", &mut command, &TaskLimits::default()).return_code, 0);
+    let result = execute_os("greet", &mut command, &SpillPolicy::default());
+    assert_eq!(result.return_code, -1);
+    assert!(result.task_execution_error.unwrap().message.contains("No such file or directory"));
+}
+
+#[test]
+fn task_command_sandboxes_the_task() {
+    let mut task = make_task("sandboxed", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "touch /factotum-sandbox-test; echo $?".to_string();
+    task.sandbox = Some(Sandbox::default());
+
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("sandboxed", &mut command, &SpillPolicy::default());
+    assert_eq!(result.return_code, 0);
+    assert!(result.stdout != Some("0".to_string()));
+    assert!(!Path::new("/factotum-sandbox-test").exists());
+}
+
+#[test]
+fn task_command_gives_the_task_its_workspace() {
+    let mut task = make_task("with workspace", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "echo $FACTOTUM_WORKSPACE $FACTOTUM_TASK_WORKSPACE".to_string();
+    task.workspace = Some(TaskWorkspace {
+        run_dir: "/scratch/abc".to_string(),
+        task_dir: Some("/scratch/abc/with_workspace".to_string()),
+    });
+
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("with workspace", &mut command, &SpillPolicy::default());
+    assert_eq!(result.stdout,
+               Some("/scratch/abc /scratch/abc/with_workspace".to_string()));
+
+    task.workspace = None;
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    let result = execute_os("with workspace", &mut command, &SpillPolicy::default());
+    assert_eq!(result.stdout, None);
+}
+
+#[test]
+fn strategies_are_given_the_idle_timeout() {
+    use std::sync::{Arc, Mutex};
+    let mut silent = make_task("silent", &vec![]);
+    silent.idle_timeout = Some(StdDuration::from_secs(60));
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&silent).unwrap();
+    ff.add_task_obj(&make_task("chatty", &vec![])).unwrap();
+
+    let given = Arc::new(Mutex::new(vec![]));
+    let strategy = {
+        let given = given.clone();
+        move |name: &str, command: &mut Command, limits: &TaskLimits| {
+            given.lock().unwrap().push((name.to_string(), limits.idle_timeout));
+            execute_simulation(name, command, limits)
+        }
+    };
+    execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    let mut given = given.lock().unwrap().clone();
+    given.sort();
+    assert_eq!(given,
+               vec![("chatty".to_string(), None),
+                    ("silent".to_string(), Some(StdDuration::from_secs(60)))]);
+}
+
+#[test]
+fn task_command_in_a_tty() {
+    let mut task = make_task("colours", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "test -t 1 && echo".to_string();
+    task.arguments = vec!["it's a tty".to_string()];
+    task.tty = true;
+
+    let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+    if cfg!(target_os = "macos") {
+        assert_eq!(format!("{:?}", command),
+                   "\"script\" \"-q\" \"/dev/null\" \"sh\" \"-c\" \
+                    \"test -t 1 && echo \\\"it's a tty\\\"\"");
+    } else {
+        assert!(format!("{:?}", command)
+            .contains("\"script\" \"-qefc\" \"'sh' '-c' 'test -t 1 && echo \\\"it'\\\\''s a \
+                       tty\\\"'\" \"/dev/null\""));
+    }
+    let result = execute_os("colours", &mut command, &SpillPolicy::default());
+    assert_eq!(result.return_code, 0);
+    assert_eq!(result.stdout, Some("it's a tty".to_string()));
+}
+
 #[test]
 fn get_task_snapshot_clones() {
 
@@ -87,8 +331,8 @@ fn get_task_snapshot_clones() {
     use chrono::duration::Duration;
 
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec!["apple"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec!["apple"])).unwrap();
 
     let mut tl = get_task_execution_list(&ff, None);
 
@@ -98,6 +342,8 @@ fn get_task_snapshot_clones() {
     tl.tasks[0][0].run_started = Some(UTC::now());
     tl.tasks[0][0].run_result = Some(RunResult {
         return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
         stderr: Some("hello world".to_string()),
         stdout: Some("hello world".to_string()),
         duration: Duration::seconds(0).to_std().ok().unwrap(),
@@ -129,12 +375,12 @@ fn execute_sends_started_msg() {
     use std::time::Duration;
 
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     let task_count_in_factfile = 6;
 
@@ -142,6 +388,7 @@ fn execute_sends_started_msg() {
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -175,7 +422,7 @@ fn execute_sends_running_messages() {
 
     for mut task in tasks.into_iter() {
         task.on_result.continue_job.push(0);
-        ff.add_task_obj(&task);
+        ff.add_task_obj(&task).unwrap();
     }
 
     let task_count_in_factfile = 6;
@@ -193,6 +440,7 @@ fn execute_sends_running_messages() {
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -259,13 +507,14 @@ fn execute_sends_updates_for_skipped_fail() {
         } else {
             task.on_result.continue_job.push(1);
         }
-        ff.add_task_obj(&task);
+        ff.add_task_obj(&task).unwrap();
     }
 
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -350,13 +599,14 @@ fn execute_sends_failed_skipped_messages() {
 
     for mut task in tasks.into_iter() {
         task.on_result.continue_job.push(1);
-        ff.add_task_obj(&task);
+        ff.add_task_obj(&task).unwrap();
     }
 
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -481,13 +731,14 @@ fn execute_sends_updates_for_skipped_noop() {
         } else {
             task.on_result.terminate_job.push(0);
         }
-        ff.add_task_obj(&task);
+        ff.add_task_obj(&task).unwrap();
     }
 
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -572,13 +823,14 @@ fn execute_sends_noop_skipped_messages() {
 
     for mut task in tasks.into_iter() {
         task.on_result.terminate_job.push(0);
-        ff.add_task_obj(&task);
+        ff.add_task_obj(&task).unwrap();
     }
 
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
 
     execute_factfile(&ff,
                      None,
+                     &HashMap::new(),
                      execution_strategy::execute_simulation,
                      Some(tx.clone()));
 
@@ -712,3 +964,585 @@ fn execute_sends_noop_skipped_messages() {
 }
 
 // todo write test for rejecting non "shell" execution types
+
+#[test]
+fn skip_tasks_skips_descendants() {
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+
+    let mut tl = get_task_execution_list(&ff, None);
+    let mut skip = HashMap::new();
+    skip.insert("apple".to_string(), "circuit breaker open".to_string());
+    skip.insert("missing".to_string(), "not in the job".to_string());
+    skip_tasks(&mut tl, &skip);
+
+    assert_eq!(tl.get_task_by_name("apple").unwrap().state,
+               State::Skipped("circuit breaker open".to_string()));
+    assert_eq!(tl.get_task_by_name("orange").unwrap().state,
+               State::Skipped("the task 'apple' was skipped".to_string()));
+    assert_eq!(tl.get_task_by_name("turnip").unwrap().state, State::Waiting);
+}
+
+#[test]
+fn skipped_tasks_follow_when_upstream_skipped() {
+    //   apple --- orange --- pear
+    //        \--- lemon --- lime
+    //         \-- grape --- plum
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    let mut orange = make_task("orange", &vec!["apple"]);
+    orange.when_upstream_skipped = Some(UpstreamSkipped::Run);
+    ff.add_task_obj(&orange).unwrap();
+    ff.add_task_obj(&make_task("pear", &vec!["orange"])).unwrap();
+    let mut lemon = make_task("lemon", &vec!["apple"]);
+    lemon.when_upstream_skipped = Some(UpstreamSkipped::Fail);
+    ff.add_task_obj(&lemon).unwrap();
+    ff.add_task_obj(&make_task("lime", &vec!["lemon"])).unwrap();
+    ff.add_task_obj(&make_task("grape", &vec!["apple"])).unwrap();
+    let mut plum = make_task("plum", &vec!["grape"]);
+    plum.when_upstream_skipped = Some(UpstreamSkipped::Run);
+    ff.add_task_obj(&plum).unwrap();
+
+    let mut tl = get_task_execution_list(&ff, None);
+    let mut skip = HashMap::new();
+    skip.insert("apple".to_string(), "circuit breaker open".to_string());
+    skip_tasks(&mut tl, &skip);
+
+    let skipped = State::Skipped("the task 'apple' was skipped".to_string());
+    assert_eq!(tl.get_task_by_name("orange").unwrap().state, State::Waiting);
+    assert_eq!(tl.get_task_by_name("pear").unwrap().state, State::Waiting);
+    assert_eq!(tl.get_task_by_name("lemon").unwrap().state,
+               State::Failed("the task 'apple' was skipped".to_string()));
+    assert_eq!(tl.get_task_by_name("lime").unwrap().state,
+               State::Skipped("the task 'lemon' failed".to_string()));
+    assert_eq!(tl.get_task_by_name("grape").unwrap().state, skipped);
+    assert_eq!(tl.get_task_by_name("plum").unwrap().state, State::Waiting);
+}
+
+#[test]
+fn disabled_tasks_dependents_can_be_skipped() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut vacuum = make_task("vacuum", &vec![]);
+    vacuum.disabled = true;
+    ff.add_task_obj(&vacuum).unwrap();
+    let mut load = make_task("load", &vec!["vacuum"]);
+    load.when_upstream_skipped = Some(UpstreamSkipped::Skip);
+    ff.add_task_obj(&load).unwrap();
+    ff.add_task_obj(&make_task("report", &vec!["load"])).unwrap();
+    ff.add_task_obj(&make_task("archive", &vec!["vacuum"])).unwrap();
+
+    let mut tl = get_task_execution_list(&ff, None);
+    skip_disabled_tasks(&mut tl);
+
+    let skipped = State::Skipped("the task 'vacuum' was skipped".to_string());
+    assert_eq!(tl.get_task_by_name("load").unwrap().state, skipped);
+    assert_eq!(tl.get_task_by_name("report").unwrap().state, skipped);
+    assert_eq!(tl.get_task_by_name("archive").unwrap().state, State::Waiting);
+}
+
+fn generator_factfile() -> Factfile {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut discover = make_task("discover", &vec![]);
+    discover.executor = "generator".to_string();
+    discover.command = "discover".to_string();
+    discover.on_result.continue_job.push(0);
+    let mut report = make_task("report", &vec!["discover"]);
+    report.on_result.continue_job.push(0);
+    ff.add_task_obj(&discover).unwrap();
+    ff.add_task_obj(&report).unwrap();
+    ff
+}
+
+fn generator_strategy(output: &'static str)
+    -> impl Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + Clone {
+    move |name: &str, _: &mut Command, _: &TaskLimits| {
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: Some(if name == "discover" { output } else { name }.to_string()),
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    }
+}
+
+#[test]
+fn generated_tasks_are_spliced_in() {
+    let ff = generator_factfile();
+    let output = r#"{"tasks": [
+        {"name": "load a", "executor": "shell", "command": "load", "arguments": ["a"],
+         "dependsOn": [], "onResult": {"terminateJobWithSuccess": [], "continueJob": [0]}},
+        {"name": "check a", "executor": "shell", "command": "check", "arguments": [],
+         "dependsOn": ["load a"], "onResult": {"terminateJobWithSuccess": [], "continueJob": [0]}}
+    ]}"#;
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), generator_strategy(output), None);
+
+    let order = tl.tasks
+        .iter()
+        .map(|g| g.iter().map(|t| t.name.clone()).collect::<Vec<String>>().join(","))
+        .collect::<Vec<String>>();
+    assert_eq!(order, vec!["discover", "load a", "check a", "report"]);
+    assert!(tl.tasks.iter().flat_map(|g| g.iter()).all(|t| t.state == State::Success));
+    assert_eq!(tl.tasks[1][0].task_spec.depends_on, vec!["discover"]);
+    assert_eq!(tl.get_descendants("load a"), vec!["check a", "report"]);
+}
+
+#[test]
+fn invalid_generated_tasks_fail_the_generator() {
+    let ff = generator_factfile();
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), generator_strategy("nope"), None);
+
+    match tl.tasks[0][0].state {
+        State::Failed(ref msg) => {
+            assert!(msg.starts_with("the generated tasks are invalid: the output isn't a list \
+                                     of tasks"))
+        }
+        ref other => panic!("unexpected state {:?}", other),
+    }
+    assert_eq!(tl.tasks[1][0].state,
+               State::Skipped("the task 'discover' failed".to_string()));
+}
+
+#[test]
+fn generated_tasks_are_checked_against_the_policy() {
+    use factotum::policy;
+
+    let mut ff = generator_factfile();
+    ff.policy = Some(policy::parse_policy("[executors]\ngenerator = [\"discover\"]\n\
+                                           shell = [\"load\"]")
+        .unwrap());
+    let output = r#"{"tasks": [
+        {"name": "load a", "executor": "shell", "command": "load", "arguments": ["a"],
+         "dependsOn": [], "onResult": {"terminateJobWithSuccess": [], "continueJob": [0]}},
+        {"name": "wipe", "executor": "shell", "command": "rm", "arguments": ["-rf", "/"],
+         "dependsOn": [], "onResult": {"terminateJobWithSuccess": [], "continueJob": [0]}}
+    ]}"#;
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), generator_strategy(output), None);
+
+    assert_eq!(tl.tasks.len(), 2);
+    assert_eq!(tl.tasks[0][0].state,
+               State::Failed("the generated tasks are invalid: the task 'wipe' runs 'rm', which \
+                              the policy doesn't permit for the 'shell' executor"
+                   .to_string()));
+}
+
+#[test]
+fn simulated_generators_generate_nothing() {
+    let ff = generator_factfile();
+
+    let tl = execute_factfile(&ff,
+                              None,
+                              &HashMap::new(),
+                              execution_strategy::execute_simulation,
+                              None);
+
+    assert_eq!(tl.tasks.len(), 2);
+    assert_eq!(tl.tasks[0][0].state, State::Success);
+}
+
+#[test]
+fn barriers_run_nothing() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut a = make_task("a", &vec![]);
+    a.on_result.continue_job.push(0);
+    let mut join = make_task("join", &vec!["a"]);
+    join.executor = BARRIER_EXECUTOR.to_string();
+    join.on_result.continue_job.push(0);
+    ff.add_task_obj(&a).unwrap();
+    ff.add_task_obj(&join).unwrap();
+
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| {
+        assert!(name != "join", "a barrier shouldn't be run");
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    assert_eq!(tl.tasks[1][0].state, State::Success);
+    assert_eq!(tl.tasks[1][0].run_result.as_ref().unwrap().return_code, 0);
+}
+
+#[test]
+fn disabled_tasks_are_skipped_but_dependents_run() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut vacuum = make_task("vacuum", &vec![]);
+    vacuum.disabled = true;
+    vacuum.on_result.continue_job.push(0);
+    let mut load = make_task("load", &vec!["vacuum"]);
+    load.on_result.continue_job.push(0);
+    ff.add_task_obj(&vacuum).unwrap();
+    ff.add_task_obj(&load).unwrap();
+
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| {
+        assert!(name != "vacuum", "a disabled task shouldn't be run");
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    assert_eq!(tl.tasks[0][0].state,
+               State::Skipped("the task is disabled".to_string()));
+    assert_eq!(tl.tasks[0][0].run_result, None);
+    assert_eq!(tl.tasks[1][0].state, State::Success);
+}
+
+#[test]
+fn tasks_with_unmet_constraints_are_skipped_with_dependents() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut extract = make_task("extract", &vec![]);
+    extract.constraints = Some(TaskConstraints {
+        constraints: Constraints { check: Some("exit 1".to_string()), ..Constraints::default() },
+        fail_job: false,
+    });
+    extract.on_result.continue_job.push(0);
+    let mut load = make_task("load", &vec!["extract"]);
+    load.on_result.continue_job.push(0);
+    let mut report = make_task("report", &vec![]);
+    report.constraints = Some(TaskConstraints {
+        constraints: Constraints { check: Some("true".to_string()), ..Constraints::default() },
+        fail_job: false,
+    });
+    report.on_result.continue_job.push(0);
+    ff.add_task_obj(&extract).unwrap();
+    ff.add_task_obj(&load).unwrap();
+    ff.add_task_obj(&report).unwrap();
+
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| {
+        assert!(name == "report", "only a task with its constraints met should be run");
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+
+    let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, Some(tx));
+
+    let states = tl.tasks
+        .iter()
+        .flat_map(|g| g.iter().map(|t| (t.name.clone(), t.state.clone())))
+        .collect::<HashMap<String, State>>();
+    assert_eq!(states["extract"],
+               State::Skipped("the check constraint isn't met: 'exit 1' exited with 1"
+                   .to_string()));
+    assert_eq!(states["load"],
+               State::Skipped("the task 'extract' was skipped".to_string()));
+    assert_eq!(states["report"], State::Success);
+
+    let skipped = rx.iter()
+        .flat_map(|u| match u.transition {
+            Transition::Task(transitions) => transitions,
+            _ => vec![],
+        })
+        .filter(|t| t.from_state == State::Waiting && t.to_state != State::Running)
+        .map(|t| t.task_name)
+        .collect::<Vec<String>>();
+    assert_eq!(skipped, vec!["extract", "load"]);
+}
+
+#[test]
+fn tasks_are_skipped_when_their_when_expression_is_false() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut extract = make_task("extract", &vec![]);
+    extract.on_result.continue_job.extend(vec![0, 3]);
+    let mut load = make_task("load", &vec!["extract"]);
+    load.when = Some("tasks.extract.exit_code == 0".to_string());
+    load.on_result.continue_job.push(0);
+    let mut notify = make_task("notify", &vec!["extract"]);
+    notify.when = Some("tasks.extract.exit_code == 3 && tasks.extract.output =~ ^empty"
+        .to_string());
+    notify.on_result.continue_job.push(0);
+    let mut report = make_task("report", &vec!["load"]);
+    report.on_result.continue_job.push(0);
+    let mut audit = make_task("audit", &vec![]);
+    audit.when = Some("tasks.load.state == SUCCEEDED".to_string());
+    audit.on_result.continue_job.push(0);
+    for task in vec![&extract, &load, &notify, &report, &audit] {
+        ff.add_task_obj(task).unwrap();
+    }
+
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| {
+        assert!(name == "extract" || name == "notify",
+                "'{}' shouldn't be run",
+                name);
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: Some("empty partition\n".to_string()),
+            stderr: None,
+            return_code: if name == "extract" { 3 } else { 0 },
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    let states = tl.tasks
+        .iter()
+        .flat_map(|g| g.iter().map(|t| (t.name.clone(), t.state.clone())))
+        .collect::<HashMap<String, State>>();
+    assert_eq!(states["load"],
+               State::Skipped("its when expression 'tasks.extract.exit_code == 0' is false"
+                   .to_string()));
+    assert_eq!(states["report"],
+               State::Skipped("the task 'load' was skipped".to_string()));
+    assert_eq!(states["notify"], State::Success);
+    // it runs alongside extract, before load's turn
+    assert_eq!(states["audit"],
+               State::Failed("its when expression couldn't be checked: the task 'load' hasn't \
+                              finished (a task can only check the tasks it depends on)"
+                   .to_string()));
+}
+
+#[test]
+fn tasks_with_unmet_constraints_can_fail_the_job() {
+    let mut ff = Factfile::new("N/A", "test");
+    let mut load = make_task("load", &vec![]);
+    load.constraints = Some(TaskConstraints {
+        constraints: Constraints { check: Some("false".to_string()), ..Constraints::default() },
+        fail_job: true,
+    });
+    load.on_result.continue_job.push(0);
+    let mut notify = make_task("notify", &vec!["load"]);
+    notify.on_result.continue_job.push(0);
+    ff.add_task_obj(&load).unwrap();
+    ff.add_task_obj(&notify).unwrap();
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), execute_simulation, None);
+
+    assert_eq!(tl.tasks[0][0].state,
+               State::Failed("the check constraint isn't met: 'false' exited with 1".to_string()));
+    assert_eq!(tl.tasks[0][0].run_result, None);
+    assert_eq!(tl.tasks[1][0].state,
+               State::Skipped("the task 'load' failed".to_string()));
+}
+
+#[test]
+fn cached_tasks_are_skipped_but_dependents_run() {
+    use factotum::cache;
+
+    let mut ff = Factfile::new("N/A", "test");
+    let mut train = make_task("train", &vec![]);
+    train.cache = Some(TaskCache { enabled: true, ..TaskCache::default() });
+    let key = cache::cache_key(&train).unwrap();
+    train.cache = Some(TaskCache {
+        enabled: true,
+        last_success: Some(("run-1".to_string(), key.clone())),
+        ..TaskCache::default()
+    });
+    train.on_result.continue_job.push(0);
+    let mut publish = make_task("publish", &vec!["train"]);
+    publish.on_result.continue_job.push(0);
+    ff.add_task_obj(&train).unwrap();
+    ff.add_task_obj(&publish).unwrap();
+
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| {
+        assert!(name != "train", "a cached task shouldn't be run");
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    assert_eq!(tl.tasks[0][0].state,
+               State::Skipped("cached - the task's inputs haven't changed since it succeeded \
+                               in run run-1"
+                   .to_string()));
+    assert_eq!(tl.tasks[0][0].cache_key, Some(key));
+    assert_eq!(tl.tasks[1][0].state, State::Success);
+}
+
+#[test]
+fn cache_keys_are_worked_out_when_the_task_runs() {
+    use factotum::cache;
+    use std::env;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    let input = env::temp_dir().join("factotum-executor-test-cache-input.csv");
+    fs::write(&input, "a,b\n").unwrap();
+
+    let mut ff = Factfile::new("N/A", "test");
+    let mut prepare = make_task("prepare", &vec![]);
+    prepare.on_result.continue_job.push(0);
+    let mut train = make_task("train", &vec!["prepare"]);
+    train.cache = Some(TaskCache {
+        inputs: vec![input.to_string_lossy().into_owned()],
+        enabled: true,
+        ..TaskCache::default()
+    });
+    let key = cache::cache_key(&train).unwrap();
+    if let Some(ref mut cache) = train.cache {
+        cache.last_success = Some(("run-1".to_string(), key.clone()));
+    }
+    train.on_result.continue_job.push(0);
+    ff.add_task_obj(&prepare).unwrap();
+    ff.add_task_obj(&train).unwrap();
+
+    // the upstream task rewrites the cached task's input as the job runs
+    let ran = Arc::new(Mutex::new(vec![]));
+    let strategy = {
+        let ran = ran.clone();
+        let input = input.clone();
+        move |name: &str, _: &mut Command, _: &TaskLimits| {
+            if name == "prepare" {
+                fs::write(&input, "a,b\n1,2\n").unwrap();
+            }
+            ran.lock().unwrap().push(name.to_string());
+            RunResult {
+                duration: StdDuration::from_secs(0),
+                task_execution_error: None,
+                stdout: None,
+                stderr: None,
+                return_code: 0,
+                resource_usage: None,
+                spilled_output: None,
+            }
+        }
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    assert_eq!(*ran.lock().unwrap(), vec!["prepare", "train"]);
+    assert_eq!(tl.tasks[1][0].state, State::Success);
+    assert!(tl.tasks[1][0].cache_key.is_some());
+    assert!(tl.tasks[1][0].cache_key != Some(key));
+    assert!(fs::remove_file(&input).is_ok());
+}
+
+#[test]
+fn produced_artifacts_are_checksummed() {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    let path = env::temp_dir().join("factotum-executor-test-produced.csv");
+    File::create(&path).unwrap().write_all(b"hello\n").unwrap();
+
+    let mut ff = Factfile::new("N/A", "test");
+    let mut export = make_task("export", &vec![]);
+    export.produces = vec![path.to_string_lossy().into_owned()];
+    export.on_result.continue_job.push(0);
+    ff.add_task_obj(&export).unwrap();
+
+    let tl = execute_factfile(&ff,
+                              None,
+                              &HashMap::new(),
+                              execution_strategy::execute_simulation,
+                              None);
+    assert_eq!(tl.tasks[0][0].artifacts, vec![]);
+
+    let strategy = |_: &str, _: &mut Command, _: &TaskLimits| {
+        RunResult {
+            duration: StdDuration::from_secs(0),
+            task_execution_error: None,
+            stdout: None,
+            stderr: None,
+            return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    };
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    assert_eq!(tl.tasks[0][0].state, State::Success);
+    let artifacts = &tl.tasks[0][0].artifacts;
+    assert_eq!(artifacts.len(), 1);
+    assert_eq!(artifacts[0].checksum.as_ref().map(|c| c.size_bytes), Some(6));
+    assert!(fs::remove_file(&path).is_ok());
+}
+
+#[test]
+#[ignore] // a benchmark - run with `cargo test --release large_factfile_benchmark -- --ignored --nocapture`
+fn large_factfile_benchmark() {
+    use factotum::config::ExecutorSettings;
+    use factotum::parser::{self, OverrideResultMappings};
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::time::Instant;
+
+    // 10,000 tasks in layers of 100, each depending on two tasks in the layer before and
+    // on the first task
+    let mut tasks = vec![];
+    for i in 0..10000 {
+        let depends_on = if i == 0 {
+            vec![]
+        } else if i < 101 {
+            vec!["t0".to_string()]
+        } else {
+            let layer_start = (i - 1) / 100 * 100 - 99;
+            vec![format!("t{}", layer_start + i % 100),
+                 format!("t{}", layer_start + (i + 1) % 100),
+                 "t0".to_string()]
+        };
+        tasks.push(format!("{{\"name\": \"t{}\", \"executor\": \"shell\", \"command\": \"true\", \
+                            \"arguments\": [], \"dependsOn\": [{}], \"onResult\": \
+                            {{\"terminateJobWithSuccess\": [], \"continueJob\": [0]}}}}",
+                           i,
+                           depends_on.iter()
+                               .map(|d| format!("\"{}\"", d))
+                               .collect::<Vec<String>>()
+                               .join(", ")));
+    }
+    let factfile = format!("{{\"schema\": \
+                            \"iglu:com.snowplowanalytics.factotum/factfile/jsonschema/1-0-0\", \
+                            \"data\": {{\"name\": \"large\", \"tasks\": [{}]}}}}",
+                           tasks.join(",\n"));
+
+    let path = env::temp_dir().join("factotum-large-factfile-benchmark.factfile");
+    File::create(&path).unwrap().write_all(factfile.as_bytes()).unwrap();
+
+    let started = Instant::now();
+    let ff = parser::parse(path.to_str().unwrap(),
+                           None,
+                           OverrideResultMappings::None,
+                           &ExecutorSettings::new())
+        .unwrap();
+    let parsed = started.elapsed();
+    let tasklist = get_task_execution_list(&ff, None);
+    let scheduled = started.elapsed() - parsed;
+
+    println!("parsed 10,000 tasks in {:?}, scheduled {} groups in {:?}",
+             parsed,
+             tasklist.tasks.len(),
+             scheduled);
+    assert_eq!(tasklist.tasks.iter().map(|g| g.len()).sum::<usize>(), 10000);
+    assert!(fs::remove_file(&path).is_ok());
+}
@@ -0,0 +1,88 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+
+// the exit code for each class of outcome - these are stable, as wrapper scripts and
+// monitors rely on them, and can be changed with --exit-code-map
+pub const SUCCESS: i32 = 0;
+pub const VALIDATION_FAILED: i32 = 1;
+pub const TASK_FAILED: i32 = 2;
+pub const INTERNAL_ERROR: i32 = 3;
+pub const INVALID_ARGUMENTS: i32 = 4;
+pub const CONSTRAINT_NOT_MET: i32 = 5;
+pub const ABORTED: i32 = 6;
+pub const POLICY_VIOLATION: i32 = 7;
+pub const UNVERIFIED: i32 = 8;
+pub const PREFLIGHT_FAILED: i32 = 9;
+
+pub const CLASSES: [(&'static str, i32); 9] = [("invalid-arguments", INVALID_ARGUMENTS),
+                                               ("validation-failed", VALIDATION_FAILED),
+                                               ("unverified", UNVERIFIED),
+                                               ("policy-violation", POLICY_VIOLATION),
+                                               ("preflight-failed", PREFLIGHT_FAILED),
+                                               ("constraint-not-met", CONSTRAINT_NOT_MET),
+                                               ("task-failed", TASK_FAILED),
+                                               ("aborted", ABORTED),
+                                               ("internal-error", INTERNAL_ERROR)];
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ExitCodeMap {
+    overrides: BTreeMap<i32, i32>,
+}
+
+impl ExitCodeMap {
+    pub fn code_for(&self, default_code: i32) -> i32 {
+        *self.overrides.get(&default_code).unwrap_or(&default_code)
+    }
+}
+
+pub fn class_name(code: i32) -> &'static str {
+    if code == SUCCESS {
+        return "success";
+    }
+    CLASSES.iter().find(|&&(_, c)| c == code).map(|&(name, _)| name).unwrap_or("unknown")
+}
+
+// a comma separated list of <class>=<code>, e.g. "constraint-not-met=0,task-failed=10"
+pub fn parse_exit_code_map(spec: &str) -> Result<ExitCodeMap, String> {
+    let mut overrides = BTreeMap::new();
+
+    for entry in spec.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+        let (class, code) = match entry.find('=') {
+            Some(idx) => (entry[..idx].trim(), entry[idx + 1..].trim()),
+            None => return Err(format!("'{}' must be given as <class>=<code>", entry)),
+        };
+
+        let default_code = try!(CLASSES.iter()
+            .find(|&&(name, _)| name == class)
+            .map(|&(_, code)| code)
+            .ok_or_else(|| {
+                format!("'{}' isn't a class of exit code, it must be one of: {}",
+                        class,
+                        CLASSES.iter().map(|&(name, _)| name).collect::<Vec<&str>>().join(", "))
+            }));
+
+        let code = try!(code.parse::<u8>()
+            .map_err(|_| format!("the exit code for '{}' must be between 0 and 255, not '{}'",
+                                 class,
+                                 code)));
+        overrides.insert(default_code, code as i32);
+    }
+
+    Ok(ExitCodeMap { overrides: overrides })
+}
@@ -0,0 +1,58 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+
+#[test]
+fn default_codes_are_unchanged() {
+    let map = ExitCodeMap::default();
+    for &(_, code) in CLASSES.iter() {
+        assert_eq!(map.code_for(code), code);
+    }
+    assert_eq!(map.code_for(SUCCESS), 0);
+}
+
+#[test]
+fn parse_exit_code_map_good() {
+    let map = parse_exit_code_map("constraint-not-met=0, task-failed=10,").unwrap();
+
+    assert_eq!(map.code_for(CONSTRAINT_NOT_MET), 0);
+    assert_eq!(map.code_for(TASK_FAILED), 10);
+    assert_eq!(map.code_for(ABORTED), ABORTED);
+    assert_eq!(map.code_for(SUCCESS), SUCCESS);
+    assert_eq!(parse_exit_code_map(""), Ok(ExitCodeMap::default()));
+}
+
+#[test]
+fn parse_exit_code_map_bad() {
+    assert_eq!(parse_exit_code_map("aborted"),
+               Err("'aborted' must be given as <class>=<code>".to_string()));
+    assert_eq!(parse_exit_code_map("success=1"),
+               Err("'success' isn't a class of exit code, it must be one of: invalid-arguments, \
+                    validation-failed, unverified, policy-violation, preflight-failed, \
+                    constraint-not-met, task-failed, aborted, internal-error"
+                   .to_string()));
+    assert_eq!(parse_exit_code_map("aborted=256"),
+               Err("the exit code for 'aborted' must be between 0 and 255, not '256'"
+                   .to_string()));
+}
+
+#[test]
+fn class_name_good() {
+    assert_eq!(class_name(SUCCESS), "success");
+    assert_eq!(class_name(TASK_FAILED), "task-failed");
+    assert_eq!(class_name(UNVERIFIED), "unverified");
+    assert_eq!(class_name(PREFLIGHT_FAILED), "preflight-failed");
+    assert_eq!(class_name(42), "unknown");
+}
@@ -0,0 +1,593 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+
+// the functions an expression can call, and how many arguments each takes
+pub const FUNCTIONS: [(&'static str, usize); 7] = [("lower", 1),
+                                                   ("upper", 1),
+                                                   ("trim", 1),
+                                                   ("len", 1),
+                                                   ("contains", 2),
+                                                   ("startsWith", 2),
+                                                   ("endsWith", 2)];
+
+const TASK_FIELDS: [&'static str; 3] = ["exit_code", "state", "output"];
+
+// longest first, so "<=" isn't read as "<"
+const SYMBOLS: [&'static str; 11] = ["==", "!=", "<=", ">=", "=~", "!~", "&&", "||", "<", ">",
+                                     "!"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    Matches,
+    DoesNotMatch,
+}
+
+impl Operator {
+    fn from_str(op: &str) -> Option<Operator> {
+        match op {
+            "==" => Some(Operator::Equal),
+            "!=" => Some(Operator::NotEqual),
+            "<" => Some(Operator::LessThan),
+            "<=" => Some(Operator::LessThanOrEqual),
+            ">" => Some(Operator::GreaterThan),
+            ">=" => Some(Operator::GreaterThanOrEqual),
+            "=~" => Some(Operator::Matches),
+            "!~" => Some(Operator::DoesNotMatch),
+            _ => None,
+        }
+    }
+}
+
+// how a finished task turned out, as the expressions of the tasks after it see it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub state: String,
+    pub exit_code: Option<i32>,
+    pub output: Option<String>,
+}
+
+// what an expression can refer to as it's evaluated
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Context {
+    pub variables: BTreeMap<String, String>,
+    pub outcomes: HashMap<String, Outcome>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Str(String),
+    Word(String),
+    // ${task name}, the task's output
+    Output(String),
+    // tasks.<name>.<field>
+    Task(String, String),
+    // vars.<name>
+    Var(String),
+    Symbol(&'static str),
+    Open,
+    Close,
+    Comma,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match *self {
+            Token::Str(ref s) => format!("'{}'", s),
+            Token::Word(ref w) => format!("'{}'", w),
+            Token::Output(ref name) => format!("'${{{}}}'", name),
+            Token::Task(ref name, ref field) => format!("'tasks.{}.{}'", name, field),
+            Token::Var(ref name) => format!("'vars.{}'", name),
+            Token::Symbol(s) => format!("'{}'", s),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::Comma => "','".to_string(),
+        }
+    }
+}
+
+// a token and the column (from 1) it starts at
+#[derive(Debug, Clone, PartialEq)]
+struct Located {
+    token: Token,
+    column: usize,
+    end: usize,
+}
+
+fn at(problem: String, column: usize) -> String {
+    format!("{} (at character {})", problem, column)
+}
+
+fn starts_with(chars: &[char], i: usize, prefix: &str) -> bool {
+    let prefix = prefix.chars().collect::<Vec<char>>();
+    chars.len() >= i + prefix.len() && chars[i..i + prefix.len()] == prefix[..]
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+// a quoted string starting at i, with \ escaping the next character; returns it and where
+// it ends
+fn read_string(chars: &[char], i: usize) -> Result<(String, usize), String> {
+    let quote = chars[i];
+    let mut value = String::new();
+    let mut j = i + 1;
+    while j < chars.len() {
+        if chars[j] == '\\' && j + 1 < chars.len() {
+            value.push(chars[j + 1]);
+            j += 2;
+        } else if chars[j] == quote {
+            return Ok((value, j + 1));
+        } else {
+            value.push(chars[j]);
+            j += 1;
+        }
+    }
+    Err(at("the string isn't closed".to_string(), i + 1))
+}
+
+fn read_name(chars: &[char], i: usize) -> (String, usize) {
+    let mut j = i;
+    while j < chars.len() && is_name_char(chars[j]) {
+        j += 1;
+    }
+    (chars[i..j].iter().cloned().collect(), j)
+}
+
+// tasks.<name>.<field>, where a name with other characters in it is quoted
+fn read_task_reference(chars: &[char], i: usize) -> Result<Option<(Token, usize)>, String> {
+    let start = i + "tasks.".len();
+    let (name, after_name) = match chars.get(start) {
+        Some(&'\'') | Some(&'"') => try!(read_string(chars, start)),
+        Some(&c) if is_name_char(c) => read_name(chars, start),
+        _ => return Ok(None),
+    };
+    if chars.get(after_name) != Some(&'.') {
+        return Err(at(format!("'tasks.{}' should be followed by .exit_code, .state or .output",
+                              name),
+                      i + 1));
+    }
+    let (field, end) = read_name(chars, after_name + 1);
+    if !TASK_FIELDS.contains(&&field[..]) {
+        return Err(at(format!("'{}' isn't something known about a task, it must be exit_code, \
+                               state or output",
+                              field),
+                      after_name + 2));
+    }
+    Ok(Some((Token::Task(name, field), end)))
+}
+
+fn tokenize(source: &str) -> Result<Vec<Located>, String> {
+    let chars = source.chars().collect::<Vec<char>>();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        let task_reference = if starts_with(&chars, i, "tasks.") {
+            try!(read_task_reference(&chars, i))
+        } else {
+            None
+        };
+        let is_variable = starts_with(&chars, i, "vars.") &&
+                          chars.get(i + 5).map(|c| is_name_char(*c)) == Some(true);
+
+        let (token, end) = if let Some(reference) = task_reference {
+            reference
+        } else if is_variable {
+            let (name, end) = read_name(&chars, i + 5);
+            (Token::Var(name), end)
+        } else if c == '\'' || c == '"' {
+            let (value, end) = try!(read_string(&chars, i));
+            (Token::Str(value), end)
+        } else if starts_with(&chars, i, "${") {
+            // a task name in ${...} can contain spaces
+            match chars[i..].iter().position(|c| *c == '}') {
+                Some(len) => (Token::Output(chars[i + 2..i + len].iter().cloned().collect()),
+                              i + len + 1),
+                None => return Err(at("the output reference isn't closed".to_string(), i + 1)),
+            }
+        } else if c == '(' {
+            (Token::Open, i + 1)
+        } else if c == ')' {
+            (Token::Close, i + 1)
+        } else if c == ',' {
+            (Token::Comma, i + 1)
+        } else if "=!<>&|".contains(c) {
+            // a run of operator characters is read whole, so e.g. "=>" is reported as it's
+            // written
+            let mut end = i;
+            while end < chars.len() && "=!<>&|~".contains(chars[end]) {
+                end += 1;
+            }
+            let run = chars[i..end].iter().cloned().collect::<String>();
+            match SYMBOLS.iter().find(|s| **s == run) {
+                Some(symbol) => (Token::Symbol(symbol), end),
+                None => (Token::Word(run), end),
+            }
+        } else {
+            // anything else, e.g. a number, a bare word or a regex, runs up to whitespace, a
+            // bracket, a comma or an operator
+            let mut end = i;
+            while end < chars.len() && !chars[end].is_whitespace() &&
+                  !"(),".contains(chars[end]) &&
+                  !SYMBOLS.iter().any(|s| *s != "!" && starts_with(&chars, end, s)) {
+                end += 1;
+            }
+            (Token::Word(chars[i..end].iter().cloned().collect()), end)
+        };
+        tokens.push(Located {
+            token: token,
+            column: i + 1,
+            end: end + 1,
+        });
+        i = end;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    // a literal and the column it's at
+    Literal(String, usize),
+    Output(String),
+    Task(String, String),
+    Var(String),
+    Call(String, Vec<Expr>),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, Operator, Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Located>,
+    next: usize,
+    // the column just past the end of the expression
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Located> {
+        self.tokens.get(self.next)
+    }
+
+    fn peek_symbol(&self, symbols: &[&str]) -> Option<&'static str> {
+        match self.peek().map(|t| &t.token) {
+            Some(&Token::Symbol(s)) if symbols.contains(&s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> String {
+        match self.peek() {
+            Some(found) => {
+                at(format!("expected {} but found {}", expected, found.token.describe()),
+                   found.column)
+            }
+            None => at(format!("expected {} but the expression ends", expected), self.end),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = try!(self.and());
+        while self.peek_symbol(&["||"]).is_some() {
+            self.next += 1;
+            let right = try!(self.and());
+            let left_side = try!(boolean(left));
+            left = Expr::Or(Box::new(left_side), Box::new(try!(boolean(right))));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = try!(self.not());
+        while self.peek_symbol(&["&&"]).is_some() {
+            self.next += 1;
+            let right = try!(self.not());
+            let left_side = try!(boolean(left));
+            left = Expr::And(Box::new(left_side), Box::new(try!(boolean(right))));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.peek_symbol(&["!"]).is_some() {
+            self.next += 1;
+            let negated = try!(self.not());
+            return Ok(Expr::Not(Box::new(try!(boolean(negated)))));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = try!(self.value());
+        let symbol = match self.peek_symbol(&["==", "!=", "<", "<=", ">", ">=", "=~", "!~"]) {
+            Some(symbol) => symbol,
+            None => return Ok(left),
+        };
+        self.next += 1;
+        let operator = Operator::from_str(symbol).unwrap();
+        let right = try!(self.value());
+
+        if let Expr::Literal(ref pattern, column) = right {
+            if operator == Operator::Matches || operator == Operator::DoesNotMatch {
+                try!(Regex::new(pattern)
+                    .map_err(|e| at(format!("'{}' isn't a valid regex ({})", pattern, e), column)));
+            }
+        }
+        Ok(Expr::Compare(Box::new(left), operator, Box::new(right)))
+    }
+
+    fn value(&mut self) -> Result<Expr, String> {
+        let located = match self.peek() {
+            Some(located) => located.clone(),
+            None => return Err(self.unexpected("a value")),
+        };
+        self.next += 1;
+
+        match located.token {
+            Token::Str(s) => Ok(Expr::Literal(s, located.column)),
+            Token::Output(name) => Ok(Expr::Output(name)),
+            Token::Task(name, field) => Ok(Expr::Task(name, field)),
+            Token::Var(name) => Ok(Expr::Var(name)),
+            Token::Word(word) => {
+                match self.peek() {
+                    // a call's bracket follows its name straight away
+                    Some(&Located { token: Token::Open, column, .. }) if column ==
+                                                                          located.end => {
+                        self.next += 1;
+                        self.call(word, located.column)
+                    }
+                    _ => Ok(Expr::Literal(word, located.column)),
+                }
+            }
+            Token::Open => {
+                let inner = try!(self.or());
+                match self.peek() {
+                    Some(&Located { token: Token::Close, .. }) => {
+                        self.next += 1;
+                        Ok(inner)
+                    }
+                    _ => Err(self.unexpected("')'")),
+                }
+            }
+            _ => {
+                self.next -= 1;
+                Err(self.unexpected("a value"))
+            }
+        }
+    }
+
+    fn call(&mut self, name: String, column: usize) -> Result<Expr, String> {
+        let arity = try!(FUNCTIONS.iter()
+            .find(|&&(f, _)| f == name)
+            .map(|&(_, arity)| arity)
+            .ok_or(at(format!("'{}' isn't a function, it must be one of {}",
+                              name,
+                              FUNCTIONS.iter()
+                                  .map(|&(f, _)| f)
+                                  .collect::<Vec<&str>>()
+                                  .join(", ")),
+                      column)));
+
+        let mut args = vec![];
+        if self.peek().map(|t| t.token == Token::Close) != Some(true) {
+            loop {
+                args.push(try!(self.or()));
+                match self.peek().map(|t| t.token.clone()) {
+                    Some(Token::Comma) => self.next += 1,
+                    Some(Token::Close) => break,
+                    _ => return Err(self.unexpected("',' or ')'")),
+                }
+            }
+        }
+        self.next += 1;
+
+        if args.len() != arity {
+            return Err(at(format!("{}() takes {} argument{}, not {}",
+                                  name,
+                                  arity,
+                                  if arity == 1 { "" } else { "s" },
+                                  args.len()),
+                          column));
+        }
+        Ok(Expr::Call(name, args))
+    }
+}
+
+// a literal where true or false is needed can only be one of them
+fn boolean(expr: Expr) -> Result<Expr, String> {
+    match expr {
+        Expr::Literal(ref value, column) if value != "true" && value != "false" => {
+            Err(at(format!("'{}' isn't a comparison, true or false", value), column))
+        }
+        expr => Ok(expr),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Expr,
+}
+
+// comparisons (==, !=, <, <=, >, >=, =~ and !~), joined with && and || and negated with !,
+// where && binds tighter than || and brackets group; values can be quoted, call a
+// function, or refer to a variable (vars.<name>), to how an earlier task turned out
+// (tasks.<name>.exit_code, .state or .output) or to a task's output (${task name})
+pub fn parse(source: &str) -> Result<Expression, String> {
+    let mut parser = Parser {
+        tokens: try!(tokenize(source)),
+        next: 0,
+        end: source.chars().count() + 1,
+    };
+    let root = try!(parser.or());
+    if parser.peek().is_some() {
+        return Err(parser.unexpected("an operator (==, !=, <, <=, >, >=, =~ or !~), && or ||"));
+    }
+    Ok(Expression { root: try!(boolean(root)) })
+}
+
+// values compare as numbers when both sides are numbers, otherwise as strings
+fn compare(left: &str, right: &str) -> Ordering {
+    match (left.trim().parse::<f64>(), right.trim().parse::<f64>()) {
+        (Ok(l), Ok(r)) => l.partial_cmp(&r).unwrap_or(Ordering::Equal),
+        _ => left.cmp(right),
+    }
+}
+
+fn outcome<'a>(context: &'a Context, name: &str) -> Result<&'a Outcome, String> {
+    context.outcomes.get(name).ok_or(format!("the task '{}' hasn't finished (a task can only \
+                                              check the tasks it depends on)",
+                                             name))
+}
+
+fn value_of(expr: &Expr, context: &Context) -> Result<String, String> {
+    match *expr {
+        Expr::Literal(ref value, _) => Ok(value.clone()),
+        Expr::Output(ref name) => {
+            context.outcomes
+                .get(name)
+                .and_then(|o| o.output.as_ref())
+                .map(|output| output.trim().to_string())
+                .ok_or(format!("the output of the task '{}' isn't available (tasks can only \
+                                use the output of tasks they depend on)",
+                               name))
+        }
+        Expr::Task(ref name, ref field) => {
+            let outcome = try!(outcome(context, name));
+            Ok(match &field[..] {
+                "exit_code" => outcome.exit_code.map(|c| c.to_string()).unwrap_or_default(),
+                "state" => outcome.state.clone(),
+                _ => outcome.output.as_ref().map(|o| o.trim().to_string()).unwrap_or_default(),
+            })
+        }
+        Expr::Var(ref name) => {
+            context.variables
+                .get(name)
+                .cloned()
+                .ok_or(format!("the variable '{}' has no value", name))
+        }
+        Expr::Call(ref name, ref args) => {
+            let mut values = vec![];
+            for arg in args.iter() {
+                values.push(try!(value_of(arg, context)));
+            }
+            Ok(match &name[..] {
+                "lower" => values[0].to_lowercase(),
+                "upper" => values[0].to_uppercase(),
+                "trim" => values[0].trim().to_string(),
+                "len" => values[0].chars().count().to_string(),
+                "contains" => values[0].contains(&values[1][..]).to_string(),
+                "startsWith" => values[0].starts_with(&values[1][..]).to_string(),
+                _ => values[0].ends_with(&values[1][..]).to_string(),
+            })
+        }
+        ref other => holds(other, context).map(|b| b.to_string()),
+    }
+}
+
+fn holds(expr: &Expr, context: &Context) -> Result<bool, String> {
+    match *expr {
+        Expr::Not(ref negated) => holds(negated, context).map(|b| !b),
+        Expr::And(ref left, ref right) => {
+            Ok(try!(holds(left, context)) && try!(holds(right, context)))
+        }
+        Expr::Or(ref left, ref right) => {
+            Ok(try!(holds(left, context)) || try!(holds(right, context)))
+        }
+        Expr::Compare(ref left, ref operator, ref right) => {
+            let left = try!(value_of(left, context));
+            let right = try!(value_of(right, context));
+            let ordering = compare(&left, &right);
+            Ok(match *operator {
+                Operator::Equal => ordering == Ordering::Equal,
+                Operator::NotEqual => ordering != Ordering::Equal,
+                Operator::LessThan => ordering == Ordering::Less,
+                Operator::LessThanOrEqual => ordering != Ordering::Greater,
+                Operator::GreaterThan => ordering == Ordering::Greater,
+                Operator::GreaterThanOrEqual => ordering != Ordering::Less,
+                Operator::Matches | Operator::DoesNotMatch => {
+                    let regex = try!(Regex::new(&right)
+                        .map_err(|e| format!("'{}' isn't a valid regex ({})", right, e)));
+                    regex.is_match(&left) == (*operator == Operator::Matches)
+                }
+            })
+        }
+        ref other => {
+            match &try!(value_of(other, context))[..] {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                value => Err(format!("'{}' isn't true or false", value)),
+            }
+        }
+    }
+}
+
+fn collect_tasks(expr: &Expr, names: &mut Vec<String>) {
+    match *expr {
+        Expr::Task(ref name, _) |
+        Expr::Output(ref name) => {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        Expr::Call(_, ref args) => {
+            for arg in args.iter() {
+                collect_tasks(arg, names);
+            }
+        }
+        Expr::Not(ref inner) => collect_tasks(inner, names),
+        Expr::And(ref left, ref right) |
+        Expr::Or(ref left, ref right) |
+        Expr::Compare(ref left, _, ref right) => {
+            collect_tasks(left, names);
+            collect_tasks(right, names);
+        }
+        _ => {}
+    }
+}
+
+impl Expression {
+    pub fn evaluate(&self, context: &Context) -> Result<bool, String> {
+        holds(&self.root, context)
+    }
+
+    // the tasks the expression refers to, in the order they're first referred to
+    pub fn tasks(&self) -> Vec<String> {
+        let mut names = vec![];
+        collect_tasks(&self.root, &mut names);
+        names
+    }
+}
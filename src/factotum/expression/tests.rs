@@ -0,0 +1,137 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::collections::HashMap;
+
+fn context() -> Context {
+    let mut outcomes = HashMap::new();
+    outcomes.insert("extract".to_string(),
+                    Outcome {
+                        state: "SUCCEEDED".to_string(),
+                        exit_code: Some(0),
+                        output: Some("1200\n".to_string()),
+                    });
+    outcomes.insert("load events".to_string(),
+                    Outcome {
+                        state: "SKIPPED".to_string(),
+                        exit_code: None,
+                        output: None,
+                    });
+    let mut variables = BTreeMap::new();
+    variables.insert("env".to_string(), "Prod".to_string());
+    Context {
+        variables: variables,
+        outcomes: outcomes,
+    }
+}
+
+fn holds(source: &str) -> Result<bool, String> {
+    parse(source).and_then(|e| e.evaluate(&context()))
+}
+
+#[test]
+fn comparisons_of_numbers_and_strings() {
+    assert_eq!(holds("950 >= 1000"), Ok(false));
+    assert_eq!(holds("'1000.0' == 1000"), Ok(true));
+    assert_eq!(holds("9 < 10"), Ok(true));
+    assert_eq!(holds("abc < abd"), Ok(true));
+    assert_eq!(holds("abc != abc"), Ok(false));
+    assert_eq!(holds("10 <= 10"), Ok(true));
+    assert_eq!(holds("11 > 10"), Ok(true));
+    assert_eq!(holds("'eu west' == \"eu west\""), Ok(true));
+    assert_eq!(holds("'it\\'s'=='it\\'s'"), Ok(true));
+}
+
+#[test]
+fn comparisons_with_regexes() {
+    assert_eq!(holds("eu-west-1 =~ ^eu-"), Ok(true));
+    assert_eq!(holds("us-east-1 =~ ^eu-"), Ok(false));
+    assert_eq!(holds("'some error' !~ error"), Ok(false));
+    assert_eq!(holds("'it\\'s' =~ ^it"), Ok(true));
+}
+
+#[test]
+fn boolean_operators_and_brackets() {
+    assert_eq!(holds("true"), Ok(true));
+    assert_eq!(holds("false || false && true"), Ok(false));
+    assert_eq!(holds("(false || true) && true"), Ok(true));
+    assert_eq!(holds("!(1 == 2) && !false"), Ok(true));
+    assert_eq!(holds("'a && b' == 'a && b' || false"), Ok(true));
+}
+
+#[test]
+fn functions_variables_and_tasks() {
+    assert_eq!(holds("lower(vars.env) == 'prod' && tasks.extract.exit_code == 0"), Ok(true));
+    assert_eq!(holds("upper(trim(' eu ')) == EU"), Ok(true));
+    assert_eq!(holds("len(${extract}) == 4 && tasks.extract.output >= 1000"), Ok(true));
+    assert_eq!(holds("contains(tasks.extract.state, SUCC) && startsWith(vars.env, P)"),
+               Ok(true));
+    assert_eq!(holds("endsWith(vars.env, x)"), Ok(false));
+    assert_eq!(holds("tasks.'load events'.state == SKIPPED && \
+                      tasks.\"load events\".exit_code == ''"),
+               Ok(true));
+}
+
+#[test]
+fn tasks_referred_to() {
+    let parsed = parse("tasks.extract.exit_code == 0 && tasks.'load events'.state != SKIPPED || \
+                        ${count rows} > 5 && len(tasks.extract.output) > 1")
+        .unwrap();
+    assert_eq!(parsed.tasks(), vec!["extract", "load events", "count rows"]);
+    assert_eq!(parse("prod == 'prod'").unwrap().tasks(), Vec::<String>::new());
+}
+
+#[test]
+fn syntax_errors_say_where_they_are() {
+    let invalid = |source: &str| parse(source).unwrap_err();
+
+    assert_eq!(invalid("1 >= "), "expected a value but the expression ends (at character 6)");
+    assert_eq!(invalid("1 => 2"),
+               "expected an operator (==, !=, <, <=, >, >=, =~ or !~), && or || but found '=>' \
+                (at character 3)");
+    assert_eq!(invalid("'abc == abc"), "the string isn't closed (at character 1)");
+    assert!(invalid("abc =~ '('").starts_with("'(' isn't a valid regex"));
+    assert!(invalid("abc =~ '('").ends_with("(at character 8)"));
+    assert_eq!(invalid("true && yes"),
+               "'yes' isn't a comparison, true or false (at character 9)");
+    assert_eq!(invalid("(1 == 1"), "expected ')' but the expression ends (at character 8)");
+    assert_eq!(invalid("size(abc) > 1"),
+               "'size' isn't a function, it must be one of lower, upper, trim, len, contains, \
+                startsWith, endsWith (at character 1)");
+    assert_eq!(invalid("contains(abc) == true"),
+               "contains() takes 2 arguments, not 1 (at character 1)");
+    assert_eq!(invalid("tasks.extract.status == 0"),
+               "'status' isn't something known about a task, it must be exit_code, state or \
+                output (at character 15)");
+    assert_eq!(invalid("1 == 1 == 1"),
+               "expected an operator (==, !=, <, <=, >, >=, =~ or !~), && or || but found '==' \
+                (at character 8)");
+}
+
+#[test]
+fn evaluation_errors() {
+    assert_eq!(holds("tasks.report.exit_code == 0"),
+               Err("the task 'report' hasn't finished (a task can only check the tasks it \
+                    depends on)"
+                   .to_string()));
+    assert_eq!(holds("${count rows} >= 1000"),
+               Err("the output of the task 'count rows' isn't available (tasks can only use \
+                    the output of tasks they depend on)"
+                   .to_string()));
+    assert_eq!(holds("vars.region == eu"),
+               Err("the variable 'region' has no value".to_string()));
+    assert_eq!(holds("vars.env"), Err("'Prod' isn't true or false".to_string()));
+    assert_eq!(holds("tasks.extract.output =~ vars.env").unwrap(), false);
+}
@@ -0,0 +1,168 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::{Factfile, Task};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskChange {
+    Added(String),
+    Removed(String),
+    Changed(String, Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactfileDiff {
+    pub name: Option<(String, String)>,
+    pub tasks: Vec<TaskChange>,
+}
+
+impl FactfileDiff {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.tasks.is_empty()
+    }
+}
+
+// tasks are compared in execution order, by name within each level
+fn all_tasks(factfile: &Factfile) -> Vec<&Task> {
+    factfile.get_tasks_in_order()
+        .into_iter()
+        .flat_map(|mut grp| {
+            grp.sort_by(|a, b| a.name.cmp(&b.name));
+            grp.into_iter()
+        })
+        .collect()
+}
+
+fn describe_list<T: ToString>(items: &Vec<T>) -> String {
+    format!("[{}]",
+            items.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(", "))
+}
+
+fn compare_field<T: PartialEq + ToString>(changes: &mut Vec<String>,
+                                          field: &str,
+                                          before: &Vec<T>,
+                                          after: &Vec<T>) {
+    if before != after {
+        changes.push(format!("{}: {} -> {}",
+                             field,
+                             describe_list(before),
+                             describe_list(after)));
+    }
+}
+
+fn compare_tasks(before: &Task, after: &Task) -> Vec<String> {
+    let mut changes = vec![];
+
+    if before.executor != after.executor {
+        changes.push(format!("executor: '{}' -> '{}'", before.executor, after.executor));
+    }
+    if before.command != after.command {
+        changes.push(format!("command: '{}' -> '{}'", before.command, after.command));
+    }
+    compare_field(&mut changes, "arguments", &before.arguments, &after.arguments);
+
+    let mut deps_before = before.depends_on.clone();
+    let mut deps_after = after.depends_on.clone();
+    deps_before.sort();
+    deps_after.sort();
+    compare_field(&mut changes, "dependsOn", &deps_before, &deps_after);
+
+    compare_field(&mut changes,
+                  "continueJob",
+                  &before.on_result.continue_job,
+                  &after.on_result.continue_job);
+    compare_field(&mut changes,
+                  "terminateJobWithSuccess",
+                  &before.on_result.terminate_job,
+                  &after.on_result.terminate_job);
+
+    if before.circuit_breaker != after.circuit_breaker {
+        changes.push(format!("circuit breaker: {:?} -> {:?}",
+                             before.circuit_breaker,
+                             after.circuit_breaker));
+    }
+
+    compare_field(&mut changes, "requires", &before.requires, &after.requires);
+
+    if before.expected_duration != after.expected_duration {
+        changes.push(format!("expectedDuration: {:?} -> {:?}",
+                             before.expected_duration,
+                             after.expected_duration));
+    }
+
+    if before.idle_timeout != after.idle_timeout {
+        changes.push(format!("idleTimeout: {:?} -> {:?}", before.idle_timeout, after.idle_timeout));
+    }
+
+    changes
+}
+
+pub fn diff_factfiles(before: &Factfile, after: &Factfile) -> FactfileDiff {
+    let before_tasks = all_tasks(before);
+    let after_tasks = all_tasks(after);
+    let mut changes = vec![];
+
+    for task in before_tasks.iter() {
+        match after_tasks.iter().find(|t| t.name == task.name) {
+            Some(other) => {
+                let task_changes = compare_tasks(task, other);
+                if !task_changes.is_empty() {
+                    changes.push(TaskChange::Changed(task.name.clone(), task_changes));
+                }
+            }
+            None => changes.push(TaskChange::Removed(task.name.clone())),
+        }
+    }
+
+    for task in after_tasks.iter() {
+        if !before_tasks.iter().any(|t| t.name == task.name) {
+            changes.push(TaskChange::Added(task.name.clone()));
+        }
+    }
+
+    FactfileDiff {
+        name: if before.name != after.name {
+            Some((before.name.clone(), after.name.clone()))
+        } else {
+            None
+        },
+        tasks: changes,
+    }
+}
+
+pub fn format_diff(diff: &FactfileDiff) -> String {
+    let mut lines = vec![];
+
+    if let Some((ref before, ref after)) = diff.name {
+        lines.push(format!("~ job name: '{}' -> '{}'", before, after));
+    }
+
+    for change in diff.tasks.iter() {
+        match *change {
+            TaskChange::Added(ref name) => lines.push(format!("+ task '{}'", name)),
+            TaskChange::Removed(ref name) => lines.push(format!("- task '{}'", name)),
+            TaskChange::Changed(ref name, ref changes) => {
+                lines.push(format!("~ task '{}'", name));
+                for c in changes.iter() {
+                    lines.push(format!("    {}", c));
+                }
+            }
+        }
+    }
+
+    lines.join("\n")
+}
@@ -0,0 +1,74 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use factotum::factfile::*;
+use factotum::factfile::diff::*;
+use factotum::tests::make_task;
+
+fn with_command(name: &str, depends_on: &Vec<&str>, command: &str) -> Task {
+    let mut task = make_task(name, depends_on);
+    task.command = command.to_string();
+    task
+}
+
+#[test]
+fn identical_factfiles_have_no_diff() {
+    let mut a = Factfile::new("none", "job");
+    a.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    let mut b = Factfile::new("none", "job");
+    b.add_task_obj(&make_task("apple", &vec![])).unwrap();
+
+    let diff = diff_factfiles(&a, &b);
+    assert!(diff.is_empty());
+    assert_eq!(format_diff(&diff), "");
+}
+
+#[test]
+fn diff_reports_added_removed_and_changed_tasks() {
+    let mut a = Factfile::new("none", "job");
+    a.add_task_obj(&with_command("apple", &vec![], "./apple.sh")).unwrap();
+    a.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    a.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+
+    let mut b = Factfile::new("none", "job v2");
+    b.add_task_obj(&with_command("apple", &vec![], "./apple.sh --fast")).unwrap();
+    b.add_task_obj(&make_task("egg", &vec![])).unwrap();
+    let mut orange = make_task("orange", &vec!["apple", "egg"]);
+    orange.on_result.continue_job = vec![0, 3];
+    b.add_task_obj(&orange).unwrap();
+
+    let diff = diff_factfiles(&a, &b);
+    assert_eq!(diff.name,
+               Some(("job".to_string(), "job v2".to_string())));
+    assert_eq!(diff.tasks,
+               vec![TaskChange::Changed("apple".to_string(),
+                                        vec!["command: './apple.sh' -> './apple.sh --fast'"
+                                                 .to_string()]),
+                    TaskChange::Removed("turnip".to_string()),
+                    TaskChange::Changed("orange".to_string(),
+                                        vec!["dependsOn: [apple] -> [apple, egg]".to_string(),
+                                             "continueJob: [] -> [0, 3]".to_string()]),
+                    TaskChange::Added("egg".to_string())]);
+
+    assert_eq!(format_diff(&diff),
+               vec!["~ job name: 'job' -> 'job v2'",
+                    "~ task 'apple'",
+                    "    command: './apple.sh' -> './apple.sh --fast'",
+                    "- task 'turnip'",
+                    "~ task 'orange'",
+                    "    dependsOn: [apple] -> [apple, egg]",
+                    "    continueJob: [] -> [0, 3]",
+                    "+ task 'egg'"]
+                   .join("\n"));
+}
@@ -15,7 +15,21 @@
 #[cfg(test)]
 mod tests;
 
-use factotum::factfile::Factfile;
+use factotum::factfile::{Factfile, Task, GROUP_PREFIX};
+use factotum::executor::BARRIER_EXECUTOR;
+
+fn tooltip(task: &Task) -> Option<String> {
+    let parts = task.description
+        .iter()
+        .cloned()
+        .chain(task.owner.iter().map(|o| format!("owner: {}", o)))
+        .collect::<Vec<String>>();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(" - "))
+    }
+}
 
 pub fn generate_graphviz_dot(factfile: &Factfile, start: Option<String>) -> String {
     let tasks = if let Some(start_task) = start {
@@ -34,19 +48,54 @@ pub fn generate_graphviz_dot(factfile: &Factfile, start: Option<String>) -> Stri
 
     let title = format!("digraph \"{}\" {{", factfile.name);
 
-    let task_names = topologically_sorted_tasks.iter()
-        .map(|t| format!("    \"{}\"\n", t.name))
-        .collect::<String>();
+    // a group is drawn as a single node, with its tasks' edges going to and from it
+    let node_name = |name: &str| match factfile.group_of(name) {
+        Some(group) => format!("{}{}", GROUP_PREFIX, group),
+        None => name.to_string(),
+    };
 
-    let task_connections = topologically_sorted_tasks.iter()
-        .map(|t| {
-            t.depends_on
-                .iter()
-                .map(|dep| format!("    \"{}\" -> \"{}\"\n", dep, t.name))
-                .collect::<String>()
+    let mut node_names = vec![];
+    let mut edges = vec![];
+    for task in topologically_sorted_tasks.iter() {
+        let node = node_name(&task.name);
+        for dep in task.depends_on.iter() {
+            let edge = (node_name(dep), node.clone());
+            if edge.0 != edge.1 && !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+        if !node_names.contains(&node) {
+            node_names.push(node);
+        }
+    }
+
+    // barriers are drawn as join points rather than as tasks, and disabled tasks are dashed;
+    // a task's description and owner are shown when it's hovered over
+    let task_names = node_names.iter()
+        .map(|n| {
+            let mut attributes = vec![];
+            if let Some(task) = factfile.find_task(n) {
+                if task.executor == BARRIER_EXECUTOR {
+                    attributes.push("shape=diamond".to_string());
+                }
+                if task.disabled {
+                    attributes.push("style=dashed".to_string());
+                }
+                if let Some(tooltip) = tooltip(task) {
+                    attributes.push(format!("tooltip=\"{}\"", tooltip.replace("\"", "\\\"")));
+                }
+            }
+            if attributes.is_empty() {
+                format!("    \"{}\"\n", n)
+            } else {
+                format!("    \"{}\" [{}]\n", n, attributes.join(", "))
+            }
         })
         .collect::<String>();
 
+    let task_connections = edges.iter()
+        .map(|&(ref from, ref to)| format!("    \"{}\" -> \"{}\"\n", from, to))
+        .collect::<String>();
 
     format!("{}\n{}{}{}", title, task_names, task_connections, "}")
 }
@@ -47,12 +47,12 @@ fn generate_graphviz_dot_good() {
     let example = file_contents(&resource("dot/example_apples.dot"));
 
     let mut ff = Factfile::new("N/A", "Sample job");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     print!("EXPECTED:\n{}\n", example);
 
@@ -68,15 +68,15 @@ fn generate_graphviz_dot_good_poly() {
     let example = file_contents(&resource("dot/example_apples_poly.dot"));
 
     let mut ff = Factfile::new("N/A", "Sample job #2");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
-    ff.add_task_obj(&make_task("milk", &vec!["turnip"]));
-    ff.add_task_obj(&make_task("cheese", &vec!["milk"]));
-    ff.add_task_obj(&make_task("cake", &vec!["milk"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
+    ff.add_task_obj(&make_task("milk", &vec!["turnip"])).unwrap();
+    ff.add_task_obj(&make_task("cheese", &vec!["milk"])).unwrap();
+    ff.add_task_obj(&make_task("cake", &vec!["milk"])).unwrap();
 
     print!("EXPECTED:\n{}\n", example);
 
@@ -92,15 +92,15 @@ fn generate_graphviz_dot_good_short() {
     let example = file_contents(&resource("dot/example_reduced.dot"));
 
     let mut ff = Factfile::new("N/A", "Sample job #3 (reduced run)");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
-    ff.add_task_obj(&make_task("milk", &vec!["turnip"]));
-    ff.add_task_obj(&make_task("cheese", &vec!["milk"]));
-    ff.add_task_obj(&make_task("cake", &vec!["milk"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
+    ff.add_task_obj(&make_task("milk", &vec!["turnip"])).unwrap();
+    ff.add_task_obj(&make_task("cheese", &vec!["milk"])).unwrap();
+    ff.add_task_obj(&make_task("cake", &vec!["milk"])).unwrap();
 
     print!("EXPECTED:\n{}\n", example);
 
@@ -110,3 +110,49 @@ fn generate_graphviz_dot_good_short() {
 
     assert_eq!(actual, example);
 }
+
+#[test]
+fn generate_graphviz_dot_collapses_groups() {
+    let mut ff = Factfile::new("N/A", "Grouped job");
+    ff.add_task_obj(&make_task("extract a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("extract b", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("merge", &vec!["extract a", "extract b"])).unwrap();
+    ff.add_task_obj(&make_task("load", &vec!["merge", "extract b"])).unwrap();
+    ff.groups.insert("extract".to_string(),
+                     vec!["extract a".to_string(), "extract b".to_string()]);
+
+    assert_eq!(generate_graphviz_dot(&ff, None),
+               "digraph \"Grouped job\" {\n    \"group:extract\"\n    \"merge\"\n    \
+                \"load\"\n    \"group:extract\" -> \"merge\"\n    \"merge\" -> \"load\"\n    \
+                \"group:extract\" -> \"load\"\n}");
+}
+
+#[test]
+fn generate_graphviz_dot_marks_barriers() {
+    let mut ff = Factfile::new("N/A", "Barrier job");
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec![])).unwrap();
+    let mut join = make_task("join", &vec!["a", "b"]);
+    join.executor = "barrier".to_string();
+    ff.add_task_obj(&join).unwrap();
+    let mut report = make_task("report", &vec!["join"]);
+    report.disabled = true;
+    ff.add_task_obj(&report).unwrap();
+
+    assert_eq!(generate_graphviz_dot(&ff, None),
+               "digraph \"Barrier job\" {\n    \"b\"\n    \"a\"\n    \"join\" [shape=diamond]\n    \
+                \"report\" [style=dashed]\n    \"a\" -> \"join\"\n    \"b\" -> \"join\"\n    \
+                \"join\" -> \"report\"\n}");
+}
+
+#[test]
+fn generate_graphviz_dot_adds_tooltips() {
+    let mut ff = Factfile::new("N/A", "Owned job");
+    let mut extract = make_task("extract", &vec![]);
+    extract.description = Some("Pulls the \"orders\" table".to_string());
+    extract.owner = Some("data-eng".to_string());
+    ff.add_task_obj(&extract).unwrap();
+
+    assert!(generate_graphviz_dot(&ff, None)
+        .contains("    \"extract\" [tooltip=\"Pulls the \\\"orders\\\" table - owner: data-eng\"]\n"));
+}
@@ -0,0 +1,113 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::{Factfile, Task};
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+
+pub const FORMATS: &'static [&'static str] = &["table", "json", "names"];
+
+fn tasks_in_order(factfile: &Factfile) -> Vec<&Task> {
+    factfile.get_tasks_in_order().into_iter().flat_map(|grp| grp.into_iter()).collect()
+}
+
+fn or_dash(value: Option<&str>) -> String {
+    match value {
+        Some(v) if !v.is_empty() => v.to_string(),
+        _ => "-".to_string(),
+    }
+}
+
+pub fn format_table(factfile: &Factfile) -> String {
+    let header = vec!["TASK", "EXECUTOR", "DEPENDS ON", "GROUP", "OWNER", "DESCRIPTION"]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<String>>();
+
+    let mut rows = vec![header];
+    for task in tasks_in_order(factfile) {
+        let name = if task.disabled {
+            format!("{} (disabled)", task.name)
+        } else {
+            task.name.clone()
+        };
+        rows.push(vec![name,
+                       task.executor.clone(),
+                       or_dash(Some(&task.depends_on.join(", "))),
+                       or_dash(factfile.group_of(&task.name)),
+                       or_dash(task.owner.as_ref().map(|o| o.as_str())),
+                       or_dash(task.description.as_ref().map(|d| d.as_str()))]);
+    }
+
+    let widths = (0..rows[0].len())
+        .map(|col| rows.iter().map(|r| r[col].chars().count()).max().unwrap_or(0))
+        .collect::<Vec<usize>>();
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .map(|(col, cell)| format!("{:width$}", cell, width = widths[col]))
+                .collect::<Vec<String>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn to_json(factfile: &Factfile) -> Json {
+    Json::Array(tasks_in_order(factfile)
+        .into_iter()
+        .map(|task| {
+            let mut d = BTreeMap::new();
+            d.insert("name".to_string(), task.name.to_json());
+            d.insert("executor".to_string(), task.executor.to_json());
+            d.insert("dependsOn".to_string(), task.depends_on.to_json());
+            d.insert("enabled".to_string(), (!task.disabled).to_json());
+            if let Some(group) = factfile.group_of(&task.name) {
+                d.insert("group".to_string(), group.to_json());
+            }
+            if let Some(ref owner) = task.owner {
+                d.insert("owner".to_string(), owner.to_json());
+            }
+            if let Some(ref description) = task.description {
+                d.insert("description".to_string(), description.to_json());
+            }
+            Json::Object(d)
+        })
+        .collect())
+}
+
+// one name per line, for shell completion
+pub fn names(factfile: &Factfile) -> String {
+    tasks_in_order(factfile).iter().map(|t| t.name.clone()).collect::<Vec<String>>().join("\n")
+}
+
+pub fn list_tasks(factfile: &Factfile, format: &str) -> Result<String, String> {
+    match format {
+        "table" => Ok(format_table(factfile)),
+        "json" => Ok(to_json(factfile).pretty().to_string()),
+        "names" => Ok(names(factfile)),
+        other => {
+            Err(format!("'{}' isn't a list-tasks format, use one of {}",
+                        other,
+                        FORMATS.join(", ")))
+        }
+    }
+}
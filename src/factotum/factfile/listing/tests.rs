@@ -0,0 +1,64 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use factotum::factfile::*;
+use factotum::factfile::listing::*;
+use factotum::tests::make_task;
+
+fn listed_factfile() -> Factfile {
+    let mut ff = Factfile::new("none", "job");
+    let mut extract = make_task("extract", &vec![]);
+    extract.executor = "shell".to_string();
+    extract.owner = Some("data-eng".to_string());
+    extract.description = Some("Pulls the orders".to_string());
+    let mut load = make_task("load", &vec!["extract"]);
+    load.executor = "shell".to_string();
+    load.disabled = true;
+    ff.add_task_obj(&extract).unwrap();
+    ff.add_task_obj(&load).unwrap();
+    ff.groups.insert("ingest".to_string(), vec!["extract".to_string()]);
+    ff
+}
+
+#[test]
+fn format_table_good() {
+    let expected = vec!["TASK             EXECUTOR  DEPENDS ON  GROUP   OWNER     DESCRIPTION",
+                        "extract          shell     -           ingest  data-eng  Pulls the orders",
+                        "load (disabled)  shell     extract     -       -         -"];
+
+    assert_eq!(format_table(&listed_factfile()), expected.join("\n"));
+}
+
+#[test]
+fn to_json_good() {
+    let json = to_json(&listed_factfile());
+    let tasks = json.as_array().unwrap();
+
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].find("group").unwrap().as_string(), Some("ingest"));
+    assert_eq!(tasks[0].find("owner").unwrap().as_string(), Some("data-eng"));
+    assert_eq!(tasks[0].find("enabled").unwrap().as_boolean(), Some(true));
+    assert_eq!(tasks[1].find("dependsOn").unwrap().as_array().unwrap().len(), 1);
+    assert_eq!(tasks[1].find("enabled").unwrap().as_boolean(), Some(false));
+    assert!(tasks[1].find("description").is_none());
+}
+
+#[test]
+fn list_tasks_formats() {
+    let ff = listed_factfile();
+
+    assert_eq!(list_tasks(&ff, "names"), Ok("extract\nload".to_string()));
+    assert_eq!(list_tasks(&ff, "xml"),
+               Err("'xml' isn't a list-tasks format, use one of table, json, names".to_string()));
+}
@@ -15,19 +15,45 @@
 #[cfg(test)]
 mod tests;
 mod dot;
+pub mod diff;
+pub mod stats;
+pub mod listing;
+pub mod snapshot;
 
 use daggy::*;
+use factotum::constraint::Constraints;
+use factotum::policy::Policy;
 use factotum::sequencer;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use chrono::{DateTime, NaiveTime, UTC};
 
+// a dependency on "group:<name>" is a dependency on every task in the group
+pub const GROUP_PREFIX: &'static str = "group:";
 
 pub struct Factfile {
     pub name: String,
     pub raw: String,
+    pub variables: BTreeMap<String, String>,
+    // the vars tasks have of their own, layered over the job's variables for them alone
+    pub task_variables: BTreeMap<String, BTreeMap<String, String>>,
+    pub sla: Option<Sla>,
+    pub groups: BTreeMap<String, Vec<String>>,
+    // set when the job runs under a policy, so the tasks generated while it runs are
+    // checked too
+    pub policy: Option<Policy>,
     dag: Dag<Task, ()>,
     root: NodeIndex,
+    // where each task is in the dag, so tasks can be found without walking it
+    index: HashMap<String, NodeIndex>,
+    // the tasks generators add as the job runs, which the run's task list borrows just
+    // as it does the factfile's own
+    generated: Mutex<Vec<Box<Task>>>,
 }
 
-#[derive(Clone,Debug, PartialEq)]
+#[derive(Clone,Debug, PartialEq, Default)]
 pub struct Task {
     pub name: String,
     pub depends_on: Vec<String>,
@@ -35,14 +61,171 @@ pub struct Task {
     pub command: String,
     pub arguments: Vec<String>,
     pub on_result: OnResult,
+    pub circuit_breaker: CircuitBreaker,
+    pub expected_duration: Option<Duration>,
+    // the task's stopped (and fails) once it's printed nothing for this long
+    pub idle_timeout: Option<Duration>,
+    pub requires: Vec<String>,
+    // the local files the task writes, which are checksummed once it succeeds
+    pub produces: Vec<String>,
+    pub disabled: bool,
+    pub description: Option<String>,
+    pub owner: Option<String>,
+    pub stdin: Option<TaskInput>,
+    pub shell: Shell,
+    // run under a pseudo-terminal, where stdout and stderr are the one stream
+    pub tty: bool,
+    pub constraints: Option<TaskConstraints>,
+    pub sandbox: Option<Sandbox>,
+    pub workspace: Option<TaskWorkspace>,
+    pub cache: Option<TaskCache>,
+    // what becomes of the task when a task it depends on is skipped; without it, the task's
+    // skipped too unless the task it depends on was only disabled
+    pub when_upstream_skipped: Option<UpstreamSkipped>,
+    // a condition checked when the task's turn comes, e.g. "tasks.extract.exit_code == 0";
+    // when it's false the task is skipped
+    pub when: Option<String>,
+}
+
+// why a task couldn't be added to a factfile
+#[derive(Clone, Debug, PartialEq)]
+pub enum TaskError {
+    Duplicate(String),
+    DependsOnItself(String),
+    UnknownDependency { task: String, dependency: String },
+    Cycle { task: String, dependency: String },
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TaskError::Duplicate(ref task) => {
+                write!(f, "the task '{}' is defined more than once", task)
+            }
+            TaskError::DependsOnItself(ref task) => {
+                write!(f, "the task '{}' depends on itself", task)
+            }
+            TaskError::UnknownDependency { ref task, ref dependency } => {
+                write!(f,
+                       "the task '{}' depends on '{}', which isn't defined before it",
+                       task,
+                       dependency)
+            }
+            TaskError::Cycle { ref task, ref dependency } => {
+                write!(f,
+                       "the task '{}' can't depend on '{}' as it would make a cycle",
+                       task,
+                       dependency)
+            }
+        }
+    }
+}
+
+// checked just before the task runs; a task whose constraints aren't met is skipped,
+// with the tasks that depend on it, unless it fails the job instead
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskConstraints {
+    pub constraints: Constraints,
+    pub fail_job: bool,
 }
 
+// what runs a shell task's command
 #[derive(Clone,Debug, PartialEq)]
+pub enum Shell {
+    Sh,
+    Bash,
+    Pwsh,
+    // "none" in a factfile - the command is run directly with the arguments, so nothing in
+    // them is interpreted
+    Exec,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UpstreamSkipped {
+    Run,
+    Skip,
+    Fail,
+}
+
+impl Default for Shell {
+    fn default() -> Self {
+        Shell::Sh
+    }
+}
+
+// what a sandboxed task is still allowed to do; "sandbox": {} in a factfile takes
+// everything away, leaving it only able to write to its own /tmp
+#[derive(Clone,Debug, PartialEq)]
+pub struct Sandbox {
+    pub network: bool,
+    pub read_only_root: bool,
+    pub writable_paths: Vec<String>,
+    pub seccomp: bool,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox {
+            network: false,
+            read_only_root: true,
+            writable_paths: vec![],
+            seccomp: true,
+        }
+    }
+}
+
+// the scratch directories a task is given when the job runs with a workspace; it only
+// has a directory of its own if it asks for one with "workspace": true
+#[derive(Clone,Debug, PartialEq)]
+pub struct TaskWorkspace {
+    pub run_dir: String,
+    pub task_dir: Option<String>,
+}
+
+// what a task's result depends on besides its command, so it can be reused while none of
+// it changes; the task's key is only worked out when its turn comes in a job run with --cache
+#[derive(Clone,Debug, PartialEq, Default)]
+pub struct TaskCache {
+    pub inputs: Vec<String>,
+    pub variables: BTreeMap<String, String>,
+    pub enabled: bool,
+    // the run the task last succeeded in, and its key then
+    pub last_success: Option<(String, String)>,
+}
+
+// what's piped into a task's standard input
+#[derive(Clone,Debug, PartialEq)]
+pub enum TaskInput {
+    Text(String),
+    File(String),
+}
+
+#[derive(Clone,Debug, PartialEq, Default)]
 pub struct OnResult {
     pub terminate_job: Vec<i32>,
     pub continue_job: Vec<i32>,
 }
 
+#[derive(Clone,Debug, PartialEq, Default)]
+pub struct CircuitBreaker {
+    pub skip_if_failed_last: Option<u32>,
+    pub skip_if_failure_rate: Option<FailureRate>,
+}
+
+#[derive(Clone,Debug, PartialEq)]
+pub struct FailureRate {
+    pub threshold: f64,
+    pub over_last: u32,
+}
+
+// when the job is expected to have finished by
+#[derive(Clone,Debug, PartialEq)]
+pub enum Sla {
+    Within(Duration),
+    TimeOfDay(NaiveTime),
+    At(DateTime<UTC>),
+}
+
 impl Factfile {
     pub fn new<S: Into<String>>(raw: S, name: S) -> Factfile {
         let mut new_dag = Dag::<Task, ()>::new();
@@ -56,16 +239,49 @@ impl Factfile {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         };
         let parent = new_dag.add_node(root_task);
         Factfile {
             name: name.into(),
             dag: new_dag,
             root: parent,
+            index: HashMap::new(),
             raw: raw.into(),
+            variables: BTreeMap::new(),
+            task_variables: BTreeMap::new(),
+            sla: None,
+            groups: BTreeMap::new(),
+            policy: None,
+            generated: Mutex::new(vec![]),
         }
     }
 
+    pub fn keep_generated(&self, task: Task) -> &Task {
+        let task = Box::new(task);
+        let kept: *const Task = &*task;
+        self.generated.lock().unwrap().push(task);
+        // a boxed task doesn't move as more are added, and none are dropped before the
+        // factfile is, so it can be borrowed for as long as the factfile
+        unsafe { &*kept }
+    }
+
     pub fn as_dotfile(&self, start_task: Option<String>) -> String {
         dot::generate_graphviz_dot(&self, start_task)
     }
@@ -99,7 +315,7 @@ impl Factfile {
     }
 
     fn find_task_by_name(&self, name: &str) -> Option<(NodeIndex, &Task)> {
-        sequencer::find_task_recursive(&self.dag, name, self.root)
+        self.index.get(name).map(|idx| (*idx, &self.dag[*idx]))
     }
 
     pub fn can_job_run_from_task(&self, name: &str) -> Result<bool, &'static str> {
@@ -111,16 +327,137 @@ impl Factfile {
         }
     }
 
-    // this is used in tests
-    #[cfg(test)]
-    pub fn add_task_obj(&mut self, task: &Task) {
-        self.add_task(&task.name,
-                      &task.depends_on.iter().map(AsRef::as_ref).collect(),
-                      &task.executor,
-                      &task.command,
-                      &task.arguments.iter().map(AsRef::as_ref).collect(),
-                      &task.on_result.terminate_job,
-                      &task.on_result.continue_job) // TODO should this function really be the main one? or even the only one, its nicer to pass a struct as it has named params
+    pub fn group_of(&self, name: &str) -> Option<&str> {
+        self.groups
+            .iter()
+            .find(|&(_, members)| members.iter().any(|m| m == name))
+            .map(|(group, _)| group.as_ref())
+    }
+
+    // the task names a list of task names and "group:<name>"s refer to
+    pub fn resolve_targets(&self, targets: &Vec<String>) -> Result<Vec<String>, String> {
+        let mut names = vec![];
+        for target in targets.iter() {
+            let found = if target.starts_with(GROUP_PREFIX) {
+                let group = &target[GROUP_PREFIX.len()..];
+                try!(self.groups
+                    .get(group)
+                    .cloned()
+                    .ok_or(format!("there's no group called '{}'", group)))
+            } else if self.find_task(target).is_some() {
+                vec![target.clone()]
+            } else {
+                return Err(format!("there's no task called '{}'", target));
+            };
+            for name in found.into_iter() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    // a copy of the factfile with the targets and everything they depend on, so the job
+    // ends once they've run
+    pub fn up_to(&self, targets: &Vec<String>) -> Result<Factfile, String> {
+        let mut names = try!(self.resolve_targets(targets));
+        let mut to_visit = names.clone();
+        while let Some(name) = to_visit.pop() {
+            if let Some(task) = self.find_task(&name) {
+                for dependency in task.depends_on.iter() {
+                    if !names.contains(dependency) {
+                        names.push(dependency.clone());
+                        to_visit.push(dependency.clone());
+                    }
+                }
+            }
+        }
+        self.select(&names).map_err(|e| e.to_string())
+    }
+
+    // a copy of the factfile with just the named tasks; dependencies on tasks that
+    // aren't kept are dropped
+    pub fn select(&self, names: &Vec<String>) -> Result<Factfile, TaskError> {
+        let mut selected = Factfile::new(self.raw.clone(), self.name.clone());
+        selected.variables = self.variables.clone();
+        selected.task_variables = self.task_variables
+            .iter()
+            .filter(|&(name, _)| names.contains(name))
+            .map(|(name, vars)| (name.clone(), vars.clone()))
+            .collect();
+        selected.sla = self.sla.clone();
+        selected.policy = self.policy.clone();
+
+        for task in self.get_tasks_in_order().into_iter().flat_map(|grp| grp.into_iter()) {
+            if !names.contains(&task.name) || selected.find_task(&task.name).is_some() {
+                continue;
+            }
+            let mut kept = task.clone();
+            kept.depends_on.retain(|d| names.contains(d));
+            try!(selected.add_task_obj(&kept));
+        }
+
+        for (group, members) in self.groups.iter() {
+            let kept = members.iter()
+                .filter(|m| names.contains(m))
+                .cloned()
+                .collect::<Vec<String>>();
+            if !kept.is_empty() {
+                selected.groups.insert(group.clone(), kept);
+            }
+        }
+
+        Ok(selected)
+    }
+
+    pub fn add_task_obj(&mut self, task: &Task) -> Result<(), TaskError> {
+        try!(self.add_task(&task.name,
+                           &task.depends_on.iter().map(AsRef::as_ref).collect(),
+                           &task.executor,
+                           &task.command,
+                           &task.arguments.iter().map(AsRef::as_ref).collect(),
+                           &task.on_result.terminate_job,
+                           &task.on_result.continue_job)); // TODO should this function really be the main one? or even the only one, its nicer to pass a struct as it has named params
+        if let Some(added) = self.find_task_mut(&task.name) {
+            added.circuit_breaker = task.circuit_breaker.clone();
+            added.expected_duration = task.expected_duration;
+            added.idle_timeout = task.idle_timeout;
+            added.requires = task.requires.clone();
+            added.produces = task.produces.clone();
+            added.disabled = task.disabled;
+            added.description = task.description.clone();
+            added.owner = task.owner.clone();
+            added.stdin = task.stdin.clone();
+            added.shell = task.shell.clone();
+            added.tty = task.tty;
+            added.constraints = task.constraints.clone();
+            added.sandbox = task.sandbox.clone();
+            added.workspace = task.workspace.clone();
+            added.cache = task.cache.clone();
+            added.when_upstream_skipped = task.when_upstream_skipped;
+            added.when = task.when.clone();
+        }
+        Ok(())
+    }
+
+    pub fn get_dependents(&self, name: &str) -> Vec<&Task> {
+        self.get_tasks_in_order()
+            .into_iter()
+            .flat_map(|grp| grp.into_iter())
+            .filter(|t| t.depends_on.iter().any(|d| d == name))
+            .collect()
+    }
+
+    pub fn find_task(&self, name: &str) -> Option<&Task> {
+        self.find_task_by_name(name).map(|(_, task)| task)
+    }
+
+    pub fn find_task_mut(&mut self, name: &str) -> Option<&mut Task> {
+        match self.find_task_by_name(name) {
+            Some((idx, _)) => self.dag.node_weight_mut(idx),
+            None => None,
+        }
     }
 
     pub fn add_task(&mut self,
@@ -130,16 +467,15 @@ impl Factfile {
                     command: &str,
                     args: &Vec<&str>,
                     terminate_job_on: &Vec<i32>,
-                    continue_job_on: &Vec<i32>) {
-        // TODO ensure all fields are validated, Result is returned rather than panic (and get tests in shape for this validation)
-
-        if let Some((_, task)) = self.find_task_by_name(name) {
-            panic!("Key '{}' already exists!", task.name)
+                    continue_job_on: &Vec<i32>)
+                    -> Result<(), TaskError> {
+        if self.find_task_by_name(name).is_some() {
+            return Err(TaskError::Duplicate(name.to_string()));
         }
 
         if depends_on.len() > 0 {
             if depends_on.iter().any(|s| s == &name) {
-                panic!("A task cannot depend on itself");
+                return Err(TaskError::DependsOnItself(name.to_string()));
             }
 
             let mut parents: Vec<NodeIndex> = vec![];
@@ -150,9 +486,10 @@ impl Factfile {
                     parents.push(idx);
                     deps.push(dependency.to_string());
                 } else {
-                    panic!("A task must have it's dependencies already defined - couldn't find \
-                            definition of {}",
-                           dependency);
+                    return Err(TaskError::UnknownDependency {
+                        task: name.to_string(),
+                        dependency: dependency.to_string(),
+                    });
                 }
             }
 
@@ -166,13 +503,33 @@ impl Factfile {
                     terminate_job: terminate_job_on.iter().map(|i| *i).collect(),
                     continue_job: continue_job_on.iter().map(|i| *i).collect(),
                 },
+                circuit_breaker: CircuitBreaker::default(),
+                expected_duration: None,
+                idle_timeout: None,
+                requires: vec![],
+                produces: vec![],
+                disabled: false,
+                description: None,
+                owner: None,
+                stdin: None,
+                shell: Shell::Sh,
+                tty: false,
+                constraints: None,
+                sandbox: None,
+                workspace: None,
+                cache: None,
+                when_upstream_skipped: None,
+                when: None,
             });
 
+            self.index.insert(name.to_string(), node);
+
             for parent in parents {
                 if let Err(_) = self.dag.add_edge(parent, node, ()) {
-                    panic!("Couldn't add edge between {} and {}!",
-                           self.dag[parent].name,
-                           self.dag[node].name);
+                    return Err(TaskError::Cycle {
+                        task: name.to_string(),
+                        dependency: self.dag[parent].name.clone(),
+                    });
                 }
             }
         } else {
@@ -186,8 +543,27 @@ impl Factfile {
                     terminate_job: terminate_job_on.iter().map(|i| *i).collect(),
                     continue_job: continue_job_on.iter().map(|i| *i).collect(),
                 },
+                circuit_breaker: CircuitBreaker::default(),
+                expected_duration: None,
+                idle_timeout: None,
+                requires: vec![],
+                produces: vec![],
+                disabled: false,
+                description: None,
+                owner: None,
+                stdin: None,
+                shell: Shell::Sh,
+                tty: false,
+                constraints: None,
+                sandbox: None,
+                workspace: None,
+                cache: None,
+                when_upstream_skipped: None,
+                when: None,
             };
-            self.dag.add_child(self.root, (), new_task);
+            let (_, node) = self.dag.add_child(self.root, (), new_task);
+            self.index.insert(name.to_string(), node);
         }
+        Ok(())
     }
 }
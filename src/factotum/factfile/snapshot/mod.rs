@@ -0,0 +1,110 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Factfile;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+
+// tasks are sorted by name and edges by (from, to), so a snapshot only changes when the
+// resolved DAG does, and not when tasks are reordered in the factfile
+pub fn snapshot_json(factfile: &Factfile) -> Json {
+    let mut tasks = factfile.get_tasks_in_order()
+        .into_iter()
+        .flat_map(|grp| grp.into_iter())
+        .collect::<Vec<_>>();
+    tasks.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut edges = vec![];
+    let task_json = tasks.iter()
+        .map(|task| {
+            for dep in task.depends_on.iter() {
+                edges.push(vec![dep.clone(), task.name.clone()]);
+            }
+
+            let mut on_result = BTreeMap::new();
+            on_result.insert("continueJob".to_string(), task.on_result.continue_job.to_json());
+            on_result.insert("terminateJobWithSuccess".to_string(),
+                             task.on_result.terminate_job.to_json());
+
+            let mut d = BTreeMap::new();
+            d.insert("name".to_string(), task.name.to_json());
+            d.insert("executor".to_string(), task.executor.to_json());
+            d.insert("command".to_string(), task.command.to_json());
+            d.insert("arguments".to_string(), task.arguments.to_json());
+            d.insert("onResult".to_string(), Json::Object(on_result));
+            d.insert("enabled".to_string(), (!task.disabled).to_json());
+            if let Some(group) = factfile.group_of(&task.name) {
+                d.insert("group".to_string(), group.to_json());
+            }
+            Json::Object(d)
+        })
+        .collect::<Vec<Json>>();
+    edges.sort();
+
+    let mut d = BTreeMap::new();
+    d.insert("name".to_string(), factfile.name.to_json());
+    d.insert("tasks".to_string(), Json::Array(task_json));
+    d.insert("edges".to_string(), edges.to_json());
+    Json::Object(d)
+}
+
+pub fn snapshot(factfile: &Factfile) -> String {
+    format!("{}\n", snapshot_json(factfile).pretty())
+}
+
+// the lines that were removed (-) and added (+) going from one text to the other
+pub fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    let old = before.lines().collect::<Vec<&str>>();
+    let new = after.lines().collect::<Vec<&str>>();
+
+    // lcs[i][j] is the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                ::std::cmp::max(lcs[i + 1][j], lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            changes.push(format!("- {}", old[i]));
+            i += 1;
+        } else {
+            changes.push(format!("+ {}", new[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+pub fn check_snapshot(factfile: &Factfile, saved: &str) -> Result<(), String> {
+    let changes = diff_lines(saved, &snapshot(factfile));
+    if changes.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("the resolved DAG doesn't match the snapshot:\n{}", changes.join("\n")))
+    }
+}
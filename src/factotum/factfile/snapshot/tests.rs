@@ -0,0 +1,137 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::Factfile;
+use factotum::tests::make_task;
+
+fn job(order: Vec<&str>) -> Factfile {
+    let mut ff = Factfile::new("N/A", "snap");
+    for name in order {
+        let mut task = match name {
+            "load" => make_task("load", &vec!["extract"]),
+            "report" => make_task("report", &vec!["load", "extract"]),
+            _ => make_task(name, &vec![]),
+        };
+        task.command = format!("./{}.sh", name);
+        task.on_result.continue_job = vec![0];
+        ff.add_task_obj(&task).unwrap();
+    }
+    ff
+}
+
+#[test]
+fn snapshot_is_canonical() {
+    let expected = r#"{
+  "edges": [
+    [
+      "extract",
+      "load"
+    ],
+    [
+      "extract",
+      "report"
+    ],
+    [
+      "load",
+      "report"
+    ]
+  ],
+  "name": "snap",
+  "tasks": [
+    {
+      "arguments": [],
+      "command": "./cleanup.sh",
+      "enabled": true,
+      "executor": "",
+      "name": "cleanup",
+      "onResult": {
+        "continueJob": [
+          0
+        ],
+        "terminateJobWithSuccess": []
+      }
+    },
+    {
+      "arguments": [],
+      "command": "./extract.sh",
+      "enabled": true,
+      "executor": "",
+      "name": "extract",
+      "onResult": {
+        "continueJob": [
+          0
+        ],
+        "terminateJobWithSuccess": []
+      }
+    },
+    {
+      "arguments": [],
+      "command": "./load.sh",
+      "enabled": true,
+      "executor": "",
+      "name": "load",
+      "onResult": {
+        "continueJob": [
+          0
+        ],
+        "terminateJobWithSuccess": []
+      }
+    },
+    {
+      "arguments": [],
+      "command": "./report.sh",
+      "enabled": true,
+      "executor": "",
+      "name": "report",
+      "onResult": {
+        "continueJob": [
+          0
+        ],
+        "terminateJobWithSuccess": []
+      }
+    }
+  ]
+}
+"#;
+
+    assert_eq!(snapshot(&job(vec!["extract", "cleanup", "load", "report"])), expected);
+    assert_eq!(snapshot(&job(vec!["cleanup", "extract", "load", "report"])), expected);
+}
+
+#[test]
+fn diff_lines_shows_changes() {
+    assert!(diff_lines("a\nb\nc", "a\nb\nc").is_empty());
+    assert_eq!(diff_lines("a\nb\nc", "a\nx\nc\nd"), vec!["- b", "+ x", "+ d"]);
+    assert_eq!(diff_lines("", "a"), vec!["+ a"]);
+    assert_eq!(diff_lines("a\na", "a"), vec!["- a"]);
+}
+
+#[test]
+fn check_snapshot_fails_on_drift() {
+    let saved = snapshot(&job(vec!["extract", "load", "report"]));
+    assert_eq!(check_snapshot(&job(vec!["extract", "load", "report"]), &saved), Ok(()));
+
+    let mut changed = job(vec!["extract", "load"]);
+    let mut task = make_task("report", &vec!["load", "extract"]);
+    task.command = "./report.sh --all".to_string();
+    task.on_result.continue_job = vec![0];
+    changed.add_task_obj(&task).unwrap();
+
+    assert_eq!(check_snapshot(&changed, &saved),
+               Err(vec!["the resolved DAG doesn't match the snapshot:",
+                        "-       \"command\": \"./report.sh\",",
+                        "+       \"command\": \"./report.sh --all\","]
+                   .join("\n")));
+}
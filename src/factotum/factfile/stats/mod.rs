@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Factfile;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactfileStats {
+    pub task_count: usize,
+    pub edge_count: usize,
+    pub max_depth: usize,
+    pub max_width: usize,
+    pub isolated_tasks: Vec<String>,
+    pub executors: BTreeMap<String, usize>,
+}
+
+// depth and width are measured over the execution levels, so a task's depth is
+// the length of the longest chain of dependencies leading to it
+pub fn factfile_stats(factfile: &Factfile) -> FactfileStats {
+    let levels = factfile.get_tasks_in_order()
+        .into_iter()
+        .filter(|grp| !grp.is_empty())
+        .collect::<Vec<_>>();
+    let tasks = levels.iter().flat_map(|grp| grp.iter()).collect::<Vec<_>>();
+
+    let mut isolated_tasks = tasks.iter()
+        .filter(|t| t.depends_on.is_empty() && factfile.get_dependents(&t.name).is_empty())
+        .map(|t| t.name.clone())
+        .collect::<Vec<String>>();
+    isolated_tasks.sort();
+
+    let mut executors = BTreeMap::new();
+    for task in tasks.iter() {
+        *executors.entry(task.executor.clone()).or_insert(0) += 1;
+    }
+
+    FactfileStats {
+        task_count: tasks.len(),
+        edge_count: tasks.iter().map(|t| t.depends_on.len()).sum(),
+        max_depth: levels.len(),
+        max_width: levels.iter().map(|grp| grp.len()).max().unwrap_or(0),
+        isolated_tasks: isolated_tasks,
+        executors: executors,
+    }
+}
+
+pub fn format_stats(stats: &FactfileStats) -> String {
+    let mut lines = vec![format!("Tasks:          {}", stats.task_count),
+                         format!("Edges:          {}", stats.edge_count),
+                         format!("Max depth:      {}", stats.max_depth),
+                         format!("Max width:      {}", stats.max_width)];
+
+    if stats.isolated_tasks.is_empty() {
+        lines.push("Isolated tasks: none".to_string());
+    } else {
+        lines.push(format!("Isolated tasks: {}", stats.isolated_tasks.join(", ")));
+    }
+
+    lines.push("Executors:".to_string());
+    for (executor, count) in stats.executors.iter() {
+        lines.push(format!("  {}: {}", executor, count));
+    }
+
+    lines.join("\n")
+}
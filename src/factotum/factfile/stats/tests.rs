@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use factotum::factfile::*;
+use factotum::factfile::stats::*;
+use factotum::tests::make_task;
+use std::collections::BTreeMap;
+
+fn with_executor(name: &str, depends_on: &Vec<&str>, executor: &str) -> Task {
+    let mut task = make_task(name, depends_on);
+    task.executor = executor.to_string();
+    task
+}
+
+#[test]
+fn stats_good() {
+    //   a       e
+    //  / \
+    // b   c
+    //  \ / \
+    //   d   |
+    //    \ /
+    //     f
+    let mut ff = Factfile::new("none", "job");
+    ff.add_task_obj(&with_executor("a", &vec![], "shell")).unwrap();
+    ff.add_task_obj(&with_executor("b", &vec!["a"], "shell")).unwrap();
+    ff.add_task_obj(&with_executor("c", &vec!["a"], "shell")).unwrap();
+    ff.add_task_obj(&with_executor("d", &vec!["b", "c"], "shell")).unwrap();
+    ff.add_task_obj(&with_executor("e", &vec![], "docker")).unwrap();
+    ff.add_task_obj(&with_executor("f", &vec!["c", "d"], "shell")).unwrap();
+
+    let mut executors = BTreeMap::new();
+    executors.insert("docker".to_string(), 1);
+    executors.insert("shell".to_string(), 5);
+
+    let stats = factfile_stats(&ff);
+    assert_eq!(stats,
+               FactfileStats {
+                   task_count: 6,
+                   edge_count: 6,
+                   max_depth: 4,
+                   max_width: 2,
+                   isolated_tasks: vec!["e".to_string()],
+                   executors: executors,
+               });
+
+    let expected = vec!["Tasks:          6",
+                        "Edges:          6",
+                        "Max depth:      4",
+                        "Max width:      2",
+                        "Isolated tasks: e",
+                        "Executors:",
+                        "  docker: 1",
+                        "  shell: 5"];
+    assert_eq!(format_stats(&stats), expected.join("\n"));
+}
+
+#[test]
+fn stats_without_isolated_tasks() {
+    let mut ff = Factfile::new("none", "job");
+    ff.add_task_obj(&with_executor("a", &vec![], "shell")).unwrap();
+    ff.add_task_obj(&with_executor("b", &vec!["a"], "shell")).unwrap();
+
+    let stats = factfile_stats(&ff);
+    assert_eq!(stats.max_depth, 2);
+    assert_eq!(stats.max_width, 1);
+    assert!(format_stats(&stats).contains("Isolated tasks: none"));
+}
@@ -15,40 +15,48 @@
 use factotum::factfile::*;
 use factotum::tests::compare_tasks;
 use factotum::tests::make_task;
+use std::collections::BTreeMap;
 
 #[test]
-#[should_panic(expected = "Key 'hello' already exists!")]
-fn duplicate_names_panics() {
+fn duplicate_names_are_errors() {
     let mut f = Factfile::new("none", "test");
     let dup_task = make_task("hello", &vec![]);
-    f.add_task_obj(&dup_task);
-    f.add_task_obj(&dup_task);
+    f.add_task_obj(&dup_task).unwrap();
+    assert_eq!(f.add_task_obj(&dup_task),
+               Err(TaskError::Duplicate("hello".to_string())));
 }
 
 #[test]
-#[should_panic(expected = "A task cannot depend on itself")]
 fn job_depend_itself() {
     let self_depending_task = make_task("hello", &vec!["hello"]);
-    Factfile::new("none", "test").add_task_obj(&self_depending_task);
+    assert_eq!(Factfile::new("none", "test").add_task_obj(&self_depending_task),
+               Err(TaskError::DependsOnItself("hello".to_string())));
 }
 
 #[test]
-#[should_panic(expected = "A task must have it's dependencies already defined")]
 fn task_depend_existing_tasks_only() {
     let non_existing_task = make_task("mytask", &vec!["undefined as yet"]);
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&non_existing_task);
+    let err = ff.add_task_obj(&non_existing_task).unwrap_err();
+    assert_eq!(err,
+               TaskError::UnknownDependency {
+                   task: "mytask".to_string(),
+                   dependency: "undefined as yet".to_string(),
+               });
+    assert_eq!(err.to_string(),
+               "the task 'mytask' depends on 'undefined as yet', which isn't defined before it");
+    assert!(ff.find_task("mytask").is_none());
 }
 
 #[test]
 fn a_complicated_tree_works() {
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     //           FactotumJob --------------
     //          /                          \
@@ -71,10 +79,10 @@ fn a_complicated_tree_works() {
 #[test]
 fn heirachy_only_last() {
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&make_task("a", &vec![]));
-    ff.add_task_obj(&make_task("b", &vec!["a"]));
-    ff.add_task_obj(&make_task("c", &vec!["a"]));
-    ff.add_task_obj(&make_task("banana", &vec!["b", "a"]));
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("banana", &vec!["b", "a"])).unwrap();
 
     //              FactotumJob
     //                  |
@@ -99,10 +107,10 @@ fn heirachy_only_last() {
 #[test]
 fn no_cycles_ok() {
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&make_task("hello", &vec![]));
-    ff.add_task_obj(&make_task("hello_world", &vec!["hello"]));
-    ff.add_task_obj(&make_task("say_hello", &vec!["hello"]));
-    ff.add_task_obj(&make_task("say_goodbye", &vec!["say_hello", "hello_world"]));
+    ff.add_task_obj(&make_task("hello", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("hello_world", &vec!["hello"])).unwrap();
+    ff.add_task_obj(&make_task("say_hello", &vec!["hello"])).unwrap();
+    ff.add_task_obj(&make_task("say_goodbye", &vec!["say_hello", "hello_world"])).unwrap();
 
     //              FactotumJob
     //                  |
@@ -123,9 +131,9 @@ fn no_cycles_ok() {
 fn can_job_run_from_task_name_not_present_in_dag() {
     // invalid task name
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&make_task("a", &vec![]));
-    ff.add_task_obj(&make_task("b", &vec!["a"]));
-    ff.add_task_obj(&make_task("c", &vec!["a"]));
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["a"])).unwrap();
 
     match ff.can_job_run_from_task("this task name does not exist") {
         Err(msg) => assert_eq!(msg, "the task specified could not be found"),
@@ -138,10 +146,10 @@ fn can_job_run_from_task_name_not_present_in_dag() {
 fn can_job_run_from_task_name() {
     let mut ff = Factfile::new("none", "test");
 
-    ff.add_task_obj(&make_task("a", &vec![]));
-    ff.add_task_obj(&make_task("b", &vec!["a"]));
-    ff.add_task_obj(&make_task("c", &vec!["a"]));
-    ff.add_task_obj(&make_task("d", &vec!["b", "c"]));
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("d", &vec!["b", "c"])).unwrap();
 
     match ff.can_job_run_from_task("c") { // we can't run from c, because d depends on b also
         Ok(b) => assert_eq!(false, b),
@@ -166,9 +174,9 @@ fn running_jobs_from_non_existing_task() {
 #[test]
 fn running_jobs_from_existing_task() {
     let mut ff = Factfile::new("none", "test");
-    ff.add_task_obj(&make_task("a", &vec![]));
-    ff.add_task_obj(&make_task("b", &vec!["a"]));
-    ff.add_task_obj(&make_task("c", &vec!["b"]));
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["b"])).unwrap();
 
     let expected = vec![vec!["b"], vec!["c"]];
 
@@ -176,3 +184,142 @@ fn running_jobs_from_existing_task() {
 
     compare_tasks(expected, actual);
 }
+
+#[test]
+fn get_dependents_are_direct_children() {
+    let mut ff = Factfile::new("none", "test");
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple", "turnip"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["egg"])).unwrap();
+
+    let mut dependents = ff.get_dependents("apple").iter().map(|t| t.name.clone()).collect::<Vec<String>>();
+    dependents.sort();
+    assert_eq!(dependents, vec!["egg", "orange"]);
+    assert!(ff.get_dependents("potato").is_empty());
+    assert_eq!(ff.find_task("egg").unwrap().depends_on, vec!["apple", "turnip"]);
+    assert!(ff.find_task("nope").is_none());
+}
+
+fn grouped_factfile() -> Factfile {
+    let mut ff = Factfile::new("none", "test");
+    ff.add_task_obj(&make_task("extract a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("extract b", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("merge", &vec!["extract a", "extract b"])).unwrap();
+    ff.add_task_obj(&make_task("load", &vec!["merge"])).unwrap();
+    ff.groups.insert("extract".to_string(),
+                     vec!["extract a".to_string(), "extract b".to_string()]);
+    ff
+}
+
+#[test]
+fn resolve_targets_expands_groups() {
+    let ff = grouped_factfile();
+
+    assert_eq!(ff.group_of("extract b"), Some("extract"));
+    assert_eq!(ff.group_of("merge"), None);
+    assert_eq!(ff.resolve_targets(&vec!["group:extract".to_string(), "extract a".to_string(),
+                                        "load".to_string()]),
+               Ok(vec!["extract a".to_string(), "extract b".to_string(), "load".to_string()]));
+    assert_eq!(ff.resolve_targets(&vec!["group:load".to_string()]),
+               Err("there's no group called 'load'".to_string()));
+    assert_eq!(ff.resolve_targets(&vec!["nope".to_string()]),
+               Err("there's no task called 'nope'".to_string()));
+}
+
+#[test]
+fn select_keeps_named_tasks() {
+    let ff = grouped_factfile();
+
+    let selected = ff.select(&vec!["extract b".to_string(), "merge".to_string()]).unwrap();
+
+    let names = selected.get_tasks_in_order()
+        .iter()
+        .map(|grp| grp.iter().map(|t| t.name.clone()).collect::<Vec<String>>())
+        .collect::<Vec<Vec<String>>>();
+    assert_eq!(names, vec![vec!["extract b".to_string()], vec!["merge".to_string()]]);
+    assert_eq!(selected.find_task("merge").unwrap().depends_on, vec!["extract b"]);
+    assert_eq!(selected.groups.get("extract"), Some(&vec!["extract b".to_string()]));
+}
+
+#[test]
+fn up_to_keeps_ancestors() {
+    let mut ff = grouped_factfile();
+    ff.add_task_obj(&make_task("report", &vec!["extract a"])).unwrap();
+
+    let names = |ff: &Factfile| {
+        let mut names = ff.get_tasks_in_order()
+            .iter()
+            .flat_map(|grp| grp.iter().map(|t| t.name.clone()))
+            .collect::<Vec<String>>();
+        names.sort();
+        names
+    };
+
+    let up_to_merge = ff.up_to(&vec!["merge".to_string()]).unwrap();
+    assert_eq!(names(&up_to_merge), vec!["extract a", "extract b", "merge"]);
+    assert_eq!(up_to_merge.find_task("merge").unwrap().depends_on,
+               vec!["extract a", "extract b"]);
+
+    let up_to_group = ff.up_to(&vec!["group:extract".to_string(), "report".to_string()]).unwrap();
+    assert_eq!(names(&up_to_group), vec!["extract a", "extract b", "report"]);
+
+    assert_eq!(ff.up_to(&vec!["nope".to_string()]).err(),
+               Some("there's no task called 'nope'".to_string()));
+}
+
+#[test]
+fn select_keeps_the_kept_tasks_vars() {
+    let mut ff = grouped_factfile();
+    for name in vec!["extract a", "merge", "load"] {
+        let mut vars = BTreeMap::new();
+        vars.insert("table".to_string(), name.to_string());
+        ff.task_variables.insert(name.to_string(), vars);
+    }
+
+    let selected = ff.select(&vec!["extract b".to_string(), "merge".to_string()]).unwrap();
+    assert_eq!(selected.task_variables.keys().collect::<Vec<&String>>(), vec!["merge"]);
+    assert_eq!(selected.task_variables["merge"]["table"], "merge");
+
+    let up_to_merge = ff.up_to(&vec!["merge".to_string()]).unwrap();
+    assert_eq!(up_to_merge.task_variables.keys().collect::<Vec<&String>>(),
+               vec!["extract a", "merge"]);
+}
+
+#[test]
+fn generated_tasks_are_kept_with_the_factfile() {
+    let ff = grouped_factfile();
+
+    let first = ff.keep_generated(make_task("table a", &vec![]));
+    let second = ff.keep_generated(make_task("table b", &vec!["table a"]));
+
+    assert_eq!(first.name, "table a");
+    assert_eq!(second.depends_on, vec!["table a"]);
+    // they aren't tasks of the factfile itself
+    assert!(ff.find_task("table a").is_none());
+}
+
+#[test]
+fn large_dags_are_ordered_by_longest_path() {
+    // every task depends on "setup" and the task before it, so each task can be reached
+    // along many paths of different lengths
+    let mut ff = Factfile::new("none", "test");
+    ff.add_task_obj(&make_task("setup", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("t0", &vec!["setup"])).unwrap();
+    for i in 1..10000 {
+        let previous = format!("t{}", i - 1);
+        ff.add_task_obj(&make_task(&format!("t{}", i), &vec![&previous, "setup"])).unwrap();
+    }
+
+    let tasks = ff.get_tasks_in_order();
+    assert_eq!(tasks.len(), 10001);
+    assert!(tasks.iter().all(|row| row.len() == 1));
+    assert_eq!(tasks[0][0].name, "setup");
+    assert_eq!(tasks[10000][0].name, "t9999");
+
+    assert_eq!(ff.find_task("t5000").map(|t| t.depends_on.clone()),
+               Some(vec!["t4999".to_string(), "setup".to_string()]));
+    assert_eq!(ff.can_job_run_from_task("t9000"), Ok(false));
+    assert_eq!(ff.can_job_run_from_task("setup"), Ok(true));
+}
@@ -0,0 +1,418 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use chrono::{DateTime, UTC};
+use factotum::retry;
+use factotum::waitfor::WaitOptions;
+use rustc_serialize::json::{self, Json, ToJson};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::process::Command;
+use std::time::Duration;
+
+pub const CLOUD_RUN_EXECUTOR: &'static str = "cloud-run";
+pub const CLOUD_BATCH_EXECUTOR: &'static str = "cloud-batch";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct GcpLocation {
+    pub project: String,
+    pub region: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CloudRunJob {
+    pub job: String,
+    pub location: GcpLocation,
+    pub args: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub polling: WaitOptions,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CloudBatchJob {
+    // the job's spec, as taken by `gcloud batch jobs submit --config`
+    pub spec: String,
+    pub name: String,
+    pub location: GcpLocation,
+    pub polling: WaitOptions,
+}
+
+pub fn access_token() -> Result<String, String> {
+    // application default credentials, as set up by `gcloud auth application-default login`
+    // or provided by the metadata server / GOOGLE_APPLICATION_CREDENTIALS
+    let output = try!(Command::new("gcloud")
+        .arg("auth")
+        .arg("application-default")
+        .arg("print-access-token")
+        .output()
+        .map_err(|e| format!("couldn't run the gcloud cli ({})", e)));
+
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !token.is_empty() {
+        Ok(token)
+    } else {
+        Err(format!("couldn't get application default credentials: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn default_polling() -> WaitOptions {
+    WaitOptions {
+        poll_interval: Duration::from_secs(30),
+        timeout: Duration::from_secs(24 * 60 * 60),
+    }
+}
+
+// splits the task's "<parameter>=<value>" arguments, handling the ones every gcp job has
+fn parse_params<F>(params: &Vec<String>,
+                   polling: &mut WaitOptions,
+                   mut other: F)
+                   -> Result<GcpLocation, String>
+    where F: FnMut(&str, &str) -> Result<bool, String>
+{
+    let mut location = GcpLocation {
+        project: String::new(),
+        region: String::new(),
+    };
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value,
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "project" => location.project = value.to_string(),
+            "region" => location.region = value.to_string(),
+            "poll-interval" => polling.poll_interval = try!(retry::parse_duration(value)),
+            "timeout" => polling.timeout = try!(retry::parse_duration(value)),
+            _ => {
+                if !try!(other(name, value)) {
+                    return Err(format!("'{}' isn't a parameter of the job", name));
+                }
+            }
+        }
+    }
+
+    if location.project.is_empty() || location.region.is_empty() {
+        return Err("the job needs a project and region (e.g. project=acme, region=europe-west1)"
+            .to_string());
+    }
+    if polling.poll_interval.as_secs() == 0 {
+        return Err("the poll interval must be at least 1s".to_string());
+    }
+
+    Ok(location)
+}
+
+// a cloud-run task's command is the job's name and its arguments its parameters, e.g.
+// [ "project=acme", "region=europe-west1", "arg=--date=2021-01-01", "env=MODE=full" ]
+pub fn parse_cloud_run_job(job: &str, params: &Vec<String>) -> Result<CloudRunJob, String> {
+    let job = job.trim();
+    if job.is_empty() {
+        return Err("there is no Cloud Run job to run".to_string());
+    }
+
+    let mut polling = default_polling();
+    let mut args = vec![];
+    let mut env = BTreeMap::new();
+    let location = try!(parse_params(params, &mut polling, |name, value| {
+        match name {
+            "arg" => args.push(value.to_string()),
+            "env" => {
+                match value.find('=') {
+                    Some(i) if i > 0 => {
+                        env.insert(value[..i].to_string(), value[i + 1..].to_string());
+                    }
+                    _ => return Err(format!("'env={}' must be given as env=<name>=<value>", value)),
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }));
+
+    Ok(CloudRunJob {
+        job: job.to_string(),
+        location: location,
+        args: args,
+        env: env,
+        polling: polling,
+    })
+}
+
+// a cloud-batch task's command is the path to the job's spec and its arguments its parameters,
+// e.g. [ "project=acme", "region=europe-west1", "name=nightly-load" ]
+pub fn parse_cloud_batch_job(spec: &str, params: &Vec<String>) -> Result<CloudBatchJob, String> {
+    let spec = spec.trim();
+    if spec.is_empty() {
+        return Err("there is no Cloud Batch job spec to submit".to_string());
+    }
+
+    let mut polling = default_polling();
+    let mut name = String::new();
+    let location = try!(parse_params(params, &mut polling, |param, value| {
+        match param {
+            "name" => name = value.to_string(),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }));
+
+    if name.is_empty() {
+        let file_name = spec.rsplit('/').next().unwrap_or(spec);
+        name = file_name.split('.').next().unwrap_or(file_name).to_string();
+    }
+
+    Ok(CloudBatchJob {
+        spec: spec.to_string(),
+        name: name,
+        location: location,
+        polling: polling,
+    })
+}
+
+// batch job ids must be unique, lowercase and start with a letter
+pub fn batch_job_id(name: &str, now: &DateTime<UTC>) -> String {
+    let name = name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .skip_while(|c| !c.is_ascii_alphabetic())
+        .take(45)
+        .collect::<String>();
+    let name = name.trim_end_matches('-');
+    format!("{}-{}",
+            if name.is_empty() { "factotum" } else { name },
+            now.format("%Y%m%d-%H%M%S"))
+}
+
+pub fn cloud_run_overrides(job: &CloudRunJob) -> Json {
+    let mut container = BTreeMap::new();
+    if !job.args.is_empty() {
+        container.insert("args".to_string(), job.args.to_json());
+    }
+    if !job.env.is_empty() {
+        let env = job.env
+            .iter()
+            .map(|(name, value)| {
+                let mut var = BTreeMap::new();
+                var.insert("name".to_string(), name.to_json());
+                var.insert("value".to_string(), value.to_json());
+                Json::Object(var)
+            })
+            .collect();
+        container.insert("env".to_string(), Json::Array(env));
+    }
+
+    let mut body = BTreeMap::new();
+    if !container.is_empty() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("containerOverrides".to_string(),
+                         Json::Array(vec![Json::Object(container)]));
+        body.insert("overrides".to_string(), Json::Object(overrides));
+    }
+    Json::Object(body)
+}
+
+// None while the execution's still to finish
+pub fn cloud_run_outcome(execution: &Json) -> Option<Result<(), String>> {
+    let completed = execution.find("conditions")
+        .and_then(|c| c.as_array())
+        .and_then(|conditions| {
+            conditions.iter()
+                .find(|c| c.find("type").and_then(|t| t.as_string()) == Some("Completed"))
+        });
+    let state = completed.and_then(|c| c.find("state")).and_then(|s| s.as_string());
+    let message = completed.and_then(|c| c.find("message")).and_then(|m| m.as_string());
+
+    match state {
+        Some("CONDITION_SUCCEEDED") => Some(Ok(())),
+        Some("CONDITION_FAILED") => {
+            Some(Err(format!("the execution failed ({})",
+                             message.unwrap_or("no reason was given"))))
+        }
+        _ => None,
+    }
+}
+
+// None while the job's still to finish
+pub fn cloud_batch_outcome(job: &Json) -> Option<Result<(), String>> {
+    match job.find_path(&["status", "state"]).and_then(|s| s.as_string()) {
+        Some("SUCCEEDED") => Some(Ok(())),
+        Some("FAILED") => {
+            let reason = job.find_path(&["status", "statusEvents"])
+                .and_then(|e| e.as_array())
+                .and_then(|events| events.last())
+                .and_then(|e| e.find("description"))
+                .and_then(|d| d.as_string())
+                .unwrap_or("no reason was given");
+            Some(Err(format!("the job FAILED ({})", reason)))
+        }
+        _ => None,
+    }
+}
+
+pub trait GcpClient {
+    fn request(&self, method: &str, url: &str, body: Option<&Json>) -> Result<Json, String>;
+}
+
+// calls Google's APIs with a fresh token each time, as runs can outlast one
+pub struct AdcClient;
+
+impl GcpClient for AdcClient {
+    fn request(&self, method: &str, url: &str, body: Option<&Json>) -> Result<Json, String> {
+        use hyper::Client;
+        use hyper::net::HttpsConnector;
+        use hyper_native_tls::NativeTlsClient;
+        use hyper::header::{Authorization, Bearer, ContentType, Headers};
+
+        let token = try!(access_token());
+        let body = match body {
+            Some(body) => try!(json::encode(body).map_err(|e| e.to_string())),
+            None => String::new(),
+        };
+
+        let ssl = try!(NativeTlsClient::new().map_err(|e| format!("{}", e)));
+        let client = Client::with_connector(HttpsConnector::new(ssl));
+        let mut headers = Headers::new();
+        headers.set(Authorization(Bearer { token: token }));
+        headers.set(ContentType::json());
+
+        let request = match method {
+            "POST" => client.post(url).body(&body[..]),
+            "DELETE" => client.delete(url),
+            _ => client.get(url),
+        };
+        let mut res = try!(request.headers(headers)
+            .send()
+            .map_err(|e| format!("{} {} failed: {}", method, url, e)));
+
+        let mut contents = String::new();
+        try!(res.read_to_string(&mut contents)
+            .map_err(|e| format!("couldn't read the response from '{}' ({})", url, e)));
+        if !res.status.is_success() {
+            return Err(format!("{} {} failed: {} {}", method, url, res.status, contents.trim()));
+        }
+        if contents.trim().is_empty() {
+            Ok(Json::Null)
+        } else {
+            Json::from_str(&contents)
+                .map_err(|e| format!("the response from '{}' isn't JSON ({})", url, e))
+        }
+    }
+}
+
+// calls check every poll interval until it gives an outcome, returning how many checks it
+// took, or None if it timed out
+fn poll<C, S>(polling: &WaitOptions, sleep: S, check: C) -> Result<Option<u64>, String>
+    where C: Fn() -> Result<Option<Result<(), String>>, String>,
+          S: Fn(Duration)
+{
+    let max_checks = polling.timeout.as_secs() / polling.poll_interval.as_secs() + 1;
+    for attempt in 1..max_checks + 1 {
+        if let Some(outcome) = try!(check()) {
+            return outcome.map(|_| Some(attempt));
+        }
+        if attempt < max_checks {
+            sleep(polling.poll_interval);
+        }
+    }
+    Ok(None)
+}
+
+// starts an execution of the job, then polls it until it finishes, returning how many checks
+// it took
+pub fn run_cloud_run_job<C, S, R>(client: &C,
+                                  job: &CloudRunJob,
+                                  sleep: S,
+                                  report: R)
+                                  -> Result<u64, String>
+    where C: GcpClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let url = format!("https://run.googleapis.com/v2/projects/{}/locations/{}/jobs/{}:run",
+                      job.location.project,
+                      job.location.region,
+                      job.job);
+    let operation = try!(client.request("POST", &url, Some(&cloud_run_overrides(job)))
+        .map_err(|e| format!("couldn't run the job '{}': {}", job.job, e)));
+    let execution = try!(operation.find_path(&["metadata", "name"])
+        .and_then(|n| n.as_string())
+        .map(|n| n.to_string())
+        .ok_or("the response has no execution name".to_string()));
+    report(format!("Started the execution {}", execution));
+
+    let execution_url = format!("https://run.googleapis.com/v2/{}", execution);
+    match try!(poll(&job.polling, sleep, || {
+        client.request("GET", &execution_url, None).map(|e| cloud_run_outcome(&e))
+    })) {
+        Some(checks) => Ok(checks),
+        None => {
+            // don't leave the execution running once nothing's watching it
+            let cancel_url = format!("{}:cancel", execution_url);
+            let no_body = Json::Object(BTreeMap::new());
+            if let Err(e) = client.request("POST", &cancel_url, Some(&no_body)) {
+                report(format!("Couldn't cancel the execution {}: {}", execution, e));
+            }
+            Err(format!("timed out after {}s waiting for the execution {} to finish",
+                        job.polling.timeout.as_secs(),
+                        execution))
+        }
+    }
+}
+
+// submits the job, then polls it until it finishes, returning how many checks it took
+pub fn run_cloud_batch_job<C, S, R>(client: &C,
+                                    job: &CloudBatchJob,
+                                    spec: &Json,
+                                    job_id: &str,
+                                    sleep: S,
+                                    report: R)
+                                    -> Result<u64, String>
+    where C: GcpClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let url = format!("https://batch.googleapis.com/v1/projects/{}/locations/{}/jobs?job_id={}",
+                      job.location.project,
+                      job.location.region,
+                      job_id);
+    let submitted = try!(client.request("POST", &url, Some(spec))
+        .map_err(|e| format!("couldn't submit the job '{}': {}", job.spec, e)));
+    let name = try!(submitted.find("name")
+        .and_then(|n| n.as_string())
+        .map(|n| n.to_string())
+        .ok_or("the response has no job name".to_string()));
+    report(format!("Submitted '{}' as {}", job.spec, name));
+
+    let job_url = format!("https://batch.googleapis.com/v1/{}", name);
+    match try!(poll(&job.polling, sleep, || {
+        client.request("GET", &job_url, None).map(|j| cloud_batch_outcome(&j))
+    })) {
+        Some(checks) => Ok(checks),
+        None => {
+            if let Err(e) = client.request("DELETE", &job_url, None) {
+                report(format!("Couldn't delete the job {}: {}", name, e));
+            }
+            Err(format!("timed out after {}s waiting for the job {} to finish",
+                        job.polling.timeout.as_secs(),
+                        name))
+        }
+    }
+}
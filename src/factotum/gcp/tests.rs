@@ -0,0 +1,211 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use chrono::TimeZone;
+use std::cell::RefCell;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+fn polling() -> Vec<&'static str> {
+    vec!["project=acme", "region=europe-west1", "poll-interval=1s", "timeout=3s"]
+}
+
+// answers each request with the next canned response
+struct FakeGcp {
+    responses: RefCell<Vec<Json>>,
+    requests: RefCell<Vec<String>>,
+}
+
+impl FakeGcp {
+    fn new(responses: Vec<&str>) -> FakeGcp {
+        FakeGcp {
+            responses: RefCell::new(responses.iter()
+                .rev()
+                .map(|r| Json::from_str(r).unwrap())
+                .collect()),
+            requests: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl GcpClient for FakeGcp {
+    fn request(&self, method: &str, url: &str, body: Option<&Json>) -> Result<Json, String> {
+        self.requests.borrow_mut().push(match body {
+            Some(body) => format!("{} {} {}", method, url, body),
+            None => format!("{} {}", method, url),
+        });
+        self.responses.borrow_mut().pop().ok_or("no more responses".to_string())
+    }
+}
+
+#[test]
+fn parse_cloud_run_job_good_and_bad() {
+    let mut ps = polling();
+    ps.extend(vec!["arg=--date=2021-01-01", "env=MODE=full"]);
+    let job = parse_cloud_run_job("nightly-load", &params(ps)).unwrap();
+    assert_eq!(job.location,
+               GcpLocation {
+                   project: "acme".to_string(),
+                   region: "europe-west1".to_string(),
+               });
+    assert_eq!(job.args, vec!["--date=2021-01-01"]);
+    assert_eq!(job.env.get("MODE"), Some(&"full".to_string()));
+    assert_eq!(job.polling.poll_interval, Duration::from_secs(1));
+
+    assert_eq!(parse_cloud_run_job("", &params(polling())),
+               Err("there is no Cloud Run job to run".to_string()));
+    assert_eq!(parse_cloud_run_job("load", &params(vec!["project=acme"])),
+               Err("the job needs a project and region (e.g. project=acme, \
+                    region=europe-west1)"
+                   .to_string()));
+    assert_eq!(parse_cloud_run_job("load", &params(vec!["project=acme", "env=MODE"])),
+               Err("'env=MODE' must be given as env=<name>=<value>".to_string()));
+    assert_eq!(parse_cloud_run_job("load", &params(vec!["project=acme", "name=x"])),
+               Err("'name' isn't a parameter of the job".to_string()));
+}
+
+#[test]
+fn parse_cloud_batch_job_good_and_bad() {
+    let job = parse_cloud_batch_job("jobs/nightly.json", &params(polling())).unwrap();
+    assert_eq!(job.name, "nightly");
+    assert_eq!(job.spec, "jobs/nightly.json");
+
+    let mut ps = polling();
+    ps.push("name=load");
+    assert_eq!(parse_cloud_batch_job("jobs/nightly.json", &params(ps)).unwrap().name,
+               "load");
+    assert_eq!(parse_cloud_batch_job(" ", &params(polling())),
+               Err("there is no Cloud Batch job spec to submit".to_string()));
+    assert_eq!(parse_cloud_batch_job("nightly.json", &params(vec!["region=europe-west1"])),
+               Err("the job needs a project and region (e.g. project=acme, \
+                    region=europe-west1)"
+                   .to_string()));
+    assert!(parse_cloud_batch_job("nightly.json", &params(vec!["arg=x"])).is_err());
+}
+
+#[test]
+fn batch_job_ids_are_valid() {
+    let now = UTC.ymd(2021, 1, 2).and_hms(3, 4, 5);
+    assert_eq!(batch_job_id("Nightly_Load", &now), "nightly-load-20210102-030405");
+    assert_eq!(batch_job_id("2021 load", &now), "load-20210102-030405");
+    assert_eq!(batch_job_id("__", &now), "factotum-20210102-030405");
+}
+
+#[test]
+fn cloud_run_overrides_are_only_sent_when_given() {
+    let job = parse_cloud_run_job("load", &params(polling())).unwrap();
+    assert_eq!(cloud_run_overrides(&job).to_string(), "{}");
+
+    let mut ps = polling();
+    ps.extend(vec!["arg=--full", "env=MODE=full"]);
+    let job = parse_cloud_run_job("load", &params(ps)).unwrap();
+    assert_eq!(cloud_run_overrides(&job).to_string(),
+               "{\"overrides\":{\"containerOverrides\":[{\"args\":[\"--full\"],\
+                \"env\":[{\"name\":\"MODE\",\"value\":\"full\"}]}]}}");
+}
+
+#[test]
+fn outcomes_follow_the_state() {
+    let execution = |state: &str| {
+        Json::from_str(&format!("{{\"conditions\": [{{\"type\": \"Ready\", \"state\": \
+                                 \"CONDITION_SUCCEEDED\"}}, {{\"type\": \"Completed\", \
+                                 \"state\": \"{}\", \"message\": \"Task failed\"}}]}}",
+                                state))
+            .unwrap()
+    };
+    assert_eq!(cloud_run_outcome(&execution("CONDITION_PENDING")), None);
+    assert_eq!(cloud_run_outcome(&execution("CONDITION_SUCCEEDED")), Some(Ok(())));
+    assert_eq!(cloud_run_outcome(&execution("CONDITION_FAILED")),
+               Some(Err("the execution failed (Task failed)".to_string())));
+    assert_eq!(cloud_run_outcome(&Json::from_str("{}").unwrap()), None);
+
+    let job = |state: &str| {
+        Json::from_str(&format!("{{\"status\": {{\"state\": \"{}\", \"statusEvents\": \
+                                 [{{\"description\": \"Job state is set from RUNNING to \
+                                 FAILED\"}}]}}}}",
+                                state))
+            .unwrap()
+    };
+    assert_eq!(cloud_batch_outcome(&job("QUEUED")), None);
+    assert_eq!(cloud_batch_outcome(&job("SUCCEEDED")), Some(Ok(())));
+    assert_eq!(cloud_batch_outcome(&job("FAILED")),
+               Some(Err("the job FAILED (Job state is set from RUNNING to FAILED)".to_string())));
+}
+
+#[test]
+fn run_cloud_run_job_polls_the_execution() {
+    let gcp = FakeGcp::new(vec!["{\"metadata\": {\"name\": \"projects/acme/locations/eu/jobs/\
+                                 load/executions/load-x\"}}",
+                                "{\"conditions\": []}",
+                                "{\"conditions\": [{\"type\": \"Completed\", \"state\": \
+                                 \"CONDITION_SUCCEEDED\"}]}"]);
+    let job = parse_cloud_run_job("load", &params(polling())).unwrap();
+    let reports = RefCell::new(vec![]);
+
+    assert_eq!(run_cloud_run_job(&gcp, &job, |_| {}, |r| reports.borrow_mut().push(r)),
+               Ok(2));
+    assert_eq!(*reports.borrow(),
+               vec!["Started the execution projects/acme/locations/eu/jobs/load/executions/\
+                     load-x"]);
+    assert_eq!(gcp.requests.borrow()[0],
+               "POST https://run.googleapis.com/v2/projects/acme/locations/europe-west1/jobs/\
+                load:run {}");
+    assert_eq!(gcp.requests.borrow()[2],
+               "GET https://run.googleapis.com/v2/projects/acme/locations/eu/jobs/load/\
+                executions/load-x");
+}
+
+#[test]
+fn run_cloud_run_job_cancels_on_timeout() {
+    let mut responses = vec!["{\"metadata\": {\"name\": \"executions/load-x\"}}"];
+    responses.extend(vec!["{}"; 5]);
+    let gcp = FakeGcp::new(responses);
+    let job = parse_cloud_run_job("load", &params(polling())).unwrap();
+
+    assert_eq!(run_cloud_run_job(&gcp, &job, |_| {}, |_| {}),
+               Err("timed out after 3s waiting for the execution executions/load-x to finish"
+                   .to_string()));
+    assert_eq!(gcp.requests.borrow()[5],
+               "POST https://run.googleapis.com/v2/executions/load-x:cancel {}");
+}
+
+#[test]
+fn run_cloud_batch_job_polls_the_job() {
+    let gcp = FakeGcp::new(vec!["{\"name\": \"projects/acme/locations/eu/jobs/nightly-1\"}",
+                                "{\"status\": {\"state\": \"RUNNING\"}}",
+                                "{\"status\": {\"state\": \"FAILED\"}}"]);
+    let job = parse_cloud_batch_job("nightly.json", &params(polling())).unwrap();
+    let spec = Json::from_str("{\"taskGroups\": []}").unwrap();
+
+    assert_eq!(run_cloud_batch_job(&gcp, &job, &spec, "nightly-1", |_| {}, |_| {}),
+               Err("the job FAILED (no reason was given)".to_string()));
+    assert_eq!(gcp.requests.borrow()[0],
+               "POST https://batch.googleapis.com/v1/projects/acme/locations/europe-west1/jobs?\
+                job_id=nightly-1 {\"taskGroups\":[]}");
+}
+
+#[test]
+fn run_cloud_batch_job_deletes_on_timeout() {
+    let mut responses = vec!["{\"name\": \"jobs/nightly-1\"}"];
+    responses.extend(vec!["{\"status\": {\"state\": \"QUEUED\"}}"; 5]);
+    let gcp = FakeGcp::new(responses);
+    let job = parse_cloud_batch_job("nightly.json", &params(polling())).unwrap();
+
+    assert!(run_cloud_batch_job(&gcp, &job, &Json::Null, "nightly-1", |_| {}, |_| {}).is_err());
+    assert_eq!(gcp.requests.borrow()[5],
+               "DELETE https://batch.googleapis.com/v1/jobs/nightly-1");
+}
@@ -0,0 +1,97 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Task;
+use std::collections::{HashMap, HashSet};
+
+pub const EXECUTOR: &'static str = "generator";
+
+// orders a generator's tasks into groups that can run together; tasks that don't depend
+// on another generated task depend on the generator itself
+pub fn plan_generated(generator: &str,
+                      tasks: Vec<Task>,
+                      existing_names: &Vec<String>)
+                      -> Result<Vec<Vec<Task>>, String> {
+    if tasks.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut names = HashSet::new();
+    for task in tasks.iter() {
+        if task.name.trim().is_empty() {
+            return Err("a generated task has no name".to_string());
+        }
+        if existing_names.contains(&task.name) || !names.insert(task.name.clone()) {
+            return Err(format!("the task '{}' has been added already - task names must be \
+                                unique",
+                               task.name));
+        }
+    }
+
+    for task in tasks.iter() {
+        for dep in task.depends_on.iter() {
+            if dep != generator && !names.contains(dep) {
+                return Err(format!("the task '{}' depends on '{}', which isn't '{}' or one of \
+                                    the tasks it generated",
+                                   task.name,
+                                   dep,
+                                   generator));
+            }
+        }
+    }
+
+    let mut levels: HashMap<String, usize> = HashMap::new();
+    while levels.len() < tasks.len() {
+        let before = levels.len();
+        for task in tasks.iter() {
+            if levels.contains_key(&task.name) {
+                continue;
+            }
+            let generated_deps = task.depends_on
+                .iter()
+                .filter(|d| names.contains(*d))
+                .collect::<Vec<&String>>();
+            if generated_deps.iter().all(|d| levels.contains_key(*d)) {
+                let level = generated_deps.iter().map(|d| levels[*d] + 1).max().unwrap_or(0);
+                levels.insert(task.name.clone(), level);
+            }
+        }
+        if levels.len() == before {
+            return Err("the generated tasks have a circular dependency".to_string());
+        }
+    }
+
+    let depth = levels.values().max().cloned().unwrap_or(0) + 1;
+    let mut groups: Vec<Vec<Task>> = (0..depth).map(|_| vec![]).collect();
+    for mut task in tasks.into_iter() {
+        if task.depends_on.is_empty() {
+            task.depends_on = vec![generator.to_string()];
+        }
+        groups[levels[&task.name]].push(task);
+    }
+
+    Ok(groups)
+}
+
+// the generated tasks nothing else generated depends on
+pub fn leaf_names(groups: &Vec<Vec<Task>>) -> Vec<String> {
+    let all = groups.iter().flat_map(|g| g.iter()).collect::<Vec<&Task>>();
+    all.iter()
+        .filter(|t| !all.iter().any(|other| other.depends_on.contains(&t.name)))
+        .map(|t| t.name.clone())
+        .collect()
+}
@@ -0,0 +1,68 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::tests::make_task;
+
+fn names(groups: &Vec<Vec<Task>>) -> Vec<Vec<String>> {
+    groups.iter().map(|g| g.iter().map(|t| t.name.clone()).collect()).collect()
+}
+
+#[test]
+fn plan_generated_groups_by_level() {
+    let tasks = vec![make_task("check", &vec!["load a", "load b"]),
+                     make_task("load a", &vec![]),
+                     make_task("load b", &vec!["discover"])];
+
+    let groups = plan_generated("discover", tasks, &vec!["discover".to_string()]).unwrap();
+
+    assert_eq!(names(&groups),
+               vec![vec!["load a".to_string(), "load b".to_string()],
+                    vec!["check".to_string()]]);
+    assert_eq!(groups[0][0].depends_on, vec!["discover"]);
+    assert_eq!(groups[0][1].depends_on, vec!["discover"]);
+    assert_eq!(leaf_names(&groups), vec!["check"]);
+}
+
+#[test]
+fn plan_generated_nothing() {
+    assert_eq!(plan_generated("discover", vec![], &vec![]).unwrap().len(), 0);
+}
+
+#[test]
+fn plan_generated_bad() {
+    let existing = vec!["discover".to_string(), "report".to_string()];
+
+    assert_eq!(plan_generated("discover", vec![make_task(" ", &vec![])], &existing).err(),
+               Some("a generated task has no name".to_string()));
+    assert_eq!(plan_generated("discover", vec![make_task("report", &vec![])], &existing).err(),
+               Some("the task 'report' has been added already - task names must be unique"
+                   .to_string()));
+    assert_eq!(plan_generated("discover",
+                              vec![make_task("a", &vec![]), make_task("a", &vec![])],
+                              &existing)
+                   .err(),
+               Some("the task 'a' has been added already - task names must be unique"
+                   .to_string()));
+    assert_eq!(plan_generated("discover", vec![make_task("a", &vec!["report"])], &existing)
+                   .err(),
+               Some("the task 'a' depends on 'report', which isn't 'discover' or one of the \
+                     tasks it generated"
+                   .to_string()));
+    assert_eq!(plan_generated("discover",
+                              vec![make_task("a", &vec!["b"]), make_task("b", &vec!["a"])],
+                              &existing)
+                   .err(),
+               Some("the generated tasks have a circular dependency".to_string()));
+}
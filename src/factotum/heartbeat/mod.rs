@@ -0,0 +1,132 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::{ExecutionState, ExecutionUpdate};
+use factotum::executor::execution_strategy::TaskOutput;
+use factotum::executor::task_list::State;
+use factotum::webhook::jobcontext::JobContext;
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+pub const HEARTBEAT_EVENT: &'static str = "TASK_HEARTBEAT";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    pub task_name: String,
+    pub elapsed: Duration,
+    pub last_output: Option<String>,
+}
+
+// a task is due its nth heartbeat once it's been running for n intervals
+pub fn due_heartbeats(elapsed: &HashMap<String, Duration>,
+                      beats_sent: &HashMap<String, u32>,
+                      interval: Duration,
+                      last_output: &HashMap<String, String>)
+                      -> Vec<Heartbeat> {
+    let mut beats = elapsed.iter()
+        .filter(|&(name, running_for)| {
+            let sent = *beats_sent.get(name).unwrap_or(&0);
+            *running_for >= interval * (sent + 1)
+        })
+        .map(|(name, running_for)| {
+            Heartbeat {
+                task_name: name.clone(),
+                elapsed: *running_for,
+                last_output: last_output.get(name).cloned(),
+            }
+        })
+        .collect::<Vec<Heartbeat>>();
+    beats.sort_by(|a, b| a.task_name.cmp(&b.task_name));
+    beats
+}
+
+pub fn heartbeat_json(context: &JobContext, beat: &Heartbeat) -> Json {
+    let mut d = BTreeMap::new();
+    d.insert("event".to_string(), HEARTBEAT_EVENT.to_json());
+    d.insert("jobName".to_string(), context.job_name.to_json());
+    d.insert("jobReference".to_string(), context.job_reference.to_json());
+    d.insert("runReference".to_string(), context.run_reference.to_json());
+    d.insert("taskName".to_string(), beat.task_name.to_json());
+    d.insert("elapsed".to_string(),
+             ChronoDuration::from_std(beat.elapsed).unwrap().to_string().to_json());
+    d.insert("lastOutputLine".to_string(), beat.last_output.to_json());
+    d.insert("context".to_string(), context.context_json());
+    Json::Object(d)
+}
+
+// the last line of output is only known for tasks whose output is streamed
+pub fn connect_heartbeat<F>(interval: Duration,
+                            updates_channel: Receiver<ExecutionUpdate>,
+                            output_channel: Option<Receiver<TaskOutput>>,
+                            poll_interval: Duration,
+                            on_heartbeat: F)
+                            -> JoinHandle<u32>
+    where F: Fn(&Heartbeat) + Send + 'static
+{
+    thread::spawn(move || {
+        let mut started: HashMap<String, Instant> = HashMap::new();
+        let mut beats_sent: HashMap<String, u32> = HashMap::new();
+        let mut last_output: HashMap<String, String> = HashMap::new();
+        let mut total = 0;
+
+        loop {
+            let finished = match updates_channel.recv_timeout(poll_interval) {
+                Ok(update) => {
+                    for task in update.task_snapshot.iter() {
+                        if task.state == State::Running {
+                            started.entry(task.name.clone()).or_insert(Instant::now());
+                        } else if started.remove(&task.name).is_some() {
+                            beats_sent.remove(&task.name);
+                            last_output.remove(&task.name);
+                        }
+                    }
+                    update.execution_state == ExecutionState::Finished
+                }
+                Err(RecvTimeoutError::Timeout) => false,
+                Err(RecvTimeoutError::Disconnected) => true,
+            };
+
+            if finished {
+                break;
+            }
+
+            if let Some(ref output) = output_channel {
+                for line in output.try_iter() {
+                    if started.contains_key(&line.task_name) {
+                        last_output.insert(line.task_name, line.line);
+                    }
+                }
+            }
+
+            let elapsed = started.iter()
+                .map(|(name, start)| (name.clone(), start.elapsed()))
+                .collect::<HashMap<String, Duration>>();
+            for beat in due_heartbeats(&elapsed, &beats_sent, interval, &last_output) {
+                *beats_sent.entry(beat.task_name.clone()).or_insert(0) += 1;
+                on_heartbeat(&beat);
+                total += 1;
+            }
+        }
+
+        total
+    })
+}
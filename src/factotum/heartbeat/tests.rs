@@ -0,0 +1,113 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+use super::*;
+use factotum::executor::{ExecutionState, ExecutionUpdate, Transition, JobTransition};
+use factotum::executor::execution_strategy::{OutputStream, TaskOutput};
+use factotum::executor::task_list::{Task, State};
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+fn update(state: ExecutionState, running: Vec<&str>) -> ExecutionUpdate {
+    let snapshot = running.iter()
+        .map(|name| {
+            let mut task = Task::new(*name, make_task(name, &vec![]));
+            task.state = State::Running;
+            task
+        })
+        .collect();
+    ExecutionUpdate::new(state,
+                         snapshot,
+                         Transition::Job(JobTransition::new(None, ExecutionState::Running)))
+}
+
+#[test]
+fn due_heartbeats_good() {
+    let mut elapsed = HashMap::new();
+    elapsed.insert("a".to_string(), Duration::from_secs(65));
+    elapsed.insert("b".to_string(), Duration::from_secs(59));
+    elapsed.insert("c".to_string(), Duration::from_secs(125));
+
+    let mut sent = HashMap::new();
+    sent.insert("c".to_string(), 2);
+
+    let mut last_output = HashMap::new();
+    last_output.insert("a".to_string(), "loaded 500 rows".to_string());
+
+    assert_eq!(due_heartbeats(&elapsed, &sent, Duration::from_secs(60), &last_output),
+               vec![Heartbeat {
+                        task_name: "a".to_string(),
+                        elapsed: Duration::from_secs(65),
+                        last_output: Some("loaded 500 rows".to_string()),
+                    }]);
+
+    sent.insert("c".to_string(), 1);
+    assert_eq!(due_heartbeats(&elapsed, &sent, Duration::from_secs(60), &last_output)
+                   .iter()
+                   .map(|b| b.task_name.as_str())
+                   .collect::<Vec<&str>>(),
+               vec!["a", "c"]);
+}
+
+#[test]
+fn heartbeat_json_good() {
+    let context = JobContext::new("job", "{}", None);
+    let beat = Heartbeat {
+        task_name: "a".to_string(),
+        elapsed: Duration::from_secs(300),
+        last_output: None,
+    };
+
+    let json = heartbeat_json(&context, &beat);
+    assert_eq!(json.find("event").unwrap().as_string(), Some("TASK_HEARTBEAT"));
+    assert_eq!(json.find("runReference").unwrap().as_string(),
+               Some(context.run_reference.as_ref()));
+    assert_eq!(json.find("taskName").unwrap().as_string(), Some("a"));
+    assert_eq!(json.find("elapsed").unwrap().as_string(), Some("PT300S"));
+    assert_eq!(json.find("lastOutputLine"), Some(&Json::Null));
+}
+
+#[test]
+fn heartbeat_repeats_with_the_last_output_line() {
+    let (tx, rx) = mpsc::channel();
+    let (output_tx, output_rx) = mpsc::channel();
+    let (beat_tx, beat_rx) = mpsc::channel();
+    let handle = connect_heartbeat(Duration::from_millis(40),
+                                   rx,
+                                   Some(output_rx),
+                                   Duration::from_millis(5),
+                                   move |beat| beat_tx.send(beat.clone()).unwrap());
+
+    tx.send(update(ExecutionState::Running, vec!["slow"])).unwrap();
+    output_tx.send(TaskOutput {
+            task_name: "slow".to_string(),
+            stream: OutputStream::Stdout,
+            line: "step 1 of 3".to_string(),
+        })
+        .unwrap();
+    thread::sleep(Duration::from_millis(100));
+    tx.send(update(ExecutionState::Finished, vec![])).unwrap();
+
+    let beats = beat_rx.iter().collect::<Vec<Heartbeat>>();
+    assert_eq!(handle.join().unwrap() as usize, beats.len());
+    assert!(beats.len() >= 2);
+    assert!(beats.iter().all(|b| b.task_name == "slow"));
+    assert_eq!(beats[0].last_output, Some("step 1 of 3".to_string()));
+    assert!(beats[1].elapsed >= Duration::from_millis(80));
+}
@@ -0,0 +1,673 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::task_list::Task;
+use factotum::factfile::Task as FactfileTask;
+use factotum::webhook::jobcontext::JobContext;
+use factotum::report;
+use factotum::retry;
+use chrono::{DateTime, UTC};
+use hyper::Url;
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str;
+use std::time::Duration;
+
+pub const HISTORY_FILE: &'static str = ".factotum/history.jsonl";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunRecord {
+    pub job_name: String,
+    pub run_reference: String,
+    pub start_time: String,
+    pub run_state: String,
+    pub tasks: Vec<TaskRecord>,
+    pub source: Option<RunSource>,
+}
+
+// what's needed to run the job again as it was run
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSource {
+    pub factfile: String,
+    pub variables: Json,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRecord {
+    pub task_name: String,
+    pub state: String,
+    pub error_message: Option<String>,
+    pub depends_on: Vec<String>,
+    pub started: Option<String>,
+    pub duration_millis: Option<u64>,
+    pub cache_key: Option<String>,
+}
+
+impl RunRecord {
+    pub fn task(&self, name: &str) -> Option<&TaskRecord> {
+        self.tasks.iter().find(|t| t.task_name == name)
+    }
+}
+
+// where runs are recorded: a file on this host, or a Postgres database that many hosts share
+pub trait RunStore {
+    fn append(&self, record: &Json) -> Result<(), String>;
+    // runs are returned oldest first, in the order they were recorded
+    fn load_all_runs(&self) -> Result<Vec<RunRecord>, String>;
+    // how the store is named in errors
+    fn describe(&self) -> String;
+    // returns how many runs were removed
+    fn prune(&self, retention: &HistoryRetention, now: &DateTime<UTC>) -> Result<usize, String>;
+
+    fn load_runs(&self, job_name: &str) -> Result<Vec<RunRecord>, String> {
+        let runs = try!(self.load_all_runs());
+        Ok(runs.into_iter().filter(|r| r.job_name == job_name).collect())
+    }
+
+    fn find_run(&self, run_reference: &str) -> Result<RunRecord, String> {
+        let runs = try!(self.load_all_runs());
+        runs.into_iter()
+            .find(|r| r.run_reference == run_reference)
+            .ok_or(format!("no run '{}' was found in {}", run_reference, self.describe()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HistoryStore {
+    File(PathBuf),
+    Postgres(String),
+}
+
+impl HistoryStore {
+    pub fn open(&self) -> Box<dyn RunStore + Send + Sync> {
+        match *self {
+            HistoryStore::File(ref path) => Box::new(FileStore { path: path.clone() }),
+            HistoryStore::Postgres(ref url) => Box::new(PostgresStore { url: url.clone() }),
+        }
+    }
+}
+
+impl Default for HistoryStore {
+    fn default() -> Self {
+        HistoryStore::File(PathBuf::from(HISTORY_FILE))
+    }
+}
+
+pub fn parse_history_store(location: &str) -> HistoryStore {
+    if location.starts_with("postgres://") || location.starts_with("postgresql://") {
+        HistoryStore::Postgres(location.to_string())
+    } else {
+        HistoryStore::File(PathBuf::from(location))
+    }
+}
+
+// runs older than keep_for, or past the newest max_runs, are pruned
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HistoryRetention {
+    pub keep_for: Option<Duration>,
+    pub max_runs: Option<usize>,
+}
+
+impl HistoryRetention {
+    pub fn is_unlimited(&self) -> bool {
+        self.keep_for.is_none() && self.max_runs.is_none()
+    }
+
+    // newest counts back from the most recent run, which is 0; runs whose start time can't
+    // be read are only pruned by max_runs
+    pub fn keeps(&self, newest: usize, start_time: &str, now: &DateTime<UTC>) -> bool {
+        let within_max = self.max_runs.map(|max| newest < max).unwrap_or(true);
+        let within_age = match (self.keep_for, DateTime::parse_from_rfc3339(start_time)) {
+            (Some(keep_for), Ok(started)) => {
+                *now - started.with_timezone(&UTC) <= ChronoDuration::from_std(keep_for).unwrap()
+            }
+            _ => true,
+        };
+        within_max && within_age
+    }
+}
+
+pub fn parse_history_retention(keep: Option<&str>,
+                               max_runs: Option<usize>)
+                               -> Result<HistoryRetention, String> {
+    let keep_for = match keep {
+        Some(keep) => {
+            Some(try!(retry::parse_duration(keep).map_err(|_| {
+                format!("'{}' isn't a history retention (expected a duration such as 90d)",
+                        keep)
+            })))
+        }
+        None => None,
+    };
+    if max_runs == Some(0) {
+        return Err("the history must keep at least 1 run".to_string());
+    }
+    Ok(HistoryRetention {
+        keep_for: keep_for,
+        max_runs: max_runs,
+    })
+}
+
+pub fn record_run(store: &dyn RunStore,
+                  context: &JobContext,
+                  tasks: &Vec<&Task<&FactfileTask>>,
+                  source: Option<&RunSource>)
+                  -> Result<(), String> {
+    let mut record = report::run_result_json(context, tasks);
+    if let (Some(source), &mut Json::Object(ref mut d)) = (source, &mut record) {
+        d.insert("factfile".to_string(), source.factfile.to_json());
+        d.insert("variables".to_string(), source.variables.clone());
+    }
+    store.append(&record)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+pub fn parse_export_format(format: &str) -> Result<ExportFormat, String> {
+    match format {
+        "csv" => Ok(ExportFormat::Csv),
+        "json" => Ok(ExportFormat::Json),
+        _ => Err(format!("'{}' isn't an export format (expected csv or json)", format)),
+    }
+}
+
+pub const CSV_HEADER: &'static str = "job_name,run_reference,run_start_time,run_state,task_name,\
+                                      task_state,task_started,task_duration_millis,\
+                                      task_error_message";
+
+// runs whose start time can't be read are left out, as there's no telling how old they are
+pub fn runs_since(runs: Vec<RunRecord>, since: &Duration, now: &DateTime<UTC>) -> Vec<RunRecord> {
+    let cutoff = *now - ChronoDuration::from_std(*since).unwrap();
+    runs.into_iter()
+        .filter(|run| {
+            DateTime::parse_from_rfc3339(&run.start_time)
+                .map(|started| started.with_timezone(&UTC) >= cutoff)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') ||
+       field.contains('\r') {
+        format!("\"{}\"", field.replace("\"", "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// csv has a row per task (or one with the task columns empty for a run without tasks), json a
+// run per line
+pub fn export_runs(runs: &[RunRecord], format: &ExportFormat) -> String {
+    let mut out = String::new();
+    match *format {
+        ExportFormat::Csv => {
+            out.push_str(CSV_HEADER);
+            out.push('\n');
+            for run in runs {
+                let run_fields = vec![run.job_name.as_str(),
+                                      run.run_reference.as_str(),
+                                      run.start_time.as_str(),
+                                      run.run_state.as_str()];
+                let no_tasks = vec![None];
+                let tasks = if run.tasks.is_empty() {
+                    no_tasks
+                } else {
+                    run.tasks.iter().map(Some).collect()
+                };
+                for task in tasks {
+                    let task_fields = match task {
+                        Some(task) => {
+                            vec![task.task_name.clone(),
+                                 task.state.clone(),
+                                 task.started.clone().unwrap_or("".to_string()),
+                                 task.duration_millis
+                                     .map(|d| d.to_string())
+                                     .unwrap_or("".to_string()),
+                                 task.error_message.clone().unwrap_or("".to_string())]
+                        }
+                        None => vec!["".to_string(); 5],
+                    };
+                    let row = run_fields.iter()
+                        .map(|f| csv_field(f))
+                        .chain(task_fields.iter().map(|f| csv_field(f)))
+                        .collect::<Vec<String>>();
+                    out.push_str(&row.join(","));
+                    out.push('\n');
+                }
+            }
+        }
+        ExportFormat::Json => {
+            for run in runs {
+                out.push_str(&run.to_json().to_string());
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+impl ToJson for TaskRecord {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("taskName".to_string(), self.task_name.to_json());
+        d.insert("state".to_string(), self.state.to_json());
+        d.insert("errorMessage".to_string(), self.error_message.to_json());
+        d.insert("dependsOn".to_string(), self.depends_on.to_json());
+        d.insert("started".to_string(), self.started.to_json());
+        d.insert("durationMillis".to_string(), self.duration_millis.to_json());
+        Json::Object(d)
+    }
+}
+
+// the source is left out, as its variables can hold secrets
+impl ToJson for RunRecord {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("jobName".to_string(), self.job_name.to_json());
+        d.insert("runReference".to_string(), self.run_reference.to_json());
+        d.insert("startTime".to_string(), self.start_time.to_json());
+        d.insert("runState".to_string(), self.run_state.to_json());
+        d.insert("tasks".to_string(), self.tasks.to_json());
+        Json::Object(d)
+    }
+}
+
+fn get_str(json: &Json, key: &str) -> Option<String> {
+    json.find(key).and_then(|v| v.as_string()).map(|s| s.to_string())
+}
+
+fn parse_run_record(line: &str) -> Result<RunRecord, String> {
+    let json = try!(Json::from_str(line).map_err(|e| e.to_string()));
+
+    let tasks = match json.find("tasks").and_then(|t| t.as_array()) {
+        Some(tasks) => {
+            tasks.iter()
+                .map(|t| {
+                    TaskRecord {
+                        task_name: get_str(t, "taskName").unwrap_or("".to_string()),
+                        state: get_str(t, "state").unwrap_or("".to_string()),
+                        error_message: get_str(t, "errorMessage"),
+                        depends_on: t.find("dependsOn")
+                            .and_then(|d| d.as_array())
+                            .map(|d| {
+                                d.iter()
+                                    .filter_map(|n| n.as_string())
+                                    .map(|n| n.to_string())
+                                    .collect()
+                            })
+                            .unwrap_or(vec![]),
+                        started: get_str(t, "started"),
+                        duration_millis: t.find("durationMillis").and_then(|d| d.as_u64()),
+                        cache_key: get_str(t, "cacheKey"),
+                    }
+                })
+                .collect()
+        }
+        None => vec![],
+    };
+
+    match (get_str(&json, "jobName"), get_str(&json, "runReference")) {
+        (Some(job_name), Some(run_reference)) => {
+            Ok(RunRecord {
+                job_name: job_name,
+                run_reference: run_reference,
+                start_time: get_str(&json, "startTime").unwrap_or("".to_string()),
+                run_state: get_str(&json, "runState").unwrap_or("".to_string()),
+                tasks: tasks,
+                source: get_str(&json, "factfile").map(|factfile| {
+                    RunSource {
+                        factfile: factfile,
+                        variables: json.find("variables")
+                            .cloned()
+                            .unwrap_or(Json::Object(BTreeMap::new())),
+                    }
+                }),
+            })
+        }
+        _ => Err("the record is missing a jobName or runReference".to_string()),
+    }
+}
+
+// records that can't be read are skipped, so one bad record doesn't lose the rest
+fn parse_run_records<I>(lines: I, store: &str) -> Result<Vec<RunRecord>, String>
+    where I: Iterator<Item = Result<String, String>>
+{
+    let mut runs = vec![];
+    for (idx, line) in lines.enumerate() {
+        let line = try!(line);
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_run_record(&line) {
+            Ok(run) => runs.push(run),
+            Err(msg) => warn!("Ignoring record {} of {}: {}", idx + 1, store, msg),
+        }
+    }
+    Ok(runs)
+}
+
+// runs are appended as a line of JSON each
+pub struct FileStore {
+    pub path: PathBuf,
+}
+
+impl RunStore for FileStore {
+    fn append(&self, record: &Json) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            try!(fs::create_dir_all(parent)
+                .map_err(|e| format!("couldn't create '{}' ({})", parent.display(), e)));
+        }
+
+        let mut f = try!(OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(|e| format!("couldn't open '{}' ({})", self.path.display(), e)));
+
+        writeln!(f, "{}", record)
+            .map_err(|e| format!("couldn't write to '{}' ({})", self.path.display(), e))
+    }
+
+    fn load_all_runs(&self) -> Result<Vec<RunRecord>, String> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+
+        let f = try!(File::open(&self.path)
+            .map_err(|e| format!("couldn't open '{}' ({})", self.path.display(), e)));
+        let path = self.path.display().to_string();
+        let lines = BufReader::new(f)
+            .lines()
+            .map(|line| line.map_err(|e| format!("couldn't read '{}' ({})", path, e)));
+        parse_run_records(lines, &self.describe())
+    }
+
+    fn describe(&self) -> String {
+        format!("'{}'", self.path.display())
+    }
+
+    // the kept lines are written to a new file that replaces the old one, so a prune that
+    // fails part way loses nothing
+    fn prune(&self, retention: &HistoryRetention, now: &DateTime<UTC>) -> Result<usize, String> {
+        if !self.path.exists() || retention.is_unlimited() {
+            return Ok(0);
+        }
+
+        let f = try!(File::open(&self.path)
+            .map_err(|e| format!("couldn't open '{}' ({})", self.path.display(), e)));
+        let mut lines = vec![];
+        for line in BufReader::new(f).lines() {
+            let line = try!(line.map_err(|e| {
+                format!("couldn't read '{}' ({})", self.path.display(), e)
+            }));
+            if !line.trim().is_empty() {
+                lines.push(line);
+            }
+        }
+
+        // lines that aren't runs are left for someone to look at
+        let start_times = lines.iter()
+            .map(|line| parse_run_record(line).ok().map(|run| run.start_time))
+            .collect::<Vec<Option<String>>>();
+        let run_count = start_times.iter().filter(|t| t.is_some()).count();
+        let mut seen = 0;
+        let mut kept = vec![];
+        for (line, start_time) in lines.iter().zip(start_times.iter()) {
+            match *start_time {
+                Some(ref start_time) => {
+                    seen += 1;
+                    if retention.keeps(run_count - seen, start_time, now) {
+                        kept.push(line.as_str());
+                    }
+                }
+                None => kept.push(line.as_str()),
+            }
+        }
+
+        let pruned = lines.len() - kept.len();
+        if pruned == 0 {
+            return Ok(0);
+        }
+
+        let tmp_path = self.path.with_extension("jsonl.pruning");
+        {
+            let mut tmp = try!(File::create(&tmp_path)
+                .map_err(|e| format!("couldn't create '{}' ({})", tmp_path.display(), e)));
+            for line in kept {
+                try!(writeln!(tmp, "{}", line)
+                    .map_err(|e| format!("couldn't write to '{}' ({})", tmp_path.display(), e)));
+            }
+        }
+        try!(fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("couldn't replace '{}' ({})", self.path.display(), e)));
+        Ok(pruned)
+    }
+}
+
+pub const POSTGRES_TABLE: &'static str = "factotum_runs";
+
+// the table is made the first time it's used, so a new database needs no setup
+pub const POSTGRES_SCHEMA: &'static str = "SET client_min_messages = warning;
+CREATE TABLE IF NOT EXISTS factotum_runs (
+    id BIGSERIAL PRIMARY KEY,
+    job_name TEXT NOT NULL,
+    run_reference TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    record TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS factotum_runs_job_name ON factotum_runs (job_name);
+CREATE INDEX IF NOT EXISTS factotum_runs_run_reference ON factotum_runs (run_reference);
+";
+
+// dollar quoted, with a tag the value doesn't contain, so nothing in it needs escaping; a
+// value ending in "$factotum" would end the literal early too, as the closing "$" follows it
+pub fn sql_literal(value: &str) -> String {
+    let mut tag = "factotum".to_string();
+    while value.contains(&format!("${}", tag)) {
+        tag.push('_');
+    }
+    format!("${}${}${}$", tag, value, tag)
+}
+
+pub fn insert_sql(record: &Json) -> String {
+    let field = |name: &str| record.find(name).and_then(|v| v.as_string()).unwrap_or("");
+    format!("INSERT INTO {} (job_name, run_reference, record) VALUES ({}, {}, {});\n",
+            POSTGRES_TABLE,
+            sql_literal(field("jobName")),
+            sql_literal(field("runReference")),
+            sql_literal(&record.to_string()))
+}
+
+pub fn select_sql(column: Option<(&str, &str)>) -> String {
+    let condition = match column {
+        Some((name, value)) => format!(" WHERE {} = {}", name, sql_literal(value)),
+        None => "".to_string(),
+    };
+    format!("SELECT record FROM {}{} ORDER BY id;\n", POSTGRES_TABLE, condition)
+}
+
+// the age of a run is from when it was recorded, which is when it ended
+pub fn prune_sql(retention: &HistoryRetention, now: &DateTime<UTC>) -> Option<String> {
+    let mut conditions = vec![];
+    if let Some(keep_for) = retention.keep_for {
+        let cutoff = *now - ChronoDuration::from_std(keep_for).unwrap();
+        conditions.push(format!("recorded_at < to_timestamp({})", cutoff.timestamp()));
+    }
+    if let Some(max_runs) = retention.max_runs {
+        conditions.push(format!("id NOT IN (SELECT id FROM {} ORDER BY id DESC LIMIT {})",
+                                POSTGRES_TABLE,
+                                max_runs));
+    }
+    if conditions.is_empty() {
+        return None;
+    }
+    Some(format!("WITH pruned AS (DELETE FROM {} WHERE {} RETURNING 1) \
+                  SELECT count(*) FROM pruned;\n",
+                 POSTGRES_TABLE,
+                 conditions.join(" OR ")))
+}
+
+// the password in a URL is percent-encoded, psql wants it as it is
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// the connection URL without its password (given as user:password@ or ?password=), and the
+// password, which psql is given in PGPASSWORD so it isn't on a command line anyone can see
+pub fn split_password(url: &str) -> (String, Option<String>) {
+    let mut parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(_) => return (url.to_string(), None),
+    };
+
+    let mut password = parsed.password().map(percent_decode);
+    if password.is_some() && parsed.set_password(None).is_err() {
+        return (url.to_string(), None);
+    }
+
+    let pairs = parsed.query_pairs().into_owned().collect::<Vec<(String, String)>>();
+    if pairs.iter().any(|&(ref name, _)| name == "password") {
+        let mut kept = vec![];
+        for (name, value) in pairs {
+            if name == "password" {
+                password = Some(value);
+            } else {
+                kept.push((name, value));
+            }
+        }
+        if kept.is_empty() {
+            parsed.set_query(None);
+        } else {
+            parsed.query_pairs_mut().clear().extend_pairs(kept);
+        }
+    }
+
+    (parsed.to_string(), password)
+}
+
+// run with the psql CLI
+pub struct PostgresStore {
+    pub url: String,
+}
+
+impl PostgresStore {
+    fn psql(&self, sql: &str) -> Result<String, String> {
+        let (url, password) = split_password(&self.url);
+        let mut command = Command::new("psql");
+        if let Some(password) = password {
+            command.env("PGPASSWORD", password);
+        }
+        let mut child = try!(command.arg(&url)
+            .args(&["--no-psqlrc", "--quiet", "--tuples-only", "--no-align"])
+            .args(&["--set", "ON_ERROR_STOP=1"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("couldn't run psql ({})", e)));
+
+        {
+            let stdin = child.stdin.as_mut().unwrap();
+            try!(stdin.write_all(POSTGRES_SCHEMA.as_bytes())
+                .and_then(|_| stdin.write_all(sql.as_bytes()))
+                .map_err(|e| format!("couldn't send the query to psql ({})", e)));
+        }
+
+        let output = try!(child.wait_with_output()
+            .map_err(|e| format!("couldn't run psql ({})", e)));
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(format!("the history database refused the query: {}",
+                        String::from_utf8_lossy(&output.stderr).trim()))
+        }
+    }
+
+    fn select(&self, column: Option<(&str, &str)>) -> Result<Vec<RunRecord>, String> {
+        let output = try!(self.psql(&select_sql(column)));
+        parse_run_records(output.lines().map(|l| Ok(l.to_string())), &self.describe())
+    }
+}
+
+impl RunStore for PostgresStore {
+    fn append(&self, record: &Json) -> Result<(), String> {
+        self.psql(&insert_sql(record)).map(|_| ())
+    }
+
+    fn load_all_runs(&self) -> Result<Vec<RunRecord>, String> {
+        self.select(None)
+    }
+
+    fn load_runs(&self, job_name: &str) -> Result<Vec<RunRecord>, String> {
+        self.select(Some(("job_name", job_name)))
+    }
+
+    fn find_run(&self, run_reference: &str) -> Result<RunRecord, String> {
+        let runs = try!(self.select(Some(("run_reference", run_reference))));
+        runs.into_iter()
+            .next()
+            .ok_or(format!("no run '{}' was found in {}", run_reference, self.describe()))
+    }
+
+    fn describe(&self) -> String {
+        "the history database".to_string()
+    }
+
+    fn prune(&self, retention: &HistoryRetention, now: &DateTime<UTC>) -> Result<usize, String> {
+        match prune_sql(retention, now) {
+            Some(sql) => {
+                let output = try!(self.psql(&sql));
+                output.trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("psql sent an unexpected reply '{}'", output.trim()))
+            }
+            None => Ok(0),
+        }
+    }
+}
@@ -0,0 +1,327 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::task_list::{Task, State};
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+#[test]
+fn record_and_load_runs() {
+    let context = JobContext::new("my job", "{}", None);
+    let other_context = JobContext::new("other job", "{}", None);
+
+    let mut history_dir = env::temp_dir();
+    history_dir.push(format!("factotum-history-test-{}", context.run_reference));
+    let history_file = history_dir.join("history.jsonl");
+    let store = FileStore { path: history_file.clone() };
+
+    assert_eq!(store.load_runs("my job"), Ok(vec![]));
+
+    let spec_a = make_task("a", &vec![]);
+    let spec_b = make_task("b", &vec!["a"]);
+    let mut a = Task::new("a", &spec_a);
+    a.state = State::Failed("bad code".to_string());
+    let mut b = Task::new("b", &spec_b);
+    b.state = State::Skipped("the task 'a' failed".to_string());
+
+    record_run(&store, &context, &vec![&a, &b], None).unwrap();
+    record_run(&store, &other_context, &vec![&a], None).unwrap();
+
+    let runs = store.load_runs("my job").unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].run_reference, context.run_reference);
+    assert_eq!(runs[0].run_state, "FAILED");
+    assert_eq!(runs[0].task("a"),
+               Some(&TaskRecord {
+                   task_name: "a".to_string(),
+                   state: "FAILED".to_string(),
+                   error_message: Some("bad code".to_string()),
+                   depends_on: vec![],
+                   started: None,
+                   duration_millis: None,
+                   cache_key: None,
+               }));
+    assert_eq!(runs[0].task("b").unwrap().depends_on, vec!["a".to_string()]);
+    assert_eq!(runs[0].task("b").unwrap().state, "SKIPPED");
+    assert_eq!(runs[0].task("c"), None);
+
+    assert!(fs::remove_dir_all(&history_dir).is_ok());
+}
+
+#[test]
+fn load_runs_ignores_bad_lines() {
+    let context = JobContext::new("my job", "{}", None);
+    let mut history_file = env::temp_dir();
+    history_file.push(format!("factotum-history-test-{}.jsonl", context.run_reference));
+    let store = FileStore { path: history_file.clone() };
+    let mut f = File::create(&history_file).unwrap();
+    f.write_all(b"not json\n\n{\"jobName\":\"my job\",\"runReference\":\"abc\",\"tasks\":[]}\n")
+        .unwrap();
+
+    let runs = store.load_runs("my job").unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].run_reference, "abc");
+
+    assert!(fs::remove_file(&history_file).is_ok());
+}
+
+#[test]
+fn find_run_by_reference() {
+    let context = JobContext::new("my job", "{}", None);
+    let other_context = JobContext::new("other job", "{}", None);
+    let mut history_file = env::temp_dir();
+    history_file.push(format!("factotum-history-test-{}.jsonl", context.run_reference));
+    let store = FileStore { path: history_file.clone() };
+
+    record_run(&store, &context, &vec![], None).unwrap();
+    record_run(&store, &other_context, &vec![], None).unwrap();
+
+    let run = store.find_run(&other_context.run_reference).unwrap();
+    assert_eq!(run.job_name, "other job");
+    assert_eq!(store.find_run("missing"),
+               Err(format!("no run 'missing' was found in '{}'", history_file.display())));
+
+    assert!(fs::remove_file(&history_file).is_ok());
+}
+
+#[test]
+fn record_run_keeps_the_source() {
+    let context = JobContext::new("my job", "{}", None);
+    let mut history_file = env::temp_dir();
+    history_file.push(format!("factotum-history-test-{}.jsonl", context.run_reference));
+    let store = FileStore { path: history_file.clone() };
+
+    let source = RunSource {
+        factfile: "/jobs/my_job.factfile".to_string(),
+        variables: Json::from_str("{\"run_date\":\"2021-01-01\"}").unwrap(),
+    };
+    let other_context = JobContext::new("my job", "{}", None);
+    record_run(&store, &context, &vec![], Some(&source)).unwrap();
+    record_run(&store, &other_context, &vec![], None).unwrap();
+
+    assert_eq!(store.find_run(&context.run_reference).unwrap().source,
+               Some(source));
+    assert_eq!(store.find_run(&other_context.run_reference).unwrap().source,
+               None);
+
+    assert!(fs::remove_file(&history_file).is_ok());
+}
+
+#[test]
+fn history_stores_parsed() {
+    assert_eq!(parse_history_store("postgres://factotum@db/history"),
+               HistoryStore::Postgres("postgres://factotum@db/history".to_string()));
+    assert_eq!(parse_history_store("postgresql://db/history"),
+               HistoryStore::Postgres("postgresql://db/history".to_string()));
+    assert_eq!(parse_history_store("/var/lib/factotum/history.jsonl"),
+               HistoryStore::File(PathBuf::from("/var/lib/factotum/history.jsonl")));
+    assert_eq!(HistoryStore::default(), HistoryStore::File(PathBuf::from(HISTORY_FILE)));
+}
+
+#[test]
+fn sql_literals_need_no_escaping() {
+    assert_eq!(sql_literal("it's"), "$factotum$it's$factotum$");
+    assert_eq!(sql_literal("a $factotum$ b"), "$factotum_$a $factotum$ b$factotum_$");
+    assert_eq!(sql_literal("etl$factotum"), "$factotum_$etl$factotum$factotum_$");
+    assert_eq!(sql_literal("$factotum_ and $factotum"),
+               "$factotum__$$factotum_ and $factotum$factotum__$");
+}
+
+#[test]
+fn postgres_password_kept_off_the_command_line() {
+    assert_eq!(split_password("postgres://factotum:s3cr%40t@db:5432/history"),
+               ("postgres://factotum@db:5432/history".to_string(), Some("s3cr@t".to_string())));
+    assert_eq!(split_password("postgresql://db/history?user=factotum&password=pw&sslmode=require"),
+               ("postgresql://db/history?user=factotum&sslmode=require".to_string(),
+                Some("pw".to_string())));
+    assert_eq!(split_password("postgres://db/history?password=pw"),
+               ("postgres://db/history".to_string(), Some("pw".to_string())));
+    assert_eq!(split_password("postgres://factotum@db/history"),
+               ("postgres://factotum@db/history".to_string(), None));
+}
+
+#[test]
+fn postgres_queries_good() {
+    let record = Json::from_str(r#"{"jobName": "my job", "runReference": "abc"}"#).unwrap();
+    assert_eq!(insert_sql(&record),
+               "INSERT INTO factotum_runs (job_name, run_reference, record) VALUES \
+                ($factotum$my job$factotum$, $factotum$abc$factotum$, \
+                $factotum${\"jobName\":\"my job\",\"runReference\":\"abc\"}$factotum$);\n");
+    assert_eq!(select_sql(None), "SELECT record FROM factotum_runs ORDER BY id;\n");
+    assert_eq!(select_sql(Some(("job_name", "my job"))),
+               "SELECT record FROM factotum_runs WHERE job_name = $factotum$my job$factotum$ \
+                ORDER BY id;\n");
+}
+
+#[test]
+fn postgres_records_parsed() {
+    let output = "{\"jobName\":\"my job\",\"runReference\":\"abc\",\"tasks\":[]}\nnot json\n";
+    let runs = parse_run_records(output.lines().map(|l| Ok(l.to_string())), "the database")
+        .unwrap();
+    assert_eq!(runs.len(), 1);
+    assert_eq!(runs[0].run_reference, "abc");
+}
+
+#[test]
+fn history_retention_parsed() {
+    assert_eq!(parse_history_retention(None, None), Ok(HistoryRetention::default()));
+    assert!(HistoryRetention::default().is_unlimited());
+    assert_eq!(parse_history_retention(Some("90d"), Some(100)),
+               Ok(HistoryRetention {
+                   keep_for: Some(Duration::from_secs(90 * 24 * 60 * 60)),
+                   max_runs: Some(100),
+               }));
+    assert_eq!(parse_history_retention(Some("forever"), None),
+               Err("'forever' isn't a history retention (expected a duration such as 90d)"
+                   .to_string()));
+    assert_eq!(parse_history_retention(None, Some(0)),
+               Err("the history must keep at least 1 run".to_string()));
+}
+
+#[test]
+fn history_retention_keeps_recent_runs() {
+    let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00Z").unwrap().with_timezone(&UTC);
+    let retention = HistoryRetention {
+        keep_for: Some(Duration::from_secs(24 * 60 * 60)),
+        max_runs: Some(2),
+    };
+    assert!(retention.keeps(0, "2024-03-10T00:00:00Z", &now));
+    assert!(!retention.keeps(0, "2024-03-08T00:00:00Z", &now));
+    assert!(!retention.keeps(2, "2024-03-10T00:00:00Z", &now));
+    assert!(retention.keeps(1, "not a time", &now));
+}
+
+#[test]
+fn file_store_pruned() {
+    let context = JobContext::new("my job", "{}", None);
+    let mut history_dir = env::temp_dir();
+    history_dir.push(format!("factotum-history-prune-test-{}", context.run_reference));
+    fs::create_dir_all(&history_dir).unwrap();
+    let history_file = history_dir.join("history.jsonl");
+    let store = FileStore { path: history_file.clone() };
+
+    let run = |reference: &str, start_time: &str| {
+        format!("{{\"jobName\":\"my job\",\"runReference\":\"{}\",\"startTime\":\"{}\",\
+                 \"tasks\":[]}}",
+                reference,
+                start_time)
+    };
+    let mut f = File::create(&history_file).unwrap();
+    writeln!(f, "{}", run("a", "2024-03-01T00:00:00Z")).unwrap();
+    writeln!(f, "not json").unwrap();
+    writeln!(f, "{}", run("b", "2024-03-09T00:00:00Z")).unwrap();
+    writeln!(f, "{}", run("c", "2024-03-09T06:00:00Z")).unwrap();
+    writeln!(f, "{}", run("d", "2024-03-10T00:00:00Z")).unwrap();
+
+    let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00Z").unwrap().with_timezone(&UTC);
+    let retention = HistoryRetention {
+        keep_for: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+        max_runs: Some(2),
+    };
+    assert_eq!(store.prune(&retention, &now), Ok(2));
+    assert_eq!(store.prune(&retention, &now), Ok(0));
+
+    let refs = store.load_all_runs()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.run_reference)
+        .collect::<Vec<String>>();
+    assert_eq!(refs, vec!["c".to_string(), "d".to_string()]);
+    let mut contents = String::new();
+    File::open(&history_file).unwrap().read_to_string(&mut contents).unwrap();
+    assert!(contents.contains("not json"));
+
+    assert!(fs::remove_dir_all(&history_dir).is_ok());
+}
+
+#[test]
+fn postgres_prune_queries_good() {
+    let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00Z").unwrap().with_timezone(&UTC);
+    assert_eq!(prune_sql(&HistoryRetention::default(), &now), None);
+    assert_eq!(prune_sql(&HistoryRetention {
+                             keep_for: Some(Duration::from_secs(24 * 60 * 60)),
+                             max_runs: Some(10),
+                         },
+                         &now),
+               Some("WITH pruned AS (DELETE FROM factotum_runs WHERE recorded_at < \
+                     to_timestamp(1709985600) OR id NOT IN (SELECT id FROM factotum_runs \
+                     ORDER BY id DESC LIMIT 10) RETURNING 1) SELECT count(*) FROM pruned;\n"
+                   .to_string()));
+}
+
+fn export_run(reference: &str, start_time: &str, tasks: Vec<TaskRecord>) -> RunRecord {
+    RunRecord {
+        job_name: "my job".to_string(),
+        run_reference: reference.to_string(),
+        start_time: start_time.to_string(),
+        run_state: "SUCCESS".to_string(),
+        tasks: tasks,
+        source: None,
+    }
+}
+
+#[test]
+fn export_formats_parsed() {
+    assert_eq!(parse_export_format("csv"), Ok(ExportFormat::Csv));
+    assert_eq!(parse_export_format("json"), Ok(ExportFormat::Json));
+    assert_eq!(parse_export_format("xml"),
+               Err("'xml' isn't an export format (expected csv or json)".to_string()));
+}
+
+#[test]
+fn runs_since_keeps_recent_runs() {
+    let now = DateTime::parse_from_rfc3339("2024-03-10T12:00:00Z").unwrap().with_timezone(&UTC);
+    let runs = vec![export_run("a", "2024-02-01T00:00:00Z", vec![]),
+                    export_run("b", "2024-03-09T00:00:00Z", vec![]),
+                    export_run("c", "", vec![])];
+    let recent = runs_since(runs, &Duration::from_secs(7 * 24 * 60 * 60), &now);
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].run_reference, "b");
+}
+
+#[test]
+fn runs_exported_as_csv() {
+    let task = TaskRecord {
+        task_name: "load".to_string(),
+        state: "FAILED".to_string(),
+        error_message: Some("the \"db\" said no, twice".to_string()),
+        depends_on: vec![],
+        started: Some("2024-03-09T00:00:01Z".to_string()),
+        duration_millis: Some(1500),
+        cache_key: None,
+    };
+    let runs = vec![export_run("a", "2024-03-09T00:00:00Z", vec![task]),
+                    export_run("b", "2024-03-09T01:00:00Z", vec![])];
+    assert_eq!(export_runs(&runs, &ExportFormat::Csv),
+               format!("{}\nmy job,a,2024-03-09T00:00:00Z,SUCCESS,load,FAILED,\
+                        2024-03-09T00:00:01Z,1500,\"the \"\"db\"\" said no, twice\"\n\
+                        my job,b,2024-03-09T01:00:00Z,SUCCESS,,,,,\n",
+                       CSV_HEADER));
+}
+
+#[test]
+fn runs_exported_as_json() {
+    let mut run = export_run("a", "2024-03-09T00:00:00Z", vec![]);
+    run.source = Some(RunSource {
+        factfile: "x.factfile".to_string(),
+        variables: Json::from_str("{\"password\":\"hunter2\"}").unwrap(),
+    });
+    assert_eq!(export_runs(&vec![run], &ExportFormat::Json),
+               "{\"jobName\":\"my job\",\"runReference\":\"a\",\"runState\":\"SUCCESS\",\
+                \"startTime\":\"2024-03-09T00:00:00Z\",\"tasks\":[]}\n");
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::errors::{ErrorKind, FactotumError};
+use factotum::executor::execution_strategy::{RunResult, TaskLimits};
+use libc;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+// the signal that's aborting the job, or 0
+static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+const SIGNALS: [libc::c_int; 3] = [libc::SIGINT, libc::SIGTERM, libc::SIGHUP];
+
+extern "C" fn on_signal(signal: libc::c_int) {
+    RECEIVED.store(signal as usize, Ordering::SeqCst);
+    // a second signal ends factotum straight away
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+    }
+}
+
+// rather than ending factotum, the first SIGINT, SIGTERM or SIGHUP stops any more tasks
+// starting - tasks that are already running are waited for
+pub fn install_handlers() {
+    for signal in SIGNALS.iter() {
+        unsafe {
+            libc::signal(*signal, on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+    }
+}
+
+pub fn received() -> Option<i32> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(signal as i32),
+    }
+}
+
+// aborts the job as if factotum had been sent the signal
+pub fn abort(signal: i32) {
+    RECEIVED.store(signal as usize, Ordering::SeqCst);
+}
+
+pub fn signal_name(signal: i32) -> String {
+    match signal {
+        libc::SIGINT => "SIGINT".to_string(),
+        libc::SIGTERM => "SIGTERM".to_string(),
+        libc::SIGHUP => "SIGHUP".to_string(),
+        other => format!("signal {}", other),
+    }
+}
+
+pub fn aborted(task_name: &str, signal: i32) -> RunResult {
+    RunResult {
+        duration: Duration::from_secs(0),
+        task_execution_error: Some(FactotumError::new(ErrorKind::Execution,
+                                                      format!("the job was aborted by {} \
+                                                               before '{}' started",
+                                                              signal_name(signal),
+                                                              task_name))
+            .for_task(task_name)),
+        stdout: None,
+        stderr: None,
+        return_code: -1,
+        resource_usage: None,
+        spilled_output: None,
+    }
+}
+
+// wraps an execution strategy so tasks don't start once the job has been aborted
+pub fn with_abort<F>(strategy: F)
+    -> impl Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+{
+    move |name: &str, command: &mut Command, limits: &TaskLimits| {
+        match received() {
+            Some(signal) => {
+                warn!("Not running the task '{}' as the job was aborted by {}",
+                      name,
+                      signal_name(signal));
+                aborted(name, signal)
+            }
+            None => strategy(name, command, limits),
+        }
+    }
+}
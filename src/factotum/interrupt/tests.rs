@@ -0,0 +1,28 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use libc;
+
+#[test]
+fn aborted_tasks_dont_start() {
+    assert_eq!(signal_name(libc::SIGTERM), "SIGTERM");
+    assert_eq!(signal_name(libc::SIGUSR1), format!("signal {}", libc::SIGUSR1));
+
+    let result = aborted("load", libc::SIGINT);
+    assert_eq!(result.return_code, -1);
+    let error = result.task_execution_error.unwrap();
+    assert_eq!(error.message, "the job was aborted by SIGINT before 'load' started");
+    assert_eq!(error.task, Some("load".to_string()));
+}
@@ -0,0 +1,104 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq, RustcDecodable)]
+pub struct Checksum {
+    pub sha256: String,
+    pub size_bytes: u64,
+}
+
+// an artifact a task said it produces; it has no checksum if it couldn't be read
+#[derive(Clone, Debug, PartialEq, RustcDecodable)]
+pub struct Artifact {
+    pub path: String,
+    pub checksum: Option<Checksum>,
+}
+
+// feeds the file to the digest a block at a time, giving how many bytes it had
+pub fn hash_file(digest: &mut Sha256, path: &Path) -> io::Result<u64> {
+    let mut f = try!(File::open(path));
+    let mut buf = [0u8; 65536];
+    let mut size = 0;
+    loop {
+        let n = try!(f.read(&mut buf));
+        if n == 0 {
+            return Ok(size);
+        }
+        digest.input(&buf[..n]);
+        size += n as u64;
+    }
+}
+
+pub fn checksum(path: &Path) -> Result<Checksum, String> {
+    let mut digest = Sha256::new();
+    let size = try!(hash_file(&mut digest, path)
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+    Ok(Checksum {
+        sha256: digest.result_str(),
+        size_bytes: size,
+    })
+}
+
+pub fn build_manifest(paths: &Vec<String>) -> Vec<Artifact> {
+    paths.iter()
+        .map(|path| {
+            let checksum = match checksum(Path::new(path)) {
+                Ok(checksum) => Some(checksum),
+                Err(msg) => {
+                    warn!("Couldn't checksum an artifact: {}", msg);
+                    None
+                }
+            };
+            Artifact {
+                path: path.clone(),
+                checksum: checksum,
+            }
+        })
+        .collect()
+}
+
+pub fn missing(artifacts: &Vec<Artifact>) -> Vec<&str> {
+    artifacts.iter()
+        .filter(|a| a.checksum.is_none())
+        .map(|a| a.path.as_str())
+        .collect()
+}
+
+impl ToJson for Artifact {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        d.insert("path".to_string(), self.path.to_json());
+        match self.checksum {
+            Some(ref checksum) => {
+                d.insert("sha256".to_string(), checksum.sha256.to_json());
+                d.insert("sizeBytes".to_string(), checksum.size_bytes.to_json());
+            }
+            None => {
+                d.insert("missing".to_string(), true.to_json());
+            }
+        }
+        Json::Object(d)
+    }
+}
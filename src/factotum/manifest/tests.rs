@@ -0,0 +1,75 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+fn temp_file(name: &str, contents: &[u8]) -> String {
+    let path = env::temp_dir().join(format!("factotum-manifest-test-{}", name));
+    File::create(&path).unwrap().write_all(contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[test]
+fn checksum_good() {
+    let path = temp_file("hello", b"hello\n");
+    assert_eq!(checksum(Path::new(&path)),
+               Ok(Checksum {
+                   sha256: "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+                       .to_string(),
+                   size_bytes: 6,
+               }));
+    assert!(fs::remove_file(&path).is_ok());
+}
+
+#[test]
+fn checksum_missing_file() {
+    let result = checksum(Path::new("/this/does/not/exist.csv"));
+    assert!(result.unwrap_err().starts_with("couldn't read '/this/does/not/exist.csv'"));
+}
+
+#[test]
+fn build_manifest_marks_missing_artifacts() {
+    let path = temp_file("empty", b"");
+    let manifest = build_manifest(&vec![path.clone(), "/this/does/not/exist.csv".to_string()]);
+
+    assert_eq!(manifest.len(), 2);
+    assert_eq!(manifest[0].path, path);
+    assert_eq!(manifest[0].checksum.as_ref().map(|c| c.size_bytes), Some(0));
+    assert_eq!(manifest[1].checksum, None);
+    assert_eq!(missing(&manifest), vec!["/this/does/not/exist.csv"]);
+    assert!(fs::remove_file(&path).is_ok());
+}
+
+#[test]
+fn artifact_to_json() {
+    let present = Artifact {
+        path: "/data/out.csv".to_string(),
+        checksum: Some(Checksum {
+            sha256: "abc".to_string(),
+            size_bytes: 42,
+        }),
+    };
+    assert_eq!(present.to_json().to_string(),
+               r#"{"path":"/data/out.csv","sha256":"abc","sizeBytes":42}"#);
+
+    let absent = Artifact {
+        path: "/data/gone.csv".to_string(),
+        checksum: None,
+    };
+    assert_eq!(absent.to_json().to_string(),
+               r#"{"missing":true,"path":"/data/gone.csv"}"#);
+}
@@ -0,0 +1,137 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::execution_strategy::RunResult;
+use rustc_serialize::json::Json;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// what a task "returns" when it's run by the mock executor; tasks missing from the
+// mock results succeed with no output
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MockResult {
+    pub return_code: i32,
+    pub stdout: Option<String>,
+    pub stderr: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockCall {
+    pub task_name: String,
+    pub command: String,
+}
+
+fn parse_mock_result(task_name: &str, value: &Json) -> Result<MockResult, String> {
+    let bad_result = || {
+        format!("the mock result for '{}' must be a return code, or an object with a \
+                 returnCode and optional stdout and stderr",
+                task_name)
+    };
+
+    if let Some(code) = value.as_i64() {
+        return Ok(MockResult { return_code: code as i32, ..MockResult::default() });
+    }
+
+    let obj = try!(value.as_object().ok_or_else(&bad_result));
+    let mut result = MockResult::default();
+    for (key, v) in obj.iter() {
+        match key.as_ref() {
+            "returnCode" => result.return_code = try!(v.as_i64().ok_or_else(&bad_result)) as i32,
+            "stdout" => result.stdout = Some(try!(v.as_string().ok_or_else(&bad_result)).to_string()),
+            "stderr" => result.stderr = Some(try!(v.as_string().ok_or_else(&bad_result)).to_string()),
+            _ => return Err(bad_result()),
+        }
+    }
+    Ok(result)
+}
+
+// mock results are a JSON object of task name to either a return code, or
+// {"returnCode": 1, "stdout": "...", "stderr": "..."}
+pub fn mock_results_from_json(json: &Json) -> Result<HashMap<String, MockResult>, String> {
+    let obj = try!(json.as_object()
+        .ok_or("it must be a JSON object of task names to results".to_string()));
+
+    let mut results = HashMap::new();
+    for (task_name, value) in obj.iter() {
+        results.insert(task_name.clone(), try!(parse_mock_result(task_name, value)));
+    }
+    Ok(results)
+}
+
+pub fn parse_mock_results(json: &str) -> Result<HashMap<String, MockResult>, String> {
+    let parsed = try!(Json::from_str(json).map_err(|e| format!("it isn't valid JSON ({})", e)));
+    mock_results_from_json(&parsed)
+}
+
+pub fn load_mock_results(path: &Path) -> Result<HashMap<String, MockResult>, String> {
+    let contents = try!(fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read the mock results '{}' ({})", path.display(), e)));
+    parse_mock_results(&contents)
+        .map_err(|e| format!("the mock results '{}' can't be used: {}", path.display(), e))
+}
+
+// an execution strategy that runs nothing, recording the command each task would have run
+#[derive(Debug, Clone, Default)]
+pub struct MockExecutor {
+    results: Arc<HashMap<String, MockResult>>,
+    calls: Arc<Mutex<Vec<MockCall>>>,
+}
+
+impl MockExecutor {
+    pub fn new(results: HashMap<String, MockResult>) -> MockExecutor {
+        MockExecutor {
+            results: Arc::new(results),
+            calls: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    pub fn execute(&self, name: &str, command: &mut Command) -> RunResult {
+        info!("Mocking execution for {} with command {:?}", name, command);
+        self.calls.lock().unwrap().push(MockCall {
+            task_name: name.to_string(),
+            command: format!("{:?}", command),
+        });
+
+        let result = self.results.get(name).cloned().unwrap_or(MockResult::default());
+        RunResult {
+            duration: Duration::from_secs(0),
+            task_execution_error: None,
+            stdout: result.stdout,
+            stderr: result.stderr,
+            return_code: result.return_code,
+            resource_usage: None,
+            spilled_output: None,
+        }
+    }
+
+    // the tasks run so far, in the order they were started
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+pub fn format_calls(calls: &Vec<MockCall>) -> String {
+    calls.iter()
+        .enumerate()
+        .map(|(idx, call)| format!("  {}. {}: {}", idx + 1, call.task_name, call.command))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
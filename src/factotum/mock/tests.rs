@@ -0,0 +1,130 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::execute_factfile;
+use factotum::executor::execution_strategy::TaskLimits;
+use factotum::executor::task_list::State;
+use factotum::factfile::{Factfile, Task};
+use factotum::tests::make_task;
+use std::collections::HashMap;
+use std::process::Command;
+
+fn result(return_code: i32, stdout: Option<&str>) -> MockResult {
+    MockResult {
+        return_code: return_code,
+        stdout: stdout.map(|s| s.to_string()),
+        stderr: None,
+    }
+}
+
+#[test]
+fn parse_mock_results_good() {
+    let results = parse_mock_results(r#"{"a": 3, "b": {"returnCode": 1, "stdout": "hi"},
+                                         "c": {"stderr": "oops"}}"#)
+        .unwrap();
+
+    assert_eq!(results.len(), 3);
+    assert_eq!(results["a"], result(3, None));
+    assert_eq!(results["b"], result(1, Some("hi")));
+    assert_eq!(results["c"],
+               MockResult { stderr: Some("oops".to_string()), ..MockResult::default() });
+    assert_eq!(parse_mock_results("{}"), Ok(HashMap::new()));
+}
+
+#[test]
+fn parse_mock_results_bad() {
+    assert!(parse_mock_results("{").unwrap_err().starts_with("it isn't valid JSON"));
+    assert_eq!(parse_mock_results("[1]"),
+               Err("it must be a JSON object of task names to results".to_string()));
+    let bad_result = Err("the mock result for 'a' must be a return code, or an object with a \
+                          returnCode and optional stdout and stderr"
+        .to_string());
+    assert_eq!(parse_mock_results(r#"{"a": "1"}"#), bad_result);
+    assert_eq!(parse_mock_results(r#"{"a": {"returnCode": "1"}}"#), bad_result);
+    assert_eq!(parse_mock_results(r#"{"a": {"exitCode": 1}}"#), bad_result);
+}
+
+#[test]
+fn mock_executor_records_calls() {
+    let mut results = HashMap::new();
+    results.insert("a".to_string(), result(2, Some("out")));
+    let mock = MockExecutor::new(results);
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("rm -rf /tmp/nothing");
+    let a = mock.execute("a", &mut command);
+    let b = mock.execute("b", &mut Command::new("true"));
+
+    assert_eq!(a.return_code, 2);
+    assert_eq!(a.stdout, Some("out".to_string()));
+    assert_eq!(b.return_code, 0);
+    assert_eq!(b.stdout, None);
+    assert_eq!(mock.calls(),
+               vec![MockCall {
+                        task_name: "a".to_string(),
+                        command: "\"sh\" \"-c\" \"rm -rf /tmp/nothing\"".to_string(),
+                    },
+                    MockCall {
+                        task_name: "b".to_string(),
+                        command: "\"true\"".to_string(),
+                    }]);
+    assert_eq!(format_calls(&mock.calls()),
+               "  1. a: \"sh\" \"-c\" \"rm -rf /tmp/nothing\"\n  2. b: \"true\"");
+}
+
+fn continues_on_zero(name: &str, depends_on: &Vec<&str>) -> Task {
+    let mut task = make_task(name, depends_on);
+    task.on_result.continue_job = vec![0];
+    task
+}
+
+#[test]
+fn mock_results_drive_the_job() {
+    let mut ff = Factfile::new("N/A", "mocked");
+    ff.add_task_obj(&continues_on_zero("a", &vec![])).unwrap();
+    ff.add_task_obj(&continues_on_zero("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&continues_on_zero("c", &vec!["a"])).unwrap();
+    ff.add_task_obj(&continues_on_zero("d", &vec!["b"])).unwrap();
+
+    let mut results = HashMap::new();
+    results.insert("b".to_string(), result(1, None));
+    let mock = MockExecutor::new(results);
+    let strategy = {
+        let mock = mock.clone();
+        move |name: &str, command: &mut Command, _: &TaskLimits| mock.execute(name, command)
+    };
+
+    let tl = execute_factfile(&ff, None, &HashMap::new(), strategy, None);
+
+    let states = tl.tasks
+        .iter()
+        .flat_map(|g| g.iter())
+        .map(|t| (t.name.clone(), t.state.clone()))
+        .collect::<HashMap<String, State>>();
+    assert_eq!(states["a"], State::Success);
+    assert!(match states["b"] {
+        State::Failed(_) => true,
+        _ => false,
+    });
+    assert_eq!(states["c"], State::Success);
+    assert!(match states["d"] {
+        State::Skipped(_) => true,
+        _ => false,
+    });
+
+    let mut called = mock.calls().into_iter().map(|c| c.task_name).collect::<Vec<String>>();
+    called.sort();
+    assert_eq!(called, vec!["a", "b", "c"]);
+}
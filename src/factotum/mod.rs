@@ -17,6 +17,60 @@ pub mod parser;
 pub mod executor;
 pub mod sequencer;
 pub mod webhook;
+pub mod report;
+pub mod archive;
+pub mod cloudwatch;
+pub mod syslog;
+pub mod retry;
+pub mod history;
+pub mod circuitbreaker;
+pub mod backfill;
+pub mod envfile;
+pub mod criticalpath;
+pub mod timeline;
+pub mod compare;
+pub mod durationwatch;
+pub mod sla;
+pub mod timewindow;
+pub mod tzfile;
+pub mod preflight;
+pub mod waitfor;
+pub mod assertion;
+pub mod nested;
+pub mod generator;
+pub mod completions;
+pub mod config;
+pub mod rerun;
+pub mod devmode;
+pub mod mock;
+pub mod testspec;
+pub mod chaos;
+pub mod exitcode;
+pub mod interrupt;
+pub mod policy;
+pub mod sandbox;
+pub mod workspace;
+pub mod cache;
+pub mod manifest;
 
 #[cfg(test)]
 mod tests;
+pub mod signature;
+pub mod audit;
+pub mod heartbeat;
+pub mod placeholders;
+pub mod sourcemap;
+pub mod diagnostics;
+pub mod errors;
+pub mod queue;
+pub mod control;
+pub mod console;
+pub mod taskenv;
+pub mod constraint;
+pub mod emr;
+pub mod batch;
+pub mod gcp;
+pub mod dbt;
+pub mod sql;
+pub mod spark;
+pub mod expression;
@@ -0,0 +1,95 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::json::Json;
+use std::env;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+pub const EXECUTOR: &'static str = "factfile";
+
+// a nested factfile task's arguments are variable overrides for the child, e.g. [ "region=eu" ]
+pub fn parse_overrides(args: &Vec<String>) -> Result<Vec<(String, String)>, String> {
+    args.iter()
+        .map(|arg| {
+            let mut split = arg.splitn(2, '=');
+            match (split.next(), split.next()) {
+                (Some(name), Some(value)) if !name.trim().is_empty() => {
+                    Ok((name.trim().to_string(), value.to_string()))
+                }
+                _ => Err(format!("'{}' should be a variable override given as <name>=<value>", arg)),
+            }
+        })
+        .collect()
+}
+
+// somewhere for the child runs of a single job run to leave their results
+pub fn new_result_dir() -> PathBuf {
+    env::temp_dir().join(format!("factotum-nested-{}", Uuid::new_v4()))
+}
+
+pub fn result_file(result_dir: &Path, task_name: &str) -> PathBuf {
+    let safe_name = task_name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect::<String>();
+    result_dir.join(format!("{}.json", safe_name))
+}
+
+// the child run's tasks, renamed to "<task>/<child task>" so they can sit alongside the parent's
+pub fn nested_tasks(task_name: &str, run_result: &Json) -> Vec<Json> {
+    let prefix = |name: &str| format!("{}/{}", task_name, name);
+
+    run_result.find("tasks")
+        .and_then(|t| t.as_array())
+        .map(|tasks| {
+            tasks.iter()
+                .filter_map(|t| t.as_object())
+                .map(|t| {
+                    let mut t = t.clone();
+                    if let Some(name) = t.get("taskName").and_then(|n| n.as_string()).map(&prefix) {
+                        t.insert("taskName".to_string(), Json::String(name));
+                    }
+                    if let Some(deps) = t.get("dependsOn").and_then(|d| d.as_array()).map(|deps| {
+                        deps.iter()
+                            .filter_map(|d| d.as_string())
+                            .map(|d| Json::String(prefix(d)))
+                            .collect::<Vec<Json>>()
+                    }) {
+                        t.insert("dependsOn".to_string(), Json::Array(deps));
+                    }
+                    Json::Object(t)
+                })
+                .collect()
+        })
+        .unwrap_or(vec![])
+}
+
+// reads (and removes) the result a child run left behind
+pub fn take_nested_tasks(result_dir: &Path, task_name: &str) -> Result<Vec<Json>, String> {
+    let path = result_file(result_dir, task_name);
+    let mut contents = String::new();
+    try!(File::open(&path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read '{}' ({})", path.display(), e)));
+    let _ = fs::remove_file(&path);
+
+    let run_result = try!(Json::from_str(&contents)
+        .map_err(|e| format!("'{}' isn't a run result ({})", path.display(), e)));
+    Ok(nested_tasks(task_name, &run_result))
+}
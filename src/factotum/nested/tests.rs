@@ -0,0 +1,69 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::fs::File;
+use std::io::Write;
+
+#[test]
+fn parse_overrides_good_and_bad() {
+    assert_eq!(parse_overrides(&vec!["region=eu-west-1".to_string(), "query=a=b".to_string()]),
+               Ok(vec![("region".to_string(), "eu-west-1".to_string()),
+                       ("query".to_string(), "a=b".to_string())]));
+    assert_eq!(parse_overrides(&vec!["region".to_string()]),
+               Err("'region' should be a variable override given as <name>=<value>".to_string()));
+    assert!(parse_overrides(&vec!["=eu".to_string()]).is_err());
+}
+
+#[test]
+fn result_file_is_path_safe() {
+    assert_eq!(result_file(Path::new("/tmp/x"), "load eu/west"),
+               PathBuf::from("/tmp/x/load_eu_west.json"));
+}
+
+#[test]
+fn nested_tasks_are_prefixed() {
+    let run_result = Json::from_str(r#"{"tasks": [
+        {"taskName": "extract", "state": "SUCCEEDED", "dependsOn": []},
+        {"taskName": "load", "state": "FAILED", "dependsOn": ["extract"]}
+    ]}"#)
+        .unwrap();
+
+    let tasks = nested_tasks("child", &run_result);
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].find("taskName").unwrap().as_string(), Some("child/extract"));
+    assert_eq!(tasks[1].find("taskName").unwrap().as_string(), Some("child/load"));
+    assert_eq!(tasks[1].find("state").unwrap().as_string(), Some("FAILED"));
+    assert_eq!(tasks[1].find("dependsOn").unwrap(),
+               &Json::Array(vec![Json::String("child/extract".to_string())]));
+
+    assert_eq!(nested_tasks("child", &Json::Null), Vec::<Json>::new());
+}
+
+#[test]
+fn take_nested_tasks_reads_and_removes() {
+    let dir = new_result_dir();
+    fs::create_dir_all(&dir).unwrap();
+    let path = result_file(&dir, "child");
+    File::create(&path)
+        .unwrap()
+        .write_all(br#"{"tasks": [{"taskName": "load", "dependsOn": []}]}"#)
+        .unwrap();
+
+    let tasks = take_nested_tasks(&dir, "child").unwrap();
+    assert_eq!(tasks[0].find("taskName").unwrap().as_string(), Some("child/load"));
+    assert!(!path.exists());
+    assert!(take_nested_tasks(&dir, "child").is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
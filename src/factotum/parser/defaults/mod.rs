@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::json::Json;
+
+// the task fields a factfile's "defaults" can set - a task's name, dependencies and
+// what it's grouped with are its own
+pub const DEFAULTABLE: &'static [&'static str] = &["executor",
+                                                   "command",
+                                                   "arguments",
+                                                   "onResult",
+                                                   "skipIfFailedLast",
+                                                   "skipIfFailureRate",
+                                                   "expectedDuration",
+                                                   "requires",
+                                                   "enabled",
+                                                   "owner",
+                                                   "shell",
+                                                   "escape",
+                                                   "sandbox",
+                                                   "workspace",
+                                                   "cache",
+                                                   "produces",
+                                                   "constraints"];
+
+// each task is given the fields in "defaults" it doesn't set itself, and the defaults
+// are then dropped, so the factfile is checked and run as if it had been written out
+// in full; false if there weren't any defaults
+pub fn apply_defaults(factfile: &mut Json) -> Result<bool, String> {
+    let data = match factfile.as_object_mut().and_then(|f| f.get_mut("data")) {
+        Some(&mut Json::Object(ref mut data)) => data,
+        _ => return Ok(false),
+    };
+    let defaults = match data.remove("defaults") {
+        Some(Json::Object(defaults)) => defaults,
+        Some(_) => return Err("'defaults' must be an object of task fields".to_string()),
+        None => return Ok(false),
+    };
+
+    for field in defaults.keys() {
+        if !DEFAULTABLE.contains(&field.as_str()) {
+            return Err(format!("'defaults' can't set '{}', it must be one of {}",
+                               field,
+                               DEFAULTABLE.join(", ")));
+        }
+    }
+
+    if let Some(&mut Json::Array(ref mut tasks)) = data.get_mut("tasks") {
+        for task in tasks.iter_mut() {
+            if let Json::Object(ref mut task) = *task {
+                for (field, value) in defaults.iter() {
+                    if !task.contains_key(field) {
+                        debug!("task {} takes '{}' from the defaults",
+                               task.get("name").map(|n| n.to_string()).unwrap_or_default(),
+                               field);
+                        task.insert(field.clone(), value.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(true)
+}
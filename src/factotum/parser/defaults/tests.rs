@@ -0,0 +1,79 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+
+use super::*;
+use rustc_serialize::json::Json;
+
+const FACTFILE: &'static str = r#"{
+    "schema": "iglu:com.snowplowanalytics.factotum/factfile/jsonschema/1-0-0",
+    "data": {
+        "name": "defaults",
+        "defaults": {
+            "executor": "shell",
+            "onResult": { "terminateJobWithSuccess": [ 3 ], "continueJob": [ 0 ] },
+            "owner": "data-eng"
+        },
+        "tasks": [
+            { "name": "a", "command": "a", "arguments": [], "dependsOn": [] },
+            { "name": "b", "command": "b", "arguments": [], "dependsOn": [ "a" ],
+              "onResult": { "terminateJobWithSuccess": [], "continueJob": [ 0, 1 ] } }
+        ]
+    }
+}"#;
+
+fn apply(json: &str) -> Result<Option<Json>, String> {
+    let mut factfile = Json::from_str(json).unwrap();
+    apply_defaults(&mut factfile).map(|applied| if applied { Some(factfile) } else { None })
+}
+
+fn task<'a>(factfile: &'a Json, idx: usize) -> &'a Json {
+    &factfile.find_path(&["data", "tasks"]).unwrap().as_array().unwrap()[idx]
+}
+
+#[test]
+fn defaults_fill_in_tasks() {
+    let applied = apply(FACTFILE).unwrap().unwrap();
+
+    assert_eq!(applied.find_path(&["data", "defaults"]), None);
+    assert_eq!(task(&applied, 0).find("executor"),
+               Some(&Json::String("shell".to_string())));
+    assert_eq!(task(&applied, 0).find_path(&["onResult", "terminateJobWithSuccess"]),
+               Some(&Json::from_str("[3]").unwrap()));
+    assert_eq!(task(&applied, 1).find("owner"),
+               Some(&Json::String("data-eng".to_string())));
+}
+
+#[test]
+fn tasks_override_defaults() {
+    let applied = apply(FACTFILE).unwrap().unwrap();
+    assert_eq!(task(&applied, 1).find_path(&["onResult", "continueJob"]),
+               Some(&Json::from_str("[0, 1]").unwrap()));
+}
+
+#[test]
+fn factfiles_without_defaults_are_left_alone() {
+    assert_eq!(apply("{\"data\": {\"tasks\": []}}"), Ok(None));
+    assert_eq!(apply("[]"), Ok(None));
+}
+
+#[test]
+fn bad_defaults_are_rejected() {
+    assert_eq!(apply("{\"data\": {\"defaults\": [], \"tasks\": []}}"),
+               Err("'defaults' must be an object of task fields".to_string()));
+    assert!(apply("{\"data\": {\"defaults\": {\"name\": \"x\"}, \"tasks\": []}}")
+        .unwrap_err()
+        .starts_with("'defaults' can't set 'name', it must be one of executor, command"));
+}
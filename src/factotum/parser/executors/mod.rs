@@ -0,0 +1,271 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::assertion;
+use factotum::batch;
+use factotum::dbt;
+use factotum::emr;
+use factotum::expression;
+use factotum::gcp;
+use factotum::executor::BARRIER_EXECUTOR;
+use factotum::generator;
+use factotum::nested;
+use factotum::spark;
+use factotum::sql;
+use factotum::waitfor;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+
+// a task as its executor sees it once it's been templated
+pub struct ExecutorTask<'a> {
+    pub name: &'a str,
+    pub command: &'a str,
+    pub arguments: &'a Vec<String>,
+    pub continue_job: &'a Vec<i32>,
+    // without a run-time environment nothing's templated, so "{{ min_rows }}" may
+    // stand in for a value that can't be checked
+    pub templated: bool,
+}
+
+pub type Validate = fn(&ExecutorTask) -> Result<(), String>;
+
+pub const SHELL_EXECUTOR: &'static str = "shell";
+
+// every executor a task can use, with the checks its tasks must pass when the factfile's
+// parsed (rather than failing hours into a run)
+pub const EXECUTORS: &'static [(&'static str, Validate)] = &[
+    (SHELL_EXECUTOR, validate_command),
+    (generator::EXECUTOR, validate_command),
+    (waitfor::EXECUTOR, validate_wait),
+    (nested::EXECUTOR, validate_nested),
+    (assertion::EXECUTOR, validate_assertion),
+    (BARRIER_EXECUTOR, validate_barrier),
+    (emr::EXECUTOR, validate_emr),
+    (batch::EXECUTOR, validate_batch),
+    (gcp::CLOUD_RUN_EXECUTOR, validate_cloud_run),
+    (gcp::CLOUD_BATCH_EXECUTOR, validate_cloud_batch),
+    (dbt::EXECUTOR, validate_dbt),
+    (sql::EXECUTOR, validate_sql),
+    (spark::EXECUTOR, validate_spark),
+];
+
+fn executor_names() -> String {
+    EXECUTORS.iter().map(|&(name, _)| name).collect::<Vec<_>>().join(", ")
+}
+
+// the arguments shared by every task using an executor, from the config file's [executors]
+// and then the factfile's "executors", are put ahead of each task's own (so a task can still
+// override them), and the factfile's are then dropped; false if there weren't any
+pub fn apply_shared_settings(factfile: &mut Json,
+                             configured: &BTreeMap<String, Vec<String>>)
+                             -> Result<bool, String> {
+    let data = match factfile.as_object_mut().and_then(|f| f.get_mut("data")) {
+        Some(&mut Json::Object(ref mut data)) => data,
+        _ => return Ok(false),
+    };
+
+    let mut shared = configured.clone();
+    match data.remove("executors") {
+        Some(Json::Object(executors)) => {
+            for (executor, arguments) in executors.into_iter() {
+                let arguments = try!(arguments.as_array()
+                    .and_then(|args| args.iter().map(|a| a.as_string()).collect::<Option<Vec<_>>>())
+                    .ok_or(format!("'executors' must give '{}' an array of arguments", executor)));
+                shared.entry(executor)
+                    .or_insert(vec![])
+                    .extend(arguments.into_iter().map(|a| a.to_string()));
+            }
+        }
+        Some(_) => return Err("'executors' must be an object of executors' arguments".to_string()),
+        None => {}
+    }
+    if shared.is_empty() {
+        return Ok(false);
+    }
+
+    for executor in shared.keys() {
+        if !EXECUTORS.iter().any(|&(name, _)| name == executor) {
+            return Err(format!("there are shared arguments for the unknown executor '{}', it \
+                                must be one of {}",
+                               executor,
+                               executor_names()));
+        }
+    }
+
+    if let Some(&mut Json::Array(ref mut tasks)) = data.get_mut("tasks") {
+        for task in tasks.iter_mut() {
+            if let Json::Object(ref mut task) = *task {
+                let arguments = match task.get("executor")
+                    .and_then(|e| e.as_string())
+                    .and_then(|e| shared.get(e)) {
+                    Some(arguments) => arguments,
+                    None => continue,
+                };
+                let own = match task.remove("arguments") {
+                    Some(Json::Array(own)) => own,
+                    Some(other) => {
+                        // left for the schema validation to report
+                        task.insert("arguments".to_string(), other);
+                        continue;
+                    }
+                    None => vec![],
+                };
+                let mut merged = arguments.iter()
+                    .map(|a| Json::String(a.clone()))
+                    .collect::<Vec<_>>();
+                merged.extend(own);
+                task.insert("arguments".to_string(), Json::Array(merged));
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+pub fn validate(executor: &str, task: &ExecutorTask) -> Result<(), String> {
+    match EXECUTORS.iter().find(|&&(name, _)| name == executor) {
+        Some(&(_, validate)) => validate(task),
+        None => {
+            Err(format!("the task '{}' uses the unknown executor '{}', it must be one of {}",
+                        task.name,
+                        executor,
+                        executor_names()))
+        }
+    }
+}
+
+fn validate_command(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        Err(format!("the task '{}' has no command to run", task.name))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_wait(task: &ExecutorTask) -> Result<(), String> {
+    waitfor::parse_wait_condition(task.command)
+        .and_then(|_| waitfor::parse_wait_options(task.arguments))
+        .map(|_| ())
+        .map_err(|e| format!("the task '{}' has an invalid wait: {}", task.name, e))
+}
+
+fn validate_nested(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no factfile to run", task.name));
+    }
+    nested::parse_overrides(task.arguments)
+        .map(|_| ())
+        .map_err(|e| format!("the task '{}' has an invalid override: {}", task.name, e))
+}
+
+fn validate_barrier(task: &ExecutorTask) -> Result<(), String> {
+    if !task.command.trim().is_empty() || !task.arguments.is_empty() {
+        return Err(format!("the task '{}' is a barrier, so it can't have a command or arguments",
+                           task.name));
+    }
+    if !task.continue_job.contains(&0) {
+        return Err(format!("the task '{}' is a barrier, so it must continue the job on 0",
+                           task.name));
+    }
+    Ok(())
+}
+
+fn validate_assertion(task: &ExecutorTask) -> Result<(), String> {
+    if task.arguments.is_empty() {
+        return Err(format!("the task '{}' has no assertions (they're given as its arguments)",
+                           task.name));
+    }
+    for arg in task.arguments.iter().filter(|_| task.templated) {
+        try!(expression::parse(arg)
+            .map_err(|e| format!("the task '{}' has an invalid assertion: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_emr(task: &ExecutorTask) -> Result<(), String> {
+    try!(emr::parse_cluster(task.command)
+        .map_err(|e| format!("the task '{}' has an invalid cluster: {}", task.name, e)));
+    if task.templated {
+        try!(emr::parse_step(task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid step: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_batch(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no job definition to submit", task.name));
+    }
+    if task.templated {
+        try!(batch::parse_job(task.command, task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid job: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_cloud_run(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no Cloud Run job to run", task.name));
+    }
+    if task.templated {
+        try!(gcp::parse_cloud_run_job(task.command, task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid job: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_cloud_batch(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no Cloud Batch job spec to submit", task.name));
+    }
+    if task.templated {
+        try!(gcp::parse_cloud_batch_job(task.command, task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid job: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_dbt(task: &ExecutorTask) -> Result<(), String> {
+    let arguments = if task.templated { task.arguments.clone() } else { vec![] };
+    dbt::parse_invocation(task.command, &arguments)
+        .map(|_| ())
+        .map_err(|e| format!("the task '{}' has an invalid dbt invocation: {}", task.name, e))
+}
+
+fn validate_sql(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no connection to run its statement against",
+                           task.name));
+    }
+    if task.templated {
+        try!(sql::parse_sql_task(task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid statement: {}", task.name, e)));
+    }
+    Ok(())
+}
+
+fn validate_spark(task: &ExecutorTask) -> Result<(), String> {
+    if task.command.trim().is_empty() {
+        return Err(format!("the task '{}' has no application to submit", task.name));
+    }
+    if task.templated {
+        try!(spark::parse_spark_app(task.command, task.arguments)
+            .map_err(|e| format!("the task '{}' has an invalid submission: {}", task.name, e)));
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+
+use super::*;
+
+fn check(executor: &str, command: &str, arguments: Vec<&str>) -> Result<(), String> {
+    let arguments = arguments.iter().map(|a| a.to_string()).collect();
+    validate(executor,
+             &ExecutorTask {
+                 name: "task",
+                 command: command,
+                 arguments: &arguments,
+                 continue_job: &vec![0],
+                 templated: true,
+             })
+}
+
+#[test]
+fn known_executors_are_validated() {
+    assert_eq!(check("shell", "echo", vec!["hi"]), Ok(()));
+    assert_eq!(check("shell", " ", vec![]),
+               Err("the task 'task' has no command to run".to_string()));
+    assert_eq!(check("generator", "", vec![]),
+               Err("the task 'task' has no command to run".to_string()));
+    assert_eq!(check("factfile", "", vec![]),
+               Err("the task 'task' has no factfile to run".to_string()));
+    assert_eq!(check("barrier", "", vec![]), Ok(()));
+    assert_eq!(check("barrier", "echo", vec![]),
+               Err("the task 'task' is a barrier, so it can't have a command or arguments"
+                   .to_string()));
+    assert_eq!(check("assert", "", vec![]),
+               Err("the task 'task' has no assertions (they're given as its arguments)"
+                   .to_string()));
+    assert!(check("wait", "tcp://db.internal", vec![])
+        .unwrap_err()
+        .starts_with("the task 'task' has an invalid wait"));
+    assert_eq!(check("emr", "j-1", vec!["jar=command-runner.jar"]), Ok(()));
+    assert_eq!(check("emr", "", vec!["jar=command-runner.jar"]),
+               Err("the task 'task' has an invalid cluster: there is no cluster id or cluster \
+                    spec to run the step on"
+                   .to_string()));
+    assert!(check("emr", "j-1", vec![])
+        .unwrap_err()
+        .starts_with("the task 'task' has an invalid step"));
+    assert_eq!(check("aws-batch", "enrich:3", vec!["queue=heavy"]), Ok(()));
+    assert_eq!(check("aws-batch", "enrich:3", vec![]),
+               Err("the task 'task' has an invalid job: the job has no queue to be submitted to \
+                    (e.g. queue=heavy)"
+                   .to_string()));
+    assert_eq!(check("cloud-run", "load", vec!["project=acme", "region=eu"]), Ok(()));
+    assert_eq!(check("cloud-run", "", vec![]),
+               Err("the task 'task' has no Cloud Run job to run".to_string()));
+    assert_eq!(check("cloud-batch", "load.json", vec!["project=acme", "region=eu"]), Ok(()));
+    assert!(check("cloud-batch", "load.json", vec!["project=acme"])
+        .unwrap_err()
+        .starts_with("the task 'task' has an invalid job"));
+    assert_eq!(check("dbt", "run", vec!["select=orders"]), Ok(()));
+    assert!(check("dbt", "compile", vec![])
+        .unwrap_err()
+        .starts_with("the task 'task' has an invalid dbt invocation"));
+    assert_eq!(check("sql", "warehouse", vec!["query=SELECT 1"]), Ok(()));
+    assert_eq!(check("sql", "", vec!["query=SELECT 1"]),
+               Err("the task 'task' has no connection to run its statement against".to_string()));
+    assert_eq!(check("sql", "warehouse", vec![]),
+               Err("the task 'task' has an invalid statement: there's no query or script to run"
+                   .to_string()));
+    assert_eq!(check("spark", "enrich.jar", vec!["master=yarn", "deploy-mode=cluster"]),
+               Ok(()));
+    assert!(check("spark", "enrich.jar", vec!["deploy-mode=cluster"])
+        .unwrap_err()
+        .starts_with("the task 'task' has an invalid submission"));
+}
+
+#[test]
+fn unknown_executors_are_rejected() {
+    assert_eq!(check("docker", "run", vec![]),
+               Err("the task 'task' uses the unknown executor 'docker', it must be one of \
+                    shell, generator, wait, factfile, assert, barrier, emr, aws-batch, cloud-run, \
+                    cloud-batch, dbt, sql, spark"
+                   .to_string()));
+}
+
+#[test]
+fn untemplated_assertions_are_not_parsed() {
+    let arguments = vec!["{{ min_rows }}".to_string()];
+    let task = ExecutorTask {
+        name: "task",
+        command: "",
+        arguments: &arguments,
+        continue_job: &vec![0],
+        templated: false,
+    };
+    assert_eq!(validate("assert", &task), Ok(()));
+}
+
+fn factfile(data: &str) -> Json {
+    Json::from_str(&format!("{{\"schema\": \"iglu:x/y/jsonschema/1-0-0\", \"data\": {}}}", data))
+        .unwrap()
+}
+
+fn task_arguments(factfile: &Json, idx: usize) -> String {
+    factfile.find_path(&["data", "tasks"])
+        .and_then(|t| t.as_array())
+        .map(|t| t[idx].find("arguments").map(|a| a.to_string()).unwrap_or_default())
+        .unwrap()
+}
+
+#[test]
+fn shared_settings_are_put_ahead_of_each_tasks_arguments() {
+    let mut ff = factfile(r#"{"executors": {"spark": ["master=yarn", "deploy-mode=cluster"]},
+                             "tasks": [{"name": "a", "executor": "spark", "arguments": ["arg=1"]},
+                                       {"name": "b", "executor": "shell", "arguments": []},
+                                       {"name": "c", "executor": "spark"}]}"#);
+    let mut configured = BTreeMap::new();
+    configured.insert("spark".to_string(), vec!["conf=spark.ui.enabled=false".to_string()]);
+
+    assert_eq!(apply_shared_settings(&mut ff, &configured), Ok(true));
+    assert_eq!(task_arguments(&ff, 0),
+               "[\"conf=spark.ui.enabled=false\",\"master=yarn\",\"deploy-mode=cluster\",\
+                \"arg=1\"]");
+    assert_eq!(task_arguments(&ff, 1), "[]");
+    assert_eq!(task_arguments(&ff, 2),
+               "[\"conf=spark.ui.enabled=false\",\"master=yarn\",\"deploy-mode=cluster\"]");
+    assert!(ff.find_path(&["data", "executors"]).is_none());
+}
+
+#[test]
+fn without_shared_settings_nothing_changes() {
+    let mut ff = factfile(r#"{"tasks": [{"name": "a", "executor": "spark", "arguments": []}]}"#);
+    assert_eq!(apply_shared_settings(&mut ff, &BTreeMap::new()), Ok(false));
+}
+
+#[test]
+fn shared_settings_must_be_for_known_executors() {
+    let mut ff = factfile(r#"{"executors": {"docker": ["registry=acme"]}, "tasks": []}"#);
+    assert!(apply_shared_settings(&mut ff, &BTreeMap::new())
+        .unwrap_err()
+        .starts_with("there are shared arguments for the unknown executor 'docker'"));
+
+    let mut ff = factfile(r#"{"executors": {"spark": "master=yarn"}, "tasks": []}"#);
+    assert_eq!(apply_shared_settings(&mut ff, &BTreeMap::new()),
+               Err("'executors' must give 'spark' an array of arguments".to_string()));
+
+    let mut ff = factfile(r#"{"executors": [], "tasks": []}"#);
+    assert_eq!(apply_shared_settings(&mut ff, &BTreeMap::new()),
+               Err("'executors' must be an object of executors' arguments".to_string()));
+}
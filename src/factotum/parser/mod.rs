@@ -15,45 +15,272 @@
 #[cfg(test)]
 mod tests;
 mod templater;
+mod variables;
+mod defaults;
+mod tasktemplates;
+mod references;
+pub mod executors;
 pub mod schemavalidator;
 
 use std::io::prelude::*;
 use std::fs::File;
 use rustc_serialize::json::{self, Json};
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use std::collections::BTreeMap;
+use std::time::Duration;
 use super::factfile;
+use factotum::retry;
+use factotum::sla;
+use factotum::waitfor;
+use factotum::assertion;
+use factotum::expression;
+use factotum::config::ExecutorSettings;
+use factotum::emr;
+use factotum::batch;
+use factotum::gcp;
+use factotum::dbt;
+use factotum::spark;
+use factotum::sql;
+use factotum::nested;
+use factotum::workspace;
+use factotum::constraint::Constraints;
+use factotum::timewindow;
+use factotum::executor::BARRIER_EXECUTOR;
+use factotum::diagnostics;
+use factotum::errors::{ErrorKind, FactotumError};
 
 use std::error::Error;
 
+pub use self::variables::computed_command;
+
+// the variable each instance of a forEach task is given its item as
+pub const FOR_EACH_ITEM: &'static str = "item";
+
 pub struct TaskReturnCodeMapping {
     pub continue_job: Vec<i32>,
     pub terminate_early: Vec<i32>,
 }
 
+// a task's return codes given at run time, replacing those in the factfile
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TaskResultOverride {
+    pub continue_job: Option<Vec<i32>>,
+    pub terminate_early: Option<Vec<i32>>,
+}
+
 pub enum OverrideResultMappings {
     All(TaskReturnCodeMapping),
+    Tasks(BTreeMap<String, TaskResultOverride>),
     None,
 }
 
+// overrides are given as <task>:continue=<codes> or <task>:terminate=<codes>, e.g.
+// "load:continue=0,3" - a task can be given both
+pub fn parse_result_overrides(specs: &Vec<String>)
+                              -> Result<BTreeMap<String, TaskResultOverride>, String> {
+    let mut overrides: BTreeMap<String, TaskResultOverride> = BTreeMap::new();
+
+    for spec in specs.iter() {
+        let malformed = || {
+            format!("the override '{}' must be given as <task>:continue=<codes> or \
+                     <task>:terminate=<codes>",
+                    spec)
+        };
+        let (task, mapping) = match spec.rfind(':') {
+            Some(idx) if idx > 0 => (&spec[..idx], &spec[idx + 1..]),
+            _ => return Err(malformed()),
+        };
+        let (action, codes) = match mapping.find('=') {
+            Some(idx) => (&mapping[..idx], &mapping[idx + 1..]),
+            None => return Err(malformed()),
+        };
+
+        let mut parsed = vec![];
+        for code in codes.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            parsed.push(try!(code.parse::<i32>().map_err(|_| {
+                format!("the override '{}' has an invalid return code '{}'", spec, code)
+            })));
+        }
+        if parsed.is_empty() {
+            return Err(format!("the override '{}' has no return codes", spec));
+        }
+
+        let task_override = overrides.entry(task.to_string())
+            .or_insert(TaskResultOverride::default());
+        match action {
+            "continue" => task_override.continue_job = Some(parsed),
+            "terminate" => task_override.terminate_early = Some(parsed),
+            other => {
+                return Err(format!("the override '{}' has an unknown action '{}', it must be \
+                                    continue or terminate",
+                                   spec,
+                                   other))
+            }
+        }
+    }
+
+    Ok(overrides)
+}
+
+// a task's return codes, after any overrides - a code given for one action is taken
+// from the other, so "continue=0,3" moves 3 out of terminateJobWithSuccess
+fn task_result_mappings(overrides: &OverrideResultMappings,
+                        names: &[&str],
+                        on_result: &FactfileTaskResultFormat)
+                        -> (Vec<i32>, Vec<i32>) {
+    match *overrides {
+        OverrideResultMappings::All(ref with_value) => {
+            (with_value.terminate_early.clone(), with_value.continue_job.clone())
+        }
+        OverrideResultMappings::Tasks(ref tasks) => {
+            let mut terminate = on_result.terminateJobWithSuccess.clone();
+            let mut continue_job = on_result.continueJob.clone();
+            if let Some(task_override) = names.iter().filter_map(|n| tasks.get(*n)).next() {
+                if let Some(ref codes) = task_override.continue_job {
+                    continue_job = codes.clone();
+                    terminate.retain(|c| !codes.contains(c));
+                }
+                if let Some(ref codes) = task_override.terminate_early {
+                    terminate = codes.clone();
+                    if task_override.continue_job.is_none() {
+                        continue_job.retain(|c| !codes.contains(c));
+                    }
+                }
+            }
+            (terminate, continue_job)
+        }
+        OverrideResultMappings::None => {
+            (on_result.terminateJobWithSuccess.clone(), on_result.continueJob.clone())
+        }
+    }
+}
+
+// variables written as $(command) are left as they're written, so inspecting a factfile
+// (or a dry run) never runs anything; parse_for_run computes them
 pub fn parse(factfile: &str,
              env: Option<Json>,
-             overrides: OverrideResultMappings)
-             -> Result<factfile::Factfile, String> {
+             overrides: OverrideResultMappings,
+             configured: &ExecutorSettings)
+             -> Result<factfile::Factfile, FactotumError> {
+    parse_file(factfile, env, overrides, configured, false)
+}
+
+pub fn parse_for_run(factfile: &str,
+                     env: Option<Json>,
+                     overrides: OverrideResultMappings,
+                     configured: &ExecutorSettings)
+                     -> Result<factfile::Factfile, FactotumError> {
+    parse_file(factfile, env, overrides, configured, true)
+}
+
+fn parse_file(factfile: &str,
+              env: Option<Json>,
+              overrides: OverrideResultMappings,
+              configured: &ExecutorSettings,
+              compute: bool)
+              -> Result<factfile::Factfile, FactotumError> {
     info!("reading {} into memory", factfile);
-    let mut fh = try!(File::open(&factfile)
-        .map_err(|e| format!("Couldn't open '{}' for reading: {}", factfile, e)));
+    let mut fh = try!(File::open(&factfile).map_err(|e| {
+        FactotumError::new(ErrorKind::Io,
+                           format!("Couldn't open '{}' for reading: {}", factfile, e))
+    }));
     let mut f = String::new();
-    try!(fh.read_to_string(&mut f).map_err(|e| format!("Couldn't read '{}': {}", factfile, e)));
+    try!(fh.read_to_string(&mut f).map_err(|e| {
+        FactotumError::new(ErrorKind::Io, format!("Couldn't read '{}': {}", factfile, e))
+    }));
     info!("file {} was read successfully!", factfile);
 
-    parse_str(&f, factfile, env, overrides)
+    parse_str(&f, factfile, env, overrides, configured, compute)
+}
+
+// references, task templates, defaults and executors' shared arguments (from the config
+// file, loaded by the caller) are written out into each task, so the factfile is checked
+// and run as if it had been written in full; a factfile without any of them is left as it is
+fn expand(json: &str, configured: &ExecutorSettings) -> Result<Option<String>, String> {
+    let mut factfile = match Json::from_str(json) {
+        Ok(factfile) => factfile,
+        // invalid JSON is reported by the schema validation
+        Err(_) => return Ok(None),
+    };
+
+    let referenced = try!(references::resolve_references(&mut factfile));
+    let templated = try!(tasktemplates::apply_templates(&mut factfile));
+    let defaulted = try!(defaults::apply_defaults(&mut factfile));
+    let shared = try!(executors::apply_shared_settings(&mut factfile, configured));
+
+    if referenced || templated || defaulted || shared {
+        Ok(Some(factfile.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+// each template's checked where it's written, so a malformed one is reported against
+// its own field (in the same form as a schema violation) rather than the whole factfile
+fn check_templates(json: &str) -> Result<(), String> {
+    fn check(value: &Json, pointer: &str, problems: &mut Vec<String>) {
+        match *value {
+            Json::String(ref s) => {
+                if let Err(e) = templater::check_template(s) {
+                    problems.push(format!("'{}' - {}", pointer, e));
+                }
+            }
+            Json::Array(ref items) => {
+                for (idx, item) in items.iter().enumerate() {
+                    check(item, &format!("{}/{}", pointer, idx), problems);
+                }
+            }
+            Json::Object(ref fields) => {
+                for (key, field) in fields.iter() {
+                    let key = key.replace("~", "~0").replace("/", "~1");
+                    check(field, &format!("{}/{}", pointer, key), problems);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut problems = vec![];
+    if let Ok(factfile) = Json::from_str(json) {
+        check(&factfile, "", &mut problems);
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join("\n"))
+    }
+}
+
+// the factfile as it's checked and run, e.g. for finding the variables it uses
+pub fn expanded_source(json: &str, configured: &ExecutorSettings) -> Result<String, String> {
+    expand(json, configured).map(|expanded| expanded.unwrap_or(json.to_string()))
+}
+
+// each problem's given with where it is in the factfile as written
+fn invalid_factfile(kind: ErrorKind,
+                    source: &str,
+                    from_filename: &str,
+                    msg: &str)
+                    -> FactotumError {
+    FactotumError::new(kind,
+                       format!("'{}' is not a valid factotum factfile: {}",
+                               from_filename,
+                               diagnostics::locate_problems(source, msg)))
 }
 
 fn parse_str(json: &str,
              from_filename: &str,
              env: Option<Json>,
-             overrides: OverrideResultMappings)
-             -> Result<factfile::Factfile, String> {
+             overrides: OverrideResultMappings,
+             configured: &ExecutorSettings,
+             compute: bool)
+             -> Result<factfile::Factfile, FactotumError> {
     info!("parsing json:\n{}", json);
+    let source = json;
+
+    let expanded = try!(expand(json, configured)
+        .map_err(|msg| invalid_factfile(ErrorKind::Validation, source, from_filename, &msg)));
+    let json = expanded.as_ref().map(AsRef::as_ref).unwrap_or(json);
 
     let validation_result = schemavalidator::validate_against_factfile_schema(json);
 
@@ -62,18 +289,20 @@ fn parse_str(json: &str,
             info!("'{}' matches the factotum schema definition!",
                   from_filename);
 
-            parse_valid_json(json, env, overrides).map_err(|msg| {
-                format!("'{}' is not a valid factotum factfile: {}",
-                        from_filename,
-                        msg)
-            })
+            try!(check_templates(json)
+                .map_err(|msg| invalid_factfile(ErrorKind::Template, source, from_filename, &msg)));
+            parse_valid_json(json, env, overrides, compute)
+                .map_err(|msg| invalid_factfile(ErrorKind::Validation, source, from_filename, &msg))
         }
         Err(msg) => {
             info!("'{}' failed to match factfile schema definition!",
                   from_filename);
-            Err(format!("'{}' is not a valid factotum factfile: {}",
-                        from_filename,
-                        msg))
+            let kind = if msg.starts_with("invalid JSON") {
+                ErrorKind::Syntax
+            } else {
+                ErrorKind::Validation
+            };
+            Err(invalid_factfile(kind, source, from_filename, &msg))
         }
     }
 }
@@ -86,13 +315,93 @@ struct SelfDescribingJson {
     data: FactfileFormat,
 }
 
-#[derive(RustcEncodable, RustcDecodable)]
+#[derive(RustcDecodable)]
 struct FactfileFormat {
     name: String,
+    variables: Option<BTreeMap<String, String>>,
+    declarations: Option<BTreeMap<String, FactfileDeclarationFormat>>,
+    sla: Option<String>,
     tasks: Vec<FactfileTaskFormat>,
 }
 
-#[derive(RustcEncodable, RustcDecodable)]
+impl Encodable for FactfileFormat {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("FactfileFormat", 5, |s| {
+            try!(s.emit_struct_field("name", 0, |s| self.name.encode(s)));
+            if let Some(ref variables) = self.variables {
+                try!(s.emit_struct_field("variables", 1, |s| variables.encode(s)));
+            }
+            if let Some(ref declarations) = self.declarations {
+                try!(s.emit_struct_field("declarations", 2, |s| declarations.encode(s)));
+            }
+            if let Some(ref sla) = self.sla {
+                try!(s.emit_struct_field("sla", 3, |s| sla.encode(s)));
+            }
+            try!(s.emit_struct_field("tasks", 4, |s| self.tasks.encode(s)));
+            Ok(())
+        })
+    }
+}
+
+// "type" can't be used as a field name, so this is (de)serialised by hand
+struct FactfileDeclarationFormat {
+    var_type: String,
+    required: Option<bool>,
+    values: Option<Vec<String>>,
+}
+
+impl Decodable for FactfileDeclarationFormat {
+    fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+        d.read_struct("FactfileDeclarationFormat", 3, |d| {
+            Ok(FactfileDeclarationFormat {
+                var_type: try!(d.read_struct_field("type", 0, Decodable::decode)),
+                required: try!(d.read_struct_field("required", 1, Decodable::decode)),
+                values: try!(d.read_struct_field("values", 2, Decodable::decode)),
+            })
+        })
+    }
+}
+
+impl Encodable for FactfileDeclarationFormat {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("FactfileDeclarationFormat", 3, |s| {
+            try!(s.emit_struct_field("type", 0, |s| self.var_type.encode(s)));
+            if let Some(ref required) = self.required {
+                try!(s.emit_struct_field("required", 1, |s| required.encode(s)));
+            }
+            if let Some(ref values) = self.values {
+                try!(s.emit_struct_field("values", 2, |s| values.encode(s)));
+            }
+            Ok(())
+        })
+    }
+}
+
+impl FactfileDeclarationFormat {
+    fn to_declaration(&self, name: &str) -> Result<variables::Declaration, String> {
+        let var_type = match (self.var_type.as_ref(), self.values.as_ref()) {
+            ("string", _) => variables::VariableType::String,
+            ("int", _) => variables::VariableType::Int,
+            ("bool", _) => variables::VariableType::Bool,
+            ("enum", Some(values)) if values.len() > 0 => {
+                variables::VariableType::Enum(values.clone())
+            }
+            ("enum", _) => {
+                return Err(format!("the variable '{}' is declared as an enum but has no values",
+                                   name))
+            }
+            (other, _) => {
+                return Err(format!("the variable '{}' has an unknown type '{}'", name, other))
+            }
+        };
+        Ok(variables::Declaration {
+            var_type: var_type,
+            required: self.required.unwrap_or(false),
+        })
+    }
+}
+
+#[derive(RustcDecodable)]
 #[allow(non_snake_case)]
 struct FactfileTaskFormat {
     name: String,
@@ -101,6 +410,122 @@ struct FactfileTaskFormat {
     arguments: Vec<String>,
     dependsOn: Vec<String>,
     onResult: FactfileTaskResultFormat,
+    skipIfFailedLast: Option<u32>,
+    skipIfFailureRate: Option<FactfileTaskFailureRateFormat>,
+    expectedDuration: Option<String>,
+    idleTimeout: Option<String>,
+    requires: Option<Vec<String>>,
+    forEach: Option<String>,
+    group: Option<String>,
+    enabled: Option<bool>,
+    description: Option<String>,
+    owner: Option<String>,
+    stdin: Option<String>,
+    stdinFile: Option<String>,
+    shell: Option<String>,
+    escape: Option<String>,
+    sandbox: Option<FactfileSandboxFormat>,
+    workspace: Option<bool>,
+    cache: Option<FactfileCacheFormat>,
+    produces: Option<Vec<String>>,
+    tty: Option<bool>,
+    constraints: Option<FactfileConstraintsFormat>,
+    vars: Option<BTreeMap<String, String>>,
+    whenUpstreamSkipped: Option<String>,
+    when: Option<String>,
+}
+
+// optional fields are left out (rather than written as null) so the compact
+// form of a factfile - and the job reference derived from it - only changes
+// when the factfile uses them
+impl Encodable for FactfileTaskFormat {
+    fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+        s.emit_struct("FactfileTaskFormat", 29, |s| {
+            try!(s.emit_struct_field("name", 0, |s| self.name.encode(s)));
+            try!(s.emit_struct_field("executor", 1, |s| self.executor.encode(s)));
+            try!(s.emit_struct_field("command", 2, |s| self.command.encode(s)));
+            try!(s.emit_struct_field("arguments", 3, |s| self.arguments.encode(s)));
+            try!(s.emit_struct_field("dependsOn", 4, |s| self.dependsOn.encode(s)));
+            try!(s.emit_struct_field("onResult", 5, |s| self.onResult.encode(s)));
+            if let Some(ref failed_last) = self.skipIfFailedLast {
+                try!(s.emit_struct_field("skipIfFailedLast", 6, |s| failed_last.encode(s)));
+            }
+            if let Some(ref failure_rate) = self.skipIfFailureRate {
+                try!(s.emit_struct_field("skipIfFailureRate", 7, |s| failure_rate.encode(s)));
+            }
+            if let Some(ref expected_duration) = self.expectedDuration {
+                try!(s.emit_struct_field("expectedDuration", 8, |s| expected_duration.encode(s)));
+            }
+            if let Some(ref requires) = self.requires {
+                try!(s.emit_struct_field("requires", 9, |s| requires.encode(s)));
+            }
+            if let Some(ref for_each) = self.forEach {
+                try!(s.emit_struct_field("forEach", 10, |s| for_each.encode(s)));
+            }
+            if let Some(ref group) = self.group {
+                try!(s.emit_struct_field("group", 11, |s| group.encode(s)));
+            }
+            if let Some(ref enabled) = self.enabled {
+                try!(s.emit_struct_field("enabled", 12, |s| enabled.encode(s)));
+            }
+            if let Some(ref description) = self.description {
+                try!(s.emit_struct_field("description", 13, |s| description.encode(s)));
+            }
+            if let Some(ref owner) = self.owner {
+                try!(s.emit_struct_field("owner", 14, |s| owner.encode(s)));
+            }
+            if let Some(ref stdin) = self.stdin {
+                try!(s.emit_struct_field("stdin", 15, |s| stdin.encode(s)));
+            }
+            if let Some(ref stdin_file) = self.stdinFile {
+                try!(s.emit_struct_field("stdinFile", 16, |s| stdin_file.encode(s)));
+            }
+            if let Some(ref shell) = self.shell {
+                try!(s.emit_struct_field("shell", 17, |s| shell.encode(s)));
+            }
+            if let Some(ref escape) = self.escape {
+                try!(s.emit_struct_field("escape", 18, |s| escape.encode(s)));
+            }
+            if let Some(ref sandbox) = self.sandbox {
+                try!(s.emit_struct_field("sandbox", 19, |s| sandbox.encode(s)));
+            }
+            if let Some(ref workspace) = self.workspace {
+                try!(s.emit_struct_field("workspace", 20, |s| workspace.encode(s)));
+            }
+            if let Some(ref cache) = self.cache {
+                try!(s.emit_struct_field("cache", 21, |s| cache.encode(s)));
+            }
+            if let Some(ref produces) = self.produces {
+                try!(s.emit_struct_field("produces", 22, |s| produces.encode(s)));
+            }
+            if let Some(ref tty) = self.tty {
+                try!(s.emit_struct_field("tty", 23, |s| tty.encode(s)));
+            }
+            if let Some(ref constraints) = self.constraints {
+                try!(s.emit_struct_field("constraints", 24, |s| constraints.encode(s)));
+            }
+            if let Some(ref vars) = self.vars {
+                try!(s.emit_struct_field("vars", 25, |s| vars.encode(s)));
+            }
+            if let Some(ref when_skipped) = self.whenUpstreamSkipped {
+                try!(s.emit_struct_field("whenUpstreamSkipped", 26, |s| when_skipped.encode(s)));
+            }
+            if let Some(ref when) = self.when {
+                try!(s.emit_struct_field("when", 27, |s| when.encode(s)));
+            }
+            if let Some(ref idle_timeout) = self.idleTimeout {
+                try!(s.emit_struct_field("idleTimeout", 28, |s| idle_timeout.encode(s)));
+            }
+            Ok(())
+        })
+    }
+}
+
+#[derive(RustcEncodable, RustcDecodable, Clone)]
+#[allow(non_snake_case)]
+struct FactfileTaskFailureRateFormat {
+    threshold: f64,
+    overLastRuns: u32,
 }
 
 #[derive(RustcEncodable, RustcDecodable, Clone)]
@@ -110,15 +535,569 @@ struct FactfileTaskResultFormat {
     continueJob: Vec<i32>,
 }
 
+#[derive(RustcEncodable, RustcDecodable, Clone)]
+#[allow(non_snake_case)]
+struct FactfileSandboxFormat {
+    network: Option<bool>,
+    readOnlyRoot: Option<bool>,
+    writablePaths: Option<Vec<String>>,
+    seccomp: Option<bool>,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Clone)]
+#[allow(non_snake_case)]
+struct FactfileConstraintsFormat {
+    host: Option<String>,
+    window: Option<String>,
+    check: Option<String>,
+    whenUnmet: Option<String>,
+}
+
+#[derive(RustcEncodable, RustcDecodable, Clone)]
+struct FactfileCacheFormat {
+    inputs: Option<Vec<String>>,
+    variables: Option<Vec<String>>,
+}
+
+#[derive(RustcDecodable)]
+struct GeneratedTasksFormat {
+    tasks: Vec<FactfileTaskFormat>,
+}
+
+// the tasks a generator task printed, given as {"tasks": [...]} in the same form as a
+// factfile's tasks (they aren't templated)
+pub fn parse_generated_tasks(json: &str) -> Result<Vec<factfile::Task>, String> {
+    let generated: GeneratedTasksFormat = try!(json::decode(json.trim())
+        .map_err(|e| format!("the output isn't a list of tasks ({})", e)));
+
+    let mut tasks = vec![];
+    for file_task in generated.tasks.into_iter() {
+        if file_task.onResult.continueJob.len() == 0 {
+            return Err(format!("the task '{}' has no way to continue successfully.",
+                               file_task.name));
+        }
+        if file_task.onResult
+            .continueJob
+            .iter()
+            .any(|c| file_task.onResult.terminateJobWithSuccess.contains(c)) {
+            return Err(format!("the task '{}' has conflicting actions.", file_task.name));
+        }
+
+        try!(executors::validate(&file_task.executor,
+                                 &executors::ExecutorTask {
+                                     name: &file_task.name,
+                                     command: &file_task.command,
+                                     arguments: &file_task.arguments,
+                                     continue_job: &file_task.onResult.continueJob,
+                                     templated: true,
+                                 }));
+
+        let expected_duration = match file_task.expectedDuration {
+            Some(ref duration) => {
+                Some(try!(retry::parse_duration(duration).map_err(|e| {
+                    format!("the task '{}' has an invalid expectedDuration: {}",
+                            file_task.name,
+                            e)
+                })))
+            }
+            None => None,
+        };
+
+        let stdin = try!(task_input(&file_task.name,
+                                    &file_task.executor,
+                                    file_task.stdin,
+                                    file_task.stdinFile));
+        let shell = try!(task_shell(&file_task.name, &file_task.executor, &file_task.shell));
+        let tty = try!(task_tty(&file_task.name, &file_task.executor, file_task.tty));
+        let sandbox = try!(task_sandbox(&file_task.name,
+                                        &file_task.executor,
+                                        &file_task.sandbox,
+                                        &None));
+        let constraints = try!(task_constraints(&file_task.name, &file_task.constraints, &None));
+        let cache = try!(task_cache(&file_task.name, &file_task.cache, &None));
+        let produces = try!(task_produces(&file_task.name, &file_task.produces, &None));
+        let when_upstream_skipped =
+            try!(task_when_upstream_skipped(&file_task.name, &file_task.whenUpstreamSkipped));
+        let when = try!(task_when(&file_task.name, &file_task.when, &None, None));
+        let idle_timeout = try!(task_idle_timeout(&file_task.name, &file_task.idleTimeout));
+
+        tasks.push(factfile::Task {
+            name: file_task.name,
+            depends_on: file_task.dependsOn,
+            executor: file_task.executor,
+            command: file_task.command,
+            arguments: file_task.arguments,
+            on_result: factfile::OnResult {
+                terminate_job: file_task.onResult.terminateJobWithSuccess,
+                continue_job: file_task.onResult.continueJob,
+            },
+            circuit_breaker: factfile::CircuitBreaker::default(),
+            expected_duration: expected_duration,
+            idle_timeout: idle_timeout,
+            requires: file_task.requires.unwrap_or(vec![]),
+            produces: produces,
+            disabled: file_task.enabled == Some(false),
+            stdin: stdin,
+            shell: shell,
+            tty: tty,
+            constraints: constraints,
+            sandbox: sandbox,
+            workspace: None,
+            cache: cache,
+            when_upstream_skipped: when_upstream_skipped,
+            when: when,
+            description: file_task.description,
+            owner: file_task.owner,
+        });
+    }
+
+    Ok(tasks)
+}
+
+// a task reads its standard input from either the stdin text or the stdinFile
+fn task_input(task_name: &str,
+              executor: &str,
+              stdin: Option<String>,
+              stdin_file: Option<String>)
+              -> Result<Option<factfile::TaskInput>, String> {
+    let input = match (stdin, stdin_file) {
+        (Some(_), Some(_)) => {
+            return Err(format!("the task '{}' has both stdin and a stdinFile, only one can be \
+                                given",
+                               task_name))
+        }
+        (Some(text), None) => Some(factfile::TaskInput::Text(text)),
+        (None, Some(path)) => Some(factfile::TaskInput::File(path)),
+        (None, None) => None,
+    };
+
+    if input.is_some() && is_builtin_executor(executor) {
+        return Err(format!("the task '{}' uses the '{}' executor, which can't be given stdin",
+                           task_name,
+                           executor));
+    }
+
+    Ok(input)
+}
+
+// built-in executors run factotum itself rather than the task's command
+fn is_builtin_executor(executor: &str) -> bool {
+    [waitfor::EXECUTOR,
+     assertion::EXECUTOR,
+     nested::EXECUTOR,
+     emr::EXECUTOR,
+     batch::EXECUTOR,
+     gcp::CLOUD_RUN_EXECUTOR,
+     gcp::CLOUD_BATCH_EXECUTOR,
+     dbt::EXECUTOR,
+     sql::EXECUTOR,
+     spark::EXECUTOR,
+     BARRIER_EXECUTOR]
+        .contains(&executor)
+}
+
+// a when expression is templated like the task's arguments, and can only refer to the
+// tasks before it (generated tasks aren't given them, so aren't checked for this)
+fn task_when(task_name: &str,
+             when: &Option<String>,
+             conf: &Option<Json>,
+             earlier: Option<&Vec<String>>)
+             -> Result<Option<String>, String> {
+    let when = match (when, conf) {
+        (&Some(ref when), &Some(ref subs)) => {
+            try!(templater::decorate_str_escaped(when, subs, templater::no_escape)
+                .map_err(String::from))
+        }
+        // without variables nothing is templated, so what a template gives can't be checked
+        (&Some(ref when), &None) if when.contains("{{") => return Ok(Some(when.clone())),
+        (&Some(ref when), &None) => when.clone(),
+        _ => return Ok(None),
+    };
+
+    let parsed = try!(expression::parse(&when).map_err(|e| {
+        format!("the task '{}' has an invalid when expression: {}", task_name, e)
+    }));
+    for name in parsed.tasks() {
+        if earlier.map(|names| names.contains(&name)) == Some(false) {
+            return Err(format!("the task '{}' has a when expression that refers to '{}', \
+                                which isn't defined before it",
+                               task_name,
+                               name));
+        }
+    }
+    Ok(Some(when))
+}
+
+fn task_when_upstream_skipped(task_name: &str,
+                              when_skipped: &Option<String>)
+                              -> Result<Option<factfile::UpstreamSkipped>, String> {
+    match when_skipped.as_ref().map(|w| w.as_ref()) {
+        None => Ok(None),
+        Some("run") => Ok(Some(factfile::UpstreamSkipped::Run)),
+        Some("skip") => Ok(Some(factfile::UpstreamSkipped::Skip)),
+        Some("fail") => Ok(Some(factfile::UpstreamSkipped::Fail)),
+        Some(other) => {
+            Err(format!("the task '{}' has an unknown whenUpstreamSkipped '{}', it must be run, \
+                         skip or fail",
+                        task_name,
+                        other))
+        }
+    }
+}
+
+// an idleTimeout of nothing would stop the task as soon as it started
+fn task_idle_timeout(task_name: &str,
+                     idle_timeout: &Option<String>)
+                     -> Result<Option<Duration>, String> {
+    let idle_timeout = match idle_timeout.as_ref() {
+        Some(idle_timeout) => {
+            try!(retry::parse_duration(idle_timeout).map_err(|e| {
+                format!("the task '{}' has an invalid idleTimeout: {}", task_name, e)
+            }))
+        }
+        None => return Ok(None),
+    };
+    if idle_timeout.as_secs() == 0 {
+        return Err(format!("the task '{}' has an idleTimeout of 0, it must be longer",
+                           task_name));
+    }
+    Ok(Some(idle_timeout))
+}
+
+fn task_tty(task_name: &str, executor: &str, tty: Option<bool>) -> Result<bool, String> {
+    match tty {
+        Some(true) if is_builtin_executor(executor) => {
+            Err(format!("the task '{}' uses the '{}' executor, which can't run under a \
+                         pseudo-terminal",
+                        task_name,
+                        executor))
+        }
+        _ => Ok(tty.unwrap_or(false)),
+    }
+}
+
+fn task_shell(task_name: &str,
+              executor: &str,
+              shell: &Option<String>)
+              -> Result<factfile::Shell, String> {
+    let shell_name = match *shell {
+        Some(ref name) => name,
+        None => return Ok(factfile::Shell::default()),
+    };
+
+    if is_builtin_executor(executor) {
+        return Err(format!("the task '{}' uses the '{}' executor, which doesn't run in a shell",
+                           task_name,
+                           executor));
+    }
+
+    match shell_name.as_ref() {
+        "sh" => Ok(factfile::Shell::Sh),
+        "bash" => Ok(factfile::Shell::Bash),
+        "pwsh" => Ok(factfile::Shell::Pwsh),
+        "none" => Ok(factfile::Shell::Exec),
+        other => {
+            Err(format!("the task '{}' has an unknown shell '{}', it must be sh, bash, pwsh or \
+                         none",
+                        task_name,
+                        other))
+        }
+    }
+}
+
+// the paths a sandboxed task can write to are templated like its arguments
+fn task_constraints(task_name: &str,
+                    constraints: &Option<FactfileConstraintsFormat>,
+                    conf: &Option<Json>)
+                    -> Result<Option<factfile::TaskConstraints>, String> {
+    let constraints = match *constraints {
+        Some(ref constraints) => constraints,
+        None => return Ok(None),
+    };
+
+    let host = match constraints.host {
+        Some(ref host) => Some(try!(decorate(host, conf))),
+        None => None,
+    };
+    let window = match constraints.window {
+        Some(ref window) => {
+            let window = try!(decorate(window, conf));
+            Some(try!(timewindow::parse_time_window(&window).map_err(|e| {
+                format!("the task '{}' has an invalid window constraint: {}", task_name, e)
+            })))
+        }
+        None => None,
+    };
+    let check = match constraints.check {
+        Some(ref check) => Some(try!(decorate(check, conf))),
+        None => None,
+    };
+    let fail_job = match constraints.whenUnmet.as_ref().map(|w| w.as_str()) {
+        None | Some("skip") => false,
+        Some("fail") => true,
+        Some(other) => {
+            return Err(format!("the task '{}' has an unknown whenUnmet '{}', it must be skip \
+                                or fail",
+                               task_name,
+                               other))
+        }
+    };
+
+    Ok(Some(factfile::TaskConstraints {
+        constraints: Constraints {
+            host: host,
+            window: window,
+            check: check,
+        },
+        fail_job: fail_job,
+    }))
+}
+
+fn task_sandbox(task_name: &str,
+                executor: &str,
+                sandbox: &Option<FactfileSandboxFormat>,
+                conf: &Option<Json>)
+                -> Result<Option<factfile::Sandbox>, String> {
+    let sandbox = match *sandbox {
+        Some(ref sandbox) => sandbox,
+        None => return Ok(None),
+    };
+
+    if is_builtin_executor(executor) {
+        return Err(format!("the task '{}' uses the '{}' executor, which can't be sandboxed",
+                           task_name,
+                           executor));
+    }
+
+    let defaults = factfile::Sandbox::default();
+    let mut writable_paths = vec![];
+    for path in sandbox.writablePaths.iter().flat_map(|p| p.iter()) {
+        writable_paths.push(try!(decorate(path, conf)));
+    }
+
+    Ok(Some(factfile::Sandbox {
+        network: sandbox.network.unwrap_or(defaults.network),
+        read_only_root: sandbox.readOnlyRoot.unwrap_or(defaults.read_only_root),
+        writable_paths: writable_paths,
+        seccomp: sandbox.seccomp.unwrap_or(defaults.seccomp),
+    }))
+}
+
+// only local files can be checksummed, unlike the artifacts a task requires
+fn task_produces(task_name: &str,
+                 produces: &Option<Vec<String>>,
+                 conf: &Option<Json>)
+                 -> Result<Vec<String>, String> {
+    let mut paths = vec![];
+    for path in produces.iter().flat_map(|p| p.iter()) {
+        let path = try!(decorate(path, conf));
+        if path.contains("://") {
+            return Err(format!("the task '{}' produces '{}', but only local files can be \
+                                checksummed",
+                               task_name,
+                               path));
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+// a task's inputs are templated like its arguments, and the variables it's cached on are
+// looked up (generated tasks have no variables, so they can only be cached on files)
+fn task_cache(task_name: &str,
+              cache: &Option<FactfileCacheFormat>,
+              conf: &Option<Json>)
+              -> Result<Option<factfile::TaskCache>, String> {
+    let cache = match *cache {
+        Some(ref cache) => cache,
+        None => return Ok(None),
+    };
+
+    let mut inputs = vec![];
+    for input in cache.inputs.iter().flat_map(|i| i.iter()) {
+        inputs.push(try!(decorate(input, conf)));
+    }
+
+    let mut variables = BTreeMap::new();
+    if let Some(Json::Object(ref vars)) = *conf {
+        for name in cache.variables.iter().flat_map(|v| v.iter()) {
+            let value = match vars.get(name) {
+                Some(&Json::String(ref s)) => s.clone(),
+                Some(other) => other.to_string(),
+                None => {
+                    return Err(format!("the task '{}' is cached on the variable '{}', which \
+                                        has no value",
+                                       task_name,
+                                       name))
+                }
+            };
+            variables.insert(name.clone(), value);
+        }
+    }
+
+    Ok(Some(factfile::TaskCache {
+        inputs: inputs,
+        variables: variables,
+        ..factfile::TaskCache::default()
+    }))
+}
+
+// how the variables in a task's arguments are escaped; by default they're
+// html escaped, as mustache does
+fn task_escape(task_name: &str,
+               executor: &str,
+               shell: &factfile::Shell,
+               escape: &Option<String>)
+               -> Result<Option<templater::Escape>, String> {
+    match escape.as_ref().map(AsRef::as_ref) {
+        None => Ok(None),
+        Some("none") => Ok(Some(templater::no_escape)),
+        // built-in executors and tasks without a shell are given their arguments as they are
+        Some("shell") if is_builtin_executor(executor) || *shell == factfile::Shell::Exec => {
+            Ok(Some(templater::no_escape))
+        }
+        Some("shell") if *shell == factfile::Shell::Pwsh => Ok(Some(templater::pwsh_escape)),
+        Some("shell") => Ok(Some(templater::sh_escape)),
+        Some(other) => {
+            Err(format!("the task '{}' has an unknown escape '{}', it must be shell or none",
+                        task_name,
+                        other))
+        }
+    }
+}
+
+// the variable a template tag refers to, e.g. "aws" for "{{ aws.emr.cluster_id }}"
+pub fn variable_root(name: &str) -> String {
+    templater::variable_root(name)
+}
+
+fn decorate(template: &str, conf: &Option<Json>) -> Result<String, String> {
+    match *conf {
+        Some(ref subs) => templater::decorate_str(template, subs).map_err(String::from),
+        None => Ok(template.to_string()),
+    }
+}
+
+// a forEach templates to a comma separated list, e.g. "{{ partitions }}" with
+// partitions set to "2024-01-01,2024-01-02"
+fn for_each_items(for_each: &str, conf: &Json) -> Result<Vec<String>, String> {
+    Ok(try!(templater::decorate_str(for_each, conf))
+        .split(',')
+        .map(|i| i.trim().to_string())
+        .filter(|i| !i.is_empty())
+        .collect())
+}
+
+// each instance of a task, with the variables it's templated with; without variables
+// nothing is templated, so a forEach task stays as it's written
+fn for_each_instances(task_name: &str,
+                      for_each: &Option<String>,
+                      conf: &Option<Json>)
+                      -> Result<Vec<(Option<String>, Option<Json>)>, String> {
+    match (for_each, conf) {
+        (&Some(ref for_each), &Some(Json::Object(ref vars))) => {
+            let items = try!(for_each_items(for_each, &Json::Object(vars.clone())));
+            if items.is_empty() {
+                return Err(format!("the task '{}' has no items in its forEach", task_name));
+            }
+            Ok(items.into_iter()
+                .map(|item| {
+                    let mut instance_vars = vars.clone();
+                    instance_vars.insert(FOR_EACH_ITEM.to_string(), Json::String(item.clone()));
+                    (Some(item), Some(Json::Object(instance_vars)))
+                })
+                .collect())
+        }
+        _ => Ok(vec![(None, conf.clone())]),
+    }
+}
+
+// when the run has a workspace each task is given it, and a directory of its own in it
+// (as the task_workspace variable) if it asks for one
+fn task_workspace(task_name: &str,
+                  own_dir: Option<bool>,
+                  conf: Option<Json>)
+                  -> (Option<Json>, Option<factfile::TaskWorkspace>) {
+    let mut vars = match conf {
+        Some(Json::Object(vars)) => vars,
+        other => return (other, None),
+    };
+    let run_dir = match vars.get(workspace::RUN_VARIABLE) {
+        Some(&Json::String(ref run_dir)) => run_dir.clone(),
+        _ => return (Some(Json::Object(vars)), None),
+    };
+
+    let task_dir = if own_dir == Some(true) {
+        let task_dir = workspace::task_dir(&run_dir, task_name);
+        vars.insert(workspace::TASK_VARIABLE.to_string(),
+                    Json::String(task_dir.clone()));
+        Some(task_dir)
+    } else {
+        None
+    };
+
+    (Some(Json::Object(vars)),
+     Some(factfile::TaskWorkspace {
+        run_dir: run_dir,
+        task_dir: task_dir,
+    }))
+}
+
+pub fn is_pattern(dep: &str) -> bool {
+    dep.contains('*') || dep.contains('?')
+}
+
+// "*" matches any run of characters and "?" any one character
+pub fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
 fn parse_valid_json(file: &str,
                     conf: Option<Json>,
-                    overrides: OverrideResultMappings)
+                    overrides: OverrideResultMappings,
+                    compute: bool)
                     -> Result<factfile::Factfile, String> {
     let schema: SelfDescribingJson = try!(json::decode(file).map_err(|e| e.to_string()));
     let compact_json:String = try!(json::encode(&schema).map_err(|e| e.to_string()));
     let decoded_json = schema.data;
+    // without a run-time environment (e.g. when drawing the dot graph) nothing is
+    // templated, so there's nothing to check the declarations against
+    let check_declarations = conf.is_some();
+    let conf = try!(variables::merge_variables(&decoded_json.variables, conf, compute));
+    let conf = try!(variables::resolve_variables(conf));
+
+    if let Some(ref declarations) = decoded_json.declarations {
+        let mut checked = BTreeMap::new();
+        for (name, declaration) in declarations.iter() {
+            checked.insert(name.clone(), try!(declaration.to_declaration(name)));
+        }
+        if check_declarations {
+            try!(variables::check_declarations(&checked, &conf));
+        }
+    }
 
-    let final_compact_json:String = if let Some(ref subs) = conf {
+    let final_compact_json:String = if let Some(ref subs) = workspace::without_run_variable(&conf) {
         try!(templater::decorate_str(&compact_json, &subs))
     } else {
         compact_json.clone()
@@ -132,86 +1111,388 @@ fn parse_valid_json(file: &str,
 
     let mut ff = factfile::Factfile::new(final_compact_json, final_dag_name);
 
-    for file_task in decoded_json.tasks.iter() {
-        let final_name = if let Some(ref subs) = conf {
-            try!(templater::decorate_str(&file_task.name, &subs))
+    if let Some(ref file_sla) = decoded_json.sla {
+        let final_sla = if let Some(ref subs) = conf {
+            try!(templater::decorate_str(file_sla, &subs))
         } else {
-            file_task.name.clone()
-        }.to_string();
+            file_sla.clone()
+        };
+        ff.sla = Some(try!(sla::parse_sla(&final_sla)));
+    }
+
+    if let Some(Json::Object(ref vars)) = conf {
+        for (name, value) in vars.iter() {
+            let value = match *value {
+                Json::String(ref s) => s.clone(),
+                ref other => other.to_string(),
+            };
+            ff.variables.insert(name.clone(), value);
+        }
+    }
 
-        // TODO errs in here - ? add task should Result not panic!
-        info!("adding task '{}'", final_name);
+    // forEach tasks are expanded into an instance per item, keyed here by the task's name
+    let mut earlier = EarlierTasks {
+        for_each_groups: BTreeMap::new(),
+        names: vec![],
+        failed: vec![],
+    };
+    // every problem is reported at once, so a factfile can be fixed in one go
+    let mut problems = vec![];
 
-        if file_task.onResult.continueJob.len() == 0 {
-            return Err(format!("the task '{}' has no way to continue successfully.",
-                               final_name));
-        } else {
-            for cont in file_task.onResult.continueJob.iter() {
-                if file_task.onResult
-                    .terminateJobWithSuccess
-                    .iter()
-                    .any(|conflict| conflict == cont) {
-                    return Err(format!("the task '{}' has conflicting actions.", final_name));
+    for file_task in decoded_json.tasks.iter() {
+        // everything about the task is templated with its own vars too
+        let conf = match variables::task_variables(&file_task.vars, &conf, compute) {
+            Ok(conf) => conf,
+            Err(msg) => {
+                problems.push(format!("the task '{}' has invalid vars: {}", file_task.name, msg));
+                continue;
+            }
+        };
+        let base_name = match decorate(&file_task.name, &conf) {
+            Ok(base_name) => base_name,
+            Err(msg) => {
+                problems.push(msg);
+                continue;
+            }
+        };
+        let instances = match for_each_instances(&base_name, &file_task.forEach, &conf) {
+            Ok(instances) => instances,
+            Err(msg) => {
+                problems.push(msg);
+                earlier.failed.push(base_name);
+                continue;
+            }
+        };
+        let mut instance_names = vec![];
+
+        for (item, conf) in instances {
+            let decorated_name = match decorate(&file_task.name, &conf) {
+                Ok(decorated_name) => decorated_name,
+                Err(msg) => {
+                    problems.push(msg);
+                    continue;
+                }
+            };
+            let final_name = match item {
+                // instances need distinct names even if the item isn't part of the name
+                Some(ref item) if decorated_name == base_name => {
+                    format!("{} [{}]", base_name, item)
+                }
+                _ => decorated_name,
+            };
+
+            info!("adding task '{}'", final_name);
+            match add_file_task(&mut ff,
+                                file_task,
+                                &final_name,
+                                &base_name,
+                                conf.clone(),
+                                &overrides,
+                                &earlier) {
+                Ok(true) => {}
+                // it depends on a task with problems, which are all that's reported
+                Ok(false) => earlier.failed.push(final_name.clone()),
+                Err(msg) => {
+                    problems.push(msg);
+                    earlier.failed.push(final_name.clone());
                 }
             }
+
+            if let Some(ref group) = file_task.group {
+                match decorate(group, &conf) {
+                    Ok(group) => ff.groups.entry(group).or_insert(vec![]).push(final_name.clone()),
+                    Err(msg) => problems.push(msg),
+                }
+            }
+            earlier.names.push(final_name.clone());
+            instance_names.push(final_name);
         }
 
-        let mut decorated_args = vec![];
-        let mut decorated_deps = vec![];
-        if let Some(ref subs) = conf {
-            info!("applying variables command and args of '{}'",
-                  &final_name);
+        if file_task.forEach.is_some() && conf.is_some() {
+            earlier.for_each_groups.insert(base_name, instance_names);
+        }
+    }
 
-            info!("before:\n\tcommand: '{}'\n\targs: '{}'",
-                  file_task.command,
-                  file_task.arguments.join(" "));
+    if let OverrideResultMappings::Tasks(ref tasks) = overrides {
+        for name in tasks.keys() {
+            if ff.find_task(name).is_none() && !earlier.names.contains(name) &&
+               !earlier.for_each_groups.contains_key(name) {
+                problems.push(format!("the results of the task '{}' are overridden, but \
+                                       there's no such task",
+                                      name));
+            }
+        }
+    }
 
-            let decorated_command = try!(templater::decorate_str(&file_task.command, &subs));
+    if problems.is_empty() {
+        Ok(ff)
+    } else {
+        Err(problems.join("\n"))
+    }
+}
 
-            for arg in file_task.arguments.iter() {
-                decorated_args.push(try!(templater::decorate_str(arg, &subs)))
-            }
+// the tasks before the one being added, which it can depend on
+struct EarlierTasks {
+    for_each_groups: BTreeMap<String, Vec<String>>,
+    names: Vec<String>,
+    failed: Vec<String>,
+}
 
-            info!("after:\n\tcommand: '{}'\n\targs: '{}'",
-                  decorated_command,
-                  decorated_args.join(" "));
+// adds a task (or one instance of a forEach task) to the factfile, giving all the
+// problems found with it together - or false, if it isn't added as it depends on a task
+// that couldn't be
+fn add_file_task(ff: &mut factfile::Factfile,
+                 file_task: &FactfileTaskFormat,
+                 final_name: &str,
+                 base_name: &str,
+                 conf: Option<Json>,
+                 overrides: &OverrideResultMappings,
+                 earlier: &EarlierTasks)
+                 -> Result<bool, String> {
+    let final_name = final_name.to_string();
+    let (conf, task_workspace) = task_workspace(&final_name, file_task.workspace, conf);
 
-            for dep in file_task.dependsOn.iter() {
-                decorated_deps.push(try!(templater::decorate_str(dep, &subs)))
-            }
+    let shell = try!(task_shell(&final_name, &file_task.executor, &file_task.shell));
+    let tty = try!(task_tty(&final_name, &file_task.executor, file_task.tty));
+    let escape = try!(task_escape(&final_name,
+                                  &file_task.executor,
+                                  &shell,
+                                  &file_task.escape));
 
-            info!("after:\n\tcommand: '{}'\n\tdeps: '{}'",
-                  decorated_command,
-                  decorated_deps.join(" "));
-        } else {
-            info!("No config specified, writing args & deps as undecorated strings");
-            for arg in file_task.arguments.iter() {
-                decorated_args.push(arg.to_string());
-            }
-            for dep in file_task.dependsOn.iter() {
-                decorated_deps.push(dep.to_string());
-            }
+    let mut decorated_args = vec![];
+    let mut decorated_deps = vec![];
+    if let Some(ref subs) = conf {
+        info!("applying variables command and args of '{}'",
+              &final_name);
+        debug!("the args of '{}' are {}",
+               &final_name,
+               if escape.is_some() {
+                   "escaped for its shell"
+               } else {
+                   "not escaped"
+               });
+
+        debug!("before:\n\tcommand: '{}'\n\targs: '{}'",
+               file_task.command,
+               file_task.arguments.join(" "));
+
+        let decorated_command = try!(templater::decorate_str(&file_task.command, &subs));
+
+        for arg in file_task.arguments.iter() {
+            decorated_args.push(try!(match escape {
+                Some(escape) => templater::decorate_str_escaped(arg, &subs, escape),
+                None => templater::decorate_str(arg, &subs),
+            }))
+        }
+
+        debug!("after:\n\tcommand: '{}'\n\targs: '{}'",
+               decorated_command,
+               decorated_args.join(" "));
+
+        for dep in file_task.dependsOn.iter() {
+            decorated_deps.push(try!(templater::decorate_str(dep, &subs)))
+        }
+
+        debug!("after:\n\tcommand: '{}'\n\tdeps: '{}'",
+               decorated_command,
+               decorated_deps.join(" "));
+    } else {
+        info!("No config specified, writing args & deps as undecorated strings");
+        for arg in file_task.arguments.iter() {
+            decorated_args.push(arg.to_string());
+        }
+        for dep in file_task.dependsOn.iter() {
+            decorated_deps.push(dep.to_string());
         }
+    }
 
-        let deps: Vec<&str> = decorated_deps.iter().map(AsRef::as_ref).collect();
-        let args: Vec<&str> = decorated_args.iter().map(AsRef::as_ref).collect();
+    let mut problems = vec![];
 
-        let (terminate_mappings, continue_mappings) = match overrides {
-            OverrideResultMappings::All(ref with_value) => {
-                (&with_value.terminate_early, &with_value.continue_job)
+    // depending on a forEach task means depending on all of its instances,
+    // depending on "group:<name>" on all of the group's tasks, and depending on a
+    // pattern (e.g. "load-*") on all of the tasks before it that it matches
+    let mut expanded_deps = vec![];
+    for dep in decorated_deps.into_iter() {
+        if dep.starts_with(factfile::GROUP_PREFIX) {
+            let group = &dep[factfile::GROUP_PREFIX.len()..];
+            match ff.groups.get(group) {
+                Some(members) => expanded_deps.extend(members.iter().cloned()),
+                None => {
+                    problems.push(format!("the task '{}' depends on the group '{}', but no \
+                                           task before it is in that group",
+                                          final_name,
+                                          group))
+                }
             }
-            OverrideResultMappings::None => {
-                (&file_task.onResult.terminateJobWithSuccess, &file_task.onResult.continueJob)
+        } else if is_pattern(&dep) {
+            let matched = earlier.names
+                .iter()
+                .filter(|name| pattern_matches(&dep, name))
+                .cloned()
+                .collect::<Vec<String>>();
+            if matched.is_empty() {
+                problems.push(format!("the task '{}' depends on '{}', which doesn't match \
+                                       any task before it",
+                                      final_name,
+                                      dep));
             }
-        };
+            expanded_deps.extend(matched);
+        } else {
+            expanded_deps.extend(earlier.for_each_groups.get(&dep).cloned().unwrap_or(vec![dep]));
+        }
+    }
+    let mut decorated_deps: Vec<String> = vec![];
+    for dep in expanded_deps.into_iter() {
+        if !decorated_deps.contains(&dep) {
+            decorated_deps.push(dep);
+        }
+    }
+    let deps: Vec<&str> = decorated_deps.iter().map(AsRef::as_ref).collect();
+    let args: Vec<&str> = decorated_args.iter().map(AsRef::as_ref).collect();
+
+    let (terminate_mappings, continue_mappings) =
+        task_result_mappings(overrides, &[&final_name, base_name], &file_task.onResult);
+    let on_result = &file_task.onResult;
+    // an override can take away a task's only way to continue
+    if on_result.continueJob.is_empty() || continue_mappings.is_empty() {
+        problems.push(format!("the task '{}' has no way to continue successfully.",
+                              final_name));
+    } else if on_result.continueJob.iter().any(|c| on_result.terminateJobWithSuccess.contains(c)) ||
+              continue_mappings.iter().any(|c| terminate_mappings.contains(c)) {
+        problems.push(format!("the task '{}' has conflicting actions.", final_name));
+    }
+
+    if let Err(msg) = executors::validate(&file_task.executor,
+                                          &executors::ExecutorTask {
+                                              name: &final_name,
+                                              command: &file_task.command,
+                                              arguments: &decorated_args,
+                                              continue_job: &file_task.onResult.continueJob,
+                                              templated: conf.is_some(),
+                                          }) {
+        problems.push(msg);
+    }
 
-        ff.add_task(&final_name,
-                    &deps,
-                    &file_task.executor,
-                    &file_task.command,
-                    &args,
-                    terminate_mappings,
-                    continue_mappings);
+    // add_task stops at the first of these, so they're checked here to be reported along
+    // with the task's other problems
+    if ff.find_task(&final_name).is_some() {
+        problems.push(factfile::TaskError::Duplicate(final_name.clone()).to_string());
+    }
+    let mut depends_on_failed = false;
+    for dep in deps.iter() {
+        if *dep == final_name {
+            problems.push(factfile::TaskError::DependsOnItself(final_name.clone()).to_string());
+        } else if earlier.failed.iter().any(|failed| failed == dep) {
+            depends_on_failed = true;
+        } else if ff.find_task(dep).is_none() {
+            problems.push(factfile::TaskError::UnknownDependency {
+                    task: final_name.clone(),
+                    dependency: dep.to_string(),
+                }
+                .to_string());
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(problems.join("\n"));
+    }
+    if depends_on_failed {
+        return Ok(false);
+    }
+
+    try!(ff.add_task(&final_name,
+                     &deps,
+                     &file_task.executor,
+                     &file_task.command,
+                     &args,
+                     &terminate_mappings,
+                     &continue_mappings)
+        .map_err(|e| e.to_string()));
+
+    let expected_duration = match file_task.expectedDuration {
+        Some(ref duration) => {
+            Some(try!(retry::parse_duration(duration).map_err(|e| {
+                format!("the task '{}' has an invalid expectedDuration: {}", final_name, e)
+            })))
+        }
+        None => None,
+    };
+
+    let mut requires = vec![];
+    for artifact in file_task.requires.iter().flat_map(|r| r.iter()) {
+        requires.push(if let Some(ref subs) = conf {
+            try!(templater::decorate_str(artifact, &subs))
+        } else {
+            artifact.to_string()
+        });
+    }
+
+    let description = match file_task.description {
+        Some(ref description) => Some(try!(decorate(description, &conf))),
+        None => None,
+    };
+    let owner = match file_task.owner {
+        Some(ref owner) => Some(try!(decorate(owner, &conf))),
+        None => None,
+    };
+    let stdin = match file_task.stdin {
+        Some(ref stdin) => Some(try!(decorate(stdin, &conf))),
+        None => None,
+    };
+    let stdin_file = match file_task.stdinFile {
+        Some(ref stdin_file) => Some(try!(decorate(stdin_file, &conf))),
+        None => None,
+    };
+    let stdin = try!(task_input(&final_name, &file_task.executor, stdin, stdin_file));
+    let sandbox = try!(task_sandbox(&final_name,
+                                    &file_task.executor,
+                                    &file_task.sandbox,
+                                    &conf));
+    let cache = try!(task_cache(&final_name, &file_task.cache, &conf));
+    let produces = try!(task_produces(&final_name, &file_task.produces, &conf));
+    let constraints = try!(task_constraints(&final_name, &file_task.constraints, &conf));
+    let when_upstream_skipped = try!(task_when_upstream_skipped(&final_name,
+                                                                &file_task.whenUpstreamSkipped));
+    let when = try!(task_when(&final_name, &file_task.when, &conf, Some(&earlier.names)));
+    let idle_timeout = try!(task_idle_timeout(&final_name, &file_task.idleTimeout));
+
+    if let Some(task) = ff.find_task_mut(&final_name) {
+        task.expected_duration = expected_duration;
+        task.idle_timeout = idle_timeout;
+        task.requires = requires;
+        task.produces = produces;
+        task.disabled = file_task.enabled == Some(false);
+        task.description = description;
+        task.owner = owner;
+        task.stdin = stdin;
+        task.shell = shell;
+        task.tty = tty;
+        task.constraints = constraints;
+        task.sandbox = sandbox;
+        task.workspace = task_workspace;
+        task.cache = cache;
+        task.when_upstream_skipped = when_upstream_skipped;
+        task.when = when;
+        task.circuit_breaker = factfile::CircuitBreaker {
+            skip_if_failed_last: file_task.skipIfFailedLast,
+            skip_if_failure_rate: file_task.skipIfFailureRate.as_ref().map(|r| {
+                factfile::FailureRate {
+                    threshold: r.threshold,
+                    over_last: r.overLastRuns,
+                }
+            }),
+        };
+    }
+    if let (&Some(ref vars), &Some(Json::Object(ref values))) = (&file_task.vars, &conf) {
+        let mut task_vars = BTreeMap::new();
+        for (name, value) in values.iter().filter(|&(name, _)| vars.contains_key(name)) {
+            let value = match *value {
+                Json::String(ref s) => s.clone(),
+                ref other => other.to_string(),
+            };
+            task_vars.insert(name.clone(), value);
+        }
+        ff.task_variables.insert(final_name, task_vars);
     }
-    Ok(ff)
+    Ok(true)
 }
@@ -0,0 +1,116 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::json::Json;
+
+pub const REF_KEY: &'static str = "$ref";
+
+// things referred to by more than one place can be kept here, as the factfile itself
+// has no other use for them
+pub const DEFINITIONS_KEY: &'static str = "definitions";
+
+// an object that's only {"$ref": "#/data/definitions/loadArgs"} is replaced by the
+// value it points to, which is found in the factfile itself - references to other
+// documents aren't followed; false if the factfile has no references
+pub fn resolve_references(factfile: &mut Json) -> Result<bool, String> {
+    let root = factfile.clone();
+    let resolved = try!(resolve(factfile, &root, &mut vec![]));
+
+    let definitions = match factfile.as_object_mut().and_then(|f| f.get_mut("data")) {
+        Some(&mut Json::Object(ref mut data)) => data.remove(DEFINITIONS_KEY).is_some(),
+        _ => false,
+    };
+    Ok(resolved || definitions)
+}
+
+fn resolve(value: &mut Json, root: &Json, resolving: &mut Vec<String>) -> Result<bool, String> {
+    let reference = match *value {
+        Json::Object(ref fields) if fields.contains_key(REF_KEY) => {
+            if fields.len() > 1 {
+                return Err(format!("a reference can't have other fields alongside '{}'",
+                                   REF_KEY));
+            }
+            match fields.get(REF_KEY) {
+                Some(&Json::String(ref reference)) => Some(reference.clone()),
+                _ => return Err(format!("'{}' must be a string", REF_KEY)),
+            }
+        }
+        _ => None,
+    };
+
+    if let Some(reference) = reference {
+        if resolving.contains(&reference) {
+            resolving.push(reference);
+            return Err(format!("the references refer to each other in a cycle: {}",
+                               resolving.join(" -> ")));
+        }
+        let mut target = try!(find_reference(root, &reference)).clone();
+        resolving.push(reference);
+        try!(resolve(&mut target, root, resolving));
+        resolving.pop();
+        *value = target;
+        return Ok(true);
+    }
+
+    let mut resolved = false;
+    match *value {
+        Json::Object(ref mut fields) => {
+            for field in fields.values_mut() {
+                resolved = try!(resolve(field, root, resolving)) || resolved;
+            }
+        }
+        Json::Array(ref mut items) => {
+            for item in items.iter_mut() {
+                resolved = try!(resolve(item, root, resolving)) || resolved;
+            }
+        }
+        _ => {}
+    }
+    Ok(resolved)
+}
+
+// a reference is "#" followed by a JSON pointer, with "~1" and "~0" standing in for
+// "/" and "~" as in RFC 6901
+pub fn find_reference<'a>(root: &'a Json, reference: &str) -> Result<&'a Json, String> {
+    if !reference.starts_with('#') {
+        return Err(format!("the reference '{}' isn't to this factfile, only references \
+                            starting with '#' can be resolved",
+                           reference));
+    }
+
+    let pointer = &reference[1..];
+    if pointer.is_empty() {
+        return Ok(root);
+    }
+    if !pointer.starts_with('/') {
+        return Err(format!("the reference '{}' must be '#' followed by a JSON pointer",
+                           reference));
+    }
+
+    let mut found = root;
+    for token in pointer[1..].split('/').map(|t| t.replace("~1", "/").replace("~0", "~")) {
+        let next = match *found {
+            Json::Object(ref fields) => fields.get(&token),
+            Json::Array(ref items) => token.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        found = try!(next.ok_or(format!("the reference '{}' doesn't point to anything",
+                                        reference)));
+    }
+    Ok(found)
+}
@@ -0,0 +1,76 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+
+use super::*;
+use rustc_serialize::json::Json;
+
+fn resolved(json: &str) -> Result<Json, String> {
+    let mut factfile = Json::from_str(json).unwrap();
+    resolve_references(&mut factfile).map(|_| factfile)
+}
+
+#[test]
+fn references_are_replaced() {
+    let factfile = resolved(r##"{"data": {
+        "definitions": {
+            "args": ["--all"],
+            "mappings": {"continueJob": [0],
+                         "terminateJobWithSuccess": {"$ref": "#/data/definitions/done"}},
+            "done": [3]
+        },
+        "tasks": [{"arguments": {"$ref": "#/data/definitions/args"},
+                   "onResult": {"$ref": "#/data/definitions/mappings"}}]
+    }}"##)
+        .unwrap();
+
+    assert_eq!(factfile,
+               Json::from_str(r##"{"data": {"tasks": [{"arguments": ["--all"],
+                   "onResult": {"continueJob": [0], "terminateJobWithSuccess": [3]}}]}}"##)
+                   .unwrap());
+}
+
+#[test]
+fn references_can_point_anywhere_in_the_factfile() {
+    let factfile = resolved(r##"{"data": {"tasks": [
+        {"name": "a/b", "arguments": ["x"]},
+        {"arguments": {"$ref": "#/data/tasks/0/arguments"}}
+    ]}}"##)
+        .unwrap();
+    assert_eq!(factfile.find_path(&["data", "tasks"]).unwrap()[1].find("arguments"),
+               Some(&Json::from_str(r##"["x"]"##).unwrap()));
+}
+
+#[test]
+fn bad_references_are_rejected() {
+    assert_eq!(resolved(r##"{"a": {"$ref": "#/missing"}}"##),
+               Err("the reference '#/missing' doesn't point to anything".to_string()));
+    assert_eq!(resolved(r##"{"a": {"$ref": "other.json#/a"}}"##),
+               Err("the reference 'other.json#/a' isn't to this factfile, only references \
+                    starting with '#' can be resolved"
+                   .to_string()));
+    assert_eq!(resolved(r##"{"a": {"$ref": "#/b"}, "b": {"$ref": "#/a"}}"##),
+               Err("the references refer to each other in a cycle: #/b -> #/a -> #/b"
+                   .to_string()));
+    assert_eq!(resolved(r##"{"a": {"$ref": "#/b", "c": 1}, "b": 1}"##),
+               Err("a reference can't have other fields alongside '$ref'".to_string()));
+}
+
+#[test]
+fn pointers_are_unescaped() {
+    let root = Json::from_str(r##"{"a/b": {"c~d": [1, 2]}}"##).unwrap();
+    assert_eq!(find_reference(&root, "#/a~1b/c~0d/1"), Ok(&Json::U64(2)));
+    assert_eq!(find_reference(&root, "#"), Ok(&root));
+}
@@ -62,8 +62,30 @@ pub fn validate_schema(json: &str, schema: &str) -> Result<(), String> {
     }
 }
 
+// each version of the factfile schema that's bundled, oldest first
+pub const FACTFILE_SCHEMAS: &'static [(&'static str, &'static str)] =
+    &[("1-0-0", include_str!("jsonschemas/factotum.json"))];
+
+// the latest schema when no version's given
+pub fn factfile_schema(version: Option<&str>) -> Result<&'static str, String> {
+    match version {
+        None => Ok(FACTFILE_SCHEMAS[FACTFILE_SCHEMAS.len() - 1].1),
+        Some(version) => {
+            FACTFILE_SCHEMAS.iter()
+                .find(|&&(v, _)| v == version)
+                .map(|&(_, schema)| schema)
+                .ok_or(format!("there's no factfile schema '{}', the supported versions are {}",
+                               version,
+                               FACTFILE_SCHEMAS.iter()
+                                   .map(|&(v, _)| v)
+                                   .collect::<Vec<_>>()
+                                   .join(", ")))
+        }
+    }
+}
+
 pub fn validate_against_factfile_schema(json: &str) -> Result<(), String> {
-    let factotum_schema_str: &'static str = include_str!("jsonschemas/factotum.json");
+    let factotum_schema_str = try!(factfile_schema(None));
 
     validate_schema(json, factotum_schema_str)
 }
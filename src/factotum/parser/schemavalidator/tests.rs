@@ -14,3 +14,21 @@
 
 
 // TODO write some tests for the schema validator
+
+use super::*;
+use rustc_serialize::json::Json;
+
+#[test]
+fn factfile_schema_defaults_to_the_latest() {
+    let latest = factfile_schema(None).unwrap();
+    assert_eq!(factfile_schema(Some("1-0-0")), Ok(latest));
+    assert_eq!(Json::from_str(latest).unwrap().find_path(&["self", "version"]),
+               Some(&Json::String("1-0-0".to_string())));
+}
+
+#[test]
+fn unknown_factfile_schema_versions_are_rejected() {
+    assert_eq!(factfile_schema(Some("9-9-9")),
+               Err("there's no factfile schema '9-9-9', the supported versions are 1-0-0"
+                   .to_string()));
+}
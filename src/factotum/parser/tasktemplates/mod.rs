@@ -0,0 +1,171 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+
+// a task given as {"template": "load", "with": {"table": "events"}} takes the fields of
+// the "load" template it doesn't set itself, with "{{ table }}" in them replaced by
+// "events" - any other variables are left for the factfile's own templating
+pub fn apply_templates(factfile: &mut Json) -> Result<bool, String> {
+    let data = match factfile.as_object_mut().and_then(|f| f.get_mut("data")) {
+        Some(&mut Json::Object(ref mut data)) => data,
+        _ => return Ok(false),
+    };
+    let templates = match data.remove("taskTemplates") {
+        Some(Json::Object(templates)) => templates,
+        Some(_) => return Err("'taskTemplates' must be an object of named templates".to_string()),
+        None => return Ok(false),
+    };
+
+    if let Some(&mut Json::Array(ref mut tasks)) = data.get_mut("tasks") {
+        for task in tasks.iter_mut() {
+            if let Json::Object(ref mut task) = *task {
+                try!(instantiate(task, &templates));
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+fn instantiate(task: &mut BTreeMap<String, Json>,
+               templates: &BTreeMap<String, Json>)
+               -> Result<(), String> {
+    let task_name = task.get("name").and_then(|n| n.as_string()).unwrap_or("").to_string();
+    let template_name = match task.remove("template") {
+        Some(Json::String(name)) => name,
+        Some(_) => return Err(format!("the task '{}' must name its template", task_name)),
+        None => {
+            if task.contains_key("with") {
+                return Err(format!("the task '{}' has parameters but no template", task_name));
+            }
+            return Ok(());
+        }
+    };
+    let template = match templates.get(&template_name) {
+        Some(&Json::Object(ref template)) => template,
+        Some(_) => return Err(format!("the task template '{}' must be an object", template_name)),
+        None => {
+            return Err(format!("the task '{}' uses the template '{}', which doesn't exist",
+                               task_name,
+                               template_name))
+        }
+    };
+
+    let params = try!(parameters(&task_name, task.remove("with")));
+    let declared = template.get("parameters")
+        .and_then(|p| p.as_array())
+        .map(|p| p.iter().filter_map(|n| n.as_string()).map(|n| n.to_string()).collect())
+        .unwrap_or(vec![]);
+    for name in declared.iter() {
+        if !params.contains_key(name) {
+            return Err(format!("the task '{}' doesn't give the template '{}' its parameter '{}'",
+                               task_name,
+                               template_name,
+                               name));
+        }
+    }
+    for name in params.keys() {
+        if !declared.contains(name) {
+            return Err(format!("the task '{}' gives the template '{}' the parameter '{}', \
+                                which it doesn't have",
+                               task_name,
+                               template_name,
+                               name));
+        }
+    }
+
+    for (field, value) in template.iter().filter(|&(f, _)| f != "parameters" && f != "name") {
+        if !task.contains_key(field) {
+            task.insert(field.clone(), substitute(value, &params));
+        }
+    }
+    Ok(())
+}
+
+fn parameters(task_name: &str, with: Option<Json>) -> Result<BTreeMap<String, String>, String> {
+    let mut params = BTreeMap::new();
+    match with {
+        Some(Json::Object(with)) => {
+            for (name, value) in with.into_iter() {
+                let value = match value {
+                    Json::String(s) => s,
+                    Json::I64(_) | Json::U64(_) | Json::F64(_) | Json::Boolean(_) => {
+                        value.to_string()
+                    }
+                    _ => {
+                        return Err(format!("the parameter '{}' of the task '{}' must be a \
+                                            string, number or bool",
+                                           name,
+                                           task_name))
+                    }
+                };
+                params.insert(name, value);
+            }
+        }
+        Some(_) => {
+            return Err(format!("the task '{}' must give its parameters as an object",
+                               task_name))
+        }
+        None => {}
+    }
+    Ok(params)
+}
+
+fn substitute(value: &Json, params: &BTreeMap<String, String>) -> Json {
+    match *value {
+        Json::String(ref s) => Json::String(substitute_str(s, params)),
+        Json::Array(ref items) => {
+            Json::Array(items.iter().map(|i| substitute(i, params)).collect())
+        }
+        Json::Object(ref fields) => {
+            Json::Object(fields.iter()
+                .map(|(k, v)| (k.clone(), substitute(v, params)))
+                .collect())
+        }
+        ref other => other.clone(),
+    }
+}
+
+// only tags naming a parameter are replaced, e.g. "{{ table }}" but not "{{ region }}"
+fn substitute_str(template: &str, params: &BTreeMap<String, String>) -> String {
+    let mut substituted = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let close = if rest[start..].starts_with("{{{") { "}}}" } else { "}}" };
+        let open = close.len();
+        let end = match rest[start + open..].find(close) {
+            Some(end) => start + open + end,
+            None => break,
+        };
+        let inner = rest[start + open..end].trim();
+        let name = if inner.starts_with('&') { inner[1..].trim() } else { inner };
+
+        substituted.push_str(&rest[..start]);
+        match params.get(name) {
+            Some(value) => substituted.push_str(value),
+            None => substituted.push_str(&rest[start..end + close.len()]),
+        }
+        rest = &rest[end + close.len()..];
+    }
+
+    substituted.push_str(rest);
+    substituted
+}
@@ -0,0 +1,88 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+
+use super::*;
+use rustc_serialize::json::Json;
+
+fn factfile(tasks: &str) -> Json {
+    Json::from_str(&format!(r#"{{"data": {{
+        "taskTemplates": {{
+            "load": {{
+                "parameters": ["table"],
+                "command": "./load.sh {{{{ table }}}}",
+                "arguments": ["--table={{{{{{table}}}}}}", "--region={{{{ region }}}}"]
+            }}
+        }},
+        "tasks": {}
+    }}}}"#,
+                            tasks))
+        .unwrap()
+}
+
+fn first_task(factfile: &Json) -> &Json {
+    &factfile.find_path(&["data", "tasks"]).unwrap().as_array().unwrap()[0]
+}
+
+#[test]
+fn templates_are_instantiated() {
+    let mut json = factfile(r#"[{"name": "a", "template": "load", "with": {"table": "events"}}]"#);
+    assert_eq!(apply_templates(&mut json), Ok(true));
+
+    let task = first_task(&json);
+    assert_eq!(task.find("command"),
+               Some(&Json::String("./load.sh events".to_string())));
+    assert_eq!(task.find("arguments"),
+               Some(&Json::from_str(r#"["--table=events", "--region={{ region }}"]"#).unwrap()));
+    assert_eq!(task.find("template"), None);
+    assert_eq!(task.find("with"), None);
+    assert_eq!(json.find_path(&["data", "taskTemplates"]), None);
+}
+
+#[test]
+fn tasks_override_templates() {
+    let mut json = factfile(r#"[{"name": "a", "template": "load", "with": {"table": 1},
+                                 "command": "./other.sh"}]"#);
+    assert_eq!(apply_templates(&mut json), Ok(true));
+    assert_eq!(first_task(&json).find("command"),
+               Some(&Json::String("./other.sh".to_string())));
+    assert_eq!(first_task(&json).find_path(&["arguments"]).unwrap()[0],
+               Json::String("--table=1".to_string()));
+}
+
+#[test]
+fn parameters_must_match() {
+    let mut json = factfile(r#"[{"name": "a", "template": "load"}]"#);
+    assert_eq!(apply_templates(&mut json),
+               Err("the task 'a' doesn't give the template 'load' its parameter 'table'"
+                   .to_string()));
+
+    let mut json = factfile(r#"[{"name": "a", "template": "load",
+                                 "with": {"table": "x", "schema": "y"}}]"#);
+    assert_eq!(apply_templates(&mut json),
+               Err("the task 'a' gives the template 'load' the parameter 'schema', which it \
+                    doesn't have"
+                   .to_string()));
+
+    let mut json = factfile(r#"[{"name": "a", "template": "nope"}]"#);
+    assert_eq!(apply_templates(&mut json),
+               Err("the task 'a' uses the template 'nope', which doesn't exist".to_string()));
+}
+
+#[test]
+fn factfiles_without_templates_are_left_alone() {
+    let mut json = Json::from_str(r#"{"data": {"tasks": []}}"#).unwrap();
+    assert_eq!(apply_templates(&mut json), Ok(false));
+}
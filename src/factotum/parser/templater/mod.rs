@@ -17,16 +17,234 @@ extern crate mustache;
 #[cfg(test)]
 mod tests;
 
+use std::cell::Cell;
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::panic;
+use std::sync::Once;
 use rustc_serialize::json::Json;
+use factotum::errors::{ErrorKind, FactotumError};
 
-pub fn decorate_str(template: &str, env: &Json) -> Result<String, String> {
-    let compiled_template = mustache::compile_str(&template);
+// a variable starting with this is a JSON pointer into the env, e.g. "{{ @/aws/emr/0/id }}"
+// - unlike a dotted name it can index arrays and reach keys with dots in them
+pub const POINTER_PREFIX: &'static str = "@/";
+
+const POINTER_VARIABLE: &'static str = "factotum_pointer_";
+
+// the reference tokens of a pointer, with "~1" and "~0" unescaped as in RFC 6901
+fn pointer_tokens(pointer: &str) -> Vec<String> {
+    pointer[POINTER_PREFIX.len()..]
+        .split('/')
+        .map(|t| t.replace("~1", "/").replace("~0", "~"))
+        .collect()
+}
+
+pub fn find_pointer<'a>(env: &'a Json, pointer: &str) -> Option<&'a Json> {
+    pointer_tokens(pointer).iter().fold(Some(env), |found, token| {
+        match found {
+            Some(&Json::Object(ref fields)) => fields.get(token),
+            Some(&Json::Array(ref items)) => token.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        }
+    })
+}
+
+// the top level variable a name refers to, e.g. "aws" for "aws.emr" or "@/aws/emr"
+pub fn variable_root(name: &str) -> String {
+    if name.starts_with(POINTER_PREFIX) {
+        pointer_tokens(name).swap_remove(0)
+    } else {
+        name.split('.').next().unwrap_or("").to_string()
+    }
+}
+
+// mustache can't look pointers up, so each is swapped for a variable holding what it points to
+fn expand_pointers(template: &str, env: &Json) -> Option<(String, Json)> {
+    if !template.contains(POINTER_PREFIX) {
+        return None;
+    }
+
+    let mut fields = match *env {
+        Json::Object(ref fields) => fields.clone(),
+        _ => BTreeMap::new(),
+    };
+    let mut expanded = String::new();
+    let mut rest = template;
+    let mut count = 0;
+
+    while let Some(start) = rest.find("{{") {
+        let open = if rest[start..].starts_with("{{{") { 3 } else { 2 };
+        let tag_start = start + open;
+        let end = match rest[tag_start..].find("}}") {
+            Some(end) => tag_start + end,
+            None => break,
+        };
+        let inner = rest[tag_start..end].trim();
+        let (ampersand, name) = if inner.starts_with('&') {
+            ("&", inner[1..].trim())
+        } else {
+            ("", inner)
+        };
+
+        expanded.push_str(&rest[..tag_start]);
+        if name.starts_with(POINTER_PREFIX) {
+            let variable = format!("{}{}", POINTER_VARIABLE, count);
+            count += 1;
+            // mustache can't render numbers or nulls, so values are given as strings
+            let value = match find_pointer(env, name) {
+                Some(&Json::String(ref value)) => Json::String(value.clone()),
+                Some(found @ &Json::Object(_)) |
+                Some(found @ &Json::Array(_)) => found.clone(),
+                Some(&Json::Null) | None => Json::String(String::new()),
+                Some(other) => Json::String(other.to_string()),
+            };
+            fields.insert(variable.clone(), value);
+            expanded.push_str(&format!("{}{}", ampersand, variable));
+        } else {
+            expanded.push_str(&rest[tag_start..end]);
+        }
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+
+    if count == 0 {
+        None
+    } else {
+        Some((expanded, Json::Object(fields)))
+    }
+}
+
+thread_local!(static COMPILING: Cell<bool> = Cell::new(false));
+
+static QUIET_COMPILE_PANICS: Once = Once::new();
+
+// mustache panics on a malformed template (e.g. an unclosed section), so the panic is
+// caught and its message returned instead - without it being printed as a crash
+fn compile(template: &str) -> Result<mustache::Template, String> {
+    QUIET_COMPILE_PANICS.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !COMPILING.with(|c| c.get()) {
+                default_hook(info);
+            }
+        }));
+    });
+
+    COMPILING.with(|c| c.set(true));
+    let compiled = panic::catch_unwind(|| mustache::compile_str(template));
+    COMPILING.with(|c| c.set(false));
+
+    compiled.map_err(|cause| {
+        let reason = cause.downcast_ref::<String>()
+            .cloned()
+            .or(cause.downcast_ref::<&str>().map(|r| r.to_string()))
+            .unwrap_or("it couldn't be compiled".to_string());
+        format!("the template '{}' is invalid: {}", template, reason)
+    })
+}
+
+fn template_error(message: String) -> FactotumError {
+    FactotumError::new(ErrorKind::Template, message)
+}
+
+pub fn check_template(template: &str) -> Result<(), FactotumError> {
+    compile(template).map(|_| ()).map_err(template_error)
+}
+
+pub fn decorate_str(template: &str, env: &Json) -> Result<String, FactotumError> {
+    if let Some((expanded, expanded_env)) = expand_pointers(template, env) {
+        return decorate_str(&expanded, &expanded_env);
+    }
+
+    let compiled_template = try!(compile(template).map_err(template_error));
     let mut bytes = vec![];
     try!(compiled_template.render(&mut bytes, &env)
-        .map_err(|e| format!("Error rendering template: {}", Error::description(&e))));
+        .map_err(|e| {
+            template_error(format!("Error rendering template: {}", Error::description(&e)))
+        }));
     String::from_utf8(bytes).map_err(|e| {
-        format!("Error inflating rendered template to utf8: {}",
-                Error::description(&e))
+        template_error(format!("Error inflating rendered template to utf8: {}",
+                               Error::description(&e)))
+    })
+}
+
+// the escaping applied to each value substituted into a template; a value in
+// triple braces ({{{name}}}) or an ampersand tag ({{&name}}) is left as it is
+pub type Escape = fn(&str) -> String;
+
+// unlike decorate_str, only variables (and comments) can be used - sections and
+// partials are rejected, as there's no single value to escape
+pub fn decorate_str_escaped(template: &str,
+                            env: &Json,
+                            escape: Escape)
+                            -> Result<String, FactotumError> {
+    let mut decorated = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        decorated.push_str(&rest[..start]);
+        let tag = &rest[start..];
+        let (raw, close) = if tag.starts_with("{{{") {
+            (true, "}}}")
+        } else {
+            (false, "}}")
+        };
+        let open = close.len();
+        let end = try!(tag[open..]
+            .find(close)
+            .map(|end| end + open)
+            .ok_or(template_error(format!("the template '{}' has an unclosed tag", template))));
+        let inner = tag[open..end].trim();
+        rest = &tag[end + close.len()..];
+
+        let (raw, name) = if inner.starts_with('!') {
+            continue;
+        } else if !raw && inner.starts_with('&') {
+            (true, inner[1..].trim())
+        } else if !raw && inner.starts_with(|c| "#^/>=".contains(c)) {
+            return Err(template_error(format!("the template '{}' uses the tag '{{{{{}}}}}', \
+                                               but only variables can be used when values \
+                                               are escaped",
+                                              template,
+                                              inner)));
+        } else {
+            (raw, inner)
+        };
+
+        let value = try!(decorate_str(&format!("{{{{{{{}}}}}}}", name), env));
+        debug!("'{}' in '{}' is {}",
+               name,
+               template,
+               if raw { "substituted as it is" } else { "escaped" });
+        decorated.push_str(&if raw { value } else { escape(&value) });
+    }
+
+    decorated.push_str(rest);
+    Ok(decorated)
+}
+
+pub fn no_escape(value: &str) -> String {
+    value.to_string()
+}
+
+// arguments are given to sh and bash in double quotes, where only these are special
+pub fn sh_escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        if "\\$`\"".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+// powershell's escape character is the backtick, rather than a backslash
+pub fn pwsh_escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        if "`$\"".contains(c) {
+            escaped.push('`');
+        }
+        escaped.push(c);
+        escaped
     })
 }
@@ -32,3 +32,71 @@ fn decorated_nested_string_works() {
                             &from_json("{\"person\": { \"name\":\"Ted\" } }"))
                    .unwrap())
 }
+
+#[test]
+fn decorated_escaped_string_works() {
+    let env = from_json("{\"name\":\"Ed \\\"$HOME\\\"\", \"person\": { \"name\":\"Ted\" } }");
+    assert_eq!(decorate_str_escaped("hello {{name}}!", &env, sh_escape),
+               Ok("hello Ed \\\"\\$HOME\\\"!".to_string()));
+    assert_eq!(decorate_str_escaped("{{ name }}/{{{name}}}/{{& name}}", &env, pwsh_escape),
+               Ok("Ed `\"`$HOME`\"/Ed \"$HOME\"/Ed \"$HOME\"".to_string()));
+    assert_eq!(decorate_str_escaped("hi {{! a comment }}{{person.name}}{{missing}}",
+                                    &env,
+                                    no_escape),
+               Ok("hi Ted".to_string()));
+}
+
+#[test]
+fn decorated_escaped_string_only_allows_variables() {
+    let env = from_json("{\"names\": [\"Ed\", \"Ted\"]}");
+    assert_eq!(decorate_str_escaped("{{#names}}{{.}}{{/names}}", &env, sh_escape),
+               Err(template_error("the template '{{#names}}{{.}}{{/names}}' uses the tag \
+                                   '{{#names}}', but only variables can be used when values \
+                                   are escaped"
+                   .to_string())));
+    assert_eq!(decorate_str_escaped("hello {{name", &env, sh_escape),
+               Err(template_error("the template 'hello {{name' has an unclosed tag".to_string())));
+}
+
+#[test]
+fn decorated_pointer_works() {
+    let env = from_json("{\"aws\": { \"emr\": [ { \"cluster_id\": \"j-1\" } ], \
+                         \"a.b\": \"dotted\", \"c/d~\": 3 }, \"name\": \"Ed\"}");
+    assert_eq!(decorate_str("{{ @/aws/emr/0/cluster_id }} for {{name}}", &env),
+               Ok("j-1 for Ed".to_string()));
+    assert_eq!(decorate_str("{{@/aws/a.b}}/{{{ @/aws/c~1d~0 }}}/{{& @/aws/missing}}", &env),
+               Ok("dotted/3/".to_string()));
+    assert_eq!(decorate_str("{{#aws}}{{ @/name }}{{/aws}}", &env),
+               Ok("Ed".to_string()));
+    assert_eq!(decorate_str_escaped("{{ @/aws/emr/0/cluster_id }}-{{ @/name }}", &env, sh_escape),
+               Ok("j-1-Ed".to_string()));
+}
+
+#[test]
+fn pointers_are_found() {
+    let env = from_json("{\"aws\": { \"emr\": [ \"j-1\" ] } }");
+    assert_eq!(find_pointer(&env, "@/aws/emr/0"), Some(&from_json("\"j-1\"")));
+    assert_eq!(find_pointer(&env, "@/aws/emr/1"), None);
+    assert_eq!(find_pointer(&env, "@/aws/emr/x"), None);
+    assert_eq!(find_pointer(&env, "@/gcp"), None);
+}
+
+#[test]
+fn variable_root_works() {
+    assert_eq!(variable_root("aws.emr.cluster_id"), "aws");
+    assert_eq!(variable_root("@/aws/emr"), "aws");
+    assert_eq!(variable_root("@/a~1b/c"), "a/b");
+    assert_eq!(variable_root("name"), "name");
+}
+
+#[test]
+fn malformed_templates_are_errors() {
+    let env = from_json("{\"x\": \"y\"}");
+    assert_eq!(decorate_str("{{#x}} never closed", &env),
+               Err(FactotumError::new(ErrorKind::Template,
+                                      "the template '{{#x}} never closed' is invalid: \
+                                       Unclosed mustache section x")));
+    let empty = decorate_str("{{ }}", &env).unwrap_err();
+    assert_eq!(empty.kind, ErrorKind::Template);
+    assert!(empty.message.ends_with("is invalid: empty tag"));
+}
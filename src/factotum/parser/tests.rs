@@ -12,19 +12,38 @@
 // governing permissions and limitations there under.
 //
 
+use factotum::config::ExecutorSettings;
+use factotum::errors::FactotumError;
+use factotum::factfile::Factfile;
 use factotum::parser::*;
 use rustc_serialize::json::{self, Json};
+use std::fs;
 
 #[inline]
 fn resource(name: &str) -> String {
     format!("./tests/resources/{}", name)
 }
 
+// the tests don't depend on the config file of whoever runs them
+fn parse(factfile: &str,
+         env: Option<Json>,
+         overrides: OverrideResultMappings)
+         -> Result<Factfile, FactotumError> {
+    super::parse(factfile, env, overrides, &ExecutorSettings::new())
+}
+
+fn parse_for_run(factfile: &str,
+                 env: Option<Json>,
+                 overrides: OverrideResultMappings)
+                 -> Result<Factfile, FactotumError> {
+    super::parse_for_run(factfile, env, overrides, &ExecutorSettings::new())
+}
+
 #[test]
 fn invalid_files_err() {
     let res = parse("asdhf;asdjhfasdf", None, OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    "Couldn't open 'asdhf;asdjhfasdf' for reading: No such file or directory (os \
                     error 2)"
                        .to_string())
@@ -39,7 +58,7 @@ fn invalid_json_err() {
                     None,
                     OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    format!("'{}' is not a valid factotum factfile: invalid JSON - invalid syntax \
                             at line 1, column 3",
                            resource("invalid_json.factfile"))
@@ -54,9 +73,9 @@ fn invalid_against_schema_err() {
     let invalid = resource("example_invalid_no_name.factfile");
     let res = parse(&invalid, None, OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    format!("'{}' is not a valid factotum factfile: '/data/name' - This property \
-                            is required",
+                            is required (line 3, column 5)",
                            invalid)
                        .to_string())
     } else {
@@ -69,10 +88,10 @@ fn invalid_against_schema_wrong_type() {
     let invalid = resource("example_wrong_type.factfile");
     let res = parse(&invalid, None, OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    format!("'{}' is not a valid factotum factfile: \
                             '/data/tasks/0/onResult/terminateJobWithSuccess/0' - Type of the \
-                            value is wrong (The value must be integer)",
+                            value is wrong (The value must be integer) (line 13, column 50)",
                            invalid)
                        .to_string())
     } else {
@@ -85,23 +104,36 @@ fn invalid_ambiguous_on_result() {
     let invalid = resource("example_invalid_terminate_continue_same.factfile");
     let res = parse(&invalid, None, OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    format!("'{}' is not a valid factotum factfile: the task 'ambi' has \
-                            conflicting actions.",
+                            conflicting actions. (line 12, column 13)",
                            invalid))
     } else {
         panic!("conflicting actions in onResult should fail");
     }
 }
 
+#[test]
+fn all_problems_are_reported() {
+    // Transform depends on a task with problems, so it isn't reported as well
+    let invalid = resource("example_invalid_many_problems.factfile");
+    assert_eq!(parse(&invalid, None, OverrideResultMappings::None).err().unwrap().message,
+               format!("'{}' is not a valid factotum factfile: the task 'Extract' has no way \
+                        to continue successfully. (line 14, column 21)\n\
+                        the task 'Load' has conflicting actions. (line 34, column 17)\n\
+                        the task 'Load' depends on 'Stage', which isn't defined before it \
+                        (line 33, column 17)",
+                       invalid));
+}
+
 #[test]
 fn invalid_must_continue() {
     let invalid = resource("example_invalid_no_continue.factfile");
     let res = parse(&invalid, None, OverrideResultMappings::None);
     if let Err(msg) = res {
-        assert_eq!(msg,
+        assert_eq!(msg.message,
                    format!("'{}' is not a valid factotum factfile: the task 'continue' has no \
-                            way to continue successfully.",
+                            way to continue successfully. (line 14, column 21)",
                            invalid))
     } else {
         panic!("having no values in continue should fail");
@@ -176,3 +208,932 @@ fn overrides_set_noop_values() {
     }
 
 }
+
+#[test]
+fn valid_circuit_breakers_parsed() {
+    use factotum::factfile::{CircuitBreaker, FailureRate};
+    let valid = resource("example_circuit_breaker.factfile");
+
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    let tasks = factfile.get_tasks_in_order();
+
+    assert_eq!(tasks[0][0].circuit_breaker, CircuitBreaker::default());
+    assert_eq!(tasks[1][0].circuit_breaker,
+               CircuitBreaker {
+                   skip_if_failed_last: Some(3),
+                   skip_if_failure_rate: Some(FailureRate {
+                       threshold: 0.5,
+                       over_last: 10,
+                   }),
+               });
+
+    // unset optional fields aren't written into the compact form
+    assert!(factfile.raw.contains("\"skipIfFailedLast\":3"));
+    assert_eq!(factfile.raw.matches("skipIfFailedLast").count(), 1);
+    assert!(!factfile.raw.contains("null"));
+}
+
+#[test]
+fn sla_parsed() {
+    use chrono::NaiveTime;
+    use factotum::factfile::Sla;
+    let valid = resource("example_sla.factfile");
+
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.sla, Some(Sla::TimeOfDay(NaiveTime::from_hms(6, 30, 0))));
+
+    let env = Json::from_str("{\"deadline\":\"3h\"}").unwrap();
+    let factfile = parse(&valid, Some(env), OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.sla,
+               Some(Sla::Within(::std::time::Duration::from_secs(3 * 60 * 60))));
+
+    let env = Json::from_str("{\"deadline\":\"whenever\"}").unwrap();
+    assert!(parse(&valid, Some(env), OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .contains("'whenever' is not a valid sla"));
+
+    let no_sla = parse(&resource("example_ok.factfile"), None, OverrideResultMappings::None);
+    assert_eq!(no_sla.unwrap().sla, None);
+}
+
+#[test]
+fn expected_durations_parsed() {
+    use std::time::Duration;
+    let valid = resource("example_expected_duration.factfile");
+
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    let tasks = factfile.get_tasks_in_order();
+    assert_eq!(tasks[0][0].expected_duration, None);
+    assert_eq!(tasks[1][0].expected_duration, Some(Duration::from_secs(90)));
+
+    let invalid = resource("example_invalid_expected_duration.factfile");
+    assert!(parse(&invalid, None, OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'Export' has an invalid expectedDuration: 'a while' is not a \
+                    valid duration (expected a whole number followed by 's', 'm', 'h' or 'd') \
+                    (line 27, column 17)"));
+}
+
+#[test]
+fn wait_tasks_are_validated() {
+    let valid = resource("example_wait.factfile");
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    let tasks = factfile.get_tasks_in_order();
+    assert_eq!(tasks[0][0].executor, "wait");
+    assert_eq!(tasks[0][0].command, "tcp://db.internal:5432");
+    assert_eq!(tasks[0][0].arguments, vec!["--poll-interval=30s", "--timeout=2h"]);
+
+    let invalid = resource("example_invalid_wait.factfile");
+    assert!(parse(&invalid, None, OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'Wait for database' has an invalid wait: 'tcp://db.internal' must \
+                    be given as tcp://<host>:<port> (line 6, column 13)"));
+}
+
+#[test]
+fn assert_tasks_are_validated() {
+    let valid = resource("example_assert.factfile");
+    let factfile = parse(&valid, Some(Json::Object(BTreeMap::new())), OverrideResultMappings::None)
+        .unwrap();
+    assert_eq!(factfile.get_tasks_in_order()[1][0].arguments,
+               vec!["${Count rows} >= 1000"]);
+
+    assert!(parse(&valid, None, OverrideResultMappings::None).is_ok());
+
+    let invalid = resource("example_invalid_assert.factfile");
+    assert!(parse(&invalid, Some(Json::Object(BTreeMap::new())), OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'Enough rows' has an invalid assertion: expected an operator (==, \
+                    !=, <, <=, >, >=, =~ or !~), && or || but found '=>' (at character 15) \
+                    (line 20, column 13)"));
+}
+
+#[test]
+fn nested_factfile_tasks_are_validated() {
+    let valid = resource("example_nested.factfile");
+    let factfile = parse(&valid, Some(Json::Object(BTreeMap::new())), OverrideResultMappings::None)
+        .unwrap();
+    let task = &factfile.get_tasks_in_order()[0][0];
+    assert_eq!(task.executor, "factfile");
+    assert_eq!(task.arguments, vec!["region=eu-west-1"]);
+
+    let invalid = resource("example_invalid_nested.factfile");
+    assert!(parse(&invalid, None, OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'Load region' has an invalid override: 'region' should be a \
+                    variable override given as <name>=<value> (line 9, column 13)"));
+}
+
+#[test]
+fn for_each_tasks_are_expanded() {
+    let valid = resource("example_for_each.factfile");
+    let factfile = parse(&valid, Some(Json::Object(BTreeMap::new())), OverrideResultMappings::None)
+        .unwrap();
+    let names = |group: &Vec<&factfile::Task>| {
+        let mut names = group.iter().map(|t| t.name.clone()).collect::<Vec<String>>();
+        names.sort();
+        names
+    };
+
+    let tasks = factfile.get_tasks_in_order();
+    assert_eq!(tasks.len(), 4);
+    assert_eq!(names(&tasks[1]),
+               vec!["Load [2024-01-01]", "Load [2024-01-02]"]);
+    assert_eq!(tasks[1][0].depends_on, vec!["Extract"]);
+    assert!(tasks[1].iter().any(|t| t.arguments == vec!["--partition=2024-01-02"]));
+    assert_eq!(names(&tasks[2]),
+               vec!["Vacuum 2024-01-01", "Vacuum 2024-01-02"]);
+    assert_eq!(tasks[2][0].depends_on,
+               vec!["Load [2024-01-01]", "Load [2024-01-02]"]);
+    assert_eq!(tasks[3][0].depends_on,
+               vec!["Vacuum 2024-01-01", "Vacuum 2024-01-02"]);
+
+    let mut env = BTreeMap::new();
+    env.insert("partitions".to_string(), Json::String("b,a".to_string()));
+    let overridden = parse(&valid, Some(Json::Object(env)), OverrideResultMappings::None).unwrap();
+    assert_eq!(names(&overridden.get_tasks_in_order()[1]), vec!["Load [a]", "Load [b]"]);
+
+    let mut env = BTreeMap::new();
+    env.insert("partitions".to_string(), Json::String(" , ".to_string()));
+    assert!(parse(&valid, Some(Json::Object(env)), OverrideResultMappings::None)
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'Load' has no items in its forEach (line 25, column 17)\n\
+                    the task 'Vacuum ' has no items in its forEach"));
+
+    let from_defaults = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    assert_eq!(from_defaults.get_tasks_in_order()[1].len(), 2);
+}
+
+#[test]
+fn factfile_variables_are_defaults() {
+    let valid = resource("example_variables.factfile");
+
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.name, "Load eu-west-1");
+    assert_eq!(factfile.get_tasks_in_order()[0][0].arguments,
+               vec!["s3://logs/eu-west-1"]);
+
+    let env = Json::from_str("{\"region\":\"us-east-1\"}").unwrap();
+    let factfile = parse(&valid, Some(env), OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.name, "Load us-east-1");
+    assert_eq!(factfile.get_tasks_in_order()[0][0].arguments,
+               vec!["s3://logs/us-east-1"]);
+}
+
+#[test]
+fn computed_variables_use_command_output() {
+    let valid = resource("example_computed_variables.factfile");
+
+    let factfile = parse_for_run(&valid, None, OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.get_tasks_in_order()[0][0].arguments,
+               vec!["s3://logs/run=3"]);
+
+    // supplied values replace the command, which then isn't run
+    let env = Json::from_str("{\"latest_partition\":\"run=1\"}").unwrap();
+    let factfile = parse_for_run(&valid, Some(env), OverrideResultMappings::None).unwrap();
+    assert_eq!(factfile.get_tasks_in_order()[0][0].arguments,
+               vec!["s3://logs/run=1"]);
+}
+
+#[test]
+fn computed_variables_shown_as_written_until_run() {
+    let valid = resource("example_computed_variables.factfile");
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    assert!(factfile.get_tasks_in_order()[0][0].arguments[0].starts_with("s3://logs/$(printf"));
+
+    let invalid = resource("example_invalid_computed_variable.factfile");
+    assert!(parse(&invalid, None, OverrideResultMappings::None).is_ok());
+}
+
+#[test]
+fn invalid_computed_variable_err() {
+    let invalid = resource("example_invalid_computed_variable.factfile");
+    let res = parse_for_run(&invalid, None, OverrideResultMappings::None);
+    if let Err(msg) = res {
+        assert_eq!(msg.message,
+                   format!("'{}' is not a valid factotum factfile: the variable \
+                            'latest_partition' couldn't be computed: 'echo no partitions 1>&2; \
+                            exit 4' exited with 4 (no partitions)",
+                           invalid))
+    } else {
+        panic!("a failing variable command should fail the parse");
+    }
+}
+
+#[test]
+fn task_vars_are_only_used_by_their_task() {
+    let valid = resource("example_task_vars.factfile");
+
+    let factfile = parse(&valid, None, OverrideResultMappings::None).unwrap();
+    let events = factfile.find_task("Load events").unwrap();
+    assert_eq!(events.arguments, vec!["eu-west-1", "events"]);
+    let pages = factfile.find_task("Load pages").unwrap();
+    assert_eq!(pages.arguments, vec!["eu-west-1", "pages", "eu-west-1/pages"]);
+    assert_eq!(factfile.find_task("Report").unwrap().arguments, vec!["events"]);
+    assert_eq!(factfile.task_variables.keys().collect::<Vec<_>>(), vec!["Load pages"]);
+    assert_eq!(factfile.task_variables["Load pages"]["path"], "eu-west-1/pages");
+
+    let broken = fs::read_to_string(&valid)
+        .unwrap()
+        .replace("\"table\": \"pages\"", "\"table\": \"{{ path }}\"");
+    if let Err(msg) = parse_str(&broken,
+                                "broken",
+                                None,
+                                OverrideResultMappings::None,
+                                &ExecutorSettings::new(),
+                                false) {
+        assert_eq!(msg.message,
+                   "'broken' is not a valid factotum factfile: the task 'Load {{ table }}' has \
+                    invalid vars: the variables refer to each other in a cycle: path -> table \
+                    -> path (line 10, column 13)\nthe task 'Report' depends on 'Load pages', \
+                    which isn't defined before it (line 41, column 17)");
+    } else {
+        panic!("the vars' cycle wasn't rejected")
+    }
+}
+
+#[test]
+fn declared_variables_are_checked() {
+    let factfile = resource("example_declarations.factfile");
+
+    let env = Json::from_str("{\"run_date\":\"2024-01-01\",\"target\":\"prod\"}").unwrap();
+    let parsed = parse(&factfile, Some(env), OverrideResultMappings::None).unwrap();
+    assert_eq!(parsed.get_tasks_in_order()[0][0].arguments,
+               vec!["2024-01-01", "100", "prod"]);
+
+    let env = Json::from_str("{\"batch_size\":\"lots\",\"target\":\"qa\"}").unwrap();
+    if let Err(msg) = parse(&factfile, Some(env), OverrideResultMappings::None) {
+        assert_eq!(msg.message,
+                   format!("'{}' is not a valid factotum factfile: missing/invalid variables - \
+                            'batch_size' should be an int but was 'lots', 'run_date' is \
+                            required but wasn't supplied, 'target' should be one of [dev, \
+                            prod] but was 'qa'",
+                           factfile))
+    } else {
+        panic!("invalid variables should fail the parse");
+    }
+
+    assert!(parse(&factfile, None, OverrideResultMappings::None).is_ok());
+}
+
+#[test]
+fn generated_tasks_good() {
+    let tasks = parse_generated_tasks(r#"{"tasks": [{"name": "load a", "executor": "shell",
+        "command": "load", "arguments": ["a"], "dependsOn": [], "expectedDuration": "5m",
+        "onResult": {"terminateJobWithSuccess": [3], "continueJob": [0]}}]}"#)
+        .unwrap();
+
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0].name, "load a");
+    assert_eq!(tasks[0].arguments, vec!["a"]);
+    assert_eq!(tasks[0].on_result.terminate_job, vec![3]);
+    assert!(tasks[0].expected_duration.is_some());
+}
+
+#[test]
+fn generated_tasks_bad() {
+    assert!(parse_generated_tasks("[1, 2]")
+        .unwrap_err()
+        .starts_with("the output isn't a list of tasks ("));
+    assert_eq!(parse_generated_tasks(r#"{"tasks": [{"name": "a", "executor": "shell",
+        "command": "a", "arguments": [], "dependsOn": [],
+        "onResult": {"terminateJobWithSuccess": [0], "continueJob": [0]}}]}"#),
+               Err("the task 'a' has conflicting actions.".to_string()));
+}
+
+#[test]
+fn group_dependencies_are_expanded() {
+    let factfile = parse(&resource("example_groups.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.groups.get("extract"),
+               Some(&vec!["Extract orders".to_string(), "Extract customers".to_string()]));
+    assert_eq!(factfile.find_task("Merge").unwrap().depends_on,
+               vec!["Extract orders", "Extract customers"]);
+
+    let invalid = resource("example_invalid_group.factfile");
+    assert_eq!(parse(&invalid, None, OverrideResultMappings::None).err().unwrap().message,
+               format!("'{}' is not a valid factotum factfile: the task 'Merge' depends on the \
+                        group 'extract', but no task before it is in that group \
+                        (line 11, column 17)",
+                       invalid));
+}
+
+#[test]
+fn pattern_matches_globs() {
+    assert!(pattern_matches("load-*", "load-orders"));
+    assert!(pattern_matches("load-*", "load-"));
+    assert!(pattern_matches("*-orders", "load-orders"));
+    assert!(pattern_matches("load-?", "load-a"));
+    assert!(pattern_matches("l*d-*s", "load-orders"));
+    assert!(!pattern_matches("load-?", "load-ab"));
+    assert!(!pattern_matches("load-*", "loader"));
+    assert!(!pattern_matches("load", "load-orders"));
+}
+
+#[test]
+fn dependency_patterns_are_expanded() {
+    let factfile = parse(&resource("example_dependency_patterns.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Report").unwrap().depends_on,
+               vec!["load-orders", "load-customers"]);
+
+    let invalid = resource("example_invalid_dependency_pattern.factfile");
+    assert_eq!(parse(&invalid, None, OverrideResultMappings::None).err().unwrap().message,
+               format!("'{}' is not a valid factotum factfile: the task 'Report' depends on \
+                        'load-*', which doesn't match any task before it (line 11, column 17)",
+                       invalid));
+}
+
+#[test]
+fn barriers_are_checked() {
+    let factfile = parse(&resource("example_barrier.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+    assert_eq!(factfile.find_task("Loaded").unwrap().depends_on,
+               vec!["Load orders", "Load customers"]);
+
+    let invalid = resource("example_invalid_barrier.factfile");
+    assert_eq!(parse(&invalid, None, OverrideResultMappings::None).err().unwrap().message,
+               format!("'{}' is not a valid factotum factfile: the task 'Loaded' is a barrier, \
+                        so it can't have a command or arguments (line 19, column 13)",
+                       invalid));
+}
+
+#[test]
+fn disabled_tasks_are_kept() {
+    let factfile = parse(&resource("example_disabled.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert!(!factfile.find_task("Extract").unwrap().disabled);
+    assert!(factfile.find_task("Vacuum").unwrap().disabled);
+    assert!(!factfile.find_task("Load").unwrap().disabled);
+    assert_eq!(factfile.find_task("Load").unwrap().depends_on, vec!["Vacuum"]);
+}
+
+#[test]
+fn task_metadata_is_templated() {
+    let factfile = parse(&resource("example_task_metadata.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    let extract = factfile.find_task("Extract").unwrap();
+    assert_eq!(extract.description,
+               Some("Pulls yesterday's orders from the API".to_string()));
+    assert_eq!(extract.owner, Some("data-eng@acme.com".to_string()));
+    assert_eq!(factfile.find_task("Load").unwrap().owner, None);
+}
+
+#[test]
+fn task_stdin_is_templated() {
+    let factfile = parse(&resource("example_stdin.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Query").unwrap().stdin,
+               Some(factfile::TaskInput::Text("SELECT count(*) FROM orders;".to_string())));
+    assert_eq!(factfile.find_task("Import").unwrap().stdin,
+               Some(factfile::TaskInput::File("/var/exports/orders.csv".to_string())));
+}
+
+#[test]
+fn task_stdin_bad() {
+    let invalid = resource("example_invalid_stdin.factfile");
+    assert_eq!(parse(&invalid, None, OverrideResultMappings::None).err().unwrap().message,
+               format!("'{}' is not a valid factotum factfile: the task 'Query' has both stdin \
+                        and a stdinFile, only one can be given (line 6, column 13)",
+                       invalid));
+
+    assert_eq!(task_input("Ready", "wait", Some("hello".to_string()), None),
+               Err("the task 'Ready' uses the 'wait' executor, which can't be given stdin"
+                   .to_string()));
+    assert_eq!(task_input("Query", "shell", None, None), Ok(None));
+}
+
+#[test]
+fn task_shell_good_and_bad() {
+    let tasks = parse_generated_tasks(r#"{"tasks": [{"name": "load", "executor": "shell",
+                                                     "command": "./load", "arguments": [],
+                                                     "dependsOn": [], "shell": "none",
+                                                     "onResult": {"terminateJobWithSuccess": [],
+                                                                  "continueJob": [0]}}]}"#)
+        .unwrap();
+    assert_eq!(tasks[0].shell, factfile::Shell::Exec);
+
+    assert_eq!(task_shell("load", "shell", &None), Ok(factfile::Shell::Sh));
+    assert_eq!(task_shell("load", "shell", &Some("bash".to_string())),
+               Ok(factfile::Shell::Bash));
+    assert_eq!(task_shell("load", "shell", &Some("pwsh".to_string())),
+               Ok(factfile::Shell::Pwsh));
+    assert_eq!(task_shell("load", "shell", &Some("zsh".to_string())),
+               Err("the task 'load' has an unknown shell 'zsh', it must be sh, bash, pwsh or none"
+                   .to_string()));
+    assert_eq!(task_shell("ready", "wait", &Some("bash".to_string())),
+               Err("the task 'ready' uses the 'wait' executor, which doesn't run in a shell"
+                   .to_string()));
+}
+
+#[test]
+fn task_when_upstream_skipped_good_and_bad() {
+    let tasks = parse_generated_tasks(r#"{"tasks": [{"name": "load", "executor": "shell",
+                                                     "command": "./load", "arguments": [],
+                                                     "dependsOn": [], "whenUpstreamSkipped": "run",
+                                                     "onResult": {"terminateJobWithSuccess": [],
+                                                                  "continueJob": [0]}}]}"#)
+        .unwrap();
+    assert_eq!(tasks[0].when_upstream_skipped, Some(factfile::UpstreamSkipped::Run));
+
+    assert_eq!(task_when_upstream_skipped("load", &None), Ok(None));
+    assert_eq!(task_when_upstream_skipped("load", &Some("fail".to_string())),
+               Ok(Some(factfile::UpstreamSkipped::Fail)));
+    assert_eq!(task_when_upstream_skipped("load", &Some("maybe".to_string())),
+               Err("the task 'load' has an unknown whenUpstreamSkipped 'maybe', it must be run, \
+                    skip or fail"
+                   .to_string()));
+}
+
+#[test]
+fn task_idle_timeout_good_and_bad() {
+    use std::time::Duration;
+    let tasks = parse_generated_tasks(r#"{"tasks": [{"name": "load", "executor": "shell",
+                                                     "command": "./load", "arguments": [],
+                                                     "dependsOn": [], "idleTimeout": "10m",
+                                                     "onResult": {"terminateJobWithSuccess": [],
+                                                                  "continueJob": [0]}}]}"#)
+        .unwrap();
+    assert_eq!(tasks[0].idle_timeout, Some(Duration::from_secs(600)));
+
+    assert_eq!(task_idle_timeout("load", &None), Ok(None));
+    assert_eq!(task_idle_timeout("load", &Some("0s".to_string())),
+               Err("the task 'load' has an idleTimeout of 0, it must be longer".to_string()));
+    assert_eq!(task_idle_timeout("load", &Some("a bit".to_string())),
+               Err("the task 'load' has an invalid idleTimeout: 'a bit' is not a valid duration \
+                    (expected a whole number followed by 's', 'm', 'h' or 'd')"
+                   .to_string()));
+}
+
+#[test]
+fn task_when_is_templated_and_checked() {
+    let conf = Some(Json::from_str("{\"env\":\"prod & dev\"}").unwrap());
+    let earlier = vec!["extract".to_string()];
+    let when = |w: &str, conf: &Option<Json>| {
+        task_when("load", &Some(w.to_string()), conf, Some(&earlier))
+    };
+
+    assert_eq!(when("'{{ env }}' == prod && tasks.extract.exit_code == 0", &conf),
+               Ok(Some("'prod & dev' == prod && tasks.extract.exit_code == 0".to_string())));
+    assert_eq!(when("{{ env }} == prod", &None),
+               Ok(Some("{{ env }} == prod".to_string())));
+    assert_eq!(task_when("load", &None, &conf, Some(&earlier)), Ok(None));
+    assert_eq!(when("tasks.report.state == SKIPPED", &conf),
+               Err("the task 'load' has a when expression that refers to 'report', which isn't \
+                    defined before it"
+                   .to_string()));
+    assert_eq!(when("tasks.extract.exit_code = 0", &conf),
+               Err("the task 'load' has an invalid when expression: expected an operator (==, \
+                    !=, <, <=, >, >=, =~ or !~), && or || but found '=' (at character 25)"
+                   .to_string()));
+    // generated tasks can't be checked against the tasks before them
+    assert_eq!(task_when("load", &Some("tasks.report.state == SKIPPED".to_string()), &None, None),
+               Ok(Some("tasks.report.state == SKIPPED".to_string())));
+}
+
+#[test]
+fn task_arguments_are_escaped() {
+    let factfile = parse(&resource("example_escape.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Count").unwrap().arguments,
+               vec!["orders; DROP TABLE users", "--verbose --dry-run", "--verbose --dry-run"]);
+    assert_eq!(factfile.find_task("Archive").unwrap().arguments,
+               vec!["orders; DROP TABLE users"]);
+
+    let mut conf = BTreeMap::new();
+    conf.insert("table".to_string(), Json::String("$(whoami)\"`".to_string()));
+    let factfile = parse(&resource("example_escape.factfile"),
+                         Some(Json::Object(conf)),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Count").unwrap().arguments[0],
+               "\\$(whoami)\\\"\\`");
+    assert_eq!(factfile.find_task("Report").unwrap().arguments,
+               vec!["`$(whoami)`\"``"]);
+    assert_eq!(factfile.find_task("Archive").unwrap().arguments,
+               vec!["$(whoami)\"`"]);
+}
+
+#[test]
+fn task_escape_good_and_bad() {
+    let sh = factfile::Shell::Sh;
+    assert_eq!(task_escape("load", "shell", &sh, &None), Ok(None));
+    assert_eq!(task_escape("load", "shell", &sh, &Some("shell".to_string())),
+               Ok(Some(templater::sh_escape as templater::Escape)));
+    assert_eq!(task_escape("load", "shell", &factfile::Shell::Exec, &Some("shell".to_string())),
+               Ok(Some(templater::no_escape as templater::Escape)));
+    assert_eq!(task_escape("ready", "wait", &sh, &Some("shell".to_string())),
+               Ok(Some(templater::no_escape as templater::Escape)));
+    assert_eq!(task_escape("load", "shell", &sh, &Some("html".to_string())),
+               Err("the task 'load' has an unknown escape 'html', it must be shell or none"
+                   .to_string()));
+}
+
+#[test]
+fn escaped_arguments_stay_literal_in_a_shell() {
+    use factotum::executor::task_command;
+    use factotum::executor::execution_strategy::{execute_os, SpillPolicy};
+    use factotum::tests::make_task;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    let value = "$(echo injected) `echo injected` \\\" ; echo injected";
+    let mut task = make_task("greet", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "printf %s".to_string();
+    task.arguments = vec![templater::sh_escape(value)];
+
+    for shell in vec![factfile::Shell::Sh, factfile::Shell::Bash] {
+        task.shell = shell;
+        let mut command = task_command(&task, &HashMap::new(), Path::new("/tmp/nested"));
+        let result = execute_os("greet", &mut command, &SpillPolicy::default());
+        assert_eq!(result.stdout, Some(value.to_string()));
+    }
+}
+
+#[test]
+fn task_sandbox_is_templated() {
+    let factfile = parse(&resource("example_sandbox.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Transform").unwrap().sandbox,
+               Some(factfile::Sandbox {
+                   writable_paths: vec!["/var/exports".to_string()],
+                   ..factfile::Sandbox::default()
+               }));
+    assert_eq!(factfile.find_task("Upload").unwrap().sandbox,
+               Some(factfile::Sandbox {
+                   network: true,
+                   read_only_root: false,
+                   writable_paths: vec![],
+                   seccomp: false,
+               }));
+    assert_eq!(factfile.find_task("Notify").unwrap().sandbox, None);
+}
+
+#[test]
+fn task_constraints_are_templated() {
+    let factfile = parse(&resource("example_constraints.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    assert_eq!(factfile.find_task("Extract").unwrap().constraints,
+               Some(factfile::TaskConstraints {
+                   constraints: Constraints {
+                       host: Some("etl-1".to_string()),
+                       window: None,
+                       check: Some("test -f /data/ready".to_string()),
+                   },
+                   fail_job: false,
+               }));
+    assert_eq!(factfile.find_task("Load").unwrap().constraints,
+               Some(factfile::TaskConstraints {
+                   constraints: Constraints {
+                       window: Some(timewindow::parse_time_window("22:00-06:00,Europe/London")
+                           .unwrap()),
+                       ..Constraints::default()
+                   },
+                   fail_job: true,
+               }));
+    assert_eq!(factfile.find_task("Notify").unwrap().constraints, None);
+}
+
+#[test]
+fn task_constraints_bad() {
+    let constraints = |window: &str, when_unmet: &str| {
+        Some(FactfileConstraintsFormat {
+            host: None,
+            window: Some(window.to_string()),
+            check: None,
+            whenUnmet: Some(when_unmet.to_string()),
+        })
+    };
+    assert_eq!(task_constraints("load", &constraints("22:00-06:00", "later"), &None),
+               Err("the task 'load' has an unknown whenUnmet 'later', it must be skip or fail"
+                   .to_string()));
+    assert!(task_constraints("load", &constraints("22:00", "skip"), &None)
+        .unwrap_err()
+        .starts_with("the task 'load' has an invalid window constraint: "));
+}
+
+#[test]
+fn task_workspace_is_templated() {
+    let mut vars = BTreeMap::new();
+    vars.insert("workspace".to_string(), Json::String("/scratch/abc".to_string()));
+    let factfile = parse(&resource("example_workspace.factfile"),
+                         Some(Json::Object(vars)),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    let extract = factfile.find_task("Extract").unwrap();
+    assert_eq!(extract.arguments, vec!["--out=/scratch/abc/Extract/rows.csv"]);
+    assert_eq!(extract.workspace,
+               Some(factfile::TaskWorkspace {
+                   run_dir: "/scratch/abc".to_string(),
+                   task_dir: Some("/scratch/abc/Extract".to_string()),
+               }));
+    let load = factfile.find_task("Load").unwrap();
+    assert_eq!(load.arguments, vec!["/scratch/abc/Extract/rows.csv"]);
+    assert_eq!(load.workspace,
+               Some(factfile::TaskWorkspace {
+                   run_dir: "/scratch/abc".to_string(),
+                   task_dir: None,
+               }));
+
+    // the workspace changes every run, the job's reference doesn't
+    let mut other_vars = BTreeMap::new();
+    other_vars.insert("workspace".to_string(), Json::String("/scratch/def".to_string()));
+    let other = parse(&resource("example_workspace.factfile"),
+                      Some(Json::Object(other_vars)),
+                      OverrideResultMappings::None)
+        .unwrap();
+    assert_eq!(factfile.raw, other.raw);
+
+    let without = parse(&resource("example_workspace.factfile"),
+                        Some(Json::Object(BTreeMap::new())),
+                        OverrideResultMappings::None)
+        .unwrap();
+    assert_eq!(without.find_task("Extract").unwrap().workspace, None);
+}
+
+#[test]
+fn task_cache_is_templated() {
+    let factfile = parse(&resource("example_cache.factfile"),
+                         Some(Json::Object(BTreeMap::new())),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    let mut variables = BTreeMap::new();
+    variables.insert("region".to_string(), "eu-west-1".to_string());
+    assert_eq!(factfile.find_task("Train").unwrap().cache,
+               Some(factfile::TaskCache {
+                   inputs: vec!["tests/resources/example_ok.factfile".to_string()],
+                   variables: variables,
+                   enabled: false,
+                   last_success: None,
+               }));
+    assert_eq!(factfile.find_task("Publish").unwrap().cache, None);
+
+    let generated = parse_generated_tasks(r#"{"tasks": [{"name": "load", "executor": "shell",
+                                                         "command": "./load.sh",
+                                                         "arguments": [], "dependsOn": [],
+                                                         "cache": {"variables": ["region"]},
+                                                         "onResult": {
+                                                             "terminateJobWithSuccess": [],
+                                                             "continueJob": [0]}}]}"#)
+        .unwrap();
+    assert_eq!(generated[0].cache, Some(factfile::TaskCache::default()));
+}
+
+#[test]
+fn task_cache_bad() {
+    let mut vars = BTreeMap::new();
+    vars.insert("region".to_string(), Json::Null);
+    let cache = FactfileCacheFormat {
+        inputs: None,
+        variables: Some(vec!["zone".to_string()]),
+    };
+    assert_eq!(task_cache("load", &Some(cache), &Some(Json::Object(vars))),
+               Err("the task 'load' is cached on the variable 'zone', which has no value"
+                   .to_string()));
+}
+
+#[test]
+fn task_produces_is_templated() {
+    let mut vars = BTreeMap::new();
+    vars.insert("date".to_string(), Json::String("2021-01-01".to_string()));
+    let produces = Some(vec!["/data/{{ date }}/out.csv".to_string()]);
+    assert_eq!(task_produces("export", &produces, &Some(Json::Object(vars))),
+               Ok(vec!["/data/2021-01-01/out.csv".to_string()]));
+    assert_eq!(task_produces("export", &None, &None), Ok(vec![]));
+}
+
+#[test]
+fn task_produces_bad() {
+    let produces = Some(vec!["s3://bucket/out.csv".to_string()]);
+    assert_eq!(task_produces("export", &produces, &None),
+               Err("the task 'export' produces 's3://bucket/out.csv', but only local files can \
+                    be checksummed"
+                   .to_string()));
+}
+
+#[test]
+fn task_sandbox_bad() {
+    let generated = parse_generated_tasks(r#"{"tasks": [{"name": "ready", "executor": "wait",
+                                                         "command": "file:/tmp/ready",
+                                                         "arguments": [], "dependsOn": [],
+                                                         "sandbox": {},
+                                                         "onResult": {
+                                                             "terminateJobWithSuccess": [],
+                                                             "continueJob": [0]}}]}"#);
+    assert_eq!(generated.err(),
+               Some("the task 'ready' uses the 'wait' executor, which can't be sandboxed"
+                   .to_string()));
+}
+
+#[test]
+fn result_overrides_are_parsed() {
+    let specs = vec!["load:continue=0, 3".to_string(),
+                     "load:terminate=1".to_string(),
+                     "ns:check:continue=0".to_string()];
+    let overrides = parse_result_overrides(&specs).unwrap();
+    assert_eq!(overrides.get("load"),
+               Some(&TaskResultOverride {
+                   continue_job: Some(vec![0, 3]),
+                   terminate_early: Some(vec![1]),
+               }));
+    assert_eq!(overrides.get("ns:check").and_then(|o| o.continue_job.clone()),
+               Some(vec![0]));
+
+    assert!(parse_result_overrides(&vec!["load".to_string()]).is_err());
+    assert_eq!(parse_result_overrides(&vec!["load:continue".to_string()]),
+               Err("the override 'load:continue' must be given as <task>:continue=<codes> or \
+                    <task>:terminate=<codes>"
+                   .to_string()));
+    assert_eq!(parse_result_overrides(&vec!["load:continue=".to_string()]),
+               Err("the override 'load:continue=' has no return codes".to_string()));
+    assert_eq!(parse_result_overrides(&vec!["load:continue=x".to_string()]),
+               Err("the override 'load:continue=x' has an invalid return code 'x'".to_string()));
+    assert_eq!(parse_result_overrides(&vec!["load:skip=1".to_string()]),
+               Err("the override 'load:skip=1' has an unknown action 'skip', it must be \
+                    continue or terminate"
+                   .to_string()));
+}
+
+#[test]
+fn result_overrides_apply_to_one_task() {
+    let overrides = parse_result_overrides(&vec!["StorageLoader:continue=0,3".to_string()])
+        .unwrap();
+    let factfile = parse(&resource("example_ok.factfile"),
+                         None,
+                         OverrideResultMappings::Tasks(overrides))
+        .unwrap();
+
+    let loader = factfile.find_task("StorageLoader").unwrap();
+    assert_eq!(loader.on_result.continue_job, vec![0, 3]);
+    assert_eq!(loader.on_result.terminate_job, Vec::<i32>::new());
+    let runner = factfile.find_task("SQL Runner").unwrap();
+    assert_eq!(runner.on_result.continue_job, vec![0]);
+    assert_eq!(runner.on_result.terminate_job, vec![3]);
+}
+
+#[test]
+fn result_overrides_must_name_a_task() {
+    let overrides = parse_result_overrides(&vec!["Nope:continue=0".to_string()]).unwrap();
+    assert!(parse(&resource("example_ok.factfile"),
+                  None,
+                  OverrideResultMappings::Tasks(overrides))
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the results of the task 'Nope' are overridden, but there's no such task"));
+
+    let overrides = parse_result_overrides(&vec!["EmrEtlRunner:terminate=0".to_string()])
+        .unwrap();
+    assert!(parse(&resource("example_ok.factfile"),
+                  None,
+                  OverrideResultMappings::Tasks(overrides))
+        .err()
+        .unwrap()
+        .message
+        .ends_with("the task 'EmrEtlRunner' has no way to continue successfully. \
+                    (line 14, column 21)"));
+}
+
+#[test]
+fn defaults_are_applied_to_tasks() {
+    let factfile = parse(&resource("example_defaults.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    let extract = factfile.find_task("Extract").unwrap();
+    assert_eq!(extract.executor, "shell");
+    assert_eq!(extract.arguments, Vec::<String>::new());
+    assert_eq!(extract.on_result.terminate_job, vec![3]);
+
+    let load = factfile.find_task("Load").unwrap();
+    assert_eq!(load.arguments, vec!["--all"]);
+    assert_eq!(load.on_result.terminate_job, Vec::<i32>::new());
+}
+
+#[test]
+fn task_templates_are_instantiated() {
+    let env = Json::from_str("{\"region\": \"eu-west-1\"}").unwrap();
+    let factfile = parse(&resource("example_task_templates.factfile"),
+                         Some(env),
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    let events = factfile.find_task("Load events").unwrap();
+    assert_eq!(events.command, "./load.sh");
+    assert_eq!(events.arguments, vec!["--table=events", "--region=eu-west-1"]);
+
+    let users = factfile.find_task("Load users").unwrap();
+    assert_eq!(users.command, "./load-slowly.sh");
+    assert_eq!(users.arguments, vec!["--table=users", "--region=eu-west-1"]);
+}
+
+#[test]
+fn references_are_resolved() {
+    let factfile = parse(&resource("example_references.factfile"),
+                         None,
+                         OverrideResultMappings::None)
+        .unwrap();
+
+    for name in vec!["Load events", "Load users"] {
+        let task = factfile.find_task(name).unwrap();
+        assert_eq!(task.arguments, vec!["--all", "--compress"]);
+        assert_eq!(task.on_result.terminate_job, vec![3]);
+        assert_eq!(task.on_result.continue_job, vec![0]);
+    }
+}
+
+#[test]
+fn every_malformed_template_is_reported() {
+    let json = r#"{"data": {"name": "{{#a}}", "tasks": [{"arguments": ["ok", "{{/b}}"]}]}}"#;
+    let problems = check_templates(json).err().unwrap();
+    let problems = problems.lines().collect::<Vec<&str>>();
+    assert_eq!(problems.len(), 2);
+    assert!(problems[0].starts_with("'/data/name' - the template '{{#a}}' is invalid"));
+    assert!(problems[1].starts_with("'/data/tasks/0/arguments/1' - the template '{{/b}}'"));
+
+    assert!(check_templates(r#"{"data": {"name": "{{ a }}"}}"#).is_ok());
+}
+
+#[test]
+fn parse_errors_have_a_kind() {
+    let kind = |file: &str| parse(file, None, OverrideResultMappings::None).err().unwrap().kind;
+    assert_eq!(kind("./tests/resources/no_such.factfile"), ErrorKind::Io);
+    assert_eq!(kind(&resource("invalid_json.factfile")), ErrorKind::Syntax);
+    assert_eq!(kind(&resource("example_wrong_type.factfile")), ErrorKind::Validation);
+    assert_eq!(kind(&resource("example_invalid_no_continue.factfile")),
+               ErrorKind::Validation);
+}
+
+#[test]
+fn task_tty_good_and_bad() {
+    let tasks = parse_generated_tasks(r#"{"tasks": [{"name": "load", "executor": "shell",
+                                                     "command": "./load", "arguments": [],
+                                                     "dependsOn": [], "tty": true,
+                                                     "onResult": {"terminateJobWithSuccess": [],
+                                                                  "continueJob": [0]}}]}"#)
+        .unwrap();
+    assert!(tasks[0].tty);
+
+    assert_eq!(task_tty("load", "shell", None), Ok(false));
+    assert_eq!(task_tty("ready", "wait", Some(false)), Ok(false));
+    assert_eq!(task_tty("ready", "wait", Some(true)),
+               Err("the task 'ready' uses the 'wait' executor, which can't run under a \
+                    pseudo-terminal"
+                   .to_string()));
+}
+
+#[test]
+fn executor_settings_are_the_ones_given() {
+    let json = r#"{"schema": "iglu:x/y/jsonschema/1-0-0",
+                   "data": {"tasks": [{"name": "a", "executor": "spark", "arguments": []}]}}"#;
+    let mut configured = ExecutorSettings::new();
+    configured.insert("spark".to_string(), vec!["master=yarn".to_string()]);
+
+    assert!(expanded_source(json, &configured)
+        .unwrap()
+        .contains("\"arguments\":[\"master=yarn\"]"));
+    assert_eq!(expanded_source(json, &ExecutorSettings::new()), Ok(json.to_string()));
+}
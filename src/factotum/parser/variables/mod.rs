@@ -0,0 +1,273 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::preflight;
+use rustc_serialize::json::Json;
+use std::collections::{BTreeMap, BTreeSet};
+use super::templater;
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableType {
+    String,
+    Int,
+    Bool,
+    Enum(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    pub var_type: VariableType,
+    pub required: bool,
+}
+
+// variables declared in the factfile are defaults, anything supplied at run time wins
+pub fn merge_variables(defaults: &Option<BTreeMap<String, String>>,
+                       conf: Option<Json>,
+                       compute: bool)
+                       -> Result<Option<Json>, String> {
+    match (defaults.as_ref(), conf) {
+        (None, conf) => Ok(conf),
+        (Some(defaults), conf) => {
+            let supplied = match conf {
+                Some(Json::Object(supplied)) => supplied,
+                _ => BTreeMap::new(),
+            };
+            let mut merged = BTreeMap::new();
+            for (name, value) in defaults.iter() {
+                if !supplied.contains_key(name) {
+                    let value = try!(compute_variable(name, value, compute));
+                    merged.insert(name.clone(), Json::String(value));
+                }
+            }
+            merged.extend(supplied);
+            Ok(Some(Json::Object(merged)))
+        }
+    }
+}
+
+// the variables a value's templates refer to, e.g. "company" and "env" for
+// "{{ company }}-{{ env }}-data"
+fn references(value: &Json) -> BTreeSet<String> {
+    match *value {
+        Json::String(ref s) => {
+            preflight::template_variables(s).iter().map(|v| templater::variable_root(v)).collect()
+        }
+        Json::Array(ref items) => items.iter().flat_map(|i| references(i)).collect(),
+        Json::Object(ref fields) => fields.values().flat_map(|v| references(v)).collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn render_value(value: &Json, vars: &Json) -> Result<Json, String> {
+    match *value {
+        // the value is escaped where it's finally used, so it isn't escaped here too
+        Json::String(ref s) if s.contains("{{") => {
+            templater::decorate_str_escaped(s, vars, templater::no_escape)
+                .map(Json::String)
+                .map_err(String::from)
+        }
+        Json::Array(ref items) => {
+            let mut rendered = vec![];
+            for item in items.iter() {
+                rendered.push(try!(render_value(item, vars)));
+            }
+            Ok(Json::Array(rendered))
+        }
+        Json::Object(ref fields) => {
+            let mut rendered = BTreeMap::new();
+            for (key, field) in fields.iter() {
+                rendered.insert(key.clone(), try!(render_value(field, vars)));
+            }
+            Ok(Json::Object(rendered))
+        }
+        ref other => Ok(other.clone()),
+    }
+}
+
+fn resolve_variable(name: &str,
+                    vars: &mut BTreeMap<String, Json>,
+                    resolving: &mut Vec<String>,
+                    resolved: &mut BTreeSet<String>)
+                    -> Result<(), String> {
+    if resolved.contains(name) {
+        return Ok(());
+    }
+    if let Some(start) = resolving.iter().position(|r| r == name) {
+        let mut cycle = resolving[start..].to_vec();
+        cycle.push(name.to_string());
+        return Err(format!("the variables refer to each other in a cycle: {}",
+                           cycle.join(" -> ")));
+    }
+
+    let value = match vars.get(name) {
+        Some(value) => value.clone(),
+        None => return Ok(()),
+    };
+    let refs = references(&value);
+
+    resolving.push(name.to_string());
+    for reference in refs.iter() {
+        try!(resolve_variable(reference, vars, resolving, resolved));
+    }
+    resolving.pop();
+
+    // a variable that's only known later (e.g. "item" in a forEach) can't be resolved
+    // yet, so the value's left as it's written
+    if refs.iter().all(|r| vars.contains_key(r)) {
+        let rendered = try!(render_value(&value, &Json::Object(vars.clone()))
+            .map_err(|e| format!("the variable '{}' couldn't be resolved: {}", name, e)));
+        vars.insert(name.to_string(), rendered);
+    }
+    resolved.insert(name.to_string());
+    Ok(())
+}
+
+// variables can be composed from others, e.g. a bucket of "{{ company }}-{{ env }}-data";
+// each is resolved after the variables it refers to
+pub fn resolve_variables(conf: Option<Json>) -> Result<Option<Json>, String> {
+    let mut vars = match conf {
+        Some(Json::Object(vars)) => vars,
+        other => return Ok(other),
+    };
+
+    let names = vars.keys().cloned().collect::<Vec<String>>();
+    let mut resolved = BTreeSet::new();
+    for name in names.iter() {
+        try!(resolve_variable(name, &mut vars, &mut vec![], &mut resolved));
+    }
+    Ok(Some(Json::Object(vars)))
+}
+
+// a task's own vars are layered over the (already resolved) job variables for that task
+// alone; they can be composed from the job's variables and each other
+pub fn task_variables(vars: &Option<BTreeMap<String, String>>,
+                      conf: &Option<Json>,
+                      compute: bool)
+                      -> Result<Option<Json>, String> {
+    let (vars, job_vars) = match (vars.as_ref(), conf.as_ref()) {
+        (Some(vars), Some(&Json::Object(ref job_vars))) => (vars, job_vars),
+        _ => return Ok(conf.clone()),
+    };
+
+    let mut merged = job_vars.clone();
+    for (name, value) in vars.iter() {
+        merged.insert(name.clone(), Json::String(try!(compute_variable(name, value, compute))));
+    }
+
+    // the job's variables are resolved already, so only the task's are rendered here
+    let mut resolved = job_vars.keys()
+        .filter(|name| !vars.contains_key(*name))
+        .cloned()
+        .collect::<BTreeSet<String>>();
+    for name in vars.keys() {
+        try!(resolve_variable(name, &mut merged, &mut vec![], &mut resolved));
+    }
+    Ok(Some(Json::Object(merged)))
+}
+
+// the command a variable written as $(command) is computed by
+pub fn computed_command(value: &str) -> Option<&str> {
+    if value.starts_with("$(") && value.ends_with(")") {
+        Some(&value[2..value.len() - 1])
+    } else {
+        None
+    }
+}
+
+// a variable written as $(command) takes the trimmed stdout of the command; until the job
+// really runs it's shown as it's written
+fn compute_variable(name: &str, value: &str, compute: bool) -> Result<String, String> {
+    let command = match computed_command(value) {
+        Some(command) if compute => command,
+        _ => return Ok(value.to_string()),
+    };
+    info!("computing variable '{}' from '{}'", name, command);
+
+    let output = try!(Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| format!("the variable '{}' couldn't be computed: {}", name, e)));
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        let code = output.status
+            .code()
+            .map(|c| c.to_string())
+            .unwrap_or("a signal".to_string());
+        Err(format!("the variable '{}' couldn't be computed: '{}' exited with {} ({})",
+                    name,
+                    command,
+                    code,
+                    String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+fn check_value(var_type: &VariableType, value: &Json) -> Result<(), String> {
+    let as_string = match *value {
+        Json::String(ref s) => s.clone(),
+        ref other => other.to_string(),
+    };
+
+    let valid = match *var_type {
+        VariableType::String => value.is_string(),
+        VariableType::Int => value.is_i64() || value.is_u64() || as_string.parse::<i64>().is_ok(),
+        VariableType::Bool => value.is_boolean() || as_string == "true" || as_string == "false",
+        VariableType::Enum(ref values) => values.contains(&as_string),
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        let expected = match *var_type {
+            VariableType::String => "a string".to_string(),
+            VariableType::Int => "an int".to_string(),
+            VariableType::Bool => "a bool".to_string(),
+            VariableType::Enum(ref values) => format!("one of [{}]", values.join(", ")),
+        };
+        Err(format!("should be {} but was '{}'", expected, as_string))
+    }
+}
+
+pub fn check_declarations(declarations: &BTreeMap<String, Declaration>,
+                          conf: &Option<Json>)
+                          -> Result<(), String> {
+    let mut problems = vec![];
+
+    for (name, declaration) in declarations.iter() {
+        match conf.as_ref().and_then(|c| c.find(name)) {
+            Some(value) => {
+                if let Err(msg) = check_value(&declaration.var_type, value) {
+                    problems.push(format!("'{}' {}", name, msg));
+                }
+            }
+            None => {
+                if declaration.required {
+                    problems.push(format!("'{}' is required but wasn't supplied", name));
+                }
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("missing/invalid variables - {}", problems.join(", ")))
+    }
+}
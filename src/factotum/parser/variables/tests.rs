@@ -0,0 +1,123 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use factotum::parser::variables::*;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+
+fn declare(var_type: VariableType, required: bool) -> Declaration {
+    Declaration {
+        var_type: var_type,
+        required: required,
+    }
+}
+
+#[test]
+fn merge_variables_prefers_supplied() {
+    let mut defaults = BTreeMap::new();
+    defaults.insert("region".to_string(), "eu-west-1".to_string());
+    defaults.insert("bucket".to_string(), "logs".to_string());
+
+    let merged = merge_variables(&Some(defaults),
+                                 Some(Json::from_str("{\"region\":\"us-east-1\"}").unwrap()),
+                                 true)
+        .unwrap();
+    assert_eq!(merged,
+               Some(Json::from_str("{\"bucket\":\"logs\",\"region\":\"us-east-1\"}").unwrap()));
+    assert_eq!(merge_variables(&None, None, true), Ok(None));
+}
+
+#[test]
+fn declarations_accept_valid_values() {
+    let mut declarations = BTreeMap::new();
+    declarations.insert("name".to_string(), declare(VariableType::String, true));
+    declarations.insert("size".to_string(), declare(VariableType::Int, true));
+    declarations.insert("full".to_string(), declare(VariableType::Bool, false));
+    declarations.insert("env".to_string(),
+                        declare(VariableType::Enum(vec!["dev".to_string(), "prod".to_string()]),
+                                false));
+    declarations.insert("optional".to_string(), declare(VariableType::String, false));
+
+    let conf = Json::from_str("{\"name\":\"x\",\"size\":\"10\",\"full\":\"true\",\"env\":\"prod\"}")
+        .unwrap();
+    assert_eq!(check_declarations(&declarations, &Some(conf)), Ok(()));
+
+    let conf = Json::from_str("{\"name\":\"x\",\"size\":10,\"full\":false}").unwrap();
+    assert_eq!(check_declarations(&declarations, &Some(conf)), Ok(()));
+}
+
+#[test]
+fn declarations_report_every_problem() {
+    let mut declarations = BTreeMap::new();
+    declarations.insert("name".to_string(), declare(VariableType::String, true));
+    declarations.insert("size".to_string(), declare(VariableType::Int, false));
+    declarations.insert("full".to_string(), declare(VariableType::Bool, false));
+    declarations.insert("env".to_string(),
+                        declare(VariableType::Enum(vec!["dev".to_string(), "prod".to_string()]),
+                                true));
+
+    let conf = Json::from_str("{\"size\":\"ten\",\"full\":\"yes\",\"env\":\"test\"}").unwrap();
+    assert_eq!(check_declarations(&declarations, &Some(conf)),
+               Err("missing/invalid variables - 'env' should be one of [dev, prod] but was \
+                    'test', 'full' should be a bool but was 'yes', 'name' is required but \
+                    wasn't supplied, 'size' should be an int but was 'ten'"
+                   .to_string()));
+    assert_eq!(check_declarations(&declarations, &None),
+               Err("missing/invalid variables - 'env' is required but wasn't supplied, 'name' \
+                    is required but wasn't supplied"
+                   .to_string()));
+}
+
+#[test]
+fn variables_are_resolved_from_others() {
+    let conf = Json::from_str("{\"bucket\":\"{{ company }}-{{ env }}-data\",\"company\":\"acme\",\
+                               \"env\":\"{{ stage }}\",\"stage\":\"prod & dev\",\
+                               \"paths\":[\"s3://{{ bucket }}/in\"],\"each\":\"{{ item }}\"}")
+        .unwrap();
+    let resolved = resolve_variables(Some(conf)).unwrap().unwrap();
+    assert_eq!(resolved.find("bucket"),
+               Some(&Json::String("acme-prod & dev-data".to_string())));
+    assert_eq!(resolved.find("paths"),
+               Some(&Json::from_str("[\"s3://acme-prod & dev-data/in\"]").unwrap()));
+    assert_eq!(resolved.find("each"), Some(&Json::String("{{ item }}".to_string())));
+    assert_eq!(resolve_variables(None), Ok(None));
+}
+
+#[test]
+fn variable_cycles_are_rejected() {
+    let conf = Json::from_str("{\"a\":\"{{ b }}\",\"b\":\"x-{{ c }}\",\"c\":\"{{ a }}\"}").unwrap();
+    assert_eq!(resolve_variables(Some(conf)),
+               Err("the variables refer to each other in a cycle: a -> b -> c -> a".to_string()));
+
+    let conf = Json::from_str("{\"a\":\"{{ a }}\"}").unwrap();
+    assert_eq!(resolve_variables(Some(conf)),
+               Err("the variables refer to each other in a cycle: a -> a".to_string()));
+}
+
+#[test]
+fn task_variables_are_layered_over_the_job() {
+    let conf = Some(Json::from_str("{\"bucket\":\"logs\",\"table\":\"events\"}").unwrap());
+    let mut vars = BTreeMap::new();
+    vars.insert("table".to_string(), "pages".to_string());
+    vars.insert("path".to_string(), "s3://{{ bucket }}/{{ table }}".to_string());
+
+    let layered = task_variables(&Some(vars.clone()), &conf, true).unwrap().unwrap();
+    assert_eq!(layered.find("table"), Some(&Json::String("pages".to_string())));
+    assert_eq!(layered.find("path"), Some(&Json::String("s3://logs/pages".to_string())));
+    assert_eq!(layered.find("bucket"), Some(&Json::String("logs".to_string())));
+
+    // without a run-time environment nothing is templated
+    assert_eq!(task_variables(&Some(vars), &None, true), Ok(None));
+    assert_eq!(task_variables(&None, &conf, true), Ok(conf.clone()));
+}
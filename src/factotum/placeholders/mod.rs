@@ -0,0 +1,190 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::parser;
+use factotum::preflight;
+use factotum::workspace;
+use rustc_serialize::json::Json;
+use std::cmp;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Supplied,
+    Default,
+    RunTime,
+    Missing,
+}
+
+impl Status {
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Status::Supplied => "supplied",
+            Status::Default => "default",
+            Status::RunTime => "set at run time",
+            Status::Missing => "missing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Usage {
+    pub variable: String,
+    pub locations: Vec<String>,
+    pub status: Status,
+}
+
+fn location(path: &Vec<String>, task_names: &Vec<String>) -> String {
+    // data.tasks.<n> is given by the task's name, as written
+    if path.len() >= 2 && path[0] == "tasks" {
+        if let Ok(idx) = path[1].parse::<usize>() {
+            if let Some(name) = task_names.get(idx) {
+                let field = path[2..].iter().fold(String::new(), |mut field, part| {
+                    if part.parse::<usize>().is_ok() {
+                        field.push_str(&format!("[{}]", part));
+                    } else {
+                        if !field.is_empty() {
+                            field.push('.');
+                        }
+                        field.push_str(part);
+                    }
+                    field
+                });
+                return format!("task '{}' {}", name, field);
+            }
+        }
+    }
+    path.join(".")
+}
+
+fn find_in(json: &Json, path: &mut Vec<String>, found: &mut Vec<(String, Vec<String>)>) {
+    match *json {
+        Json::String(ref s) => {
+            for variable in preflight::template_variables(s) {
+                found.push((variable, path.clone()));
+            }
+        }
+        Json::Array(ref items) => {
+            for (idx, item) in items.iter().enumerate() {
+                path.push(idx.to_string());
+                find_in(item, path, found);
+                path.pop();
+            }
+        }
+        Json::Object(ref fields) => {
+            for (key, value) in fields.iter() {
+                path.push(key.clone());
+                find_in(value, path, found);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+fn status(variable: &str, supplied: &Json, defaults: &BTreeMap<String, Json>) -> Status {
+    // only the top level of a dotted name or pointer, e.g. "nested" for "nested.message", is a
+    // variable
+    let top = parser::variable_root(variable);
+    let top = top.as_str();
+    if supplied.find(top).is_some() {
+        Status::Supplied
+    } else if defaults.contains_key(top) {
+        Status::Default
+    } else if top == parser::FOR_EACH_ITEM || top == workspace::RUN_VARIABLE ||
+              top == workspace::TASK_VARIABLE {
+        Status::RunTime
+    } else {
+        Status::Missing
+    }
+}
+
+// source is the factfile as written, before any templating
+pub fn find_usages(source: &str, supplied: &Json) -> Result<Vec<Usage>, String> {
+    let json = try!(Json::from_str(source).map_err(|e| format!("it isn't valid JSON ({})", e)));
+    let data = try!(json.find("data").ok_or("it has no 'data'".to_string()));
+
+    let task_names = data.find("tasks")
+        .and_then(|t| t.as_array())
+        .map(|tasks| {
+            tasks.iter()
+                .map(|t| t.find("name").and_then(|n| n.as_string()).unwrap_or("").to_string())
+                .collect()
+        })
+        .unwrap_or(vec![]);
+    let defaults = data.find("variables")
+        .and_then(|v| v.as_object())
+        .cloned()
+        .unwrap_or(BTreeMap::new());
+
+    let mut found = vec![];
+    find_in(data, &mut vec![], &mut found);
+
+    let mut usages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (variable, path) in found.into_iter() {
+        let place = location(&path, &task_names);
+        let locations = usages.entry(variable).or_insert(vec![]);
+        if !locations.contains(&place) {
+            locations.push(place);
+        }
+    }
+
+    Ok(usages.into_iter()
+        .map(|(variable, locations)| {
+            Usage {
+                status: status(&variable, supplied, &defaults),
+                variable: variable,
+                locations: locations,
+            }
+        })
+        .collect())
+}
+
+pub fn format_usages(usages: &Vec<Usage>) -> String {
+    if usages.is_empty() {
+        return "No variables are used".to_string();
+    }
+
+    let name_width = usages.iter()
+        .map(|u| u.variable.chars().count())
+        .fold("VARIABLE".len(), cmp::max);
+    let status_width = usages.iter().map(|u| u.status.describe().len()).fold("STATUS".len(), cmp::max);
+
+    let mut lines = vec![format!("{:nw$}  {:sw$}  USED IN",
+                                 "VARIABLE",
+                                 "STATUS",
+                                 nw = name_width,
+                                 sw = status_width)];
+    for usage in usages.iter() {
+        lines.push(format!("{:nw$}  {:sw$}  {}",
+                           usage.variable,
+                           usage.status.describe(),
+                           usage.locations.join(", "),
+                           nw = name_width,
+                           sw = status_width));
+    }
+
+    let missing = usages.iter().filter(|u| u.status == Status::Missing).count();
+    if missing > 0 {
+        lines.push(String::new());
+        lines.push(format!("{} of {} variable(s) have no value",
+                           missing,
+                           usages.len()));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,97 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+
+use super::*;
+use rustc_serialize::json::Json;
+
+const FACTFILE: &'static str = r#"{
+    "schema": "iglu:com.snowplowanalytics.factotum/factfile/jsonschema/1-0-0",
+    "data": {
+        "name": "Load {{ region }}",
+        "variables": { "region": "eu-west-1" },
+        "tasks": [
+            {
+                "name": "Load",
+                "executor": "shell",
+                "command": "./load.sh {{ bucket }}",
+                "arguments": [ "--to={{ nested.table }}", "--region={{ region }}", "{{ workspace }}" ],
+                "dependsOn": [],
+                "onResult": { "terminateJobWithSuccess": [], "continueJob": [ 0 ] }
+            }
+        ]
+    }
+}"#;
+
+fn find(usages: &Vec<Usage>, variable: &str) -> Usage {
+    usages.iter().find(|u| u.variable == variable).unwrap().clone()
+}
+
+#[test]
+fn usages_are_located_by_task_and_field() {
+    let usages = find_usages(FACTFILE, &Json::from_str("{}").unwrap()).unwrap();
+    assert_eq!(usages.len(), 4);
+
+    assert_eq!(find(&usages, "region").locations,
+               vec!["name".to_string(), "task 'Load' arguments[1]".to_string()]);
+    assert_eq!(find(&usages, "bucket").locations,
+               vec!["task 'Load' command".to_string()]);
+}
+
+#[test]
+fn usages_have_a_status() {
+    let supplied = Json::from_str(r#"{ "nested": { "table": "events" } }"#).unwrap();
+    let usages = find_usages(FACTFILE, &supplied).unwrap();
+
+    assert_eq!(find(&usages, "nested.table").status, Status::Supplied);
+    assert_eq!(find(&usages, "region").status, Status::Default);
+    assert_eq!(find(&usages, "workspace").status, Status::RunTime);
+    assert_eq!(find(&usages, "bucket").status, Status::Missing);
+}
+
+#[test]
+fn supplied_values_win_over_defaults() {
+    let supplied = Json::from_str(r#"{ "region": "us-east-1" }"#).unwrap();
+    let usages = find_usages(FACTFILE, &supplied).unwrap();
+    assert_eq!(find(&usages, "region").status, Status::Supplied);
+}
+
+#[test]
+fn bad_factfiles_are_errors() {
+    assert!(find_usages("{", &Json::Null).is_err());
+    assert!(find_usages("{}", &Json::Null).is_err());
+}
+
+#[test]
+fn format_usages_counts_missing() {
+    let usages = vec![Usage {
+                          variable: "region".to_string(),
+                          locations: vec!["name".to_string()],
+                          status: Status::Default,
+                      },
+                      Usage {
+                          variable: "bucket".to_string(),
+                          locations: vec!["task 'Load' command".to_string(),
+                                          "task 'Copy' command".to_string()],
+                          status: Status::Missing,
+                      }];
+    let expected = vec!["VARIABLE  STATUS   USED IN",
+                        "region    default  name",
+                        "bucket    missing  task 'Load' command, task 'Copy' command",
+                        "",
+                        "1 of 2 variable(s) have no value"];
+    assert_eq!(format_usages(&usages), expected.join("\n"));
+    assert_eq!(format_usages(&vec![]), "No variables are used");
+}
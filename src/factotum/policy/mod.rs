@@ -0,0 +1,250 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::{Factfile, Shell, Task};
+use factotum::generator;
+use factotum::parser::computed_command;
+use rustc_serialize::Decodable;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use toml;
+
+// the commands each executor is permitted to run, e.g.
+//
+//   [executors]
+//   shell = ["psql", "/opt/etl/bin/*"]
+//   wait = ["*"]
+//
+// a trailing * permits anything starting with what comes before it, and an executor
+// that isn't listed can't be used at all
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Policy {
+    executors: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(RustcDecodable)]
+struct PolicyFile {
+    executors: Option<BTreeMap<String, Vec<String>>>,
+}
+
+// a ".." could climb out of the directory a prefix permits, e.g. "/opt/etl/bin/../../../bin/sh"
+fn permits(allowed: &str, command: &str) -> bool {
+    if allowed.ends_with('*') {
+        command.starts_with(&allowed[..allowed.len() - 1]) && !command.split('/').any(|c| c == "..")
+    } else {
+        allowed == command
+    }
+}
+
+// shell metacharacters could run something other than the command's program, so
+// under a policy a command is only plain words
+fn is_plain_word(word: &str) -> bool {
+    word.chars().all(|c| c.is_alphanumeric() || "-_./:,=+@%".contains(c))
+}
+
+// "NAME=value program" would run program, not "NAME=value"
+fn is_plain_command(command: &str) -> bool {
+    let program = command.split_whitespace().next().unwrap_or("");
+    command.split_whitespace().all(is_plain_word) && !program.contains('=')
+}
+
+// arguments are given to the shell in double quotes, where these can still run commands
+// or end the quoting, unless they're escaped (as "escape": "shell" does)
+fn unescaped_special(argument: &str, escape_char: char, special: &str) -> Option<char> {
+    let mut chars = argument.chars();
+    while let Some(c) = chars.next() {
+        if c == escape_char {
+            if chars.next().is_none() {
+                return Some(c);
+            }
+        } else if special.contains(c) {
+            return Some(c);
+        }
+    }
+    None
+}
+
+impl Policy {
+    pub fn check_task(&self, task: &Task) -> Result<(), String> {
+        let allowed = try!(self.executors.get(&task.executor).ok_or(format!(
+            "the task '{}' uses the '{}' executor, which the policy doesn't permit",
+            task.name,
+            task.executor)));
+
+        try!(self.check_constraint(task));
+
+        let runs_command = task.executor == "shell" || task.executor == generator::EXECUTOR;
+        let program = if runs_command && task.shell != Shell::Exec {
+            let program = task.command.split_whitespace().next().unwrap_or("");
+            if !is_plain_command(&task.command) {
+                return Err(format!("the task '{}' has the command '{}', which can't be checked \
+                                    against the policy as it isn't a plain program and arguments",
+                                   task.name,
+                                   task.command));
+            }
+            let (escape_char, special) = match task.shell {
+                Shell::Pwsh => ('`', "$\""),
+                _ => ('\\', "$`\""),
+            };
+            for argument in task.arguments.iter() {
+                if let Some(c) = unescaped_special(argument, escape_char, special) {
+                    return Err(format!("the task '{}' has the argument '{}', which the policy \
+                                        doesn't permit as it has an unescaped '{}'",
+                                       task.name,
+                                       argument,
+                                       c));
+                }
+            }
+            program
+        } else {
+            task.command.as_str()
+        };
+
+        if allowed.iter().any(|a| permits(a, program)) {
+            Ok(())
+        } else {
+            Err(format!("the task '{}' runs '{}', which the policy doesn't permit for the '{}' \
+                         executor",
+                        task.name,
+                        program,
+                        task.executor))
+        }
+    }
+
+    // a $(command) variable is computed with the shell before any task runs, so its command
+    // is held to what the shell executor is permitted; the factfile is one parsed without
+    // computing them
+    pub fn check_computed_variables(&self, factfile: &Factfile) -> Vec<String> {
+        let task_vars = factfile.task_variables.values().flat_map(|vars| vars.iter());
+        factfile.variables
+            .iter()
+            .chain(task_vars)
+            .filter_map(|(name, value)| computed_command(value).map(|command| (name, command)))
+            .filter_map(|(name, command)| self.check_variable(name, command).err())
+            .collect::<BTreeSet<String>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn check_variable(&self, name: &str, command: &str) -> Result<(), String> {
+        if !is_plain_command(command) {
+            return Err(format!("the variable '{}' is computed by '{}', which can't be checked \
+                                against the policy as it isn't a plain program and arguments",
+                               name,
+                               command));
+        }
+
+        let program = command.split_whitespace().next().unwrap_or("");
+        if self.shell_permits(program) {
+            Ok(())
+        } else {
+            Err(format!("the variable '{}' is computed by running '{}', which the policy \
+                         doesn't permit for the 'shell' executor",
+                        name,
+                        program))
+        }
+    }
+
+    // a task's check constraint is run with the shell too, just before the task is
+    fn check_constraint(&self, task: &Task) -> Result<(), String> {
+        let check = match task.constraints.as_ref().and_then(|c| c.constraints.check.as_ref()) {
+            Some(check) => check,
+            None => return Ok(()),
+        };
+        if !is_plain_command(check) {
+            return Err(format!("the task '{}' has the check constraint '{}', which can't be \
+                                checked against the policy as it isn't a plain program and \
+                                arguments",
+                               task.name,
+                               check));
+        }
+
+        let program = check.split_whitespace().next().unwrap_or("");
+        if self.shell_permits(program) {
+            Ok(())
+        } else {
+            Err(format!("the task '{}' checks its constraint by running '{}', which the policy \
+                         doesn't permit for the 'shell' executor",
+                        task.name,
+                        program))
+        }
+    }
+
+    fn shell_permits(&self, program: &str) -> bool {
+        self.executors
+            .get("shell")
+            .map(|allowed| allowed.iter().any(|a| permits(a, program)))
+            .unwrap_or(false)
+    }
+
+    // every task is checked, including those that won't run this time
+    pub fn check_factfile(&self, factfile: &Factfile) -> Vec<String> {
+        factfile.get_tasks_in_order()
+            .into_iter()
+            .flat_map(|grp| grp.into_iter())
+            .filter_map(|task| self.check_task(task).err())
+            .collect()
+    }
+}
+
+pub fn parse_policy(contents: &str) -> Result<Policy, String> {
+    let mut parser = toml::Parser::new(contents);
+    let table = match parser.parse() {
+        Some(table) => table,
+        None => {
+            let problems = parser.errors
+                .iter()
+                .map(|e| {
+                    let (line, col) = parser.to_linecol(e.lo);
+                    format!("{} at line {} column {}", e.desc, line + 1, col + 1)
+                })
+                .collect::<Vec<String>>();
+            return Err(format!("it isn't valid TOML ({})", problems.join(", ")));
+        }
+    };
+
+    let mut decoder = toml::Decoder::new(toml::Value::Table(table));
+    let policy = try!(PolicyFile::decode(&mut decoder).map_err(|e| format!("{}", e)));
+    if let Some(toml::Value::Table(ref leftover)) = decoder.toml {
+        if !leftover.is_empty() {
+            return Err(format!("it has unknown settings: {}",
+                               leftover.keys().cloned().collect::<Vec<String>>().join(", ")));
+        }
+    }
+
+    Ok(Policy { executors: policy.executors.unwrap_or(BTreeMap::new()) })
+}
+
+pub fn load_policy(path: &Path) -> Result<Policy, String> {
+    let mut contents = String::new();
+    try!(File::open(path)
+        .and_then(|mut f| f.read_to_string(&mut contents))
+        .map_err(|e| format!("couldn't read the policy file '{}' ({})", path.display(), e)));
+
+    parse_policy(&contents)
+        .map_err(|e| format!("the policy file '{}' can't be used: {}", path.display(), e))
+}
+
+pub fn format_report(problems: &Vec<String>) -> String {
+    let mut lines = vec![format!("The job isn't permitted by the policy ({} problem{}):",
+                                 problems.len(),
+                                 if problems.len() == 1 { "" } else { "s" })];
+    lines.extend(problems.iter().map(|p| format!("  - {}", p)));
+    lines.join("\n")
+}
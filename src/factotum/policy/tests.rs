@@ -0,0 +1,183 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::constraint::Constraints;
+use factotum::factfile::{Factfile, Shell, TaskConstraints};
+use factotum::tests::make_task;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::Write;
+
+const POLICY: &'static str = r#"
+[executors]
+shell = ["psql", "/opt/etl/bin/*"]
+wait = ["*"]
+"#;
+
+fn shell_task(name: &str, command: &str, arguments: Vec<&str>) -> Task {
+    let mut task = make_task(name, &vec![]);
+    task.executor = "shell".to_string();
+    task.command = command.to_string();
+    task.arguments = arguments.iter().map(|a| a.to_string()).collect();
+    task
+}
+
+#[test]
+fn permitted_commands_pass() {
+    let policy = parse_policy(POLICY).unwrap();
+
+    assert_eq!(policy.check_task(&shell_task("query", "psql", vec!["-c", "SELECT 1;"])),
+               Ok(()));
+    assert_eq!(policy.check_task(&shell_task("load", "/opt/etl/bin/load.sh --all", vec![])),
+               Ok(()));
+    assert_eq!(policy.check_task(&shell_task("escaped", "psql", vec!["\\$(whoami)\\\""])),
+               Ok(()));
+
+    let mut wait = make_task("ready", &vec![]);
+    wait.executor = "wait".to_string();
+    wait.command = "file:/tmp/ready".to_string();
+    assert_eq!(policy.check_task(&wait), Ok(()));
+
+    // without a shell, nothing in the arguments is interpreted
+    let mut exec = shell_task("exec", "/opt/etl/bin/load", vec!["$(whoami)"]);
+    exec.shell = Shell::Exec;
+    assert_eq!(policy.check_task(&exec), Ok(()));
+}
+
+#[test]
+fn unpermitted_commands_fail() {
+    let policy = parse_policy(POLICY).unwrap();
+
+    assert_eq!(policy.check_task(&shell_task("wipe", "rm", vec!["-rf", "/"])),
+               Err("the task 'wipe' runs 'rm', which the policy doesn't permit for the 'shell' \
+                    executor"
+                   .to_string()));
+    assert_eq!(policy.check_task(&shell_task("load", "/opt/etl/load.sh", vec![])),
+               Err("the task 'load' runs '/opt/etl/load.sh', which the policy doesn't permit \
+                    for the 'shell' executor"
+                   .to_string()));
+
+    assert_eq!(policy.check_task(&shell_task("escape", "/opt/etl/bin/../../../bin/sh", vec![])),
+               Err("the task 'escape' runs '/opt/etl/bin/../../../bin/sh', which the policy \
+                    doesn't permit for the 'shell' executor"
+                   .to_string()));
+
+    let mut nested = make_task("child", &vec![]);
+    nested.executor = "factfile".to_string();
+    assert_eq!(policy.check_task(&nested),
+               Err("the task 'child' uses the 'factfile' executor, which the policy doesn't \
+                    permit"
+                   .to_string()));
+}
+
+#[test]
+fn commands_that_could_run_something_else_fail() {
+    let policy = parse_policy(POLICY).unwrap();
+
+    assert_eq!(policy.check_task(&shell_task("chained", "psql; rm -rf /", vec![])),
+               Err("the task 'chained' has the command 'psql; rm -rf /', which can't be \
+                    checked against the policy as it isn't a plain program and arguments"
+                   .to_string()));
+    assert!(policy.check_task(&shell_task("assigned", "psql_opts=1 rm", vec![])).is_err());
+    assert_eq!(policy.check_task(&shell_task("substituted", "psql", vec!["$(rm -rf /)"])),
+               Err("the task 'substituted' has the argument '$(rm -rf /)', which the policy \
+                    doesn't permit as it has an unescaped '$'"
+                   .to_string()));
+    assert!(policy.check_task(&shell_task("quoted", "psql", vec!["\"; rm -rf /"])).is_err());
+    assert!(policy.check_task(&shell_task("trailing", "psql", vec!["a\\"])).is_err());
+
+    let mut pwsh = shell_task("report", "psql", vec!["`$HOME", "$HOME"]);
+    pwsh.shell = Shell::Pwsh;
+    assert_eq!(policy.check_task(&pwsh),
+               Err("the task 'report' has the argument '$HOME', which the policy doesn't \
+                    permit as it has an unescaped '$'"
+                   .to_string()));
+}
+
+#[test]
+fn check_factfile_lists_every_problem() {
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&shell_task("query", "psql", vec![])).unwrap();
+    ff.add_task_obj(&shell_task("wipe", "rm", vec![])).unwrap();
+    let mut disabled = shell_task("curl", "curl", vec![]);
+    disabled.disabled = true;
+    ff.add_task_obj(&disabled).unwrap();
+
+    assert_eq!(parse_policy(POLICY).unwrap().check_factfile(&ff).len(), 2);
+    assert_eq!(Policy::default().check_factfile(&ff).len(), 3);
+}
+
+#[test]
+fn check_constraints_are_checked() {
+    let policy = parse_policy(POLICY).unwrap();
+    let with_check = |check: &str| {
+        let mut task = shell_task("query", "psql", vec![]);
+        task.constraints = Some(TaskConstraints {
+            constraints: Constraints { check: Some(check.to_string()), ..Constraints::default() },
+            fail_job: false,
+        });
+        task
+    };
+
+    assert_eq!(policy.check_task(&with_check("/opt/etl/bin/ready --today")), Ok(()));
+    assert_eq!(policy.check_task(&with_check("curl -f http://example.com")),
+               Err("the task 'query' checks its constraint by running 'curl', which the policy \
+                    doesn't permit for the 'shell' executor"
+                   .to_string()));
+    assert_eq!(policy.check_task(&with_check("psql; rm -rf /")),
+               Err("the task 'query' has the check constraint 'psql; rm -rf /', which can't be \
+                    checked against the policy as it isn't a plain program and arguments"
+                   .to_string()));
+}
+
+#[test]
+fn computed_variables_are_checked() {
+    let mut ff = Factfile::new("N/A", "test");
+    ff.variables.insert("rows".to_string(), "$(psql -c count)".to_string());
+    ff.variables.insert("who".to_string(), "$(whoami)".to_string());
+    ff.variables.insert("plain".to_string(), "curl".to_string());
+    let mut vars = BTreeMap::new();
+    vars.insert("host".to_string(), "$(hostname; rm -rf /)".to_string());
+    ff.task_variables.insert("query".to_string(), vars);
+
+    assert_eq!(parse_policy(POLICY).unwrap().check_computed_variables(&ff),
+               vec!["the variable 'host' is computed by 'hostname; rm -rf /', which can't be \
+                     checked against the policy as it isn't a plain program and arguments"
+                        .to_string(),
+                    "the variable 'who' is computed by running 'whoami', which the policy \
+                     doesn't permit for the 'shell' executor"
+                        .to_string()]);
+}
+
+#[test]
+fn parse_policy_bad() {
+    assert!(parse_policy("[executors").unwrap_err().starts_with("it isn't valid TOML"));
+    assert_eq!(parse_policy("[executors]\nshell = [\"psql\"]\n[shells]\nbash = true"),
+               Err("it has unknown settings: shells".to_string()));
+    assert_eq!(parse_policy(""), Ok(Policy::default()));
+}
+
+#[test]
+fn load_policy_reads_the_file() {
+    let path = env::temp_dir().join("factotum-policy-test.toml");
+    File::create(&path).unwrap().write_all(POLICY.as_bytes()).unwrap();
+    assert_eq!(load_policy(&path), parse_policy(POLICY));
+    assert!(fs::remove_file(&path).is_ok());
+
+    assert!(load_policy(&path)
+        .unwrap_err()
+        .starts_with("couldn't read the policy file '"));
+}
@@ -0,0 +1,271 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor;
+use factotum::factfile::{Factfile, Shell, Task};
+use factotum::nested;
+use factotum::generator;
+use factotum::parser;
+use factotum::sandbox;
+use factotum::workspace;
+use rustc_serialize::json::Json;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub trait PreflightEnvironment {
+    fn command_resolves(&self, command: &str) -> bool;
+    fn artifact_exists(&self, location: &str) -> Result<bool, String>;
+}
+
+pub struct OsEnvironment;
+
+impl PreflightEnvironment for OsEnvironment {
+    // asks the shell the task will run under, so builtins and aliases count too
+    fn command_resolves(&self, command: &str) -> bool {
+        Command::new("sh")
+            .arg("-c")
+            .arg("command -v \"$1\"")
+            .arg("sh")
+            .arg(command)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false)
+    }
+
+    fn artifact_exists(&self, location: &str) -> Result<bool, String> {
+        if location.starts_with("s3://") {
+            let output = try!(Command::new("aws")
+                .arg("s3")
+                .arg("ls")
+                .arg(location)
+                .output()
+                .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+            // `aws s3 ls` lists keys by prefix, so look for the exact key
+            let key_name = location.rsplit('/').next().unwrap_or("");
+            Ok(output.status.success() &&
+               String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|l| l.split_whitespace().last() == Some(key_name)))
+        } else {
+            Ok(Path::new(location).exists())
+        }
+    }
+}
+
+// the program a shell task runs, e.g. "./load.sh" for "./load.sh --all"
+pub fn command_word(command: &str) -> Option<&str> {
+    command.split_whitespace().next()
+}
+
+// variables used in the factfile's templates, e.g. "region" for "{{ region }}";
+// sections, comments and partials are left out
+pub fn template_variables(source: &str) -> BTreeSet<String> {
+    let mut variables = BTreeSet::new();
+    let mut rest = source;
+
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(e) => e,
+            None => break,
+        };
+        let tag = after[..end].trim_matches(|c| c == '{' || c == '}').trim();
+        let tag = if tag.starts_with('&') { tag[1..].trim() } else { tag };
+        if !tag.is_empty() && !tag.starts_with(|c| "#^/!>=".contains(c)) {
+            variables.insert(tag.to_string());
+        }
+        rest = &after[end + 2..];
+    }
+
+    variables
+}
+
+pub fn check_tasks(tasks: &Vec<&Task>, environment: &dyn PreflightEnvironment) -> Vec<String> {
+    let mut problems = vec![];
+
+    // a disabled task won't run, so it doesn't need anything
+    for task in tasks.iter().filter(|t| !t.disabled) {
+        if task.executor == "shell" || task.executor == generator::EXECUTOR {
+            let shell = match task.shell {
+                Shell::Bash => Some("bash"),
+                Shell::Pwsh => Some("pwsh"),
+                _ => None,
+            };
+            if let Some(shell) = shell {
+                if !environment.command_resolves(shell) {
+                    problems.push(format!("the task '{}' runs under {}, which couldn't be found",
+                                          task.name,
+                                          shell));
+                }
+            }
+
+            // without a shell the command is the program itself; powershell's own commands
+            // can't be looked up from sh
+            let word = match task.shell {
+                Shell::Exec if !task.command.trim().is_empty() => Some(task.command.as_str()),
+                Shell::Exec => None,
+                _ => command_word(&task.command),
+            };
+            match word {
+                Some(_) if task.shell == Shell::Pwsh => {}
+                // what a $(command) gives isn't known until it's run, e.g. a command
+                // templated from a computed variable
+                Some(word) if word.contains("$(") => {}
+                Some(word) => {
+                    if !environment.command_resolves(word) {
+                        problems.push(format!("the task '{}' runs '{}', which couldn't be found",
+                                              task.name,
+                                              word));
+                    }
+                }
+                None => problems.push(format!("the task '{}' has no command", task.name)),
+            }
+        }
+
+        if task.tty && !environment.command_resolves(executor::TTY_COMMAND) {
+            problems.push(format!("the task '{}' runs under a pseudo-terminal, which needs '{}', \
+                                   which couldn't be found",
+                                  task.name,
+                                  executor::TTY_COMMAND));
+        }
+
+        if let Some(ref sandbox) = task.sandbox {
+            if let Err(msg) = sandbox::prepare(sandbox) {
+                problems.push(format!("the task '{}' can't be sandboxed: {}", task.name, msg));
+            }
+            for path in sandbox.writable_paths.iter() {
+                if environment.artifact_exists(path) != Ok(true) {
+                    problems.push(format!("the task '{}' can write to '{}' in its sandbox, \
+                                           which doesn't exist",
+                                          task.name,
+                                          path));
+                }
+            }
+        }
+
+        if task.executor == nested::EXECUTOR {
+            match environment.artifact_exists(&task.command) {
+                Ok(true) => {}
+                _ => {
+                    problems.push(format!("the task '{}' runs the factfile '{}', which doesn't \
+                                           exist",
+                                          task.name,
+                                          task.command))
+                }
+            }
+        }
+
+        for artifact in task.requires.iter() {
+            match environment.artifact_exists(artifact) {
+                Ok(true) => {}
+                Ok(false) => {
+                    problems.push(format!("the task '{}' requires '{}', which doesn't exist",
+                                          task.name,
+                                          artifact))
+                }
+                Err(msg) => {
+                    problems.push(format!("the task '{}' requires '{}', which couldn't be \
+                                           checked: {}",
+                                          task.name,
+                                          artifact,
+                                          msg))
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+pub fn check_variables(source: &str, variables: &BTreeMap<String, String>) -> Vec<String> {
+    template_variables(source)
+        .iter()
+        // only the top level of a dotted name or pointer, e.g. "nested" for "nested.message"
+        // or "@/nested/message", is a variable
+        .filter(|v| !variables.contains_key(&parser::variable_root(v)))
+        .filter(|v| *v != parser::FOR_EACH_ITEM)
+        // the workspace is only known once the run starts
+        .filter(|v| *v != workspace::RUN_VARIABLE && *v != workspace::TASK_VARIABLE)
+        .map(|v| format!("the variable '{}' is used but has no value", v))
+        .collect()
+}
+
+// a task's own vars only have values for that task, so each task in the source is checked
+// against them as well as the job's variables; tasks that were left out of the job (e.g. by
+// --only) aren't checked, though one with a templated name, or a forEach, can't be told apart
+fn check_source_variables(factfile: &Factfile,
+                          source: &str,
+                          variables: &BTreeMap<String, String>)
+                          -> Vec<String> {
+    let mut source = match Json::from_str(source) {
+        Ok(source) => source,
+        Err(_) => return check_variables(source, variables),
+    };
+    let tasks = source.as_object_mut()
+        .and_then(|f| f.get_mut("data"))
+        .and_then(|d| d.as_object_mut())
+        .and_then(|d| d.remove("tasks"));
+
+    let mut problems = check_variables(&source.to_string(), variables);
+    for task in tasks.as_ref().and_then(|t| t.as_array()).into_iter().flat_map(|t| t.iter()) {
+        let name = task.find("name").and_then(|n| n.as_string()).unwrap_or("");
+        let left_out = factfile.find_task(name).is_none() && !name.contains("{{") &&
+                       task.find("forEach").is_none();
+        if left_out {
+            continue;
+        }
+
+        let mut task_variables = variables.clone();
+        if let Some(vars) = task.find("vars").and_then(|v| v.as_object()) {
+            task_variables.extend(vars.keys().map(|k| (k.clone(), String::new())));
+        }
+        for problem in check_variables(&task.to_string(), &task_variables) {
+            if !problems.contains(&problem) {
+                problems.push(problem);
+            }
+        }
+    }
+    problems
+}
+
+// source is the factfile as written, before any templating
+pub fn preflight(factfile: &Factfile,
+                 source: &str,
+                 start_from: Option<&str>,
+                 environment: &dyn PreflightEnvironment)
+                 -> Vec<String> {
+    let tasks = match start_from {
+        Some(start) => factfile.get_tasks_in_order_from(start),
+        None => factfile.get_tasks_in_order(),
+    };
+    let tasks = tasks.into_iter().flat_map(|grp| grp.into_iter()).collect::<Vec<&Task>>();
+
+    let mut problems = check_source_variables(factfile, source, &factfile.variables);
+    problems.extend(check_tasks(&tasks, environment));
+    problems
+}
+
+pub fn format_report(problems: &Vec<String>) -> String {
+    let mut lines = vec![format!("Preflight checks failed ({} problem{}):",
+                                 problems.len(),
+                                 if problems.len() == 1 { "" } else { "s" })];
+    lines.extend(problems.iter().map(|p| format!("  - {}", p)));
+    lines.join("\n")
+}
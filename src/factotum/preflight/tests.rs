@@ -0,0 +1,248 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::config::ExecutorSettings;
+use factotum::parser::{self, OverrideResultMappings};
+use factotum::factfile::Sandbox;
+use factotum::tests::make_task;
+use std::fs;
+use rustc_serialize::json::Json;
+
+struct MockEnvironment {
+    commands: Vec<&'static str>,
+    artifacts: Vec<&'static str>,
+}
+
+impl PreflightEnvironment for MockEnvironment {
+    fn command_resolves(&self, command: &str) -> bool {
+        self.commands.contains(&command)
+    }
+
+    fn artifact_exists(&self, location: &str) -> Result<bool, String> {
+        if location.starts_with("s3://") {
+            Err("no credentials".to_string())
+        } else {
+            Ok(self.artifacts.contains(&location))
+        }
+    }
+}
+
+#[test]
+fn command_word_is_first_word() {
+    assert_eq!(command_word("./load.sh --all"), Some("./load.sh"));
+    assert_eq!(command_word("  echo"), Some("echo"));
+    assert_eq!(command_word(" "), None);
+}
+
+#[test]
+fn template_variables_skips_sections_and_comments() {
+    let source = "{{ region }} {{{ raw }}} {{#items}}{{.}}{{/items}} {{! note }} \
+                  {{& escaped }} {{^empty}}{{/empty}} {{region}}";
+    let expected = vec!["escaped", "raw", "region"]
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect::<BTreeSet<String>>();
+    let mut variables = template_variables(source);
+    variables.remove(".");
+    assert_eq!(variables, expected);
+}
+
+#[test]
+fn check_variables_reports_missing() {
+    let mut variables = BTreeMap::new();
+    variables.insert("region".to_string(), "eu-west-1".to_string());
+    variables.insert("nested".to_string(), "{}".to_string());
+
+    assert_eq!(check_variables("{{ region }} {{ nested.message }} {{ item }}", &variables),
+               Vec::<String>::new());
+    assert_eq!(check_variables("{{ region }} {{ bucket }}", &variables),
+               vec!["the variable 'bucket' is used but has no value".to_string()]);
+    assert_eq!(check_variables("{{ @/nested/message }} {{ @/aws/region }}", &variables),
+               vec!["the variable '@/aws/region' is used but has no value".to_string()]);
+}
+
+#[test]
+fn check_tasks_reports_commands_and_artifacts() {
+    let mut load = make_task("load", &vec![]);
+    load.executor = "shell".to_string();
+    load.command = "./load.sh --all".to_string();
+    load.requires = vec!["/data/in.csv".to_string(),
+                         "/data/missing.csv".to_string(),
+                         "s3://bucket/key".to_string()];
+    let mut ok = make_task("ok", &vec![]);
+    ok.executor = "shell".to_string();
+    ok.command = "echo".to_string();
+    let mut empty = make_task("empty", &vec![]);
+    empty.executor = "shell".to_string();
+    let other = make_task("other", &vec![]);
+
+    let environment = MockEnvironment {
+        commands: vec!["echo"],
+        artifacts: vec!["/data/in.csv"],
+    };
+
+    assert_eq!(check_tasks(&vec![&load, &ok, &empty, &other], &environment),
+               vec!["the task 'load' runs './load.sh', which couldn't be found".to_string(),
+                    "the task 'load' requires '/data/missing.csv', which doesn't exist"
+                        .to_string(),
+                    "the task 'load' requires 's3://bucket/key', which couldn't be checked: \
+                     no credentials"
+                        .to_string(),
+                    "the task 'empty' has no command".to_string()]);
+}
+
+#[test]
+fn check_tasks_skips_commands_that_are_computed() {
+    let mut load = make_task("load", &vec![]);
+    load.executor = "shell".to_string();
+    load.command = "$(which psql) -f load.sql".to_string();
+    let mut exec = make_task("exec", &vec![]);
+    exec.executor = "shell".to_string();
+    exec.command = "$(dirname $0)/load".to_string();
+    exec.shell = Shell::Exec;
+
+    let environment = MockEnvironment {
+        commands: vec![],
+        artifacts: vec![],
+    };
+
+    assert_eq!(check_tasks(&vec![&load, &exec], &environment), Vec::<String>::new());
+}
+
+#[test]
+fn check_tasks_follows_the_shell() {
+    let mut exec = make_task("exec", &vec![]);
+    exec.executor = "shell".to_string();
+    exec.command = "/opt/my tools/load".to_string();
+    exec.shell = Shell::Exec;
+    let mut bash = make_task("bash", &vec![]);
+    bash.executor = "shell".to_string();
+    bash.command = "echo".to_string();
+    bash.shell = Shell::Bash;
+    let mut pwsh = make_task("pwsh", &vec![]);
+    pwsh.executor = "shell".to_string();
+    pwsh.command = "Get-ChildItem".to_string();
+    pwsh.shell = Shell::Pwsh;
+
+    let environment = MockEnvironment {
+        commands: vec!["echo", "bash"],
+        artifacts: vec![],
+    };
+
+    assert_eq!(check_tasks(&vec![&exec, &bash, &pwsh], &environment),
+               vec!["the task 'exec' runs '/opt/my tools/load', which couldn't be found"
+                        .to_string(),
+                    "the task 'pwsh' runs under pwsh, which couldn't be found".to_string()]);
+}
+
+#[test]
+fn check_tasks_reports_missing_writable_paths() {
+    let mut task = make_task("sandboxed", &vec![]);
+    task.sandbox = Some(Sandbox {
+        writable_paths: vec!["/data/out".to_string(), "/data/missing".to_string()],
+        ..Sandbox::default()
+    });
+
+    let environment = MockEnvironment {
+        commands: vec![],
+        artifacts: vec!["/data/out"],
+    };
+
+    assert_eq!(check_tasks(&vec![&task], &environment),
+               vec!["the task 'sandboxed' can write to '/data/missing' in its sandbox, which \
+                     doesn't exist"
+                        .to_string()]);
+}
+
+#[test]
+fn check_tasks_needs_script_for_a_tty() {
+    let mut task = make_task("colours", &vec![]);
+    task.executor = "shell".to_string();
+    task.command = "echo".to_string();
+    task.tty = true;
+
+    let environment = MockEnvironment {
+        commands: vec!["echo"],
+        artifacts: vec![],
+    };
+    assert_eq!(check_tasks(&vec![&task], &environment),
+               vec!["the task 'colours' runs under a pseudo-terminal, which needs 'script', \
+                     which couldn't be found"
+                        .to_string()]);
+}
+
+#[test]
+fn preflight_checks_whole_factfile() {
+    let path = "./tests/resources/example_requires.factfile";
+    let source = fs::read_to_string(path).unwrap();
+    let factfile = parser::parse(path,
+                                 Some(Json::Object(Default::default())),
+                                 OverrideResultMappings::None,
+                                 &ExecutorSettings::new())
+        .unwrap();
+    let environment = MockEnvironment {
+        commands: vec!["echo"],
+        artifacts: vec![],
+    };
+
+    let problems = preflight(&factfile, &source, None, &environment);
+    assert_eq!(problems,
+               vec!["the variable 'bucket' is used but has no value".to_string(),
+                    "the task 'Load' runs './load.sh', which couldn't be found"
+                        .to_string(),
+                    "the task 'Load' requires '/tmp/factotum-missing/eu-west-1.csv', which \
+                     doesn't exist"
+                        .to_string()]);
+
+    assert_eq!(preflight(&factfile, &source, Some("Load"), &environment).len(), 3);
+    assert_eq!(format_report(&problems[..1].to_vec()),
+               "Preflight checks failed (1 problem):\n  - the variable 'bucket' is used but has \
+                no value");
+}
+
+#[test]
+fn preflight_checks_each_task_against_its_own_vars() {
+    let path = "./tests/resources/example_task_vars_unset.factfile";
+    let source = fs::read_to_string(path).unwrap();
+    let factfile = parser::parse(path,
+                                 Some(Json::Object(Default::default())),
+                                 OverrideResultMappings::None,
+                                 &ExecutorSettings::new())
+        .unwrap();
+    let environment = MockEnvironment {
+        commands: vec!["echo"],
+        artifacts: vec![],
+    };
+
+    // 'Load' gives 'path' a value, but only for itself
+    assert_eq!(preflight(&factfile, &source, None, &environment),
+               vec!["the variable 'path' is used but has no value".to_string()]);
+
+    let load_only = factfile.select(&vec!["Load".to_string()]).unwrap();
+    assert_eq!(preflight(&load_only, &source, None, &environment),
+               Vec::<String>::new());
+}
+
+#[test]
+fn os_environment_resolves_commands_and_files() {
+    let environment = OsEnvironment;
+    assert!(environment.command_resolves("sh"));
+    assert!(environment.command_resolves("echo"));
+    assert!(!environment.command_resolves("this-doesn't-exist"));
+    assert_eq!(environment.artifact_exists("./tests/resources/example_requires.factfile"),
+               Ok(true));
+    assert_eq!(environment.artifact_exists("./tests/resources/nope.factfile"),
+               Ok(false));
+}
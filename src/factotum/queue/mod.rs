@@ -0,0 +1,321 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::envfile;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const REDIS_DEFAULT_PORT: u16 = 6379;
+// how long a worker waits on the queue before checking whether it's been stopped
+pub const POLL_WAIT_SECS: u64 = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueueSource {
+    Redis { address: String, list: String },
+    Sqs { url: String },
+}
+
+pub fn parse_queue(queue: &str) -> Result<QueueSource, String> {
+    let invalid = || {
+        format!("'{}' isn't a queue, it must be redis://<host>[:<port>]/<list> or an SQS \
+                 queue URL",
+                queue)
+    };
+
+    if queue.starts_with("redis://") {
+        let rest = &queue["redis://".len()..];
+        match rest.find('/') {
+            Some(idx) if idx > 0 && idx + 1 < rest.len() => {
+                let host = &rest[..idx];
+                let address = if host.contains(':') {
+                    host.to_string()
+                } else {
+                    format!("{}:{}", host, REDIS_DEFAULT_PORT)
+                };
+                Ok(QueueSource::Redis {
+                    address: address,
+                    list: rest[idx + 1..].to_string(),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    } else if queue.starts_with("https://sqs.") && queue.contains(".amazonaws.com/") {
+        Ok(QueueSource::Sqs { url: queue.to_string() })
+    } else {
+        Err(invalid())
+    }
+}
+
+// the receipt is what's needed to acknowledge the message once its job has run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub body: String,
+    pub receipt: String,
+}
+
+pub trait QueueClient {
+    // None when no message arrived within POLL_WAIT_SECS
+    fn receive(&self) -> Result<Option<Message>, String>;
+    fn acknowledge(&self, message: &Message) -> Result<(), String>;
+}
+
+pub fn client(source: &QueueSource) -> Box<dyn QueueClient + Send + Sync> {
+    match *source {
+        QueueSource::Redis { ref address, ref list } => {
+            Box::new(RedisQueue {
+                address: address.clone(),
+                list: list.clone(),
+            })
+        }
+        QueueSource::Sqs { ref url } => Box::new(SqsQueue { url: url.clone() }),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    Status(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<String>),
+    Array(Option<Vec<Reply>>),
+}
+
+pub fn encode_command(args: &[&str]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len());
+    for arg in args {
+        command.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+    }
+    command.into_bytes()
+}
+
+pub fn read_reply<R: BufRead>(reader: &mut R) -> Result<Reply, String> {
+    let mut line = String::new();
+    try!(reader.read_line(&mut line)
+        .map_err(|e| format!("couldn't read the reply from redis ({})", e)));
+    let line = line.trim_end_matches("\r\n");
+    if line.is_empty() {
+        return Err("redis closed the connection".to_string());
+    }
+
+    let (kind, value) = line.split_at(1);
+    let length = || {
+        value.parse::<i64>().map_err(|_| format!("redis sent an unexpected reply '{}'", line))
+    };
+    match kind {
+        "+" => Ok(Reply::Status(value.to_string())),
+        "-" => Ok(Reply::Error(value.to_string())),
+        ":" => length().map(Reply::Integer),
+        "$" => {
+            let len = try!(length());
+            if len < 0 {
+                return Ok(Reply::Bulk(None));
+            }
+            let mut data = vec![0; len as usize + 2];
+            try!(reader.read_exact(&mut data)
+                .map_err(|e| format!("couldn't read the reply from redis ({})", e)));
+            data.truncate(len as usize);
+            String::from_utf8(data)
+                .map(|s| Reply::Bulk(Some(s)))
+                .map_err(|_| "redis sent a message that isn't UTF-8".to_string())
+        }
+        "*" => {
+            let len = try!(length());
+            if len < 0 {
+                return Ok(Reply::Array(None));
+            }
+            let mut items = vec![];
+            for _ in 0..len {
+                items.push(try!(read_reply(reader)));
+            }
+            Ok(Reply::Array(Some(items)))
+        }
+        _ => Err(format!("redis sent an unexpected reply '{}'", line)),
+    }
+}
+
+// messages are moved to a processing list while their job runs, so a job whose worker
+// dies is still there to be recovered
+pub struct RedisQueue {
+    pub address: String,
+    pub list: String,
+}
+
+impl RedisQueue {
+    pub fn processing_list(&self) -> String {
+        format!("{}:processing", self.list)
+    }
+
+    fn command(&self, args: &[&str]) -> Result<Reply, String> {
+        let mut stream = try!(TcpStream::connect(&self.address[..])
+            .map_err(|e| format!("couldn't connect to redis at '{}' ({})", self.address, e)));
+        try!(stream.set_read_timeout(Some(Duration::from_secs(POLL_WAIT_SECS + 10)))
+            .map_err(|e| e.to_string()));
+        try!(stream.write_all(&encode_command(args))
+            .map_err(|e| format!("couldn't send to redis at '{}' ({})", self.address, e)));
+        match try!(read_reply(&mut BufReader::new(stream))) {
+            Reply::Error(msg) => Err(format!("redis refused '{}': {}", args[0], msg)),
+            reply => Ok(reply),
+        }
+    }
+}
+
+impl QueueClient for RedisQueue {
+    fn receive(&self) -> Result<Option<Message>, String> {
+        let processing = self.processing_list();
+        let wait = POLL_WAIT_SECS.to_string();
+        match try!(self.command(&["BRPOPLPUSH", &self.list, &processing, &wait])) {
+            Reply::Bulk(Some(body)) => {
+                Ok(Some(Message {
+                    receipt: body.clone(),
+                    body: body,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn acknowledge(&self, message: &Message) -> Result<(), String> {
+        let processing = self.processing_list();
+        self.command(&["LREM", &processing, "1", &message.receipt]).map(|_| ())
+    }
+}
+
+// jobs that run for longer than the queue's visibility timeout are delivered again
+pub struct SqsQueue {
+    pub url: String,
+}
+
+impl SqsQueue {
+    fn run(&self, args: Vec<&str>) -> Result<String, String> {
+        let output = try!(Command::new("aws")
+            .arg("sqs")
+            .args(&args)
+            .args(&["--queue-url", &self.url, "--output", "json"])
+            .output()
+            .map_err(|e| format!("couldn't run the aws cli ({})", e)));
+
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+        }
+    }
+}
+
+impl QueueClient for SqsQueue {
+    fn receive(&self) -> Result<Option<Message>, String> {
+        let wait = POLL_WAIT_SECS.to_string();
+        let output = try!(self.run(vec!["receive-message",
+                                        "--max-number-of-messages",
+                                        "1",
+                                        "--wait-time-seconds",
+                                        &wait]));
+        parse_sqs_messages(&output)
+    }
+
+    fn acknowledge(&self, message: &Message) -> Result<(), String> {
+        self.run(vec!["delete-message", "--receipt-handle", &message.receipt]).map(|_| ())
+    }
+}
+
+// the aws cli prints nothing when no message arrived
+pub fn parse_sqs_messages(output: &str) -> Result<Option<Message>, String> {
+    if output.trim().is_empty() {
+        return Ok(None);
+    }
+    let json = try!(Json::from_str(output)
+        .map_err(|e| format!("couldn't read the messages from SQS ({})", e)));
+    let message = json.find("Messages").and_then(|m| m.as_array()).and_then(|m| m.first());
+    match message {
+        Some(message) => {
+            let field = |name: &str| message.find(name).and_then(|f| f.as_string());
+            match (field("Body"), field("ReceiptHandle")) {
+                (Some(body), Some(receipt)) => {
+                    Ok(Some(Message {
+                        body: body.to_string(),
+                        receipt: receipt.to_string(),
+                    }))
+                }
+                _ => Err("SQS sent a message without a body or receipt handle".to_string()),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobMessage {
+    pub factfile: String,
+    pub variables: BTreeMap<String, Json>,
+}
+
+// a job message is {"factfile": "<path or https:// URL>", "variables": {...}}
+pub fn parse_job_message(body: &str) -> Result<JobMessage, String> {
+    let json = try!(Json::from_str(body)
+        .map_err(|e| format!("the job message isn't valid JSON ({})", e)));
+    let factfile = match json.find("factfile") {
+        Some(&Json::String(ref factfile)) if !factfile.is_empty() => factfile.clone(),
+        _ => return Err("the job message has no 'factfile'".to_string()),
+    };
+    let variables = match json.find("variables") {
+        Some(&Json::Object(ref variables)) => variables.clone(),
+        None => BTreeMap::new(),
+        Some(_) => return Err("the job message's 'variables' must be an object".to_string()),
+    };
+    Ok(JobMessage {
+        factfile: factfile,
+        variables: variables,
+    })
+}
+
+// the message's variables win over those given to the worker
+pub fn job_env(env: &Option<Json>, message: &JobMessage) -> Json {
+    let mut vars = match *env {
+        Some(Json::Object(ref o)) => o.clone(),
+        _ => BTreeMap::new(),
+    };
+    for (name, value) in message.variables.iter() {
+        vars.insert(name.clone(), value.clone());
+    }
+    Json::Object(vars)
+}
+
+pub fn is_url(factfile: &str) -> bool {
+    factfile.starts_with("https://") || factfile.starts_with("http://")
+}
+
+// remote factfiles are fetched to a temporary file, which the caller removes after the run
+pub fn fetch_factfile(url: &str) -> Result<PathBuf, String> {
+    if !url.starts_with("https://") {
+        return Err(format!("'{}' must be fetched over https", url));
+    }
+    let contents = try!(envfile::fetch_url(url, None));
+    let path = env::temp_dir().join(format!("factotum-{}.factfile", Uuid::new_v4()));
+    let mut f = try!(File::create(&path)
+        .map_err(|e| format!("couldn't create '{}' ({})", path.display(), e)));
+    try!(f.write_all(contents.as_bytes())
+        .map_err(|e| format!("couldn't write to '{}' ({})", path.display(), e)));
+    Ok(path)
+}
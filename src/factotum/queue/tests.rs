@@ -0,0 +1,164 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::io::{BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+#[test]
+fn parse_queue_good() {
+    assert_eq!(parse_queue("redis://localhost/jobs"),
+               Ok(QueueSource::Redis {
+                   address: "localhost:6379".to_string(),
+                   list: "jobs".to_string(),
+               }));
+    assert_eq!(parse_queue("redis://10.0.0.1:6380/etl:jobs"),
+               Ok(QueueSource::Redis {
+                   address: "10.0.0.1:6380".to_string(),
+                   list: "etl:jobs".to_string(),
+               }));
+    let url = "https://sqs.eu-west-1.amazonaws.com/123456789012/jobs";
+    assert_eq!(parse_queue(url), Ok(QueueSource::Sqs { url: url.to_string() }));
+}
+
+#[test]
+fn parse_queue_bad() {
+    assert_eq!(parse_queue("redis://localhost"),
+               Err("'redis://localhost' isn't a queue, it must be \
+                    redis://<host>[:<port>]/<list> or an SQS queue URL"
+                   .to_string()));
+    assert!(parse_queue("redis:///jobs").is_err());
+    assert!(parse_queue("https://example.com/jobs").is_err());
+}
+
+#[test]
+fn commands_encoded() {
+    assert_eq!(encode_command(&["LREM", "jobs:processing", "1", "{}"]),
+               b"*4\r\n$4\r\nLREM\r\n$15\r\njobs:processing\r\n$1\r\n1\r\n$2\r\n{}\r\n".to_vec());
+}
+
+#[test]
+fn replies_read() {
+    let read = |s: &str| read_reply(&mut BufReader::new(s.as_bytes()));
+    assert_eq!(read("+OK\r\n"), Ok(Reply::Status("OK".to_string())));
+    assert_eq!(read("-ERR wrong type\r\n"), Ok(Reply::Error("ERR wrong type".to_string())));
+    assert_eq!(read(":3\r\n"), Ok(Reply::Integer(3)));
+    assert_eq!(read("$5\r\nhello\r\n"), Ok(Reply::Bulk(Some("hello".to_string()))));
+    assert_eq!(read("$-1\r\n"), Ok(Reply::Bulk(None)));
+    assert_eq!(read("*2\r\n$1\r\na\r\n:1\r\n"),
+               Ok(Reply::Array(Some(vec![Reply::Bulk(Some("a".to_string())),
+                                         Reply::Integer(1)]))));
+    assert_eq!(read(""), Err("redis closed the connection".to_string()));
+    assert_eq!(read("?\r\n"), Err("redis sent an unexpected reply '?'".to_string()));
+}
+
+// answers each connection's command with the next reply, returning the commands it was sent
+fn fake_redis(replies: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let handle = thread::spawn(move || {
+        replies.into_iter()
+            .map(|reply| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buf = [0; 1024];
+                let n = stream.read(&mut buf).unwrap();
+                stream.write_all(reply.as_bytes()).unwrap();
+                String::from_utf8_lossy(&buf[..n]).into_owned()
+            })
+            .collect()
+    });
+    (address, handle)
+}
+
+#[test]
+fn redis_messages_received_and_acknowledged() {
+    let (address, server) = fake_redis(vec!["$2\r\n{}\r\n", ":1\r\n", "$-1\r\n"]);
+    let queue = RedisQueue {
+        address: address,
+        list: "jobs".to_string(),
+    };
+
+    let message = queue.receive().unwrap().unwrap();
+    assert_eq!(message,
+               Message {
+                   body: "{}".to_string(),
+                   receipt: "{}".to_string(),
+               });
+    assert_eq!(queue.acknowledge(&message), Ok(()));
+    assert_eq!(queue.receive(), Ok(None));
+
+    let commands = server.join().unwrap();
+    assert!(commands[0].contains("BRPOPLPUSH\r\n$4\r\njobs\r\n$15\r\njobs:processing"));
+    assert!(commands[1].contains("LREM\r\n$15\r\njobs:processing\r\n$1\r\n1\r\n$2\r\n{}"));
+}
+
+#[test]
+fn redis_errors_reported() {
+    let (address, server) = fake_redis(vec!["-WRONGTYPE not a list\r\n"]);
+    let queue = RedisQueue {
+        address: address,
+        list: "jobs".to_string(),
+    };
+    assert_eq!(queue.receive(),
+               Err("redis refused 'BRPOPLPUSH': WRONGTYPE not a list".to_string()));
+    server.join().unwrap();
+}
+
+#[test]
+fn sqs_messages_parsed() {
+    assert_eq!(parse_sqs_messages(""), Ok(None));
+    assert_eq!(parse_sqs_messages(r#"{"Messages": [{"MessageId": "1", "ReceiptHandle": "r-1",
+                                                    "Body": "{\"factfile\": \"a\"}"}]}"#),
+               Ok(Some(Message {
+                   body: "{\"factfile\": \"a\"}".to_string(),
+                   receipt: "r-1".to_string(),
+               })));
+    assert!(parse_sqs_messages(r#"{"Messages": [{"MessageId": "1"}]}"#).is_err());
+}
+
+#[test]
+fn job_messages_parsed() {
+    let message = parse_job_message(r#"{"factfile": "jobs/etl.factfile",
+                                        "variables": {"date": "2021-01-01"}}"#)
+        .unwrap();
+    assert_eq!(message.factfile, "jobs/etl.factfile");
+    assert_eq!(message.variables.get("date"),
+               Some(&Json::String("2021-01-01".to_string())));
+
+    assert_eq!(parse_job_message(r#"{"factfile": "a.factfile"}"#).unwrap().variables,
+               BTreeMap::new());
+    assert_eq!(parse_job_message(r#"{"variables": {}}"#),
+               Err("the job message has no 'factfile'".to_string()));
+    assert_eq!(parse_job_message(r#"{"factfile": "a", "variables": []}"#),
+               Err("the job message's 'variables' must be an object".to_string()));
+    assert!(parse_job_message("nope").is_err());
+}
+
+#[test]
+fn job_env_prefers_the_message() {
+    let env = Some(Json::from_str(r#"{"date": "2020-01-01", "region": "eu"}"#).unwrap());
+    let message = parse_job_message(r#"{"factfile": "a", "variables": {"date": "2021-01-01"}}"#)
+        .unwrap();
+    assert_eq!(job_env(&env, &message),
+               Json::from_str(r#"{"date": "2021-01-01", "region": "eu"}"#).unwrap());
+}
+
+#[test]
+fn remote_factfiles_need_https() {
+    assert!(is_url("https://example.com/etl.factfile"));
+    assert!(!is_url("jobs/etl.factfile"));
+    assert_eq!(fetch_factfile("http://example.com/etl.factfile"),
+               Err("'http://example.com/etl.factfile' must be fetched over https".to_string()));
+}
@@ -0,0 +1,293 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::task_list::{Task, State};
+use factotum::factfile::Task as FactfileTask;
+use factotum::webhook::jobcontext::JobContext;
+use factotum::criticalpath;
+use factotum::webhook::jobupdate::to_string_datetime;
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::iter;
+use std::time::Duration;
+
+pub const FAILURE_EVENT: &'static str = "TASK_FAILED";
+
+pub fn task_state_name(state: &State) -> &'static str {
+    match *state {
+        State::Waiting => "WAITING",
+        State::Running => "RUNNING",
+        State::Success => "SUCCEEDED",
+        State::SuccessNoop => "SUCCEEDED_NO_OP",
+        State::Failed(_) => "FAILED",
+        State::Skipped(_) => "SKIPPED",
+    }
+}
+
+pub fn job_state_name(tasks: &Vec<&Task<&FactfileTask>>) -> &'static str {
+    let failed = tasks.iter().any(|t| match t.state {
+        State::Failed(_) => true,
+        _ => false,
+    });
+    if failed { "FAILED" } else { "SUCCEEDED" }
+}
+
+pub fn duration_millis(d: &Duration) -> u64 {
+    d.as_secs() * 1000 + (d.subsec_nanos() / 1000000) as u64
+}
+
+fn task_to_json(task: &Task<&FactfileTask>) -> Json {
+    let mut d = BTreeMap::new();
+
+    d.insert("taskName".to_string(), task.name.to_json());
+    d.insert("state".to_string(), task_state_name(&task.state).to_json());
+    d.insert("dependsOn".to_string(), task.task_spec.depends_on.to_json());
+    if let Some(ref description) = task.task_spec.description {
+        d.insert("description".to_string(), description.to_json());
+    }
+    if let Some(ref owner) = task.task_spec.owner {
+        d.insert("owner".to_string(), owner.to_json());
+    }
+
+    if let Some(ref started) = task.run_started {
+        d.insert("started".to_string(), to_string_datetime(started).to_json());
+    }
+    if let Some(ref key) = task.cache_key {
+        d.insert("cacheKey".to_string(), key.to_json());
+    }
+
+    match task.state {
+        State::Failed(ref reason) |
+        State::Skipped(ref reason) => {
+            d.insert("errorMessage".to_string(), reason.to_json());
+        }
+        _ => {}
+    }
+
+    if let Some(ref result) = task.run_result {
+        d.insert("duration".to_string(),
+                 ChronoDuration::from_std(result.duration).unwrap().to_string().to_json());
+        d.insert("durationMillis".to_string(), duration_millis(&result.duration).to_json());
+        d.insert("returnCode".to_string(), result.return_code.to_json());
+        if let Some(ref usage) = result.resource_usage {
+            d.insert("maxRssKb".to_string(), usage.max_rss_kb.to_json());
+            d.insert("userCpu".to_string(),
+                     ChronoDuration::from_std(usage.user_time).unwrap().to_string().to_json());
+            d.insert("systemCpu".to_string(),
+                     ChronoDuration::from_std(usage.system_time).unwrap().to_string().to_json());
+        }
+        if let Some(ref execution_error) = result.task_execution_error {
+            d.insert("errorMessage".to_string(), execution_error.to_string().to_json());
+        }
+        if let Some(ref spilled) = result.spilled_output {
+            if let Some(ref path) = spilled.stdout_file {
+                d.insert("stdoutFile".to_string(), path.to_string_lossy().to_json());
+            }
+            if let Some(ref path) = spilled.stderr_file {
+                d.insert("stderrFile".to_string(), path.to_string_lossy().to_json());
+            }
+        }
+    }
+
+    if !task.artifacts.is_empty() {
+        d.insert("artifacts".to_string(), task.artifacts.to_json());
+    }
+
+    Json::Object(d)
+}
+
+pub fn run_result_json(context: &JobContext, tasks: &Vec<&Task<&FactfileTask>>) -> Json {
+    let mut d = BTreeMap::new();
+
+    d.insert("jobName".to_string(), context.job_name.to_json());
+    d.insert("jobReference".to_string(), context.job_reference.to_json());
+    d.insert("runReference".to_string(), context.run_reference.to_json());
+    d.insert("factotumVersion".to_string(),
+             context.factotum_version.to_json());
+    d.insert("startTime".to_string(),
+             to_string_datetime(&context.start_time).to_json());
+    d.insert("runState".to_string(), job_state_name(tasks).to_json());
+    d.insert("tags".to_string(), context.tags.to_json());
+    d.insert("context".to_string(), context.context_json());
+    d.insert("tasks".to_string(),
+             Json::Array(tasks.iter()
+                 .flat_map(|t| iter::once(task_to_json(t)).chain(t.nested_tasks.iter().cloned()))
+                 .collect()));
+
+    Json::Object(d)
+}
+
+// sent to the alert webhook for each task that failed, so it can be routed to the task's owner
+pub fn failure_alert_json(context: &JobContext, task: &Task<&FactfileTask>) -> Json {
+    let mut d = BTreeMap::new();
+    d.insert("event".to_string(), FAILURE_EVENT.to_json());
+    d.insert("jobName".to_string(), context.job_name.to_json());
+    d.insert("jobReference".to_string(), context.job_reference.to_json());
+    d.insert("runReference".to_string(), context.run_reference.to_json());
+    d.insert("taskName".to_string(), task.name.to_json());
+    if let State::Failed(ref reason) = task.state {
+        d.insert("errorMessage".to_string(), reason.to_json());
+    }
+    if let Some(ref description) = task.task_spec.description {
+        d.insert("description".to_string(), description.to_json());
+    }
+    d.insert("owner".to_string(), task.task_spec.owner.to_json());
+    d.insert("context".to_string(), context.context_json());
+    Json::Object(d)
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+        .replace("'", "&#39;")
+}
+
+// a nested factfile's task, from its run result
+fn nested_task_row(task: &Json) -> String {
+    let field = |key: &str| {
+        match task.find(key) {
+            Some(&Json::String(ref s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => "".to_string(),
+        }
+    };
+    let max_rss = match task.find("maxRssKb") {
+        Some(kb) => format!("{} KB", kb),
+        None => "".to_string(),
+    };
+    let cpu = match (task.find("userCpu"), task.find("systemCpu")) {
+        (Some(_), Some(_)) => format!("{} / {}", field("userCpu"), field("systemCpu")),
+        _ => "".to_string(),
+    };
+    format!("      <tr class=\"{}\"><td{}>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            field("state").to_lowercase(),
+            title_attribute(&task.find("description").and_then(|d| d.as_string())),
+            escape_html(&field("taskName")),
+            field("state"),
+            field("started"),
+            field("duration"),
+            max_rss,
+            cpu,
+            field("returnCode"),
+            escape_html(&field("errorMessage")),
+            escape_html(&field("owner")))
+}
+
+fn title_attribute(description: &Option<&str>) -> String {
+    match *description {
+        Some(description) => format!(" title=\"{}\"", escape_html(description)),
+        None => "".to_string(),
+    }
+}
+
+pub fn html_report(context: &JobContext, tasks: &Vec<&Task<&FactfileTask>>) -> String {
+    let rows = tasks.iter()
+        .map(|t| {
+            let started = match t.run_started {
+                Some(ref s) => to_string_datetime(s),
+                None => "".to_string(),
+            };
+            let (duration, return_code) = match t.run_result {
+                Some(ref r) => {
+                    (ChronoDuration::from_std(r.duration).unwrap().to_string(),
+                     r.return_code.to_string())
+                }
+                None => ("".to_string(), "".to_string()),
+            };
+            let (max_rss, cpu) = match t.run_result.as_ref().and_then(|r| r.resource_usage.as_ref()) {
+                Some(usage) => {
+                    (format!("{} KB", usage.max_rss_kb),
+                     format!("{} / {}",
+                             ChronoDuration::from_std(usage.user_time).unwrap(),
+                             ChronoDuration::from_std(usage.system_time).unwrap()))
+                }
+                None => ("".to_string(), "".to_string()),
+            };
+            let reason = match t.state {
+                State::Failed(ref m) |
+                State::Skipped(ref m) => m.clone(),
+                _ => "".to_string(),
+            };
+            let row = format!("      <tr class=\"{}\"><td{}>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    task_state_name(&t.state).to_lowercase(),
+                    title_attribute(&t.task_spec.description.as_ref().map(|d| d.as_str())),
+                    escape_html(&t.name),
+                    task_state_name(&t.state),
+                    started,
+                    duration,
+                    max_rss,
+                    cpu,
+                    return_code,
+                    escape_html(&reason),
+                    escape_html(t.task_spec.owner.as_ref().map(|o| o.as_str()).unwrap_or("")));
+            iter::once(row).chain(t.nested_tasks.iter().map(nested_task_row)).collect::<String>()
+        })
+        .collect::<String>();
+
+    let path = criticalpath::critical_path(&criticalpath::from_tasks(tasks));
+    let critical_path = if path.is_empty() {
+        "".to_string()
+    } else {
+        let items = path.iter()
+            .map(|t| {
+                format!("      <li>{} ({})</li>\n",
+                        escape_html(&t.name),
+                        ChronoDuration::milliseconds(t.duration_millis as i64))
+            })
+            .collect::<String>();
+        format!("    <h2>Critical path</h2>
+    <p>These tasks determined the run's total duration of {}:</p>
+    <ol>
+{}    </ol>
+",
+                ChronoDuration::milliseconds(criticalpath::total_millis(&path) as i64),
+                items)
+    };
+
+    format!("<!DOCTYPE html>
+<html>
+  <head>
+    <meta charset=\"utf-8\">
+    <title>{name} - {run}</title>
+    <style>
+      body {{ font-family: sans-serif; }}
+      table {{ border-collapse: collapse; }}
+      td, th {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}
+      tr.succeeded td {{ background: #e6f4ea; }}
+      tr.failed td {{ background: #fce8e6; }}
+      tr.skipped td, tr.succeeded_no_op td {{ background: #f1f3f4; }}
+    </style>
+  </head>
+  <body>
+    <h1>{name}</h1>
+    <p>Run <code>{run}</code> started at {start}: <strong>{state}</strong></p>
+    <table>
+      <tr><th>Task</th><th>State</th><th>Started</th><th>Duration</th><th>Max RSS</th><th>CPU (user / system)</th><th>Return code</th><th>Reason</th><th>Owner</th></tr>
+{rows}    </table>
+{critical_path}  </body>
+</html>
+",
+            name = escape_html(&context.job_name),
+            run = context.run_reference,
+            start = to_string_datetime(&context.start_time),
+            state = job_state_name(tasks),
+            rows = rows,
+            critical_path = critical_path)
+}
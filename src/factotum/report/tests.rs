@@ -0,0 +1,204 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::task_list::{Task, State};
+use factotum::executor::execution_strategy::{RunResult, ResourceUsage, SpilledOutput};
+use std::path::PathBuf;
+use factotum::factfile::Task as FactfileTask;
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use chrono::UTC;
+use std::time::Duration;
+
+fn make_run_task<'a>(spec: &'a FactfileTask, state: State, code: Option<i32>) -> Task<&'a FactfileTask> {
+    let mut task = Task::new(spec.name.clone(), spec);
+    task.state = state;
+    if let Some(return_code) = code {
+        task.run_started = Some(UTC::now());
+        task.run_result = Some(RunResult {
+            duration: Duration::from_secs(5),
+            task_execution_error: None,
+            stdout: Some("hello".to_string()),
+            stderr: None,
+            return_code: return_code,
+            resource_usage: None,
+            spilled_output: None,
+        });
+    }
+    task
+}
+
+#[test]
+fn task_state_names_match_webhook_states() {
+    assert_eq!(task_state_name(&State::Waiting), "WAITING");
+    assert_eq!(task_state_name(&State::Running), "RUNNING");
+    assert_eq!(task_state_name(&State::Success), "SUCCEEDED");
+    assert_eq!(task_state_name(&State::SuccessNoop), "SUCCEEDED_NO_OP");
+    assert_eq!(task_state_name(&State::Failed("x".into())), "FAILED");
+    assert_eq!(task_state_name(&State::Skipped("x".into())), "SKIPPED");
+}
+
+#[test]
+fn run_result_json_good() {
+    let context = JobContext::new("my job", "{}", None);
+    let spec_a = make_task("a", &vec![]);
+    let spec_b = make_task("b", &vec!["a"]);
+    let a = make_run_task(&spec_a, State::Failed("bad code".into()), Some(1));
+    let b = make_run_task(&spec_b, State::Skipped("the task 'a' failed".into()), None);
+    let tasks = vec![&a, &b];
+
+    let result = run_result_json(&context, &tasks);
+
+    assert_eq!(result.find("jobName").unwrap().as_string(), Some("my job"));
+    assert_eq!(result.find("runReference").unwrap().as_string(),
+               Some(context.run_reference.as_ref()));
+    assert_eq!(result.find("runState").unwrap().as_string(), Some("FAILED"));
+    assert_eq!(result.find("context"), Some(&context.context_json()));
+
+    let task_results = result.find("tasks").unwrap().as_array().unwrap();
+    assert_eq!(task_results.len(), 2);
+    assert_eq!(task_results[0].find("returnCode").unwrap().as_i64(), Some(1));
+    assert_eq!(task_results[0].find("duration").unwrap().as_string(),
+               Some("PT5S"));
+    assert_eq!(task_results[1].find("state").unwrap().as_string(), Some("SKIPPED"));
+    assert_eq!(task_results[1].find("errorMessage").unwrap().as_string(),
+               Some("the task 'a' failed"));
+    assert!(task_results[1].find("returnCode").is_none());
+    assert_eq!(task_results[0].find("durationMillis").unwrap().as_u64(), Some(5000));
+    assert_eq!(task_results[1].find("dependsOn").unwrap().as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn html_report_escapes_names() {
+    let context = JobContext::new("<job>", "{}", None);
+    let spec = make_task("a & b", &vec![]);
+    let task = make_run_task(&spec, State::Success, Some(0));
+
+    let html = html_report(&context, &vec![&task]);
+
+    assert!(html.contains("<h1>&lt;job&gt;</h1>"));
+    assert!(html.contains("<td>a &amp; b</td><td>SUCCEEDED</td>"));
+    assert!(html.contains("<strong>SUCCEEDED</strong>"));
+}
+
+#[test]
+fn html_report_lists_critical_path() {
+    let context = JobContext::new("job", "{}", None);
+    let spec_a = make_task("a", &vec![]);
+    let spec_b = make_task("b", &vec!["a"]);
+    let a = make_run_task(&spec_a, State::Success, Some(0));
+    let b = make_run_task(&spec_b, State::Success, Some(0));
+
+    let html = html_report(&context, &vec![&a, &b]);
+    assert!(html.contains("<h2>Critical path</h2>"));
+    assert!(html.contains("total duration of PT10S"));
+    assert!(html.contains("<ol>\n      <li>a (PT5S)</li>\n      <li>b (PT5S)</li>\n    </ol>"));
+
+    let not_run = make_run_task(&spec_a, State::Skipped("x".into()), None);
+    assert!(!html_report(&context, &vec![&not_run]).contains("Critical path"));
+}
+
+#[test]
+fn resource_usage_is_reported() {
+    let context = JobContext::new("job", "{}", None);
+    let spec = make_task("a", &vec![]);
+    let mut task = make_run_task(&spec, State::Success, Some(0));
+    if let Some(ref mut result) = task.run_result {
+        result.resource_usage = Some(ResourceUsage {
+            max_rss_kb: 1024,
+            user_time: Duration::from_millis(1500),
+            system_time: Duration::from_millis(250),
+        });
+    }
+
+    let result = run_result_json(&context, &vec![&task]);
+    let task_result = &result.find("tasks").unwrap().as_array().unwrap()[0];
+    assert_eq!(task_result.find("maxRssKb").unwrap().as_u64(), Some(1024));
+    assert_eq!(task_result.find("userCpu").unwrap().as_string(), Some("PT1.500S"));
+    assert_eq!(task_result.find("systemCpu").unwrap().as_string(),
+               Some("PT0.250S"));
+
+    let html = html_report(&context, &vec![&task]);
+    assert!(html.contains("<td>PT5S</td><td>1024 KB</td><td>PT1.500S / PT0.250S</td><td>0</td>"));
+}
+
+#[test]
+fn spilled_output_files_are_reported() {
+    let context = JobContext::new("job", "{}", None);
+    let spec = make_task("a", &vec![]);
+    let mut task = make_run_task(&spec, State::Success, Some(0));
+    if let Some(ref mut result) = task.run_result {
+        result.spilled_output = Some(SpilledOutput {
+            stdout_file: Some(PathBuf::from("/tmp/factotum-output/a-1-0.stdout.log")),
+            stderr_file: None,
+        });
+    }
+
+    let result = run_result_json(&context, &vec![&task]);
+    let task_result = &result.find("tasks").unwrap().as_array().unwrap()[0];
+    assert_eq!(task_result.find("stdoutFile").unwrap().as_string(),
+               Some("/tmp/factotum-output/a-1-0.stdout.log"));
+    assert_eq!(task_result.find("stderrFile"), None);
+}
+
+#[test]
+fn nested_tasks_follow_their_parent() {
+    let context = JobContext::new("job", "{}", None);
+    let spec = make_task("child", &vec![]);
+    let mut task = make_run_task(&spec, State::Success, Some(0));
+    task.nested_tasks = vec![Json::from_str(r#"{"taskName": "child/load", "state": "SUCCEEDED",
+                                                "duration": "PT2S", "returnCode": 0,
+                                                "dependsOn": []}"#)
+                                 .unwrap()];
+
+    let result = run_result_json(&context, &vec![&task]);
+    let task_results = result.find("tasks").unwrap().as_array().unwrap();
+    assert_eq!(task_results.len(), 2);
+    assert_eq!(task_results[1].find("taskName").unwrap().as_string(),
+               Some("child/load"));
+
+    let html = html_report(&context, &vec![&task]);
+    assert!(html.contains("<tr class=\"succeeded\"><td>child/load</td><td>SUCCEEDED</td><td></td>\
+                           <td>PT2S</td><td></td><td></td><td>0</td><td></td><td></td></tr>"));
+}
+
+#[test]
+fn task_metadata_is_reported() {
+    let context = JobContext::new("job", "{}", None);
+    let mut spec = make_task("load", &vec![]);
+    spec.description = Some("Loads <orders>".to_string());
+    spec.owner = Some("data-eng".to_string());
+    let task = make_run_task(&spec, State::Failed("exit 1".into()), Some(1));
+
+    let result = run_result_json(&context, &vec![&task]);
+    let task_result = &result.find("tasks").unwrap().as_array().unwrap()[0];
+    assert_eq!(task_result.find("owner").unwrap().as_string(), Some("data-eng"));
+    assert_eq!(task_result.find("description").unwrap().as_string(),
+               Some("Loads <orders>"));
+
+    let html = html_report(&context, &vec![&task]);
+    assert!(html.contains("<td title=\"Loads &lt;orders&gt;\">load</td>"));
+    assert!(html.contains("<td>exit 1</td><td>data-eng</td></tr>"));
+
+    let alert = failure_alert_json(&context, &task);
+    assert_eq!(alert.find("event").unwrap().as_string(), Some(FAILURE_EVENT));
+    assert_eq!(alert.find("taskName").unwrap().as_string(), Some("load"));
+    assert_eq!(alert.find("owner").unwrap().as_string(), Some("data-eng"));
+    assert_eq!(alert.find("errorMessage").unwrap().as_string(), Some("exit 1"));
+
+    let unowned = make_task("other", &vec![]);
+    let task = make_run_task(&unowned, State::Failed("exit 1".into()), Some(1));
+    assert_eq!(failure_alert_json(&context, &task).find("owner"), Some(&Json::Null));
+}
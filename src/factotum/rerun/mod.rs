@@ -0,0 +1,37 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Factfile;
+use factotum::history::RunRecord;
+
+fn succeeded(run: &RunRecord, task_name: &str) -> bool {
+    match run.task(task_name) {
+        Some(task) => task.state == "SUCCEEDED" || task.state == "SUCCEEDED_NO_OP",
+        None => false,
+    }
+}
+
+// the tasks that failed or never ran (skipped, or missing from the run), in the order they
+// run; a dependency that wasn't met is one of these too, so it runs again before its dependents
+pub fn rerun_targets(ff: &Factfile, run: &RunRecord) -> Vec<String> {
+    ff.get_tasks_in_order()
+        .into_iter()
+        .flat_map(|grp| grp.into_iter())
+        .filter(|t| !succeeded(run, &t.name))
+        .map(|t| t.name.clone())
+        .collect()
+}
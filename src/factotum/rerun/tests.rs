@@ -0,0 +1,85 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::Factfile;
+use factotum::history::{RunRecord, TaskRecord};
+use factotum::tests::make_task;
+
+fn run_with(states: Vec<(&str, &str)>) -> RunRecord {
+    RunRecord {
+        job_name: "job".to_string(),
+        run_reference: "abc".to_string(),
+        start_time: "".to_string(),
+        run_state: "FAILED".to_string(),
+        tasks: states.iter()
+            .map(|&(name, state)| {
+                TaskRecord {
+                    task_name: name.to_string(),
+                    state: state.to_string(),
+                    error_message: None,
+                    depends_on: vec![],
+                    started: None,
+                    duration_millis: None,
+                    cache_key: None,
+                }
+            })
+            .collect(),
+        source: None,
+    }
+}
+
+fn diamond() -> Factfile {
+    //   a
+    //  / \
+    // b   c
+    //  \ /
+    //   d     e
+    let mut ff = Factfile::new("N/A", "job");
+    ff.add_task_obj(&make_task("a", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("b", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("c", &vec!["a"])).unwrap();
+    ff.add_task_obj(&make_task("d", &vec!["b", "c"])).unwrap();
+    ff.add_task_obj(&make_task("e", &vec![])).unwrap();
+    ff
+}
+
+#[test]
+fn rerun_targets_are_failed_and_skipped_tasks() {
+    let run = run_with(vec![("a", "SUCCEEDED"),
+                            ("b", "FAILED"),
+                            ("c", "SUCCEEDED"),
+                            ("d", "SKIPPED"),
+                            ("e", "SUCCEEDED_NO_OP")]);
+
+    assert_eq!(rerun_targets(&diamond(), &run), vec!["b", "d"]);
+}
+
+#[test]
+fn rerun_targets_include_tasks_missing_from_the_run() {
+    let run = run_with(vec![("b", "SUCCEEDED"), ("c", "FAILED"), ("e", "SUCCEEDED")]);
+
+    assert_eq!(rerun_targets(&diamond(), &run), vec!["a", "c", "d"]);
+}
+
+#[test]
+fn rerun_targets_are_empty_for_a_successful_run() {
+    let run = run_with(vec![("a", "SUCCEEDED"),
+                            ("b", "SUCCEEDED"),
+                            ("c", "SUCCEEDED"),
+                            ("d", "SUCCEEDED"),
+                            ("e", "SUCCEEDED")]);
+
+    assert!(rerun_targets(&diamond(), &run).is_empty());
+}
@@ -0,0 +1,107 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use std::cmp;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub delay: Duration,
+    pub max_delay: Option<Duration>,
+    pub jitter_percent: u32,
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            max_retries: max_retries,
+            delay: delay,
+            max_delay: None,
+            jitter_percent: 0,
+        }
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter_percent: u32) -> Result<Self, String> {
+        if jitter_percent > 100 {
+            return Err(format!("the jitter must be a percentage between 0 and 100, not {}",
+                               jitter_percent));
+        }
+        self.jitter_percent = jitter_percent;
+        Ok(self)
+    }
+
+    pub fn none() -> Self {
+        RetryPolicy::new(0, Duration::from_secs(0))
+    }
+
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt <= self.max_retries
+    }
+
+    // with a cap the delay doubles after each attempt until it reaches the cap,
+    // without one every retry waits for the same delay
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        match self.max_delay {
+            Some(max_delay) => {
+                let exponent = cmp::min(attempt.saturating_sub(1), 32);
+                let secs = self.delay.as_secs().saturating_mul(1u64 << exponent);
+                cmp::min(Duration::from_secs(secs), max_delay)
+            }
+            None => self.delay,
+        }
+    }
+
+    // random is expected to be in the range [0, 1) and takes up to
+    // jitter_percent off the backoff so parallel retries spread out
+    pub fn delay_for(&self, attempt: u32, random: f64) -> Duration {
+        let backoff = self.backoff(attempt);
+        let jitter_millis = (backoff.as_secs() * 1000) as f64 * (self.jitter_percent as f64 / 100.0) *
+                            random;
+        backoff - Duration::from_millis(jitter_millis as u64)
+    }
+}
+
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let duration = duration.trim();
+    if duration.is_empty() {
+        return Err("the duration is empty".to_string());
+    }
+
+    let (number, multiplier) = match duration.chars().last().unwrap() {
+        's' => (&duration[..duration.len() - 1], 1),
+        'm' => (&duration[..duration.len() - 1], 60),
+        'h' => (&duration[..duration.len() - 1], 60 * 60),
+        'd' => (&duration[..duration.len() - 1], 60 * 60 * 24),
+        _ => (duration, 1),
+    };
+
+    // a number too big to be a duration in seconds is as invalid as one that isn't a number
+    match number.parse::<u64>().ok().and_then(|n| n.checked_mul(multiplier)) {
+        Some(secs) => Ok(Duration::from_secs(secs)),
+        None => {
+            Err(format!("'{}' is not a valid duration (expected a whole number followed by \
+                         's', 'm', 'h' or 'd')",
+                        duration))
+        }
+    }
+}
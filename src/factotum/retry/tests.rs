@@ -0,0 +1,78 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::time::Duration;
+
+#[test]
+fn parse_duration_units() {
+    assert_eq!(parse_duration("30"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86400)));
+}
+
+#[test]
+fn parse_duration_bad() {
+    assert_eq!(parse_duration(""), Err("the duration is empty".to_string()));
+    assert_eq!(parse_duration("5 minutes"),
+               Err("'5 minutes' is not a valid duration (expected a whole number followed by \
+                    's', 'm', 'h' or 'd')"
+                   .to_string()));
+    assert!(parse_duration("-5m").is_err());
+    assert_eq!(parse_duration("999999999999999999d"),
+               Err("'999999999999999999d' is not a valid duration (expected a whole number \
+                    followed by 's', 'm', 'h' or 'd')"
+                   .to_string()));
+}
+
+#[test]
+fn should_retry_until_exhausted() {
+    let policy = RetryPolicy::new(2, Duration::from_secs(1));
+    assert!(policy.should_retry(1));
+    assert!(policy.should_retry(2));
+    assert!(!policy.should_retry(3));
+    assert!(!RetryPolicy::none().should_retry(1));
+}
+
+#[test]
+fn backoff_without_cap_is_constant() {
+    let policy = RetryPolicy::new(5, Duration::from_secs(30));
+    assert_eq!(policy.backoff(1), Duration::from_secs(30));
+    assert_eq!(policy.backoff(4), Duration::from_secs(30));
+}
+
+#[test]
+fn backoff_with_cap_doubles_until_capped() {
+    let policy = RetryPolicy::new(10, Duration::from_secs(30)).with_max_delay(Duration::from_secs(100));
+    assert_eq!(policy.backoff(1), Duration::from_secs(30));
+    assert_eq!(policy.backoff(2), Duration::from_secs(60));
+    assert_eq!(policy.backoff(3), Duration::from_secs(100));
+    assert_eq!(policy.backoff(200), Duration::from_secs(100));
+}
+
+#[test]
+fn jitter_reduces_delay_within_bounds() {
+    let policy = RetryPolicy::new(3, Duration::from_secs(60)).with_jitter(50).unwrap();
+    assert_eq!(policy.delay_for(1, 0.0), Duration::from_secs(60));
+    assert_eq!(policy.delay_for(1, 0.5), Duration::from_secs(45));
+    assert_eq!(policy.delay_for(1, 0.999), Duration::from_millis(30030));
+}
+
+#[test]
+fn jitter_must_be_a_percentage() {
+    assert_eq!(RetryPolicy::new(1, Duration::from_secs(1)).with_jitter(101),
+               Err("the jitter must be a percentage between 0 and 100, not 101".to_string()));
+}
@@ -0,0 +1,319 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Sandbox;
+use std::io;
+
+// a sandbox ready to be entered by a task's process between fork and exec, where nothing
+// can be allocated - so every path and the seccomp filter are built beforehand
+#[cfg(target_os = "linux")]
+pub struct SandboxSetup {
+    unshare_flags: libc::c_int,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    read_only_root: bool,
+    private_tmp: bool,
+    writable_paths: Vec<CString>,
+    filter: Option<Vec<libc::sock_filter>>,
+}
+
+#[cfg(not(target_os = "linux"))]
+pub struct SandboxSetup;
+
+#[cfg(target_os = "linux")]
+use std::env;
+#[cfg(target_os = "linux")]
+use std::ffi::CString;
+
+#[cfg(target_os = "linux")]
+mod consts {
+    // from linux/mount.h and linux/audit.h, which older versions of libc don't have
+    pub const AT_RECURSIVE: libc::c_uint = 0x8000;
+    pub const MOUNT_ATTR_RDONLY: u64 = 0x1;
+    #[cfg(target_arch = "x86_64")]
+    pub const AUDIT_ARCH: u32 = 0xc000003e;
+    #[cfg(target_arch = "aarch64")]
+    pub const AUDIT_ARCH: u32 = 0xc00000b7;
+
+    pub const SECCOMP_RET_ALLOW: u32 = 0x7fff0000;
+    pub const SECCOMP_RET_ERRNO: u32 = 0x00050000;
+    pub const SECCOMP_RET_KILL_PROCESS: u32 = 0x80000000;
+
+    #[repr(C)]
+    pub struct MountAttr {
+        pub attr_set: u64,
+        pub attr_clr: u64,
+        pub propagation: u64,
+        pub userns_fd: u64,
+    }
+}
+
+// system calls that could reach outside the sandbox (or undo it), which fail with EPERM
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[cfg(target_os = "linux")]
+const DENIED_SYSCALLS: [libc::c_long; 34] = [libc::SYS_mount,
+                                             libc::SYS_umount2,
+                                             libc::SYS_pivot_root,
+                                             libc::SYS_unshare,
+                                             libc::SYS_setns,
+                                             libc::SYS_ptrace,
+                                             libc::SYS_process_vm_readv,
+                                             libc::SYS_process_vm_writev,
+                                             libc::SYS_kexec_load,
+                                             libc::SYS_kexec_file_load,
+                                             libc::SYS_reboot,
+                                             libc::SYS_init_module,
+                                             libc::SYS_finit_module,
+                                             libc::SYS_delete_module,
+                                             libc::SYS_bpf,
+                                             libc::SYS_perf_event_open,
+                                             libc::SYS_keyctl,
+                                             libc::SYS_add_key,
+                                             libc::SYS_request_key,
+                                             libc::SYS_swapon,
+                                             libc::SYS_swapoff,
+                                             libc::SYS_open_by_handle_at,
+                                             libc::SYS_userfaultfd,
+                                             libc::SYS_acct,
+                                             libc::SYS_settimeofday,
+                                             libc::SYS_clock_settime,
+                                             libc::SYS_quotactl,
+                                             libc::SYS_open_tree,
+                                             libc::SYS_move_mount,
+                                             libc::SYS_fsopen,
+                                             libc::SYS_fsconfig,
+                                             libc::SYS_fsmount,
+                                             libc::SYS_fspick,
+                                             libc::SYS_mount_setattr];
+
+#[cfg(target_os = "linux")]
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k: k,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: jt,
+        jf: jf,
+        k: k,
+    }
+}
+
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub fn seccomp_filter() -> Result<Vec<libc::sock_filter>, String> {
+    use self::consts::*;
+    use libc::{BPF_ABS, BPF_JEQ, BPF_JGE, BPF_JMP, BPF_K, BPF_LD, BPF_RET, BPF_W};
+
+    let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+    // seccomp_data is the syscall number, then the architecture
+    let mut filter = vec![bpf_stmt(BPF_LD | BPF_W | BPF_ABS, 4),
+                          bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, AUDIT_ARCH, 1, 0),
+                          bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_KILL_PROCESS),
+                          bpf_stmt(BPF_LD | BPF_W | BPF_ABS, 0)];
+    if cfg!(target_arch = "x86_64") {
+        // x32 system calls share the architecture, but not the numbers
+        filter.push(bpf_jump(BPF_JMP | BPF_JGE | BPF_K, 0x40000000, 0, 1));
+        filter.push(bpf_stmt(BPF_RET | BPF_K, deny));
+    }
+    for syscall in DENIED_SYSCALLS.iter() {
+        filter.push(bpf_jump(BPF_JMP | BPF_JEQ | BPF_K, *syscall as u32, 0, 1));
+        filter.push(bpf_stmt(BPF_RET | BPF_K, deny));
+    }
+    filter.push(bpf_stmt(BPF_RET | BPF_K, SECCOMP_RET_ALLOW));
+    Ok(filter)
+}
+
+#[cfg(all(target_os = "linux", not(any(target_arch = "x86_64", target_arch = "aarch64"))))]
+pub fn seccomp_filter() -> Result<Vec<libc::sock_filter>, String> {
+    Err("seccomp is only supported on x86_64 and aarch64".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn c_string(value: &str) -> Result<CString, String> {
+    CString::new(value).map_err(|_| format!("'{}' has a nul byte in it", value.escape_default()))
+}
+
+#[cfg(target_os = "linux")]
+pub fn prepare(sandbox: &Sandbox) -> Result<SandboxSetup, String> {
+    let mut unshare_flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWIPC |
+                            libc::CLONE_NEWUTS;
+    if !sandbox.network {
+        // a new network namespace has nothing in it but a loopback interface that's down
+        unshare_flags |= libc::CLONE_NEWNET;
+    }
+
+    let working_dir = try!(env::current_dir()
+        .map_err(|e| format!("couldn't get the working directory ({})", e)));
+    let mut writable_paths = vec![];
+    for path in sandbox.writable_paths.iter() {
+        writable_paths.push(working_dir.join(path));
+    }
+    // the task keeps its own user and group ids inside the sandbox
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+    Ok(SandboxSetup {
+        unshare_flags: unshare_flags,
+        uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+        gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+        read_only_root: sandbox.read_only_root,
+        // a private /tmp would hide writable paths under it
+        private_tmp: !writable_paths.iter().any(|p| p.starts_with("/tmp")),
+        writable_paths: try!(writable_paths.iter()
+            .map(|p| c_string(&p.to_string_lossy()))
+            .collect()),
+        filter: if sandbox.seccomp {
+            Some(try!(seccomp_filter()))
+        } else {
+            None
+        },
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn prepare(_: &Sandbox) -> Result<SandboxSetup, String> {
+    Err("sandboxing is only supported on Linux".to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn check(result: libc::c_long) -> io::Result<()> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_proc_file(path: &[u8], contents: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY);
+        try!(check(fd as libc::c_long));
+        let written = libc::write(fd, contents.as_ptr() as *const libc::c_void, contents.len());
+        libc::close(fd);
+        check(written as libc::c_long)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_mount_attr(path: &[u8], attr: consts::MountAttr) -> io::Result<()> {
+    unsafe {
+        check(libc::syscall(libc::SYS_mount_setattr,
+                            libc::AT_FDCWD,
+                            path.as_ptr() as *const libc::c_char,
+                            consts::AT_RECURSIVE,
+                            &attr as *const consts::MountAttr,
+                            ::std::mem::size_of::<consts::MountAttr>()))
+    }
+}
+
+// like writable paths, a working directory under /tmp would be hidden by a private one
+#[cfg(target_os = "linux")]
+fn is_under_tmp(path: &[libc::c_char]) -> bool {
+    let tmp = b"/tmp";
+    tmp.iter().zip(path.iter()).all(|(t, p)| *t as libc::c_char == *p) &&
+    (path[tmp.len()] == 0 || path[tmp.len()] == b'/' as libc::c_char)
+}
+
+#[cfg(target_os = "linux")]
+impl SandboxSetup {
+    // runs in the task's process after fork, so it only makes system calls
+    pub fn enter(&self) -> io::Result<()> {
+        use self::consts::*;
+        let null = ::std::ptr::null::<libc::c_char>();
+        let mut working_dir = [0 as libc::c_char; libc::PATH_MAX as usize];
+
+        unsafe {
+            try!(check(libc::unshare(self.unshare_flags) as libc::c_long));
+            // setgroups doesn't exist before linux 3.19, when it didn't need to be denied
+            match write_proc_file(b"/proc/self/setgroups\0", b"deny") {
+                Err(ref e) if e.raw_os_error() == Some(libc::ENOENT) => {}
+                other => try!(other),
+            }
+            try!(write_proc_file(b"/proc/self/uid_map\0", &self.uid_map));
+            try!(write_proc_file(b"/proc/self/gid_map\0", &self.gid_map));
+
+            if self.read_only_root {
+                if libc::getcwd(working_dir.as_mut_ptr(), working_dir.len()).is_null() {
+                    return Err(io::Error::last_os_error());
+                }
+                try!(check(libc::mount(null,
+                                       b"/\0".as_ptr() as *const libc::c_char,
+                                       null,
+                                       libc::MS_REC | libc::MS_PRIVATE,
+                                       ::std::ptr::null()) as libc::c_long));
+                try!(set_mount_attr(b"/\0",
+                                    MountAttr {
+                                        attr_set: MOUNT_ATTR_RDONLY,
+                                        attr_clr: 0,
+                                        propagation: 0,
+                                        userns_fd: 0,
+                                    }));
+                if self.private_tmp && !is_under_tmp(&working_dir) {
+                    try!(check(libc::mount(b"tmpfs\0".as_ptr() as *const libc::c_char,
+                                           b"/tmp\0".as_ptr() as *const libc::c_char,
+                                           b"tmpfs\0".as_ptr() as *const libc::c_char,
+                                           libc::MS_NOSUID | libc::MS_NODEV,
+                                           ::std::ptr::null()) as libc::c_long));
+                }
+                for path in self.writable_paths.iter() {
+                    try!(check(libc::mount(path.as_ptr(),
+                                           path.as_ptr(),
+                                           null,
+                                           libc::MS_BIND | libc::MS_REC,
+                                           ::std::ptr::null()) as libc::c_long));
+                    try!(set_mount_attr(path.as_bytes_with_nul(),
+                                        MountAttr {
+                                            attr_set: 0,
+                                            attr_clr: MOUNT_ATTR_RDONLY,
+                                            propagation: 0,
+                                            userns_fd: 0,
+                                        }));
+                }
+                // the working directory may have just been mounted over
+                try!(check(libc::chdir(working_dir.as_ptr()) as libc::c_long));
+            }
+
+            if let Some(ref filter) = self.filter {
+                let program = libc::sock_fprog {
+                    len: filter.len() as libc::c_ushort,
+                    filter: filter.as_ptr() as *mut libc::sock_filter,
+                };
+                try!(check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) as libc::c_long));
+                try!(check(libc::prctl(libc::PR_SET_SECCOMP,
+                                       libc::SECCOMP_MODE_FILTER,
+                                       &program as *const libc::sock_fprog) as
+                           libc::c_long));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl SandboxSetup {
+    pub fn enter(&self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::Sandbox;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+fn run_sandboxed(sandbox: &Sandbox, script: &str) -> (i32, String) {
+    let setup = prepare(sandbox).unwrap();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(script).current_dir("/");
+    unsafe {
+        command.pre_exec(move || setup.enter());
+    }
+    let output = command.output().unwrap();
+    (output.status.code().unwrap_or(-1),
+     String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[test]
+fn sandboxed_tasks_keep_their_ids() {
+    let (code, stdout) = run_sandboxed(&Sandbox::default(), "id -u");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, unsafe { libc::getuid() }.to_string());
+}
+
+#[test]
+fn sandboxed_tasks_have_no_network() {
+    let (_, stdout) = run_sandboxed(&Sandbox::default(), "cat /proc/net/dev");
+    assert!(stdout.contains("lo:"));
+    assert!(!stdout.contains("eth0:"));
+
+    let (_, with_network) = run_sandboxed(&Sandbox { network: true, ..Sandbox::default() },
+                                          "cat /proc/net/dev");
+    assert_eq!(with_network, fs::read_to_string("/proc/net/dev").unwrap().trim());
+}
+
+#[test]
+fn sandboxed_tasks_can_only_write_to_writable_paths() {
+    let writable = env::temp_dir().join("factotum-sandbox-test");
+    fs::create_dir_all(&writable).unwrap();
+    let sandbox = Sandbox {
+        writable_paths: vec![writable.to_string_lossy().into_owned()],
+        ..Sandbox::default()
+    };
+
+    let (code, _) = run_sandboxed(&sandbox,
+                                  &format!("echo hello > {}/out.txt", writable.display()));
+    assert_eq!(code, 0);
+    assert_eq!(fs::read_to_string(writable.join("out.txt")).unwrap(), "hello\n");
+
+    let (code, _) = run_sandboxed(&sandbox, "touch /factotum-sandbox-test.txt");
+    assert!(code != 0);
+    assert!(fs::remove_dir_all(&writable).is_ok());
+
+    // without writable paths under it, /tmp is the task's own
+    let (code, stdout) = run_sandboxed(&Sandbox::default(),
+                                       "echo hi > /tmp/scratch && cat /tmp/scratch");
+    assert_eq!((code, stdout), (0, "hi".to_string()));
+    assert!(!Path::new("/tmp/scratch").exists());
+}
+
+#[test]
+fn sandboxed_tasks_keep_a_working_directory_under_tmp() {
+    let working_dir = env::temp_dir().join("factotum-sandbox-cwd-test");
+    fs::create_dir_all(&working_dir).unwrap();
+    let setup = prepare(&Sandbox::default()).unwrap();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg("pwd").current_dir(&working_dir);
+    unsafe {
+        command.pre_exec(move || setup.enter());
+    }
+    let output = command.output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(),
+               working_dir.to_string_lossy());
+    assert!(fs::remove_dir_all(&working_dir).is_ok());
+}
+
+#[test]
+fn seccomp_denies_escaping_the_sandbox() {
+    let (code, stdout) = run_sandboxed(&Sandbox::default(),
+                                       "unshare -r true 2>/dev/null; echo $?");
+    assert_eq!(code, 0);
+    assert!(stdout != "0");
+
+    let unfiltered = Sandbox { seccomp: false, ..Sandbox::default() };
+    assert_eq!(run_sandboxed(&unfiltered, "echo hello"), (0, "hello".to_string()));
+}
+
+#[test]
+fn prepare_rejects_bad_paths() {
+    let sandbox = Sandbox { writable_paths: vec!["/tmp/a\0b".to_string()], ..Sandbox::default() };
+    assert_eq!(prepare(&sandbox).err(),
+               Some("'/tmp/a\\u{0}b' has a nul byte in it".to_string()));
+}
+
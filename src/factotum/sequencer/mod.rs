@@ -18,86 +18,72 @@ use daggy::*;
 #[cfg(test)]
 mod tests;
 
+// groups the tasks under start so each runs in the group after the last of its dependencies;
+// every node and edge is visited once, counting how many dependencies each task has left
 pub fn get_tasks_in_order<'a>(dag: &'a Dag<Task, ()>,
                               start: &Vec<NodeIndex>,
                               tree: &mut Vec<Vec<&'a Task>>) {
-    let mut row: Vec<&Task> = vec![];
+    let mut reachable = vec![false; dag.node_count()];
+    let mut in_degree = vec![0usize; dag.node_count()];
 
-    for idx in start {
-        for row in tree.iter_mut() {
-            let posn = row.iter().position(|s| s.name == dag[*idx].name);
-            if let Some(remove_idx) = posn {
-                row.remove(remove_idx);
-            }
-        }
-        let no_dups = !row.iter().any(|s| s.name == dag[*idx].name);
-        if no_dups {
-            row.push(&dag[*idx]);
+    let mut row: Vec<NodeIndex> = vec![];
+    for idx in start.iter() {
+        if !reachable[idx.index()] {
+            reachable[idx.index()] = true;
+            row.push(*idx);
         }
     }
-    tree.push(row);
 
-    let mut children: Vec<NodeIndex> = vec![];
-    for parent in start.iter() {
-        for (_, node_index) in dag.children(*parent).iter(&dag) {
-            children.push(node_index);
+    let mut to_visit = row.clone();
+    while let Some(idx) = to_visit.pop() {
+        for (_, child) in dag.children(idx).iter(dag) {
+            in_degree[child.index()] += 1;
+            if !reachable[child.index()] {
+                reachable[child.index()] = true;
+                to_visit.push(child);
+            }
         }
     }
 
-    if children.len() != 0 {
-        get_tasks_in_order(dag, &children, tree);
-    }
-}
+    // a start task that depends on another start task runs after it
+    row.retain(|idx| in_degree[idx.index()] == 0);
 
-pub fn find_task_recursive<'a>(dag: &'a Dag<Task, ()>,
-                               name: &str,
-                               start: NodeIndex)
-                               -> Option<(NodeIndex, &'a Task)> {
-    if dag.children(start).iter(&dag).count() != 0 {
-        if let Some((_, node)) = dag.children(start).find(&dag, |g, _, n| g[n].name == name) {
-            return Some((node, &dag[node]));
-        } else {
-            for (_, child_node) in dag.children(start).iter(&dag) {
-                if let Some(v) = find_task_recursive(dag, name, child_node) {
-                    return Some(v);
+    loop {
+        let mut next: Vec<NodeIndex> = vec![];
+        for idx in row.iter() {
+            for (_, child) in dag.children(*idx).iter(dag) {
+                in_degree[child.index()] -= 1;
+                if in_degree[child.index()] == 0 {
+                    next.push(child);
                 }
             }
-            None
         }
-    } else {
-        None
+        tree.push(row.iter().map(|idx| &dag[*idx]).collect());
+
+        if next.is_empty() {
+            break;
+        }
+        row = next;
     }
 }
 
+// starting from a task is only possible if nothing under it depends on a task outside it
 pub fn is_proper_sub_tree(dag: &Dag<Task, ()>, start: NodeIndex) -> bool {
+    let mut below = vec![false; dag.node_count()];
+    below[start.index()] = true;
 
-    let mut allowed_deps = vec![];
-    allowed_deps.push(&dag[start]);
-    let mut process_children = vec![start];
-
-    while process_children.len() != 0 {
-
-        let mut next_to_process = vec![];
-
-        for child_index in process_children.iter() {
-            for (_, child_node) in dag.children(*child_index).iter(&dag) {
-                allowed_deps.push(&dag[child_node]);
-                next_to_process.push(child_node);
+    let mut descendants = vec![];
+    let mut to_visit = vec![start];
+    while let Some(idx) = to_visit.pop() {
+        for (_, child) in dag.children(idx).iter(dag) {
+            if !below[child.index()] {
+                below[child.index()] = true;
+                descendants.push(child);
+                to_visit.push(child);
             }
         }
-
-        for child_index in process_children.iter() {
-            if *child_index != start {
-                for (_, parent_node) in dag.parents(*child_index).iter(&dag) {
-                    if !allowed_deps.iter().any(|node| node.name == dag[parent_node].name) {
-                        return false;
-                    }
-                }
-            }
-        }
-
-        process_children = next_to_process;
     }
 
-    true
+    descendants.iter()
+        .all(|idx| dag.parents(*idx).iter(dag).all(|(_, parent)| below[parent.index()]))
 }
@@ -16,30 +16,6 @@ use factotum::factfile::*;
 use daggy::*;
 use factotum::tests::*;
 
-#[test]
-fn recursive_find_ok() {
-    let mut dag = Dag::<Task, ()>::new();
-    let parent = make_task("root", &vec![]);
-
-    let idx = dag.add_node(parent);
-
-    let task_child1 = make_task("child1", &vec![]);
-    dag.add_child(idx, (), task_child1);
-
-    let task_child2 = make_task("child2", &vec![]);
-    let (_, child2) = dag.add_child(idx, (), task_child2);
-
-    let grandchild_node = make_task("grandchild", &vec![]);
-    let (_, grandchild_idx) = dag.add_child(child2, (), grandchild_node);
-
-    if let Some((found_idx, found_node)) = super::find_task_recursive(&dag, "grandchild", idx) {
-        assert_eq!(found_idx, grandchild_idx);
-        assert_eq!(found_node.name, "grandchild");
-    } else {
-        panic!("couldn't find value");
-    }
-}
-
 #[test]
 fn get_tasks_in_order_basic() {
     let mut dag = Dag::<Task, ()>::new();
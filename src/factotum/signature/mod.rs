@@ -0,0 +1,243 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// looked for next to the Factfile, in this order, when no --signature is given
+pub const SIGNATURE_EXTENSIONS: [&'static str; 4] = ["sigstore.json", "sigstore", "asc", "sig"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum SignatureFormat {
+    Gpg,
+    Sigstore,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Signature {
+    pub path: PathBuf,
+    pub format: SignatureFormat,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SignatureRequirement {
+    pub signature: Option<String>,
+    pub signer: Option<String>,
+}
+
+pub trait Verifier {
+    // the signer the signature was made by, if it's good
+    fn verify(&self,
+              factfile: &Path,
+              signature: &Signature,
+              signer: &Option<String>)
+              -> Result<String, String>;
+}
+
+pub fn format_of(path: &Path) -> SignatureFormat {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    if name.ends_with(".sigstore") || name.ends_with(".sigstore.json") {
+        SignatureFormat::Sigstore
+    } else {
+        SignatureFormat::Gpg
+    }
+}
+
+pub fn find_signature(factfile: &Path, explicit: &Option<String>) -> Result<Signature, String> {
+    let path = match *explicit {
+        Some(ref path) => {
+            let path = PathBuf::from(path);
+            if !path.is_file() {
+                return Err(format!("the signature '{}' doesn't exist", path.display()));
+            }
+            path
+        }
+        None => {
+            let candidates = SIGNATURE_EXTENSIONS.iter()
+                .map(|ext| PathBuf::from(format!("{}.{}", factfile.display(), ext)))
+                .collect::<Vec<PathBuf>>();
+            match candidates.iter().find(|p| p.is_file()) {
+                Some(path) => path.clone(),
+                None => {
+                    return Err(format!("'{}' has no signature (looked for {})",
+                                       factfile.display(),
+                                       candidates.iter()
+                                           .map(|p| format!("'{}'", p.display()))
+                                           .collect::<Vec<String>>()
+                                           .join(", ")))
+                }
+            }
+        }
+    };
+
+    Ok(Signature {
+        format: format_of(&path),
+        path: path,
+    })
+}
+
+pub fn verify_factfile(factfile: &Path,
+                       requirement: &SignatureRequirement,
+                       verifier: &dyn Verifier)
+                       -> Result<String, String> {
+    let signature = try!(find_signature(factfile, &requirement.signature));
+    if signature.format == SignatureFormat::Sigstore && requirement.signer.is_none() {
+        return Err(format!("the sigstore bundle '{}' can only be verified against a signer \
+                            identity, set with --signer",
+                           signature.path.display()));
+    }
+    verifier.verify(factfile, &signature, &requirement.signer)
+}
+
+// what gpg's machine readable status output says about a good signature
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GpgSignature {
+    // e.g. "Jane Doe <jane@acme.com>"
+    pub user: String,
+    // the signing key's fingerprint, then its primary key's
+    pub fingerprints: Vec<String>,
+    // whether the key is trusted fully or ultimately, rather than just being in the keyring
+    pub trusted: bool,
+}
+
+pub fn parse_gpg_status(status: &str) -> Option<GpgSignature> {
+    let mut signature = GpgSignature::default();
+    let mut good = false;
+    for line in status.lines() {
+        let mut parts = line.splitn(4, ' ');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("[GNUPG:]"), Some("GOODSIG"), Some(_), Some(user)) => {
+                good = true;
+                signature.user = user.trim().to_string();
+            }
+            (Some("[GNUPG:]"), Some("VALIDSIG"), Some(fingerprint), rest) => {
+                signature.fingerprints.push(fingerprint.to_string());
+                // the primary key's fingerprint is the tenth field, after the signature's class
+                if let Some(primary) = rest.and_then(|r| r.split(' ').nth(8)) {
+                    signature.fingerprints.push(primary.to_string());
+                }
+            }
+            (Some("[GNUPG:]"), Some("TRUST_FULLY"), _, _) |
+            (Some("[GNUPG:]"), Some("TRUST_ULTIMATE"), _, _) => signature.trusted = true,
+            _ => {}
+        }
+    }
+    if good { Some(signature) } else { None }
+}
+
+// the signer is a key's fingerprint, or the email address in its user id; part of a user id
+// isn't enough, as anyone can put a name in the keys they make
+pub fn signed_by(signature: &GpgSignature, signer: &str) -> bool {
+    let fingerprint = signer.replace(" ", "").to_uppercase();
+    if signature.fingerprints.iter().any(|f| f.to_uppercase() == fingerprint) {
+        return true;
+    }
+    let email = signer.trim_matches(|c| c == '<' || c == '>');
+    let user_email = signature.user
+        .rfind('<')
+        .map(|start| signature.user[start + 1..].trim_end_matches('>'))
+        .unwrap_or(&signature.user);
+    email.contains('@') && email.eq_ignore_ascii_case(user_email)
+}
+
+// runs gpg (with the keys in its keyring) or cosign
+pub struct CliVerifier;
+
+impl CliVerifier {
+    fn verify_gpg(factfile: &Path,
+                  signature: &Path,
+                  signer: &Option<String>)
+                  -> Result<String, String> {
+        let output = try!(Command::new("gpg")
+            .arg("--batch")
+            .arg("--status-fd")
+            .arg("1")
+            .arg("--verify")
+            .arg(signature)
+            .arg(factfile)
+            .output()
+            .map_err(|e| format!("couldn't run gpg ({})", e)));
+
+        let status = String::from_utf8_lossy(&output.stdout);
+        let good = match parse_gpg_status(&status) {
+            Some(good) if output.status.success() => good,
+            _ => {
+                return Err(format!("gpg couldn't verify '{}': {}",
+                                   signature.display(),
+                                   String::from_utf8_lossy(&output.stderr).trim()))
+            }
+        };
+
+        if !good.trusted {
+            return Err(format!("'{}' was signed by '{}', whose key isn't trusted (gpg needs \
+                                to trust it fully or ultimately)",
+                               factfile.display(),
+                               good.user));
+        }
+        match *signer {
+            Some(ref signer) if !signed_by(&good, signer) => {
+                Err(format!("'{}' was signed by '{}', not '{}'",
+                            factfile.display(),
+                            good.user,
+                            signer))
+            }
+            _ => Ok(good.user),
+        }
+    }
+
+    fn verify_sigstore(factfile: &Path, bundle: &Path, signer: &str) -> Result<String, String> {
+        // the identity is what's trusted, whichever OIDC provider vouched for it
+        let output = try!(Command::new("cosign")
+            .arg("verify-blob")
+            .arg("--bundle")
+            .arg(bundle)
+            .arg("--certificate-identity")
+            .arg(signer)
+            .arg("--certificate-oidc-issuer-regexp")
+            .arg(".*")
+            .arg(factfile)
+            .output()
+            .map_err(|e| format!("couldn't run cosign ({})", e)));
+
+        if output.status.success() {
+            Ok(signer.to_string())
+        } else {
+            Err(format!("cosign couldn't verify '{}': {}",
+                        bundle.display(),
+                        String::from_utf8_lossy(&output.stderr).trim()))
+        }
+    }
+}
+
+impl Verifier for CliVerifier {
+    fn verify(&self,
+              factfile: &Path,
+              signature: &Signature,
+              signer: &Option<String>)
+              -> Result<String, String> {
+        match (&signature.format, signer) {
+            (&SignatureFormat::Sigstore, &Some(ref signer)) => {
+                CliVerifier::verify_sigstore(factfile, &signature.path, signer)
+            }
+            (&SignatureFormat::Sigstore, &None) => {
+                Err("a sigstore bundle needs a signer identity".to_string())
+            }
+            (&SignatureFormat::Gpg, _) => CliVerifier::verify_gpg(factfile, &signature.path, signer),
+        }
+    }
+}
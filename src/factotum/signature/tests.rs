@@ -0,0 +1,170 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+use super::*;
+use std::env;
+use std::fs::{self, File};
+
+struct MockVerifier {
+    result: Result<String, String>,
+    expected: Signature,
+}
+
+impl Verifier for MockVerifier {
+    fn verify(&self,
+              _: &Path,
+              signature: &Signature,
+              _: &Option<String>)
+              -> Result<String, String> {
+        assert_eq!(signature, &self.expected);
+        self.result.clone()
+    }
+}
+
+fn temp_factfile(name: &str, signatures: &Vec<&str>) -> PathBuf {
+    let dir = env::temp_dir().join(format!("factotum-signature-test-{}", name));
+    fs::create_dir_all(&dir).unwrap();
+    let factfile = dir.join("job.factfile");
+    File::create(&factfile).unwrap();
+    for ext in signatures.iter() {
+        File::create(dir.join(format!("job.factfile.{}", ext))).unwrap();
+    }
+    factfile
+}
+
+#[test]
+fn format_of_good() {
+    assert_eq!(format_of(Path::new("job.factfile.sigstore.json")),
+               SignatureFormat::Sigstore);
+    assert_eq!(format_of(Path::new("job.factfile.sigstore")),
+               SignatureFormat::Sigstore);
+    assert_eq!(format_of(Path::new("job.factfile.asc")), SignatureFormat::Gpg);
+    assert_eq!(format_of(Path::new("job.factfile.sig")), SignatureFormat::Gpg);
+}
+
+#[test]
+fn find_signature_looks_next_to_the_factfile() {
+    let factfile = temp_factfile("find", &vec!["asc", "sigstore.json"]);
+    let dir = factfile.parent().unwrap().to_path_buf();
+
+    assert_eq!(find_signature(&factfile, &None),
+               Ok(Signature {
+                   path: dir.join("job.factfile.sigstore.json"),
+                   format: SignatureFormat::Sigstore,
+               }));
+
+    let explicit = Some(dir.join("job.factfile.asc").to_string_lossy().into_owned());
+    assert_eq!(find_signature(&factfile, &explicit),
+               Ok(Signature {
+                   path: dir.join("job.factfile.asc"),
+                   format: SignatureFormat::Gpg,
+               }));
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+#[test]
+fn find_signature_bad() {
+    let factfile = temp_factfile("missing", &vec![]);
+    let dir = factfile.parent().unwrap().to_path_buf();
+
+    let msg = find_signature(&factfile, &None).unwrap_err();
+    assert!(msg.starts_with(&format!("'{}' has no signature (looked for '{}.sigstore.json'",
+                                     factfile.display(),
+                                     factfile.display())));
+
+    assert_eq!(find_signature(&factfile, &Some("/no/such.asc".to_string())),
+               Err("the signature '/no/such.asc' doesn't exist".to_string()));
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+#[test]
+fn verify_factfile_uses_the_verifier() {
+    let factfile = temp_factfile("verify", &vec!["sig"]);
+    let dir = factfile.parent().unwrap().to_path_buf();
+    let verifier = MockVerifier {
+        result: Ok("Jane Doe <jane@acme.com>".to_string()),
+        expected: Signature {
+            path: dir.join("job.factfile.sig"),
+            format: SignatureFormat::Gpg,
+        },
+    };
+
+    assert_eq!(verify_factfile(&factfile, &SignatureRequirement::default(), &verifier),
+               Ok("Jane Doe <jane@acme.com>".to_string()));
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+#[test]
+fn verify_factfile_needs_a_signer_for_sigstore() {
+    let factfile = temp_factfile("sigstore", &vec!["sigstore"]);
+    let dir = factfile.parent().unwrap().to_path_buf();
+    let bundle = Signature {
+        path: dir.join("job.factfile.sigstore"),
+        format: SignatureFormat::Sigstore,
+    };
+    let verifier = MockVerifier {
+        result: Err("bad signature".to_string()),
+        expected: bundle.clone(),
+    };
+
+    assert_eq!(verify_factfile(&factfile, &SignatureRequirement::default(), &verifier),
+               Err(format!("the sigstore bundle '{}' can only be verified against a signer \
+                            identity, set with --signer",
+                           bundle.path.display())));
+
+    let requirement = SignatureRequirement {
+        signature: None,
+        signer: Some("jane@acme.com".to_string()),
+    };
+    assert_eq!(verify_factfile(&factfile, &requirement, &verifier),
+               Err("bad signature".to_string()));
+    assert!(fs::remove_dir_all(&dir).is_ok());
+}
+
+const GOOD_STATUS: &'static str = "[GNUPG:] NEWSIG\n[GNUPG:] KEY_CONSIDERED ABCDEF 0\n\
+                                   [GNUPG:] GOODSIG 0123456789ABCDEF Jane Doe <jane@acme.com>\n\
+                                   [GNUPG:] VALIDSIG 5D3E0123456789ABCDEF 2024-03-09 \
+                                   1709942400 0 4 0 1 10 00 9A8B0123456789ABCDEF\n\
+                                   [GNUPG:] TRUST_ULTIMATE 0 pgp";
+
+#[test]
+fn parse_gpg_status_good() {
+    assert_eq!(parse_gpg_status(GOOD_STATUS),
+               Some(GpgSignature {
+                   user: "Jane Doe <jane@acme.com>".to_string(),
+                   fingerprints: vec!["5D3E0123456789ABCDEF".to_string(),
+                                      "9A8B0123456789ABCDEF".to_string()],
+                   trusted: true,
+               }));
+
+    let untrusted = GOOD_STATUS.replace("TRUST_ULTIMATE", "TRUST_UNDEFINED");
+    assert_eq!(parse_gpg_status(&untrusted).map(|s| s.trusted), Some(false));
+    assert_eq!(parse_gpg_status("[GNUPG:] BADSIG 0123456789ABCDEF Jane Doe"), None);
+    assert_eq!(parse_gpg_status(""), None);
+}
+
+#[test]
+fn signed_by_matches_fingerprints_and_emails_exactly() {
+    let signature = parse_gpg_status(GOOD_STATUS).unwrap();
+    assert!(signed_by(&signature, "5D3E0123456789ABCDEF"));
+    assert!(signed_by(&signature, "9a8b 0123 4567 89ab cdef"));
+    assert!(signed_by(&signature, "jane@acme.com"));
+    assert!(signed_by(&signature, "<Jane@Acme.com>"));
+
+    assert!(!signed_by(&signature, "Jane"));
+    assert!(!signed_by(&signature, "acme.com"));
+    assert!(!signed_by(&signature, "e@acme.com"));
+    assert!(!signed_by(&signature, "0123456789ABCDEF"));
+}
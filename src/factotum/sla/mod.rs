@@ -0,0 +1,122 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::{ExecutionState, ExecutionUpdate};
+use factotum::factfile::Sla;
+use factotum::retry;
+use factotum::webhook::jobcontext::JobContext;
+use factotum::webhook::jobupdate::to_string_datetime;
+use chrono::{DateTime, NaiveTime, UTC};
+use chrono::duration::Duration as ChronoDuration;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::BTreeMap;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub const BREACH_EVENT: &'static str = "JOB_SLA_BREACHED";
+
+// an sla is a duration from the start of the run ("2h"), a UTC time of day
+// ("06:30") or an RFC 3339 timestamp ("2017-01-01T06:30:00Z")
+pub fn parse_sla(sla: &str) -> Result<Sla, String> {
+    let sla = sla.trim();
+    if let Ok(time) = NaiveTime::parse_from_str(sla, "%H:%M") {
+        return Ok(Sla::TimeOfDay(time));
+    }
+    if let Ok(at) = DateTime::parse_from_rfc3339(sla) {
+        return Ok(Sla::At(at.with_timezone(&UTC)));
+    }
+    match retry::parse_duration(sla) {
+        Ok(duration) => Ok(Sla::Within(duration)),
+        Err(_) => {
+            Err(format!("'{}' is not a valid sla (expected a duration such as '2h', a UTC \
+                         time such as '06:30' or an RFC 3339 timestamp)",
+                        sla))
+        }
+    }
+}
+
+// a time of day is taken to be the first one after the run started
+pub fn deadline(sla: &Sla, start_time: &DateTime<UTC>) -> DateTime<UTC> {
+    match *sla {
+        Sla::Within(ref duration) => *start_time + ChronoDuration::from_std(*duration).unwrap(),
+        Sla::TimeOfDay(ref time) => {
+            let same_day = start_time.date().and_time(*time).unwrap();
+            if same_day > *start_time {
+                same_day
+            } else {
+                same_day + ChronoDuration::days(1)
+            }
+        }
+        Sla::At(ref at) => *at,
+    }
+}
+
+pub fn breach_json(context: &JobContext, deadline: &DateTime<UTC>) -> Json {
+    let mut d = BTreeMap::new();
+    d.insert("event".to_string(), BREACH_EVENT.to_json());
+    d.insert("jobName".to_string(), context.job_name.to_json());
+    d.insert("jobReference".to_string(), context.job_reference.to_json());
+    d.insert("runReference".to_string(), context.run_reference.to_json());
+    d.insert("startTime".to_string(),
+             to_string_datetime(&context.start_time).to_json());
+    d.insert("deadline".to_string(), to_string_datetime(deadline).to_json());
+    d.insert("context".to_string(), context.context_json());
+    Json::Object(d)
+}
+
+fn time_until(deadline: &DateTime<UTC>) -> Option<Duration> {
+    (*deadline - UTC::now()).to_std().ok()
+}
+
+// returns whether the deadline passed before the job finished; the job is
+// left to carry on either way
+pub fn connect_sla_watch<F>(deadline: DateTime<UTC>,
+                            updates_channel: Receiver<ExecutionUpdate>,
+                            on_breach: F)
+                            -> JoinHandle<bool>
+    where F: Fn(&DateTime<UTC>) + Send + 'static
+{
+    thread::spawn(move || {
+        let mut breached = false;
+
+        loop {
+            let remaining = time_until(&deadline);
+            if !breached && remaining.is_none() {
+                breached = true;
+                on_breach(&deadline);
+            }
+
+            let next = match remaining {
+                Some(wait) => updates_channel.recv_timeout(wait),
+                None => updates_channel.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+
+            match next {
+                Ok(update) => {
+                    if update.execution_state == ExecutionState::Finished {
+                        break;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        breached
+    })
+}
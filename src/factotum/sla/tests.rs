@@ -0,0 +1,101 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::{ExecutionState, ExecutionUpdate, Transition, JobTransition};
+use factotum::factfile::Sla;
+use factotum::webhook::jobcontext::JobContext;
+use chrono::{NaiveTime, TimeZone, UTC};
+use chrono::duration::Duration as ChronoDuration;
+use std::sync::mpsc;
+use std::time::Duration;
+
+fn update(state: ExecutionState) -> ExecutionUpdate {
+    ExecutionUpdate::new(state,
+                         vec![],
+                         Transition::Job(JobTransition::new(None, ExecutionState::Running)))
+}
+
+#[test]
+fn parse_sla_good() {
+    assert_eq!(parse_sla("2h"), Ok(Sla::Within(Duration::from_secs(7200))));
+    assert_eq!(parse_sla("06:30"),
+               Ok(Sla::TimeOfDay(NaiveTime::from_hms(6, 30, 0))));
+    assert_eq!(parse_sla("2017-01-01T06:30:00+01:00"),
+               Ok(Sla::At(UTC.ymd(2017, 1, 1).and_hms(5, 30, 0))));
+}
+
+#[test]
+fn parse_sla_bad() {
+    assert_eq!(parse_sla("soon"),
+               Err("'soon' is not a valid sla (expected a duration such as '2h', a UTC time \
+                    such as '06:30' or an RFC 3339 timestamp)"
+                   .to_string()));
+    assert!(parse_sla("25:00").is_err());
+}
+
+#[test]
+fn deadline_good() {
+    let start = UTC.ymd(2017, 1, 1).and_hms(22, 0, 0);
+
+    assert_eq!(deadline(&Sla::Within(Duration::from_secs(3600)), &start),
+               UTC.ymd(2017, 1, 1).and_hms(23, 0, 0));
+    assert_eq!(deadline(&Sla::TimeOfDay(NaiveTime::from_hms(23, 30, 0)), &start),
+               UTC.ymd(2017, 1, 1).and_hms(23, 30, 0));
+    assert_eq!(deadline(&Sla::TimeOfDay(NaiveTime::from_hms(6, 0, 0)), &start),
+               UTC.ymd(2017, 1, 2).and_hms(6, 0, 0));
+    assert_eq!(deadline(&Sla::At(UTC.ymd(2017, 1, 1).and_hms(1, 0, 0)), &start),
+               UTC.ymd(2017, 1, 1).and_hms(1, 0, 0));
+}
+
+#[test]
+fn breach_json_good() {
+    let context = JobContext::new("job", "{}", None);
+    let at = UTC.ymd(2017, 1, 1).and_hms(6, 30, 0);
+
+    let json = breach_json(&context, &at);
+    assert_eq!(json.find("event").unwrap().as_string(), Some("JOB_SLA_BREACHED"));
+    assert_eq!(json.find("jobName").unwrap().as_string(), Some("job"));
+    assert_eq!(json.find("deadline").unwrap().as_string(),
+               Some("2017-01-01T06:30:00.000Z"));
+}
+
+#[test]
+fn sla_watch_reports_breach_while_running() {
+    let (tx, rx) = mpsc::channel();
+    let (breach_tx, breach_rx) = mpsc::channel();
+    let handle = connect_sla_watch(UTC::now() + ChronoDuration::milliseconds(20),
+                                   rx,
+                                   move |_| breach_tx.send(()).unwrap());
+
+    tx.send(update(ExecutionState::Running)).unwrap();
+    breach_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    tx.send(update(ExecutionState::Finished)).unwrap();
+
+    assert_eq!(handle.join().unwrap(), true);
+    assert_eq!(breach_rx.iter().count(), 0);
+}
+
+#[test]
+fn sla_watch_met() {
+    let (tx, rx) = mpsc::channel();
+    let handle = connect_sla_watch(UTC::now() + ChronoDuration::hours(1),
+                                   rx,
+                                   |_| panic!("the sla shouldn't be breached"));
+
+    tx.send(update(ExecutionState::Running)).unwrap();
+    tx.send(update(ExecutionState::Finished)).unwrap();
+
+    assert_eq!(handle.join().unwrap(), false);
+}
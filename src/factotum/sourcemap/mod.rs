@@ -0,0 +1,199 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+// 1-based, as editors show them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn start() -> Position {
+        Position {
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+// where each value in a JSON document starts, keyed by its JSON pointer (e.g.
+// "/data/tasks/0/onResult"); an object's fields are placed at their keys
+pub type SourceMap = BTreeMap<String, Position>;
+
+// the scan just stops at the end of the document, or anything that isn't JSON
+macro_rules! opt {
+    ($e:expr) => (match $e {
+        Some(value) => value,
+        None => return None,
+    })
+}
+
+struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+    map: SourceMap,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().cloned()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        match c {
+            Some('\n') => {
+                self.position.line += 1;
+                self.position.column = 1;
+            }
+            Some(_) => self.position.column += 1,
+            None => {}
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.bump();
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.bump() != Some('"') {
+            return None;
+        }
+        let mut s = String::new();
+        loop {
+            match opt!(self.bump()) {
+                '"' => return Some(s),
+                '\\' => {
+                    match opt!(self.bump()) {
+                        'n' => s.push('\n'),
+                        't' => s.push('\t'),
+                        'r' => s.push('\r'),
+                        'b' => s.push('\u{8}'),
+                        'f' => s.push('\u{c}'),
+                        'u' => {
+                            let mut hex = String::new();
+                            for _ in 0..4 {
+                                hex.push(opt!(self.bump()));
+                            }
+                            let code = u32::from_str_radix(&hex, 16).unwrap_or(0xfffd);
+                            s.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                        }
+                        other => s.push(other),
+                    }
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self, pointer: String, at: Option<Position>) -> Option<()> {
+        self.skip_whitespace();
+        let start = self.position;
+        self.map.insert(pointer.clone(), at.unwrap_or(start));
+
+        match opt!(self.peek()) {
+            '{' => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match opt!(self.peek()) {
+                        '}' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        ',' => {
+                            self.bump();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    let key_at = self.position;
+                    let key = opt!(self.string());
+                    self.skip_whitespace();
+                    if self.bump() != Some(':') {
+                        return None;
+                    }
+                    let escaped = key.replace("~", "~0").replace("/", "~1");
+                    opt!(self.value(format!("{}/{}", pointer, escaped), Some(key_at)));
+                }
+            }
+            '[' => {
+                self.bump();
+                let mut idx = 0;
+                loop {
+                    self.skip_whitespace();
+                    match opt!(self.peek()) {
+                        ']' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        ',' => {
+                            self.bump();
+                            continue;
+                        }
+                        _ => {}
+                    }
+                    opt!(self.value(format!("{}/{}", pointer, idx), None));
+                    idx += 1;
+                }
+            }
+            '"' => self.string().map(|_| ()),
+            _ => {
+                // numbers, true, false and null
+                while self.peek().map(|c| !",]}".contains(c) && !c.is_whitespace()) == Some(true) {
+                    self.bump();
+                }
+                Some(())
+            }
+        }
+    }
+}
+
+// as much of the document as can be scanned - a syntax error just ends the map early
+pub fn source_map(source: &str) -> SourceMap {
+    let mut scanner = Scanner {
+        chars: source.chars().peekable(),
+        position: Position::start(),
+        map: BTreeMap::new(),
+    };
+    scanner.value(String::new(), None);
+    scanner.map
+}
+
+// the position of the value at the pointer, or failing that of the nearest value
+// containing it (e.g. the task a missing field belongs in)
+pub fn locate(map: &SourceMap, pointer: &str) -> Option<Position> {
+    let mut pointer = pointer;
+    loop {
+        if let Some(position) = map.get(pointer) {
+            return Some(*position);
+        }
+        match pointer.rfind('/') {
+            Some(idx) => pointer = &pointer[..idx],
+            None => return None,
+        }
+    }
+}
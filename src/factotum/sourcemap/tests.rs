@@ -0,0 +1,56 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+
+const SOURCE: &'static str = "{\n  \"data\": {\n    \"tasks\": [\n      { \"name\": \"a\" },\n      {\n        \"name\": \"b\"\n      }\n    ]\n  }\n}";
+
+fn at(line: usize, column: usize) -> Position {
+    Position {
+        line: line,
+        column: column,
+    }
+}
+
+#[test]
+fn values_are_mapped_by_pointer() {
+    let map = source_map(SOURCE);
+    assert_eq!(map.get(""), Some(&at(1, 1)));
+    assert_eq!(map.get("/data"), Some(&at(2, 3)));
+    assert_eq!(map.get("/data/tasks/0"), Some(&at(4, 7)));
+    assert_eq!(map.get("/data/tasks/0/name"), Some(&at(4, 9)));
+    assert_eq!(map.get("/data/tasks/1/name"), Some(&at(6, 9)));
+}
+
+#[test]
+fn keys_are_escaped_in_pointers() {
+    let map = source_map("{\"a/b\": {\"c~d\": 1}}");
+    assert_eq!(map.get("/a~1b/c~0d"), Some(&at(1, 10)));
+}
+
+#[test]
+fn syntax_errors_end_the_map() {
+    let map = source_map("{\"a\": 1, \"b\": [1, ");
+    assert_eq!(map.get("/a"), Some(&at(1, 2)));
+    assert_eq!(map.get("/b/0"), Some(&at(1, 16)));
+    assert_eq!(map.get("/b/1"), None);
+}
+
+#[test]
+fn locate_falls_back_to_the_parent() {
+    let map = source_map(SOURCE);
+    assert_eq!(locate(&map, "/data/tasks/1/command"), Some(at(5, 7)));
+    assert_eq!(locate(&map, "/data/tasks/1"), Some(at(5, 7)));
+    assert_eq!(locate(&source_map(""), "/data"), Some(Position::start()));
+}
@@ -0,0 +1,339 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::retry;
+use factotum::waitfor::WaitOptions;
+use regex::Regex;
+use std::process::Command;
+use std::time::Duration;
+
+pub const EXECUTOR: &'static str = "spark";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ClusterManager {
+    Yarn,
+    Standalone,
+    Kubernetes,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SparkApp {
+    pub application: String,
+    pub master: Option<String>,
+    pub cluster_mode: bool,
+    pub class: Option<String>,
+    pub name: Option<String>,
+    pub jars: Vec<String>,
+    pub py_files: Vec<String>,
+    pub conf: Vec<(String, String)>,
+    pub args: Vec<String>,
+    pub polling: WaitOptions,
+}
+
+pub fn cluster_manager(master: &str) -> ClusterManager {
+    if master == "yarn" {
+        ClusterManager::Yarn
+    } else if master.starts_with("spark://") {
+        ClusterManager::Standalone
+    } else if master.starts_with("k8s://") {
+        ClusterManager::Kubernetes
+    } else {
+        ClusterManager::Other
+    }
+}
+
+// a spark task's command is the application (a jar or python file) and its arguments
+// spark-submit's options, e.g. [ "master=yarn", "deploy-mode=cluster", "class=com.acme.Enrich",
+// "conf=spark.executor.memory=4g", "arg=--date=2021-01-01" ]
+pub fn parse_spark_app(application: &str, params: &Vec<String>) -> Result<SparkApp, String> {
+    let application = application.trim();
+    if application.is_empty() {
+        return Err("there's no application to submit".to_string());
+    }
+
+    let mut app = SparkApp {
+        application: application.to_string(),
+        master: None,
+        cluster_mode: false,
+        class: None,
+        name: None,
+        jars: vec![],
+        py_files: vec![],
+        conf: vec![],
+        args: vec![],
+        polling: WaitOptions {
+            poll_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(24 * 60 * 60),
+        },
+    };
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value.to_string(),
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "master" => app.master = Some(value),
+            "deploy-mode" => {
+                app.cluster_mode = match value.as_ref() {
+                    "cluster" => true,
+                    "client" => false,
+                    _ => {
+                        return Err(format!("'{}' isn't a deploy mode, it must be client or \
+                                            cluster",
+                                           value))
+                    }
+                }
+            }
+            "class" => app.class = Some(value),
+            "name" => app.name = Some(value),
+            "jar" => app.jars.push(value),
+            "py-file" => app.py_files.push(value),
+            "conf" => {
+                match value.find('=') {
+                    Some(i) if i > 0 => {
+                        app.conf.push((value[..i].to_string(), value[i + 1..].to_string()))
+                    }
+                    _ => return Err(format!("'{}' must be given as conf=<key>=<value>", param)),
+                }
+            }
+            "arg" => app.args.push(value),
+            "poll-interval" => app.polling.poll_interval = try!(retry::parse_duration(&value)),
+            "timeout" => app.polling.timeout = try!(retry::parse_duration(&value)),
+            _ => {
+                return Err(format!("'{}' isn't a spark parameter (expected master, deploy-mode, \
+                                    class, name, jar, py-file, conf, arg, poll-interval or \
+                                    timeout)",
+                                   name))
+            }
+        }
+    }
+
+    if app.cluster_mode {
+        match app.master.as_ref().map(|m| cluster_manager(m)) {
+            Some(ClusterManager::Other) | None => {
+                return Err("an application can only be tracked in cluster deploy mode on yarn, \
+                            spark:// or k8s:// masters"
+                    .to_string())
+            }
+            _ => {}
+        }
+    }
+    if app.polling.poll_interval.as_secs() == 0 {
+        return Err("the poll interval must be at least 1s".to_string());
+    }
+
+    Ok(app)
+}
+
+pub fn submit_args(app: &SparkApp) -> Vec<String> {
+    let mut args = vec![];
+    if let Some(ref master) = app.master {
+        args.push(format!("--master={}", master));
+    }
+    if app.cluster_mode {
+        args.push("--deploy-mode=cluster".to_string());
+    }
+    if let Some(ref class) = app.class {
+        args.push(format!("--class={}", class));
+    }
+    if let Some(ref name) = app.name {
+        args.push(format!("--name={}", name));
+    }
+    if !app.jars.is_empty() {
+        args.push(format!("--jars={}", app.jars.join(",")));
+    }
+    if !app.py_files.is_empty() {
+        args.push(format!("--py-files={}", app.py_files.join(",")));
+    }
+    for &(ref key, ref value) in app.conf.iter() {
+        args.push(format!("--conf={}={}", key, value));
+    }
+    if app.cluster_mode {
+        // factotum follows the application itself, rather than leaving spark-submit waiting
+        let wait_conf = match app.master.as_ref().map(|m| cluster_manager(m)) {
+            Some(ClusterManager::Yarn) => Some("spark.yarn.submit.waitAppCompletion"),
+            Some(ClusterManager::Kubernetes) => {
+                Some("spark.kubernetes.submission.waitAppCompletion")
+            }
+            _ => None,
+        };
+        if let Some(wait_conf) = wait_conf {
+            args.push(format!("--conf={}=false", wait_conf));
+        }
+    }
+    args.push(app.application.clone());
+    args.extend(app.args.iter().cloned());
+    args
+}
+
+// the id the cluster manager gave the application, from what spark-submit printed
+pub fn submission_id(manager: &ClusterManager, output: &str) -> Option<String> {
+    let pattern = match *manager {
+        ClusterManager::Yarn => r"(application_\d+_\d+)",
+        ClusterManager::Standalone => r"(driver-\d+-\d+)",
+        ClusterManager::Kubernetes => r"submission ID (\S+)",
+        ClusterManager::Other => return None,
+    };
+    field(output, pattern)
+}
+
+fn field(output: &str, pattern: &str) -> Option<String> {
+    Regex::new(pattern)
+        .ok()
+        .and_then(|r| r.captures(output).and_then(|c| c.at(1)).map(|v| v.to_string()))
+}
+
+// None while the application's still to finish
+pub fn app_outcome(manager: &ClusterManager, status: &str) -> Option<Result<(), String>> {
+    match *manager {
+        ClusterManager::Yarn => {
+            let state = field(status, r"\bState\s*:\s*(\w+)");
+            let final_state = field(status, r"Final-State\s*:\s*(\w+)");
+            match state.as_ref().map(|s| &s[..]) {
+                Some("FINISHED") | Some("FAILED") | Some("KILLED") => {
+                    match final_state.as_ref().map(|s| &s[..]) {
+                        Some("SUCCEEDED") => Some(Ok(())),
+                        other => {
+                            Some(Err(format!("the application finished as {}",
+                                             other.unwrap_or("UNDEFINED"))))
+                        }
+                    }
+                }
+                _ => None,
+            }
+        }
+        ClusterManager::Standalone => {
+            match field(status, r"State of driver \S+ is (\w+)")
+                .or(field(status, r#""driverState"\s*:\s*"(\w+)""#)) {
+                Some(ref state) if state == "FINISHED" => Some(Ok(())),
+                Some(ref state) if ["FAILED", "ERROR", "KILLED"].contains(&&state[..]) => {
+                    Some(Err(format!("the driver finished as {}", state)))
+                }
+                _ => None,
+            }
+        }
+        ClusterManager::Kubernetes => {
+            match field(status, r"phase:\s*(\w+)") {
+                Some(ref phase) if phase == "Succeeded" => Some(Ok(())),
+                Some(ref phase) if phase == "Failed" => {
+                    Some(Err("the driver pod finished as Failed".to_string()))
+                }
+                _ => None,
+            }
+        }
+        ClusterManager::Other => Some(Ok(())),
+    }
+}
+
+pub trait SparkClient {
+    // runs the program, giving its exit code and everything it printed
+    fn run(&self, program: &str, args: &Vec<String>) -> Result<(i32, String), String>;
+}
+
+pub struct CliSparkClient;
+
+impl SparkClient for CliSparkClient {
+    fn run(&self, program: &str, args: &Vec<String>) -> Result<(i32, String), String> {
+        let output = try!(Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("couldn't run {} ({})", program, e)));
+        let printed = format!("{}{}",
+                              String::from_utf8_lossy(&output.stdout),
+                              String::from_utf8_lossy(&output.stderr));
+        Ok((output.status.code().unwrap_or(-1), printed))
+    }
+}
+
+fn status_command(manager: &ClusterManager,
+                  master: &str,
+                  id: &str,
+                  kill: bool)
+                  -> (&'static str, Vec<String>) {
+    match *manager {
+        ClusterManager::Yarn => {
+            ("yarn",
+             vec!["application".to_string(),
+                  if kill { "-kill" } else { "-status" }.to_string(),
+                  id.to_string()])
+        }
+        _ => {
+            ("spark-submit",
+             vec![format!("--master={}", master),
+                  if kill { "--kill" } else { "--status" }.to_string(),
+                  id.to_string()])
+        }
+    }
+}
+
+// submits the application in cluster deploy mode, then polls the cluster manager until it
+// finishes, returning how many checks it took
+pub fn run_cluster_app<C, S, R>(client: &C,
+                                app: &SparkApp,
+                                sleep: S,
+                                report: R)
+                                -> Result<u64, String>
+    where C: SparkClient,
+          S: Fn(Duration),
+          R: Fn(String)
+{
+    let master = app.master.clone().unwrap_or(String::new());
+    let manager = cluster_manager(&master);
+
+    let (code, output) = try!(client.run("spark-submit", &submit_args(app)));
+    report(output.trim_end().to_string());
+    if code != 0 {
+        return Err(format!("spark-submit exited with {}", code));
+    }
+    let id = try!(submission_id(&manager, &output)
+        .ok_or("spark-submit didn't say what the application was submitted as".to_string()));
+    report(format!("Submitted '{}' as {}", app.application, id));
+
+    let max_checks = app.polling.timeout.as_secs() / app.polling.poll_interval.as_secs() + 1;
+    for attempt in 1..max_checks + 1 {
+        let (program, args) = status_command(&manager, &master, &id, false);
+        let (_, status) = try!(client.run(program, &args));
+        if let Some(outcome) = app_outcome(&manager, &status) {
+            return outcome.map(|_| attempt);
+        }
+        if attempt < max_checks {
+            sleep(app.polling.poll_interval);
+        }
+    }
+
+    // don't leave the application running once nothing's watching it
+    let (program, args) = status_command(&manager, &master, &id, true);
+    if let Err(e) = client.run(program, &args) {
+        report(format!("Couldn't kill {}: {}", id, e));
+    }
+    Err(format!("timed out after {}s waiting for {} to finish",
+                app.polling.timeout.as_secs(),
+                id))
+}
+
+// in client deploy mode spark-submit runs the driver, so its exit code is the application's
+pub fn run_client_app(app: &SparkApp) -> Result<i32, String> {
+    let status = try!(Command::new("spark-submit")
+        .args(&submit_args(app))
+        .status()
+        .map_err(|e| format!("couldn't run spark-submit ({})", e)));
+    status.code().ok_or("spark-submit was killed by a signal".to_string())
+}
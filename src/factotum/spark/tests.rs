@@ -0,0 +1,183 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::cell::RefCell;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+fn cluster_app(master: &str) -> SparkApp {
+    parse_spark_app("enrich.jar",
+                    &params(vec![&format!("master={}", master),
+                                 "deploy-mode=cluster",
+                                 "poll-interval=1s",
+                                 "timeout=3s"]))
+        .unwrap()
+}
+
+// answers each run with the next canned output
+struct FakeSpark {
+    outputs: RefCell<Vec<(i32, String)>>,
+    runs: RefCell<Vec<String>>,
+}
+
+impl FakeSpark {
+    fn new(outputs: Vec<(i32, &str)>) -> FakeSpark {
+        FakeSpark {
+            outputs: RefCell::new(outputs.iter().rev().map(|&(c, o)| (c, o.to_string())).collect()),
+            runs: RefCell::new(vec![]),
+        }
+    }
+}
+
+impl SparkClient for FakeSpark {
+    fn run(&self, program: &str, args: &Vec<String>) -> Result<(i32, String), String> {
+        self.runs.borrow_mut().push(format!("{} {}", program, args.join(" ")));
+        self.outputs.borrow_mut().pop().ok_or("no more outputs".to_string())
+    }
+}
+
+#[test]
+fn parse_spark_app_good() {
+    let app = parse_spark_app("enrich.jar",
+                              &params(vec!["master=yarn",
+                                           "deploy-mode=cluster",
+                                           "class=com.acme.Enrich",
+                                           "jar=a.jar",
+                                           "jar=b.jar",
+                                           "conf=spark.executor.memory=4g",
+                                           "arg=--date=2021-01-01"]))
+        .unwrap();
+    assert!(app.cluster_mode);
+    assert_eq!(app.class, Some("com.acme.Enrich".to_string()));
+    assert_eq!(app.conf,
+               vec![("spark.executor.memory".to_string(), "4g".to_string())]);
+    assert_eq!(submit_args(&app),
+               vec!["--master=yarn",
+                    "--deploy-mode=cluster",
+                    "--class=com.acme.Enrich",
+                    "--jars=a.jar,b.jar",
+                    "--conf=spark.executor.memory=4g",
+                    "--conf=spark.yarn.submit.waitAppCompletion=false",
+                    "enrich.jar",
+                    "--date=2021-01-01"]);
+
+    let local = parse_spark_app("job.py", &params(vec!["py-file=deps.zip"])).unwrap();
+    assert!(!local.cluster_mode);
+    assert_eq!(submit_args(&local), vec!["--py-files=deps.zip", "job.py"]);
+}
+
+#[test]
+fn parse_spark_app_bad() {
+    assert_eq!(parse_spark_app(" ", &vec![]),
+               Err("there's no application to submit".to_string()));
+    assert_eq!(parse_spark_app("a.jar", &params(vec!["deploy-mode=remote"])),
+               Err("'remote' isn't a deploy mode, it must be client or cluster".to_string()));
+    assert_eq!(parse_spark_app("a.jar", &params(vec!["conf=spark.x"])),
+               Err("'conf=spark.x' must be given as conf=<key>=<value>".to_string()));
+    assert_eq!(parse_spark_app("a.jar", &params(vec!["master=local[4]", "deploy-mode=cluster"])),
+               Err("an application can only be tracked in cluster deploy mode on yarn, spark:// \
+                    or k8s:// masters"
+                   .to_string()));
+    assert!(parse_spark_app("a.jar", &params(vec!["executor-memory=4g"]))
+        .unwrap_err()
+        .starts_with("'executor-memory' isn't a spark parameter"));
+}
+
+#[test]
+fn submission_ids_are_found_in_the_output() {
+    assert_eq!(submission_id(&ClusterManager::Yarn,
+                             "INFO Client: Submitted application application_1610000000000_0042"),
+               Some("application_1610000000000_0042".to_string()));
+    assert_eq!(submission_id(&ClusterManager::Standalone,
+                             "Driver successfully submitted as driver-20210101120000-0003"),
+               Some("driver-20210101120000-0003".to_string()));
+    assert_eq!(submission_id(&ClusterManager::Kubernetes,
+                             "Deployed Spark application enrich with submission ID \
+                              spark:enrich-driver into Kubernetes"),
+               Some("spark:enrich-driver".to_string()));
+    assert_eq!(submission_id(&ClusterManager::Yarn, "nothing"), None);
+}
+
+#[test]
+fn app_states_map_to_outcomes() {
+    let yarn = |state: &str, final_state: &str| {
+        app_outcome(&ClusterManager::Yarn,
+                    &format!("Application Report :\n\tState : {}\n\tFinal-State : {}\n",
+                             state,
+                             final_state))
+    };
+    assert_eq!(yarn("RUNNING", "UNDEFINED"), None);
+    assert_eq!(yarn("FINISHED", "SUCCEEDED"), Some(Ok(())));
+    assert_eq!(yarn("FINISHED", "FAILED"),
+               Some(Err("the application finished as FAILED".to_string())));
+    assert_eq!(yarn("KILLED", "KILLED"),
+               Some(Err("the application finished as KILLED".to_string())));
+
+    let standalone = |state: &str| {
+        app_outcome(&ClusterManager::Standalone,
+                    &format!("State of driver driver-1-2 is {}", state))
+    };
+    assert_eq!(standalone("RUNNING"), None);
+    assert_eq!(standalone("FINISHED"), Some(Ok(())));
+    assert_eq!(standalone("ERROR"),
+               Some(Err("the driver finished as ERROR".to_string())));
+
+    assert_eq!(app_outcome(&ClusterManager::Kubernetes, "phase: Running"), None);
+    assert_eq!(app_outcome(&ClusterManager::Kubernetes, "phase: Succeeded"), Some(Ok(())));
+    assert!(app_outcome(&ClusterManager::Kubernetes, "phase: Failed").unwrap().is_err());
+}
+
+#[test]
+fn run_cluster_app_polls_yarn() {
+    let spark = FakeSpark::new(vec![(0, "Submitted application application_1_2"),
+                                    (0, "State : ACCEPTED\nFinal-State : UNDEFINED"),
+                                    (0, "State : FINISHED\nFinal-State : SUCCEEDED")]);
+    let reports = RefCell::new(vec![]);
+
+    assert_eq!(run_cluster_app(&spark,
+                               &cluster_app("yarn"),
+                               |_| {},
+                               |r| reports.borrow_mut().push(r)),
+               Ok(2));
+    assert_eq!(reports.borrow()[1],
+               "Submitted 'enrich.jar' as application_1_2");
+    assert_eq!(spark.runs.borrow()[1], "yarn application -status application_1_2");
+}
+
+#[test]
+fn run_cluster_app_fails_when_submission_fails() {
+    let spark = FakeSpark::new(vec![(1, "Exception in thread \"main\"")]);
+    assert_eq!(run_cluster_app(&spark, &cluster_app("yarn"), |_| {}, |_| {}),
+               Err("spark-submit exited with 1".to_string()));
+
+    let spark = FakeSpark::new(vec![(0, "no id here")]);
+    assert_eq!(run_cluster_app(&spark, &cluster_app("yarn"), |_| {}, |_| {}),
+               Err("spark-submit didn't say what the application was submitted as".to_string()));
+}
+
+#[test]
+fn run_cluster_app_kills_on_timeout() {
+    let mut outputs = vec![(0, "Driver successfully submitted as driver-1-2")];
+    outputs.extend(vec![(0, "State of driver driver-1-2 is RUNNING"); 4]);
+    outputs.push((0, ""));
+    let spark = FakeSpark::new(outputs);
+
+    assert_eq!(run_cluster_app(&spark, &cluster_app("spark://master:7077"), |_| {}, |_| {}),
+               Err("timed out after 3s waiting for driver-1-2 to finish".to_string()));
+    assert_eq!(spark.runs.borrow().last(),
+               Some(&"spark-submit --master=spark://master:7077 --kill driver-1-2".to_string()));
+}
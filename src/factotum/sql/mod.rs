@@ -0,0 +1,243 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::config::Connection;
+use factotum::envfile;
+use rustc_serialize::json::Json;
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+
+pub const EXECUTOR: &'static str = "sql";
+pub const DRIVERS: &'static [&'static str] = &["postgres", "redshift", "snowflake", "odbc"];
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Statement {
+    Query(String),
+    Script(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SqlTask {
+    pub statement: Statement,
+    // the exit code for a statement that returns no rows, so onResult can act on it
+    pub no_rows: Option<i32>,
+}
+
+// how the driver's command line client is run
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClientCommand {
+    pub program: String,
+    pub args: Vec<String>,
+    pub env: Vec<(String, String)>,
+    pub stdin: Option<String>,
+}
+
+// a sql task's command is the connection's name and its arguments its parameters, e.g.
+// [ "query=SELECT * FROM loads WHERE day = '{{ day }}'", "no-rows=3" ]
+pub fn parse_sql_task(params: &Vec<String>) -> Result<SqlTask, String> {
+    let mut statement = None;
+    let mut no_rows = None;
+
+    for param in params.iter() {
+        let mut split = param.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = match split.next() {
+            Some(value) => value,
+            None => return Err(format!("'{}' must be given as <parameter>=<value>", param)),
+        };
+        match name {
+            "query" | "script" if statement.is_some() => {
+                return Err("only one query or script can be run by a task".to_string())
+            }
+            "query" => statement = Some(Statement::Query(value.to_string())),
+            "script" => statement = Some(Statement::Script(value.to_string())),
+            "no-rows" => {
+                no_rows = Some(try!(value.parse()
+                    .map_err(|_| format!("'{}' isn't an exit code", value))))
+            }
+            _ => {
+                return Err(format!("'{}' isn't a sql parameter (expected query, script or \
+                                    no-rows)",
+                                   name))
+            }
+        }
+    }
+
+    match statement {
+        Some(statement) => {
+            Ok(SqlTask {
+                statement: statement,
+                no_rows: no_rows,
+            })
+        }
+        None => Err("there's no query or script to run".to_string()),
+    }
+}
+
+// credentials can be given as "env:<variable>", "file:<path>" or "sops:<path>#<key>" (with
+// the file decrypted by sops), rather than kept in the config file
+pub fn resolve_secret(value: &str) -> Result<String, String> {
+    if value.starts_with("env:") {
+        let name = &value["env:".len()..];
+        env::var(name).map_err(|_| format!("the environment variable '{}' isn't set", name))
+    } else if value.starts_with("file:") {
+        let path = &value["file:".len()..];
+        let mut contents = String::new();
+        try!(File::open(path)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read '{}' ({})", path, e)));
+        Ok(contents.trim().to_string())
+    } else if value.starts_with("sops:") {
+        let reference = &value["sops:".len()..];
+        let (path, key) = match reference.rfind('#') {
+            Some(i) if i > 0 && i < reference.len() - 1 => (&reference[..i], &reference[i + 1..]),
+            _ => return Err(format!("'{}' must be given as sops:<path>#<key>", value)),
+        };
+        let secrets = Json::Object(try!(envfile::load_env_file(path)));
+        secrets.find_path(&key.split('.').collect::<Vec<_>>())
+            .and_then(|v| v.as_string())
+            .map(|v| v.to_string())
+            .ok_or(format!("'{}' has no '{}'", path, key))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+pub fn client_command(connection: &Connection,
+                      task: &SqlTask,
+                      password: Option<String>)
+                      -> Result<ClientCommand, String> {
+    let mut command = ClientCommand {
+        program: String::new(),
+        args: vec![],
+        env: vec![],
+        stdin: None,
+    };
+    let mut option = |flag: &str, value: &Option<String>| {
+        if let Some(ref value) = *value {
+            command.args.push(format!("{}={}", flag, value));
+        }
+    };
+
+    match connection.driver.as_ref() {
+        "postgres" | "redshift" => {
+            option("--host", &connection.host);
+            option("--port", &connection.port.map(|p| p.to_string()));
+            option("--dbname", &connection.database);
+            option("--username", &connection.user);
+            command.program = "psql".to_string();
+            command.args.extend(vec!["--no-psqlrc".to_string(),
+                                     "--tuples-only".to_string(),
+                                     "--no-align".to_string(),
+                                     "--set=ON_ERROR_STOP=1".to_string()]);
+            if let Some(password) = password {
+                command.env.push(("PGPASSWORD".to_string(), password));
+            }
+            match task.statement {
+                Statement::Query(ref query) => command.args.push(format!("--command={}", query)),
+                Statement::Script(ref path) => command.args.push(format!("--file={}", path)),
+            }
+        }
+        "snowflake" => {
+            option("--accountname", &connection.account);
+            option("--username", &connection.user);
+            option("--dbname", &connection.database);
+            option("--schemaname", &connection.schema);
+            option("--warehouse", &connection.warehouse);
+            option("--rolename", &connection.role);
+            command.program = "snowsql".to_string();
+            for setting in &["exit_on_error=true",
+                             "friendly=false",
+                             "header=false",
+                             "timing=false",
+                             "output_format=tsv"] {
+                command.args.push("--option".to_string());
+                command.args.push(setting.to_string());
+            }
+            if let Some(password) = password {
+                command.env.push(("SNOWSQL_PWD".to_string(), password));
+            }
+            match task.statement {
+                Statement::Query(ref query) => command.args.push(format!("--query={}", query)),
+                Statement::Script(ref path) => command.args.push(format!("--filename={}", path)),
+            }
+        }
+        "odbc" => {
+            let dsn = try!(connection.dsn
+                .clone()
+                .ok_or("an odbc connection needs a dsn".to_string()));
+            // isql reads its statement from stdin, and takes the credentials as arguments
+            command.program = "isql".to_string();
+            command.args.push("-b".to_string());
+            command.args.push(dsn);
+            if let Some(ref user) = connection.user {
+                command.args.push(user.clone());
+                if let Some(password) = password {
+                    command.args.push(password);
+                }
+            }
+            command.stdin = Some(match task.statement {
+                Statement::Query(ref query) => query.clone(),
+                Statement::Script(ref path) => {
+                    let mut contents = String::new();
+                    try!(File::open(path)
+                        .and_then(|mut f| f.read_to_string(&mut contents))
+                        .map_err(|e| format!("couldn't read '{}' ({})", path, e)));
+                    contents
+                }
+            });
+        }
+        driver => {
+            return Err(format!("'{}' isn't a database driver, it must be one of {}",
+                               driver,
+                               DRIVERS.join(", ")))
+        }
+    }
+
+    Ok(command)
+}
+
+// rows come back one per line, with nothing else printed
+pub fn count_rows(output: &str) -> usize {
+    output.lines().filter(|l| !l.trim().is_empty()).count()
+}
+
+// runs the client, printing what it returned, and gives its exit code and output
+pub fn run_client(command: &ClientCommand) -> Result<(i32, String), String> {
+    let mut child = try!(Command::new(&command.program)
+        .args(&command.args)
+        .envs(command.env.iter().map(|&(ref k, ref v)| (k, v)))
+        .stdin(if command.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("couldn't run {} ({})", command.program, e)));
+
+    if let (Some(ref input), Some(mut stdin)) = (command.stdin.as_ref(), child.stdin.take()) {
+        try!(stdin.write_all(input.as_bytes())
+            .map_err(|e| format!("couldn't send the statement to {} ({})", command.program, e)));
+    }
+
+    let output = try!(child.wait_with_output()
+        .map_err(|e| format!("couldn't run {} ({})", command.program, e)));
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let code = try!(output.status
+        .code()
+        .ok_or(format!("{} was killed by a signal", command.program)));
+    Ok((code, stdout))
+}
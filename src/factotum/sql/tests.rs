@@ -0,0 +1,152 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::fs;
+
+fn params(params: Vec<&str>) -> Vec<String> {
+    params.iter().map(|p| p.to_string()).collect()
+}
+
+fn query(sql: &str) -> SqlTask {
+    SqlTask {
+        statement: Statement::Query(sql.to_string()),
+        no_rows: None,
+    }
+}
+
+#[test]
+fn parse_sql_task_good() {
+    assert_eq!(parse_sql_task(&params(vec!["query=SELECT 1 WHERE a = 'b'", "no-rows=3"])),
+               Ok(SqlTask {
+                   statement: Statement::Query("SELECT 1 WHERE a = 'b'".to_string()),
+                   no_rows: Some(3),
+               }));
+    assert_eq!(parse_sql_task(&params(vec!["script=sql/load.sql"])).map(|t| t.statement),
+               Ok(Statement::Script("sql/load.sql".to_string())));
+}
+
+#[test]
+fn parse_sql_task_bad() {
+    assert_eq!(parse_sql_task(&vec![]),
+               Err("there's no query or script to run".to_string()));
+    assert_eq!(parse_sql_task(&params(vec!["query=SELECT 1", "script=load.sql"])),
+               Err("only one query or script can be run by a task".to_string()));
+    assert_eq!(parse_sql_task(&params(vec!["query=SELECT 1", "no-rows=none"])),
+               Err("'none' isn't an exit code".to_string()));
+    assert!(parse_sql_task(&params(vec!["sql=SELECT 1"]))
+        .unwrap_err()
+        .starts_with("'sql' isn't a sql parameter"));
+}
+
+#[test]
+fn resolve_secret_by_reference() {
+    env::set_var("FACTOTUM_SQL_TEST_PASSWORD", "hunter2");
+    assert_eq!(resolve_secret("env:FACTOTUM_SQL_TEST_PASSWORD"),
+               Ok("hunter2".to_string()));
+    assert_eq!(resolve_secret("env:FACTOTUM_SQL_TEST_UNSET"),
+               Err("the environment variable 'FACTOTUM_SQL_TEST_UNSET' isn't set".to_string()));
+
+    let path = env::temp_dir().join("factotum-sql-secret-test.json");
+    File::create(&path).unwrap().write_all(b"{\"db\": {\"password\": \"s3cret\"}}").unwrap();
+    assert_eq!(resolve_secret(&format!("sops:{}#db.password", path.display())),
+               Ok("s3cret".to_string()));
+    assert!(resolve_secret(&format!("sops:{}#db.user", path.display())).is_err());
+    assert!(resolve_secret(&format!("sops:{}", path.display())).is_err());
+    assert_eq!(resolve_secret(&format!("file:{}", path.display())),
+               Ok("{\"db\": {\"password\": \"s3cret\"}}".to_string()));
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(resolve_secret("plain"), Ok("plain".to_string()));
+}
+
+#[test]
+fn client_command_for_postgres() {
+    let connection = Connection {
+        driver: "redshift".to_string(),
+        host: Some("cluster.redshift.amazonaws.com".to_string()),
+        port: Some(5439),
+        database: Some("analytics".to_string()),
+        user: Some("loader".to_string()),
+        ..Connection::default()
+    };
+    let command = client_command(&connection, &query("SELECT 1"), Some("pw".to_string()))
+        .unwrap();
+
+    assert_eq!(command.program, "psql");
+    assert_eq!(command.args,
+               vec!["--host=cluster.redshift.amazonaws.com",
+                    "--port=5439",
+                    "--dbname=analytics",
+                    "--username=loader",
+                    "--no-psqlrc",
+                    "--tuples-only",
+                    "--no-align",
+                    "--set=ON_ERROR_STOP=1",
+                    "--command=SELECT 1"]);
+    assert_eq!(command.env, vec![("PGPASSWORD".to_string(), "pw".to_string())]);
+    assert_eq!(command.stdin, None);
+}
+
+#[test]
+fn client_command_for_snowflake_and_odbc() {
+    let snowflake = Connection {
+        driver: "snowflake".to_string(),
+        account: Some("acme".to_string()),
+        warehouse: Some("loading".to_string()),
+        ..Connection::default()
+    };
+    let task = SqlTask {
+        statement: Statement::Script("load.sql".to_string()),
+        no_rows: None,
+    };
+    let command = client_command(&snowflake, &task, None).unwrap();
+    assert_eq!(command.program, "snowsql");
+    assert_eq!(&command.args[..2], &["--accountname=acme", "--warehouse=loading"]);
+    assert_eq!(command.args.last(), Some(&"--filename=load.sql".to_string()));
+    assert!(command.env.is_empty());
+
+    let odbc = Connection {
+        driver: "odbc".to_string(),
+        dsn: Some("Warehouse".to_string()),
+        user: Some("loader".to_string()),
+        ..Connection::default()
+    };
+    let command = client_command(&odbc, &query("SELECT 1"), Some("pw".to_string())).unwrap();
+    assert_eq!(command.program, "isql");
+    assert_eq!(command.args, vec!["-b", "Warehouse", "loader", "pw"]);
+    assert_eq!(command.stdin, Some("SELECT 1".to_string()));
+
+    assert_eq!(client_command(&Connection { dsn: None, ..odbc }, &query("SELECT 1"), None),
+               Err("an odbc connection needs a dsn".to_string()));
+    assert_eq!(client_command(&Connection { driver: "mysql".to_string(), ..snowflake },
+                              &query("SELECT 1"),
+                              None),
+               Err("'mysql' isn't a database driver, it must be one of postgres, redshift, \
+                    snowflake, odbc"
+                   .to_string()));
+}
+
+#[test]
+fn run_client_captures_rows() {
+    let command = ClientCommand {
+        program: "sh".to_string(),
+        args: vec!["-c".to_string(), "cat; exit 2".to_string()],
+        env: vec![],
+        stdin: Some("1\n\n2\n".to_string()),
+    };
+    let (code, output) = run_client(&command).unwrap();
+    assert_eq!(code, 2);
+    assert_eq!(count_rows(&output), 2);
+}
@@ -0,0 +1,228 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::{ExecutionState, ExecutionUpdate, Transition};
+use factotum::executor::task_list::State;
+use factotum::webhook::jobcontext::JobContext;
+use factotum::report::task_state_name;
+use libc;
+use std::collections::BTreeMap;
+use std::ffi::CString;
+use std::os::unix::net::UnixDatagram;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+const JOURNALD_SOCKET: &'static str = "/run/systemd/journal/socket";
+const IDENTIFIER: &'static str = "factotum";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogSink {
+    Syslog,
+    Journald,
+}
+
+pub fn parse_log_sink(sink: &str) -> Result<LogSink, String> {
+    match sink {
+        "syslog" => Ok(LogSink::Syslog),
+        "journald" => Ok(LogSink::Journald),
+        _ => Err(format!("unknown log sink '{}' (expected 'syslog' or 'journald')", sink)),
+    }
+}
+
+// priorities as defined by syslog(3)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Info = 6,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LifecycleMessage {
+    pub priority: Priority,
+    pub message: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+fn job_fields(context: &JobContext) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    fields.insert("FACTOTUM_JOB_NAME".to_string(), context.job_name.clone());
+    fields.insert("FACTOTUM_JOB_REFERENCE".to_string(),
+                  context.job_reference.clone());
+    fields.insert("FACTOTUM_RUN_REFERENCE".to_string(),
+                  context.run_reference.clone());
+    fields.insert("FACTOTUM_VERSION".to_string(),
+                  context.factotum_version.clone());
+    fields.insert("FACTOTUM_PID".to_string(), context.host.pid.to_string());
+    if let Some(ref hostname) = context.host.hostname {
+        fields.insert("FACTOTUM_HOSTNAME".to_string(), hostname.clone());
+    }
+    if let Some(ref user) = context.host.user {
+        fields.insert("FACTOTUM_USER".to_string(), user.clone());
+    }
+    if let Some(ref commit) = context.host.factfile_commit {
+        fields.insert("FACTOTUM_FACTFILE_COMMIT".to_string(), commit.clone());
+    }
+    fields
+}
+
+fn task_priority(state: &State) -> Priority {
+    match *state {
+        State::Failed(_) => Priority::Error,
+        State::Skipped(_) => Priority::Warning,
+        State::SuccessNoop => Priority::Notice,
+        _ => Priority::Info,
+    }
+}
+
+pub fn lifecycle_messages(context: &JobContext, update: &ExecutionUpdate) -> Vec<LifecycleMessage> {
+    match update.transition {
+        Transition::Job(ref job_transition) => {
+            let (priority, state) = match job_transition.to {
+                ExecutionState::Started => (Priority::Info, "WAITING"),
+                ExecutionState::Running => (Priority::Info, "RUNNING"),
+                ExecutionState::Finished => {
+                    let failed = update.task_snapshot.iter().any(|t| match t.state {
+                        State::Failed(_) => true,
+                        _ => false,
+                    });
+                    if failed {
+                        (Priority::Error, "FAILED")
+                    } else {
+                        (Priority::Notice, "SUCCEEDED")
+                    }
+                }
+            };
+            let mut fields = job_fields(context);
+            fields.insert("FACTOTUM_JOB_STATE".to_string(), state.to_string());
+            vec![LifecycleMessage {
+                     priority: priority,
+                     message: format!("job '{}' is {}", context.job_name, state),
+                     fields: fields,
+                 }]
+        }
+        Transition::Task(ref task_transitions) => {
+            task_transitions.iter()
+                .map(|t| {
+                    let mut fields = job_fields(context);
+                    fields.insert("FACTOTUM_TASK_NAME".to_string(), t.task_name.clone());
+                    fields.insert("FACTOTUM_TASK_STATE".to_string(),
+                                  task_state_name(&t.to_state).to_string());
+                    let mut message = format!("task '{}' is {}",
+                                              t.task_name,
+                                              task_state_name(&t.to_state));
+                    match t.to_state {
+                        State::Failed(ref reason) |
+                        State::Skipped(ref reason) => {
+                            message.push_str(&format!(": {}", reason));
+                        }
+                        _ => {}
+                    }
+                    LifecycleMessage {
+                        priority: task_priority(&t.to_state),
+                        message: message,
+                        fields: fields,
+                    }
+                })
+                .collect()
+        }
+    }
+}
+
+pub fn emit_syslog(message: &LifecycleMessage) -> Result<(), String> {
+    let structured = message.fields
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, v.replace("\"", "\\\"")))
+        .collect::<Vec<String>>()
+        .join(" ");
+    let line = try!(CString::new(format!("{} [{}]", message.message, structured))
+        .map_err(|e| e.to_string()));
+    let ident = CString::new(IDENTIFIER).unwrap();
+    let format = CString::new("%s").unwrap();
+
+    unsafe {
+        libc::openlog(ident.as_ptr(), libc::LOG_PID, libc::LOG_USER);
+        libc::syslog(message.priority as libc::c_int,
+                     format.as_ptr(),
+                     line.as_ptr());
+        libc::closelog();
+    }
+
+    Ok(())
+}
+
+pub fn journald_payload(message: &LifecycleMessage) -> Vec<u8> {
+    let mut fields = message.fields.clone();
+    fields.insert("MESSAGE".to_string(), message.message.clone());
+    fields.insert("PRIORITY".to_string(),
+                  (message.priority as u8).to_string());
+    fields.insert("SYSLOG_IDENTIFIER".to_string(), IDENTIFIER.to_string());
+
+    let mut payload = vec![];
+    for (key, value) in fields.iter() {
+        payload.extend(key.as_bytes());
+        if value.contains('\n') {
+            // multi-line values use the length-prefixed binary form
+            payload.push(b'\n');
+            let len = value.len() as u64;
+            for i in 0..8 {
+                payload.push(((len >> (i * 8)) & 0xff) as u8);
+            }
+        } else {
+            payload.push(b'=');
+        }
+        payload.extend(value.as_bytes());
+        payload.push(b'\n');
+    }
+    payload
+}
+
+pub fn emit_journald(message: &LifecycleMessage) -> Result<(), String> {
+    let socket = try!(UnixDatagram::unbound().map_err(|e| e.to_string()));
+    socket.send_to(&journald_payload(message), JOURNALD_SOCKET)
+        .map(|_| ())
+        .map_err(|e| format!("couldn't write to '{}' ({})", JOURNALD_SOCKET, e))
+}
+
+pub fn connect_log_sink<F>(context: JobContext,
+                           updates_channel: Receiver<ExecutionUpdate>,
+                           emitter_func: F)
+                           -> JoinHandle<u32>
+    where F: Fn(&LifecycleMessage) -> Result<(), String> + Send + 'static
+{
+    thread::spawn(move || {
+        let mut failures = 0;
+
+        for update in updates_channel.iter() {
+            for message in lifecycle_messages(&context, &update) {
+                if let Err(msg) = emitter_func(&message) {
+                    warn!("Failed to write lifecycle message '{}': {}",
+                          message.message,
+                          msg);
+                    failures += 1;
+                }
+            }
+
+            if update.execution_state == ExecutionState::Finished {
+                break;
+            }
+        }
+
+        failures
+    })
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::executor::{JobTransition, TaskTransition};
+use factotum::executor::task_list::Task;
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::Host;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+
+fn job_update(to: ExecutionState, snapshot: Vec<Task<::factotum::factfile::Task>>) -> ExecutionUpdate {
+    ExecutionUpdate::new(to.clone(),
+                         snapshot,
+                         Transition::Job(JobTransition::new(None, to)))
+}
+
+#[test]
+fn parse_log_sink_good() {
+    assert_eq!(parse_log_sink("syslog"), Ok(LogSink::Syslog));
+    assert_eq!(parse_log_sink("journald"), Ok(LogSink::Journald));
+    assert_eq!(parse_log_sink("stdout"),
+               Err("unknown log sink 'stdout' (expected 'syslog' or 'journald')".to_string()));
+}
+
+#[test]
+fn job_messages_have_priorities() {
+    let context = JobContext::new("my job", "{}", None);
+
+    let started = lifecycle_messages(&context, &job_update(ExecutionState::Started, vec![]));
+    assert_eq!(started.len(), 1);
+    assert_eq!(started[0].priority, Priority::Info);
+    assert_eq!(started[0].message, "job 'my job' is WAITING");
+    assert_eq!(started[0].fields.get("FACTOTUM_RUN_REFERENCE"),
+               Some(&context.run_reference));
+
+    let finished = lifecycle_messages(&context, &job_update(ExecutionState::Finished, vec![]));
+    assert_eq!(finished[0].priority, Priority::Notice);
+    assert_eq!(finished[0].fields.get("FACTOTUM_JOB_STATE"),
+               Some(&"SUCCEEDED".to_string()));
+
+    let mut failed_task = Task::new("a", make_task("a", &vec![]));
+    failed_task.state = State::Failed("bad code".to_string());
+    let failed = lifecycle_messages(&context,
+                                    &job_update(ExecutionState::Finished, vec![failed_task]));
+    assert_eq!(failed[0].priority, Priority::Error);
+    assert_eq!(failed[0].message, "job 'my job' is FAILED");
+}
+
+#[test]
+fn messages_name_the_host() {
+    let host = Host {
+        user: Some("jane".to_string()),
+        ..Host::new(Some("worker-1".to_string()))
+    };
+    let context = JobContext::new("my job", "{}", None).on_host(host);
+
+    let fields = &lifecycle_messages(&context, &job_update(ExecutionState::Started, vec![]))[0]
+        .fields;
+    assert_eq!(fields.get("FACTOTUM_HOSTNAME"), Some(&"worker-1".to_string()));
+    assert_eq!(fields.get("FACTOTUM_USER"), Some(&"jane".to_string()));
+    assert_eq!(fields.get("FACTOTUM_PID"), Some(&::std::process::id().to_string()));
+    assert_eq!(fields.get("FACTOTUM_FACTFILE_COMMIT"), None);
+}
+
+#[test]
+fn task_messages_have_priorities() {
+    let context = JobContext::new("my job", "{}", None);
+    let update = ExecutionUpdate::new(ExecutionState::Running,
+                                      vec![],
+                                      Transition::Task(vec![
+        TaskTransition::new("a", State::Waiting, State::Running),
+        TaskTransition::new("b", State::Running, State::Failed("bad code".to_string())),
+        TaskTransition::new("c", State::Waiting, State::Skipped("'b' failed".to_string())),
+        TaskTransition::new("d", State::Running, State::SuccessNoop),
+    ]));
+
+    let messages = lifecycle_messages(&context, &update);
+    let priorities = messages.iter().map(|m| m.priority).collect::<Vec<Priority>>();
+    assert_eq!(priorities,
+               vec![Priority::Info, Priority::Error, Priority::Warning, Priority::Notice]);
+    assert_eq!(messages[1].message, "task 'b' is FAILED: bad code");
+    assert_eq!(messages[1].fields.get("FACTOTUM_TASK_NAME"),
+               Some(&"b".to_string()));
+    assert_eq!(messages[1].fields.get("FACTOTUM_TASK_STATE"),
+               Some(&"FAILED".to_string()));
+}
+
+#[test]
+fn journald_payload_good() {
+    let mut fields = BTreeMap::new();
+    fields.insert("FACTOTUM_TASK_NAME".to_string(), "a".to_string());
+    let message = LifecycleMessage {
+        priority: Priority::Error,
+        message: "line one\nline two".to_string(),
+        fields: fields,
+    };
+
+    let payload = journald_payload(&message);
+    let mut expected = b"FACTOTUM_TASK_NAME=a\nMESSAGE\n".to_vec();
+    expected.extend(&[17, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend(b"line one\nline two\nPRIORITY=3\nSYSLOG_IDENTIFIER=factotum\n");
+    assert_eq!(payload, expected);
+}
+
+#[test]
+fn log_sink_stops_when_job_finishes() {
+    let context = JobContext::new("my job", "{}", None);
+    let written = Arc::new(Mutex::new(vec![]));
+    let sink_written = written.clone();
+    let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+
+    let handle = connect_log_sink(context, rx, move |m| {
+        sink_written.lock().unwrap().push(m.message.clone());
+        if m.priority == Priority::Notice {
+            Err("socket closed".to_string())
+        } else {
+            Ok(())
+        }
+    });
+
+    tx.send(job_update(ExecutionState::Started, vec![])).unwrap();
+    tx.send(job_update(ExecutionState::Finished, vec![])).unwrap();
+
+    assert_eq!(handle.join().unwrap(), 1);
+    assert_eq!(*written.lock().unwrap(),
+               vec!["job 'my job' is WAITING".to_string(),
+                    "job 'my job' is SUCCEEDED".to_string()]);
+}
@@ -0,0 +1,49 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+
+#[cfg(test)]
+mod tests;
+
+use factotum::executor::execution_strategy::{RunResult, TaskLimits};
+use factotum::webhook::jobcontext::JobContext;
+use std::process::Command;
+
+// what every task is told about the run it's part of, so its own logs and outputs can be
+// tagged without passing arguments; the run's workspace is given as workspace::RUN_ENV_VAR
+pub const RUN_ID_VAR: &'static str = "FACTOTUM_RUN_ID";
+pub const JOB_NAME_VAR: &'static str = "FACTOTUM_JOB_NAME";
+pub const TASK_NAME_VAR: &'static str = "FACTOTUM_TASK_NAME";
+pub const ATTEMPT_VAR: &'static str = "FACTOTUM_ATTEMPT";
+
+pub fn run_env(context: &JobContext, task_name: &str) -> Vec<(&'static str, String)> {
+    vec![(RUN_ID_VAR, context.run_reference.clone()),
+         (JOB_NAME_VAR, context.job_name.clone()),
+         (TASK_NAME_VAR, task_name.to_string()),
+         (ATTEMPT_VAR, context.attempt.to_string())]
+}
+
+// wraps an execution strategy so each task's command is given the run's metadata; dry runs
+// pass None, as the simulated command is compared with the task's own to spot simulated output
+pub fn with_run_env<F>(context: Option<JobContext>, strategy: F)
+    -> impl Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+{
+    move |name: &str, command: &mut Command, limits: &TaskLimits| {
+        if let Some(ref context) = context {
+            command.envs(run_env(context, name));
+        }
+        strategy(name, command, limits)
+    }
+}
@@ -0,0 +1,64 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::time::Duration;
+
+// reports the environment the task's command was given
+fn print_env(_: &str, command: &mut Command, _: &TaskLimits) -> RunResult {
+    let env = command.get_envs()
+        .map(|(k, v)| format!("{}={}", k.to_string_lossy(), v.unwrap().to_string_lossy()))
+        .collect::<Vec<String>>()
+        .join("\n");
+    RunResult {
+        duration: Duration::from_secs(1),
+        task_execution_error: None,
+        stdout: Some(env),
+        stderr: None,
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+    }
+}
+
+#[test]
+fn run_env_describes_the_run() {
+    let context = JobContext::new("nightly", "{}", None).on_attempt(2, 3);
+    assert_eq!(run_env(&context, "load"),
+               vec![("FACTOTUM_RUN_ID", context.run_reference.clone()),
+                    ("FACTOTUM_JOB_NAME", "nightly".to_string()),
+                    ("FACTOTUM_TASK_NAME", "load".to_string()),
+                    ("FACTOTUM_ATTEMPT", "2".to_string())]);
+}
+
+#[test]
+fn tasks_given_the_run_env() {
+    let context = JobContext::new("nightly", "{}", None);
+    let strategy = with_run_env(Some(context.clone()), print_env);
+    let mut command = Command::new("true");
+    command.env("FACTOTUM_WORKSPACE", "/tmp/ws");
+    let env = strategy("extract", &mut command, &TaskLimits::default()).stdout.unwrap();
+    assert!(env.contains(&format!("FACTOTUM_RUN_ID={}", context.run_reference)));
+    assert!(env.contains("FACTOTUM_TASK_NAME=extract"));
+    assert!(env.contains("FACTOTUM_ATTEMPT=1"));
+    assert!(env.contains("FACTOTUM_WORKSPACE=/tmp/ws"));
+}
+
+#[test]
+fn simulated_tasks_not_given_the_run_env() {
+    let strategy = with_run_env(None, print_env);
+    let mut command = Command::new("true");
+    let env = strategy("extract", &mut command, &TaskLimits::default()).stdout.unwrap();
+    assert_eq!(env, "");
+}
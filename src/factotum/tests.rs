@@ -14,6 +14,8 @@
 
 use factotum::factfile::Task;
 use factotum::factfile::OnResult;
+use factotum::factfile::CircuitBreaker;
+use factotum::factfile::Shell;
 
 pub fn compare_tasks(expected: Vec<Vec<&str>>, actual: Vec<Vec<&Task>>) {
     for i in 0..expected.len() {
@@ -38,5 +40,22 @@ pub fn make_task(name: &str, depends_on: &Vec<&str>) -> Task {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     }
 }
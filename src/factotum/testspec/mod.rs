@@ -0,0 +1,258 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::config::ExecutorSettings;
+use factotum::executor::execute_factfile;
+use factotum::executor::execution_strategy::TaskLimits;
+use factotum::mock::{self, MockCall, MockExecutor, MockResult};
+use factotum::parser::{self, OverrideResultMappings};
+use factotum::report;
+use rustc_serialize::json::Json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestSpec {
+    pub factfile: String,
+    pub cases: Vec<TestCase>,
+}
+
+// one run of the factfile against the mock executor, and what should come of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub variables: Json,
+    pub results: HashMap<String, MockResult>,
+    pub expected_states: BTreeMap<String, String>,
+    pub expected_order: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub name: String,
+    pub failures: Vec<String>,
+}
+
+const STATES: [&'static str; 4] = ["SUCCEEDED", "SUCCEEDED_NO_OP", "FAILED", "SKIPPED"];
+
+fn string_list(json: &Json) -> Option<Vec<String>> {
+    json.as_array().and_then(|a| {
+        a.iter().map(|v| v.as_string().map(|s| s.to_string())).collect()
+    })
+}
+
+fn parse_expected_states(case_name: &str,
+                         json: &Json)
+                         -> Result<BTreeMap<String, String>, String> {
+    let bad_states = format!("the test '{}' has bad expected states: they must be an object of \
+                              task names to one of {}",
+                             case_name,
+                             STATES.join(", "));
+    let obj = try!(json.as_object().ok_or(bad_states.clone()));
+
+    let mut states = BTreeMap::new();
+    for (task_name, state) in obj.iter() {
+        match state.as_string() {
+            Some(s) if STATES.contains(&s) => {
+                states.insert(task_name.clone(), s.to_string());
+            }
+            _ => return Err(bad_states),
+        }
+    }
+    Ok(states)
+}
+
+// each entry is a list of tasks that must start in that order, e.g. ["a", "b", "c"]
+fn parse_expected_order(case_name: &str, json: &Json) -> Result<Vec<(String, String)>, String> {
+    let bad_order = format!("the test '{}' has a bad expected order: it must be a list of lists \
+                             of two or more task names",
+                            case_name);
+    let chains = try!(json.as_array().ok_or(bad_order.clone()));
+
+    let mut order = vec![];
+    for chain in chains.iter() {
+        let names = try!(string_list(chain).ok_or(bad_order.clone()));
+        if names.len() < 2 {
+            return Err(bad_order);
+        }
+        for pair in names.windows(2) {
+            order.push((pair[0].clone(), pair[1].clone()));
+        }
+    }
+    Ok(order)
+}
+
+fn parse_test_case(idx: usize, json: &Json) -> Result<TestCase, String> {
+    let name = json.find("name")
+        .and_then(|n| n.as_string())
+        .map(|n| n.to_string())
+        .unwrap_or(format!("test {}", idx + 1));
+
+    let variables = match json.find("variables") {
+        Some(v) if v.is_object() => v.clone(),
+        Some(_) => return Err(format!("the variables of the test '{}' must be an object", name)),
+        None => Json::Object(BTreeMap::new()),
+    };
+    let results = match json.find("results") {
+        Some(r) => {
+            try!(mock::mock_results_from_json(r)
+                .map_err(|e| format!("the results of the test '{}' are bad: {}", name, e)))
+        }
+        None => HashMap::new(),
+    };
+    let expected_states = match json.find_path(&["expect", "states"]) {
+        Some(s) => try!(parse_expected_states(&name, s)),
+        None => BTreeMap::new(),
+    };
+    let expected_order = match json.find_path(&["expect", "order"]) {
+        Some(o) => try!(parse_expected_order(&name, o)),
+        None => vec![],
+    };
+
+    Ok(TestCase {
+        name: name,
+        variables: variables,
+        results: results,
+        expected_states: expected_states,
+        expected_order: expected_order,
+    })
+}
+
+pub fn parse_test_spec(json: &str) -> Result<TestSpec, String> {
+    let parsed = try!(Json::from_str(json).map_err(|e| format!("it isn't valid JSON ({})", e)));
+
+    let factfile = try!(parsed.find("factfile")
+        .and_then(|f| f.as_string())
+        .ok_or("it must name the factfile it tests".to_string()));
+    let cases = try!(parsed.find("tests")
+        .and_then(|t| t.as_array())
+        .ok_or("it must have a list of tests".to_string()));
+
+    let mut parsed_cases = vec![];
+    for (idx, case) in cases.iter().enumerate() {
+        parsed_cases.push(try!(parse_test_case(idx, case)));
+    }
+
+    Ok(TestSpec {
+        factfile: factfile.to_string(),
+        cases: parsed_cases,
+    })
+}
+
+// the factfile is found relative to the spec, so specs can live alongside what they test
+pub fn load_test_spec(path: &Path) -> Result<TestSpec, String> {
+    let contents = try!(fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read the test spec '{}' ({})", path.display(), e)));
+    let mut spec = try!(parse_test_spec(&contents)
+        .map_err(|e| format!("the test spec '{}' can't be used: {}", path.display(), e)));
+
+    if let Some(dir) = path.parent() {
+        spec.factfile = dir.join(&spec.factfile).to_string_lossy().into_owned();
+    }
+    Ok(spec)
+}
+
+pub fn check_case(case: &TestCase,
+                  states: &BTreeMap<String, String>,
+                  calls: &Vec<MockCall>)
+                  -> Vec<String> {
+    let mut failures = vec![];
+
+    for (task_name, expected) in case.expected_states.iter() {
+        match states.get(task_name) {
+            Some(actual) if actual == expected => (),
+            Some(actual) => {
+                failures.push(format!("expected '{}' to be {}, but it was {}",
+                                      task_name,
+                                      expected,
+                                      actual))
+            }
+            None => {
+                failures.push(format!("expected '{}' to be {}, but there's no task called '{}'",
+                                      task_name,
+                                      expected,
+                                      task_name))
+            }
+        }
+    }
+
+    let started = |name: &str| calls.iter().position(|c| c.task_name == name);
+    for &(ref before, ref after) in case.expected_order.iter() {
+        match (started(before), started(after)) {
+            (Some(b), Some(a)) if b < a => (),
+            (Some(_), Some(_)) => {
+                failures.push(format!("expected '{}' to run before '{}', but it ran after it",
+                                      before,
+                                      after))
+            }
+            (None, _) => {
+                failures.push(format!("expected '{}' to run before '{}', but '{}' never ran",
+                                      before,
+                                      after,
+                                      before))
+            }
+            (_, None) => {
+                failures.push(format!("expected '{}' to run before '{}', but '{}' never ran",
+                                      before,
+                                      after,
+                                      after))
+            }
+        }
+    }
+
+    failures
+}
+
+pub fn run_case(factfile: &str,
+                case: &TestCase,
+                configured: &ExecutorSettings)
+                -> Result<CaseResult, String> {
+    let job = try!(parser::parse(factfile,
+                                 Some(case.variables.clone()),
+                                 OverrideResultMappings::None,
+                                 configured));
+
+    let mock = MockExecutor::new(case.results.clone());
+    let strategy = {
+        let mock = mock.clone();
+        move |name: &str, command: &mut Command, _: &TaskLimits| mock.execute(name, command)
+    };
+    let tasklist = execute_factfile(&job, None, &HashMap::new(), strategy, None);
+
+    let states = tasklist.tasks
+        .iter()
+        .flat_map(|grp| grp.iter())
+        .map(|t| (t.name.clone(), report::task_state_name(&t.state).to_string()))
+        .collect::<BTreeMap<String, String>>();
+
+    Ok(CaseResult {
+        name: case.name.clone(),
+        failures: check_case(case, &states, &mock.calls()),
+    })
+}
+
+pub fn run_test_spec(spec: &TestSpec,
+                     configured: &ExecutorSettings)
+                     -> Result<Vec<CaseResult>, String> {
+    let mut results = vec![];
+    for case in spec.cases.iter() {
+        results.push(try!(run_case(&spec.factfile, case, configured)));
+    }
+    Ok(results)
+}
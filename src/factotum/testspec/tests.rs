@@ -0,0 +1,131 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::mock::{MockCall, MockResult};
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+fn states(pairs: Vec<(&str, &str)>) -> BTreeMap<String, String> {
+    pairs.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect()
+}
+
+fn calls(names: Vec<&str>) -> Vec<MockCall> {
+    names.iter()
+        .map(|n| {
+            MockCall {
+                task_name: n.to_string(),
+                command: "\"true\"".to_string(),
+            }
+        })
+        .collect()
+}
+
+fn case(expected_states: Vec<(&str, &str)>, expected_order: Vec<(&str, &str)>) -> TestCase {
+    TestCase {
+        name: "test".to_string(),
+        variables: Json::Object(BTreeMap::new()),
+        results: HashMap::new(),
+        expected_states: states(expected_states),
+        expected_order: expected_order.iter()
+            .map(|&(a, b)| (a.to_string(), b.to_string()))
+            .collect(),
+    }
+}
+
+#[test]
+fn parse_test_spec_good() {
+    let spec = parse_test_spec(r#"{"factfile": "job.factfile", "tests": [
+        {"name": "fails", "variables": {"x": "1"}, "results": {"a": 1},
+         "expect": {"states": {"a": "FAILED"}, "order": [["a", "b", "c"]]}},
+        {}
+    ]}"#)
+        .unwrap();
+
+    assert_eq!(spec.factfile, "job.factfile");
+    assert_eq!(spec.cases.len(), 2);
+    assert_eq!(spec.cases[0].name, "fails");
+    assert_eq!(spec.cases[0].variables, Json::from_str(r#"{"x": "1"}"#).unwrap());
+    assert_eq!(spec.cases[0].results["a"],
+               MockResult { return_code: 1, ..MockResult::default() });
+    assert_eq!(spec.cases[0].expected_states, states(vec![("a", "FAILED")]));
+    assert_eq!(spec.cases[0].expected_order,
+               vec![("a".to_string(), "b".to_string()), ("b".to_string(), "c".to_string())]);
+    assert_eq!(spec.cases[1], TestCase { name: "test 2".to_string(), ..case(vec![], vec![]) });
+}
+
+#[test]
+fn parse_test_spec_bad() {
+    assert_eq!(parse_test_spec(r#"{"tests": []}"#),
+               Err("it must name the factfile it tests".to_string()));
+    assert_eq!(parse_test_spec(r#"{"factfile": "job.factfile"}"#),
+               Err("it must have a list of tests".to_string()));
+    assert_eq!(parse_test_spec(r#"{"factfile": "f", "tests": [{"variables": []}]}"#),
+               Err("the variables of the test 'test 1' must be an object".to_string()));
+    assert_eq!(parse_test_spec(r#"{"factfile": "f", "tests": [{"results": {"a": "x"}}]}"#),
+               Err("the results of the test 'test 1' are bad: the mock result for 'a' must be \
+                    a return code, or an object with a returnCode and optional stdout and \
+                    stderr"
+                   .to_string()));
+    assert_eq!(parse_test_spec(r#"{"factfile": "f", "tests": [{"expect": {"states": {"a": "OK"}}}]}"#),
+               Err("the test 'test 1' has bad expected states: they must be an object of task \
+                    names to one of SUCCEEDED, SUCCEEDED_NO_OP, FAILED, SKIPPED"
+                   .to_string()));
+    assert_eq!(parse_test_spec(r#"{"factfile": "f", "tests": [{"expect": {"order": [["a"]]}}]}"#),
+               Err("the test 'test 1' has a bad expected order: it must be a list of lists of \
+                    two or more task names"
+                   .to_string()));
+}
+
+#[test]
+fn check_case_passes() {
+    let c = case(vec![("a", "SUCCEEDED"), ("b", "SKIPPED")], vec![("a", "c")]);
+
+    assert!(check_case(&c,
+                       &states(vec![("a", "SUCCEEDED"), ("b", "SKIPPED"), ("c", "SUCCEEDED")]),
+                       &calls(vec!["a", "c"]))
+        .is_empty());
+}
+
+#[test]
+fn check_case_failures() {
+    let c = case(vec![("a", "SUCCEEDED"), ("z", "FAILED")],
+                 vec![("a", "b"), ("b", "c"), ("a", "d")]);
+
+    assert_eq!(check_case(&c,
+                          &states(vec![("a", "FAILED"), ("b", "SUCCEEDED")]),
+                          &calls(vec!["b", "a", "c"])),
+               vec!["expected 'a' to be SUCCEEDED, but it was FAILED",
+                    "expected 'z' to be FAILED, but there's no task called 'z'",
+                    "expected 'a' to run before 'b', but it ran after it",
+                    "expected 'a' to run before 'd', but 'd' never ran"]);
+}
+
+#[test]
+fn run_test_spec_against_the_mock_executor() {
+    let spec = load_test_spec(Path::new("./tests/resources/example_barrier.test.json")).unwrap();
+    assert_eq!(spec.factfile, "./tests/resources/example_barrier.factfile");
+
+    let results = run_test_spec(&spec, &ExecutorSettings::new()).unwrap();
+    assert_eq!(results,
+               vec![CaseResult {
+                        name: "the report runs once both loads are done".to_string(),
+                        failures: vec![],
+                    },
+                    CaseResult {
+                        name: "a failed load stops the report".to_string(),
+                        failures: vec![],
+                    }]);
+}
@@ -0,0 +1,184 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::history::RunRecord;
+use factotum::report::escape_html;
+use chrono::NaiveDateTime;
+
+const STARTED_FORMAT: &'static str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+const LABEL_WIDTH: u64 = 200;
+const CHART_WIDTH: u64 = 800;
+const ROW_HEIGHT: u64 = 24;
+const HEADER_HEIGHT: u64 = 40;
+const AXIS_HEIGHT: u64 = 30;
+const TICKS: u64 = 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineBar {
+    pub name: String,
+    pub state: String,
+    pub offset_millis: u64,
+    pub duration_millis: u64,
+}
+
+impl TimelineBar {
+    pub fn finish_millis(&self) -> u64 {
+        self.offset_millis + self.duration_millis
+    }
+}
+
+fn parse_started(started: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(started, STARTED_FORMAT)
+        .map_err(|e| format!("the start time '{}' couldn't be read ({})", started, e))
+}
+
+// bars are offset from the first task to start, and ordered by when they started
+pub fn timeline_bars(run: &RunRecord) -> Result<Vec<TimelineBar>, String> {
+    let mut timed = vec![];
+    for task in run.tasks.iter() {
+        if let (&Some(ref started), Some(duration)) = (&task.started, task.duration_millis) {
+            timed.push((task, try!(parse_started(started)), duration));
+        }
+    }
+
+    let first_start = match timed.iter().map(|&(_, started, _)| started).min() {
+        Some(s) => s,
+        None => return Err(format!("the run '{}' has no recorded task timings", run.run_reference)),
+    };
+
+    let mut bars = timed.iter()
+        .map(|&(task, started, duration)| {
+            TimelineBar {
+                name: task.task_name.clone(),
+                state: task.state.clone(),
+                offset_millis: (started - first_start).num_milliseconds() as u64,
+                duration_millis: duration,
+            }
+        })
+        .collect::<Vec<TimelineBar>>();
+    bars.sort_by(|a, b| a.offset_millis.cmp(&b.offset_millis).then(a.name.cmp(&b.name)));
+    Ok(bars)
+}
+
+pub fn wall_clock_millis(bars: &Vec<TimelineBar>) -> u64 {
+    bars.iter().map(|b| b.finish_millis()).max().unwrap_or(0)
+}
+
+// the most tasks that were running at the same moment
+pub fn max_parallelism(bars: &Vec<TimelineBar>) -> usize {
+    // (time, change) - finishes sort before starts at the same instant
+    let mut events = vec![];
+    for bar in bars.iter().filter(|b| b.duration_millis > 0) {
+        events.push((bar.offset_millis, 1i64));
+        events.push((bar.finish_millis(), -1i64));
+    }
+    events.sort();
+
+    let mut running = 0i64;
+    let mut max = 0i64;
+    for (_, change) in events {
+        running += change;
+        if running > max {
+            max = running;
+        }
+    }
+    max as usize
+}
+
+// the total task time divided by the wall clock time of the run
+pub fn average_parallelism(bars: &Vec<TimelineBar>) -> f64 {
+    let wall_clock = wall_clock_millis(bars);
+    if wall_clock == 0 {
+        0.0
+    } else {
+        bars.iter().map(|b| b.duration_millis).sum::<u64>() as f64 / wall_clock as f64
+    }
+}
+
+fn bar_colour(state: &str) -> &'static str {
+    match state {
+        "SUCCEEDED" => "#34a853",
+        "SUCCEEDED_NO_OP" => "#9aa0a6",
+        "FAILED" => "#ea4335",
+        _ => "#fbbc04",
+    }
+}
+
+fn format_seconds(millis: u64) -> String {
+    format!("{:.1}s", millis as f64 / 1000.0)
+}
+
+pub fn timeline_svg(run: &RunRecord, bars: &Vec<TimelineBar>) -> String {
+    let wall_clock = wall_clock_millis(bars);
+    let scale = if wall_clock == 0 {
+        0.0
+    } else {
+        CHART_WIDTH as f64 / wall_clock as f64
+    };
+    let width = LABEL_WIDTH + CHART_WIDTH + 20;
+    let height = HEADER_HEIGHT + bars.len() as u64 * ROW_HEIGHT + AXIS_HEIGHT;
+    let axis_y = HEADER_HEIGHT + bars.len() as u64 * ROW_HEIGHT;
+
+    let mut svg = format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+                           font-family=\"sans-serif\" font-size=\"12\">\n",
+                          width,
+                          height);
+    svg.push_str(&format!("  <text x=\"0\" y=\"16\" font-size=\"14\">{} - {}</text>\n",
+                          escape_html(&run.job_name),
+                          escape_html(&run.run_reference)));
+    svg.push_str(&format!("  <text x=\"0\" y=\"32\">wall clock {}, max parallelism {}, \
+                           average parallelism {:.2}</text>\n",
+                          format_seconds(wall_clock),
+                          max_parallelism(bars),
+                          average_parallelism(bars)));
+
+    for (idx, bar) in bars.iter().enumerate() {
+        let y = HEADER_HEIGHT + idx as u64 * ROW_HEIGHT;
+        let x = LABEL_WIDTH as f64 + bar.offset_millis as f64 * scale;
+        let bar_width = (bar.duration_millis as f64 * scale).max(1.0);
+        svg.push_str(&format!("  <text x=\"0\" y=\"{}\">{}</text>\n",
+                              y + 16,
+                              escape_html(&bar.name)));
+        svg.push_str(&format!("  <rect x=\"{:.1}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" \
+                               fill=\"{}\"><title>{}: {} from +{} ({})</title></rect>\n",
+                              x,
+                              y + 4,
+                              bar_width,
+                              ROW_HEIGHT - 8,
+                              bar_colour(&bar.state),
+                              escape_html(&bar.name),
+                              bar.state,
+                              format_seconds(bar.offset_millis),
+                              format_seconds(bar.duration_millis)));
+    }
+
+    svg.push_str(&format!("  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#5f6368\"/>\n",
+                          LABEL_WIDTH,
+                          axis_y,
+                          LABEL_WIDTH + CHART_WIDTH,
+                          axis_y));
+    for tick in 0..(TICKS + 1) {
+        let x = LABEL_WIDTH + CHART_WIDTH * tick / TICKS;
+        svg.push_str(&format!("  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n",
+                              x,
+                              axis_y + 16,
+                              format_seconds(wall_clock * tick / TICKS)));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::history::{RunRecord, TaskRecord};
+
+fn task(name: &str, state: &str, started: Option<&str>, duration: Option<u64>) -> TaskRecord {
+    TaskRecord {
+        task_name: name.to_string(),
+        state: state.to_string(),
+        error_message: None,
+        depends_on: vec![],
+        started: started.map(|s| s.to_string()),
+        duration_millis: duration,
+        cache_key: None,
+    }
+}
+
+fn run(tasks: Vec<TaskRecord>) -> RunRecord {
+    RunRecord {
+        job_name: "<job>".to_string(),
+        run_reference: "abc".to_string(),
+        start_time: "2017-01-01T10:00:00.000Z".to_string(),
+        run_state: "SUCCEEDED".to_string(),
+        source: None,
+        tasks: tasks,
+    }
+}
+
+fn bar(name: &str, offset_millis: u64, duration_millis: u64) -> TimelineBar {
+    TimelineBar {
+        name: name.to_string(),
+        state: "SUCCEEDED".to_string(),
+        offset_millis: offset_millis,
+        duration_millis: duration_millis,
+    }
+}
+
+#[test]
+fn timeline_bars_are_offset_from_first_start() {
+    let r = run(vec![task("c", "SUCCEEDED", Some("2017-01-01T10:00:02.500Z"), Some(1000)),
+                     task("a", "SUCCEEDED", Some("2017-01-01T10:00:00.250Z"), Some(2000)),
+                     task("b", "SUCCEEDED", Some("2017-01-01T10:00:02.500Z"), Some(500)),
+                     task("d", "SKIPPED", None, None)]);
+
+    assert_eq!(timeline_bars(&r),
+               Ok(vec![bar("a", 0, 2000), bar("b", 2250, 500), bar("c", 2250, 1000)]));
+}
+
+#[test]
+fn timeline_bars_need_timings() {
+    assert_eq!(timeline_bars(&run(vec![task("a", "SKIPPED", None, None)])),
+               Err("the run 'abc' has no recorded task timings".to_string()));
+    assert!(timeline_bars(&run(vec![task("a", "SUCCEEDED", Some("yesterday"), Some(1))]))
+        .unwrap_err()
+        .starts_with("the start time 'yesterday' couldn't be read"));
+}
+
+#[test]
+fn parallelism_good() {
+    let bars = vec![bar("a", 0, 2000), bar("b", 2000, 1000), bar("c", 2000, 2000)];
+    assert_eq!(wall_clock_millis(&bars), 4000);
+    assert_eq!(max_parallelism(&bars), 2);
+    assert_eq!(average_parallelism(&bars), 1.25);
+
+    assert_eq!(max_parallelism(&vec![]), 0);
+    assert_eq!(average_parallelism(&vec![]), 0.0);
+}
+
+#[test]
+fn timeline_svg_draws_each_task() {
+    let r = run(vec![]);
+    let bars = vec![bar("a & b", 0, 1000), bar("c", 1000, 1000)];
+    let svg = timeline_svg(&r, &bars);
+
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1020\" height=\"118\""));
+    assert!(svg.contains("&lt;job&gt; - abc"));
+    assert!(svg.contains("wall clock 2.0s, max parallelism 1, average parallelism 1.00"));
+    assert!(svg.contains("<rect x=\"200.0\" y=\"44\" width=\"400.0\" height=\"16\" fill=\"#34a853\">\
+                          <title>a &amp; b: SUCCEEDED from +0.0s (1.0s)</title></rect>"));
+    assert!(svg.contains("<rect x=\"600.0\" y=\"68\" width=\"400.0\""));
+    assert!(svg.ends_with("</svg>\n"));
+}
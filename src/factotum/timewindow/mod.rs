@@ -0,0 +1,94 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use chrono::{DateTime, Duration, NaiveTime, Timelike, UTC};
+use factotum::tzfile;
+
+pub const DEFAULT_TIMEZONE: &'static str = "UTC";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub timezone: String,
+}
+
+impl TimeWindow {
+    // a window whose end is before its start runs over midnight
+    pub fn contains(&self, time: &NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            *time >= self.start && *time < self.end
+        } else {
+            *time >= self.start || *time < self.end
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map_err(|_| format!("'{}' is not a valid time (expected HH:MM)", time.trim()))
+}
+
+// e.g. "22:00-06:00,Europe/London" - the timezone defaults to UTC
+pub fn parse_time_window(window: &str) -> Result<TimeWindow, String> {
+    let mut parts = window.splitn(2, ',');
+    let range = parts.next().unwrap_or("");
+    let timezone = parts.next().map(|tz| tz.trim()).unwrap_or("");
+
+    let times = range.split('-').collect::<Vec<&str>>();
+    if times.len() != 2 {
+        return Err(format!("'{}' is not a valid time window (expected HH:MM-HH:MM)",
+                           range.trim()));
+    }
+
+    Ok(TimeWindow {
+        start: try!(parse_time(times[0])),
+        end: try!(parse_time(times[1])),
+        timezone: if timezone.is_empty() {
+            DEFAULT_TIMEZONE.to_string()
+        } else {
+            timezone.to_string()
+        },
+    })
+}
+
+// the zone's read from the zoneinfo database each time, as tasks run while it's checked and
+// nothing process-wide (like the TZ variable) can be changed safely then
+pub fn local_time_in(timezone: &str, now: &DateTime<UTC>) -> Result<NaiveTime, String> {
+    let offset = if timezone == DEFAULT_TIMEZONE {
+        0
+    } else {
+        try!(tzfile::load(timezone)).utc_offset(now.timestamp())
+    };
+    let local = now.naive_utc() + Duration::seconds(offset as i64);
+    Ok(NaiveTime::from_hms(local.hour(), local.minute(), local.second()))
+}
+
+pub fn check_time_window(window: &TimeWindow, now: &DateTime<UTC>) -> Result<(), String> {
+    let local = try!(local_time_in(&window.timezone, now));
+    if window.contains(&local) {
+        Ok(())
+    } else {
+        Err(format!("the time in {} is {}, outside of the allowed window {}-{}",
+                    window.timezone,
+                    local.format("%H:%M"),
+                    window.start.format("%H:%M"),
+                    window.end.format("%H:%M")))
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use chrono::{NaiveTime, TimeZone, UTC};
+
+fn window(start: (u32, u32), end: (u32, u32), timezone: &str) -> TimeWindow {
+    TimeWindow {
+        start: NaiveTime::from_hms(start.0, start.1, 0),
+        end: NaiveTime::from_hms(end.0, end.1, 0),
+        timezone: timezone.to_string(),
+    }
+}
+
+#[test]
+fn parse_time_window_good() {
+    assert_eq!(parse_time_window("22:00-06:00,Europe/London"),
+               Ok(window((22, 0), (6, 0), "Europe/London")));
+    assert_eq!(parse_time_window(" 09:30 - 17:00 "),
+               Ok(window((9, 30), (17, 0), "UTC")));
+}
+
+#[test]
+fn parse_time_window_bad() {
+    assert_eq!(parse_time_window("22:00"),
+               Err("'22:00' is not a valid time window (expected HH:MM-HH:MM)".to_string()));
+    assert_eq!(parse_time_window("22:00-6pm,UTC"),
+               Err("'6pm' is not a valid time (expected HH:MM)".to_string()));
+}
+
+#[test]
+fn contains_good() {
+    let day = window((9, 0), (17, 0), "UTC");
+    assert!(day.contains(&NaiveTime::from_hms(9, 0, 0)));
+    assert!(day.contains(&NaiveTime::from_hms(16, 59, 59)));
+    assert!(!day.contains(&NaiveTime::from_hms(17, 0, 0)));
+    assert!(!day.contains(&NaiveTime::from_hms(3, 0, 0)));
+
+    let night = window((22, 0), (6, 0), "UTC");
+    assert!(night.contains(&NaiveTime::from_hms(23, 0, 0)));
+    assert!(night.contains(&NaiveTime::from_hms(5, 0, 0)));
+    assert!(!night.contains(&NaiveTime::from_hms(12, 0, 0)));
+
+    assert!(window((0, 0), (0, 0), "UTC").contains(&NaiveTime::from_hms(12, 0, 0)));
+}
+
+#[test]
+fn local_time_in_good() {
+    let winter = UTC.ymd(2017, 1, 15).and_hms(12, 30, 0);
+    let summer = UTC.ymd(2017, 7, 15).and_hms(12, 30, 0);
+
+    assert_eq!(local_time_in("UTC", &winter), Ok(NaiveTime::from_hms(12, 30, 0)));
+    assert_eq!(local_time_in("Europe/London", &winter),
+               Ok(NaiveTime::from_hms(12, 30, 0)));
+    assert_eq!(local_time_in("Europe/London", &summer),
+               Ok(NaiveTime::from_hms(13, 30, 0)));
+    assert_eq!(local_time_in("America/New_York", &winter),
+               Ok(NaiveTime::from_hms(7, 30, 0)));
+    assert_eq!(local_time_in("Mars/Olympus_Mons", &winter),
+               Err("the timezone 'Mars/Olympus_Mons' is not known".to_string()));
+}
+
+#[test]
+fn check_time_window_good() {
+    let now = UTC.ymd(2017, 7, 15).and_hms(21, 30, 0);
+
+    assert_eq!(check_time_window(&window((22, 0), (6, 0), "Europe/London"), &now),
+               Ok(()));
+    assert_eq!(check_time_window(&window((22, 0), (6, 0), "UTC"), &now),
+               Err("the time in UTC is 21:30, outside of the allowed window 22:00-06:00"
+                   .to_string()));
+}
@@ -0,0 +1,383 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+const HEADER_LEN: usize = 44;
+const DEFAULT_RULE_TIME: i32 = 2 * 60 * 60;
+
+// a timezone as its TZif file in the zoneinfo database describes it (RFC 8536); the file's
+// read rather than leaving the conversion to the C library, which is only told the timezone
+// through the process-wide TZ variable
+#[derive(Debug, Clone, PartialEq)]
+pub struct Zone {
+    // when the offset changed, and the index of the offset it changed to
+    pub transitions: Vec<(i64, usize)>,
+    // in seconds east of UTC
+    pub offsets: Vec<i32>,
+    // how the offset changes after the last transition
+    pub rule: Option<PosixRule>,
+}
+
+// a POSIX TZ string such as "GMT0BST,M3.5.0/1,M10.5.0", with its offsets in seconds east of UTC
+#[derive(Debug, Clone, PartialEq)]
+pub struct PosixRule {
+    pub std_offset: i32,
+    pub dst: Option<DstRule>,
+}
+
+// daylight saving starts and ends at a time (in seconds) on a day, both in local time
+#[derive(Debug, Clone, PartialEq)]
+pub struct DstRule {
+    pub offset: i32,
+    pub start: (RuleDay, i32),
+    pub end: (RuleDay, i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleDay {
+    // Jn: 1 to 365, February 29th is never counted
+    Julian(u32),
+    // n: 0 to 365, February 29th is counted
+    ZeroBased(u32),
+    // Mm.w.d: day d (0 is Sunday) of week w (5 is the last) of month m
+    MonthWeekDay(u32, u32, u32),
+}
+
+pub fn zoneinfo_path(timezone: &str) -> PathBuf {
+    let zoneinfo_dir = env::var("TZDIR").unwrap_or("/usr/share/zoneinfo".to_string());
+    PathBuf::from(zoneinfo_dir).join(timezone)
+}
+
+pub fn load(timezone: &str) -> Result<Zone, String> {
+    let unknown = format!("the timezone '{}' is not known", timezone);
+    if timezone.contains("..") {
+        return Err(unknown);
+    }
+    let mut data = vec![];
+    try!(File::open(zoneinfo_path(timezone))
+        .and_then(|mut f| f.read_to_end(&mut data))
+        .map_err(|_| unknown));
+    parse_tzif(&data).map_err(|e| format!("the timezone '{}' couldn't be read: {}", timezone, e))
+}
+
+fn be_u32(data: &[u8], at: usize) -> u32 {
+    data[at..at + 4].iter().fold(0, |n, b| (n << 8) | *b as u32)
+}
+
+fn be_i64(data: &[u8], at: usize) -> i64 {
+    data[at..at + 8].iter().fold(0, |n, b| (n << 8) | *b as i64)
+}
+
+struct Header {
+    version: u8,
+    isutcnt: usize,
+    isstdcnt: usize,
+    leapcnt: usize,
+    timecnt: usize,
+    typecnt: usize,
+    charcnt: usize,
+}
+
+impl Header {
+    // the length of the data after the header, given the size of its times
+    fn data_len(&self, time_size: usize) -> usize {
+        self.timecnt * time_size + self.timecnt + self.typecnt * 6 + self.charcnt +
+        self.leapcnt * (time_size + 4) + self.isstdcnt + self.isutcnt
+    }
+}
+
+fn header(data: &[u8], at: usize) -> Result<Header, String> {
+    if data.len() < at + HEADER_LEN || &data[at..at + 4] != b"TZif" {
+        return Err("it isn't a TZif file".to_string());
+    }
+    let count = |n: usize| be_u32(data, at + 20 + n * 4) as usize;
+    Ok(Header {
+        version: data[at + 4],
+        isutcnt: count(0),
+        isstdcnt: count(1),
+        leapcnt: count(2),
+        timecnt: count(3),
+        typecnt: count(4),
+        charcnt: count(5),
+    })
+}
+
+// version 1 files only have 32 bit times; later versions follow them with the same data
+// with 64 bit times, and a POSIX TZ string for the times after the last transition
+pub fn parse_tzif(data: &[u8]) -> Result<Zone, String> {
+    let v1 = try!(header(data, 0));
+    let (header, at, time_size) = if v1.version >= b'2' {
+        let v2_at = HEADER_LEN + v1.data_len(4);
+        (try!(header(data, v2_at)), v2_at + HEADER_LEN, 8)
+    } else {
+        (v1, HEADER_LEN, 4)
+    };
+
+    let end = at + header.data_len(time_size);
+    if data.len() < end {
+        return Err("it's truncated".to_string());
+    }
+    if header.typecnt == 0 {
+        return Err("it has no offsets".to_string());
+    }
+
+    let indices_at = at + header.timecnt * time_size;
+    let types_at = indices_at + header.timecnt;
+    let mut transitions = vec![];
+    for i in 0..header.timecnt {
+        let time = if time_size == 8 {
+            be_i64(data, at + i * 8)
+        } else {
+            be_u32(data, at + i * 4) as i32 as i64
+        };
+        let index = data[indices_at + i] as usize;
+        if index >= header.typecnt {
+            return Err(format!("a transition has the unknown type {}", index));
+        }
+        transitions.push((time, index));
+    }
+    let offsets = (0..header.typecnt)
+        .map(|i| be_u32(data, types_at + i * 6) as i32)
+        .collect::<Vec<i32>>();
+
+    let footer = String::from_utf8_lossy(&data[end..]);
+    let rule = match footer.trim_matches('\n') {
+        "" => None,
+        tz => Some(try!(parse_posix_rule(tz))),
+    };
+
+    Ok(Zone {
+        transitions: transitions,
+        offsets: offsets,
+        rule: rule,
+    })
+}
+
+impl Zone {
+    // the offset (in seconds east of UTC) at a unix timestamp
+    pub fn utc_offset(&self, timestamp: i64) -> i32 {
+        let after_last = self.transitions.last().map_or(true, |&(at, _)| timestamp >= at);
+        if let (true, Some(rule)) = (after_last, self.rule.as_ref()) {
+            return rule.utc_offset(timestamp);
+        }
+        // before the first transition the first offset's used
+        let index = self.transitions
+            .iter()
+            .rev()
+            .find(|&&(at, _)| at <= timestamp)
+            .map_or(0, |&(_, index)| index);
+        self.offsets[index]
+    }
+}
+
+struct RuleParser<'a> {
+    rule: &'a [u8],
+    at: usize,
+}
+
+impl<'a> RuleParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.rule.get(self.at).cloned()
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        if self.peek() == Some(c) {
+            self.at += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self) -> Result<u32, String> {
+        let start = self.at;
+        while self.peek().map_or(false, |c| c.is_ascii_digit()) {
+            self.at += 1;
+        }
+        String::from_utf8_lossy(&self.rule[start..self.at])
+            .parse::<u32>()
+            .map_err(|_| "a number was expected".to_string())
+    }
+
+    // e.g. "GMT", or "<+0330>" for a name that isn't just letters
+    fn name(&mut self) -> Result<(), String> {
+        let start = self.at;
+        if self.eat(b'<') {
+            while self.peek().map_or(false, |c| c != b'>') {
+                self.at += 1;
+            }
+            if !self.eat(b'>') {
+                return Err("a quoted name isn't closed".to_string());
+            }
+        } else {
+            while self.peek().map_or(false, |c| c.is_ascii_alphabetic()) {
+                self.at += 1;
+            }
+        }
+        if self.at == start {
+            Err("a zone name was expected".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    // [+-]hh[:mm[:ss]] in seconds
+    fn time(&mut self) -> Result<i32, String> {
+        let sign = if self.eat(b'-') {
+            -1
+        } else {
+            self.eat(b'+');
+            1
+        };
+        let mut seconds = try!(self.number()) as i32 * 3600;
+        if self.eat(b':') {
+            seconds += try!(self.number()) as i32 * 60;
+            if self.eat(b':') {
+                seconds += try!(self.number()) as i32;
+            }
+        }
+        Ok(sign * seconds)
+    }
+
+    fn day(&mut self) -> Result<RuleDay, String> {
+        let day = if self.eat(b'J') {
+            match try!(self.number()) {
+                n if n >= 1 && n <= 365 => RuleDay::Julian(n),
+                n => return Err(format!("J{} isn't a day of the year", n)),
+            }
+        } else if self.eat(b'M') {
+            let month = try!(self.number());
+            let week = if self.eat(b'.') { try!(self.number()) } else { 0 };
+            let weekday = if self.eat(b'.') { try!(self.number()) } else { 7 };
+            if month < 1 || month > 12 || week < 1 || week > 5 || weekday > 6 {
+                return Err(format!("M{}.{}.{} isn't a day of a month", month, week, weekday));
+            }
+            RuleDay::MonthWeekDay(month, week, weekday)
+        } else {
+            match try!(self.number()) {
+                n if n <= 365 => RuleDay::ZeroBased(n),
+                n => return Err(format!("{} isn't a day of the year", n)),
+            }
+        };
+        Ok(day)
+    }
+
+    fn change(&mut self) -> Result<(RuleDay, i32), String> {
+        let day = try!(self.day());
+        let time = if self.eat(b'/') { try!(self.time()) } else { DEFAULT_RULE_TIME };
+        Ok((day, time))
+    }
+}
+
+// the offsets in the string are west of UTC, so they're negated
+pub fn parse_posix_rule(rule: &str) -> Result<PosixRule, String> {
+    let mut parser = RuleParser {
+        rule: rule.as_bytes(),
+        at: 0,
+    };
+    parse_rule(&mut parser).map_err(|e| format!("'{}' isn't a valid TZ rule: {}", rule, e))
+}
+
+fn parse_rule(parser: &mut RuleParser) -> Result<PosixRule, String> {
+    try!(parser.name());
+    let std_offset = -try!(parser.time());
+    if parser.peek().is_none() {
+        return Ok(PosixRule {
+            std_offset: std_offset,
+            dst: None,
+        });
+    }
+
+    try!(parser.name());
+    let offset = match parser.peek() {
+        Some(c) if c != b',' => -try!(parser.time()),
+        _ => std_offset + 3600,
+    };
+    // without rules, daylight saving is taken to follow the US's
+    let (start, end) = if parser.eat(b',') {
+        let start = try!(parser.change());
+        if !parser.eat(b',') {
+            return Err("the end of daylight saving was expected".to_string());
+        }
+        (start, try!(parser.change()))
+    } else {
+        ((RuleDay::MonthWeekDay(3, 2, 0), DEFAULT_RULE_TIME),
+         (RuleDay::MonthWeekDay(11, 1, 0), DEFAULT_RULE_TIME))
+    };
+    if parser.peek().is_some() {
+        return Err("there's more after the end of daylight saving".to_string());
+    }
+
+    Ok(PosixRule {
+        std_offset: std_offset,
+        dst: Some(DstRule {
+            offset: offset,
+            start: start,
+            end: end,
+        }),
+    })
+}
+
+// the timestamp of the day's midnight, as if it were in UTC
+fn day_start(year: i32, day: &RuleDay) -> i64 {
+    let leap = NaiveDate::from_ymd_opt(year, 2, 29).is_some();
+    let date = match *day {
+        RuleDay::Julian(n) => {
+            NaiveDate::from_yo(year, if leap && n >= 60 { n + 1 } else { n })
+        }
+        // day 365 is only in leap years, otherwise it's taken to be the last day
+        RuleDay::ZeroBased(n) => {
+            NaiveDate::from_yo_opt(year, n + 1).unwrap_or(NaiveDate::from_ymd(year, 12, 31))
+        }
+        RuleDay::MonthWeekDay(month, week, weekday) => {
+            let first = NaiveDate::from_ymd(year, month, 1).weekday().num_days_from_sunday();
+            let mut day = 1 + (weekday + 7 - first) % 7 + (week - 1) * 7;
+            while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+                day -= 7;
+            }
+            NaiveDate::from_ymd(year, month, day)
+        }
+    };
+    date.and_hms(0, 0, 0).timestamp()
+}
+
+impl PosixRule {
+    pub fn utc_offset(&self, timestamp: i64) -> i32 {
+        let dst = match self.dst {
+            Some(ref dst) => dst,
+            None => return self.std_offset,
+        };
+
+        let year = NaiveDateTime::from_timestamp(timestamp + self.std_offset as i64, 0).year();
+        // daylight saving starts in standard time, and ends in daylight saving time
+        let start = day_start(year, &dst.start.0) + (dst.start.1 - self.std_offset) as i64;
+        let end = day_start(year, &dst.end.0) + (dst.end.1 - dst.offset) as i64;
+        // in the southern hemisphere daylight saving runs over the new year
+        let in_dst = if start < end {
+            timestamp >= start && timestamp < end
+        } else {
+            timestamp < end || timestamp >= start
+        };
+
+        if in_dst { dst.offset } else { self.std_offset }
+    }
+}
@@ -0,0 +1,157 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use chrono::{TimeZone, UTC};
+
+fn at(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> i64 {
+    UTC.ymd(y, m, d).and_hms(h, min, s).timestamp()
+}
+
+// a version 2 TZif file, with a minimal version 1 part
+fn tzif(transitions: &[(i64, u8)], offsets: &[i32], footer: &str) -> Vec<u8> {
+    fn header(data: &mut Vec<u8>, timecnt: usize, typecnt: usize) {
+        data.extend(b"TZif2");
+        data.extend(&[0; 15]);
+        for count in &[0, 0, 0, timecnt, typecnt, 1] {
+            data.extend(&(*count as u32).to_be_bytes());
+        }
+    }
+
+    let mut data = vec![];
+    header(&mut data, 0, 1);
+    data.extend(&[0, 0, 0, 0, 0, 0, 0]);
+    header(&mut data, transitions.len(), offsets.len());
+    for &(time, _) in transitions {
+        data.extend(&time.to_be_bytes());
+    }
+    for &(_, index) in transitions {
+        data.push(index);
+    }
+    for offset in offsets {
+        data.extend(&offset.to_be_bytes());
+        data.extend(&[0, 0]);
+    }
+    data.push(0);
+    data.extend(format!("\n{}\n", footer).as_bytes());
+    data
+}
+
+#[test]
+fn parse_tzif_good() {
+    let data = tzif(&[(1000, 1), (2000, 0)], &[3600, 7200], "CET-1CEST,M3.5.0,M10.5.0/3");
+    let zone = parse_tzif(&data).unwrap();
+    assert_eq!(zone.transitions, vec![(1000, 1), (2000, 0)]);
+    assert_eq!(zone.offsets, vec![3600, 7200]);
+    assert_eq!(zone.rule.as_ref().map(|r| r.std_offset), Some(3600));
+
+    assert_eq!(zone.utc_offset(0), 3600);
+    assert_eq!(zone.utc_offset(1000), 7200);
+    assert_eq!(zone.utc_offset(1999), 7200);
+    // after the last transition the rule's followed
+    assert_eq!(zone.utc_offset(at(2017, 7, 15, 12, 0, 0)), 7200);
+    assert_eq!(zone.utc_offset(at(2017, 1, 15, 12, 0, 0)), 3600);
+
+    let fixed = parse_tzif(&tzif(&[], &[-18000], "")).unwrap();
+    assert_eq!(fixed.rule, None);
+    assert_eq!(fixed.utc_offset(at(2017, 7, 15, 12, 0, 0)), -18000);
+}
+
+#[test]
+fn parse_tzif_bad() {
+    assert_eq!(parse_tzif(b"not a zone"), Err("it isn't a TZif file".to_string()));
+    let data = tzif(&[(1000, 0)], &[3600], "");
+    assert_eq!(parse_tzif(&data[..data.len() - 8]), Err("it's truncated".to_string()));
+    assert_eq!(parse_tzif(&tzif(&[(1000, 2)], &[3600], "")),
+               Err("a transition has the unknown type 2".to_string()));
+    assert!(parse_tzif(&tzif(&[], &[3600], "CET-1CEST,M3.5.0")).is_err());
+}
+
+#[test]
+fn parse_posix_rule_good() {
+    assert_eq!(parse_posix_rule("GMT0BST,M3.5.0/1,M10.5.0"),
+               Ok(PosixRule {
+                   std_offset: 0,
+                   dst: Some(DstRule {
+                       offset: 3600,
+                       start: (RuleDay::MonthWeekDay(3, 5, 0), 3600),
+                       end: (RuleDay::MonthWeekDay(10, 5, 0), 7200),
+                   }),
+               }));
+    assert_eq!(parse_posix_rule("<+0330>-3:30"),
+               Ok(PosixRule {
+                   std_offset: 12600,
+                   dst: None,
+               }));
+    assert_eq!(parse_posix_rule("EST5EDT4,J60/-1,300/26:30"),
+               Ok(PosixRule {
+                   std_offset: -18000,
+                   dst: Some(DstRule {
+                       offset: -14400,
+                       start: (RuleDay::Julian(60), -3600),
+                       end: (RuleDay::ZeroBased(300), 95400),
+                   }),
+               }));
+}
+
+#[test]
+fn parse_posix_rule_bad() {
+    assert_eq!(parse_posix_rule("EST5EDT,M3.2.0"),
+               Err("'EST5EDT,M3.2.0' isn't a valid TZ rule: the end of daylight saving was \
+                    expected"
+                   .to_string()));
+    assert_eq!(parse_posix_rule("5"),
+               Err("'5' isn't a valid TZ rule: a zone name was expected".to_string()));
+    assert!(parse_posix_rule("EST5EDT,M13.1.0,M11.1.0").is_err());
+    assert!(parse_posix_rule("EST5EDT,J366,M11.1.0").is_err());
+    assert!(parse_posix_rule("EST").is_err());
+}
+
+#[test]
+fn posix_rule_offsets_change_at_the_right_time() {
+    // daylight saving starts at 2am standard time and ends at 2am daylight time
+    let new_york = parse_posix_rule("EST5EDT,M3.2.0,M11.1.0").unwrap();
+    assert_eq!(new_york.utc_offset(at(2017, 3, 12, 6, 59, 59)), -18000);
+    assert_eq!(new_york.utc_offset(at(2017, 3, 12, 7, 0, 0)), -14400);
+    assert_eq!(new_york.utc_offset(at(2017, 11, 5, 5, 59, 59)), -14400);
+    assert_eq!(new_york.utc_offset(at(2017, 11, 5, 6, 0, 0)), -18000);
+
+    // the last Sunday of March is taken when there are only four
+    let london = parse_posix_rule("GMT0BST,M3.5.0/1,M10.5.0").unwrap();
+    assert_eq!(london.utc_offset(at(2017, 3, 26, 0, 59, 59)), 0);
+    assert_eq!(london.utc_offset(at(2017, 3, 26, 1, 0, 0)), 3600);
+
+    // daylight saving runs over the new year in the southern hemisphere
+    let sydney = parse_posix_rule("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+    assert_eq!(sydney.utc_offset(at(2017, 1, 15, 12, 0, 0)), 39600);
+    assert_eq!(sydney.utc_offset(at(2017, 7, 15, 12, 0, 0)), 36000);
+    assert_eq!(sydney.utc_offset(at(2017, 12, 31, 23, 0, 0)), 39600);
+
+    let utc = parse_posix_rule("UTC0").unwrap();
+    assert_eq!(utc.utc_offset(at(2017, 7, 15, 12, 0, 0)), 0);
+}
+
+#[test]
+fn load_reads_the_zoneinfo_database() {
+    let london = load("Europe/London").unwrap();
+    assert_eq!(london.utc_offset(at(2017, 1, 15, 12, 0, 0)), 0);
+    assert_eq!(london.utc_offset(at(2017, 7, 15, 12, 0, 0)), 3600);
+    assert_eq!(london.utc_offset(at(2050, 7, 15, 12, 0, 0)), 3600);
+
+    assert_eq!(load("Mars/Olympus_Mons"),
+               Err("the timezone 'Mars/Olympus_Mons' is not known".to_string()));
+    assert_eq!(load("../../etc/passwd"),
+               Err("the timezone '../../etc/passwd' is not known".to_string()));
+    assert!(load("Europe").is_err());
+}
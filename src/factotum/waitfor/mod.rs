@@ -0,0 +1,170 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::preflight::{OsEnvironment, PreflightEnvironment};
+use factotum::retry;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+pub const EXECUTOR: &'static str = "wait";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum WaitCondition {
+    Port(String),
+    Url(String),
+    File(String),
+    S3Key(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaitOptions {
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        WaitOptions {
+            poll_interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+// "tcp://host:port", "http(s)://...", "s3://bucket/key" or a file path (optionally "file://...")
+pub fn parse_wait_condition(target: &str) -> Result<WaitCondition, String> {
+    let target = target.trim();
+    if target.starts_with("tcp://") {
+        let address = &target["tcp://".len()..];
+        match address.rfind(':').map(|i| (&address[..i], &address[i + 1..])) {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                Ok(WaitCondition::Port(address.to_string()))
+            }
+            _ => Err(format!("'{}' must be given as tcp://<host>:<port>", target)),
+        }
+    } else if target.starts_with("http://") || target.starts_with("https://") {
+        Ok(WaitCondition::Url(target.to_string()))
+    } else if target.starts_with("s3://") {
+        let path = &target["s3://".len()..];
+        match path.find('/') {
+            Some(i) if i > 0 && i < path.len() - 1 => Ok(WaitCondition::S3Key(target.to_string())),
+            _ => Err(format!("'{}' must be given as s3://<bucket>/<key>", target)),
+        }
+    } else if target.starts_with("file://") {
+        Ok(WaitCondition::File(target["file://".len()..].to_string()))
+    } else if target.is_empty() {
+        Err("there is nothing to wait for".to_string())
+    } else {
+        Ok(WaitCondition::File(target.to_string()))
+    }
+}
+
+// a wait task's arguments are its options, e.g. [ "--poll-interval=30s", "--timeout=2h" ]
+pub fn parse_wait_options(args: &Vec<String>) -> Result<WaitOptions, String> {
+    let mut options = WaitOptions::default();
+
+    for arg in args.iter() {
+        let mut split = arg.splitn(2, '=');
+        let name = split.next().unwrap_or("");
+        let value = split.next().unwrap_or("");
+        match name {
+            "--poll-interval" => options.poll_interval = try!(retry::parse_duration(value)),
+            "--timeout" => options.timeout = try!(retry::parse_duration(value)),
+            _ => {
+                return Err(format!("'{}' isn't a wait option (expected --poll-interval=<duration> \
+                                    or --timeout=<duration>)",
+                                   arg))
+            }
+        }
+    }
+
+    if options.poll_interval.as_secs() == 0 {
+        return Err("the poll interval must be at least 1s".to_string());
+    }
+
+    Ok(options)
+}
+
+pub fn describe(condition: &WaitCondition) -> String {
+    match *condition {
+        WaitCondition::Port(ref address) => format!("port {} to accept connections", address),
+        WaitCondition::Url(ref url) => format!("{} to return 200", url),
+        WaitCondition::File(ref path) => format!("the file '{}' to exist", path),
+        WaitCondition::S3Key(ref key) => format!("the key '{}' to exist", key),
+    }
+}
+
+pub fn check_condition(condition: &WaitCondition) -> Result<bool, String> {
+    match *condition {
+        WaitCondition::Port(ref address) => {
+            let addrs = match address.to_socket_addrs() {
+                Ok(addrs) => addrs.collect::<Vec<_>>(),
+                // the host may not be resolvable until it's up
+                Err(_) => return Ok(false),
+            };
+            Ok(addrs.iter()
+                .any(|a| TcpStream::connect_timeout(a, Duration::from_secs(5)).is_ok()))
+        }
+        WaitCondition::Url(ref url) => Ok(url_returns_ok(url)),
+        WaitCondition::File(ref path) => OsEnvironment.artifact_exists(path),
+        WaitCondition::S3Key(ref key) => OsEnvironment.artifact_exists(key),
+    }
+}
+
+fn url_returns_ok(url: &str) -> bool {
+    use hyper::Client;
+    use hyper::net::HttpsConnector;
+    use hyper_native_tls::NativeTlsClient;
+    use hyper::status;
+
+    let ssl = match NativeTlsClient::new() {
+        Ok(ssl) => ssl,
+        Err(_) => return false,
+    };
+    let mut client = Client::with_connector(HttpsConnector::new(ssl));
+    client.set_read_timeout(Some(Duration::from_secs(30)));
+
+    match client.get(url).send() {
+        Ok(res) => res.status == status::StatusCode::Ok,
+        Err(_) => false,
+    }
+}
+
+// checks the condition every poll interval until it's met, returning how many checks it took
+pub fn wait_for<C, S>(condition: &WaitCondition,
+                      options: &WaitOptions,
+                      check: C,
+                      sleep: S)
+                      -> Result<u64, String>
+    where C: Fn(&WaitCondition) -> Result<bool, String>,
+          S: Fn(Duration)
+{
+    let max_checks = options.timeout.as_secs() / options.poll_interval.as_secs() + 1;
+
+    for attempt in 1..max_checks + 1 {
+        if try!(check(condition)) {
+            return Ok(attempt);
+        }
+        if attempt < max_checks {
+            sleep(options.poll_interval);
+        }
+    }
+
+    Err(format!("timed out after {}s waiting for {}",
+                options.timeout.as_secs(),
+                describe(condition)))
+}
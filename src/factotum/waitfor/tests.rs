@@ -0,0 +1,125 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use std::cell::Cell;
+use std::net::TcpListener;
+
+#[test]
+fn parse_wait_condition_good() {
+    assert_eq!(parse_wait_condition("tcp://db.internal:5432"),
+               Ok(WaitCondition::Port("db.internal:5432".to_string())));
+    assert_eq!(parse_wait_condition("https://example.com/ready"),
+               Ok(WaitCondition::Url("https://example.com/ready".to_string())));
+    assert_eq!(parse_wait_condition("s3://bucket/data/_SUCCESS"),
+               Ok(WaitCondition::S3Key("s3://bucket/data/_SUCCESS".to_string())));
+    assert_eq!(parse_wait_condition("file:///data/ready"),
+               Ok(WaitCondition::File("/data/ready".to_string())));
+    assert_eq!(parse_wait_condition("./ready"),
+               Ok(WaitCondition::File("./ready".to_string())));
+}
+
+#[test]
+fn parse_wait_condition_bad() {
+    assert_eq!(parse_wait_condition("tcp://db.internal"),
+               Err("'tcp://db.internal' must be given as tcp://<host>:<port>".to_string()));
+    assert_eq!(parse_wait_condition("tcp://:80"),
+               Err("'tcp://:80' must be given as tcp://<host>:<port>".to_string()));
+    assert_eq!(parse_wait_condition("s3://bucket/"),
+               Err("'s3://bucket/' must be given as s3://<bucket>/<key>".to_string()));
+    assert_eq!(parse_wait_condition(" "),
+               Err("there is nothing to wait for".to_string()));
+}
+
+#[test]
+fn parse_wait_options_good_and_bad() {
+    assert_eq!(parse_wait_options(&vec![]), Ok(WaitOptions::default()));
+    assert_eq!(parse_wait_options(&vec!["--poll-interval=30s".to_string(),
+                                        "--timeout=2h".to_string()]),
+               Ok(WaitOptions {
+                   poll_interval: Duration::from_secs(30),
+                   timeout: Duration::from_secs(2 * 60 * 60),
+               }));
+    assert_eq!(parse_wait_options(&vec!["--every=30s".to_string()]),
+               Err("'--every=30s' isn't a wait option (expected --poll-interval=<duration> or \
+                    --timeout=<duration>)"
+                   .to_string()));
+    assert_eq!(parse_wait_options(&vec!["--poll-interval=0s".to_string()]),
+               Err("the poll interval must be at least 1s".to_string()));
+    assert!(parse_wait_options(&vec!["--timeout=soon".to_string()]).is_err());
+}
+
+#[test]
+fn wait_for_polls_until_met() {
+    let checks = Cell::new(0);
+    let slept = Cell::new(0);
+    let options = WaitOptions {
+        poll_interval: Duration::from_secs(10),
+        timeout: Duration::from_secs(60),
+    };
+
+    let result = wait_for(&WaitCondition::File("ready".to_string()),
+                          &options,
+                          |_| {
+                              checks.set(checks.get() + 1);
+                              Ok(checks.get() == 3)
+                          },
+                          |d| slept.set(slept.get() + d.as_secs()));
+
+    assert_eq!(result, Ok(3));
+    assert_eq!(slept.get(), 20);
+}
+
+#[test]
+fn wait_for_times_out() {
+    let checks = Cell::new(0);
+    let options = WaitOptions {
+        poll_interval: Duration::from_secs(10),
+        timeout: Duration::from_secs(30),
+    };
+
+    let result = wait_for(&WaitCondition::Port("localhost:1".to_string()),
+                          &options,
+                          |_| {
+                              checks.set(checks.get() + 1);
+                              Ok(false)
+                          },
+                          |_| {});
+
+    assert_eq!(result,
+               Err("timed out after 30s waiting for port localhost:1 to accept connections"
+                   .to_string()));
+    assert_eq!(checks.get(), 4);
+
+    let failing = wait_for(&WaitCondition::S3Key("s3://bucket/key".to_string()),
+                           &options,
+                           |_| Err("couldn't run the aws cli".to_string()),
+                           |_| {});
+    assert_eq!(failing, Err("couldn't run the aws cli".to_string()));
+}
+
+#[test]
+fn check_condition_port_and_file() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    assert_eq!(check_condition(&WaitCondition::Port(address.clone())), Ok(true));
+    drop(listener);
+    assert_eq!(check_condition(&WaitCondition::Port(address)), Ok(false));
+
+    assert_eq!(check_condition(&WaitCondition::File("./tests/resources/example_ok.factfile"
+                   .to_string())),
+               Ok(true));
+    assert_eq!(check_condition(&WaitCondition::File("./tests/resources/nope".to_string())),
+               Ok(false));
+}
@@ -21,7 +21,75 @@ use crypto::digest::Digest;
 use crypto::sha2::Sha256;
 use uuid::Uuid;
 use rustc_serialize::base64::{ToBase64, MIME};
-use std::collections::HashMap;
+use rustc_serialize::json::{Json, ToJson};
+use std::collections::{BTreeMap, HashMap};
+use std::env::consts;
+use std::path::Path;
+use std::process::{self, Command, Stdio};
+
+// where the job runs and who ran it, so events from many hosts can be told apart
+#[derive(Clone, Debug, PartialEq)]
+pub struct Host {
+    pub hostname: Option<String>,
+    pub os: String,
+    pub arch: String,
+    pub pid: u32,
+    pub user: Option<String>,
+    pub factfile_commit: Option<String>,
+}
+
+impl Host {
+    pub fn new(hostname: Option<String>) -> Self {
+        Host {
+            hostname: hostname,
+            os: consts::OS.to_string(),
+            arch: consts::ARCH.to_string(),
+            pid: process::id(),
+            user: None,
+            factfile_commit: None,
+        }
+    }
+}
+
+impl ToJson for Host {
+    fn to_json(&self) -> Json {
+        let mut d = BTreeMap::new();
+        if let Some(ref hostname) = self.hostname {
+            d.insert("hostname".to_string(), hostname.to_json());
+        }
+        d.insert("os".to_string(), self.os.to_json());
+        d.insert("arch".to_string(), self.arch.to_json());
+        d.insert("pid".to_string(), self.pid.to_json());
+        if let Some(ref user) = self.user {
+            d.insert("user".to_string(), user.to_json());
+        }
+        if let Some(ref commit) = self.factfile_commit {
+            d.insert("factfileCommit".to_string(), commit.to_json());
+        }
+        Json::Object(d)
+    }
+}
+
+// the commit checked out in the git repository holding the factfile, if it's in one
+pub fn factfile_commit(factfile: &Path) -> Option<String> {
+    let dir = match factfile.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(&["rev-parse", "HEAD"])
+        .stderr(Stdio::null())
+        .output();
+    match output {
+        Ok(ref out) if out.status.success() => {
+            let commit = String::from_utf8_lossy(&out.stdout).trim().to_string();
+            if commit.is_empty() { None } else { Some(commit) }
+        }
+        _ => None,
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct JobContext {
@@ -32,10 +100,27 @@ pub struct JobContext {
     pub factotum_version: String,
     pub start_time: DateTime<UTC>,
     pub tags: HashMap<String,String>,
+    // which run of the job this is, when failed jobs are retried
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub host: Host,
 }
 
 impl JobContext {
+    #[cfg(test)]
     pub fn new<S: Into<String>>(job_name: S, factfile: &str, tags:Option<HashMap<String,String>>) -> Self {
+        JobContext::for_run(job_name, factfile, tags, JobContext::new_run_reference())
+    }
+
+    // the run reference is made ahead of the context when something needs it before the
+    // factfile is parsed, such as the run's workspace
+    pub fn new_run_reference() -> String {
+        let mut run_digest = Sha256::new();
+        run_digest.input_str(&format!("{}", Uuid::new_v4()));
+        run_digest.result_str()
+    }
+
+    pub fn for_run<S: Into<String>>(job_name: S, factfile: &str, tags:Option<HashMap<String,String>>, run_reference: String) -> Self {
         let ff = factfile;
         
         let mut job_digest = Sha256::new();
@@ -52,10 +137,6 @@ impl JobContext {
 
         let job_ref = job_digest.result_str();
 
-        let mut run_digest = Sha256::new();
-        run_digest.input_str(&format!("{}", Uuid::new_v4()));
-        let run_ref = run_digest.result_str();
-
         let mut config = MIME;
         config.line_length = None;
         let b64_ff = ff.as_bytes().to_base64(config);
@@ -69,11 +150,36 @@ impl JobContext {
         JobContext {
             job_name: job_name.into(),
             job_reference: job_ref,
-            run_reference: run_ref,
+            run_reference: run_reference,
             factfile: b64_ff,
             factotum_version: env!("CARGO_PKG_VERSION").to_string(),
             start_time: UTC::now(),
             tags: job_tags,
+            attempt: 1,
+            max_attempts: 1,
+            host: Host::new(None),
         }
     }
+
+    pub fn on_attempt(self, attempt: u32, max_attempts: u32) -> Self {
+        JobContext {
+            attempt: attempt,
+            max_attempts: max_attempts,
+            ..self
+        }
+    }
+
+    pub fn on_host(self, host: Host) -> Self {
+        JobContext { host: host, ..self }
+    }
+
+    // attached to run results, alerts and heartbeats
+    pub fn context_json(&self) -> Json {
+        let mut context = match self.host.to_json() {
+            Json::Object(d) => d,
+            _ => unreachable!(),
+        };
+        context.insert("factotumVersion".to_string(), self.factotum_version.to_json());
+        Json::Object(context)
+    }
 }
@@ -19,6 +19,8 @@ use rustc_serialize::base64::{ToBase64, MIME};
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
 use std::collections::HashMap;
+use std::env;
+use std::fs;
 
 #[test]
 fn new_sets_name() {
@@ -63,6 +65,14 @@ fn new_sets_run_ref_to_random_hash() {
     }
 }
 
+#[test]
+fn for_run_keeps_the_run_ref() {
+    let run_reference = JobContext::new_run_reference();
+    let context = JobContext::for_run("hello", "{stuff}", None, run_reference.clone());
+    assert_eq!(context.run_reference, run_reference);
+    assert_eq!(run_reference.len(), 64);
+}
+
 #[test]
 fn factfile_is_b64_coded() {
     let job_name = "hello";
@@ -109,4 +119,44 @@ fn tags_are_used_in_job_hash() {
     let context = JobContext::new(job_name, factfile_sim, Some(tags.clone()));
 
     assert_eq!(context.job_reference, expected);
-}
\ No newline at end of file
+}
+#[test]
+fn new_is_a_single_attempt_on_this_host() {
+    let context = JobContext::new("hello", "{stuff}", None);
+    assert_eq!((context.attempt, context.max_attempts), (1, 1));
+    assert_eq!(context.host.hostname, None);
+    assert_eq!(context.host.pid, ::std::process::id());
+}
+
+#[test]
+fn attempts_and_host_are_recorded() {
+    let context = JobContext::new("hello", "{stuff}", None)
+        .on_attempt(2, 3)
+        .on_host(Host::new(Some("worker-1".to_string())));
+    assert_eq!((context.attempt, context.max_attempts), (2, 3));
+    assert_eq!(context.host.hostname, Some("worker-1".to_string()));
+}
+
+#[test]
+fn context_json_describes_the_host() {
+    let host = Host {
+        user: Some("jane".to_string()),
+        factfile_commit: Some("abc123".to_string()),
+        ..Host::new(Some("worker-1".to_string()))
+    };
+    let context = JobContext::new("hello", "{stuff}", None).on_host(host).context_json();
+    assert_eq!(context.find("hostname"), Some(&"worker-1".to_json()));
+    assert_eq!(context.find("user"), Some(&"jane".to_json()));
+    assert_eq!(context.find("factfileCommit"), Some(&"abc123".to_json()));
+    assert_eq!(context.find("factotumVersion"),
+               Some(&env!("CARGO_PKG_VERSION").to_json()));
+    assert_eq!(context.find("pid"), Some(&::std::process::id().to_json()));
+}
+
+#[test]
+fn factfiles_outside_a_repository_have_no_commit() {
+    let dir = env::temp_dir().join("factotum-jobcontext-no-repo");
+    fs::create_dir_all(&dir).unwrap();
+    assert_eq!(factfile_commit(&dir.join("job.factfile")), None);
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -16,19 +16,25 @@
 mod tests;
 
 static JOB_UPDATE_SCHEMA_NAME: &'static str = "iglu:com.snowplowanalytics.\
-                                               factotum/job_update/jsonschema/1-0-0";
+                                               factotum/job_update/jsonschema/1-0-1";
 static TASK_UPDATE_SCHEMA_NAME: &'static str = "iglu:com.snowplowanalytics.\
-                                               factotum/task_update/jsonschema/1-0-0";
+                                               factotum/task_update/jsonschema/1-0-1";
+static JOB_UPDATE_V2_SCHEMA_NAME: &'static str = "iglu:com.snowplowanalytics.\
+                                                  factotum/job_update/jsonschema/2-0-0";
+static TASK_UPDATE_V2_SCHEMA_NAME: &'static str = "iglu:com.snowplowanalytics.\
+                                                   factotum/task_update/jsonschema/2-0-0";
 
 use factotum::executor::{ExecutionState, ExecutionUpdate, TaskSnapshot,
                          Transition as ExecutorTransition};
-use super::jobcontext::JobContext;
+use super::jobcontext::{Host, JobContext};
 use chrono::{self, UTC};
 use std::collections::BTreeMap;
 use rustc_serialize::Encodable;
 use rustc_serialize;
 use rustc_serialize::json::{self, ToJson, Json};
 use factotum::executor::task_list::State;
+use factotum::manifest::Artifact;
+use factotum::audit;
 use std::collections::HashMap;
 
 #[derive(RustcDecodable, RustcEncodable, Debug, PartialEq)]
@@ -62,6 +68,10 @@ pub struct TaskUpdate {
     stderr: Option<String>,
     returnCode: Option<i32>,
     errorMessage: Option<String>,
+    maxRssKb: Option<u64>,
+    userCpu: Option<String>,
+    systemCpu: Option<String>,
+    artifacts: Option<Vec<Artifact>>,
 }
 
 impl Encodable for TaskUpdate {
@@ -112,6 +122,27 @@ impl ToJson for TaskUpdate {
             None => {}
         }
 
+        match self.maxRssKb {
+            Some(ref value) => {
+                d.insert("maxRssKb".to_string(), value.to_json());
+            }
+            None => {}
+        }
+
+        match self.userCpu {
+            Some(ref value) => {
+                d.insert("userCpu".to_string(), value.to_json());
+            }
+            None => {}
+        }
+
+        match self.systemCpu {
+            Some(ref value) => {
+                d.insert("systemCpu".to_string(), value.to_json());
+            }
+            None => {}
+        }
+
         match self.started {
             Some(ref value) => {
                 d.insert("started".to_string(), value.to_json());
@@ -119,6 +150,13 @@ impl ToJson for TaskUpdate {
             None => {}
         }
 
+        match self.artifacts {
+            Some(ref value) => {
+                d.insert("artifacts".to_string(), value.to_json());
+            }
+            None => {}
+        }
+
         d.insert("taskName".to_string(), self.taskName.to_json());
         d.insert("state".to_string(),
                  Json::from_str(&json::encode(&self.state).unwrap()).unwrap());
@@ -288,6 +326,9 @@ impl JobUpdate {
 
         tasks.iter()
             .map(|task| {
+                let resource_usage = task.run_result
+                    .as_ref()
+                    .and_then(|r| r.resource_usage.as_ref());
                 TaskUpdate {
                     taskName: task.name.clone(),
                     state: match task.state {
@@ -335,13 +376,25 @@ impl JobUpdate {
                         (&State::Skipped(ref reason), _) => Some(reason.clone()),
                         (&State::Failed(ref reason), &Some(ref result)) => {
                             if let Some(ref execution_error) = result.task_execution_error {
-                                Some(execution_error.clone())
+                                Some(execution_error.to_string())
                             } else {
                                 Some(reason.clone())
                             }                            
                         },
                         _ => None   
                     },
+                    maxRssKb: resource_usage.map(|u| u.max_rss_kb),
+                    userCpu: resource_usage.map(|u| {
+                        ChronoDuration::from_std(u.user_time).unwrap().to_string()
+                    }),
+                    systemCpu: resource_usage.map(|u| {
+                        ChronoDuration::from_std(u.system_time).unwrap().to_string()
+                    }),
+                    artifacts: if task.artifacts.is_empty() {
+                        None
+                    } else {
+                        Some(task.artifacts.clone())
+                    },
                 }
             })
             .collect()
@@ -398,6 +451,97 @@ impl ToJson for JobUpdate {
     }
 }
 
+// the version 2 update adds the run's id, attempt and host, and when each task finished and
+// the command it ran, with the values of secret variables taken out
+#[derive(Debug)]
+pub struct JobUpdateV2 {
+    update: JobUpdate,
+    run_id: String,
+    attempt: u32,
+    max_attempts: u32,
+    host: Host,
+    task_finished: Vec<Option<String>>,
+    task_commands: Vec<String>,
+}
+
+impl JobUpdateV2 {
+    pub fn new(context: &JobContext,
+               execution_update: &ExecutionUpdate,
+               max_stdouterr_size: &usize,
+               secrets: &Vec<String>)
+               -> Self {
+        use chrono::duration::Duration as ChronoDuration;
+
+        let tasks = &execution_update.task_snapshot;
+        JobUpdateV2 {
+            update: JobUpdate::new(context, execution_update, max_stdouterr_size),
+            run_id: context.run_reference.clone(),
+            attempt: context.attempt,
+            max_attempts: context.max_attempts,
+            host: context.host.clone(),
+            task_finished: tasks.iter()
+                .map(|task| match (&task.run_started, &task.run_result) {
+                    (&Some(ref started), &Some(ref result)) => {
+                        let duration = ChronoDuration::from_std(result.duration).unwrap();
+                        Some(to_string_datetime(&(*started + duration)))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            task_commands: tasks.iter()
+                .map(|task| {
+                    let mut command = vec![task.task_spec.command.clone()];
+                    command.extend(task.task_spec.arguments.iter().cloned());
+                    audit::redact_values(&command.join(" "), secrets)
+                })
+                .collect(),
+        }
+    }
+
+    pub fn as_self_desc_json(&self) -> String {
+        let mut wrapped = BTreeMap::new();
+        wrapped.insert("schema".to_string(),
+                       match self.update.transition {
+                           Some(_) => JOB_UPDATE_V2_SCHEMA_NAME.to_json(),
+                           None => TASK_UPDATE_V2_SCHEMA_NAME.to_json(),
+                       });
+        wrapped.insert("data".to_string(), self.to_json());
+        Json::Object(wrapped).to_string()
+    }
+}
+
+impl ToJson for JobUpdateV2 {
+    fn to_json(&self) -> Json {
+        let mut d = match self.update.to_json() {
+            Json::Object(d) => d,
+            _ => unreachable!(),
+        };
+
+        d.insert("runId".into(), self.run_id.to_json());
+
+        let mut attempt = BTreeMap::new();
+        attempt.insert("number".to_string(), self.attempt.to_json());
+        attempt.insert("of".to_string(), self.max_attempts.to_json());
+        d.insert("attempt".into(), Json::Object(attempt));
+
+        d.insert("host".into(), self.host.to_json());
+
+        if let Some(&mut Json::Array(ref mut states)) = d.get_mut("taskStates") {
+            let extras = self.task_finished.iter().zip(self.task_commands.iter());
+            for (state, (finished, command)) in states.iter_mut().zip(extras) {
+                if let Json::Object(ref mut state) = *state {
+                    if let Some(ref finished) = *finished {
+                        state.insert("finished".to_string(), finished.to_json());
+                    }
+                    state.insert("command".to_string(), command.to_json());
+                }
+            }
+        }
+
+        Json::Object(d)
+    }
+}
+
 pub fn to_string_datetime(datetime: &chrono::DateTime<UTC>) -> String {
     datetime.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string()
 }
@@ -17,22 +17,23 @@ use factotum::parser::schemavalidator;
 use factotum::executor::{ExecutionState, ExecutionUpdate, Transition,
                          JobTransition as ExecutorJobTransition,
                          TaskTransition as ExecutorTaskTransition};
-use factotum::webhook::jobcontext::JobContext;
+use factotum::webhook::jobcontext::{Host, JobContext};
 use chrono::UTC;
 use factotum::tests::make_task;
-use factotum::factfile::Factfile;
+use factotum::factfile::{Factfile, Task as FactfileTask};
 use factotum::executor::task_list::State;
 use factotum::executor::{get_task_execution_list, get_task_snapshot};
 use std::collections::HashMap;
 use factotum::executor::task_list::Task;
-use factotum::executor::execution_strategy::RunResult;
+use factotum::executor::execution_strategy::{RunResult, ResourceUsage};
+use factotum::errors::{ErrorKind, FactotumError};
 use chrono::Duration;
 
 
 #[test]
 fn to_json_valid_against_schema_job_transition() {
-    let schema = include_str!("../../../../tests/resources/job_update/job_transition_self_desc.\
-                               json");
+    let schema = include_str!("../../../../tests/resources/job_update/job_transition_self_desc_\
+                               1-0-1.json");
     let context = JobContext::new("hello", "world", None);
     let exec_update =
         ExecutionUpdate::new(ExecutionState::Finished,
@@ -52,16 +53,16 @@ fn to_json_valid_against_schema_job_transition() {
 
 #[test]
 fn to_json_valid_against_schema_task_transition_running_to_failed() {
-    let schema = include_str!("../../../../tests/resources/job_update/task_transition_self_desc.\
-                               json");
+    let schema = include_str!("../../../../tests/resources/job_update/task_transition_self_desc_\
+                               1-0-1.json");
 
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     let mut tasks = get_task_snapshot(&get_task_execution_list(&ff, None));
 
@@ -98,16 +99,16 @@ fn to_json_valid_against_schema_task_transition_running_to_failed() {
 
 #[test]
 fn to_json_valid_against_schema_task_transition_waiting_to_running() {
-    let schema = include_str!("../../../../tests/resources/job_update/task_transition_self_desc.\
-                               json");
+    let schema = include_str!("../../../../tests/resources/job_update/task_transition_self_desc_\
+                               1-0-1.json");
 
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     let mut tasks = get_task_snapshot(&get_task_execution_list(&ff, None));
 
@@ -137,6 +138,39 @@ fn to_json_valid_against_schema_task_transition_waiting_to_running() {
     }
 }
 
+// payloads that don't use the fields added since are still valid against the 1-0-0 schemas
+#[test]
+fn to_json_valid_against_1_0_0_schemas() {
+    let job_schema = include_str!("../../../../tests/resources/job_update/job_transition_self_desc.\
+                                   json");
+    let task_schema = include_str!("../../../../tests/resources/job_update/\
+                                    task_transition_self_desc.json");
+
+    let mut ff = Factfile::new("N/A", "test");
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    let tasks = get_task_snapshot(&get_task_execution_list(&ff, None));
+    let context = JobContext::new("hello", "world", None);
+    let max_stdouterr_size: usize = 10_000;
+
+    let started = ExecutorJobTransition::new(Some(ExecutionState::Started),
+                                             ExecutionState::Running);
+    let job_transition = ExecutionUpdate::new(ExecutionState::Running,
+                                              tasks.clone(),
+                                              Transition::Job(started));
+    let json_wrapped = JobUpdate::new(&context, &job_transition, &max_stdouterr_size)
+        .as_self_desc_json();
+    assert_eq!(schemavalidator::validate_schema(&json_wrapped, job_schema), Ok(()));
+
+    let transitions = vec![ExecutorTaskTransition::new("apple", State::Waiting, State::Running)];
+    let task_transition = ExecutionUpdate::new(ExecutionState::Running,
+                                               tasks,
+                                               Transition::Task(transitions));
+    let json_wrapped = JobUpdate::new(&context, &task_transition, &max_stdouterr_size)
+        .as_self_desc_json();
+    assert_eq!(schemavalidator::validate_schema(&json_wrapped, task_schema), Ok(()));
+}
+
 #[test]
 fn to_task_states_empty() {
     let empty = vec![];
@@ -173,12 +207,12 @@ fn headers_correct() {
 #[test]
 fn failed_headers_correct() {
     let mut ff = Factfile::new("N/A", "test");
-    ff.add_task_obj(&make_task("apple", &vec![]));
-    ff.add_task_obj(&make_task("turnip", &vec![]));
-    ff.add_task_obj(&make_task("orange", &vec!["apple"]));
-    ff.add_task_obj(&make_task("egg", &vec!["apple"]));
-    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"]));
-    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"]));
+    ff.add_task_obj(&make_task("apple", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("turnip", &vec![])).unwrap();
+    ff.add_task_obj(&make_task("orange", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("egg", &vec!["apple"])).unwrap();
+    ff.add_task_obj(&make_task("potato", &vec!["apple", "egg"])).unwrap();
+    ff.add_task_obj(&make_task("chicken", &vec!["potato", "orange"])).unwrap();
 
     let mut tasks = get_task_snapshot(&get_task_execution_list(&ff, None));
 
@@ -223,6 +257,10 @@ fn task_states_converted_no_run_data() {
         stderr: None,
         returnCode: None,
         errorMessage: None,
+        maxRssKb: None,
+        userCpu: None,
+        systemCpu: None,
+        artifacts: None,
     };
 
     assert!(job_update.taskStates.is_empty() == false);
@@ -240,7 +278,10 @@ fn task_states_converted_with_run_data() {
     example_tasks[0].run_started = Some(now.clone());
     example_tasks[0].run_result = Some(RunResult {
         return_code: -1,
-        task_execution_error: Some("some continue job stuff".to_string()),
+        resource_usage: None,
+        spilled_output: None,
+        task_execution_error: Some(FactotumError::new(ErrorKind::Execution,
+                                                      "some continue job stuff")),
         stderr: Some("banana".to_string()),
         stdout: Some("get".to_string()),
         duration: Duration::seconds(0).to_std().unwrap(),
@@ -250,11 +291,21 @@ fn task_states_converted_with_run_data() {
     example_tasks[1].run_started = Some(now.clone());
     example_tasks[1].run_result = Some(RunResult {
         return_code: 0,
+        resource_usage: Some(ResourceUsage {
+            max_rss_kb: 2048,
+            user_time: Duration::milliseconds(700).to_std().unwrap(),
+            system_time: Duration::milliseconds(200).to_std().unwrap(),
+        }),
+        spilled_output: None,
         task_execution_error: None,
         stderr: None,
         stdout: None,
         duration: Duration::seconds(1).to_std().unwrap(),
     });
+    example_tasks[1].artifacts = vec![Artifact {
+                                          path: "/data/out.csv".to_string(),
+                                          checksum: None,
+                                      }];
 
     let start_sample =
         ExecutionUpdate::new(ExecutionState::Started,
@@ -275,6 +326,10 @@ fn task_states_converted_with_run_data() {
                                    stderr: Some("banana".to_string()),
                                    returnCode: Some(-1),
                                    errorMessage: Some("some continue job stuff".to_string()),
+                                   maxRssKb: None,
+                                   userCpu: None,
+                                   systemCpu: None,
+                                   artifacts: None,
                                },
                                TaskUpdate {
                                    taskName: "toffee".to_string(),
@@ -285,6 +340,13 @@ fn task_states_converted_with_run_data() {
                                    stderr: None,
                                    returnCode: Some(0),
                                    errorMessage: None,
+                                   maxRssKb: Some(2048),
+                                   userCpu: Some(Duration::milliseconds(700).to_string()),
+                                   systemCpu: Some(Duration::milliseconds(200).to_string()),
+                                   artifacts: Some(vec![Artifact {
+                                                            path: "/data/out.csv".to_string(),
+                                                            checksum: None,
+                                                        }]),
                                }];
 
     assert!(job_update.taskStates.is_empty() == false);
@@ -292,6 +354,96 @@ fn task_states_converted_with_run_data() {
     assert_eq!(job_update.taskStates[1], expected_states[1]);
 }
 
+fn make_finished_task(name: &str, started: &chrono::DateTime<UTC>) -> Task<FactfileTask> {
+    let mut spec = make_task(name, &vec![]);
+    spec.command = "psql".to_string();
+    spec.arguments = vec!["--password".to_string(), "hunter2".to_string()];
+    let mut task = Task::new(name, spec);
+    task.state = State::Success;
+    task.run_started = Some(started.clone());
+    task.run_result = Some(RunResult {
+        return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
+        task_execution_error: None,
+        stderr: None,
+        stdout: None,
+        duration: Duration::seconds(90).to_std().unwrap(),
+    });
+    task
+}
+
+#[test]
+fn v2_job_transition_valid_against_schema() {
+    let schema = include_str!("../../../../tests/resources/job_update/job_transition_self_desc_\
+                               v2.json");
+    let context = JobContext::new("hello", "world", None)
+        .on_attempt(2, 3)
+        .on_host(Host {
+            user: Some("jane".to_string()),
+            factfile_commit: Some("abc123".to_string()),
+            ..Host::new(Some("worker-1".to_string()))
+        });
+    let exec_update =
+        ExecutionUpdate::new(ExecutionState::Finished,
+                             vec![make_finished_task("load", &UTC::now())],
+                             Transition::Job(ExecutorJobTransition::new(Some(ExecutionState::Running),
+                                                                   ExecutionState::Finished)));
+    let job_update = JobUpdateV2::new(&context, &exec_update, &10_000, &vec![]);
+    let json_wrapped = job_update.as_self_desc_json();
+    if let Err(msg) = schemavalidator::validate_schema(&json_wrapped, schema) {
+        panic!("Failed to parse job update: {}", msg);
+    }
+
+    let data = Json::from_str(&json_wrapped).unwrap().find("data").unwrap().clone();
+    assert_eq!(data.find("runId"), Some(&context.run_reference.to_json()));
+    assert_eq!(data.find_path(&["attempt", "number"]), Some(&Json::U64(2)));
+    assert_eq!(data.find_path(&["attempt", "of"]), Some(&Json::U64(3)));
+    assert_eq!(data.find_path(&["host", "hostname"]),
+               Some(&Json::String("worker-1".to_string())));
+}
+
+#[test]
+fn v2_task_transition_valid_against_schema() {
+    let schema = include_str!("../../../../tests/resources/job_update/task_transition_self_desc_\
+                               v2.json");
+    let context = JobContext::new("hello", "world", None);
+    let exec_update =
+        ExecutionUpdate::new(ExecutionState::Running,
+                             vec![make_finished_task("load", &UTC::now()),
+                                  Task::new("vacuum", make_task("vacuum", &vec![]))],
+                             Transition::Task(vec![ExecutorTaskTransition::new("load",
+                                                                               State::Running,
+                                                                               State::Success)]));
+    let json_wrapped = JobUpdateV2::new(&context, &exec_update, &10_000, &vec![])
+        .as_self_desc_json();
+    if let Err(msg) = schemavalidator::validate_schema(&json_wrapped, schema) {
+        panic!("Failed to parse task update: {}", msg);
+    }
+}
+
+#[test]
+fn v2_tasks_have_finish_times_and_redacted_commands() {
+    use chrono::TimeZone;
+    let started = UTC.ymd(2021, 7, 8).and_hms_milli(9, 10, 11, 12);
+    let context = JobContext::new("hello", "world", None);
+    let exec_update =
+        ExecutionUpdate::new(ExecutionState::Running,
+                             vec![make_finished_task("load", &started),
+                                  Task::new("vacuum", make_task("vacuum", &vec![]))],
+                             Transition::Job(ExecutorJobTransition::new(None,
+                                                                        ExecutionState::Running)));
+    let data = JobUpdateV2::new(&context, &exec_update, &10_000, &vec!["hunter2".to_string()])
+        .to_json();
+
+    let states = data.find("taskStates").unwrap().as_array().unwrap();
+    assert_eq!(states[0].find("finished"),
+               Some(&Json::String("2021-07-08T09:11:41.012Z".to_string())));
+    assert_eq!(states[0].find("command"),
+               Some(&Json::String("psql --password <redacted>".to_string())));
+    assert_eq!(states[1].find("finished"), None);
+}
+
 #[test]
 fn to_string_datetime_good() {
     use chrono::TimeZone;
@@ -329,6 +481,8 @@ fn big_task_stdout_trimmed() {
     example_tasks[0].run_started = Some(now.clone());
     example_tasks[0].run_result = Some(RunResult {
         return_code: -1,
+        resource_usage: None,
+        spilled_output: None,
         task_execution_error: None,
         stderr: None,
         stdout: Some(format!("{}tail", make_n_char_string(20000))), // too long
@@ -339,6 +493,8 @@ fn big_task_stdout_trimmed() {
     example_tasks[1].run_started = Some(now.clone());
     example_tasks[1].run_result = Some(RunResult {
         return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
         task_execution_error: None,
         stderr: None,
         stdout: Some(format!("{}tail", make_n_char_string(max_len-"tail".len()))), // just fits
@@ -388,6 +544,8 @@ fn big_task_stderr_trimmed() {
     example_tasks[0].run_started = Some(now.clone());
     example_tasks[0].run_result = Some(RunResult {
         return_code: -1,
+        resource_usage: None,
+        spilled_output: None,
         task_execution_error: None,
         stderr: Some(format!("{}tail", make_n_char_string(20000))), // too long,
         stdout: None,
@@ -398,6 +556,8 @@ fn big_task_stderr_trimmed() {
     example_tasks[1].run_started = Some(now.clone());
     example_tasks[1].run_result = Some(RunResult {
         return_code: 0,
+        resource_usage: None,
+        spilled_output: None,
         task_execution_error: None,
         stderr: Some(format!("{}tail", make_n_char_string(max_len-"tail".len()))),
         stdout: None, // just fits
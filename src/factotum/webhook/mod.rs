@@ -13,7 +13,7 @@
 //
 
 pub mod jobcontext;
-mod jobupdate;
+pub mod jobupdate;
 #[cfg(test)]
 mod tests;
 
@@ -25,10 +25,30 @@ use factotum::executor::{ExecutionState, ExecutionUpdate};
 use std::time::Duration;
 use rand;
 use factotum::webhook::jobcontext::JobContext;
-use std::collections::HashMap;
 
 const MAX_RETRIES: usize = 3;
 
+// the payload schema sent to the webhook, version 1 unless asked for otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WebhookVersion {
+    V1,
+    V2,
+}
+
+impl Default for WebhookVersion {
+    fn default() -> Self {
+        WebhookVersion::V1
+    }
+}
+
+pub fn parse_webhook_version(version: &str) -> Result<WebhookVersion, String> {
+    match version.trim() {
+        "1" => Ok(WebhookVersion::V1),
+        "2" => Ok(WebhookVersion::V2),
+        other => Err(format!("'{}' isn't a webhook version, it must be 1 or 2", other)),
+    }
+}
+
 pub fn backoff_rand_1_minute() -> Duration {
     let max_duration_millis = 60 * 1000;
     let random_ms = rand::random::<u64>();
@@ -86,6 +106,9 @@ pub struct Webhook {
     pub endpoint: String,
     job_context: JobContext,
     pub max_stdouterr_size: usize,
+    pub version: WebhookVersion,
+    // values taken out of the commands sent in version 2 updates
+    secrets: Vec<String>,
 }
 
 impl Webhook {
@@ -121,11 +144,7 @@ impl Webhook {
         }
     }
 
-    pub fn new<S: Into<String>>(factfile_job_name: S, factfile_json: S, endpoint: S, job_tags:Option<HashMap<String,String>>, max_stdouterr_size:Option<usize>) -> Self {
-        let ff_name: String = factfile_job_name.into();
-        let ff_json: String = factfile_json.into();
-        let jc = jobcontext::JobContext::new(ff_name.clone(), &ff_json, job_tags);
-
+    pub fn new<S: Into<String>>(job_context: JobContext, factfile_json: S, endpoint: S, max_stdouterr_size:Option<usize>) -> Self {
         let max_stdouterr_size_bytes: usize = if let Some(max_bytes) = max_stdouterr_size {
             max_bytes
         } else {
@@ -133,11 +152,21 @@ impl Webhook {
         };
 
         Webhook {
-            job_context: jc,
-            factfile_job_name: ff_name,
-            factfile_json: ff_json,
+            factfile_job_name: job_context.job_name.clone(),
+            job_context: job_context,
+            factfile_json: factfile_json.into(),
             endpoint: endpoint.into(),
             max_stdouterr_size: max_stdouterr_size_bytes,
+            version: WebhookVersion::V1,
+            secrets: vec![],
+        }
+    }
+
+    pub fn with_version(self, version: WebhookVersion, secrets: Vec<String>) -> Self {
+        Webhook {
+            version: version,
+            secrets: secrets,
+            ..self
         }
     }
 
@@ -153,6 +182,8 @@ impl Webhook {
         let endpoint = self.endpoint.clone();
         let job_context = self.job_context.clone();
         let max_stdouterr_size = self.max_stdouterr_size.clone();
+        let version = self.version;
+        let secrets = self.secrets.clone();
 
         thread::spawn(move || {
 
@@ -171,8 +202,19 @@ impl Webhook {
                     done = true;
                 }
 
-                let job_update = jobupdate::JobUpdate::new(&job_context, &message, &max_stdouterr_size);
-                let json_post_data = job_update.as_self_desc_json();
+                let json_post_data = match version {
+                    WebhookVersion::V1 => {
+                        jobupdate::JobUpdate::new(&job_context, &message, &max_stdouterr_size)
+                            .as_self_desc_json()
+                    }
+                    WebhookVersion::V2 => {
+                        jobupdate::JobUpdateV2::new(&job_context,
+                                                    &message,
+                                                    &max_stdouterr_size,
+                                                    &secrets)
+                            .as_self_desc_json()
+                    }
+                };
 
                 for _ in 0..MAX_RETRIES {
                     let mut good = false;
@@ -16,6 +16,7 @@ use super::*;
 use std::sync::mpsc;
 use factotum::executor::{ExecutionState, TaskSnapshot, JobTransition, Transition, ExecutionUpdate};
 use std::time::Duration;
+use factotum::webhook::jobcontext::JobContext;
 
 fn mock_200_ok(_: &str, _: &str) -> Result<u32, (u32, String)> {
     Ok(200)
@@ -25,6 +26,14 @@ fn mock_500_err(_: &str, _: &str) -> Result<u32, (u32, String)> {
     Err((500, "Internal Server Error".to_string()))
 }
 
+fn mock_v2_only(_: &str, data: &str) -> Result<u32, (u32, String)> {
+    if data.contains("jsonschema/2-0-0") {
+        Ok(200)
+    } else {
+        Err((400, "Bad Request".to_string()))
+    }
+}
+
 fn zero_backoff() -> Duration {
     Duration::from_secs(0)
 }
@@ -46,7 +55,10 @@ fn backoff_retry_1min_good() {
 
 #[test]
 fn webhook_object_constructed_good() {
-    let wh = Webhook::new("job_name", "hello", "https://goodplace.com", None, None);
+    let wh = Webhook::new(JobContext::new("job_name", "hello", None),
+                          "hello",
+                          "https://goodplace.com",
+                          None);
     assert_eq!("hello", wh.factfile_json);
     assert_eq!("https://goodplace.com", wh.endpoint);
     assert_eq!("job_name", wh.factfile_job_name);
@@ -54,7 +66,10 @@ fn webhook_object_constructed_good() {
 
 #[test]
 fn finish_stops_thread() {
-    let mut wh = Webhook::new("job_name", "hello", "https://goodplace.com", None, None);
+    let mut wh = Webhook::new(JobContext::new("job_name", "hello", None),
+                          "hello",
+                          "https://goodplace.com",
+                          None);
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
     let jh = wh.connect_webhook(rx, mock_200_ok, zero_backoff);
     let sent_state =
@@ -68,6 +83,33 @@ fn finish_stops_thread() {
                WebhookResult::new(1, 0, 1, vec![Ok(Attempt::new(Some(200), "OK", sent_state))]));
 }
 
+#[test]
+fn webhook_versions_parsed() {
+    assert_eq!(parse_webhook_version("1"), Ok(WebhookVersion::V1));
+    assert_eq!(parse_webhook_version("2"), Ok(WebhookVersion::V2));
+    assert_eq!(parse_webhook_version("3"),
+               Err("'3' isn't a webhook version, it must be 1 or 2".to_string()));
+}
+
+#[test]
+fn version_2_updates_sent() {
+    let mut wh = Webhook::new(JobContext::new("job_name", "hello", None),
+                              "hello",
+                              "https://goodplace.com",
+                              None)
+        .with_version(WebhookVersion::V2, vec![]);
+    let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+    let jh = wh.connect_webhook(rx, mock_v2_only, zero_backoff);
+    let sent_state =
+        ExecutionUpdate::new(ExecutionState::Finished,
+                             TaskSnapshot::new(),
+                             Transition::Job(JobTransition::new(Some(ExecutionState::Running),
+                                                                ExecutionState::Finished)));
+    tx.send(sent_state.clone()).unwrap();
+    assert_eq!(jh.join().ok().unwrap(),
+               WebhookResult::new(1, 0, 1, vec![Ok(Attempt::new(Some(200), "OK", sent_state))]));
+}
+
 fn make_mock_run() -> Vec<ExecutionUpdate> {
 
     vec![ 
@@ -91,7 +133,10 @@ fn make_mock_run() -> Vec<ExecutionUpdate> {
 
 #[test]
 fn multiple_messages_sent() {
-    let mut wh = Webhook::new("job_name", "hello", "https://goodplace.com", None, None);
+    let mut wh = Webhook::new(JobContext::new("job_name", "hello", None),
+                          "hello",
+                          "https://goodplace.com",
+                          None);
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
     let jh = wh.connect_webhook(rx, mock_200_ok, zero_backoff);
 
@@ -114,7 +159,10 @@ fn multiple_messages_sent() {
 
 #[test]
 fn failures_tried_three_times() {
-    let mut wh = Webhook::new("job_name", "hello", "https://goodplace.com", None, None);
+    let mut wh = Webhook::new(JobContext::new("job_name", "hello", None),
+                          "hello",
+                          "https://goodplace.com",
+                          None);
     let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
     let jh = wh.connect_webhook(rx, mock_500_err, zero_backoff);
 
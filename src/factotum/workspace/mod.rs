@@ -0,0 +1,168 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+#[cfg(test)]
+mod tests;
+
+use factotum::factfile::Factfile;
+use factotum::retry;
+use rustc_serialize::json::Json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const WORKSPACES_DIR: &'static str = ".factotum/workspaces";
+
+// the variables a factfile uses for the run's workspace, and a task's own directory in it
+pub const RUN_VARIABLE: &'static str = "workspace";
+pub const TASK_VARIABLE: &'static str = "task_workspace";
+
+// the same paths, as they're given to each task's command
+pub const RUN_ENV_VAR: &'static str = "FACTOTUM_WORKSPACE";
+pub const TASK_ENV_VAR: &'static str = "FACTOTUM_TASK_WORKSPACE";
+
+// what happens to a run's workspace once the run ends
+#[derive(Debug, PartialEq, Clone)]
+pub enum Retention {
+    Delete,
+    // a failed run's workspace is kept to see what went wrong
+    DeleteOnSuccess,
+    Keep,
+    // workspaces older than this are removed when the next run starts
+    KeepFor(Duration),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WorkspacePolicy {
+    pub root: PathBuf,
+    pub retention: Retention,
+}
+
+impl WorkspacePolicy {
+    pub fn run_dir(&self, run_reference: &str) -> PathBuf {
+        self.root.join(run_reference)
+    }
+}
+
+pub fn parse_retention(retention: &str) -> Result<Retention, String> {
+    match retention.trim() {
+        "delete" => Ok(Retention::Delete),
+        "on-success" => Ok(Retention::DeleteOnSuccess),
+        "keep" => Ok(Retention::Keep),
+        other => {
+            retry::parse_duration(other).map(Retention::KeepFor).map_err(|_| {
+                format!("'{}' isn't a workspace retention (expected delete, on-success, keep \
+                         or a duration such as 7d)",
+                        other)
+            })
+        }
+    }
+}
+
+// a task's directory is named after it, so it's easy to find in a workspace that was kept
+pub fn task_dir(run_dir: &str, task_name: &str) -> String {
+    let safe_name = task_name.chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect::<String>();
+    Path::new(run_dir).join(safe_name).to_string_lossy().into_owned()
+}
+
+// the run's variables with the workspace added, as it's templated like any other variable
+pub fn with_run_variable(env: Option<Json>, run_dir: &Path) -> Option<Json> {
+    let mut vars = match env {
+        Some(Json::Object(vars)) => vars,
+        _ => BTreeMap::new(),
+    };
+    vars.insert(RUN_VARIABLE.to_string(),
+                Json::String(run_dir.to_string_lossy().into_owned()));
+    Some(Json::Object(vars))
+}
+
+// the workspace is different every run, so it's left out of what the job's reference is
+// worked out from
+pub fn without_run_variable(conf: &Option<Json>) -> Option<Json> {
+    match *conf {
+        Some(Json::Object(ref vars)) => {
+            let mut vars = vars.clone();
+            vars.remove(RUN_VARIABLE);
+            Some(Json::Object(vars))
+        }
+        ref other => other.clone(),
+    }
+}
+
+pub fn create(factfile: &Factfile, run_dir: &Path) -> Result<(), String> {
+    let mut dirs = vec![run_dir.to_path_buf()];
+    for task in factfile.get_tasks_in_order().iter().flat_map(|grp| grp.iter()) {
+        if let Some(dir) = task.workspace.as_ref().and_then(|w| w.task_dir.as_ref()) {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+
+    for dir in dirs.iter() {
+        try!(fs::create_dir_all(dir)
+            .map_err(|e| format!("couldn't create directory '{}' ({})", dir.display(), e)));
+    }
+    Ok(())
+}
+
+pub fn remove(run_dir: &Path) -> Result<(), String> {
+    fs::remove_dir_all(run_dir)
+        .map_err(|e| format!("couldn't remove directory '{}' ({})", run_dir.display(), e))
+}
+
+pub fn should_remove(retention: &Retention, succeeded: bool) -> bool {
+    match *retention {
+        Retention::Delete => true,
+        Retention::DeleteOnSuccess => succeeded,
+        Retention::Keep | Retention::KeepFor(_) => false,
+    }
+}
+
+// a run's workspace is named after its run reference, a sha256 digest in hex
+fn is_run_dir(path: &Path) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    path.is_dir() && name.len() == 64 &&
+    name.chars().all(|c| c.is_digit(16) && !c.is_uppercase())
+}
+
+// removes the workspaces last changed longer ago than keep_for, giving the ones removed;
+// anything else under the root is left alone
+pub fn prune(root: &Path, keep_for: Duration, now: SystemTime) -> Result<Vec<PathBuf>, String> {
+    if !root.exists() {
+        return Ok(vec![]);
+    }
+
+    let entries = try!(fs::read_dir(root)
+        .map_err(|e| format!("couldn't read directory '{}' ({})", root.display(), e)));
+    let mut removed = vec![];
+    for entry in entries {
+        let path = try!(entry.map_err(|e| e.to_string())).path();
+        if !is_run_dir(&path) {
+            continue;
+        }
+        let modified = try!(fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("couldn't check '{}' ({})", path.display(), e)));
+        let age = now.duration_since(modified).unwrap_or(Duration::from_secs(0));
+        if age > keep_for {
+            try!(remove(&path));
+            removed.push(path);
+        }
+    }
+
+    removed.sort();
+    Ok(removed)
+}
@@ -0,0 +1,126 @@
+// Copyright (c) 2016-2021 Snowplow Analytics Ltd. All rights reserved.
+//
+// This program is licensed to you under the Apache License Version 2.0, and
+// you may not use this file except in compliance with the Apache License
+// Version 2.0.  You may obtain a copy of the Apache License Version 2.0 at
+// http://www.apache.org/licenses/LICENSE-2.0.
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the Apache License Version 2.0 is distributed on an "AS
+// IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or
+// implied.  See the Apache License Version 2.0 for the specific language
+// governing permissions and limitations there under.
+//
+
+use super::*;
+use factotum::factfile::TaskWorkspace;
+use factotum::tests::make_task;
+use factotum::webhook::jobcontext::JobContext;
+use std::env;
+
+#[test]
+fn parse_retention_good() {
+    assert_eq!(parse_retention("delete"), Ok(Retention::Delete));
+    assert_eq!(parse_retention("on-success"), Ok(Retention::DeleteOnSuccess));
+    assert_eq!(parse_retention("keep"), Ok(Retention::Keep));
+    assert_eq!(parse_retention("7d"),
+               Ok(Retention::KeepFor(Duration::from_secs(7 * 24 * 60 * 60))));
+}
+
+#[test]
+fn parse_retention_bad() {
+    assert_eq!(parse_retention("sometimes"),
+               Err("'sometimes' isn't a workspace retention (expected delete, on-success, \
+                    keep or a duration such as 7d)"
+                   .to_string()));
+}
+
+#[test]
+fn task_dir_is_path_safe() {
+    assert_eq!(task_dir("/scratch/abc", "load day/2"),
+               "/scratch/abc/load_day_2");
+}
+
+#[test]
+fn run_variable_is_added_and_left_out() {
+    let mut vars = BTreeMap::new();
+    vars.insert("bucket".to_string(), Json::String("logs".to_string()));
+
+    let with = with_run_variable(Some(Json::Object(vars.clone())), Path::new("/scratch/abc"));
+    let mut expected = vars.clone();
+    expected.insert("workspace".to_string(), Json::String("/scratch/abc".to_string()));
+    assert_eq!(with, Some(Json::Object(expected)));
+    assert_eq!(without_run_variable(&with), Some(Json::Object(vars)));
+
+    let mut only = BTreeMap::new();
+    only.insert("workspace".to_string(), Json::String("/scratch/abc".to_string()));
+    assert_eq!(with_run_variable(None, Path::new("/scratch/abc")),
+               Some(Json::Object(only)));
+    assert_eq!(without_run_variable(&None), None);
+}
+
+#[test]
+fn should_remove_follows_the_retention() {
+    assert!(should_remove(&Retention::Delete, false));
+    assert!(should_remove(&Retention::DeleteOnSuccess, true));
+    assert!(!should_remove(&Retention::DeleteOnSuccess, false));
+    assert!(!should_remove(&Retention::Keep, true));
+    assert!(!should_remove(&Retention::KeepFor(Duration::from_secs(60)), true));
+}
+
+#[test]
+fn create_makes_the_task_dirs() {
+    let run_dir = env::temp_dir().join("factotum-workspace-test-create");
+    let run_dir_str = run_dir.to_string_lossy().into_owned();
+
+    let mut ff = Factfile::new("N/A", "test");
+    let mut own = make_task("own", &vec![]);
+    own.workspace = Some(TaskWorkspace {
+        run_dir: run_dir_str.clone(),
+        task_dir: Some(task_dir(&run_dir_str, "own")),
+    });
+    let mut shared = make_task("shared", &vec![]);
+    shared.workspace = Some(TaskWorkspace {
+        run_dir: run_dir_str.clone(),
+        task_dir: None,
+    });
+    ff.add_task_obj(&own).unwrap();
+    ff.add_task_obj(&shared).unwrap();
+
+    assert_eq!(create(&ff, &run_dir), Ok(()));
+    assert!(run_dir.join("own").is_dir());
+    assert!(!run_dir.join("shared").exists());
+
+    assert_eq!(remove(&run_dir), Ok(()));
+    assert!(!run_dir.exists());
+}
+
+#[test]
+fn prune_removes_old_workspaces() {
+    let root = env::temp_dir().join("factotum-workspace-test-prune");
+    let policy = WorkspacePolicy {
+        root: root.clone(),
+        retention: Retention::KeepFor(Duration::from_secs(24 * 60 * 60)),
+    };
+    let old_run = policy.run_dir(&JobContext::new_run_reference());
+    let new_run = policy.run_dir(&JobContext::new_run_reference());
+    // something else kept under the root, which factotum didn't make
+    let unrelated = root.join("exports");
+    let three_days_ago = SystemTime::now() - Duration::from_secs(3 * 24 * 60 * 60);
+    for dir in vec![&old_run, &new_run, &unrelated] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    for dir in vec![&old_run, &unrelated] {
+        fs::File::open(dir).unwrap().set_modified(three_days_ago).unwrap();
+    }
+
+    assert_eq!(prune(&root, Duration::from_secs(24 * 60 * 60), SystemTime::now()),
+               Ok(vec![old_run.clone()]));
+    assert!(!old_run.exists());
+    assert!(new_run.exists());
+    assert!(unrelated.exists());
+
+    assert_eq!(prune(&root.join("missing"), Duration::from_secs(0), SystemTime::now()),
+               Ok(vec![]));
+    assert!(fs::remove_dir_all(&root).is_ok());
+}
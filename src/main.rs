@@ -29,28 +29,90 @@ extern crate hyper_native_tls;
 extern crate libc;
 extern crate ifaces;
 extern crate dns_lookup;
+extern crate regex;
+extern crate toml;
 
 use docopt::Docopt;
 use std::fs;
 use factotum::executor::task_list::{Task, State};
 use factotum::factfile::Factfile;
+use factotum::factfile::diff;
+use factotum::factfile::stats;
+use factotum::factfile::snapshot;
 use factotum::factfile::Task as FactfileTask;
+use factotum::errors::{ErrorKind, FactotumError};
 use factotum::parser::OverrideResultMappings;
 use factotum::parser::TaskReturnCodeMapping;
+use factotum::parser::TaskResultOverride;
+use factotum::parser::schemavalidator;
 use factotum::executor::execution_strategy::*;
-use factotum::webhook::Webhook;
+use factotum::webhook::{Webhook, WebhookVersion};
 use factotum::executor::ExecutionUpdate;
 use factotum::webhook;
+use factotum::webhook::jobcontext::{self, Host, JobContext};
+use factotum::archive::{self, ArchiveLocation};
+use factotum::cloudwatch;
+use factotum::syslog::{self, LogSink};
+use factotum::retry::{self, RetryPolicy};
+use factotum::console::{self, OutputMode, Verbosity};
+use factotum::taskenv;
+use factotum::constraint;
+use factotum::control::{self, RunningTasks};
+use factotum::history::{self, HistoryRetention, HistoryStore, RunStore};
+use factotum::circuitbreaker;
+use factotum::backfill;
+use factotum::envfile;
+use factotum::criticalpath;
+use factotum::timeline;
+use factotum::compare;
+use factotum::durationwatch::{self, DurationAlert};
+use factotum::heartbeat::{self, Heartbeat};
+use factotum::sla;
+use factotum::timewindow;
+use factotum::preflight::{self, OsEnvironment};
+use factotum::waitfor;
+use factotum::assertion;
+use factotum::expression;
+use factotum::nested;
+use factotum::emr;
+use factotum::batch;
+use factotum::gcp;
+use factotum::dbt;
+use factotum::spark;
+use factotum::sql;
+use factotum::config::{self, ExecutorSettings};
+use factotum::rerun;
+use factotum::devmode;
+use factotum::diagnostics::{self, Diagnostic};
+use factotum::sourcemap::Position;
+use factotum::placeholders;
+use factotum::mock::{self, MockExecutor, MockResult};
+use factotum::testspec;
+use factotum::chaos::{self, ChaosPolicy};
+use factotum::exitcode::{self, ExitCodeMap};
+use factotum::executor::plan;
+use factotum::interrupt;
+use factotum::queue;
+use factotum::policy::{self, Policy};
+use factotum::workspace::{self, Retention, WorkspacePolicy};
+use factotum::cache;
+use factotum::manifest;
+use factotum::signature::{self, SignatureRequirement, Verifier};
+use factotum::audit::{self, AuditRecord};
 use colored::*;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use std::process::Command;
-use std::io::Write;
+use std::io::{self, Read, Write};
 use std::fs::OpenOptions;
 use std::env;
 use hyper::Url;
 use std::sync::mpsc;
-use std::net;
-use rustc_serialize::json::{self, Json, ToJson};
+use std::thread;
+use std::sync::{Arc, Mutex};
+use std::cmp;
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+use rustc_serialize::json::{Json, ToJson};
 use std::collections::BTreeMap;
 #[cfg(test)]
 use std::fs::File;
@@ -59,12 +121,17 @@ use std::error::Error;
 
 mod factotum;
 
-const PROC_SUCCESS: i32 = 0;
-const PROC_PARSE_ERROR: i32 = 1;
-const PROC_EXEC_ERROR: i32 = 2;
-const PROC_OTHER_ERROR: i32 = 3;
+const PROC_SUCCESS: i32 = exitcode::SUCCESS;
+const PROC_PARSE_ERROR: i32 = exitcode::VALIDATION_FAILED;
+const PROC_EXEC_ERROR: i32 = exitcode::TASK_FAILED;
+const PROC_OTHER_ERROR: i32 = exitcode::INTERNAL_ERROR;
+const PROC_ARGS_ERROR: i32 = exitcode::INVALID_ARGUMENTS;
+const PROC_CONSTRAINT_NOT_MET: i32 = exitcode::CONSTRAINT_NOT_MET;
+const PROC_ABORTED: i32 = exitcode::ABORTED;
+const PROC_POLICY_VIOLATION: i32 = exitcode::POLICY_VIOLATION;
+const PROC_UNVERIFIED: i32 = exitcode::UNVERIFIED;
+const PROC_PREFLIGHT_FAILED: i32 = exitcode::PREFLIGHT_FAILED;
 
-const CONSTRAINT_HOST: &'static str = "host";
 
 const VERSION: &'static str = env!("CARGO_PKG_VERSION");
 const USAGE: &'static str =
@@ -72,43 +139,257 @@ const USAGE: &'static str =
 Factotum.
 
 Usage:
-  factotum run <factfile> [--start=<start_task>] [--env=<env>] [--dry-run] [--no-colour] [--webhook=<url>] [--tag=<tag>]... [--constraint=<constraint>]... [--max-stdouterr-size=<bytes>]
-  factotum validate <factfile> [--no-colour]
+  factotum run <factfile> [--start=<start_task>] [--end=<task>] [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--dry-run] [--show-plan] [--stream-output | --group-output] [--exit-code-map=<map>] [--override-result=<override>]... [-q | -v...] [--no-colour] [--webhook=<url>] [--webhook-version=<version>] [--tag=<tag>]... [--constraint=<constraint>]... [--max-stdouterr-size=<bytes>] [--spill-threshold=<bytes>] [--spill-dir=<dir>] [--workspace-dir=<dir>] [--workspace-retention=<retention>] [--archive=<location>] [--cloudwatch-logs=<group>] [--log-sink=<sink>] [--retry-job=<retries>] [--retry-delay=<delay>] [--retry-max-delay=<delay>] [--retry-jitter=<percent>] [--duration-warning-factor=<factor>] [--alert-webhook=<url>] [--heartbeat=<interval>] [--heartbeat-webhook=<url>] [--skip-preflight] [--cache] [--result-file=<file>] [--only=<task>]... [--mock-executor] [--mock-results=<file>] [--chaos=<percent>] [--chaos-task=<task>]... [--chaos-exit-code=<code>] [--policy=<file>] [--audit-log=<log>] [--history-store=<store>] [--history-keep=<age>] [--history-max-runs=<n>] [--require-signature] [--signature=<file>] [--signer=<identity>] [--profile=<profile>] [--config=<file>]
+  factotum backfill <factfile> --from=<date> --to=<date> [--var-name=<name>] [--parallelism=<n>] [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--exit-code-map=<map>] [--override-result=<override>]... [-q | -v...] [--no-colour] [--webhook=<url>] [--webhook-version=<version>] [--tag=<tag>]... [--max-stdouterr-size=<bytes>] [--spill-threshold=<bytes>] [--spill-dir=<dir>] [--workspace-dir=<dir>] [--workspace-retention=<retention>] [--archive=<location>] [--duration-warning-factor=<factor>] [--alert-webhook=<url>] [--heartbeat=<interval>] [--heartbeat-webhook=<url>] [--skip-preflight] [--cache] [--policy=<file>] [--audit-log=<log>] [--history-store=<store>] [--history-keep=<age>] [--history-max-runs=<n>] [--require-signature] [--signature=<file>] [--signer=<identity>] [--profile=<profile>] [--config=<file>]
+  factotum worker <queue> [--parallelism=<n>] [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--exit-code-map=<map>] [-q | -v...] [--no-colour] [--webhook=<url>] [--webhook-version=<version>] [--tag=<tag>]... [--max-stdouterr-size=<bytes>] [--alert-webhook=<url>] [--skip-preflight] [--audit-log=<log>] [--history-store=<store>] [--history-keep=<age>] [--history-max-runs=<n>] [--profile=<profile>] [--config=<file>]
+  factotum validate <factfile> [--exit-code-map=<map>] [--no-colour]
+  factotum test <testspec> [--exit-code-map=<map>] [--no-colour]
+  factotum check <factfile> [--watch] [--format=<format>] [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--no-colour]
+  factotum dev <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--dry-run] [--no-colour]
+  factotum explain <factfile> <task> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--no-colour]
+  factotum diff <factfile> <other-factfile> [--env=<env>] [--other-env=<env>] [--var=<var>]... [--no-colour]
+  factotum rerun <run-id> [--failed-only] [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--dry-run] [--show-plan] [--webhook=<url>] [--webhook-version=<version>] [--tag=<tag>]... [--alert-webhook=<url>] [--skip-preflight] [--result-file=<file>] [--policy=<file>] [--audit-log=<log>] [--history-store=<store>] [--history-keep=<age>] [--history-max-runs=<n>] [--profile=<profile>] [--config=<file>] [--exit-code-map=<map>] [-q | -v...] [--no-colour]
+  factotum cancel <run-id> [<task>] [--no-colour]
+  factotum critical-path <run-id> [--history-store=<store>] [--no-colour]
+  factotum compare <run-id> <other-run-id> [--history-store=<store>] [--no-colour]
+  factotum timeline <run-id> [--output=<output_file>] [--overwrite] [--history-store=<store>] [--no-colour]
+  factotum history export [--since=<age>] [--format=<format>] [--output=<output_file>] [--overwrite] [--history-store=<store>] [--profile=<profile>] [--config=<file>] [--no-colour]
+  factotum history prune [--history-keep=<age>] [--history-max-runs=<n>] [--history-store=<store>] [--profile=<profile>] [--config=<file>] [--no-colour]
+  factotum list-tasks <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--format=<format>] [--no-colour]
+  factotum snapshot <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--output=<output_file>] [--overwrite] [--check] [--no-colour]
+  factotum stats <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--no-colour]
+  factotum vars <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--no-colour]
+  factotum render <factfile> [--env=<env>] [--env-file=<file>] [--env-file-header=<header>] [--var=<var>]... [--no-colour]
+  factotum wait <condition> [--poll-interval=<interval>] [--timeout=<timeout>] [--exit-code-map=<map>] [--no-colour]
+  factotum assert <expression>... [--message=<message>] [--exit-code-map=<map>] [--no-colour]
+  factotum emr <cluster> <step-param>... [--exit-code-map=<map>] [--no-colour]
+  factotum aws-batch <job-definition> <job-param>... [--exit-code-map=<map>] [--no-colour]
+  factotum cloud-run <job> <gcp-param>... [--exit-code-map=<map>] [--no-colour]
+  factotum cloud-batch <job-spec> <gcp-param>... [--exit-code-map=<map>] [--no-colour]
+  factotum dbt <dbt-command> [<dbt-param>...] [--result-file=<file>] [--exit-code-map=<map>] [--no-colour]
+  factotum sql <connection> <sql-param>... [--config=<file>] [--exit-code-map=<map>] [--no-colour]
+  factotum spark <application> [<spark-param>...] [--exit-code-map=<map>] [--no-colour]
+  factotum completions <shell> [--no-colour]
+  factotum schema [<schema-version>] [--no-colour]
   factotum dot <factfile> [--start=<start_task>] [--output=<output_file>] [--overwrite] [--no-colour]
   factotum (-h | --help) [--no-colour]
   factotum (-v | --version) [--no-colour]
 
 Options:
   -h --help                             Show this screen.
-  -v --version                          Display the version of Factotum and exit.
+  --version                             Display the version of Factotum and exit (as does `factotum -v`).
+  -q --quiet                            Only print the tasks that failed, and why.
+  -v --verbose                          Print each task as it starts and finishes. Given twice (-vv), factotum's debug log, including how each task's variables were filled in, is printed to stderr and written to the logfile too.
   --start=<start_task>                  Begin at specified task.
-  --env=<env>                           Supply JSON to define mustache variables in Factfile.
-  --dry-run                             Pretend to execute a Factfile, showing the commands that would be executed. Can be used with other options.
-  --output=<output_file>                File to print output to. Used with `dot`.
+  --override-result=<override>          Replace a task's return codes for this run, as <task>:continue=<codes> or <task>:terminate=<codes>, e.g. load:continue=0,3.
+  --end=<task>                          End at this task (or every task in a group given as group:<name>): only it and the tasks it depends on are run.
+  --env=<env>                           Supply JSON to define mustache variables in Factfile. Nested values can be used as {{ aws.emr.cluster_id }} or with a JSON pointer, {{ @/aws/emr/0/cluster_id }}.
+  --other-env=<env>                     JSON to define mustache variables in the second Factfile given to `diff` (defaults to --env).
+  --env-file=<file>                     Read mustache variables from a local JSON file (decrypted with sops if it is sops-encrypted) or an https:// URL; --env takes precedence.
+  --env-file-header=<header>            A header such as 'Authorization: Bearer <token>' to send when fetching an https:// --env-file.
+  --var=<var>                           Set a single mustache variable as <name>=<value>, overriding --env and the Factfile's defaults.
+  --dry-run                             Pretend to execute a Factfile, showing the commands that would be executed. Can be used with other options. With `dev`, a run is simulated each time the Factfile changes and is valid.
+  --output=<output_file>                File to print output to. Used with `dot`, `timeline`, `snapshot` and `history export`.
   --overwrite                           Overwrite the output file if it exists.
+  --check                               Fail if the resolved DAG no longer matches the snapshot in --output, rather than writing it.
+  --profile=<profile>                   Use the settings of this profile in the config file, falling back to its defaults.
+  --config=<file>                       Read settings and sql connections from this file rather than $FACTOTUM_CONFIG or ~/.config/factotum/config.toml.
+  --format=<format>                     How `list-tasks` prints the tasks: table, json or names (one per line, without resolving variables, for shell completions), or how `check` reports problems: table or json-diagnostics (a JSON object per check, for editors), or how `history export` writes runs: csv (a row per task, used for table) or json (a run per line) [default: table].
+  --watch                               With `check`, check the Factfile again each time it changes.
   --no-colour                           Turn off ANSI terminal colours/formatting in output.
   --webhook=<url>                       Post updates on job execution to the specified URL.
+  --webhook-version=<version>           The schema version of the --webhook updates (1 unless set); version 2 adds the run id, attempt, host, task finish times and commands, with secret variables redacted.
   --tag=<tag>                           Add job metadata (tags).
-  --constraint=<constraint>             Checks for an external constraint that will prevent execution; allowed constraints (host).
+  --constraint=<constraint>             Checks for an external constraint that will prevent execution; allowed constraints (host, window, check), e.g. 'host,*', 'window,22:00-06:00,Europe/London' or 'check,test -f /data/ready' (a shell command that has to exit with 0). A host can be a list, any of which will do, of hostnames, globs matched against the hostname and the local Consul agent's node name (e.g. 'host,etl-*.prod,etl-backup') and <key>=<value> pairs matched against the node's Consul metadata (e.g. 'host,role=etl'); the agent is found with $CONSUL_HTTP_ADDR and $CONSUL_HTTP_TOKEN.
   --max-stdouterr-size=<bytes>          The maximum size of the individual stdout/err sent via the webhook functions for job updates.
+  --spill-threshold=<bytes>             Write a task's stdout/err to a file once it's larger than this, keeping only the tail in memory (8388608 unless the config file sets it).
+  --spill-dir=<dir>                     Where stdout/err over the --spill-threshold is written; the run result gives each file's path (the system temp directory unless set).
+  --workspace-dir=<dir>                 Where each run's workspace, a scratch directory given to tasks as {{ workspace }} and $FACTOTUM_WORKSPACE, is made (.factotum/workspaces unless set).
+  --workspace-retention=<retention>     What's done with a run's workspace when it ends: delete, on-success (delete unless the run failed), keep, or a duration such as 7d to remove workspaces older than it as each run starts (on-success unless the config file sets it).
+  --archive=<location>                  Upload task logs, the JSON run result and the HTML report to an s3:// or gs:// location when the run ends.
+  --cloudwatch-logs=<group>             Stream task output to the CloudWatch Logs group as tasks run (one log stream per run and task).
+  --log-sink=<sink>                     Write job and task lifecycle messages to the host's log (syslog or journald).
+  --from=<date>                         The first date (YYYY-MM-DD) to backfill.
+  --to=<date>                           The last date (YYYY-MM-DD) to backfill, inclusive.
+  --var-name=<name>                     The variable each backfilled date is supplied to the Factfile as, alongside run_date, run_ts, prev_run_ts and schedule_interval [default: run_date].
+  --parallelism=<n>                     How many dates to backfill, or jobs a `worker` runs, at once (1 unless the config file sets it).
+  --retry-job=<retries>                 Re-run the whole job up to this many times if it fails.
+  --retry-delay=<delay>                 How long to wait before each job retry, e.g. 30s, 5m or 1h [default: 1m].
+  --retry-max-delay=<delay>             Double the retry delay after each failed attempt, up to this maximum.
+  --retry-jitter=<percent>              Randomly shorten each retry delay by up to this percentage [default: 0].
+  --duration-warning-factor=<factor>    Warn when a task runs this many times longer than its expectedDuration, or its typical duration in the run history [default: 2].
+  --alert-webhook=<url>                 Post warning events (a task running over its expected duration or failing, or the job missing its sla) to the specified URL.
+  --heartbeat=<interval>                Report each task that's still running at this interval (e.g. 5m), with how long it's been running and the last line it output, so a hung task can be told from a slow one.
+  --heartbeat-webhook=<url>             Post each --heartbeat event to the specified URL.
+  --poll-interval=<interval>            How often `wait` checks its condition (a tcp://host:port, http(s):// URL, s3:// key or file path) [default: 10s].
+  --timeout=<timeout>                   How long `wait` checks its condition before giving up [default: 1h].
+  --message=<message>                   What `assert` is checking, shown if an expression (e.g. '42' >= 10 or eu-west-1 =~ ^eu-) doesn't hold.
+  --result-file=<file>                  Write the JSON run result to this file when the run ends.
+  --policy=<file>                       Only run tasks whose commands are permitted by this TOML policy file, which lists the programs each executor may run; tasks a generator adds and the commands $(...) variables are computed by (as the shell executor's) are checked too, and nested Factfiles when it's set in the config file's defaults.
+  --audit-log=<log>                     Append a record of who ran what (user, host, the Factfile's checksum, its variables with secrets redacted, and the outcome) to this file as a line of JSON, or send it to 'syslog' or 'journald'.
+  --history-store=<store>               Where runs are recorded for `rerun`, `critical-path`, `compare`, `timeline`, circuit breakers and caching: a file, or a postgres:// URL for a database shared by many hosts (.factotum/history.jsonl unless set).
+  --since=<age>                         Only export runs that started within this duration, such as 30d.
+  --history-keep=<age>                  Prune runs older than this duration, such as 90d, from the run history after each run (and with `history prune`).
+  --history-max-runs=<n>                Prune all but the newest n runs from the run history after each run (and with `history prune`).
+  --require-signature                   Don't run the Factfile unless its signature verifies: a detached GPG signature made by a key gpg trusts fully or ultimately, or a sigstore bundle checked with cosign, found next to it as <factfile>.sigstore.json, .sigstore, .asc or .sig.
+  --signature=<file>                    The signature (or sigstore bundle, if it ends in .sigstore or .sigstore.json) to verify the Factfile against with --require-signature.
+  --signer=<identity>                   Who the Factfile must be signed by with --require-signature: the GPG key's fingerprint or the email address in its user id, or the certificate identity (e.g. an email) a sigstore bundle needs.
+  --stream-output                       Print each line of task output as it's written, after the name of its task in a colour of its own.
+  --group-output                        Print each task's output in one piece as soon as the task finishes, each line after the name of the task in a colour of its own.
+  --show-plan                           Before any task runs, print the execution plan: the waves of tasks that will be started together, given their dependencies, and the tasks that will be skipped.
+  --skip-preflight                      Don't check that task commands, variables and required artifacts are available before the job starts.
+  --cache                               Skip each task with a cache whose command, variables and inputs haven't changed since it last succeeded, as recorded in the run history.
+  --only=<task>                         Run just this task, or every task in a group given as group:<name>; dependencies on other tasks are ignored.
+  --mock-executor                       Don't run task commands but record them, each task returning 0 (or its result in --mock-results) so the job's logic can be tested.
+  --mock-results=<file>                 A JSON object of task names to the return code (or an object with a returnCode, stdout and stderr) each task gives under --mock-executor.
+  --chaos=<percent>                     For testing: fail this percentage of tasks at random rather than running them.
+  --chaos-task=<task>                   For testing: always fail this task rather than running it.
+  --chaos-exit-code=<code>              The exit code tasks failed by --chaos or --chaos-task give [default: 1].
+  --failed-only                         Rerun just the tasks that failed or never ran, using the Factfile and variables recorded for the run. Secret variables aren't recorded, so `rerun` must be given them again with --env, --env-file or --var.
+  --exit-code-map=<map>                 Change the exit codes below, given as <class>=<code> pairs separated by commas, e.g. constraint-not-met=0,task-failed=10.
+
+Exit codes:
+  0  the command succeeded
+  1  validation-failed: the Factfile (or test spec) isn't valid
+  2  task-failed: a task failed, or a check such as `test`, `assert` or `snapshot --check` didn't pass
+  3  internal-error: anything else went wrong, e.g. a file couldn't be written
+  4  invalid-arguments: the command line (or config file) is invalid
+  5  constraint-not-met: a --constraint wasn't satisfied, so no tasks were run
+  6  aborted: factotum got SIGINT, SIGTERM or SIGHUP, so it stopped starting tasks and ended when the running ones finished
+  7  policy-violation: a task's command isn't permitted by the --policy file, so no tasks were run
+  8  unverified: --require-signature was given but the Factfile's signature couldn't be verified, so no tasks were run
+  9  preflight-failed: a command, variable or artifact a task needs couldn't be found before the job started (see --skip-preflight), so no tasks were run
 ";
 
 #[derive(Debug, RustcDecodable)]
 struct Args {
     flag_start: Option<String>,
+    flag_end: Option<String>,
+    flag_override_result: Option<Vec<String>>,
     flag_env: Option<String>,
+    flag_env_file: Option<String>,
+    flag_env_file_header: Option<String>,
+    flag_var: Option<Vec<String>>,
     flag_output: Option<String>,
     flag_webhook: Option<String>,
+    flag_webhook_version: Option<String>,
     flag_overwrite: bool,
     flag_dry_run: bool,
     flag_no_colour: bool,
     flag_tag: Option<Vec<String>>,
     flag_constraint: Option<Vec<String>>,
     flag_max_stdouterr_size: Option<usize>,
+    flag_spill_threshold: Option<usize>,
+    flag_spill_dir: Option<String>,
+    flag_workspace_dir: Option<String>,
+    flag_workspace_retention: Option<String>,
+    flag_archive: Option<String>,
+    flag_cloudwatch_logs: Option<String>,
+    flag_log_sink: Option<String>,
+    flag_retry_job: Option<u32>,
+    flag_retry_delay: String,
+    flag_retry_max_delay: Option<String>,
+    flag_retry_jitter: u32,
+    flag_duration_warning_factor: f64,
+    flag_heartbeat: Option<String>,
+    flag_heartbeat_webhook: Option<String>,
+    flag_alert_webhook: Option<String>,
+    flag_show_plan: bool,
+    flag_stream_output: bool,
+    flag_group_output: bool,
+    flag_skip_preflight: bool,
+    flag_cache: bool,
+    flag_result_file: Option<String>,
+    flag_only: Option<Vec<String>>,
+    flag_failed_only: bool,
+    flag_exit_code_map: Option<String>,
+    flag_mock_executor: bool,
+    flag_mock_results: Option<String>,
+    flag_chaos: Option<u32>,
+    flag_chaos_task: Option<Vec<String>>,
+    flag_chaos_exit_code: i32,
+    flag_format: String,
+    flag_policy: Option<String>,
+    flag_audit_log: Option<String>,
+    flag_history_store: Option<String>,
+    flag_history_keep: Option<String>,
+    flag_history_max_runs: Option<usize>,
+    flag_since: Option<String>,
+    flag_require_signature: bool,
+    flag_signature: Option<String>,
+    flag_signer: Option<String>,
+    flag_profile: Option<String>,
+    flag_config: Option<String>,
     arg_factfile: String,
     flag_version: bool,
+    flag_quiet: bool,
+    flag_verbose: usize,
     cmd_run: bool,
+    cmd_backfill: bool,
+    cmd_worker: bool,
+    flag_from: String,
+    flag_to: String,
+    flag_var_name: String,
+    flag_parallelism: Option<usize>,
     cmd_validate: bool,
+    cmd_dev: bool,
+    cmd_check: bool,
+    flag_watch: bool,
+    cmd_test: bool,
+    arg_testspec: String,
+    cmd_render: bool,
+    cmd_explain: bool,
+    cmd_list_tasks: bool,
+    cmd_completions: bool,
+    cmd_schema: bool,
+    cmd_diff: bool,
+    arg_other_factfile: String,
+    flag_other_env: Option<String>,
+    arg_task: String,
+    cmd_stats: bool,
+    cmd_vars: bool,
+    cmd_snapshot: bool,
+    flag_check: bool,
+    cmd_rerun: bool,
+    cmd_critical_path: bool,
+    cmd_cancel: bool,
+    cmd_timeline: bool,
+    cmd_compare: bool,
+    cmd_history: bool,
+    cmd_prune: bool,
+    cmd_export: bool,
+    arg_other_run_id: String,
+    arg_run_id: String,
+    arg_queue: String,
+    cmd_wait: bool,
+    arg_condition: String,
+    arg_shell: String,
+    arg_schema_version: Option<String>,
+    flag_poll_interval: String,
+    flag_timeout: String,
+    cmd_assert: bool,
+    arg_expression: Vec<String>,
+    flag_message: Option<String>,
+    cmd_emr: bool,
+    arg_cluster: String,
+    arg_step_param: Vec<String>,
+    cmd_aws_batch: bool,
+    arg_job_definition: String,
+    arg_job_param: Vec<String>,
+    cmd_cloud_run: bool,
+    arg_job: String,
+    cmd_cloud_batch: bool,
+    arg_job_spec: String,
+    arg_gcp_param: Vec<String>,
+    cmd_dbt: bool,
+    arg_dbt_command: String,
+    arg_dbt_param: Vec<String>,
+    cmd_sql: bool,
+    arg_connection: String,
+    arg_sql_param: Vec<String>,
+    cmd_spark: bool,
+    arg_application: String,
+    arg_spark_param: Vec<String>,
     cmd_dot: bool,
 }
 
@@ -154,7 +435,16 @@ fn get_duration_as_string(d: &Duration) -> String {
     }
 }
 
-fn get_task_result_line_str(task_result: &Task<&FactfileTask>) -> (String, Option<String>) {
+fn spilled_output_note(spill_file: Option<&PathBuf>) -> String {
+    match spill_file {
+        Some(path) => format!(" (the end of it - all of it is in '{}')", path.display()),
+        None => "".to_string(),
+    }
+}
+
+fn get_task_result_line_str(task_result: &Task<&FactfileTask>,
+                            show_output: bool)
+                            -> (String, Option<String>) {
 
     let state = task_result.state.clone();
     let start_time = match task_result.run_started {
@@ -169,25 +459,51 @@ fn get_task_result_line_str(task_result: &Task<&FactfileTask>) -> (String, Optio
                              task_result.name.cyan(),
                              start_time.unwrap());
 
+        let (stdout_file, stderr_file) = match res.spilled_output {
+            Some(ref spilled) => (spilled.stdout_file.as_ref(), spilled.stderr_file.as_ref()),
+            None => (None, None),
+        };
+
         let output = match res.stdout {
-            Some(ref o) => {
-                Some(format!("Task '{}' stdout:\n{}\n",
+            Some(ref o) if show_output => {
+                Some(format!("Task '{}' stdout{}:\n{}\n",
                              task_result.name.cyan(),
+                             spilled_output_note(stdout_file),
                              o.trim_right().bold()))
             } 
-            None => None,
+            _ => None,
         };
 
         let errors = match res.stderr {
-            Some(ref e) => {
-                Some(format!("Task '{}' stderr:\n{}\n",
+            Some(ref e) if show_output => {
+                Some(format!("Task '{}' stderr{}:\n{}\n",
                              task_result.name.cyan(),
+                             spilled_output_note(stderr_file),
                              e.trim_right().red()))
             }
-            None => None,
+            _ => None,
         };
 
         let summary = match (&res.task_execution_error, state) {
+            (&Some(ref error), _) if error.kind == ErrorKind::Cancelled => {
+                let mut failure_str = "Task '".red().to_string();
+                failure_str.push_str(&format!("{}", task_result.name.cyan()));
+                failure_str.push_str(&format!("': was cancelled after {}",
+                                              get_duration_as_string(&res.duration))
+                    .red()
+                    .to_string());
+                failure_str
+            }
+            (&Some(ref error), _) if error.kind == ErrorKind::TimedOut => {
+                let mut failure_str = "Task '".red().to_string();
+                failure_str.push_str(&format!("{}", task_result.name.cyan()));
+                failure_str.push_str(&format!("': was stopped after {} as {}",
+                                              get_duration_as_string(&res.duration),
+                                              error.message)
+                    .red()
+                    .to_string());
+                failure_str
+            }
             (&Some(ref task_exec_error_msg), _) => {
                 let mut failure_str = "Task '".red().to_string();
                 failure_str.push_str(&format!("{}", task_result.name.cyan()));
@@ -245,7 +561,9 @@ fn get_task_result_line_str(task_result: &Task<&FactfileTask>) -> (String, Optio
     return (result, stderr);
 }
 
-fn get_task_results_str(task_results: &Vec<&Task<&FactfileTask>>) -> (String, String) {
+fn get_task_results_str(task_results: &Vec<&Task<&FactfileTask>>,
+                        show_output: bool)
+                        -> (String, String) {
     let mut stderr = String::new();
     let mut stdout = String::new();
 
@@ -253,7 +571,7 @@ fn get_task_results_str(task_results: &Vec<&Task<&FactfileTask>>) -> (String, St
     let mut executed = 0;
 
     for task in task_results.iter() {
-        let (task_stdout, task_stderr) = get_task_result_line_str(task);
+        let (task_stdout, task_stderr) = get_task_result_line_str(task, show_output);
         stdout.push_str(&task_stdout);
 
         if let Some(task_stderr_str) = task_stderr {
@@ -275,6 +593,26 @@ fn get_task_results_str(task_results: &Vec<&Task<&FactfileTask>>) -> (String, St
     (stdout, stderr)
 }
 
+// what -q prints: only the tasks that failed, without the totals
+fn get_failed_task_results_str(task_results: &Vec<&Task<&FactfileTask>>,
+                               show_output: bool)
+                               -> (String, String) {
+    let mut stderr = String::new();
+    let mut stdout = String::new();
+
+    for task in task_results.iter() {
+        if let State::Failed(_) = task.state {
+            let (task_stdout, task_stderr) = get_task_result_line_str(task, show_output);
+            stdout.push_str(&task_stdout);
+            if let Some(task_stderr_str) = task_stderr {
+                stderr.push_str(&task_stderr_str);
+            }
+        }
+    }
+
+    (stdout, stderr)
+}
+
 fn validate_start_task(job: &Factfile, start_task: &str) -> Result<(), &'static str> {
     // A
     // / \
@@ -306,8 +644,14 @@ fn validate_start_task(job: &Factfile, start_task: &str) -> Result<(), &'static
     }
 }
 
-fn dot(factfile: &str, start_from: Option<String>) -> Result<String, String> {
-    let ff = try!(factotum::parser::parse(factfile, None, OverrideResultMappings::None));
+fn dot(factfile: &str,
+       start_from: Option<String>,
+       configured: &ExecutorSettings)
+       -> Result<String, String> {
+    let ff = try!(factotum::parser::parse(factfile,
+                                          None,
+                                          OverrideResultMappings::None,
+                                          configured));
     if let Some(ref start) = start_from {
         match ff.can_job_run_from_task(&start) {
             Ok(is_good) => {
@@ -322,307 +666,2106 @@ fn dot(factfile: &str, start_from: Option<String>) -> Result<String, String> {
     Ok(ff.as_dotfile(start_from))
 }
 
-fn validate(factfile: &str, env: Option<Json>) -> Result<String, String> {
-    match factotum::parser::parse(factfile, env, OverrideResultMappings::None) {
-        Ok(_) => Ok(format!("'{}' is a valid Factfile!", factfile).green().to_string()),
-        Err(msg) => Err(msg.red().to_string()),
-    }
+fn render(factfile: &str,
+          env: Option<Json>,
+          configured: &ExecutorSettings)
+          -> Result<String, String> {
+    let ff = try!(factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured));
+    let rendered = try!(Json::from_str(&ff.raw).map_err(|e| {
+        format!("the rendered Factfile is not valid JSON: {}", Error::description(&e))
+    }));
+    Ok(format!("{}", rendered.pretty()))
 }
 
-fn parse_file_and_simulate(factfile: &str, env: Option<Json>, start_from: Option<String>) -> i32 {
-    parse_file_and_execute_with_strategy(factfile,
-                                         env,
-                                         start_from,
-                                         factotum::executor::execution_strategy::execute_simulation,
-                                         OverrideResultMappings::All(TaskReturnCodeMapping {
-                                             continue_job: vec![0],
-                                             terminate_early: vec![],
-                                         }),
-                                         None,
-                                         None,
-                                         None)
-}
+fn explain(factfile: &str,
+           task_name: &str,
+           env: Option<Json>,
+           configured: &ExecutorSettings)
+           -> Result<String, String> {
+    let ff = try!(factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured));
+    let task = try!(ff.find_task(task_name)
+        .ok_or(format!("the task '{}' could not be found in '{}'", task_name, factfile)));
+
+    let list_or = |items: Vec<String>, empty: &str| {
+        if items.is_empty() {
+            empty.to_string()
+        } else {
+            items.join(", ")
+        }
+    };
+    let codes = |codes: &Vec<i32>| codes.iter().map(|c| c.to_string()).collect::<Vec<String>>();
+
+    let mut lines = vec![format!("Task '{}' in '{}'", task.name.cyan(), ff.name.cyan())];
+    lines.push(format!("  Executor:          {}", task.executor));
+    lines.push(format!("  Command:           {}", task.command));
+    lines.push(format!("  Arguments:         {}",
+                       list_or(task.arguments.iter().map(|a| format!("\"{}\"", a)).collect(),
+                               "none")));
+    if task.executor == waitfor::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum wait {}",
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    } else if task.executor == emr::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum emr {}",
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    } else if task.executor == batch::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum aws-batch {}",
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    } else if [gcp::CLOUD_RUN_EXECUTOR,
+                gcp::CLOUD_BATCH_EXECUTOR,
+                sql::EXECUTOR,
+                spark::EXECUTOR]
+        .contains(&&task.executor[..]) {
+        lines.push(format!("  Runs as:           factotum {} {}",
+                           task.executor,
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    } else if task.executor == dbt::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum dbt {}",
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    } else if task.executor == nested::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum run {}{}",
+                           task.command,
+                           task.arguments
+                               .iter()
+                               .map(|a| format!(" --var=\"{}\"", a))
+                               .collect::<String>()));
+    } else if task.executor == assertion::EXECUTOR {
+        lines.push(format!("  Runs as:           factotum assert {}",
+                           factotum::executor::format_args(&format!("--message=\"{}\"",
+                                                                    task.command),
+                                                           &task.arguments)));
+    } else if task.executor == factotum::executor::BARRIER_EXECUTOR {
+        lines.push("  Runs as:           nothing, it waits for the tasks it depends on"
+            .to_string());
+    } else {
+        lines.push(format!("  Runs as:           sh -c '{}'",
+                           factotum::executor::format_args(&task.command, &task.arguments)));
+    }
+    if let Some(ref when) = task.when {
+        lines.push(format!("  Runs when:         {}", when));
+    }
+    lines.push(format!("  Depends on:        {}",
+                       list_or(task.depends_on.clone(), "nothing")));
+    lines.push(format!("  Dependents:        {}",
+                       list_or(ff.get_dependents(&task.name)
+                                   .iter()
+                                   .map(|t| t.name.clone())
+                                   .collect(),
+                               "none")));
+    lines.push(format!("  Continues on:      {}",
+                       list_or(codes(&task.on_result.continue_job), "none")));
+    lines.push(format!("  Finishes early on: {}",
+                       list_or(codes(&task.on_result.terminate_job), "none")));
+    if let Some(ref idle_timeout) = task.idle_timeout {
+        lines.push(format!("  Stopped if idle:   after {} without output",
+                           get_duration_as_string(idle_timeout)));
+    }
+    // the task's own vars are layered over the job's
+    let mut variables = ff.variables.clone();
+    if let Some(task_vars) = ff.task_variables.get(&task.name) {
+        variables.extend(task_vars.clone());
+    }
+    lines.push(format!("  Variables:         {}",
+                       list_or(variables.iter()
+                                   .map(|(k, v)| format!("{}={}", k, v))
+                                   .collect(),
+                               "none")));
 
-fn parse_file_and_execute(factfile: &str,
-                          env: Option<Json>,
-                          start_from: Option<String>,
-                          webhook_url: Option<String>,
-                          job_tags: Option<HashMap<String, String>>,
-                          max_stdouterr_size: Option<usize>)
-                          -> i32 {
-    parse_file_and_execute_with_strategy(factfile,
-                                         env,
-                                         start_from,
-                                         factotum::executor::execution_strategy::execute_os,
-                                         OverrideResultMappings::None,
-                                         webhook_url,
-                                         job_tags,
-                                         max_stdouterr_size)
+    Ok(lines.join("\n"))
 }
 
-fn parse_file_and_execute_with_strategy<F>(factfile: &str,
-                                           env: Option<Json>,
-                                           start_from: Option<String>,
-                                           strategy: F,
-                                           override_result_map: OverrideResultMappings,
-                                           webhook_url: Option<String>,
-                                           job_tags: Option<HashMap<String, String>>,
-                                           max_stdouterr_size: Option<usize>)
-                                           -> i32
-    where F: Fn(&str, &mut Command) -> RunResult + Send + Sync + 'static + Copy
-{
-
-    match factotum::parser::parse(factfile, env, override_result_map) {
-        Ok(job) => {
-
-            if let Some(ref start_task) = start_from {
-                if let Err(msg) = validate_start_task(&job, &start_task) {
-                    warn!("The job could not be started from '{}' because {}",
-                          start_task,
-                          msg);
-                    println!("The job cannot be started from '{}' because {}",
-                             start_task.cyan(),
-                             msg);
-                    return PROC_OTHER_ERROR;
-                }
-            }
-
-            let (maybe_updates_channel, maybe_join_handle) = if webhook_url.is_some() {
-                let url = webhook_url.unwrap();
-                let mut wh = Webhook::new(job.name.clone(), job.raw.clone(), url, job_tags, max_stdouterr_size);
-                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
-                let join_handle =
-                    wh.connect_webhook(rx, Webhook::http_post, webhook::backoff_rand_1_minute);
-                (Some(tx), Some(join_handle))
-            } else {
-                (None, None)
-            };
-
-            let job_res = factotum::executor::execute_factfile(&job,
-                                                               start_from,
-                                                               strategy,
-                                                               maybe_updates_channel);
-
-            let mut has_errors = false;
-            let mut has_early_finish = false;
-
-            let mut tasks = vec![];
-
-            for task_group in job_res.tasks.iter() {
-                for task in task_group {
-                    if let State::Failed(_) = task.state {
-                        has_errors = true;
-                    } else if let State::SuccessNoop = task.state {
-                        has_early_finish = true;
-                    }
-                    tasks.push(task);
-                }
-            }
-
-            let normal_completion = !has_errors && !has_early_finish;
-
-            let result = if normal_completion {
-                let (stdout_summary, stderr_summary) = get_task_results_str(&tasks);
-                print!("{}", stdout_summary);
-                if !stderr_summary.trim_right().is_empty() {
-                    print_err!("{}", stderr_summary.trim_right());
-                }
-                PROC_SUCCESS
-            } else if has_early_finish && !has_errors {
-                let (stdout_summary, stderr_summary) = get_task_results_str(&tasks);
-                print!("{}", stdout_summary);
-                if !stderr_summary.trim_right().is_empty() {
-                    print_err!("{}", stderr_summary.trim_right());
-                }
-                let incomplete_tasks = tasks.iter()
-                    .filter(|r| !r.run_result.is_some())
-                    .map(|r| format!("'{}'", r.name.cyan()))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                let stop_requesters = tasks.iter()
-                    .filter(|r| match r.state {
-                        State::SuccessNoop => true,
-                        _ => false,
-                    })
-                    .map(|r| format!("'{}'", r.name.cyan()))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-                println!("Factotum job finished early as a task ({}) requested an early finish. \
-                          The following tasks were not run: {}.",
-                         stop_requesters,
-                         incomplete_tasks);
-                PROC_SUCCESS
-            } else {
-                let (stdout_summary, stderr_summary) = get_task_results_str(&tasks);
-                print!("{}", stdout_summary);
-
-                if !stderr_summary.trim_right().is_empty() {
-                    print_err!("{}", stderr_summary.trim_right());
-                }
-
-                let incomplete_tasks = tasks.iter()
-                    .filter(|r| !r.run_result.is_some())
-                    .map(|r| format!("'{}'", r.name.cyan()))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                let failed_tasks = tasks.iter()
-                    .filter(|r| match r.state {
-                        State::Failed(_) => true,
-                        _ => false,
-                    })
-                    .map(|r| format!("'{}'", r.name.cyan()))
-                    .collect::<Vec<String>>()
-                    .join(", ");
-
-                println!("Factotum job executed abnormally as a task ({}) failed - the following \
-                          tasks were not run: {}!",
-                         failed_tasks,
-                         incomplete_tasks);
-                PROC_EXEC_ERROR
-            };
-
-            if maybe_join_handle.is_some() {
-                print!("Waiting for webhook to finish sending events...");
-                let j = maybe_join_handle.unwrap();
-                let webhook_res = j.join().ok().unwrap();
-                println!("{}", " done!".green());
+fn wait(condition: &str, poll_interval: &str, timeout: &str) -> i32 {
+    let options = waitfor::parse_wait_options(&vec![format!("--poll-interval={}", poll_interval),
+                                                    format!("--timeout={}", timeout)]);
+    let (condition, options) = match waitfor::parse_wait_condition(condition)
+        .and_then(|c| options.map(|o| (c, o))) {
+        Ok(parsed) => parsed,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
 
-                if webhook_res.events_received > webhook_res.success_count {
-                    println!("{}", "Warning: some events failed to send".red());
-                }
-            }
+    println!("Waiting for {} (checking every {}, for up to {})",
+             waitfor::describe(&condition),
+             get_duration_as_string(&options.poll_interval),
+             get_duration_as_string(&options.timeout));
 
-            result
-        } 
+    match waitfor::wait_for(&condition,
+                            &options,
+                            waitfor::check_condition,
+                            thread::sleep) {
+        Ok(checks) => {
+            println!("{}",
+                     format!("Done, the condition was met after {} check{}",
+                             checks,
+                             if checks == 1 { "" } else { "s" })
+                         .green());
+            PROC_SUCCESS
+        }
         Err(msg) => {
-            println!("{}", msg);
-            return PROC_PARSE_ERROR;
-        }      
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_EXEC_ERROR
+        }
     }
 }
 
-fn write_to_file(filename: &str, contents: &str, overwrite: bool) -> Result<(), String> {
-    let mut f = if overwrite {
-        match OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(filename) {
-            Ok(f) => f,
-            Err(io) => return Err(format!("couldn't create file '{}' ({})", filename, io)),        
-        }
-    } else {
-        match OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open(filename) {
-            Ok(f) => f,
-            Err(io) => return Err(format!("couldn't create file '{}' ({})", filename, io)),        
+fn emr_step(cluster: &str, params: &Vec<String>) -> i32 {
+    let (cluster, step) = match emr::parse_cluster(cluster)
+        .and_then(|c| emr::parse_step(params).map(|s| (c, s))) {
+        Ok(parsed) => parsed,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
         }
     };
 
-    match f.write_all(contents.as_bytes()) {
-        Ok(_) => Ok(()),
-        Err(msg) => Err(format!("couldn't write to file '{}' ({})", filename, msg)),
-    }
-}
-
-fn is_valid_url(url: &str) -> Result<(), String> {
-    if url.starts_with("http://") || url.starts_with("https://") {
-        match Url::parse(url) {
-            Ok(_) => Ok(()),
-            Err(msg) => Err(format!("{}", msg)),
+    let client = emr::AwsCliEmrClient { region: step.region.clone() };
+    match emr::run_step(&client, &cluster, &step, thread::sleep, |r| println!("{}", r)) {
+        Ok(checks) => {
+            println!("{}",
+                     format!("Done, the step completed after {} check{}",
+                             checks,
+                             if checks == 1 { "" } else { "s" })
+                         .green());
+            PROC_SUCCESS
+        }
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_EXEC_ERROR
         }
-    } else {
-        Err("URL must begin with 'http://' or 'https://' to be used with Factotum webhooks".into())
     }
 }
 
-fn get_constraint_map(constraints: &Vec<String>) -> HashMap<String, String> {
-    get_tag_map(constraints)
-}
-
-fn is_valid_host(host: &str) -> Result<(), String> {
-    if host == "*" {
-        return Ok(());
-    }
-
-    let os_hostname = try!(gethostname_safe().map_err(|e| e.to_string()));
+fn batch_job(definition: &str, params: &Vec<String>) -> i32 {
+    let job = match batch::parse_job(definition, params) {
+        Ok(job) => job,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
 
-    if host == os_hostname {
-        return Ok(());
+    let client = batch::AwsCliBatchClient { region: job.region.clone() };
+    match batch::run_job(&client, &job, thread::sleep, |r| println!("{}", r)) {
+        Ok(checks) => {
+            println!("{}",
+                     format!("Done, the job succeeded after {} check{}",
+                             checks,
+                             if checks == 1 { "" } else { "s" })
+                         .green());
+            PROC_SUCCESS
+        }
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_EXEC_ERROR
+        }
     }
+}
 
-    let external_addrs = try!(get_external_addrs().map_err(|e| e.to_string()));
-    let host_addrs = try!(dns_lookup::lookup_host(&host)
-        .map_err(|_| "could not find any IPv4 addresses for the supplied hostname"));
-
-    for host_addr in host_addrs {
-        if let Ok(good_host_addr) = host_addr {
-            if external_addrs.iter().any(|external_addr| external_addr.ip() == good_host_addr) {
-                return Ok(());
-            }
+fn gcp_job_result(result: Result<u64, String>) -> i32 {
+    match result {
+        Ok(checks) => {
+            println!("{}",
+                     format!("Done, the job succeeded after {} check{}",
+                             checks,
+                             if checks == 1 { "" } else { "s" })
+                         .green());
+            PROC_SUCCESS
+        }
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_EXEC_ERROR
         }
     }
-
-    Err("failed to match any of the interface addresses to the found host addresses".into())
 }
 
-extern "C" {
-    pub fn gethostname(name: *mut libc::c_char, size: libc::size_t) -> libc::c_int;
+fn cloud_run_job(job: &str, params: &Vec<String>) -> i32 {
+    match gcp::parse_cloud_run_job(job, params) {
+        Ok(job) => {
+            gcp_job_result(gcp::run_cloud_run_job(&gcp::AdcClient,
+                                                  &job,
+                                                  thread::sleep,
+                                                  |r| println!("{}", r)))
+        }
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_ARGS_ERROR
+        }
+    }
 }
 
-fn gethostname_safe() -> Result<String, String> {
-    let len = 255;
-    let mut buf = Vec::<u8>::with_capacity(len);
-
-    let ptr = buf.as_mut_slice().as_mut_ptr();
-
-    let err = unsafe { gethostname(ptr as *mut libc::c_char, len as libc::size_t) } as libc::c_int;
+fn cloud_batch_job(spec: &str, params: &Vec<String>) -> i32 {
+    let parsed = gcp::parse_cloud_batch_job(spec, params).and_then(|job| {
+        let mut contents = String::new();
+        try!(fs::File::open(&job.spec)
+            .and_then(|mut f| f.read_to_string(&mut contents))
+            .map_err(|e| format!("couldn't read '{}' ({})", job.spec, e)));
+        let spec = try!(Json::from_str(&contents)
+            .map_err(|e| format!("'{}' isn't a valid job spec ({})", job.spec, e)));
+        Ok((job, spec))
+    });
 
-    match err {
-        0 => {
-            let mut _real_len = len;
-            let mut i = 0;
-            loop {
-                let byte = unsafe { *(((ptr as u64) + (i as u64)) as *const u8) };
-                if byte == 0 {
-                    _real_len = i;
-                    break;
-                }
-                i += 1;
-            }
-            unsafe { buf.set_len(_real_len) }
-            Ok(String::from_utf8_lossy(buf.as_slice()).into_owned())
+    match parsed {
+        Ok((job, spec)) => {
+            let job_id = gcp::batch_job_id(&job.name, &chrono::UTC::now());
+            gcp_job_result(gcp::run_cloud_batch_job(&gcp::AdcClient,
+                                                    &job,
+                                                    &spec,
+                                                    &job_id,
+                                                    thread::sleep,
+                                                    |r| println!("{}", r)))
         }
-        _ => {
-            Err("could not get hostname from system; cannot compare against supplied hostname"
-                .into())
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_ARGS_ERROR
         }
     }
 }
 
-fn get_external_addrs() -> Result<Vec<net::SocketAddr>, String> {
-    let mut external_addrs = vec![];
+fn run_dbt(command: &str, params: &Vec<String>, result_file: &Option<String>) -> i32 {
+    let invocation = match dbt::parse_invocation(command, params) {
+        Ok(invocation) => invocation,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let started = std::time::SystemTime::now();
+    let code = match dbt::run_dbt(&invocation) {
+        Ok(code) => code,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_EXEC_ERROR;
+        }
+    };
 
-    for iface in ifaces::Interface::get_all().unwrap().into_iter() {
-        if iface.kind == ifaces::Kind::Ipv4 {
-            if let Some(addr) = iface.addr {
-                if !addr.ip().is_loopback() {
-                    external_addrs.push(addr)
+    // a dbt that failed before running anything leaves the last run's results in place
+    let run_results = dbt::run_results_path(&invocation);
+    let fresh = fs::metadata(&run_results)
+        .and_then(|m| m.modified())
+        .map(|modified| modified >= started)
+        .unwrap_or(false);
+    if fresh {
+        match dbt::read_model_results(&run_results) {
+            Ok(models) => {
+                println!("{}", dbt::summarise(&models));
+                if let Some(ref path) = *result_file {
+                    if let Err(msg) = dbt::write_model_results(Path::new(path), &models) {
+                        println!("{}", format!("Warning: {}", msg).yellow());
+                    }
                 }
             }
+            Err(msg) => println!("{}", format!("Warning: {}", msg).yellow()),
         }
     }
 
-    if external_addrs.len() == 0 {
-        Err("could not find any non-loopback IPv4 addresses in the network interfaces; do you \
-             have a working network interface card?"
-            .into())
+    code
+}
+
+fn run_sql(connection: &str, params: &Vec<String>) -> i32 {
+    let prepared = sql::parse_sql_task(params).and_then(|task| {
+        let path = try!(config::default_path()
+            .ok_or("there's no config file to read the connection from".to_string()));
+        let connection = try!(config::load_connection(&path, connection));
+        let password = match connection.password {
+            Some(ref password) => {
+                Some(try!(sql::resolve_secret(password)
+                    .map_err(|e| format!("couldn't resolve the connection's password: {}", e))))
+            }
+            None => None,
+        };
+        sql::client_command(&connection, &task, password).map(|command| (task, command))
+    });
+    let (task, command) = match prepared {
+        Ok(prepared) => prepared,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let (code, output) = match sql::run_client(&command) {
+        Ok(result) => result,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_EXEC_ERROR;
+        }
+    };
+    print!("{}", output);
+    if code != 0 {
+        return code;
+    }
+
+    let rows = sql::count_rows(&output);
+    println!("{} row{} returned", rows, if rows == 1 { "" } else { "s" });
+    match task.no_rows {
+        Some(no_rows) if rows == 0 => no_rows,
+        _ => PROC_SUCCESS,
+    }
+}
+
+fn spark_submit(application: &str, params: &Vec<String>) -> i32 {
+    let app = match spark::parse_spark_app(application, params) {
+        Ok(app) => app,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    if !app.cluster_mode {
+        return match spark::run_client_app(&app) {
+            Ok(code) => code,
+            Err(msg) => {
+                println!("{}", format!("Error: {}", msg).red());
+                PROC_EXEC_ERROR
+            }
+        };
+    }
+
+    let client = spark::CliSparkClient;
+    match spark::run_cluster_app(&client, &app, thread::sleep, |r| println!("{}", r)) {
+        Ok(checks) => {
+            println!("{}",
+                     format!("Done, the application succeeded after {} check{}",
+                             checks,
+                             if checks == 1 { "" } else { "s" })
+                         .green());
+            PROC_SUCCESS
+        }
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            PROC_EXEC_ERROR
+        }
+    }
+}
+
+fn assert_expressions(expressions: &Vec<String>, message: &Option<String>) -> i32 {
+    let mut assertions = vec![];
+    for source in expressions.iter() {
+        match expression::parse(source) {
+            Ok(e) => assertions.push((source, e)),
+            Err(msg) => {
+                println!("{}", format!("Error: '{}' is invalid: {}", source, msg).red());
+                return PROC_ARGS_ERROR;
+            }
+        }
+    }
+
+    let mut failed = vec![];
+    for &(source, ref e) in assertions.iter() {
+        match e.evaluate(&expression::Context::default()) {
+            Ok(true) => println!("{} {}", "Passed:".green(), source),
+            Ok(false) => failed.push(format!("{} doesn't hold", source)),
+            Err(msg) => failed.push(format!("{} couldn't be checked: {}", source, msg)),
+        }
+    }
+
+    if failed.is_empty() {
+        PROC_SUCCESS
+    } else {
+        let heading = match *message {
+            Some(ref m) => format!("Assertion failed ({}):", m),
+            None => "Assertion failed:".to_string(),
+        };
+        println!("{}", heading.red());
+        for failure in failed.iter() {
+            println!("{}", format!("  - {}", failure).red());
+        }
+        PROC_EXEC_ERROR
+    }
+}
+
+// the shell completions list names without any variables, so a factfile whose required
+// variables haven't been given still completes
+fn list_tasks(factfile: &str,
+              env: Option<Json>,
+              format: &str,
+              configured: &ExecutorSettings)
+              -> Result<String, String> {
+    let env = if format == "names" { None } else { env };
+    let ff = try!(factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured));
+    factotum::factfile::listing::list_tasks(&ff, format)
+}
+
+fn check_snapshot(factfile: &str,
+                  env: Option<Json>,
+                  snapshot_file: &str,
+                  configured: &ExecutorSettings)
+                  -> Result<String, String> {
+    let ff = try!(factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured));
+    let saved = try!(fs::read_to_string(snapshot_file)
+        .map_err(|e| format!("couldn't read the snapshot '{}' ({})", snapshot_file, e)));
+    try!(snapshot::check_snapshot(&ff, &saved));
+    Ok(format!("'{}' matches the snapshot in '{}'", factfile, snapshot_file))
+}
+
+fn stats(factfile: &str,
+         env: Option<Json>,
+         configured: &ExecutorSettings)
+         -> Result<String, String> {
+    let ff = try!(factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured));
+    Ok(format!("Statistics for '{}'\n{}",
+               ff.name.cyan(),
+               stats::format_stats(&stats::factfile_stats(&ff))))
+}
+
+// the placeholders the factfile uses, and whether any of them are left without a value
+fn vars(factfile: &str,
+        env: Option<Json>,
+        configured: &ExecutorSettings)
+        -> Result<(String, bool), String> {
+    let contents = try!(fs::read_to_string(factfile)
+        .map_err(|e| format!("Couldn't read '{}': {}", factfile, e))
+        .and_then(|c| factotum::parser::expanded_source(&c, configured)));
+    let supplied = env.unwrap_or(Json::Object(BTreeMap::new()));
+    let usages = try!(placeholders::find_usages(&contents, &supplied)
+        .map_err(|e| format!("Couldn't list the variables in '{}', {}", factfile, e)));
+    let complete = usages.iter().all(|u| u.status != placeholders::Status::Missing);
+    Ok((format!("Variables used by '{}'\n{}", factfile.cyan(), placeholders::format_usages(&usages)),
+        complete))
+}
+
+fn critical_path(run_reference: &str, store: &dyn RunStore) -> Result<String, String> {
+    let run = try!(store.find_run(run_reference));
+    let path = criticalpath::critical_path(&criticalpath::from_run_record(&run));
+    if path.is_empty() {
+        return Err(format!("the run '{}' has no recorded task durations", run_reference));
+    }
+
+    let total = Duration::from_millis(criticalpath::total_millis(&path));
+    let name_width = path.iter().map(|t| t.name.len()).max().unwrap_or(0);
+
+    let mut lines = vec![format!("Critical path for run {} of '{}' ({} in total):",
+                                 run.run_reference.cyan(),
+                                 run.job_name.cyan(),
+                                 get_duration_as_string(&total))];
+    for (idx, task) in path.iter().enumerate() {
+        lines.push(format!("  {}. {:width$}  {}",
+                           idx + 1,
+                           task.name,
+                           get_duration_as_string(&Duration::from_millis(task.duration_millis)),
+                           width = name_width));
+    }
+
+    Ok(lines.join("\n"))
+}
+
+fn timeline(run_reference: &str, store: &dyn RunStore) -> Result<String, String> {
+    let run = try!(store.find_run(run_reference));
+    let bars = try!(timeline::timeline_bars(&run));
+    Ok(timeline::timeline_svg(&run, &bars))
+}
+
+// the factfile and variables of an earlier run, and the tasks to run again: all of them, or
+// with failed_only just those that failed or never ran; the run's secret variables weren't
+// recorded, so they must be among the supplied variables, which override the recorded ones
+fn rerun_plan(run_reference: &str,
+              store: &dyn RunStore,
+              failed_only: bool,
+              supplied: Option<Json>,
+              configured: &ExecutorSettings)
+              -> Result<(history::RunSource, Option<Vec<String>>), String> {
+    let run = try!(store.find_run(run_reference));
+    let mut source = try!(run.source.clone().ok_or(format!("the run '{}' didn't record its \
+                                                            factfile, so it can't be rerun",
+                                                           run_reference)));
+    if let (&mut Json::Object(ref mut recorded), Some(Json::Object(supplied))) =
+           (&mut source.variables, supplied) {
+        recorded.extend(supplied);
+    }
+    let missing = audit::redacted_names(&source.variables);
+    if !missing.is_empty() {
+        return Err(format!("the run '{}' didn't record its secret variables, give them again \
+                            with --env, --env-file or --var: {}",
+                           run_reference,
+                           missing.join(", ")));
+    }
+    if !failed_only {
+        return Ok((source, None));
+    }
+
+    let job = try!(factotum::parser::parse(&source.factfile,
+                                           Some(source.variables.clone()),
+                                           OverrideResultMappings::None,
+                                           configured));
+    Ok((source, Some(rerun::rerun_targets(&job, &run))))
+}
+
+fn compare(run_reference: &str,
+           other_run_reference: &str,
+           store: &dyn RunStore)
+           -> Result<String, String> {
+    let before = try!(store.find_run(run_reference));
+    let after = try!(store.find_run(other_run_reference));
+
+    let heading = if before.job_name == after.job_name {
+        format!("Comparing runs of '{}' ({} -> {}):",
+                before.job_name.cyan(),
+                before.run_reference.cyan(),
+                after.run_reference.cyan())
+    } else {
+        format!("Comparing a run of '{}' ({}) with a run of '{}' ({}):",
+                before.job_name.cyan(),
+                before.run_reference.cyan(),
+                after.job_name.cyan(),
+                after.run_reference.cyan())
+    };
+
+    Ok(format!("{}\n{}",
+               heading,
+               compare::format_comparison(&compare::compare_runs(&before, &after))))
+}
+
+fn diff(factfile: &str,
+        other_factfile: &str,
+        env: Option<Json>,
+        other_env: Option<Json>,
+        configured: &ExecutorSettings)
+        -> Result<String, String> {
+    let before = try!(factotum::parser::parse(factfile,
+                                              env,
+                                              OverrideResultMappings::None,
+                                              configured));
+    let after = try!(factotum::parser::parse(other_factfile,
+                                             other_env,
+                                             OverrideResultMappings::None,
+                                             configured));
+    let changes = diff::diff_factfiles(&before, &after);
+
+    if changes.is_empty() {
+        Ok("No differences".to_string())
+    } else {
+        Ok(diff::format_diff(&changes))
+    }
+}
+
+// the report of running the tests, and whether they all passed
+fn test_factfile(spec_file: &str, configured: &ExecutorSettings) -> Result<(String, bool), String> {
+    let spec = try!(testspec::load_test_spec(Path::new(spec_file)));
+    let results = try!(testspec::run_test_spec(&spec, configured));
+
+    let mut lines = vec![format!("Testing '{}' with '{}'", spec.factfile.cyan(), spec_file.cyan())];
+    for result in results.iter() {
+        if result.failures.is_empty() {
+            lines.push(format!("  {} {}", "PASS".green(), result.name));
+        } else {
+            lines.push(format!("  {} {}", "FAIL".red(), result.name));
+            for failure in result.failures.iter() {
+                lines.push(format!("       - {}", failure));
+            }
+        }
+    }
+
+    let passed = results.iter().filter(|r| r.failures.is_empty()).count();
+    lines.push(format!("{} of {} tests passed", passed, results.len()));
+    Ok((lines.join("\n"), passed == results.len()))
+}
+
+// a factfile that couldn't be read was given wrongly, rather than being invalid
+fn parse_error_code(error: &FactotumError) -> i32 {
+    if error.kind.is_invalid_factfile() {
+        PROC_PARSE_ERROR
+    } else {
+        PROC_ARGS_ERROR
+    }
+}
+
+fn validate(factfile: &str,
+            env: Option<Json>,
+            configured: &ExecutorSettings)
+            -> Result<String, String> {
+    match factotum::parser::parse(factfile, env, OverrideResultMappings::None, configured) {
+        Ok(_) => Ok(format!("'{}' is a valid Factfile!", factfile).green().to_string()),
+        Err(msg) => Err(msg.to_string().red().to_string()),
+    }
+}
+
+fn dev_check(args: &Args,
+             tag_map: &Option<HashMap<String, String>>,
+             configured: &ExecutorSettings) {
+    // the env file is read again, as it may be what changed
+    let env = match get_env_json(args, tag_map) {
+        Ok(env) => env,
+        Err(msg) => {
+            println!("{}", msg.red());
+            return;
+        }
+    };
+
+    match validate(&args.arg_factfile, Some(env.clone()), configured) {
+        Ok(msg) => {
+            println!("{}", msg);
+            if args.flag_dry_run {
+                parse_file_and_simulate(&args.arg_factfile,
+                                        Some(env),
+                                        None,
+                                        vec![],
+                                        None,
+                                        false,
+                                        configured);
+            }
+        }
+        Err(msg) => println!("{}", msg),
+    }
+}
+
+fn dev(args: &Args,
+       tag_map: &Option<HashMap<String, String>>,
+       configured: &ExecutorSettings)
+       -> i32 {
+    let files = devmode::watched_files(&args.arg_factfile,
+                                       args.flag_env_file.as_ref().map(|f| f.as_ref()));
+    let mut watcher = devmode::Watcher::new(files);
+
+    println!("Watching '{}' for changes, press Ctrl-C to stop",
+             args.arg_factfile.cyan());
+    dev_check(args, tag_map, configured);
+
+    loop {
+        thread::sleep(devmode::POLL_INTERVAL);
+        let changed = watcher.changed();
+        if !changed.is_empty() {
+            for file in changed.iter() {
+                println!("\n'{}' changed", file.display());
+            }
+            dev_check(args, tag_map, configured);
+        }
+    }
+}
+
+fn check_factfile(factfile: &str,
+                  env: Result<Json, String>,
+                  configured: &ExecutorSettings)
+                  -> Vec<Diagnostic> {
+    let source = match fs::read_to_string(factfile) {
+        Ok(source) => source,
+        Err(e) => {
+            return vec![Diagnostic {
+                            position: Position::start(),
+                            message: format!("couldn't read the factfile: {}", e),
+                        }]
+        }
+    };
+    let env = match env {
+        Ok(env) => env,
+        Err(msg) => {
+            return vec![Diagnostic {
+                            position: Position::start(),
+                            message: msg,
+                        }]
+        }
+    };
+
+    match factotum::parser::parse(factfile, Some(env), OverrideResultMappings::None, configured) {
+        Ok(_) => vec![],
+        Err(msg) => diagnostics::diagnose(&source, &msg.message),
+    }
+}
+
+fn report_check(args: &Args,
+                tag_map: &Option<HashMap<String, String>>,
+                configured: &ExecutorSettings)
+                -> bool {
+    // the env file is read again, as it may be what changed
+    let problems = check_factfile(&args.arg_factfile, get_env_json(args, tag_map), configured);
+
+    if args.flag_format == diagnostics::FORMAT_JSON_DIAGNOSTICS {
+        println!("{}",
+                 diagnostics::diagnostics_json(&args.arg_factfile, &problems));
+    } else if problems.is_empty() {
+        println!("{}",
+                 format!("'{}' is a valid Factfile!", args.arg_factfile).green());
+    } else {
+        println!("{}",
+                 diagnostics::format_diagnostics(&args.arg_factfile, &problems).red());
+    }
+    problems.is_empty()
+}
+
+fn check(args: &Args,
+         tag_map: &Option<HashMap<String, String>>,
+         configured: &ExecutorSettings)
+         -> i32 {
+    if args.flag_format != diagnostics::FORMAT_TABLE &&
+       args.flag_format != diagnostics::FORMAT_JSON_DIAGNOSTICS {
+        println!("{}",
+                 format!("Error: the format '{}' can't be used with check, it must be {} or {}",
+                         args.flag_format,
+                         diagnostics::FORMAT_TABLE,
+                         diagnostics::FORMAT_JSON_DIAGNOSTICS)
+                     .red());
+        return PROC_ARGS_ERROR;
+    }
+
+    let valid = report_check(args, tag_map, configured);
+    if !args.flag_watch {
+        return if valid { PROC_SUCCESS } else { PROC_PARSE_ERROR };
+    }
+
+    let files = devmode::watched_files(&args.arg_factfile,
+                                       args.flag_env_file.as_ref().map(|f| f.as_ref()));
+    let mut watcher = devmode::Watcher::new(files);
+    loop {
+        thread::sleep(devmode::POLL_INTERVAL);
+        if !watcher.changed().is_empty() {
+            report_check(args, tag_map, configured);
+        }
+    }
+}
+
+fn parse_file_and_simulate(factfile: &str,
+                           env: Option<Json>,
+                           start_from: Option<String>,
+                           only: Vec<String>,
+                           end: Option<String>,
+                           show_plan: bool,
+                           configured: &ExecutorSettings)
+                           -> i32 {
+    parse_file_and_execute_with_strategy(factfile,
+                                         env,
+                                         start_from,
+                                         factotum::executor::execution_strategy::execute_simulation,
+                                         OverrideResultMappings::All(TaskReturnCodeMapping {
+                                             continue_job: vec![0],
+                                             terminate_early: vec![],
+                                         }),
+                                         RunOptions {
+                                             only: only,
+                                             end: end,
+                                             show_plan: show_plan,
+                                             simulated: true,
+                                             executor_settings: configured.clone(),
+                                             ..RunOptions::default()
+                                         },
+                                         None)
+}
+
+fn result_mappings(overrides: &BTreeMap<String, TaskResultOverride>) -> OverrideResultMappings {
+    if overrides.is_empty() {
+        OverrideResultMappings::None
+    } else {
+        OverrideResultMappings::Tasks(overrides.clone())
+    }
+}
+
+fn parse_file_and_mock(factfile: &str,
+                       env: Option<Json>,
+                       start_from: Option<String>,
+                       results: HashMap<String, MockResult>,
+                       only: Vec<String>,
+                       end: Option<String>,
+                       chaos: Option<ChaosPolicy>,
+                       show_plan: bool,
+                       result_overrides: &BTreeMap<String, TaskResultOverride>,
+                       configured: &ExecutorSettings)
+                       -> i32 {
+    let mock = MockExecutor::new(results);
+    let strategy = {
+        let mock = mock.clone();
+        chaos::with_chaos(chaos, move |name: &str, command: &mut Command, _: &TaskLimits| {
+            mock.execute(name, command)
+        })
+    };
+
+    let result = parse_file_and_execute_with_strategy(factfile,
+                                                      env,
+                                                      start_from,
+                                                      strategy,
+                                                      result_mappings(result_overrides),
+                                                      RunOptions {
+                                                          only: only,
+                                                          end: end,
+                                                          show_plan: show_plan,
+                                                          executor_settings: configured.clone(),
+                                                          ..RunOptions::default()
+                                                      },
+                                                      None);
+
+    let calls = mock.calls();
+    if !calls.is_empty() {
+        println!("Commands recorded by the mock executor:\n{}",
+                 mock::format_calls(&calls));
+    }
+    result
+}
+
+fn preflight_check(factfile: &str,
+                   env: Option<Json>,
+                   start_from: &Option<String>,
+                   options: &RunOptions,
+                   environment: &dyn preflight::PreflightEnvironment)
+                   -> Result<(), String> {
+    // a factfile that doesn't parse, or tasks that can't be picked out or started from, is
+    // reported when the run begins
+    let job = match factotum::parser::parse(factfile,
+                                            env,
+                                            OverrideResultMappings::None,
+                                            &options.executor_settings) {
+        Ok(job) => job,
+        Err(_) => return Ok(()),
+    };
+
+    // only the tasks --only and --end leave to run are checked
+    let job = if options.only.is_empty() {
+        job
+    } else {
+        let selected = job.resolve_targets(&options.only)
+            .and_then(|names| job.select(&names).map_err(|e| e.to_string()));
+        match selected {
+            Ok(selected) => selected,
+            Err(_) => return Ok(()),
+        }
+    };
+    let job = match options.end {
+        Some(ref end) => {
+            match job.up_to(&vec![end.clone()]) {
+                Ok(job) => job,
+                Err(_) => return Ok(()),
+            }
+        }
+        None => job,
+    };
+
+    if let Some(ref start_task) = *start_from {
+        if validate_start_task(&job, start_task).is_err() {
+            return Ok(());
+        }
+    }
+
+    let source = try!(fs::read_to_string(factfile)
+        .map_err(|e| format!("Couldn't read '{}' for preflight checks ({})", factfile, e))
+        .and_then(|c| factotum::parser::expanded_source(&c, &options.executor_settings)));
+    let problems = preflight::preflight(&job,
+                                        &source,
+                                        start_from.as_ref().map(|s| s.as_str()),
+                                        environment);
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(preflight::format_report(&problems))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct RunOptions {
+    webhook_url: Option<String>,
+    webhook_version: WebhookVersion,
+    // which run of the job this is, when a failed job is retried
+    attempt: Option<(u32, u32)>,
+    job_tags: Option<HashMap<String, String>>,
+    max_stdouterr_size: Option<usize>,
+    archive_location: Option<ArchiveLocation>,
+    cloudwatch_group: Option<String>,
+    log_sink: Option<LogSink>,
+    retry_policy: Option<RetryPolicy>,
+    history: Option<HistoryStore>,
+    history_retention: HistoryRetention,
+    control: Option<RunningTasks>,
+    duration_warning_factor: Option<f64>,
+    alert_webhook: Option<String>,
+    heartbeat: Option<Duration>,
+    heartbeat_webhook: Option<String>,
+    show_plan: bool,
+    output_mode: Option<OutputMode>,
+    verbosity: Verbosity,
+    skip_preflight: bool,
+    cache: bool,
+    result_file: Option<PathBuf>,
+    only: Vec<String>,
+    end: Option<String>,
+    result_overrides: BTreeMap<String, TaskResultOverride>,
+    chaos: Option<ChaosPolicy>,
+    spill_policy: SpillPolicy,
+    policy: Option<Policy>,
+    workspace: Option<WorkspacePolicy>,
+    // a dry run, where the commands are only printed
+    simulated: bool,
+    // the config file's [executors] settings
+    executor_settings: ExecutorSettings,
+}
+
+fn parse_file_and_execute(factfile: &str,
+                          env: Option<Json>,
+                          start_from: Option<String>,
+                          options: RunOptions)
+                          -> i32 {
+    if !options.skip_preflight {
+        if let Err(report) = preflight_check(factfile,
+                                             env.clone(),
+                                             &start_from,
+                                             &options,
+                                             &OsEnvironment) {
+            warn!("{}", report);
+            println!("{}", report.red());
+            return PROC_PREFLIGHT_FAILED;
+        }
+    }
+
+    let retry_policy = options.retry_policy.clone().unwrap_or(RetryPolicy::none());
+    let mut attempt = 1;
+    interrupt::install_handlers();
+
+    loop {
+        let options = RunOptions {
+            attempt: Some((attempt, retry_policy.max_retries + 1)),
+            ..options.clone()
+        };
+        let result = parse_file_and_execute_once(factfile,
+                                                 env.clone(),
+                                                 start_from.clone(),
+                                                 options);
+
+        if let Some(signal) = interrupt::received() {
+            let message = format!("The job was aborted by {}",
+                                  interrupt::signal_name(signal));
+            warn!("{}", message);
+            println!("{}", message.red());
+            return PROC_ABORTED;
+        }
+
+        if result != PROC_EXEC_ERROR || !retry_policy.should_retry(attempt) {
+            return result;
+        }
+
+        let delay = retry_policy.delay_for(attempt, rand::random::<f64>());
+        warn!("Job failed on attempt {} of {}, retrying in {}s",
+              attempt,
+              retry_policy.max_retries + 1,
+              delay.as_secs());
+        println!("{}",
+                 format!("Job failed on attempt {} of {} - retrying in {}",
+                         attempt,
+                         retry_policy.max_retries + 1,
+                         get_duration_as_string(&delay))
+                     .yellow());
+        thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+fn parse_file_and_execute_once(factfile: &str,
+                               env: Option<Json>,
+                               start_from: Option<String>,
+                               options: RunOptions)
+                               -> i32 {
+    let running = RunningTasks::default();
+    let options = RunOptions { control: Some(running.clone()), ..options };
+    let spill_policy = options.spill_policy.clone();
+
+    let group_output = options.output_mode == Some(OutputMode::Group);
+
+    if options.cloudwatch_group.is_some() || options.heartbeat.is_some() ||
+       options.output_mode == Some(OutputMode::Stream) {
+        let (output_tx, output_rx) = mpsc::sync_channel::<TaskOutput>(10_000);
+        let streaming_strategy = move |name: &str, command: &mut Command, limits: &TaskLimits| {
+            factotum::executor::execution_strategy::execute_os_controlled(name,
+                                                                          command,
+                                                                          limits,
+                                                                          &spill_policy,
+                                                                          Some(&output_tx),
+                                                                          &running)
+        };
+        let streaming_strategy = chaos::with_chaos(options.chaos.clone(),
+                                                   interrupt::with_abort(streaming_strategy));
+        let streaming_strategy = console::with_grouped_output(group_output, streaming_strategy);
+        parse_file_and_execute_with_strategy(factfile,
+                                             env,
+                                             start_from,
+                                             streaming_strategy,
+                                             result_mappings(&options.result_overrides),
+                                             options,
+                                             Some(output_rx))
+    } else {
+        let strategy = move |name: &str, command: &mut Command, limits: &TaskLimits| {
+            factotum::executor::execution_strategy::execute_os_controlled(name,
+                                                                          command,
+                                                                          limits,
+                                                                          &spill_policy,
+                                                                          None,
+                                                                          &running)
+        };
+        let strategy = chaos::with_chaos(options.chaos.clone(), interrupt::with_abort(strategy));
+        parse_file_and_execute_with_strategy(factfile,
+                                             env,
+                                             start_from,
+                                             console::with_grouped_output(group_output, strategy),
+                                             result_mappings(&options.result_overrides),
+                                             options,
+                                             None)
+    }
+}
+
+fn warn_duration_exceeded(context: &JobContext,
+                          alert_webhook: &Option<String>,
+                          factor: f64,
+                          alert: &DurationAlert) {
+    let message = format!("the task '{}' has been running for {}, more than {}x its expected \
+                           duration of {}",
+                          alert.task_name,
+                          get_duration_as_string(&alert.elapsed),
+                          factor,
+                          get_duration_as_string(&alert.expected));
+    warn!("{}", message);
+    println!("{}", format!("Warning: {}", message).yellow());
+
+    if let Some(ref url) = *alert_webhook {
+        post_alert(url, &durationwatch::alert_json(context, alert));
+    }
+}
+
+fn report_heartbeat(context: &JobContext, heartbeat_webhook: &Option<String>, beat: &Heartbeat) {
+    let mut message = format!("the task '{}' is still running, elapsed {}",
+                              beat.task_name,
+                              get_duration_as_string(&beat.elapsed));
+    if let Some(ref line) = beat.last_output {
+        message.push_str(&format!(", last output line: {}", line));
+    }
+    info!("{}", message);
+    println!("Heartbeat: {}", message);
+
+    if let Some(ref url) = *heartbeat_webhook {
+        post_alert(url, &heartbeat::heartbeat_json(context, beat));
+    }
+}
+
+fn warn_sla_breached(context: &JobContext,
+                     alert_webhook: &Option<String>,
+                     deadline: &chrono::DateTime<chrono::UTC>) {
+    let message = format!("the job has missed its sla deadline of {} and is still running",
+                          deadline);
+    warn!("{}", message);
+    println!("{}", format!("Warning: {}", message).red());
+
+    if let Some(ref url) = *alert_webhook {
+        post_alert(url, &sla::breach_json(context, deadline));
+    }
+}
+
+fn post_alert(url: &str, event: &Json) {
+    if let Err((code, msg)) = Webhook::http_post(url, &event.to_string()) {
+        warn!("Failed to send the alert to '{}': {}, {}", url, code, msg);
+    }
+}
+
+fn write_result_file(path: &Path,
+                     context: &JobContext,
+                     tasks: &Vec<&Task<&FactfileTask>>)
+                     -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        try!(fs::create_dir_all(parent)
+            .map_err(|e| format!("couldn't create '{}' ({})", parent.display(), e)));
+    }
+    let mut f = try!(fs::File::create(path)
+        .map_err(|e| format!("couldn't create '{}' ({})", path.display(), e)));
+    f.write_all(factotum::report::run_result_json(context, tasks).pretty().to_string().as_bytes())
+        .map_err(|e| format!("couldn't write to '{}' ({})", path.display(), e))
+}
+
+fn parse_file_and_execute_with_strategy<F>(factfile: &str,
+                                           env: Option<Json>,
+                                           start_from: Option<String>,
+                                           strategy: F,
+                                           override_result_map: OverrideResultMappings,
+                                           options: RunOptions,
+                                           task_output: Option<mpsc::Receiver<TaskOutput>>)
+                                           -> i32
+    where F: Fn(&str, &mut Command, &TaskLimits) -> RunResult + Send + Sync + 'static + Clone
+{
+    let source = history::RunSource {
+        factfile: fs::canonicalize(factfile)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or(factfile.to_string()),
+        // secrets aren't kept, a rerun is given them again
+        variables: audit::redact(&env.clone().unwrap_or(Json::Object(BTreeMap::new())), &vec![]),
+    };
+
+    let run_reference = JobContext::new_run_reference();
+    let run_workspace = options.workspace.as_ref().map(|w| w.run_dir(&run_reference));
+    let env = match run_workspace {
+        Some(ref run_dir) => workspace::with_run_variable(env, run_dir),
+        None => env,
+    };
+
+    // $(command) variables are computed as the factfile is parsed for a run, so under a
+    // policy their commands are checked before any of them are
+    if let (Some(ref policy), false) = (options.policy.as_ref(), options.simulated) {
+        let uncomputed = factotum::parser::parse(factfile,
+                                                 env.clone(),
+                                                 OverrideResultMappings::None,
+                                                 &options.executor_settings);
+        if let Ok(ref job) = uncomputed {
+            let problems = policy.check_computed_variables(job);
+            if !problems.is_empty() {
+                let report = policy::format_report(&problems);
+                warn!("{}", report);
+                println!("{}", report.red());
+                return PROC_POLICY_VIOLATION;
+            }
+        }
+    }
+
+    let parsed = if options.simulated {
+        factotum::parser::parse(factfile, env, override_result_map, &options.executor_settings)
+    } else {
+        factotum::parser::parse_for_run(factfile,
+                                        env,
+                                        override_result_map,
+                                        &options.executor_settings)
+    };
+    match parsed {
+        Ok(job) => {
+
+            let job = if options.only.is_empty() {
+                job
+            } else {
+                let selected = job.resolve_targets(&options.only)
+                    .and_then(|names| job.select(&names).map_err(|e| e.to_string()));
+                match selected {
+                    Ok(selected) => selected,
+                    Err(msg) => {
+                        warn!("The job could not be run because {}", msg);
+                        println!("The job cannot be run because {}", msg);
+                        return PROC_ARGS_ERROR;
+                    }
+                }
+            };
+
+            let job = match options.end {
+                Some(ref end) => {
+                    match job.up_to(&vec![end.clone()]) {
+                        Ok(job) => job,
+                        Err(msg) => {
+                            warn!("The job could not be run because {}", msg);
+                            println!("The job cannot be ended at '{}' because {}", end.cyan(), msg);
+                            return PROC_ARGS_ERROR;
+                        }
+                    }
+                }
+                None => job,
+            };
+
+            if let Some(ref start_task) = start_from {
+                if let Err(msg) = validate_start_task(&job, &start_task) {
+                    warn!("The job could not be started from '{}' because {}",
+                          start_task,
+                          msg);
+                    println!("The job cannot be started from '{}' because {}",
+                             start_task.cyan(),
+                             msg);
+                    return PROC_ARGS_ERROR;
+                }
+            }
+
+            // checked before anything runs, so a job is never part run
+            let mut job = job;
+            if let Some(ref policy) = options.policy {
+                let problems = policy.check_factfile(&job);
+                if !problems.is_empty() {
+                    let report = policy::format_report(&problems);
+                    warn!("{}", report);
+                    println!("{}", report.red());
+                    return PROC_POLICY_VIOLATION;
+                }
+                job.policy = Some(policy.clone());
+            }
+
+            if let (Some(ref policy), Some(ref run_dir)) = (options.workspace.as_ref(),
+                                                            run_workspace.as_ref()) {
+                if let Retention::KeepFor(keep_for) = policy.retention {
+                    if let Err(msg) = workspace::prune(&policy.root, keep_for, SystemTime::now()) {
+                        warn!("Couldn't remove old workspaces: {}", msg);
+                        println!("{}",
+                                 format!("Warning: couldn't remove old workspaces. Reason: {}",
+                                         msg)
+                                     .yellow());
+                    }
+                }
+                if let Err(msg) = workspace::create(&job, run_dir) {
+                    warn!("The job could not be run because its workspace {}", msg);
+                    println!("{}",
+                             format!("The job cannot be run as its workspace {}", msg).red());
+                    return PROC_OTHER_ERROR;
+                }
+            }
+
+            let (attempt, max_attempts) = options.attempt.unwrap_or((1, 1));
+            let job_context = JobContext::for_run(job.name.clone(),
+                                                  &job.raw,
+                                                  options.job_tags,
+                                                  run_reference)
+                .on_attempt(attempt, max_attempts)
+                .on_host(Host {
+                    user: Some(current_user()),
+                    factfile_commit: jobcontext::factfile_commit(Path::new(factfile)),
+                    ..Host::new(constraint::gethostname_safe().ok())
+                });
+
+            let runs = match options.history {
+                Some(ref history) => {
+                    match history.open().load_runs(&job.name) {
+                        Ok(runs) => runs,
+                        Err(msg) => {
+                            warn!("Couldn't load the run history: {}", msg);
+                            println!("{}",
+                                     format!("Warning: couldn't load the run history, circuit \
+                                              breakers and duration baselines are disabled for \
+                                              this run. Reason: {}",
+                                             msg)
+                                         .yellow());
+                            vec![]
+                        }
+                    }
+                }
+                None => vec![],
+            };
+
+            if options.cache {
+                cache::apply(&mut job, &runs);
+            }
+
+            let mut update_consumers = vec![];
+
+            let maybe_join_handle = if let Some(url) = options.webhook_url {
+                let secrets = audit::secret_values(&job.variables, &vec![]);
+                let mut wh = Webhook::new(job_context.clone(),
+                                          job.raw.clone(),
+                                          url,
+                                          options.max_stdouterr_size)
+                    .with_version(options.webhook_version, secrets);
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                Some(wh.connect_webhook(rx, Webhook::http_post, webhook::backoff_rand_1_minute))
+            } else {
+                None
+            };
+
+            let maybe_log_sink_handle = options.log_sink.map(|sink| {
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                match sink {
+                    LogSink::Syslog => {
+                        syslog::connect_log_sink(job_context.clone(), rx, syslog::emit_syslog)
+                    }
+                    LogSink::Journald => {
+                        syslog::connect_log_sink(job_context.clone(), rx, syslog::emit_journald)
+                    }
+                }
+            });
+
+            let maybe_transitions_handle = if options.verbosity >= Verbosity::Verbose {
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                Some(console::connect_transitions(rx))
+            } else {
+                None
+            };
+
+            let alert_webhook = options.alert_webhook.clone();
+            let maybe_duration_watch_handle = options.duration_warning_factor.and_then(|factor| {
+                let expected = durationwatch::expected_durations(&job, &runs);
+                if expected.is_empty() {
+                    return None;
+                }
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                let alert_context = job_context.clone();
+                let alert_webhook = alert_webhook.clone();
+                Some(durationwatch::connect_duration_watch(expected,
+                                                           factor,
+                                                           rx,
+                                                           Duration::from_secs(1),
+                                                           move |alert| {
+                                                               warn_duration_exceeded(&alert_context,
+                                                                                      &alert_webhook,
+                                                                                      factor,
+                                                                                      alert)
+                                                           }))
+            });
+
+            let maybe_sla_handle = job.sla.as_ref().map(|job_sla| {
+                let deadline = sla::deadline(job_sla, &job_context.start_time);
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                let alert_context = job_context.clone();
+                let alert_webhook = alert_webhook.clone();
+                (deadline,
+                 sla::connect_sla_watch(deadline, rx, move |deadline| {
+                    warn_sla_breached(&alert_context, &alert_webhook, deadline)
+                }))
+            });
+
+            let mut output_consumers = vec![];
+
+            let heartbeat_webhook = options.heartbeat_webhook.clone();
+            let maybe_heartbeat_handle = options.heartbeat.map(|interval| {
+                let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+                update_consumers.push(tx);
+                let output_rx = task_output.as_ref().map(|_| {
+                    let (tx, rx) = mpsc::channel::<TaskOutput>();
+                    output_consumers.push(tx);
+                    rx
+                });
+                let heartbeat_context = job_context.clone();
+                let heartbeat_webhook = heartbeat_webhook.clone();
+                heartbeat::connect_heartbeat(interval,
+                                             rx,
+                                             output_rx,
+                                             Duration::from_secs(1),
+                                             move |beat| {
+                                                 report_heartbeat(&heartbeat_context,
+                                                                  &heartbeat_webhook,
+                                                                  beat)
+                                             })
+            });
+
+            let maybe_updates_channel = broadcast_updates(update_consumers);
+
+            let maybe_console_handle = match (options.output_mode, task_output.is_some()) {
+                (Some(OutputMode::Stream), true) => {
+                    let (tx, rx) = mpsc::channel::<TaskOutput>();
+                    output_consumers.push(tx);
+                    Some(console::connect_console(rx))
+                }
+                _ => None,
+            };
+
+            let maybe_cloudwatch_handle = match (options.cloudwatch_group.clone(),
+                                                 task_output.is_some()) {
+                (Some(group), true) => {
+                    let (tx, rx) = mpsc::channel::<TaskOutput>();
+                    output_consumers.push(tx);
+                    Some(cloudwatch::connect_cloudwatch(group,
+                                                        job_context.run_reference.clone(),
+                                                        rx,
+                                                        cloudwatch::AwsCliLogsClient))
+                }
+                _ => None,
+            };
+
+            if let Some(output_rx) = task_output {
+                broadcast_output(output_rx, output_consumers);
+            }
+
+            let open_circuits = circuitbreaker::get_open_circuits(&job, &runs);
+
+            if options.show_plan {
+                let planned = factotum::executor::plan_execution(&job,
+                                                                 start_from.clone(),
+                                                                 &open_circuits);
+                println!("{}\n", plan::format_plan(&job.name, &planned));
+            }
+
+            let maybe_control_socket = options.control.as_ref().and_then(|running| {
+                let path = control::socket_path(&job_context.run_reference);
+                match control::listen(&path, running.clone()) {
+                    Ok(socket) => Some(socket),
+                    Err(msg) => {
+                        warn!("The run can't be cancelled with `factotum cancel`: {}", msg);
+                        None
+                    }
+                }
+            });
+
+            let run_env = if options.simulated { None } else { Some(job_context.clone()) };
+            let strategy = taskenv::with_run_env(run_env, strategy);
+            let job_res = factotum::executor::execute_factfile(&job,
+                                                               start_from,
+                                                               &open_circuits,
+                                                               strategy,
+                                                               maybe_updates_channel);
+
+            if let Some(socket) = maybe_control_socket {
+                socket.close();
+            }
+
+            if let Some(handle) = maybe_transitions_handle {
+                handle.join().ok();
+            }
+
+            let mut has_errors = false;
+            let mut has_early_finish = false;
+
+            let mut tasks = vec![];
+
+            for task_group in job_res.tasks.iter() {
+                for task in task_group {
+                    if let State::Failed(_) = task.state {
+                        has_errors = true;
+                    } else if let State::SuccessNoop = task.state {
+                        has_early_finish = true;
+                    }
+                    tasks.push(task);
+                }
+            }
+
+            let normal_completion = !has_errors && !has_early_finish;
+            // output that was printed as the tasks ran isn't printed again
+            let show_output = options.output_mode.is_none();
+            let quiet = options.verbosity == Verbosity::Quiet;
+
+            let result = if normal_completion {
+                if !quiet {
+                    let (stdout_summary, stderr_summary) = get_task_results_str(&tasks,
+                                                                                show_output);
+                    print!("{}", stdout_summary);
+                    if !stderr_summary.trim_right().is_empty() {
+                        print_err!("{}", stderr_summary.trim_right());
+                    }
+                }
+                PROC_SUCCESS
+            } else if has_early_finish && !has_errors && quiet {
+                PROC_SUCCESS
+            } else if has_early_finish && !has_errors {
+                let (stdout_summary, stderr_summary) = get_task_results_str(&tasks, show_output);
+                print!("{}", stdout_summary);
+                if !stderr_summary.trim_right().is_empty() {
+                    print_err!("{}", stderr_summary.trim_right());
+                }
+                let incomplete_tasks = tasks.iter()
+                    .filter(|r| !r.run_result.is_some())
+                    .map(|r| format!("'{}'", r.name.cyan()))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let stop_requesters = tasks.iter()
+                    .filter(|r| match r.state {
+                        State::SuccessNoop => true,
+                        _ => false,
+                    })
+                    .map(|r| format!("'{}'", r.name.cyan()))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                println!("Factotum job finished early as a task ({}) requested an early finish. \
+                          The following tasks were not run: {}.",
+                         stop_requesters,
+                         incomplete_tasks);
+                PROC_SUCCESS
+            } else {
+                let (stdout_summary, stderr_summary) = if quiet {
+                    get_failed_task_results_str(&tasks, show_output)
+                } else {
+                    get_task_results_str(&tasks, show_output)
+                };
+                print!("{}", stdout_summary);
+
+                if !stderr_summary.trim_right().is_empty() {
+                    print_err!("{}", stderr_summary.trim_right());
+                }
+
+                let incomplete_tasks = tasks.iter()
+                    .filter(|r| !r.run_result.is_some())
+                    .map(|r| format!("'{}'", r.name.cyan()))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                let failed = tasks.iter()
+                    .filter(|r| match r.state {
+                        State::Failed(_) => true,
+                        _ => false,
+                    })
+                    .collect::<Vec<_>>();
+
+                let failed_tasks = failed.iter()
+                    .map(|r| match r.task_spec.owner {
+                        Some(ref owner) => format!("'{}', owned by {}", r.name.cyan(), owner),
+                        None => format!("'{}'", r.name.cyan()),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ");
+
+                if let Some(ref url) = alert_webhook {
+                    for task in failed.iter() {
+                        post_alert(url, &factotum::report::failure_alert_json(&job_context, task));
+                    }
+                }
+
+                println!("Factotum job executed abnormally as a task ({}) failed - the following \
+                          tasks were not run: {}!",
+                         failed_tasks,
+                         incomplete_tasks);
+                PROC_EXEC_ERROR
+            };
+
+            if maybe_join_handle.is_some() {
+                if !quiet {
+                    print!("Waiting for webhook to finish sending events...");
+                }
+                let j = maybe_join_handle.unwrap();
+                let webhook_res = j.join().ok().unwrap();
+                if !quiet {
+                    println!("{}", " done!".green());
+                }
+
+                if webhook_res.events_received > webhook_res.success_count {
+                    println!("{}", "Warning: some events failed to send".red());
+                }
+            }
+
+            if let Some(handle) = maybe_log_sink_handle {
+                let failures = handle.join().ok().unwrap();
+                if failures > 0 {
+                    println!("{}",
+                             format!("Warning: {} lifecycle messages failed to write to the log \
+                                      sink",
+                                     failures)
+                                 .red());
+                }
+            }
+
+            if let Some(handle) = maybe_duration_watch_handle {
+                let alerts = handle.join().ok().unwrap();
+                if !alerts.is_empty() {
+                    println!("{}",
+                             format!("Warning: {} task(s) ran longer than expected: {}",
+                                     alerts.len(),
+                                     alerts.iter()
+                                         .map(|a| format!("'{}'", a.task_name))
+                                         .collect::<Vec<String>>()
+                                         .join(", "))
+                                 .yellow());
+                }
+            }
+
+            for task in tasks.iter() {
+                let missing = manifest::missing(&task.artifacts);
+                if !missing.is_empty() {
+                    println!("{}",
+                             format!("Warning: the task '{}' didn't produce: {}",
+                                     task.name,
+                                     missing.iter()
+                                         .map(|p| format!("'{}'", p))
+                                         .collect::<Vec<String>>()
+                                         .join(", "))
+                                 .yellow());
+                }
+            }
+
+            if let Some(handle) = maybe_console_handle {
+                handle.join().ok();
+            }
+
+            if let Some(handle) = maybe_heartbeat_handle {
+                let beats = handle.join().ok().unwrap();
+                info!("{} heartbeat(s) were sent", beats);
+            }
+
+            if let Some((deadline, handle)) = maybe_sla_handle {
+                if handle.join().ok().unwrap() {
+                    println!("{}",
+                             format!("Warning: the job finished after its sla deadline of {}",
+                                     deadline)
+                                 .red());
+                }
+            }
+
+            if let Some(handle) = maybe_cloudwatch_handle {
+                if !quiet {
+                    print!("Waiting for CloudWatch Logs to finish sending task output...");
+                }
+                let cloudwatch_res = handle.join().ok().unwrap();
+                if !quiet {
+                    println!("{}", " done!".green());
+                }
+
+                if cloudwatch_res.lines_sent < cloudwatch_res.lines_received {
+                    println!("{}",
+                             format!("Warning: {} lines of task output failed to send",
+                                     cloudwatch_res.lines_received - cloudwatch_res.lines_sent)
+                                 .red());
+                }
+            }
+
+            if let Some(ref history) = options.history {
+                let store = history.open();
+                if let Err(msg) = history::record_run(&*store,
+                                                      &job_context,
+                                                      &tasks,
+                                                      Some(&source)) {
+                    warn!("Failed to record the run in the run history: {}", msg);
+                    println!("{}",
+                             format!("Warning: the run couldn't be recorded in the run history. \
+                                      Reason: {}",
+                                     msg)
+                                 .red());
+                }
+                if !options.history_retention.is_unlimited() {
+                    if let Err(msg) = store.prune(&options.history_retention, &chrono::UTC::now()) {
+                        warn!("Failed to prune the run history: {}", msg);
+                        println!("{}",
+                                 format!("Warning: the run history couldn't be pruned. \
+                                          Reason: {}",
+                                         msg)
+                                     .red());
+                    }
+                }
+            }
+
+            if let Some(ref result_file) = options.result_file {
+                if let Err(msg) = write_result_file(result_file, &job_context, &tasks) {
+                    warn!("Failed to write the run result: {}", msg);
+                    println!("{}",
+                             format!("Warning: the run result couldn't be written. Reason: {}",
+                                     msg)
+                                 .red());
+                }
+            }
+
+            if let Some(ref location) = options.archive_location {
+                let archiving = format!("Archiving run to {}...",
+                                        location.for_run(&job_context.run_reference));
+                if !quiet {
+                    print!("{}", archiving);
+                }
+                match archive::archive_run(location,
+                                           &archive::run_dir(&job_context.run_reference),
+                                           &job_context,
+                                           &tasks,
+                                           &*archive::get_backend(location)) {
+                    Ok(_) if quiet => (),
+                    Ok(_) => println!("{}", " done!".green()),
+                    Err(msg) => {
+                        warn!("Failed to archive run: {}", msg);
+                        if quiet {
+                            print!("{}", archiving);
+                        }
+                        println!("{}", format!(" failed! Reason: {}", msg).red());
+                    }
+                }
+            }
+
+            if let (Some(ref policy), Some(ref run_dir)) = (options.workspace.as_ref(),
+                                                            run_workspace.as_ref()) {
+                if workspace::should_remove(&policy.retention, result == PROC_SUCCESS) {
+                    if let Err(msg) = workspace::remove(run_dir) {
+                        warn!("Failed to remove the workspace: {}", msg);
+                        println!("{}",
+                                 format!("Warning: the workspace couldn't be removed. Reason: {}",
+                                         msg)
+                                     .red());
+                    }
+                } else if result != PROC_SUCCESS {
+                    println!("The run's workspace was kept at '{}'", run_dir.display());
+                }
+            }
+
+            result
+        } 
+        Err(msg) => {
+            println!("{}", msg);
+            return parse_error_code(&msg);
+        }      
+    }
+}
+
+fn backfill(factfile: &str,
+            env: Option<Json>,
+            from: &str,
+            to: &str,
+            var_name: &str,
+            parallelism: usize,
+            options: RunOptions)
+            -> i32 {
+    let dates = match backfill::date_range(from, to) {
+        Ok(dates) => dates,
+        Err(msg) => {
+            println!("{}",
+                     format!("Error: the backfill dates are invalid. Reason: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    if parallelism == 0 {
+        println!("{}", "Error: --parallelism must be at least 1".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    let date_count = dates.len();
+    let queue = Arc::new(Mutex::new(dates.into_iter()));
+    let (tx, rx) = mpsc::channel::<(NaiveDate, i32)>();
+
+    for _ in 0..cmp::min(parallelism, date_count) {
+        let queue = queue.clone();
+        let tx = tx.clone();
+        let factfile = factfile.to_string();
+        let env = env.clone();
+        let var_name = var_name.to_string();
+        let options = options.clone();
+
+        thread::spawn(move || {
+            loop {
+                // dates that haven't started are dropped once the backfill is aborted
+                let next = if interrupt::received().is_some() {
+                    None
+                } else {
+                    queue.lock().unwrap().next()
+                };
+                match next {
+                    Some(date) => {
+                        println!("Backfilling {} for {}...",
+                                 factfile.cyan(),
+                                 date.format(backfill::DATE_FORMAT));
+                        let result = parse_file_and_execute(&factfile,
+                                                            Some(backfill::env_for_date(&env,
+                                                                                        &var_name,
+                                                                                        &date)),
+                                                            None,
+                                                            options.clone());
+                        tx.send((date, result)).unwrap();
+                    }
+                    None => break,
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    let mut results = rx.iter().collect::<Vec<(NaiveDate, i32)>>();
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+
+    println!("Backfill summary:");
+    for &(ref date, result) in results.iter() {
+        let outcome = if result == PROC_SUCCESS {
+            "succeeded".green()
+        } else {
+            "failed".red()
+        };
+        println!("  {}: {}", date.format(backfill::DATE_FORMAT), outcome);
+    }
+
+    if interrupt::received().is_some() {
+        return PROC_ABORTED;
+    }
+
+    results.iter()
+        .map(|&(_, result)| result)
+        .find(|result| *result != PROC_SUCCESS)
+        .unwrap_or(PROC_SUCCESS)
+}
+
+// runs until it's stopped: a job that's interrupted isn't acknowledged, so it's delivered again
+fn worker(queue: &str, env: Option<Json>, parallelism: usize, options: RunOptions) -> i32 {
+    let source = match queue::parse_queue(queue) {
+        Ok(source) => source,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    if parallelism == 0 {
+        println!("{}", "Error: --parallelism must be at least 1".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    interrupt::install_handlers();
+    let client = Arc::new(queue::client(&source));
+    println!("Waiting for jobs on {}...", queue.cyan());
+
+    let workers = (0..parallelism)
+        .map(|_| {
+            let client = client.clone();
+            let env = env.clone();
+            let options = options.clone();
+            thread::spawn(move || {
+                while interrupt::received().is_none() {
+                    match client.receive() {
+                        Ok(Some(message)) => run_queued_job(&**client, &message, &env, &options),
+                        Ok(None) => {}
+                        Err(msg) => {
+                            warn!("Couldn't receive a job from the queue: {}", msg);
+                            println!("{}",
+                                     format!("Warning: couldn't receive a job from the queue. \
+                                              Reason: {}",
+                                             msg)
+                                         .yellow());
+                            thread::sleep(Duration::from_secs(queue::POLL_WAIT_SECS));
+                        }
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    PROC_SUCCESS
+}
+
+// messages that aren't jobs are acknowledged too, as they'd only ever fail again
+fn run_queued_job(client: &dyn queue::QueueClient,
+                  message: &queue::Message,
+                  env: &Option<Json>,
+                  options: &RunOptions) {
+    let result = queue::parse_job_message(&message.body).and_then(|job| {
+        let factfile = if queue::is_url(&job.factfile) {
+            try!(queue::fetch_factfile(&job.factfile))
+        } else {
+            PathBuf::from(&job.factfile)
+        };
+        println!("Running {} from the queue...", job.factfile.cyan());
+        let result = parse_file_and_execute(&factfile.to_string_lossy(),
+                                            Some(queue::job_env(env, &job)),
+                                            None,
+                                            options.clone());
+        if queue::is_url(&job.factfile) {
+            let _ = fs::remove_file(&factfile);
+        }
+        Ok((job.factfile, result))
+    });
+
+    match result {
+        Ok((_, PROC_ABORTED)) => return,
+        Ok((factfile, result)) => {
+            let outcome = if result == PROC_SUCCESS {
+                "succeeded".green()
+            } else {
+                "failed".red()
+            };
+            println!("Job {} from the queue {}", factfile.cyan(), outcome);
+        }
+        Err(msg) => {
+            warn!("Couldn't run a job from the queue: {}", msg);
+            println!("{}",
+                     format!("Couldn't run a job from the queue. Reason: {}", msg).red());
+        }
+    }
+
+    if let Err(msg) = client.acknowledge(message) {
+        warn!("Couldn't acknowledge a job on the queue: {}", msg);
+        println!("{}",
+                 format!("Warning: couldn't acknowledge the job, so it may be run again. \
+                          Reason: {}",
+                         msg)
+                     .yellow());
+    }
+}
+
+fn broadcast_updates(mut consumers: Vec<mpsc::Sender<ExecutionUpdate>>)
+                     -> Option<mpsc::Sender<ExecutionUpdate>> {
+    match consumers.len() {
+        0 => None,
+        1 => consumers.pop(),
+        _ => {
+            let (tx, rx) = mpsc::channel::<ExecutionUpdate>();
+            thread::spawn(move || {
+                for update in rx.iter() {
+                    for consumer in consumers.iter() {
+                        let _ = consumer.send(update.clone());
+                    }
+                }
+            });
+            Some(tx)
+        }
+    }
+}
+
+// the task output channel is bounded, so it's always drained even with no consumers
+fn broadcast_output(output_rx: mpsc::Receiver<TaskOutput>,
+                    consumers: Vec<mpsc::Sender<TaskOutput>>) {
+    thread::spawn(move || {
+        for line in output_rx.iter() {
+            for consumer in consumers.iter() {
+                let _ = consumer.send(line.clone());
+            }
+        }
+    });
+}
+
+fn write_to_file(filename: &str, contents: &str, overwrite: bool) -> Result<(), String> {
+    let mut f = if overwrite {
+        match OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(filename) {
+            Ok(f) => f,
+            Err(io) => return Err(format!("couldn't create file '{}' ({})", filename, io)),        
+        }
+    } else {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(filename) {
+            Ok(f) => f,
+            Err(io) => return Err(format!("couldn't create file '{}' ({})", filename, io)),        
+        }
+    };
+
+    match f.write_all(contents.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(msg) => Err(format!("couldn't write to file '{}' ({})", filename, msg)),
+    }
+}
+
+fn is_valid_url(url: &str) -> Result<(), String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        match Url::parse(url) {
+            Ok(_) => Ok(()),
+            Err(msg) => Err(format!("{}", msg)),
+        }
+    } else {
+        Err("URL must begin with 'http://' or 'https://' to be used with Factotum webhooks".into())
+    }
+}
+
+// unlike tags, a constraint's value can itself contain commas (e.g. a window's timezone)
+fn get_constraint_map(constraints: &Vec<String>) -> HashMap<String, String> {
+    constraints.iter()
+        .filter_map(|c| {
+            let mut split = c.splitn(2, ',');
+            let key = split.next().unwrap_or("").trim();
+            if key.is_empty() {
+                None
+            } else {
+                Some((key.to_string(), split.next().unwrap_or("").trim().to_string()))
+            }
+        })
+        .collect()
+}
+
+// checked before the Factfile is parsed, so nothing in an unreviewed one is acted on
+fn require_signature(factfile: &str,
+                     requirement: &SignatureRequirement,
+                     verifier: &dyn Verifier)
+                     -> i32 {
+    match signature::verify_factfile(Path::new(factfile), requirement, verifier) {
+        Ok(signer) => {
+            info!("'{}' has a good signature by '{}'", factfile, signer);
+            println!("{}",
+                     format!("The Factfile's signature by '{}' is good", signer).green());
+            PROC_SUCCESS
+        }
+        Err(msg) => {
+            warn!("The Factfile's signature couldn't be verified: {}", msg);
+            println!("{}",
+                     format!("Error: the Factfile's signature couldn't be verified, no tasks \
+                              have been executed. Reason: {}",
+                             msg)
+                         .red());
+            PROC_UNVERIFIED
+        }
+    }
+}
+
+fn get_heartbeat_interval(interval: &str) -> Result<Duration, String> {
+    let interval = try!(retry::parse_duration(interval));
+    if interval < Duration::from_secs(1) {
+        Err("the interval must be at least 1s".to_string())
+    } else {
+        Ok(interval)
+    }
+}
+
+fn get_retry_policy(retries: Option<u32>,
+                    delay: &str,
+                    max_delay: &Option<String>,
+                    jitter_percent: u32)
+                    -> Result<Option<RetryPolicy>, String> {
+    let delay = try!(retry::parse_duration(delay));
+    let max_delay = match *max_delay {
+        Some(ref d) => Some(try!(retry::parse_duration(d))),
+        None => None,
+    };
+
+    match retries {
+        Some(retries) => {
+            let mut policy = try!(RetryPolicy::new(retries, delay).with_jitter(jitter_percent));
+            if let Some(max_delay) = max_delay {
+                if max_delay < delay {
+                    return Err("the maximum retry delay is shorter than the retry delay"
+                        .to_string());
+                }
+                policy = policy.with_max_delay(max_delay);
+            }
+            Ok(Some(policy))
+        }
+        None => Ok(None),
+    }
+}
+
+fn get_chaos_policy(fail_percent: Option<u32>,
+                    tasks: &Option<Vec<String>>,
+                    exit_code: i32)
+                    -> Result<Option<ChaosPolicy>, String> {
+    if fail_percent.is_none() && tasks.is_none() {
+        return Ok(None);
+    }
+
+    let policy = try!(ChaosPolicy::new(exit_code).with_fail_percent(fail_percent.unwrap_or(0)));
+    Ok(Some(policy.with_tasks(tasks.clone().unwrap_or(vec![]))))
+}
+
+fn get_spill_policy(threshold: Option<usize>, dir: &Option<String>) -> Result<SpillPolicy, String> {
+    let default = SpillPolicy::default();
+    let threshold = threshold.unwrap_or(default.threshold);
+    if threshold == 0 {
+        return Err("the spill threshold must be greater than zero".to_string());
+    }
+
+    Ok(SpillPolicy {
+        threshold: threshold,
+        dir: dir.as_ref().map(PathBuf::from).unwrap_or(default.dir),
+    })
+}
+
+fn get_workspace_policy(dir: &Option<String>,
+                        retention: &Option<String>)
+                        -> Result<WorkspacePolicy, String> {
+    let retention = match *retention {
+        Some(ref retention) => try!(workspace::parse_retention(retention)),
+        None => Retention::DeleteOnSuccess,
+    };
+    // tasks can change directory, so they're given an absolute path
+    let root = PathBuf::from(dir.as_ref().map(|d| d.as_str()).unwrap_or(workspace::WORKSPACES_DIR));
+    let root = if root.is_absolute() {
+        root
     } else {
-        Ok(external_addrs)
+        try!(env::current_dir()
+                .map_err(|e| format!("couldn't get the current directory ({})", e)))
+            .join(root)
+    };
+
+    Ok(WorkspacePolicy {
+        root: root,
+        retention: retention,
+    })
+}
+
+fn get_var_map(args: &Vec<String>) -> Result<BTreeMap<String, String>, String> {
+    let mut var_map = BTreeMap::new();
+
+    for arg in args.iter() {
+        match arg.find('=') {
+            Some(idx) if !arg[..idx].trim().is_empty() => {
+                var_map.insert(arg[..idx].trim().to_string(), arg[idx + 1..].to_string());
+            }
+            _ => {
+                return Err(format!("the variable \"{}\" must be given as <name>=<value>", arg))
+            }
+        }
     }
+
+    Ok(var_map)
+}
+
+#[test]
+fn test_var_map() {
+    let vars = get_var_map(&vec!["region=eu-west-1".to_string(),
+                                 " query = a=b ".to_string(),
+                                 "empty=".to_string()])
+        .unwrap();
+    let mut expected = BTreeMap::new();
+    expected.insert("region".to_string(), "eu-west-1".to_string());
+    expected.insert("query".to_string(), " a=b ".to_string());
+    expected.insert("empty".to_string(), "".to_string());
+    assert_eq!(vars, expected);
+
+    assert_eq!(get_var_map(&vec!["region".to_string()]),
+               Err("the variable \"region\" must be given as <name>=<value>".to_string()));
+    assert!(get_var_map(&vec!["=value".to_string()]).is_err());
 }
 
 fn get_tag_map(args: &Vec<String>) -> HashMap<String, String> {
@@ -639,52 +2782,494 @@ fn get_tag_map(args: &Vec<String>) -> HashMap<String, String> {
             let value = "".to_string();
             arg_map.insert(key, value);
         }
-    }
+    }
+
+    arg_map
+}
+
+#[test]
+fn settings_fill_in_missing_options() {
+    let parse_args = |argv: Vec<&str>| -> Args {
+        Docopt::new(USAGE).and_then(|d| d.argv(argv.into_iter()).decode()).unwrap()
+    };
+    let settings = config::Settings {
+        webhook: Some("https://hooks.acme.com".to_string()),
+        parallelism: Some(4),
+        tags: Some(vec!["team,data".to_string()]),
+        ..config::Settings::default()
+    };
+
+    let mut args = parse_args(vec!["factotum", "backfill", "x.factfile", "--from=2024-01-01",
+                                   "--to=2024-01-02", "--tag=team,ops"]);
+    apply_settings(&mut args, &settings);
+    assert_eq!(args.flag_webhook, Some("https://hooks.acme.com".to_string()));
+    assert_eq!(args.flag_parallelism, Some(4));
+    assert_eq!(args.flag_tag, Some(vec!["team,ops".to_string()]));
+
+    let mut dry_run = parse_args(vec!["factotum", "run", "x.factfile", "--dry-run"]);
+    apply_settings(&mut dry_run, &settings);
+    assert_eq!(dry_run.flag_webhook, None);
+}
+
+#[test]
+fn verbosity_flags() {
+    let parse_args = |argv: Vec<&str>| -> Args {
+        Docopt::new(USAGE).and_then(|d| d.argv(argv.into_iter()).decode()).unwrap()
+    };
+    let args = parse_args(vec!["factotum", "run", "x.factfile", "-vv"]);
+    assert_eq!(args.flag_verbose, 2);
+    assert!(!args.flag_quiet);
+    let args = parse_args(vec!["factotum", "rerun", "abc", "--verbose"]);
+    assert_eq!(args.flag_verbose, 1);
+    assert!(parse_args(vec!["factotum", "backfill", "x.factfile", "--from=2024-01-01",
+                            "--to=2024-01-02", "-q"])
+        .flag_quiet);
+    assert!(Docopt::new(USAGE)
+        .and_then(|d| {
+            d.argv(vec!["factotum", "run", "x.factfile", "-q", "-v"].into_iter()).decode::<Args>()
+        })
+        .is_err());
+
+    // on its own, -v still asks for the version
+    let args = parse_args(vec!["factotum", "-v"]);
+    assert_eq!(args.flag_verbose, 1);
+    assert!(!args.cmd_run);
+    assert!(parse_args(vec!["factotum", "--version"]).flag_version);
+}
+
+#[test]
+fn worker_takes_a_queue() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| {
+            d.argv(vec!["factotum", "worker", "redis://localhost/jobs", "--parallelism=4"]
+                    .into_iter())
+                .decode()
+        })
+        .unwrap();
+    assert!(args.cmd_worker);
+    assert_eq!(args.arg_queue, "redis://localhost/jobs");
+    assert_eq!(args.flag_parallelism, Some(4));
+}
+
+#[test]
+fn history_prune_takes_retention_limits() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| {
+            d.argv(vec!["factotum", "history", "prune", "--history-keep=90d",
+                        "--history-max-runs=500"]
+                    .into_iter())
+                .decode()
+        })
+        .unwrap();
+    assert!(args.cmd_history && args.cmd_prune);
+    assert_eq!(args.flag_history_keep, Some("90d".to_string()));
+    assert_eq!(args.flag_history_max_runs, Some(500));
+}
+
+#[test]
+fn stream_and_group_output_are_exclusive() {
+    let parse_args = |argv: Vec<&str>| -> Result<Args, docopt::Error> {
+        Docopt::new(USAGE).and_then(|d| d.argv(argv.into_iter()).decode())
+    };
+    assert!(parse_args(vec!["factotum", "run", "x.factfile", "--stream-output"])
+        .unwrap()
+        .flag_stream_output);
+    assert!(parse_args(vec!["factotum", "run", "x.factfile", "--group-output"])
+        .unwrap()
+        .flag_group_output);
+    assert!(parse_args(vec!["factotum", "run", "x.factfile", "--stream-output",
+                            "--group-output"])
+        .is_err());
+}
+
+#[test]
+fn cancel_takes_an_optional_task() {
+    let parse_args = |argv: Vec<&str>| -> Args {
+        Docopt::new(USAGE).and_then(|d| d.argv(argv.into_iter()).decode()).unwrap()
+    };
+    let args = parse_args(vec!["factotum", "cancel", "abc"]);
+    assert!(args.cmd_cancel);
+    assert_eq!(args.arg_run_id, "abc");
+    assert_eq!(args.arg_task, "");
+    let args = parse_args(vec!["factotum", "cancel", "abc", "load"]);
+    assert_eq!(args.arg_task, "load");
+}
+
+#[test]
+fn history_export_defaults_to_csv() {
+    let path = env::temp_dir().join("factotum-export-test-history.jsonl");
+    {
+        let mut f = fs::File::create(&path).unwrap();
+        writeln!(f,
+                 "{{\"jobName\":\"my job\",\"runReference\":\"a\",\
+                  \"startTime\":\"2024-03-09T00:00:00Z\",\"runState\":\"SUCCESS\",\
+                  \"tasks\":[]}}")
+            .unwrap();
+    }
+    let store = history::FileStore { path: path.clone() };
+    let now = chrono::UTC::now();
+    let csv = export_history(&store, &None, "table", &now).unwrap();
+    assert!(csv.ends_with("my job,a,2024-03-09T00:00:00Z,SUCCESS,,,,,\n"));
+    assert_eq!(export_history(&store, &Some("1d".to_string()), "csv", &now),
+               Ok(format!("{}\n", history::CSV_HEADER)));
+    assert!(export_history(&store, &Some("soon".to_string()), "csv", &now).is_err());
+    assert!(export_history(&store, &None, "xml", &now).is_err());
+    assert!(fs::remove_file(&path).is_ok());
+}
+
+#[test]
+fn webhook_version_is_a_run_backfill_and_rerun_option() {
+    let parse_args = |argv: Vec<&str>| -> Args {
+        Docopt::new(USAGE).and_then(|d| d.argv(argv.into_iter()).decode()).unwrap()
+    };
+    let argvs = vec![vec!["factotum", "run", "x.factfile"],
+                     vec!["factotum", "backfill", "x.factfile", "--from=2024-01-01",
+                          "--to=2024-01-02"],
+                     vec!["factotum", "rerun", "abc"]];
+    for mut argv in argvs {
+        argv.extend(vec!["--webhook=https://hooks.acme.com", "--webhook-version=2"]);
+        assert_eq!(parse_args(argv).flag_webhook_version, Some("2".to_string()));
+    }
+}
+
+#[test]
+fn test_tag_map() {
+    let easy = get_tag_map(&vec!["hello,world".to_string()]);
+    let mut expected_easy = HashMap::new();
+    expected_easy.insert("hello".to_string(), "world".to_string());
+    assert_eq!(easy, expected_easy);
+
+    let trim_leading_trailing = get_tag_map(&vec!["  hello   ,  world   ".to_string()]);
+    assert_eq!(trim_leading_trailing, expected_easy);
+
+    let missing_value = get_tag_map(&vec!["  hello   ".to_string()]);
+    let mut expected_missing_value = HashMap::new();
+    expected_missing_value.insert("hello".to_string(), "".to_string());
+    assert_eq!(missing_value, expected_missing_value);
+
+    let empty = get_tag_map(&vec![" ".to_string()]);
+    assert_eq!(empty, HashMap::new());
+
+    let empty_key = get_tag_map(&vec![" , asdas".to_string()]);
+    assert_eq!(empty_key, HashMap::new());
+
+    let with_comma = get_tag_map(&vec!["the rain,first,, wow,,".to_string()]);
+    let mut expected_comma = HashMap::new();
+    expected_comma.insert("the rain".to_string(), "first wow".to_string());
+    assert_eq!(with_comma, expected_comma);
+}
+
+fn json_str_to_btreemap(j: &str) -> Result<BTreeMap<String, Json>, String> {
+    envfile::env_from_json(try!(str_to_json(j)), "--env")
+}
+
+fn str_to_json(s: &str) -> Result<Json, String> {
+    Json::from_str(s).map_err(|err| {
+        format!("Supplied string '{}' is not valid JSON: {}",
+                s,
+                Error::description(&err))
+    })
+}
+
+#[test]
+fn test_get_chaos_policy() {
+    assert_eq!(get_chaos_policy(None, &None, 1), Ok(None));
+    assert_eq!(get_chaos_policy(Some(10), &None, 2),
+               Ok(Some(ChaosPolicy {
+                   fail_percent: 10,
+                   tasks: vec![],
+                   exit_code: 2,
+               })));
+    assert_eq!(get_chaos_policy(None, &Some(vec!["load".to_string()]), 1),
+               Ok(Some(ChaosPolicy {
+                   fail_percent: 0,
+                   tasks: vec!["load".to_string()],
+                   exit_code: 1,
+               })));
+    assert!(get_chaos_policy(Some(150), &None, 1).is_err());
+}
+
+#[test]
+fn test_get_heartbeat_interval() {
+    assert_eq!(get_heartbeat_interval("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(get_heartbeat_interval("0s"),
+               Err("the interval must be at least 1s".to_string()));
+    assert!(get_heartbeat_interval("soon").is_err());
+}
+
+#[test]
+fn test_get_spill_policy() {
+    assert_eq!(get_spill_policy(None, &None), Ok(SpillPolicy::default()));
+    assert_eq!(get_spill_policy(Some(1024), &Some("/mnt/scratch".to_string())),
+               Ok(SpillPolicy {
+                   threshold: 1024,
+                   dir: PathBuf::from("/mnt/scratch"),
+               }));
+    assert_eq!(get_spill_policy(Some(0), &None),
+               Err("the spill threshold must be greater than zero".to_string()));
+}
+
+#[test]
+fn test_require_signature() {
+    struct AcceptAll;
+    impl Verifier for AcceptAll {
+        fn verify(&self,
+                  _: &Path,
+                  _: &signature::Signature,
+                  _: &Option<String>)
+                  -> Result<String, String> {
+            Ok("release@acme.com".to_string())
+        }
+    }
+
+    let unsigned = SignatureRequirement::default();
+    assert_eq!(require_signature("./tests/resources/example_ok.factfile", &unsigned, &AcceptAll),
+               PROC_UNVERIFIED);
+
+    let signed = SignatureRequirement {
+        signature: Some("./tests/resources/example_ok.factfile".to_string()),
+        signer: None,
+    };
+    assert_eq!(require_signature("./tests/resources/example_ok.factfile", &signed, &AcceptAll),
+               PROC_SUCCESS);
+}
+
+#[test]
+fn test_policy_violations_stop_the_job_before_it_starts() {
+    let policy = policy::parse_policy("[executors]\nshell = [\"./acme-emr-etl-runner.sh\"]")
+        .unwrap();
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| -> RunResult {
+        panic!("the task '{}' was run", name)
+    };
+
+    let result = parse_file_and_execute_with_strategy("./tests/resources/example_ok.factfile",
+                                                      None,
+                                                      None,
+                                                      strategy,
+                                                      OverrideResultMappings::None,
+                                                      RunOptions {
+                                                          policy: Some(policy),
+                                                          ..RunOptions::default()
+                                                      },
+                                                      None);
+    assert_eq!(result, PROC_POLICY_VIOLATION);
+}
+
+#[test]
+fn test_policy_violations_stop_computed_variables_running() {
+    let policy = policy::parse_policy("[executors]\nshell = [\"./load.sh\"]").unwrap();
+    let strategy = |name: &str, _: &mut Command, _: &TaskLimits| -> RunResult {
+        panic!("the task '{}' was run", name)
+    };
+
+    // the variable's command would fail if it were run
+    let factfile = "./tests/resources/example_invalid_computed_variable.factfile";
+    let result = parse_file_and_execute_with_strategy(factfile,
+                                                      None,
+                                                      None,
+                                                      strategy,
+                                                      OverrideResultMappings::None,
+                                                      RunOptions {
+                                                          policy: Some(policy),
+                                                          ..RunOptions::default()
+                                                      },
+                                                      None);
+    assert_eq!(result, PROC_POLICY_VIOLATION);
+}
+
+#[test]
+fn test_dry_runs_of_generators_succeed() {
+    let configured = ExecutorSettings::new();
+    assert_eq!(parse_file_and_simulate("./tests/resources/example_generator.factfile",
+                                       None,
+                                       None,
+                                       vec![],
+                                       None,
+                                       false,
+                                       &configured),
+               PROC_SUCCESS);
+}
+
+#[test]
+fn test_dry_runs_leave_computed_variables_alone() {
+    let configured = ExecutorSettings::new();
+    let factfile = "./tests/resources/example_invalid_computed_variable.factfile";
+    assert_eq!(parse_file_and_simulate(factfile,
+                                       None,
+                                       None,
+                                       vec![],
+                                       None,
+                                       false,
+                                       &configured),
+               PROC_SUCCESS);
+}
+
+#[test]
+fn test_workspaces_are_made_and_cleaned_up() {
+    let root = env::temp_dir().join("factotum-workspace-test-run");
+    let seen = Arc::new(Mutex::new(vec![]));
+    let strategy = {
+        let seen = seen.clone();
+        move |name: &str, command: &mut Command, limits: &TaskLimits| -> RunResult {
+            let task_dir = command.get_envs()
+                .find(|&(k, _)| k == "FACTOTUM_TASK_WORKSPACE")
+                .and_then(|(_, v)| v)
+                .map(PathBuf::from);
+            let mut result = execute_simulation(name, command, limits);
+            if let Some(task_dir) = task_dir {
+                // the task fails if its directory hasn't been made
+                result.return_code = if task_dir.is_dir() { 0 } else { 1 };
+                seen.lock().unwrap().push(task_dir);
+            }
+            result
+        }
+    };
 
-    arg_map
+    let run = |retention: Retention| {
+        parse_file_and_execute_with_strategy("./tests/resources/example_workspace.factfile",
+                                             None,
+                                             None,
+                                             strategy.clone(),
+                                             OverrideResultMappings::None,
+                                             RunOptions {
+                                                 workspace: Some(WorkspacePolicy {
+                                                     root: root.clone(),
+                                                     retention: retention,
+                                                 }),
+                                                 ..RunOptions::default()
+                                             },
+                                             None)
+    };
+
+    assert_eq!(run(Retention::Delete), PROC_SUCCESS);
+    assert_eq!(run(Retention::Keep), PROC_SUCCESS);
+
+    let seen = seen.lock().unwrap();
+    assert_eq!(seen.len(), 2);
+    assert!(seen.iter().all(|dir| dir.starts_with(&root) && dir.ends_with("Extract")));
+    assert!(!seen[0].exists());
+    assert!(seen[1].is_dir());
+    assert!(fs::remove_dir_all(&root).is_ok());
 }
 
 #[test]
-fn test_tag_map() {
-    let easy = get_tag_map(&vec!["hello,world".to_string()]);
-    let mut expected_easy = HashMap::new();
-    expected_easy.insert("hello".to_string(), "world".to_string());
-    assert_eq!(easy, expected_easy);
+fn test_cached_tasks_are_skipped_when_unchanged() {
+    let history_file = env::temp_dir().join("factotum-cache-test-history.jsonl");
+    let _ = fs::remove_file(&history_file);
+    let ran = Arc::new(Mutex::new(vec![]));
+    let strategy = {
+        let ran = ran.clone();
+        move |name: &str, command: &mut Command, limits: &TaskLimits| -> RunResult {
+            ran.lock().unwrap().push(name.to_string());
+            execute_simulation(name, command, limits)
+        }
+    };
 
-    let trim_leading_trailing = get_tag_map(&vec!["  hello   ,  world   ".to_string()]);
-    assert_eq!(trim_leading_trailing, expected_easy);
+    let run = |cache: bool| {
+        parse_file_and_execute_with_strategy("./tests/resources/example_cache.factfile",
+                                             None,
+                                             None,
+                                             strategy.clone(),
+                                             OverrideResultMappings::None,
+                                             RunOptions {
+                                                 history: Some(HistoryStore::File(history_file
+                                                     .clone())),
+                                                 cache: cache,
+                                                 ..RunOptions::default()
+                                             },
+                                             None)
+    };
 
-    let missing_value = get_tag_map(&vec!["  hello   ".to_string()]);
-    let mut expected_missing_value = HashMap::new();
-    expected_missing_value.insert("hello".to_string(), "".to_string());
-    assert_eq!(missing_value, expected_missing_value);
+    assert_eq!(run(true), PROC_SUCCESS);
+    assert_eq!(run(true), PROC_SUCCESS);
+    assert_eq!(run(false), PROC_SUCCESS);
+    assert_eq!(*ran.lock().unwrap(),
+               vec!["Train", "Publish", "Publish", "Train", "Publish"]);
+    assert!(fs::remove_file(&history_file).is_ok());
+}
 
-    let empty = get_tag_map(&vec![" ".to_string()]);
-    assert_eq!(empty, HashMap::new());
+#[test]
+fn test_reruns_are_given_the_secrets_history_leaves_out() {
+    let configured = ExecutorSettings::new();
+    let history_file = env::temp_dir().join("factotum-rerun-test-history.jsonl");
+    let _ = fs::remove_file(&history_file);
+    let variables = Json::from_str(r#"{"db_password": "hunter2", "region": "eu"}"#).unwrap();
+    assert_eq!(parse_file_and_execute_with_strategy("./tests/resources/example_ok.factfile",
+                                                    Some(variables),
+                                                    None,
+                                                    execute_simulation,
+                                                    OverrideResultMappings::None,
+                                                    RunOptions {
+                                                        history: Some(HistoryStore::File(
+                                                            history_file.clone())),
+                                                        ..RunOptions::default()
+                                                    },
+                                                    None),
+               PROC_SUCCESS);
+
+    let mut recorded = String::new();
+    fs::File::open(&history_file).unwrap().read_to_string(&mut recorded).unwrap();
+    assert!(!recorded.contains("hunter2"));
+
+    let store = history::FileStore { path: history_file.clone() };
+    let run_reference = store.load_all_runs().unwrap()[0].run_reference.clone();
+    let err = rerun_plan(&run_reference, &store, false, None, &configured).unwrap_err();
+    assert!(err.ends_with("give them again with --env, --env-file or --var: db_password"));
+
+    let supplied = Json::from_str(r#"{"db_password": "hunter2"}"#).unwrap();
+    let (source, _) = rerun_plan(&run_reference, &store, false, Some(supplied), &configured)
+        .unwrap();
+    assert_eq!(source.variables,
+               Json::from_str(r#"{"db_password": "hunter2", "region": "eu"}"#).unwrap());
+    assert!(fs::remove_file(&history_file).is_ok());
+}
 
-    let empty_key = get_tag_map(&vec![" , asdas".to_string()]);
-    assert_eq!(empty_key, HashMap::new());
+#[test]
+fn test_end_runs_just_the_task_and_its_dependencies() {
+    let ran = Arc::new(Mutex::new(vec![]));
+    let strategy = {
+        let ran = ran.clone();
+        move |name: &str, command: &mut Command, limits: &TaskLimits| -> RunResult {
+            ran.lock().unwrap().push(name.to_string());
+            execute_simulation(name, command, limits)
+        }
+    };
 
-    let with_comma = get_tag_map(&vec!["the rain,first,, wow,,".to_string()]);
-    let mut expected_comma = HashMap::new();
-    expected_comma.insert("the rain".to_string(), "first wow".to_string());
-    assert_eq!(with_comma, expected_comma);
+    let run = |end: &str| {
+        parse_file_and_execute_with_strategy("./tests/resources/example_ok.factfile",
+                                             None,
+                                             None,
+                                             strategy.clone(),
+                                             OverrideResultMappings::None,
+                                             RunOptions {
+                                                 end: Some(end.to_string()),
+                                                 ..RunOptions::default()
+                                             },
+                                             None)
+    };
+
+    assert_eq!(run("StorageLoader"), PROC_SUCCESS);
+    assert_eq!(*ran.lock().unwrap(), vec!["EmrEtlRunner", "StorageLoader"]);
+    assert_eq!(run("Nope"), PROC_ARGS_ERROR);
 }
 
-fn json_str_to_btreemap(j: &str) -> Result<BTreeMap<String, String>, String> {
-    json::decode(j).map_err(|err| {
-        format!("Supplied string '{}' is not valid JSON: {}",
-                j,
-                Error::description(&err))
-    })
+#[test]
+fn test_spilled_output_note() {
+    assert_eq!(spilled_output_note(None), "");
+    assert_eq!(spilled_output_note(Some(&PathBuf::from("/tmp/out/a.stdout.log"))),
+               " (the end of it - all of it is in '/tmp/out/a.stdout.log')");
 }
 
-fn str_to_json(s: &str) -> Result<Json, String> {
-    Json::from_str(s).map_err(|err| {
-        format!("Supplied string '{}' is not valid JSON: {}",
-                s,
-                Error::description(&err))
-    })
+#[test]
+fn test_get_retry_policy() {
+    assert_eq!(get_retry_policy(None, "1m", &None, 0), Ok(None));
+    assert_eq!(get_retry_policy(Some(2), "30s", &Some("5m".to_string()), 20),
+               Ok(Some(RetryPolicy::new(2, Duration::from_secs(30))
+                   .with_max_delay(Duration::from_secs(300))
+                   .with_jitter(20)
+                   .unwrap())));
+    assert_eq!(get_retry_policy(Some(2), "5m", &Some("30s".to_string()), 0),
+               Err("the maximum retry delay is shorter than the retry delay".to_string()));
 }
 
 #[test]
@@ -709,38 +3294,62 @@ fn str_to_json_bad_json() {
     }
 }
 
-fn get_log_config() -> Result<log4rs::config::Config, String> {    
-    let file_appender = match log4rs::appender::FileAppender::builder(".factotum/factotum.log").build() {
+// log4rs's console appender writes to stdout, which is kept for what factotum prints itself
+struct StderrAppender {
+    pattern: log4rs::pattern::PatternLayout,
+}
+
+impl log4rs::Append for StderrAppender {
+    fn append(&mut self, record: &log::LogRecord) -> Result<(), Box<dyn std::error::Error>> {
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        try!(self.pattern.append(&mut stderr, record));
+        try!(stderr.flush());
+        Ok(())
+    }
+}
+
+fn get_log_config(log_dir: &Path,
+                  verbosity: Verbosity)
+                  -> Result<log4rs::config::Config, String> {
+    let log_file = log_dir.join("factotum.log");
+    let file_appender = match log4rs::appender::FileAppender::builder(&log_file).build() {
         Ok(fa) => fa,
         Err(e) => {
             let cwd = env::current_dir().expect("Unable to get current working directory");
-            let expanded_path = format!("{}{}{}", cwd.display(), std::path::MAIN_SEPARATOR, ".factotum/factotum.log");
-            return Err(format!("couldn't create logfile appender to '{}'. Reason: {}", expanded_path, e.description()));
+            let expanded_path = cwd.join(&log_file);
+            return Err(format!("couldn't create logfile appender to '{}'. Reason: {}", expanded_path.display(), e.description()));
         }
     };
 
-    let root = log4rs::config::Root::builder(log::LogLevelFilter::Info)
-        .appender("file".to_string());
+    let mut appenders = vec![log4rs::config::Appender::builder("file".to_string(),
+                                                               Box::new(file_appender))
+                                 .build()];
+    if verbosity == Verbosity::Debug {
+        let stderr_appender = StderrAppender { pattern: Default::default() };
+        appenders.push(log4rs::config::Appender::builder("stderr".to_string(),
+                                                         Box::new(stderr_appender))
+            .build());
+    }
+
+    let root = log4rs::config::Root::builder(verbosity.log_level())
+        .appenders(appenders.iter().map(|a| a.name().to_string()).collect::<Vec<_>>());
 
     log4rs::config::Config::builder(root.build())
-        .appender(log4rs::config::Appender::builder("file".to_string(),
-                                                    Box::new(file_appender)).build())
+        .appenders(appenders)
         .build().map_err(|e| format!("error setting logging. Reason: {}", e.description()))
 }
 
-fn init_logger() -> Result<(), String> {
-    match fs::create_dir(".factotum") {
+fn init_logger(log_dir: &Path, verbosity: Verbosity) -> Result<(), String> {
+    match fs::create_dir_all(log_dir) {
         Ok(_) => (),
-        Err(e) => match e.kind() {
-            std::io::ErrorKind::AlreadyExists => (),
-            _ => {
-                let cwd = env::current_dir().expect("Unable to get current working directory");
-                let expected_path =  format!("{}{}{}{}", cwd.display(), std::path::MAIN_SEPARATOR, ".factotum", std::path::MAIN_SEPARATOR);
-                return Err(format!("unable to create directory '{}' for logfile. Reason: {}", expected_path, e.description()))
-            }
+        Err(e) => {
+            let cwd = env::current_dir().expect("Unable to get current working directory");
+            let expected_path = cwd.join(log_dir);
+            return Err(format!("unable to create directory '{}{}' for logfile. Reason: {}", expected_path.display(), std::path::MAIN_SEPARATOR, e.description()))
         }
     };
-    let log_config = try!(get_log_config());
+    let log_config = try!(get_log_config(log_dir, verbosity));
     log4rs::init_config(log_config).map_err(|e| format!("couldn't initialize log configuration. Reason: {}", e.description()))
 }
 
@@ -748,73 +3357,544 @@ fn main() {
     std::process::exit(factotum())
 }
 
-fn factotum() -> i32 {
-    if let Err(log) = init_logger() {
-        println!("Log initialization error: {}", log);
-        return PROC_OTHER_ERROR;
+// the mustache variables from --env-file, overridden by --env, the job's tags and --var
+fn get_env_json(args: &Args, tag_map: &Option<HashMap<String, String>>) -> Result<Json, String> {
+    // Environment should always be present as tags can populate the env
+    let env_str = args.flag_env.clone().unwrap_or("{}".to_string());
+    let mut env = try!(json_str_to_btreemap(&env_str));
+
+    if let Some(ref env_file) = args.flag_env_file {
+        let mut file_env = try!(envfile::load_env_location(env_file,
+                                                           args.flag_env_file_header
+                                                               .as_ref()
+                                                               .map(|h| h.as_ref()))
+            .map_err(|e| format!("Error: {}", e)));
+        file_env.extend(env);
+        env = file_env;
+    }
+
+    if let Some(tm) = tag_map.as_ref() {
+        for (key, value) in tm {
+            let tag_key = format!("tag:{}", key.to_string());
+            env.insert(tag_key, Json::String(value.to_string()));
+        }
+    }
+
+    if let Some(ref vars) = args.flag_var {
+        let var_map = try!(get_var_map(vars).map_err(|e| format!("Error: {}", e)));
+        env.extend(var_map.into_iter().map(|(name, value)| (name, Json::String(value))));
+    }
+
+    Ok(Json::Object(env))
+}
+
+// options given on the command line win over the config file; a dry run doesn't
+// pick up the config file's webhooks or archive location as it can't use them
+fn apply_settings(args: &mut Args, settings: &config::Settings) {
+    if !args.flag_dry_run {
+        if args.flag_webhook.is_none() {
+            args.flag_webhook = settings.webhook.clone();
+        }
+        if args.flag_alert_webhook.is_none() {
+            args.flag_alert_webhook = settings.alert_webhook.clone();
+        }
+        if args.flag_archive.is_none() {
+            args.flag_archive = settings.archive.clone();
+        }
+        if args.flag_heartbeat_webhook.is_none() {
+            args.flag_heartbeat_webhook = settings.heartbeat_webhook.clone();
+        }
+    }
+    if args.flag_parallelism.is_none() {
+        args.flag_parallelism = settings.parallelism;
     }
+    if args.flag_constraint.is_none() {
+        args.flag_constraint = settings.constraints.clone();
+    }
+    if args.flag_tag.is_none() {
+        args.flag_tag = settings.tags.clone();
+    }
+    if args.flag_max_stdouterr_size.is_none() {
+        args.flag_max_stdouterr_size = settings.max_stdouterr_size;
+    }
+    if args.flag_spill_threshold.is_none() {
+        args.flag_spill_threshold = settings.spill_threshold;
+    }
+    if args.flag_spill_dir.is_none() {
+        args.flag_spill_dir = settings.spill_dir.clone();
+    }
+    if args.flag_policy.is_none() {
+        args.flag_policy = settings.policy.clone();
+    }
+    if args.flag_heartbeat.is_none() {
+        args.flag_heartbeat = settings.heartbeat.clone();
+    }
+    if args.flag_workspace_dir.is_none() {
+        args.flag_workspace_dir = settings.workspace_dir.clone();
+    }
+    if args.flag_workspace_retention.is_none() {
+        args.flag_workspace_retention = settings.workspace_retention.clone();
+    }
+    if args.flag_audit_log.is_none() {
+        args.flag_audit_log = settings.audit_log.clone();
+    }
+    if args.flag_history_store.is_none() {
+        args.flag_history_store = settings.history_store.clone();
+    }
+    if args.flag_history_keep.is_none() {
+        args.flag_history_keep = settings.history_keep.clone();
+    }
+    if args.flag_history_max_runs.is_none() {
+        args.flag_history_max_runs = settings.history_max_runs;
+    }
+    if !args.flag_require_signature {
+        args.flag_require_signature = settings.require_signature.unwrap_or(false);
+    }
+    if args.flag_signer.is_none() {
+        args.flag_signer = settings.signer.clone();
+    }
+}
 
+fn factotum() -> i32 {
     let args: Args = match Docopt::new(USAGE).and_then(|d| d.decode()) {
         Ok(a) => a,
         Err(e) => {
             print!("{}", e);
-            return PROC_OTHER_ERROR;
+            return if e.fatal() { PROC_ARGS_ERROR } else { PROC_SUCCESS };
+        }
+    };
+
+    let exit_codes = match args.flag_exit_code_map {
+        Some(ref spec) => {
+            match exitcode::parse_exit_code_map(spec) {
+                Ok(map) => map,
+                Err(msg) => {
+                    println!("{}",
+                             format!("Error: the specified exit code map is invalid. Reason: {}",
+                                     msg)
+                                 .red());
+                    return PROC_ARGS_ERROR;
+                }
+            }
+        }
+        None => ExitCodeMap::default(),
+    };
+
+    exit_codes.code_for(run_command(args))
+}
+
+fn run_command(mut args: Args) -> i32 {
+
+    if let Some(ref path) = args.flag_config {
+        // so the factotum processes a run starts read the same config
+        env::set_var(config::CONFIG_VAR, path);
+    }
+    let config_path = args.flag_config.as_ref().map(PathBuf::from).or(config::default_path());
+    let settings = match config_path {
+        Some(ref path) => {
+            match config::load_config(path, args.flag_profile.as_ref().map(|p| p.as_ref())) {
+                Ok(settings) => settings,
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_OTHER_ERROR;
+                }
+            }
+        }
+        None => config::Settings::default(),
+    };
+    apply_settings(&mut args, &settings);
+    // read once, and given to everything that parses a factfile
+    let executor_settings =
+        match config::load_executor_settings(config_path.as_ref().map(|p| p.as_path())) {
+            Ok(executor_settings) => executor_settings,
+            Err(msg) => {
+                println!("{}", format!("Error: {}", msg).red());
+                return PROC_OTHER_ERROR;
+            }
+        };
+
+    let log_dir = settings.log_dir.clone().unwrap_or(".factotum".to_string());
+    let verbosity = console::verbosity(args.flag_quiet, args.flag_verbose);
+    if let Err(log) = init_logger(Path::new(&log_dir), verbosity) {
+        println!("Log initialization error: {}", log);
+        return PROC_OTHER_ERROR;
+    }
+
+    let audit_log = match args.flag_audit_log {
+        Some(ref log) => {
+            match audit::parse_audit_log(log) {
+                Ok(log) => Some(log),
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_ARGS_ERROR;
+                }
+            }
         }
+        None => None,
     };
 
-    let tag_map = if let Some(tags) = args.flag_tag {
-        Some(get_tag_map(&tags))
+    // every invocation that can run tasks is audited, however it ends
+    match audit_log {
+        Some(ref log) if args.cmd_run || args.cmd_backfill || args.cmd_rerun ||
+                         args.cmd_worker => {
+            let started = Instant::now();
+            let mut record = audit_record(&args, &settings.audit_redact.clone().unwrap_or(vec![]));
+            let result = execute_command(args, &executor_settings);
+            record.exit_code = result;
+            record.duration_millis = started.elapsed().as_millis() as u64;
+            if let Err(msg) = audit::write_record(log, &record) {
+                warn!("Failed to write the audit record: {}", msg);
+                println!("{}",
+                         format!("Warning: the run couldn't be recorded in the audit log. \
+                                  Reason: {}",
+                                 msg)
+                             .red());
+            }
+            result
+        }
+        _ => execute_command(args, &executor_settings),
+    }
+}
+
+// the table default of --format is taken as csv
+fn export_history(store: &dyn RunStore,
+                  since: &Option<String>,
+                  format: &str,
+                  now: &chrono::DateTime<chrono::UTC>)
+                  -> Result<String, String> {
+    let format = if format == diagnostics::FORMAT_TABLE {
+        history::ExportFormat::Csv
     } else {
-        None
+        try!(history::parse_export_format(format))
     };
+    let mut runs = try!(store.load_all_runs());
+    if let Some(ref since) = *since {
+        let since = try!(retry::parse_duration(since)
+            .map_err(|_| format!("'{}' isn't a duration (expected one such as 30d)", since)));
+        runs = history::runs_since(runs, &since, now);
+    }
+    Ok(history::export_runs(&runs, &format))
+}
 
-    // Environment should always be present as tags can populate the env
-    let env_str: String = if let Some(c) = args.flag_env {
-        c
+fn history_store(args: &Args) -> HistoryStore {
+    args.flag_history_store
+        .as_ref()
+        .map(|store| history::parse_history_store(store))
+        .unwrap_or(HistoryStore::default())
+}
+
+fn audit_record(args: &Args, redact: &Vec<String>) -> AuditRecord {
+    let command = if args.cmd_rerun {
+        "rerun"
+    } else if args.cmd_backfill {
+        "backfill"
+    } else if args.cmd_worker {
+        "worker"
     } else {
-        "{}".to_string()
+        "run"
     };
 
-    let env_json: Option<Json> = {
-        match json_str_to_btreemap(&env_str) {
-            Ok(mut a) => {
-                if let Some(tm) = tag_map.as_ref() {
-                    for (key, value) in tm {
-                        let tag_key = format!("tag:{}", key.to_string());
-                        a.insert(tag_key, value.to_string());
-                    }
+    // a rerun's Factfile and variables are the ones recorded for the run
+    let (factfile, variables) = if args.cmd_rerun {
+        match history_store(args).open().find_run(&args.arg_run_id) {
+            Ok(history::RunRecord { source: Some(source), .. }) => {
+                (source.factfile, source.variables)
+            }
+            _ => (String::new(), Json::Null),
+        }
+    } else {
+        let tag_map = args.flag_tag.as_ref().map(|tags| get_tag_map(tags));
+        (fs::canonicalize(&args.arg_factfile)
+             .map(|p| p.to_string_lossy().into_owned())
+             .unwrap_or(args.arg_factfile.clone()),
+         get_env_json(args, &tag_map).unwrap_or(Json::Null))
+    };
+
+    AuditRecord {
+        timestamp: chrono::UTC::now().to_rfc3339(),
+        user: current_user(),
+        uid: unsafe { libc::getuid() },
+        host: constraint::gethostname_safe().unwrap_or("unknown".to_string()),
+        command: command.to_string(),
+        factfile_sha256: if factfile.is_empty() {
+            None
+        } else {
+            audit::factfile_checksum(Path::new(&factfile))
+        },
+        factfile: factfile,
+        variables: audit::redact(&variables, redact),
+        exit_code: PROC_SUCCESS,
+        duration_millis: 0,
+    }
+}
+
+// the account's name for the uid, as $USER can be set to anything
+fn current_user() -> String {
+    let name = unsafe {
+        let passwd = libc::getpwuid(libc::getuid());
+        if passwd.is_null() {
+            None
+        } else {
+            Some(std::ffi::CStr::from_ptr((*passwd).pw_name).to_string_lossy().into_owned())
+        }
+    };
+    name.or(env::var("USER").ok()).unwrap_or("unknown".to_string())
+}
+
+fn execute_command(args: Args, executor_settings: &ExecutorSettings) -> i32 {
+
+    let tag_map = if let Some(ref tags) = args.flag_tag {
+        Some(get_tag_map(tags))
+    } else {
+        None
+    };
+
+    let env_json: Option<Json> = match get_env_json(&args, &tag_map) {
+        Ok(env) => Some(env),
+        Err(e) => {
+            println!("{}", e.red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    if args.flag_no_colour {
+        env::set_var("CLICOLOR", "0");
+    }
+
+    // -v is --verbose, but on its own it still asks for the version
+    let runs_tasks = args.cmd_run || args.cmd_backfill || args.cmd_worker || args.cmd_rerun;
+    if args.flag_version || (args.flag_verbose > 0 && !runs_tasks) {
+        println!("Factotum version {}", VERSION);
+        return PROC_SUCCESS;
+    }
+
+    if args.flag_dry_run && args.flag_webhook.is_some() {
+        println!("{}",
+                 "Error: --webhook cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && args.flag_cloudwatch_logs.is_some() {
+        println!("{}",
+                 "Error: --cloudwatch-logs cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && args.flag_alert_webhook.is_some() {
+        println!("{}",
+                 "Error: --alert-webhook cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && (args.flag_mock_executor || args.flag_mock_results.is_some()) {
+        println!("{}",
+                 "Error: --mock-executor cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && args.flag_log_sink.is_some() {
+        println!("{}",
+                 "Error: --log-sink cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && args.flag_retry_job.is_some() {
+        println!("{}",
+                 "Error: --retry-job cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_dry_run && args.flag_archive.is_some() {
+        println!("{}",
+                 "Error: --archive cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    let archive_location = match args.flag_archive {
+        Some(ref location) => {
+            match archive::parse_archive_url(location) {
+                Ok(l) => Some(l),
+                Err(msg) => {
+                    println!("{}",
+                             format!("Error: the specified archive location \"{}\" is invalid. \
+                                      Reason: {}",
+                                     location,
+                                     msg)
+                                 .red());
+                    return PROC_ARGS_ERROR;
                 }
+            }
+        }
+        None => None,
+    };
 
-                match str_to_json(&json::encode(&a).unwrap()) {
-                    Ok(a) => {
-                        Some(a)
-                    }
-                    Err(e) => {
-                        print!("{}", e);
-                        return PROC_OTHER_ERROR;
-                    }
+    let log_sink = match args.flag_log_sink {
+        Some(ref sink) => {
+            match syslog::parse_log_sink(sink) {
+                Ok(s) => Some(s),
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_ARGS_ERROR;
                 }
             }
-            Err(e) => {
-                print!("{}", e);
-                return PROC_OTHER_ERROR;
+        }
+        None => None,
+    };
+
+    let retry_policy = match get_retry_policy(args.flag_retry_job,
+                                              &args.flag_retry_delay,
+                                              &args.flag_retry_max_delay,
+                                              args.flag_retry_jitter) {
+        Ok(policy) => policy,
+        Err(msg) => {
+            println!("{}",
+                     format!("Error: the specified retry policy is invalid. Reason: {}", msg)
+                         .red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let chaos_policy = match get_chaos_policy(args.flag_chaos,
+                                              &args.flag_chaos_task,
+                                              args.flag_chaos_exit_code) {
+        Ok(policy) => policy,
+        Err(msg) => {
+            println!("{}",
+                     format!("Error: the specified chaos settings are invalid. Reason: {}", msg)
+                         .red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let spill_policy = match get_spill_policy(args.flag_spill_threshold, &args.flag_spill_dir) {
+        Ok(policy) => policy,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let workspace_policy = match get_workspace_policy(&args.flag_workspace_dir,
+                                                      &args.flag_workspace_retention) {
+        Ok(policy) => policy,
+        Err(msg) => {
+            println!("{}", format!("Error: {}", msg).red());
+            return PROC_ARGS_ERROR;
+        }
+    };
+
+    let command_policy = match args.flag_policy {
+        Some(ref path) => {
+            match policy::load_policy(Path::new(path)) {
+                Ok(policy) => Some(policy),
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_ARGS_ERROR;
+                }
             }
         }
+        None => None,
     };
 
-    if args.flag_no_colour {
-        env::set_var("CLICOLOR", "0");
+    let result_overrides = match args.flag_override_result {
+        Some(ref specs) => {
+            match factotum::parser::parse_result_overrides(specs) {
+                Ok(overrides) => overrides,
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_ARGS_ERROR;
+                }
+            }
+        }
+        None => BTreeMap::new(),
+    };
+
+    if !result_overrides.is_empty() && args.flag_dry_run {
+        println!("{}",
+                 "Error: --override-result cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if args.flag_end.is_some() && args.flag_only.is_some() {
+        println!("{}", "Error: --end cannot be used with the --only option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if chaos_policy.is_some() && args.flag_dry_run {
+        println!("{}", "Error: --chaos cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    if let Some(ref policy) = chaos_policy {
+        let mut failing = vec![];
+        if policy.fail_percent > 0 {
+            failing.push(format!("{}% of tasks", policy.fail_percent));
+        }
+        failing.extend(policy.tasks.iter().map(|t| format!("'{}'", t)));
+        println!("{}",
+                 format!("Warning: chaos mode is on, {} will fail with the exit code {} \
+                          without being run",
+                         failing.join(" and "),
+                         policy.exit_code)
+                     .yellow());
+    }
+
+    if args.flag_duration_warning_factor <= 0.0 {
+        println!("{}",
+                 "Error: --duration-warning-factor must be greater than zero".red());
+        return PROC_ARGS_ERROR;
     }
 
-    if args.flag_version {
-        println!("Factotum version {}", VERSION);
-        return PROC_SUCCESS;
+    if args.flag_dry_run && args.flag_heartbeat_webhook.is_some() {
+        println!("{}",
+                 "Error: --heartbeat-webhook cannot be used with the --dry-run option".red());
+        return PROC_ARGS_ERROR;
+    }
+
+    let heartbeat_interval = match args.flag_heartbeat {
+        Some(ref interval) => {
+            match get_heartbeat_interval(interval) {
+                Ok(interval) => Some(interval),
+                Err(msg) => {
+                    println!("{}",
+                             format!("Error: the specified heartbeat interval is invalid. \
+                                      Reason: {}",
+                                     msg)
+                                 .red());
+                    return PROC_ARGS_ERROR;
+                }
+            }
+        }
+        None => None,
+    };
+
+    if let Some(ref wh) = args.flag_heartbeat_webhook {
+        if heartbeat_interval.is_none() {
+            println!("{}",
+                     "Error: --heartbeat-webhook can only be used with --heartbeat".red());
+            return PROC_ARGS_ERROR;
+        }
+        if let Err(msg) = is_valid_url(&wh) {
+            println!("{}",
+                     format!("Error: the specifed heartbeat webhook URL \"{}\" is invalid. \
+                              Reason: {}",
+                             wh,
+                             msg)
+                         .red());
+            return PROC_ARGS_ERROR;
+        }
     }
 
-    if args.flag_dry_run && args.flag_webhook.is_some() {
-        println!("{}",
-                 "Error: --webhook cannot be used with the --dry-run option".red());
-        return PROC_OTHER_ERROR;
+    if let Some(ref wh) = args.flag_alert_webhook {
+        if let Err(msg) = is_valid_url(&wh) {
+            println!("{}",
+                     format!("Error: the specifed alert webhook URL \"{}\" is invalid. Reason: \
+                              {}",
+                             wh,
+                             msg)
+                         .red());
+            return PROC_ARGS_ERROR;
+        }
     }
 
     if let Some(ref wh) = args.flag_webhook {
@@ -824,39 +3904,521 @@ fn factotum() -> i32 {
                              wh,
                              msg)
                          .red());
-            return PROC_OTHER_ERROR;
+            return PROC_ARGS_ERROR;
+        }
+    }
+
+    let webhook_version = match args.flag_webhook_version {
+        Some(ref version) => {
+            if args.flag_webhook.is_none() {
+                println!("{}",
+                         "Error: --webhook-version can only be used with --webhook".red());
+                return PROC_ARGS_ERROR;
+            }
+            match webhook::parse_webhook_version(version) {
+                Ok(version) => version,
+                Err(msg) => {
+                    println!("{}", format!("Error: {}", msg).red());
+                    return PROC_ARGS_ERROR;
+                }
+            }
+        }
+        None => WebhookVersion::V1,
+    };
+
+    // dev and check keep the arguments, as they read the variables again whenever a file changes
+    if args.cmd_dev {
+        return dev(&args, &tag_map, executor_settings);
+    }
+    if args.cmd_check {
+        return check(&args, &tag_map, executor_settings);
+    }
+
+    if args.flag_require_signature {
+        let requirement = SignatureRequirement {
+            signature: args.flag_signature.clone(),
+            signer: args.flag_signer.clone(),
+        };
+        let result = require_signature(&args.arg_factfile,
+                                       &requirement,
+                                       &signature::CliVerifier);
+        if result != PROC_SUCCESS {
+            return result;
         }
+    } else if args.flag_signature.is_some() || args.flag_signer.is_some() {
+        println!("{}",
+                 "Error: --signature and --signer can only be used with --require-signature"
+                     .red());
+        return PROC_ARGS_ERROR;
     }
 
+    let history = history_store(&args);
+    let history_retention =
+        match history::parse_history_retention(args.flag_history_keep.as_ref().map(|k| k.as_str()),
+                                               args.flag_history_max_runs) {
+            Ok(retention) => retention,
+            Err(msg) => {
+                println!("{}", format!("Error: {}", msg).red());
+                return PROC_ARGS_ERROR;
+            }
+        };
+    let run_options = RunOptions {
+        webhook_url: args.flag_webhook,
+        webhook_version: webhook_version,
+        attempt: None,
+        job_tags: tag_map,
+        max_stdouterr_size: args.flag_max_stdouterr_size,
+        archive_location: archive_location,
+        cloudwatch_group: args.flag_cloudwatch_logs,
+        log_sink: log_sink,
+        retry_policy: retry_policy,
+        history: Some(history.clone()),
+        history_retention: history_retention.clone(),
+        control: None,
+        duration_warning_factor: Some(args.flag_duration_warning_factor),
+        alert_webhook: args.flag_alert_webhook,
+        heartbeat: heartbeat_interval,
+        heartbeat_webhook: args.flag_heartbeat_webhook,
+        show_plan: args.flag_show_plan,
+        output_mode: if args.flag_stream_output {
+            Some(OutputMode::Stream)
+        } else if args.flag_group_output {
+            Some(OutputMode::Group)
+        } else {
+            None
+        },
+        verbosity: console::verbosity(args.flag_quiet, args.flag_verbose),
+        skip_preflight: args.flag_skip_preflight,
+        cache: args.flag_cache,
+        result_file: args.flag_result_file.clone().map(PathBuf::from),
+        only: args.flag_only.unwrap_or(vec![]),
+        end: args.flag_end,
+        result_overrides: result_overrides,
+        chaos: chaos_policy,
+        spill_policy: spill_policy,
+        policy: command_policy,
+        workspace: Some(workspace_policy),
+        simulated: false,
+        executor_settings: executor_settings.clone(),
+    };
+
     if args.cmd_run {
         if let Some(constraints) = args.flag_constraint {
             let c_map = get_constraint_map(&constraints);
 
-            if let Some(host_value) = c_map.get(CONSTRAINT_HOST) {
-                if let Err(msg) = is_valid_host(host_value) {
+            if let Some(host_value) = c_map.get(constraint::HOST) {
+                if let Err(msg) = constraint::is_valid_host(host_value) {
                     println!("{}",
                              format!("Warn: the specifed host constraint \"{}\" did not match, \
                                       no tasks have been executed. Reason: {}",
                                      host_value,
                                      msg)
                                  .yellow());
-                    return PROC_SUCCESS;
+                    return PROC_CONSTRAINT_NOT_MET;
+                }
+            }
+
+            if let Some(window_value) = c_map.get(constraint::WINDOW) {
+                let window_check = timewindow::parse_time_window(window_value)
+                    .and_then(|w| timewindow::check_time_window(&w, &chrono::UTC::now()));
+                if let Err(msg) = window_check {
+                    println!("{}",
+                             format!("Warn: the specifed window constraint \"{}\" did not \
+                                      match, no tasks have been executed. Reason: {}",
+                                     window_value,
+                                     msg)
+                                 .yellow());
+                    return PROC_CONSTRAINT_NOT_MET;
+                }
+            }
+
+            if let Some(check_value) = c_map.get(constraint::CHECK) {
+                if let Err(msg) = constraint::run_check(check_value) {
+                    println!("{}",
+                             format!("Warn: the specifed check constraint \"{}\" did not \
+                                      pass, no tasks have been executed. Reason: {}",
+                                     check_value,
+                                     msg)
+                                 .yellow());
+                    return PROC_CONSTRAINT_NOT_MET;
                 }
             }
         }
 
-        if !args.flag_dry_run {
+        if args.flag_mock_executor || args.flag_mock_results.is_some() {
+            let results = match args.flag_mock_results {
+                Some(ref path) => {
+                    match mock::load_mock_results(Path::new(path)) {
+                        Ok(results) => results,
+                        Err(msg) => {
+                            println!("{}", format!("Error: {}", msg).red());
+                            return PROC_ARGS_ERROR;
+                        }
+                    }
+                }
+                None => HashMap::new(),
+            };
+            parse_file_and_mock(&args.arg_factfile,
+                                env_json,
+                                args.flag_start,
+                                results,
+                                run_options.only,
+                                run_options.end,
+                                run_options.chaos,
+                                run_options.show_plan,
+                                &run_options.result_overrides,
+                                executor_settings)
+        } else if !args.flag_dry_run {
             parse_file_and_execute(&args.arg_factfile,
                                    env_json,
                                    args.flag_start,
-                                   args.flag_webhook,
-                                   tag_map,
-                                   args.flag_max_stdouterr_size)
+                                   run_options)
+        } else {
+            parse_file_and_simulate(&args.arg_factfile,
+                                    env_json,
+                                    args.flag_start,
+                                    run_options.only,
+                                    run_options.end,
+                                    run_options.show_plan,
+                                    executor_settings)
+        }
+    } else if args.cmd_backfill {
+        backfill(&args.arg_factfile,
+                 env_json,
+                 &args.flag_from,
+                 &args.flag_to,
+                 &args.flag_var_name,
+                 args.flag_parallelism.unwrap_or(1),
+                 run_options)
+    } else if args.cmd_worker {
+        worker(&args.arg_queue,
+               env_json,
+               args.flag_parallelism.unwrap_or(1),
+               run_options)
+    } else if args.cmd_diff {
+        let other_env_json = match args.flag_other_env {
+            Some(ref other_env) => {
+                match str_to_json(other_env) {
+                    Ok(j) => Some(j),
+                    Err(e) => {
+                        println!("{}", e.red());
+                        return PROC_ARGS_ERROR;
+                    }
+                }
+            }
+            None => env_json.clone(),
+        };
+        match diff(&args.arg_factfile,
+                   &args.arg_other_factfile,
+                   env_json,
+                   other_env_json,
+                   executor_settings) {
+            Ok(changes) => {
+                println!("{}", changes);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_snapshot {
+        if args.flag_check {
+            let snapshot_file = match args.flag_output {
+                Some(ref f) => f,
+                None => {
+                    println!("{}",
+                             "Error: --check needs the snapshot to check against, given with \
+                              --output"
+                                 .red());
+                    return PROC_ARGS_ERROR;
+                }
+            };
+            match check_snapshot(&args.arg_factfile, env_json, snapshot_file, executor_settings) {
+                Ok(msg) => {
+                    println!("{}", msg.green());
+                    PROC_SUCCESS
+                }
+                Err(msg) => {
+                    println!("{}", msg.red());
+                    PROC_EXEC_ERROR
+                }
+            }
+        } else {
+            match factotum::parser::parse(&args.arg_factfile,
+                                          env_json,
+                                          OverrideResultMappings::None,
+                                          executor_settings) {
+                Ok(ff) => {
+                    let snap = snapshot::snapshot(&ff);
+                    if let Some(ref output_file) = args.flag_output {
+                        match write_to_file(output_file, &snap, args.flag_overwrite) {
+                            Ok(_) => {
+                                println!("{}", "File written successfully".green());
+                                PROC_SUCCESS
+                            }
+                            Err(m) => {
+                                print_err!("{}{}", "Error: ".red(), m.red());
+                                PROC_OTHER_ERROR
+                            }
+                        }
+                    } else {
+                        print!("{}", snap);
+                        PROC_SUCCESS
+                    }
+                }
+                Err(msg) => {
+                    println!("{}", msg.to_string().red());
+                    parse_error_code(&msg)
+                }
+            }
+        }
+    } else if args.cmd_stats {
+        match stats(&args.arg_factfile, env_json, executor_settings) {
+            Ok(statistics) => {
+                println!("{}", statistics);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_vars {
+        match vars(&args.arg_factfile, env_json, executor_settings) {
+            Ok((listing, true)) => {
+                println!("{}", listing);
+                PROC_SUCCESS
+            }
+            Ok((listing, false)) => {
+                println!("{}", listing);
+                PROC_PARSE_ERROR
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_rerun {
+        match rerun_plan(&args.arg_run_id,
+                         &*history.open(),
+                         args.flag_failed_only,
+                         env_json,
+                         executor_settings) {
+            Ok((_, Some(ref targets))) if targets.is_empty() => {
+                println!("{}",
+                         format!("Nothing to rerun, every task in run '{}' succeeded",
+                                 args.arg_run_id)
+                             .green());
+                PROC_SUCCESS
+            }
+            Ok((source, targets)) => {
+                let only = targets.unwrap_or(vec![]);
+                if !args.flag_dry_run {
+                    parse_file_and_execute(&source.factfile,
+                                           Some(source.variables),
+                                           None,
+                                           RunOptions { only: only, ..run_options })
+                } else {
+                    parse_file_and_simulate(&source.factfile,
+                                            Some(source.variables),
+                                            None,
+                                            only,
+                                            None,
+                                            run_options.show_plan,
+                                            executor_settings)
+                }
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_cancel {
+        let request = if args.arg_task.is_empty() {
+            control::Request::CancelRun
         } else {
-            parse_file_and_simulate(&args.arg_factfile, env_json, args.flag_start)
+            control::Request::CancelTask(args.arg_task.clone())
+        };
+        let path = control::socket_path(&args.arg_run_id);
+        if !path.exists() {
+            println!("{}",
+                     format!("Error: no run '{}' is running on this host", args.arg_run_id)
+                         .red());
+            return PROC_OTHER_ERROR;
+        }
+        match control::send(&path, &request) {
+            Ok(msg) => {
+                println!("{}", msg);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", format!("Error: {}", msg).red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_critical_path {
+        match critical_path(&args.arg_run_id, &*history.open()) {
+            Ok(path) => {
+                println!("{}", path);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_history && args.cmd_export {
+        match export_history(&*history.open(),
+                             &args.flag_since,
+                             &args.flag_format,
+                             &chrono::UTC::now()) {
+            Ok(exported) => {
+                if let Some(output_file) = args.flag_output {
+                    match write_to_file(&output_file, &exported, args.flag_overwrite) {
+                        Ok(_) => {
+                            println!("{}", "File written successfully".green());
+                            PROC_SUCCESS
+                        }
+                        Err(m) => {
+                            print_err!("{}{}", "Error: ".red(), m.red());
+                            PROC_OTHER_ERROR
+                        }
+                    }
+                } else {
+                    print!("{}", exported);
+                    PROC_SUCCESS
+                }
+            }
+            Err(msg) => {
+                print_err!("{} {}", "Error:".red(), msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_history && args.cmd_prune {
+        if history_retention.is_unlimited() {
+            println!("{}",
+                     "Error: set --history-keep or --history-max-runs to say which runs to prune"
+                         .red());
+            return PROC_ARGS_ERROR;
+        }
+        let store = history.open();
+        match store.prune(&history_retention, &chrono::UTC::now()) {
+            Ok(pruned) => {
+                println!("Pruned {} run(s) from {}", pruned, store.describe());
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_compare {
+        match compare(&args.arg_run_id,
+                      &args.arg_other_run_id,
+                      &*history.open()) {
+            Ok(comparison) => {
+                println!("{}", comparison);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_timeline {
+        match timeline(&args.arg_run_id, &*history.open()) {
+            Ok(svg) => {
+                if let Some(output_file) = args.flag_output {
+                    match write_to_file(&output_file, &svg, args.flag_overwrite) {
+                        Ok(_) => {
+                            println!("{}", "File written successfully".green());
+                            PROC_SUCCESS
+                        }
+                        Err(m) => {
+                            print_err!("{}{}", "Error: ".red(), m.red());
+                            PROC_OTHER_ERROR
+                        }
+                    }
+                } else {
+                    print!("{}", svg);
+                    PROC_SUCCESS
+                }
+            }
+            Err(msg) => {
+                print_err!("{} {}", "Error:".red(), msg.red());
+                PROC_OTHER_ERROR
+            }
+        }
+    } else if args.cmd_explain {
+        match explain(&args.arg_factfile, &args.arg_task, env_json, executor_settings) {
+            Ok(explained) => {
+                println!("{}", explained);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_list_tasks {
+        match list_tasks(&args.arg_factfile, env_json, &args.flag_format, executor_settings) {
+            Ok(listed) => {
+                println!("{}", listed);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_completions {
+        match factotum::completions::script(&args.arg_shell, USAGE) {
+            Ok(script) => {
+                print!("{}", script);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_ARGS_ERROR
+            }
+        }
+    } else if args.cmd_schema {
+        let version = args.arg_schema_version.as_ref().map(|v| v.as_str());
+        match schemavalidator::factfile_schema(version) {
+            Ok(schema) => {
+                print!("{}", schema);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_ARGS_ERROR
+            }
+        }
+    } else if args.cmd_render {
+        match render(&args.arg_factfile, env_json, executor_settings) {
+            Ok(rendered) => {
+                println!("{}", rendered);
+                PROC_SUCCESS
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
+        }
+    } else if args.cmd_test {
+        match test_factfile(&args.arg_testspec, executor_settings) {
+            Ok((report, passed)) => {
+                println!("{}", report);
+                if passed { PROC_SUCCESS } else { PROC_EXEC_ERROR }
+            }
+            Err(msg) => {
+                println!("{}", msg.red());
+                PROC_PARSE_ERROR
+            }
         }
     } else if args.cmd_validate {
-        match validate(&args.arg_factfile, env_json) {
+        match validate(&args.arg_factfile, env_json, executor_settings) {
             Ok(msg) => {
                 println!("{}", msg);
                 PROC_SUCCESS
@@ -866,8 +4428,26 @@ fn factotum() -> i32 {
                 PROC_PARSE_ERROR
             }
         }
+    } else if args.cmd_wait {
+        wait(&args.arg_condition, &args.flag_poll_interval, &args.flag_timeout)
+    } else if args.cmd_assert {
+        assert_expressions(&args.arg_expression, &args.flag_message)
+    } else if args.cmd_emr {
+        emr_step(&args.arg_cluster, &args.arg_step_param)
+    } else if args.cmd_aws_batch {
+        batch_job(&args.arg_job_definition, &args.arg_job_param)
+    } else if args.cmd_cloud_run {
+        cloud_run_job(&args.arg_job, &args.arg_gcp_param)
+    } else if args.cmd_cloud_batch {
+        cloud_batch_job(&args.arg_job_spec, &args.arg_gcp_param)
+    } else if args.cmd_dbt {
+        run_dbt(&args.arg_dbt_command, &args.arg_dbt_param, &args.flag_result_file)
+    } else if args.cmd_sql {
+        run_sql(&args.arg_connection, &args.arg_sql_param)
+    } else if args.cmd_spark {
+        spark_submit(&args.arg_application, &args.arg_spark_param)
     } else if args.cmd_dot {
-        match dot(&args.arg_factfile, args.flag_start) {
+        match dot(&args.arg_factfile, args.flag_start, executor_settings) {
             Ok(dot) => {
                 if let Some(output_file) = args.flag_output {
                     match write_to_file(&output_file, &dot, args.flag_overwrite) {
@@ -959,16 +4539,184 @@ fn test_write_to_file() {
 
 #[test]
 fn validate_ok_factfile_good() {
+    let configured = ExecutorSettings::new();
     let test_file_path = "./tests/resources/example_ok.factfile";
-    let is_valid = validate(test_file_path, None);
+    let is_valid = validate(test_file_path, None, &configured);
     let expected: String = format!("'{}' is a valid Factfile!", test_file_path).green().to_string();
     assert_eq!(is_valid, Ok(expected));
 }
 
+#[test]
+fn render_applies_variables() {
+    let configured = ExecutorSettings::new();
+    let test_file_path = "./tests/resources/example_variables.factfile";
+    let env = Json::from_str("{\"region\":\"us-east-1\"}").unwrap();
+    let rendered = render(test_file_path, Some(env), &configured).unwrap();
+    let rendered = Json::from_str(&rendered).unwrap();
+    assert_eq!(rendered.find_path(&["data", "name"]).unwrap().as_string(),
+               Some("Load us-east-1"));
+    assert_eq!(rendered.find_path(&["data", "tasks"]).unwrap()[0].find("arguments"),
+               Some(&Json::from_str("[\"s3://logs/us-east-1\"]").unwrap()));
+
+    assert!(render("./tests/resources/invalid_json.factfile", None, &configured).is_err());
+}
+
+#[test]
+fn vars_reports_unset_placeholders() {
+    let configured = ExecutorSettings::new();
+    let test_file_path = "./tests/resources/example_variables.factfile";
+    let (listing, complete) = vars(test_file_path, None, &configured).unwrap();
+    assert!(complete);
+    assert!(listing.contains("task 'Load' arguments[0]"));
+
+    let (_, complete) = vars("./tests/resources/example_ok.factfile", None, &configured).unwrap();
+    assert!(complete);
+
+    assert!(vars("./tests/resources/invalid_json.factfile", None, &configured).is_err());
+}
+
+#[test]
+fn list_tasks_names_need_no_variables() {
+    let configured = ExecutorSettings::new();
+    let factfile = "./tests/resources/example_declarations.factfile";
+    let env = Some(Json::Object(BTreeMap::new()));
+    assert!(list_tasks(factfile, env.clone(), "table", &configured).is_err());
+    assert!(list_tasks(factfile, env, "names", &configured).is_ok());
+    assert!(list_tasks("./tests/resources/example_invalid_computed_variable.factfile",
+                       None,
+                       "names",
+                       &configured)
+        .is_ok());
+}
+
+#[test]
+fn unreadable_factfiles_are_argument_errors() {
+    let configured = ExecutorSettings::new();
+    let missing = factotum::parser::parse("./tests/resources/no_such.factfile",
+                                          None,
+                                          OverrideResultMappings::None,
+                                          &configured)
+        .err()
+        .unwrap();
+    assert_eq!(parse_error_code(&missing), PROC_ARGS_ERROR);
+
+    let invalid = factotum::parser::parse("./tests/resources/invalid_json.factfile",
+                                          None,
+                                          OverrideResultMappings::None,
+                                          &configured)
+        .err()
+        .unwrap();
+    assert_eq!(parse_error_code(&invalid), PROC_PARSE_ERROR);
+}
+
+#[test]
+fn check_places_problems_in_the_factfile() {
+    let configured = ExecutorSettings::new();
+    let env = Ok(Json::Object(BTreeMap::new()));
+    assert!(check_factfile("./tests/resources/example_ok.factfile", env.clone(), &configured)
+        .is_empty());
+
+    let problems = check_factfile("./tests/resources/example_wrong_type.factfile",
+                                  env.clone(),
+                                  &configured);
+    assert_eq!(problems.len(), 1);
+    assert_eq!((problems[0].position.line, problems[0].position.column), (13, 50));
+
+    let problems = check_factfile("./tests/resources/no_such.factfile", env, &configured);
+    assert_eq!(problems[0].position, Position::start());
+    assert!(problems[0].message.starts_with("couldn't read the factfile"));
+}
+
+#[test]
+fn explain_describes_task() {
+    let configured = ExecutorSettings::new();
+    let test_file_path = "./tests/resources/example_ok.factfile";
+    let explained = explain(test_file_path, "StorageLoader", None, &configured).unwrap();
+    let expected = vec![format!("Task '{}' in '{}'", "StorageLoader".cyan(), "My First DAG".cyan()),
+                        "  Executor:          shell".to_string(),
+                        "  Command:           ./acme-storage-loader.sh".to_string(),
+                        "  Arguments:         \"???\"".to_string(),
+                        "  Runs as:           sh -c './acme-storage-loader.sh \"???\"'".to_string(),
+                        "  Depends on:        EmrEtlRunner".to_string(),
+                        "  Dependents:        SQL Runner".to_string(),
+                        "  Continues on:      0".to_string(),
+                        "  Finishes early on: 3".to_string(),
+                        "  Variables:         none".to_string()];
+    assert_eq!(explained, expected.join("\n"));
+
+    assert_eq!(explain(test_file_path, "Nope", None, &configured),
+               Err(format!("the task 'Nope' could not be found in '{}'", test_file_path)));
+}
+
+#[test]
+fn stats_describes_factfile() {
+    let configured = ExecutorSettings::new();
+    let stats = stats("./tests/resources/example_ok.factfile", None, &configured).unwrap();
+    let expected = vec![format!("Statistics for '{}'", "My First DAG".cyan()),
+                        "Tasks:          3".to_string(),
+                        "Edges:          2".to_string(),
+                        "Max depth:      3".to_string(),
+                        "Max width:      1".to_string(),
+                        "Isolated tasks: none".to_string(),
+                        "Executors:".to_string(),
+                        "  shell: 3".to_string()];
+    assert_eq!(stats, expected.join("\n"));
+}
+
+#[test]
+fn critical_path_from_history() {
+    let mut history_file = env::temp_dir();
+    history_file.push(format!("factotum-critical-path-test-{}.jsonl", uuid::Uuid::new_v4()));
+    let mut f = File::create(&history_file).unwrap();
+    f.write_all(b"{\"jobName\":\"job\",\"runReference\":\"abc\",\"tasks\":[\
+                  {\"taskName\":\"a\",\"dependsOn\":[],\"durationMillis\":1500},\
+                  {\"taskName\":\"b\",\"dependsOn\":[\"a\"],\"durationMillis\":90000},\
+                  {\"taskName\":\"c\",\"dependsOn\":[\"a\"],\"durationMillis\":500}]}\n\
+                  {\"jobName\":\"job\",\"runReference\":\"def\",\"tasks\":[]}\n")
+        .unwrap();
+
+    let expected = vec![format!("Critical path for run {} of '{}' (1m, 31s in total):",
+                                "abc".cyan(),
+                                "job".cyan()),
+                        "  1. a  1.5s".to_string(),
+                        "  2. b  1m, 30s".to_string()];
+    let store = history::FileStore { path: history_file.clone() };
+    assert_eq!(critical_path("abc", &store), Ok(expected.join("\n")));
+    assert_eq!(critical_path("def", &store),
+               Err("the run 'def' has no recorded task durations".to_string()));
+
+    assert!(fs::remove_file(&history_file).is_ok());
+}
+
+#[test]
+fn compare_runs_from_history() {
+    let mut history_file = env::temp_dir();
+    history_file.push(format!("factotum-compare-test-{}.jsonl", uuid::Uuid::new_v4()));
+    let mut f = File::create(&history_file).unwrap();
+    f.write_all(b"{\"jobName\":\"job\",\"runReference\":\"abc\",\"tasks\":[\
+                  {\"taskName\":\"a\",\"state\":\"SUCCEEDED\",\"durationMillis\":1000}]}\n\
+                  {\"jobName\":\"job\",\"runReference\":\"def\",\"tasks\":[\
+                  {\"taskName\":\"a\",\"state\":\"FAILED\",\"durationMillis\":1500}]}\n")
+        .unwrap();
+
+    let expected = vec![format!("Comparing runs of '{}' ({} -> {}):",
+                                "job".cyan(),
+                                "abc".cyan(),
+                                "def".cyan()),
+                        "TASK  BEFORE  AFTER  CHANGE        STATE".to_string(),
+                        "a     1.0s    1.5s   +0.5s (+50%)  SUCCEEDED -> FAILED".to_string()];
+    let store = history::FileStore { path: history_file.clone() };
+    assert_eq!(compare("abc", "def", &store), Ok(expected.join("\n")));
+    assert!(compare("abc", "xyz", &store).is_err());
+
+    assert!(fs::remove_file(&history_file).is_ok());
+}
+
 #[test]
 fn validate_ok_factfile_bad() {
+    let configured = ExecutorSettings::new();
     let test_file_path = "./tests/resources/invalid_json.factfile";
-    let is_valid = validate(test_file_path, None);
+    let is_valid = validate(test_file_path, None, &configured);
     match is_valid {
         Ok(_) => panic!("Validation returning valid for invalid file"),
         Err(msg) => {
@@ -981,11 +4729,21 @@ fn validate_ok_factfile_bad() {
 #[test]
 fn have_valid_config() {
     fs::create_dir(".factotum").ok();
-    if let Err(errs) = get_log_config() {
+    if let Err(errs) = get_log_config(Path::new(".factotum"), Verbosity::Normal) {
         panic!("config not building correctly! {:?}", errs);
     }
 }
 
+#[test]
+fn debug_config_logs_to_stderr_too() {
+    fs::create_dir(".factotum").ok();
+    let config = get_log_config(Path::new(".factotum"), Verbosity::Debug).unwrap();
+    assert_eq!(config.root().level(), log::LogLevelFilter::Debug);
+    assert_eq!(config.root().appenders(), ["file".to_string(), "stderr".to_string()]);
+    let config = get_log_config(Path::new(".factotum"), Verbosity::Quiet).unwrap();
+    assert_eq!(config.root().appenders(), ["file".to_string()]);
+}
+
 #[test]
 fn get_duration_under_minute() {
     assert_eq!(get_duration_as_string(&Duration::new(2, 500000099)),
@@ -1014,7 +4772,7 @@ fn get_duration_with_hours() {
 fn test_get_task_result_line_str() {
     use chrono::UTC;
     use factotum::executor::execution_strategy::RunResult;
-    use factotum::factfile::{Task as FactfileTask, OnResult};
+    use factotum::factfile::{Task as FactfileTask, OnResult, CircuitBreaker, Shell};
 
     // successful after 20 secs
     let dt = UTC::now();
@@ -1022,6 +4780,9 @@ fn test_get_task_result_line_str() {
         name: String::from("hello world"),
         // children: vec![],
         state: State::Success,
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         run_started: Some(dt),
         task_spec: &FactfileTask {
             name: "hello world".to_string(),
@@ -1033,6 +4794,23 @@ fn test_get_task_result_line_str() {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         },
         run_result: Some(RunResult {
             duration: Duration::from_secs(20),
@@ -1040,6 +4818,8 @@ fn test_get_task_result_line_str() {
             stdout: Some(String::from("hello world")),
             stderr: None,
             return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
         }),
     };
 
@@ -1051,7 +4831,7 @@ fn test_get_task_result_line_str() {
                            "Task '".green(),
                            "hello world".cyan(),
                            "': succeeded after 20.0s".green());
-    let (result_stdout, result_stderr) = get_task_result_line_str(&sample_task);
+    let (result_stdout, result_stderr) = get_task_result_line_str(&sample_task, true);
     assert_eq!(result_stdout, expected);
     assert_eq!(result_stderr, None);
 
@@ -1061,6 +4841,9 @@ fn test_get_task_result_line_str() {
         name: String::from("hello world"),
         // children: vec![],
         state: State::Failed("Something about not being in continue job".to_string()),
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         run_started: Some(dt),
         task_spec: &FactfileTask {
             name: "hello world".to_string(),
@@ -1072,6 +4855,23 @@ fn test_get_task_result_line_str() {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         },
         run_result: Some(RunResult {
             duration: Duration::from_secs(20),
@@ -1079,14 +4879,16 @@ fn test_get_task_result_line_str() {
             stdout: Some(String::from("hello world")),
             stderr: Some(String::from("There's errors")),
             return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
         }),
     };
 
     assert_eq!(format!("Task '{}' stderr:\n{}\n",
                        sample_task.name.cyan(),
                        "There's errors".red()),
-               get_task_result_line_str(&sample_task_stdout).1.unwrap());
-    assert_eq!(get_task_result_line_str(&sample_task_stdout).0,
+               get_task_result_line_str(&sample_task_stdout, true).1.unwrap());
+    assert_eq!(get_task_result_line_str(&sample_task_stdout, true).0,
                format!("Task '{}' was started at {}\nTask '{}' stdout:\n{}\n{}{}{}\n",
                        "hello world".cyan(),
                        dt,
@@ -1112,19 +4914,42 @@ fn test_get_task_result_line_str() {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         },
         state: State::Skipped("for some reason".to_string()),
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         run_result: None,
     };
 
     assert_eq!(format!("Task '{}': skipped!\n", "skip".cyan()),
-               get_task_result_line_str(&task_skipped).0);
-    assert_eq!(None, get_task_result_line_str(&task_skipped).1);
+               get_task_result_line_str(&task_skipped, true).0);
+    assert_eq!(None, get_task_result_line_str(&task_skipped, true).1);
 
     let task_init_fail = Task::<&FactfileTask> {
         name: String::from("init fail"),
         //  children: vec![],
         state: State::Failed("bla".to_string()),
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         run_started: None,
         task_spec: &FactfileTask {
             name: "hello world".to_string(),
@@ -1136,6 +4961,23 @@ fn test_get_task_result_line_str() {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         },
         run_result: None,
     };
@@ -1143,13 +4985,16 @@ fn test_get_task_result_line_str() {
     assert_eq!(format!("Task '{}': {}!\n",
                        "init fail".cyan(),
                        "Factotum could not start the task".red()),
-               get_task_result_line_str(&task_init_fail).0);
-    assert_eq!(None, get_task_result_line_str(&task_init_fail).1);
+               get_task_result_line_str(&task_init_fail, true).0);
+    assert_eq!(None, get_task_result_line_str(&task_init_fail, true).1);
 
     let task_failure = Task::<&FactfileTask> {
         name: String::from("fails"),
         // children: vec![],
         state: State::Failed("bla".to_string()),
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         run_started: Some(dt),
         task_spec: &FactfileTask {
             name: "hello world".to_string(),
@@ -1161,13 +5006,34 @@ fn test_get_task_result_line_str() {
                 terminate_job: vec![],
                 continue_job: vec![],
             },
+            circuit_breaker: CircuitBreaker::default(),
+            expected_duration: None,
+            idle_timeout: None,
+            requires: vec![],
+            produces: vec![],
+            disabled: false,
+            description: None,
+            owner: None,
+            stdin: None,
+            shell: Shell::Sh,
+            tty: false,
+            constraints: None,
+            sandbox: None,
+            workspace: None,
+            cache: None,
+            when_upstream_skipped: None,
+            when: None,
         },
         run_result: Some(RunResult {
             duration: Duration::from_secs(20),
-            task_execution_error: Some(String::from("The task exited with something unexpected")),
+            task_execution_error: Some(FactotumError::new(factotum::errors::ErrorKind::Execution,
+                                                          "The task exited with something \
+                                                           unexpected")),
             stdout: Some(String::from("hello world")),
             stderr: Some(String::from("There's errors")),
             return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
         }),
     };
 
@@ -1180,7 +5046,7 @@ fn test_get_task_result_line_str() {
                 "Task '".red(),
                 "fails".cyan(),
                 "': couldn't be started. Reason: The task exited with something unexpected".red());
-    let (stdout_failed, stderr_failed) = get_task_result_line_str(&task_failure);
+    let (stdout_failed, stderr_failed) = get_task_result_line_str(&task_failure, true);
     assert_eq!(expected_failed, stdout_failed);
     assert_eq!(format!("Task '{}' stderr:\n{}\n",
                        "fails".cyan(),
@@ -1193,7 +5059,7 @@ fn test_get_task_result_line_str() {
 fn test_get_task_results_str_summary() {
     use chrono::UTC;
     use factotum::executor::execution_strategy::RunResult;
-    use factotum::factfile::{Task as FactfileTask, OnResult};
+    use factotum::factfile::{Task as FactfileTask, OnResult, CircuitBreaker, Shell};
 
     let dt = UTC::now();
 
@@ -1207,12 +5073,32 @@ fn test_get_task_results_str_summary() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
     let task_one = Task::<&FactfileTask> {
         name: String::from("hello world"),
         // children: vec![],
         state: State::Success,
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         task_spec: &task_one_spec,
         run_started: Some(dt),
         run_result: Some(RunResult {
@@ -1221,6 +5107,8 @@ fn test_get_task_results_str_summary() {
             stdout: Some(String::from("hello world")),
             stderr: Some(String::from("Mistake")),
             return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
         }),
     };
 
@@ -1235,12 +5123,32 @@ fn test_get_task_results_str_summary() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
     let task_two = Task::<&FactfileTask> {
         name: String::from("hello world 2"),
         // children: vec![],
         state: State::Success,
+        nested_tasks: vec![],
+        artifacts: vec![],
+        cache_key: None,
         task_spec: &task_two_spec,
         run_started: Some(dt),
         run_result: Some(RunResult {
@@ -1249,11 +5157,13 @@ fn test_get_task_results_str_summary() {
             stdout: Some(String::from("hello world")),
             stderr: Some(String::from("Mistake")),
             return_code: 0,
+            resource_usage: None,
+            spilled_output: None,
         }),
     };
 
     let mut tasks: Vec<&Task<&FactfileTask>> = vec![];
-    let (stdout, stderr) = get_task_results_str(&tasks);
+    let (stdout, stderr) = get_task_results_str(&tasks, true);
     let expected: String = format!("{}", "0/0 tasks run in 0.0s\n".green());
 
     assert_eq!(stdout, expected);
@@ -1261,8 +5171,8 @@ fn test_get_task_results_str_summary() {
 
     tasks.push(&task_one);
 
-    let (one_task_stdout, one_task_stderr) = get_task_results_str(&tasks);
-    let (first_task_stdout, first_task_stderr) = get_task_result_line_str(&tasks[0]);
+    let (one_task_stdout, one_task_stderr) = get_task_results_str(&tasks, true);
+    let (first_task_stdout, first_task_stderr) = get_task_result_line_str(&tasks[0], true);
     let expected_one_task = format!("{}{}",
                                     first_task_stdout,
                                     "1/1 tasks run in 20.0s\n".green());
@@ -1273,16 +5183,31 @@ fn test_get_task_results_str_summary() {
 
     tasks.push(&task_two);
 
-    let (two_task_stdout, two_task_stderr) = get_task_results_str(&tasks);
-    let (task_two_stdout, task_two_stderr) = get_task_result_line_str(&tasks[1]);
+    let (two_task_stdout, two_task_stderr) = get_task_results_str(&tasks, true);
+    let (task_two_stdout, task_two_stderr) = get_task_result_line_str(&tasks[1], true);
     let expected_two_task = format!("{}{}{}",
                                     first_task_stdout,
                                     task_two_stdout,
                                     "2/2 tasks run in 1m, 40s\n".green());
     assert_eq!(two_task_stdout, expected_two_task);
+    let task_two_stderr_str = task_two_stderr.unwrap();
     assert_eq!(two_task_stderr,
-               format!("{}{}", first_task_stderr_str, task_two_stderr.unwrap()));
-
+               format!("{}{}", first_task_stderr_str, task_two_stderr_str));
+
+    // output that's already been printed is left out
+    let (printed_stdout, printed_stderr) = get_task_results_str(&tasks, false);
+    assert!(!printed_stdout.contains("stdout"));
+    assert!(printed_stdout.contains("2/2 tasks run in 1m, 40s"));
+    assert_eq!(printed_stderr, "");
+
+    // -q only prints what failed
+    assert_eq!(get_failed_task_results_str(&tasks, true),
+               (String::new(), String::new()));
+    let failed_two = Task { state: State::Failed("bla".to_string()), ..task_two.clone() };
+    let with_failure = vec![&task_one, &failed_two];
+    let (failed_stdout, failed_stderr) = get_failed_task_results_str(&with_failure, true);
+    assert_eq!(failed_stdout, get_task_result_line_str(&failed_two, true).0);
+    assert_eq!(failed_stderr, task_two_stderr_str);
 }
 
 #[test]
@@ -1294,7 +5219,7 @@ fn test_start_task_validation_not_present() {
         _ => unreachable!("validation did not fail"),
     }
 
-    factfile.add_task("something", &vec![], "", "", &vec![], &vec![], &vec![]);
+    factfile.add_task("something", &vec![], "", "", &vec![], &vec![], &vec![]).unwrap();
     if let Err(_) = validate_start_task(&factfile, "something") {
         unreachable!("validation failed when task present")
     }
@@ -1317,6 +5242,23 @@ fn test_start_task_cycles() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
     let task_b = Task {
@@ -1329,6 +5271,23 @@ fn test_start_task_cycles() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
     let task_c = Task {
@@ -1341,6 +5300,23 @@ fn test_start_task_cycles() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
     let task_d = Task {
@@ -1353,12 +5329,29 @@ fn test_start_task_cycles() {
             terminate_job: vec![],
             continue_job: vec![],
         },
+        circuit_breaker: CircuitBreaker::default(),
+        expected_duration: None,
+        idle_timeout: None,
+        requires: vec![],
+        produces: vec![],
+        disabled: false,
+        description: None,
+        owner: None,
+        stdin: None,
+        shell: Shell::Sh,
+        tty: false,
+        constraints: None,
+        sandbox: None,
+        workspace: None,
+        cache: None,
+        when_upstream_skipped: None,
+        when: None,
     };
 
-    factfile.add_task_obj(&task_a);
-    factfile.add_task_obj(&task_b);
-    factfile.add_task_obj(&task_c);
-    factfile.add_task_obj(&task_d);
+    factfile.add_task_obj(&task_a).unwrap();
+    factfile.add_task_obj(&task_b).unwrap();
+    factfile.add_task_obj(&task_c).unwrap();
+    factfile.add_task_obj(&task_d).unwrap();
 
     match validate_start_task(&factfile, "c") {
         Err(r) => {
@@ -1370,34 +5363,60 @@ fn test_start_task_cycles() {
 }
 
 #[test]
-fn test_gethostname_safe() {
-    let hostname = gethostname_safe();
-    if let Ok(ok_hostname) = hostname {
-        assert!(!ok_hostname.is_empty());
-    } else {
-        panic!("gethostname_safe() must return a Ok(<String>)");
-    }
+fn test_get_constraint_map() {
+    let constraints = vec!["host,*".to_string(),
+                           "window, 22:00-06:00,Europe/London".to_string(),
+                           ",ignored".to_string()];
+    let c_map = get_constraint_map(&constraints);
+
+    assert_eq!(c_map.len(), 2);
+    assert_eq!(c_map.get(constraint::HOST), Some(&"*".to_string()));
+    assert_eq!(c_map.get(constraint::WINDOW),
+               Some(&"22:00-06:00,Europe/London".to_string()));
 }
 
 #[test]
-fn test_get_external_addrs() {
-    let external_addrs = get_external_addrs();
-    if let Ok(ok_external_addrs) = external_addrs {
-        assert!(ok_external_addrs.len() > 0);
-    } else {
-        panic!("get_external_addrs() must return a Ok(Vec<net::SocketAddr>) that is non-empty");
-    }
+fn preflight_check_reports_problems() {
+    let options = RunOptions::default();
+    let env = Some(Json::Object(BTreeMap::new()));
+    let report = preflight_check("./tests/resources/example_requires.factfile",
+                                 env.clone(),
+                                 &None,
+                                 &options,
+                                 &OsEnvironment)
+        .unwrap_err();
+    assert!(report.starts_with("Preflight checks failed (3 problems):"));
+    assert!(report.contains("the task 'Load' runs './load.sh', which couldn't be found"));
+
+    assert_eq!(preflight_check("./tests/resources/invalid_json.factfile",
+                               env,
+                               &None,
+                               &options,
+                               &OsEnvironment),
+               Ok(()));
+}
+
+#[test]
+fn failed_preflights_have_their_own_exit_code() {
+    let env = Some(Json::Object(BTreeMap::new()));
+    assert_eq!(parse_file_and_execute("./tests/resources/example_requires.factfile",
+                                      env,
+                                      None,
+                                      RunOptions::default()),
+               PROC_PREFLIGHT_FAILED);
 }
 
 #[test]
-fn test_is_valid_host() {
-    is_valid_host("*").expect("must be Ok() for wildcard");
-
-    // Test each external addr is_valid_host
-    let external_addrs = get_external_addrs()
-        .expect("get_external_addrs() must return a Ok(Vec<net::SocketAddr>) that is non-empty");
-    for external_addr in external_addrs {
-        let ip_str = external_addr.ip().to_string();
-        is_valid_host(&ip_str).expect(&format!("must be Ok() for IP {}", &ip_str));
+fn preflight_check_only_checks_the_tasks_that_will_run() {
+    let env = Some(Json::Object(BTreeMap::new()));
+    let factfile = "./tests/resources/example_requires.factfile";
+    let only = RunOptions { only: vec!["Extract".to_string()], ..RunOptions::default() };
+    let ended = RunOptions { end: Some("Extract".to_string()), ..RunOptions::default() };
+
+    for options in vec![only, ended] {
+        let report = preflight_check(factfile, env.clone(), &None, &options, &OsEnvironment)
+            .unwrap_err();
+        assert!(report.starts_with("Preflight checks failed (1 problem):"));
+        assert!(!report.contains("'Load'"));
     }
 }